@@ -12,7 +12,7 @@ use crate::cdsl::formats::{
 };
 use crate::cdsl::operands::Operand;
 use crate::cdsl::type_inference::Constraint;
-use crate::cdsl::types::{LaneType, ValueType};
+use crate::cdsl::types::{LaneType, ValueType, VectorType};
 use crate::cdsl::typevar::TypeVar;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -125,6 +125,8 @@ pub struct InstructionContent {
     pub is_indirect_branch: bool,
     /// Is this a call instruction?
     pub is_call: bool,
+    /// Does this instruction call a runtime routine, clobbering the caller-saved registers?
+    pub is_implicit_call: bool,
     /// Is this a return instruction?
     pub is_return: bool,
     /// Is this a ghost instruction?
@@ -174,7 +176,11 @@ impl Instruction {
     }
 
     pub fn bind(&self, lane_type: impl Into<LaneType>) -> BoundInstruction {
-        bind(self.clone(), Some(lane_type.into()), Vec::new())
+        bind(self.clone(), Some(lane_type.into().into()), Vec::new())
+    }
+    pub fn bind_vector(&self, lane_type: impl Into<LaneType>, num_lanes: u64) -> BoundInstruction {
+        let vector_type = VectorType::new(lane_type.into(), num_lanes);
+        bind(self.clone(), Some(vector_type.into()), Vec::new())
     }
     pub fn bind_any(&self) -> BoundInstruction {
         bind(self.clone(), None, Vec::new())
@@ -223,6 +229,7 @@ pub struct InstructionBuilder {
     is_branch: bool,
     is_indirect_branch: bool,
     is_call: bool,
+    is_implicit_call: bool,
     is_return: bool,
     is_ghost: bool,
     can_load: bool,
@@ -244,6 +251,7 @@ impl InstructionBuilder {
             is_branch: false,
             is_indirect_branch: false,
             is_call: false,
+            is_implicit_call: false,
             is_return: false,
             is_ghost: false,
             can_load: false,
@@ -285,6 +293,10 @@ impl InstructionBuilder {
         self.is_call = val;
         self
     }
+    pub fn is_implicit_call(mut self, val: bool) -> Self {
+        self.is_implicit_call = val;
+        self
+    }
     pub fn is_return(mut self, val: bool) -> Self {
         self.is_return = val;
         self
@@ -362,6 +374,7 @@ impl InstructionBuilder {
                 is_branch: self.is_branch,
                 is_indirect_branch: self.is_indirect_branch,
                 is_call: self.is_call,
+                is_implicit_call: self.is_implicit_call,
                 is_return: self.is_return,
                 is_ghost: self.is_ghost,
                 can_load: self.can_load,
@@ -398,7 +411,11 @@ pub struct BoundInstruction {
 
 impl BoundInstruction {
     pub fn bind(self, lane_type: impl Into<LaneType>) -> BoundInstruction {
-        bind(self.inst, Some(lane_type.into()), self.value_types)
+        bind(self.inst, Some(lane_type.into().into()), self.value_types)
+    }
+    pub fn bind_vector(self, lane_type: impl Into<LaneType>, num_lanes: u64) -> BoundInstruction {
+        let vector_type = VectorType::new(lane_type.into(), num_lanes);
+        bind(self.inst, Some(vector_type.into()), self.value_types)
     }
     pub fn bind_any(self) -> BoundInstruction {
         bind(self.inst, None, self.value_types)
@@ -587,6 +604,12 @@ pub enum FormatPredicateKind {
     /// Is the immediate format field member equal to zero? (float64 version)
     IsZero64BitFloat,
 
+    /// Does the immediate format field member have all its bits set? (float32 version)
+    IsAllOnes32BitFloat,
+
+    /// Does the immediate format field member have all its bits set? (float64 version)
+    IsAllOnes64BitFloat,
+
     /// Has the value list (in member_name) the size specified in parameter?
     LengthEquals(usize),
 
@@ -660,6 +683,12 @@ impl FormatPredicateNode {
             FormatPredicateKind::IsZero64BitFloat => {
                 format!("predicates::is_zero_64_bit_float({})", self.member_name)
             }
+            FormatPredicateKind::IsAllOnes32BitFloat => {
+                format!("predicates::is_all_ones_32_bit_float({})", self.member_name)
+            }
+            FormatPredicateKind::IsAllOnes64BitFloat => {
+                format!("predicates::is_all_ones_64_bit_float({})", self.member_name)
+            }
             FormatPredicateKind::LengthEquals(num) => format!(
                 "predicates::has_length_of({}, {}, func)",
                 self.member_name, num
@@ -888,6 +917,28 @@ impl InstructionPredicate {
         ))
     }
 
+    pub fn new_is_all_ones_32bit_float(
+        format: &InstructionFormat,
+        field_name: &'static str,
+    ) -> InstructionPredicateNode {
+        InstructionPredicateNode::FormatPredicate(FormatPredicateNode::new(
+            format,
+            field_name,
+            FormatPredicateKind::IsAllOnes32BitFloat,
+        ))
+    }
+
+    pub fn new_is_all_ones_64bit_float(
+        format: &InstructionFormat,
+        field_name: &'static str,
+    ) -> InstructionPredicateNode {
+        InstructionPredicateNode::FormatPredicate(FormatPredicateNode::new(
+            format,
+            field_name,
+            FormatPredicateKind::IsAllOnes64BitFloat,
+        ))
+    }
+
     pub fn new_length_equals(format: &InstructionFormat, size: usize) -> InstructionPredicateNode {
         assert!(
             format.has_value_list,
@@ -1050,12 +1101,12 @@ impl Into<InstSpec> for BoundInstruction {
 /// Helper bind reused by {Bound,}Instruction::bind.
 fn bind(
     inst: Instruction,
-    lane_type: Option<LaneType>,
+    value_type: Option<ValueType>,
     mut value_types: Vec<ValueTypeOrAny>,
 ) -> BoundInstruction {
-    match lane_type {
-        Some(lane_type) => {
-            value_types.push(ValueTypeOrAny::ValueType(lane_type.into()));
+    match value_type {
+        Some(value_type) => {
+            value_types.push(ValueTypeOrAny::ValueType(value_type));
         }
         None => {
             value_types.push(ValueTypeOrAny::Any);
@@ -40,12 +40,18 @@ impl RegBank {
         let unit = if let Some(found) = self.names.iter().position(|&reg_name| reg_name == name) {
             found
         } else {
-            // Try to match without the bank prefix.
+            // Try to match without the bank prefix, or as a numbered register like `x10`.
             assert!(name.starts_with(self.prefix));
             let name_without_prefix = &name[self.prefix.len()..];
             self.names
                 .iter()
                 .position(|&reg_name| reg_name == name_without_prefix)
+                .or_else(|| {
+                    name_without_prefix
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&unit| unit < self.units as usize)
+                })
                 .expect(&format!("invalid register name {}", name))
         };
         self.first_unit + (unit as u8)
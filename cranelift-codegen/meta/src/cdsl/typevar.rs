@@ -265,9 +265,14 @@ impl TypeVar {
     pub fn to_rust_code(&self) -> String {
         match &self.base {
             Some(base) => format!(
-                "{}.{}()",
+                "{}.{}(){}",
                 base.type_var.to_rust_code(),
-                base.derived_func.name()
+                base.derived_func.name(),
+                if base.derived_func.is_partial() {
+                    ".unwrap()"
+                } else {
+                    ""
+                }
             ),
             None => {
                 if let Some(singleton) = self.singleton_type() {
@@ -354,6 +359,17 @@ impl DerivedFunc {
         }
     }
 
+    /// Returns true if the `ir::Type` method computing this function returns an `Option`.
+    pub fn is_partial(&self) -> bool {
+        match self {
+            DerivedFunc::HalfWidth
+            | DerivedFunc::DoubleWidth
+            | DerivedFunc::HalfVector
+            | DerivedFunc::DoubleVector => true,
+            _ => false,
+        }
+    }
+
     /// Returns the inverse function of this one, if it is a bijection.
     pub fn inverse(&self) -> Option<DerivedFunc> {
         match self {
@@ -471,6 +471,13 @@ fn gen_opcodes<'a>(all_inst: &AllInstructions, formats: &FormatRegistry, fmt: &m
             "Is this a call instruction?",
             fmt,
         );
+        gen_bool_accessor(
+            all_inst,
+            |inst| inst.is_implicit_call,
+            "is_implicit_call",
+            "Does this instruction call a runtime routine, clobbering the caller-saved registers?",
+            fmt,
+        );
         gen_bool_accessor(
             all_inst,
             |inst| inst.is_return,
@@ -539,6 +546,33 @@ fn gen_opcodes<'a>(all_inst: &AllInstructions, formats: &FormatRegistry, fmt: &m
     fmtln!(fmt, "];");
     fmt.empty_line();
 
+    // Generate a private opcode_effects table.
+    fmtln!(
+        fmt,
+        "const OPCODE_EFFECTS: [OpcodeEffects; {}] = [",
+        all_inst.len()
+    );
+    fmt.indent(|fmt| {
+        for inst in all_inst.values() {
+            fmtln!(fmt, "// {}", inst.name);
+            fmt.line("OpcodeEffects {");
+            fmt.indent(|fmt| {
+                fmtln!(fmt, "reads_memory: {},", inst.can_load);
+                fmtln!(fmt, "writes_memory: {},", inst.can_store);
+                fmtln!(fmt, "can_trap: {},", inst.can_trap);
+                fmtln!(fmt, "is_call: {},", inst.is_call);
+                fmtln!(fmt, "is_branch: {},", inst.is_branch);
+                fmtln!(fmt, "is_terminator: {},", inst.is_terminator);
+                fmtln!(fmt, "is_return: {},", inst.is_return);
+                fmtln!(fmt, "writes_cpu_flags: {},", inst.writes_cpu_flags);
+                fmtln!(fmt, "other_side_effects: {},", inst.other_side_effects);
+            });
+            fmt.line("},");
+        }
+    });
+    fmtln!(fmt, "];");
+    fmt.empty_line();
+
     // Generate a private opcode_name function.
     fmt.line("fn opcode_name(opc: Opcode) -> &\'static str {");
     fmt.indent(|fmt| {
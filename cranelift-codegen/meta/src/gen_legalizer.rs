@@ -1,5 +1,8 @@
+use cranelift_entity::EntityRef;
+
 use crate::cdsl::ast::{Def, DefPool, VarPool};
 use crate::cdsl::formats::FormatRegistry;
+use crate::cdsl::instructions::AllInstructions;
 use crate::cdsl::isa::TargetIsa;
 use crate::cdsl::type_inference::Constraint;
 use crate::cdsl::typevar::{TypeSet, TypeVar};
@@ -10,7 +13,7 @@ use crate::gen_inst::gen_typesets_table;
 use crate::srcgen::Formatter;
 use crate::unique_table::UniqueTable;
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::iter::FromIterator;
 
 /// Given a `Def` node, emit code that extracts all the instruction fields from
@@ -393,18 +396,10 @@ fn gen_transform<'a>(
     fmt.line("}");
 }
 
-fn gen_transform_group<'a>(
-    group: &'a TransformGroup,
-    format_registry: &FormatRegistry,
-    transform_groups: &TransformGroups,
-    type_sets: &mut UniqueTable<'a, TypeSet>,
-    fmt: &mut Formatter,
-) {
-    fmt.doc_comment(group.doc);
+/// Emit the signature shared by all legalization functions, followed by `body`.
+fn gen_legalize_fn<T: FnOnce(&mut Formatter)>(name: &str, fmt: &mut Formatter, body: T) {
     fmt.line("#[allow(unused_variables,unused_assignments,non_snake_case)]");
-
-    // Function arguments.
-    fmtln!(fmt, "pub fn {}(", group.name);
+    fmtln!(fmt, "pub fn {}(", name);
     fmt.indent(|fmt| {
         fmt.line("inst: ir::Inst,");
         fmt.line("func: &mut ir::Function,");
@@ -412,74 +407,204 @@ fn gen_transform_group<'a>(
         fmt.line("isa: &dyn TargetIsa,");
     });
     fmtln!(fmt, ") -> bool {");
+    fmt.indent(body);
+    fmtln!(fmt, "}");
+    fmt.empty_line();
+}
 
-    // Function body.
-    fmt.indent(|fmt| {
-        fmt.line("use ir::InstBuilder;");
-        fmt.line("let mut pos = FuncCursor::new(func).at_inst(inst);");
-        fmt.line("pos.use_srcloc(inst);");
-
-        // Group the transforms by opcode so we can generate a big switch.
-        // Preserve ordering.
-        let mut inst_to_transforms = HashMap::new();
+/// Collect the opcode numbers of all the instructions that `group` or any of the groups it chains
+/// to can legalize.
+fn transitive_group_opcodes(
+    group: &TransformGroup,
+    transform_groups: &TransformGroups,
+    all_inst: &AllInstructions,
+) -> BTreeSet<usize> {
+    let mut opcodes = BTreeSet::new();
+    let mut current = Some(group);
+    while let Some(group) = current {
         for transform in &group.transforms {
-            let def_index = transform.src;
-            let inst = &transform.def_pool.get(def_index).apply.inst;
-            inst_to_transforms
-                .entry(inst.camel_name.clone())
-                .or_insert(Vec::new())
-                .push(transform);
+            let inst = &transform.def_pool.get(transform.src).apply.inst;
+            opcodes.insert(inst.opcode_number.index());
+        }
+        for inst_camel_name in group.custom_legalizes.keys() {
+            let inst = all_inst
+                .values()
+                .find(|inst| inst.camel_name == *inst_camel_name)
+                .expect("custom legalization for unknown instruction");
+            opcodes.insert(inst.opcode_number.index());
+        }
+        current = group.chain_with.map(|id| transform_groups.get(id));
+    }
+    opcodes
+}
+
+/// Emit a bitset of opcodes named `name`, indexed by `Opcode as usize`.
+fn gen_opcode_set(
+    name: &str,
+    opcodes: &BTreeSet<usize>,
+    all_inst: &AllInstructions,
+    fmt: &mut Formatter,
+) {
+    // Opcode numbers start at 1.
+    let num_words = all_inst.len() / 64 + 1;
+    let mut words = vec![0u64; num_words];
+    for &index in opcodes {
+        let opcode = index + 1;
+        words[opcode / 64] |= 1 << (opcode % 64);
+    }
+    fmtln!(fmt, "static {}: [u64; {}] = [", name, num_words);
+    fmt.indent(|fmt| {
+        for word in words {
+            fmtln!(fmt, "{:#018x},", word);
         }
+    });
+    fmt.line("];");
+    fmt.empty_line();
+}
 
-        let mut sorted_inst_names = Vec::from_iter(inst_to_transforms.keys());
-        sorted_inst_names.sort();
+/// Generate a transform group.
+///
+/// Each opcode handled by the group gets its own function trying all the group's transforms for
+/// that opcode in order. The group function itself only dispatches on the opcode. Keeping the
+/// per-opcode functions small is much kinder to the Rust compiler than one giant `match`, and it
+/// makes it possible to test the legalization of a single opcode.
+///
+/// The group function starts by checking a precomputed bitset of the opcodes that the group and
+/// its chained groups can handle, so instructions that can't match any transform don't walk the
+/// whole chain.
+fn gen_transform_group<'a>(
+    group: &'a TransformGroup,
+    format_registry: &FormatRegistry,
+    transform_groups: &TransformGroups,
+    all_inst: &AllInstructions,
+    type_sets: &mut UniqueTable<'a, TypeSet>,
+    fmt: &mut Formatter,
+) {
+    // Group the transforms by opcode. Preserve ordering.
+    let mut inst_to_transforms = HashMap::new();
+    for transform in &group.transforms {
+        let def_index = transform.src;
+        let inst = &transform.def_pool.get(def_index).apply.inst;
+        inst_to_transforms
+            .entry((inst.camel_name.clone(), inst.name.clone()))
+            .or_insert(Vec::new())
+            .push(transform);
+    }
+
+    let mut sorted_insts = Vec::from_iter(inst_to_transforms.keys().cloned());
+    sorted_insts.sort();
+
+    // Per-opcode functions.
+    for (camel_name, snake_name) in &sorted_insts {
+        let func_name = format!("{}_{}", group.name, snake_name);
+        fmtln!(
+            fmt,
+            "/// Apply the `{}` transforms for `ir::Opcode::{}`.",
+            group.name,
+            camel_name
+        );
+        gen_legalize_fn(&func_name, fmt, |fmt| {
+            fmt.line("use ir::InstBuilder;");
+            fmt.line("let mut pos = FuncCursor::new(func).at_inst(inst);");
+            fmt.line("pos.use_srcloc(inst);");
+            let key = (camel_name.clone(), snake_name.clone());
+            for transform in inst_to_transforms.get(&key).unwrap() {
+                gen_transform(transform, format_registry, type_sets, fmt);
+            }
+            fmt.line("false");
+        });
+    }
 
-        fmt.line("{");
+    // The group function dispatches on the opcode.
+    let opcode_set = format!("{}_OPCODES", group.name.to_uppercase());
+    let opcodes = transitive_group_opcodes(group, transform_groups, all_inst);
+    gen_opcode_set(&opcode_set, &opcodes, all_inst, fmt);
+
+    fmt.doc_comment(group.doc);
+    gen_legalize_fn(group.name, fmt, |fmt| {
+        fmt.line("let opcode = func.dfg[inst].opcode();");
+        fmtln!(
+            fmt,
+            "if !crate::legalizer::opcode_set_contains(&{}, opcode) {{",
+            opcode_set
+        );
+        fmt.indent(|fmt| fmt.line("return false;"));
+        fmt.line("}");
+        fmt.line("let expanded = match opcode {");
         fmt.indent(|fmt| {
-            fmt.line("match pos.func.dfg[inst].opcode() {");
-            fmt.indent(|fmt| {
-                for camel_name in sorted_inst_names {
-                    fmtln!(fmt, "ir::Opcode::{} => {{", camel_name);
-                    fmt.indent(|fmt| {
-                        for transform in inst_to_transforms.get(camel_name).unwrap() {
-                            gen_transform(transform, format_registry, type_sets, fmt);
-                        }
-                    });
-                    fmtln!(fmt, "}");
-                    fmt.empty_line();
-                }
+            for (camel_name, snake_name) in &sorted_insts {
+                fmtln!(
+                    fmt,
+                    "ir::Opcode::{} => {}_{}(inst, func, cfg, isa),",
+                    camel_name,
+                    group.name,
+                    snake_name
+                );
+            }
 
-                // Emit the custom transforms. The Rust compiler will complain about any overlap with
-                // the normal transforms.
-                for (inst_camel_name, func_name) in &group.custom_legalizes {
-                    fmtln!(fmt, "ir::Opcode::{} => {{", inst_camel_name);
-                    fmt.indent(|fmt| {
-                        fmtln!(fmt, "{}(inst, pos.func, cfg, isa);", func_name);
-                        fmt.line("return true;");
-                    });
-                    fmtln!(fmt, "}");
-                    fmt.empty_line();
-                }
+            // Emit the custom transforms. The Rust compiler will complain about any overlap with
+            // the normal transforms.
+            for (inst_camel_name, func_name) in &group.custom_legalizes {
+                fmtln!(fmt, "ir::Opcode::{} => {{", inst_camel_name);
+                fmt.indent(|fmt| {
+                    fmtln!(fmt, "{}(inst, func, cfg, isa);", func_name);
+                    fmt.line("true");
+                });
+                fmtln!(fmt, "}");
+            }
 
-                // We'll assume there are uncovered opcodes.
-                fmt.line("_ => {},");
-            });
-            fmt.line("}");
+            // We'll assume there are uncovered opcodes.
+            fmt.line("_ => false,");
         });
+        fmt.line("};");
+        fmt.line("if expanded {");
+        fmt.indent(|fmt| fmt.line("return true;"));
         fmt.line("}");
 
         // If we fall through, nothing was expanded; call the chain if any.
         match &group.chain_with {
             Some(group_id) => fmtln!(
                 fmt,
-                "{}(inst, pos.func, cfg, isa)",
+                "{}(inst, func, cfg, isa)",
                 transform_groups.get(*group_id).rust_name()
             ),
             None => fmt.line("false"),
         };
     });
-    fmtln!(fmt, "}");
-    fmt.empty_line();
+}
+
+/// Generate a transform group in its own file named after the group, and return the name of
+/// that file.
+fn gen_transform_group_file<'a>(
+    group: &'a TransformGroup,
+    format_registry: &FormatRegistry,
+    transform_groups: &TransformGroups,
+    all_inst: &AllInstructions,
+    type_sets: &mut UniqueTable<'a, TypeSet>,
+    filename_prefix: &str,
+    out_dir: &str,
+) -> Result<String, error::Error> {
+    let mut fmt = Formatter::new();
+    gen_transform_group(
+        group,
+        format_registry,
+        transform_groups,
+        all_inst,
+        type_sets,
+        &mut fmt,
+    );
+    let filename = format!("{}-group-{}.rs", filename_prefix, group.name);
+    fmt.update_file(&filename, out_dir)?;
+    Ok(filename)
+}
+
+/// Include the generated file `filename`.
+fn gen_include(filename: &str, fmt: &mut Formatter) {
+    fmtln!(
+        fmt,
+        "include!(concat!(env!(\"OUT_DIR\"), \"/{}\"));",
+        filename
+    );
 }
 
 /// Generate legalization functions for `isa` and add any shared `TransformGroup`s
@@ -490,9 +615,12 @@ fn gen_isa(
     isa: &TargetIsa,
     format_registry: &FormatRegistry,
     transform_groups: &TransformGroups,
+    all_inst: &AllInstructions,
     shared_group_names: &mut HashSet<&'static str>,
-    fmt: &mut Formatter,
-) {
+    filename_prefix: &str,
+    out_dir: &str,
+) -> Result<(), error::Error> {
+    let mut fmt = Formatter::new();
     let mut type_sets = UniqueTable::new();
     for group_index in isa.transitive_transform_groups(transform_groups) {
         let group = transform_groups.get(group_index);
@@ -502,13 +630,16 @@ fn gen_isa(
                     isa_name == isa.name,
                     "ISA-specific legalizations must be used by the same ISA"
                 );
-                gen_transform_group(
+                let filename = gen_transform_group_file(
                     group,
                     format_registry,
                     transform_groups,
+                    all_inst,
                     &mut type_sets,
-                    fmt,
-                );
+                    filename_prefix,
+                    out_dir,
+                )?;
+                gen_include(&filename, &mut fmt);
             }
             None => {
                 shared_group_names.insert(group.name);
@@ -516,7 +647,7 @@ fn gen_isa(
         }
     }
 
-    gen_typesets_table(&type_sets, fmt);
+    gen_typesets_table(&type_sets, &mut fmt);
 
     let direct_groups = isa.direct_transform_groups();
     fmtln!(
@@ -530,6 +661,8 @@ fn gen_isa(
         }
     });
     fmtln!(fmt, "];");
+    fmt.update_file(format!("{}-{}.rs", filename_prefix, isa.name), out_dir)?;
+    Ok(())
 }
 
 /// Generate the legalizer files.
@@ -537,21 +670,22 @@ pub fn generate(
     isas: &Vec<TargetIsa>,
     format_registry: &FormatRegistry,
     transform_groups: &TransformGroups,
+    all_inst: &AllInstructions,
     filename_prefix: &str,
     out_dir: &str,
 ) -> Result<(), error::Error> {
     let mut shared_group_names = HashSet::new();
 
     for isa in isas {
-        let mut fmt = Formatter::new();
         gen_isa(
             isa,
             format_registry,
             transform_groups,
+            all_inst,
             &mut shared_group_names,
-            &mut fmt,
-        );
-        fmt.update_file(format!("{}-{}.rs", filename_prefix, isa.name), out_dir)?;
+            filename_prefix,
+            out_dir,
+        )?;
     }
 
     // Generate shared legalize groups.
@@ -561,13 +695,16 @@ pub fn generate(
     sorted_shared_group_names.sort();
     for group_name in &sorted_shared_group_names {
         let group = transform_groups.by_name(group_name);
-        gen_transform_group(
+        let filename = gen_transform_group_file(
             group,
             format_registry,
             transform_groups,
+            all_inst,
             &mut type_sets,
-            &mut fmt,
-        );
+            filename_prefix,
+            out_dir,
+        )?;
+        gen_include(&filename, &mut fmt);
     }
     gen_typesets_table(&type_sets, &mut fmt);
     fmt.update_file(format!("{}r.rs", filename_prefix), out_dir)?;
@@ -4,7 +4,7 @@ use crate::cdsl::settings::{
 };
 use crate::constant_hash::{generate_table, simple_hash};
 use crate::error;
-use crate::srcgen::{Formatter, Match};
+use crate::srcgen::{parse_multiline, Formatter, Match};
 use crate::unique_table::UniqueSeqTable;
 use std::collections::HashMap;
 
@@ -251,6 +251,11 @@ fn gen_descriptors(group: &SettingGroup, fmt: &mut Formatter) {
             fmtln!(fmt, "detail::Descriptor {");
             fmt.indent(|fmt| {
                 fmtln!(fmt, "name: \"{}\",", setting.name);
+                fmtln!(
+                    fmt,
+                    "description: {:?},",
+                    parse_multiline(setting.comment).join("\n")
+                );
                 fmtln!(fmt, "offset: {},", setting.byte_offset);
                 match setting.specific {
                     SpecificSetting::Bool(BoolSetting { bit_offset, .. }) => {
@@ -283,6 +288,7 @@ fn gen_descriptors(group: &SettingGroup, fmt: &mut Formatter) {
             fmtln!(fmt, "detail::Descriptor {");
             fmt.indent(|fmt| {
                 fmtln!(fmt, "name: \"{}\",", preset.name);
+                fmtln!(fmt, "description: \"\",");
                 fmtln!(fmt, "offset: {},", (idx as u8) * group.settings_size);
                 fmtln!(fmt, "detail: detail::Detail::Preset,");
             });
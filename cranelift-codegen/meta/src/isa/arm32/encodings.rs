@@ -0,0 +1,476 @@
+use crate::cdsl::encodings::{Encoding, EncodingBuilder};
+use crate::cdsl::instructions::{InstSpec, InstructionGroup, InstructionPredicateRegistry};
+use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
+use crate::cdsl::settings::SettingGroup;
+use crate::cdsl::types::LaneType;
+
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I8};
+use crate::shared::Definitions as SharedDefinitions;
+
+use super::recipes::RecipeGroup;
+
+fn enc(inst: impl Into<InstSpec>, recipe: EncodingRecipeNumber, bits: u16) -> EncodingBuilder {
+    EncodingBuilder::new(inst.into(), recipe, bits)
+}
+
+pub struct PerCpuModeEncodings<'defs> {
+    pub inst_pred_reg: InstructionPredicateRegistry,
+    pub enc_a32: Vec<Encoding>,
+    recipes: &'defs Recipes,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn new(recipes: &'defs Recipes) -> Self {
+        Self {
+            inst_pred_reg: InstructionPredicateRegistry::new(),
+            enc_a32: Vec::new(),
+            recipes,
+        }
+    }
+    fn add(&mut self, encoding: EncodingBuilder) {
+        self.enc_a32
+            .push(encoding.build(self.recipes, &mut self.inst_pred_reg));
+    }
+}
+
+// Data-processing opcodes, as found in bits 24:21 of the A32 encodings.
+const DP_AND: u16 = 0b0000;
+const DP_EOR: u16 = 0b0001;
+const DP_SUB: u16 = 0b0010;
+const DP_RSB: u16 = 0b0011;
+const DP_ADD: u16 = 0b0100;
+const DP_ORR: u16 = 0b1100;
+const DP_MOV: u16 = 0b1101;
+const DP_BIC: u16 = 0b1110;
+const DP_MVN: u16 = 0b1111;
+
+// Shift types of the A32 shifted register operands.
+const SHIFT_LSL: u16 = 0b00;
+const SHIFT_LSR: u16 = 0b01;
+const SHIFT_ASR: u16 = 0b10;
+const SHIFT_ROR: u16 = 0b11;
+
+// Condition codes for the branches comparing to zero.
+const COND_EQ: u16 = 0b0000;
+const COND_NE: u16 = 0b0001;
+
+/// A shift by an immediate amount of type `shift`, optionally rotating left instead of right.
+fn shamt_bits(shift: u16, rotl: bool) -> u16 {
+    assert!(shift <= 0b11);
+    shift | (if rotl { 0b100 } else { 0 })
+}
+
+/// A word or unsigned byte load or store.
+fn ldst_bits(load: bool, byte: bool) -> u16 {
+    (if load { 1 } else { 0 }) | (if byte { 0b10 } else { 0 })
+}
+
+/// A halfword or signed byte load or store.
+fn ldst_misc_bits(load: bool, signed: bool, half: bool) -> u16 {
+    (if load { 1 } else { 0 }) | (if signed { 0b10 } else { 0 }) | (if half { 0b100 } else { 0 })
+}
+
+/// A binary VFP instruction with the `opc1` field made of bits 23, 21 and 20, and `op` in bit 6.
+fn vfp_bits(opc1: u16, op: u16, double: bool) -> u16 {
+    assert!(opc1 <= 0b111 && op <= 1);
+    opc1 | (op << 3) | (if double { 0b10000 } else { 0 })
+}
+
+/// A unary VFP instruction with the `opc2` field in bits 19:16 and `op` in bit 7. The size of the
+/// instruction and its two operands can differ for conversions.
+fn vfp_unary_bits(opc2: u16, op: u16, double: bool, d_double: bool, m_double: bool) -> u16 {
+    assert!(opc2 <= 0b1111 && op <= 1);
+    opc2 | (op << 4)
+        | (if double { 1 << 5 } else { 0 })
+        | (if d_double { 1 << 6 } else { 0 })
+        | (if m_double { 1 << 7 } else { 0 })
+}
+
+/// A VFP load or store.
+fn vldst_bits(load: bool, double: bool) -> u16 {
+    (if load { 1 } else { 0 }) | (if double { 0b10 } else { 0 })
+}
+
+pub fn define<'defs>(
+    shared_defs: &'defs SharedDefinitions,
+    isa_settings: &SettingGroup,
+    inst_group: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    // Instructions shorthands.
+    let shared = &shared_defs.instructions;
+
+    let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let band = shared.by_name("band");
+    let band_imm = shared.by_name("band_imm");
+    let band_not = shared.by_name("band_not");
+    let bconst = shared.by_name("bconst");
+    let bint = shared.by_name("bint");
+    let bitcast = shared.by_name("bitcast");
+    let bnot = shared.by_name("bnot");
+    let bor = shared.by_name("bor");
+    let bor_imm = shared.by_name("bor_imm");
+    let br_icmp = shared.by_name("br_icmp");
+    let brnz = shared.by_name("brnz");
+    let brz = shared.by_name("brz");
+    let bxor = shared.by_name("bxor");
+    let bxor_imm = shared.by_name("bxor_imm");
+    let call = shared.by_name("call");
+    let call_indirect = shared.by_name("call_indirect");
+    let clz = shared.by_name("clz");
+    let copy = shared.by_name("copy");
+    let copy_nop = shared.by_name("copy_nop");
+    let ctz = shared.by_name("ctz");
+    let debug_value = shared.by_name("debug_value");
+    let debugtrap = shared.by_name("debugtrap");
+    let fabs = shared.by_name("fabs");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fcvt_from_uint = shared.by_name("fcvt_from_uint");
+    let fcvt_to_sint_sat = shared.by_name("fcvt_to_sint_sat");
+    let fcvt_to_uint_sat = shared.by_name("fcvt_to_uint_sat");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
+    let fill = shared.by_name("fill");
+    let fmul = shared.by_name("fmul");
+    let fneg = shared.by_name("fneg");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
+    let func_addr = shared.by_name("func_addr");
+    let iadd = shared.by_name("iadd");
+    let iadd_imm = shared.by_name("iadd_imm");
+    let icmp = shared.by_name("icmp");
+    let icmp_imm = shared.by_name("icmp_imm");
+    let iconst = shared.by_name("iconst");
+    let imul = shared.by_name("imul");
+    let ireduce = shared.by_name("ireduce");
+    let irsub_imm = shared.by_name("irsub_imm");
+    let ishl = shared.by_name("ishl");
+    let ishl_imm = shared.by_name("ishl_imm");
+    let istore16 = shared.by_name("istore16");
+    let istore8 = shared.by_name("istore8");
+    let isub = shared.by_name("isub");
+    let jump = shared.by_name("jump");
+    let load = shared.by_name("load");
+    let regfill = shared.by_name("regfill");
+    let regmove = shared.by_name("regmove");
+    let regspill = shared.by_name("regspill");
+    let return_ = shared.by_name("return");
+    let rotl = shared.by_name("rotl");
+    let rotl_imm = shared.by_name("rotl_imm");
+    let rotr = shared.by_name("rotr");
+    let rotr_imm = shared.by_name("rotr_imm");
+    let sdiv = shared.by_name("sdiv");
+    let select = shared.by_name("select");
+    let sextend = shared.by_name("sextend");
+    let sload16 = shared.by_name("sload16");
+    let sload8 = shared.by_name("sload8");
+    let smulhi = shared.by_name("smulhi");
+    let spill = shared.by_name("spill");
+    let sqrt = shared.by_name("sqrt");
+    let srem = shared.by_name("srem");
+    let sshr = shared.by_name("sshr");
+    let sshr_imm = shared.by_name("sshr_imm");
+    let store = shared.by_name("store");
+    let symbol_value = shared.by_name("symbol_value");
+    let trap = shared.by_name("trap");
+    let udiv = shared.by_name("udiv");
+    let uextend = shared.by_name("uextend");
+    let uload16 = shared.by_name("uload16");
+    let uload8 = shared.by_name("uload8");
+    let umulhi = shared.by_name("umulhi");
+    let urem = shared.by_name("urem");
+    let ushr = shared.by_name("ushr");
+    let ushr_imm = shared.by_name("ushr_imm");
+
+    let arm32_pop = inst_group.by_name("arm32_pop");
+    let arm32_push = inst_group.by_name("arm32_push");
+
+    // Recipes shorthands, prefixed with r_.
+    let r_adjsp = recipes.by_name("ADJSP");
+    let r_adjsp_r = recipes.by_name("ADJSPr");
+    let r_b = recipes.by_name("B");
+    let r_bconst = recipes.by_name("BCONST");
+    let r_bkpt = recipes.by_name("BKPT");
+    let r_bl = recipes.by_name("BL");
+    let r_blx = recipes.by_name("BLX");
+    let r_bxlr = recipes.by_name("BXlr");
+    let r_clz = recipes.by_name("CLZ");
+    let r_cmp = recipes.by_name("CMP");
+    let r_cmpb = recipes.by_name("CMPB");
+    let r_cmpbz = recipes.by_name("CMPBz");
+    let r_cmpi = recipes.by_name("CMPi");
+    let r_ctz = recipes.by_name("CTZ");
+    let r_debugvalue = recipes.by_name("debugvalue");
+    let r_div = recipes.by_name("DIV");
+    let r_dpi = recipes.by_name("DPi");
+    let r_dpmov = recipes.by_name("DPmov");
+    let r_dpr = recipes.by_name("DPr");
+    let r_dprmov = recipes.by_name("DPrmov");
+    let r_dprotl = recipes.by_name("DProtl");
+    let r_dpshamt = recipes.by_name("DPshamt");
+    let r_dpshift = recipes.by_name("DPshift");
+    let r_dpuimm = recipes.by_name("DPuimm");
+    let r_ext = recipes.by_name("EXT");
+    let r_fnaddr = recipes.by_name("FNADDR");
+    let r_gp_fi = recipes.by_name("GPfi");
+    let r_gp_rfi = recipes.by_name("GPrfi");
+    let r_gp_rsp = recipes.by_name("GPrsp");
+    let r_gp_sp = recipes.by_name("GPsp");
+    let r_gvaddr = recipes.by_name("GVADDR");
+    let r_ldr = recipes.by_name("LDR");
+    let r_ldr_r = recipes.by_name("LDRr");
+    let r_ldrh = recipes.by_name("LDRH");
+    let r_ldrh_r = recipes.by_name("LDRHr");
+    let r_movw = recipes.by_name("MOVW");
+    let r_movwt = recipes.by_name("MOVWT");
+    let r_mul = recipes.by_name("MUL");
+    let r_mulhi = recipes.by_name("MULhi");
+    let r_null = recipes.by_name("null");
+    let r_pop = recipes.by_name("POP");
+    let r_push = recipes.by_name("PUSH");
+    let r_rem = recipes.by_name("REM");
+    let r_sdiv = recipes.by_name("SDIV");
+    let r_sel = recipes.by_name("SEL");
+    let r_stacknull = recipes.by_name("stacknull");
+    let r_str = recipes.by_name("STR");
+    let r_str_r = recipes.by_name("STRr");
+    let r_strh = recipes.by_name("STRH");
+    let r_strh_r = recipes.by_name("STRHr");
+    let r_udf = recipes.by_name("UDF");
+    let r_vcvt_ds = recipes.by_name("VCVTds");
+    let r_vcvt_sd = recipes.by_name("VCVTsd");
+    let r_vmov_rs = recipes.by_name("VMOVrs");
+    let r_vmov_sr = recipes.by_name("VMOVsr");
+
+    // Predicates shorthands.
+    let use_div = isa_settings.predicate_by_name("use_div");
+    let use_vfp = isa_settings.predicate_by_name("use_vfp");
+
+    // Definitions.
+    let mut e = PerCpuModeEncodings::new(&recipes.recipes);
+
+    // Data-processing instructions with a register or an 8-bit immediate operand.
+    for &(inst, inst_imm, opcode) in &[
+        (iadd, Some(iadd_imm), DP_ADD),
+        (isub, None, DP_SUB),
+        (band, Some(band_imm), DP_AND),
+        (bor, Some(bor_imm), DP_ORR),
+        (bxor, Some(bxor_imm), DP_EOR),
+        (band_not, None, DP_BIC),
+    ] {
+        e.add(enc(inst.bind(I32), r_dpr, opcode));
+        if let Some(inst_imm) = inst_imm {
+            e.add(enc(inst_imm.bind(I32), r_dpi, opcode));
+        }
+    }
+    e.add(enc(irsub_imm.bind(I32), r_dpi, DP_RSB));
+
+    // Booleans are 0 or 1 in a GPR.
+    for &(inst, opcode) in &[(band, DP_AND), (bor, DP_ORR), (bxor, DP_EOR)] {
+        e.add(enc(inst.bind(B1), r_dpr, opcode));
+    }
+    e.add(enc(bnot.bind(I32), r_dpmov, DP_MVN));
+    e.add(enc(bnot.bind(B1), r_dpuimm, DP_EOR));
+
+    // Shifts and rotates.
+    for &(inst, inst_imm, shift) in &[
+        (ishl, ishl_imm, SHIFT_LSL),
+        (ushr, ushr_imm, SHIFT_LSR),
+        (sshr, sshr_imm, SHIFT_ASR),
+        (rotr, rotr_imm, SHIFT_ROR),
+    ] {
+        e.add(enc(inst.bind(I32).bind(I32), r_dpshift, shift));
+        e.add(enc(inst_imm.bind(I32), r_dpshamt, shamt_bits(shift, false)));
+    }
+    e.add(enc(rotl.bind(I32).bind(I32), r_dprotl, SHIFT_ROR));
+    e.add(enc(
+        rotl_imm.bind(I32),
+        r_dpshamt,
+        shamt_bits(SHIFT_ROR, true),
+    ));
+
+    // Multiplication. The encbits of the long multiplications are 1 for the signed variant.
+    e.add(enc(imul.bind(I32), r_mul, 0));
+    e.add(enc(umulhi.bind(I32), r_mulhi, 0));
+    e.add(enc(smulhi.bind(I32), r_mulhi, 1));
+
+    // Hardware division is optional in ARMv7-A. The encbits are 1 for the unsigned variants.
+    e.add(enc(udiv.bind(I32), r_div, 1).isa_predicate(use_div));
+    e.add(enc(sdiv.bind(I32), r_sdiv, 0).isa_predicate(use_div));
+    e.add(enc(urem.bind(I32), r_rem, 1).isa_predicate(use_div));
+    e.add(enc(srem.bind(I32), r_rem, 0).isa_predicate(use_div));
+
+    // Bit counting.
+    e.add(enc(clz.bind(I32), r_clz, 0));
+    e.add(enc(ctz.bind(I32), r_ctz, 0));
+
+    // Extensions, which are `sxtb`, `sxth`, `uxtb` and `uxth`.
+    e.add(enc(sextend.bind(I32).bind(I8), r_ext, 0b010));
+    e.add(enc(sextend.bind(I32).bind(I16), r_ext, 0b011));
+    e.add(enc(uextend.bind(I32).bind(I8), r_ext, 0b110));
+    e.add(enc(uextend.bind(I32).bind(I16), r_ext, 0b111));
+
+    // Reductions only reinterpret the low bits of a register.
+    e.add(enc(ireduce.bind(I8).bind(I32), r_null, 0));
+    e.add(enc(ireduce.bind(I16).bind(I32), r_null, 0));
+    e.add(enc(bint.bind(I32).bind(B1), r_null, 0));
+
+    // Comparisons and selects.
+    e.add(enc(icmp.bind(I32), r_cmp, 0));
+    e.add(enc(icmp_imm.bind(I32), r_cmpi, 0));
+    e.add(enc(select.bind(I32).bind(B1), r_sel, 0));
+    e.add(enc(select.bind(I32).bind(I32), r_sel, 0));
+
+    // Constants. A `movw` is enough for small values.
+    e.add(enc(iconst.bind(I32), r_movw, 0));
+    e.add(enc(iconst.bind(I32), r_movwt, 0));
+    e.add(enc(bconst.bind(B1), r_bconst, 0));
+    e.add(enc(func_addr.bind(I32), r_fnaddr, 0));
+    e.add(enc(symbol_value.bind(I32), r_gvaddr, 0));
+
+    // Loads and stores, with the large offset variants last.
+    for &(inst, recipe, recipe_r, bits) in &[
+        (load, r_ldr, r_ldr_r, ldst_bits(true, false)),
+        (uload8, r_ldr, r_ldr_r, ldst_bits(true, true)),
+        (sload8, r_ldrh, r_ldrh_r, ldst_misc_bits(true, true, false)),
+        (uload16, r_ldrh, r_ldrh_r, ldst_misc_bits(true, false, true)),
+        (sload16, r_ldrh, r_ldrh_r, ldst_misc_bits(true, true, true)),
+        (store, r_str, r_str_r, ldst_bits(false, false)),
+        (istore8, r_str, r_str_r, ldst_bits(false, true)),
+        (
+            istore16,
+            r_strh,
+            r_strh_r,
+            ldst_misc_bits(false, false, true),
+        ),
+    ] {
+        e.add(enc(inst.bind(I32).bind_any(), recipe, bits));
+        e.add(enc(inst.bind(I32).bind_any(), recipe_r, bits));
+    }
+
+    // Register copies, spills and fills.
+    for &ty in &[LaneType::from(I32), LaneType::from(B1)] {
+        e.add(enc(copy.bind(ty), r_dpmov, DP_MOV));
+        e.add(enc(regmove.bind(ty), r_dprmov, DP_MOV));
+        e.add(enc(spill.bind(ty), r_gp_sp, ldst_bits(false, false)));
+        e.add(enc(fill.bind(ty), r_gp_fi, ldst_bits(true, false)));
+        e.add(enc(regspill.bind(ty), r_gp_rsp, ldst_bits(false, false)));
+        e.add(enc(regfill.bind(ty), r_gp_rfi, ldst_bits(true, false)));
+    }
+
+    // Stack-slot-to-the-same-stack-slot copy, which is guaranteed to turn
+    // into a no-op.
+    for &ty in &[I32, I16, I8] {
+        e.add(enc(copy_nop.bind(ty), r_stacknull, 0));
+    }
+    e.add(enc(copy_nop.bind(B1), r_stacknull, 0));
+
+    // Debug value markers generate no code. The smaller integers are widened by legalization.
+    e.add(enc(debug_value.bind(I32), r_debugvalue, 0));
+    e.add(enc(debug_value.bind(B1), r_debugvalue, 0));
+
+    // Prologues and epilogues.
+    e.add(enc(arm32_push, r_push, 0));
+    e.add(enc(arm32_pop, r_pop, 0));
+    for &(inst, opcode) in &[(adjust_sp_down_imm, DP_SUB), (adjust_sp_up_imm, DP_ADD)] {
+        e.add(enc(inst, r_adjsp, opcode));
+        e.add(enc(inst, r_adjsp_r, opcode));
+    }
+
+    // Control flow.
+    e.add(enc(jump, r_b, 0));
+    for &ty in &[LaneType::from(I32), LaneType::from(B1)] {
+        e.add(enc(brz.bind(ty), r_cmpbz, COND_EQ));
+        e.add(enc(brnz.bind(ty), r_cmpbz, COND_NE));
+    }
+    e.add(enc(br_icmp.bind(I32), r_cmpb, 0));
+
+    // Calls and returns use the link register %r14. The return address is provided by a
+    // special-purpose `link` return value that is added by legalize_signature().
+    e.add(enc(call, r_bl, 0));
+    e.add(enc(call_indirect.bind(I32), r_blx, 0));
+    e.add(enc(return_, r_bxlr, 0));
+
+    e.add(enc(trap, r_udf, 0));
+    e.add(enc(debugtrap, r_bkpt, 0));
+
+    // VFPv3 floating point instructions.
+    // Gated by the `use_vfp` flag.
+    for &(ty, double, suffix) in &[(F32, false, "s"), (F64, true, "d")] {
+        let recipe = |name: &str| recipes.by_name(&format!("{}{}", name, suffix));
+
+        for &(inst, opc1, op) in &[
+            (fadd, 0b011, 0),
+            (fsub, 0b011, 1),
+            (fmul, 0b010, 0),
+            (fdiv, 0b100, 0),
+        ] {
+            e.add(
+                enc(inst.bind(ty), recipe("VFP"), vfp_bits(opc1, op, double))
+                    .isa_predicate(use_vfp),
+            );
+        }
+
+        let unary = |opc2, op| vfp_unary_bits(opc2, op, double, double, double);
+        for &(inst, opc2, op) in &[(fabs, 0b0000, 1), (fneg, 0b0001, 0), (sqrt, 0b0001, 1)] {
+            e.add(enc(inst.bind(ty), recipe("VFPun"), unary(opc2, op)).isa_predicate(use_vfp));
+        }
+        e.add(enc(copy.bind(ty), recipe("VFPun"), unary(0b0000, 0)).isa_predicate(use_vfp));
+        e.add(enc(regmove.bind(ty), recipe("VFPrmov"), unary(0b0000, 0)).isa_predicate(use_vfp));
+        e.add(enc(fcmp.bind(ty), recipe("VCMP"), unary(0b0100, 0)).isa_predicate(use_vfp));
+
+        let load_bits = vldst_bits(true, double);
+        let store_bits = vldst_bits(false, double);
+        e.add(enc(load.bind(ty).bind_any(), recipe("VLDR"), load_bits).isa_predicate(use_vfp));
+        e.add(enc(load.bind(ty).bind_any(), recipe("VLDRr"), load_bits).isa_predicate(use_vfp));
+        e.add(enc(store.bind(ty).bind_any(), recipe("VSTR"), store_bits).isa_predicate(use_vfp));
+        e.add(enc(store.bind(ty).bind_any(), recipe("VSTRr"), store_bits).isa_predicate(use_vfp));
+
+        e.add(enc(spill.bind(ty), recipe("VFPsp"), store_bits).isa_predicate(use_vfp));
+        e.add(enc(fill.bind(ty), recipe("VFPfi"), load_bits).isa_predicate(use_vfp));
+        e.add(enc(regspill.bind(ty), recipe("VFPrsp"), store_bits).isa_predicate(use_vfp));
+        e.add(enc(regfill.bind(ty), recipe("VFPrfi"), load_bits).isa_predicate(use_vfp));
+        e.add(enc(copy_nop.bind(ty), recipe("stacknull"), 0).isa_predicate(use_vfp));
+        e.add(enc(debug_value.bind(ty), recipe("debugvalue"), 0).isa_predicate(use_vfp));
+
+        // Integer conversions go through a single precision register, which is where `vcvt`
+        // reads and writes the integer side.
+        for &(inst, op) in &[(fcvt_from_sint, 1), (fcvt_from_uint, 0)] {
+            let bits = vfp_unary_bits(0b1000, op, double, double, false);
+            e.add(enc(inst.bind(ty).bind(I32), recipe("VCVTi"), bits).isa_predicate(use_vfp));
+        }
+        for &(inst, opc2) in &[(fcvt_to_sint_sat, 0b1101), (fcvt_to_uint_sat, 0b1100)] {
+            let bits = vfp_unary_bits(opc2, 1, double, false, double);
+            let r_vcvt = recipes.by_name(&format!("VCVT{}i", suffix));
+            e.add(enc(inst.bind(I32).bind(ty), r_vcvt, bits).isa_predicate(use_vfp));
+        }
+    }
+
+    // Conversions between single and double precision.
+    e.add(
+        enc(
+            fpromote.bind(F64).bind(F32),
+            r_vcvt_sd,
+            vfp_unary_bits(0b0111, 1, false, true, false),
+        )
+        .isa_predicate(use_vfp),
+    );
+    e.add(
+        enc(
+            fdemote.bind(F32).bind(F64),
+            r_vcvt_ds,
+            vfp_unary_bits(0b0111, 1, true, false, true),
+        )
+        .isa_predicate(use_vfp),
+    );
+
+    e.add(enc(bitcast.bind(F32).bind(I32), r_vmov_sr, 0).isa_predicate(use_vfp));
+    e.add(enc(bitcast.bind(I32).bind(F32), r_vmov_rs, 0).isa_predicate(use_vfp));
+
+    e
+}
@@ -0,0 +1,59 @@
+#![allow(non_snake_case)]
+
+use crate::cdsl::formats::FormatRegistry;
+use crate::cdsl::instructions::{
+    AllInstructions, InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
+};
+use crate::cdsl::operands::create_operand as operand;
+use crate::cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+pub fn define(
+    all_instructions: &mut AllInstructions,
+    format_registry: &FormatRegistry,
+) -> InstructionGroup {
+    let mut ig = InstructionGroupBuilder::new(
+        "arm32",
+        "arm32 specific instruction set",
+        all_instructions,
+        format_registry,
+    );
+
+    let iWord = &TypeVar::new(
+        "iWord",
+        "A scalar integer machine word",
+        TypeSetBuilder::new().ints(32..32).build(),
+    );
+    let x = &operand("x", iWord);
+
+    ig.push(
+        Inst::new(
+            "arm32_push",
+            r#"
+    Pushes a value onto the stack.
+
+    Decrements the stack pointer by 4 and stores the specified value on to the
+    top.
+    "#,
+        )
+        .operands_in(vec![x])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm32_pop",
+            r#"
+    Pops a value from the stack.
+
+    Loads a value from the top of the stack and then increments the stack
+    pointer by 4.
+    "#,
+        )
+        .operands_out(vec![x])
+        .other_side_effects(true)
+        .can_load(true),
+    );
+
+    ig.build()
+}
@@ -1,14 +1,36 @@
 use crate::cdsl::cpu_modes::CpuMode;
-use crate::cdsl::instructions::{InstructionGroupBuilder, InstructionPredicateMap};
 use crate::cdsl::isa::TargetIsa;
-use crate::cdsl::recipes::Recipes;
 use crate::cdsl::regs::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
-use crate::cdsl::settings::{SettingGroup, SettingGroupBuilder};
+use crate::cdsl::settings::{PredicateNode, SettingGroup, SettingGroupBuilder};
 
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I8};
 use crate::shared::Definitions as SharedDefinitions;
 
-fn define_settings(_shared: &SettingGroup) -> SettingGroup {
-    let setting = SettingGroupBuilder::new("arm32");
+mod encodings;
+mod instructions;
+mod recipes;
+
+fn define_settings(shared: &SettingGroup) -> SettingGroup {
+    let mut setting = SettingGroupBuilder::new("arm32");
+
+    let has_vfp = setting.add_bool(
+        "has_vfp",
+        "CPU supports the VFPv3 floating point extension",
+        false,
+    );
+    let has_div = setting.add_bool(
+        "has_div",
+        "CPU supports the SDIV and UDIV instructions",
+        false,
+    );
+
+    let shared_enable_float = shared.get_bool("enable_float");
+
+    setting.add_predicate("use_vfp", predicate!(has_vfp && shared_enable_float));
+    setting.add_predicate("use_div", predicate!(has_div));
+
     setting.build()
 }
 
@@ -53,30 +75,35 @@ pub fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
     let settings = define_settings(&shared_defs.settings);
     let regs = define_regs();
 
-    let inst_group = InstructionGroupBuilder::new(
-        "arm32",
-        "arm32 specific instruction set",
+    let inst_group = instructions::define(
         &mut shared_defs.all_instructions,
         &shared_defs.format_registry,
-    )
-    .build();
+    );
 
-    // CPU modes for 32-bit ARM and Thumb2.
+    // CPU mode for 32-bit ARM. Thumb2 encodings are not implemented.
     let mut a32 = CpuMode::new("A32");
-    let mut t32 = CpuMode::new("T32");
 
-    // TODO refine these.
+    let expand = shared_defs.transform_groups.by_name("expand");
     let narrow = shared_defs.transform_groups.by_name("narrow");
+    let widen = shared_defs.transform_groups.by_name("widen");
+    a32.legalize_monomorphic(expand);
     a32.legalize_default(narrow);
-    t32.legalize_default(narrow);
+    a32.legalize_type(B1, expand);
+    a32.legalize_type(I8, widen);
+    a32.legalize_type(I16, widen);
+    a32.legalize_type(I32, expand);
+    a32.legalize_type(F32, expand);
+    a32.legalize_type(F64, expand);
+
+    let recipes = recipes::define(shared_defs, &regs);
 
-    let cpu_modes = vec![a32, t32];
+    let encodings = encodings::define(shared_defs, &settings, &inst_group, &recipes);
+    a32.set_encodings(encodings.enc_a32);
+    let encodings_predicates = encodings.inst_pred_reg.extract();
 
-    // TODO implement arm32 recipes.
-    let recipes = Recipes::new();
+    let recipes = recipes.collect();
 
-    // TODO implement arm32 encodings and predicates.
-    let encodings_predicates = InstructionPredicateMap::new();
+    let cpu_modes = vec![a32];
 
     TargetIsa::new(
         "arm32",
@@ -0,0 +1,875 @@
+use std::collections::HashMap;
+
+use crate::cdsl::formats::FormatRegistry;
+use crate::cdsl::instructions::InstructionPredicate;
+use crate::cdsl::recipes::{EncodingRecipeBuilder, EncodingRecipeNumber, Recipes, Stack};
+use crate::cdsl::regs::IsaRegs;
+use crate::shared::Definitions as SharedDefinitions;
+
+/// An helper to create recipes and use them when defining the ARM32 encodings.
+pub struct RecipeGroup<'formats> {
+    /// Memoized format registry, to pass it to the builders.
+    formats: &'formats FormatRegistry,
+
+    /// The actualy list of recipes explicitly created in this file.
+    pub recipes: Recipes,
+
+    /// Provides fast lookup from a name to an encoding recipe.
+    name_to_recipe: HashMap<String, EncodingRecipeNumber>,
+}
+
+impl<'formats> RecipeGroup<'formats> {
+    fn new(formats: &'formats FormatRegistry) -> Self {
+        Self {
+            formats,
+            recipes: Recipes::new(),
+            name_to_recipe: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, builder: EncodingRecipeBuilder) {
+        assert!(
+            !self.name_to_recipe.contains_key(&builder.name),
+            "arm32 recipe '{}' created twice",
+            builder.name
+        );
+        let name = builder.name.clone();
+        let number = self.recipes.push(builder.build(self.formats));
+        self.name_to_recipe.insert(name, number);
+    }
+
+    pub fn by_name(&self, name: &str) -> EncodingRecipeNumber {
+        *self
+            .name_to_recipe
+            .get(name)
+            .unwrap_or_else(|| panic!("unknown arm32 recipe name {}", name))
+    }
+
+    pub fn collect(self) -> Recipes {
+        self.recipes
+    }
+}
+
+pub fn define<'formats>(
+    shared_defs: &'formats SharedDefinitions,
+    regs: &IsaRegs,
+) -> RecipeGroup<'formats> {
+    let formats = &shared_defs.format_registry;
+
+    // Format shorthands.
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_icmp = formats.by_name("BranchIcmp");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_func_addr = formats.by_name("FuncAddr");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
+    let f_reg_fill = formats.by_name("RegFill");
+    let f_reg_move = formats.by_name("RegMove");
+    let f_reg_spill = formats.by_name("RegSpill");
+    let f_store = formats.by_name("Store");
+    let f_ternary = formats.by_name("Ternary");
+    let f_trap = formats.by_name("Trap");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_bool = formats.by_name("UnaryBool");
+    let f_unary_global_value = formats.by_name("UnaryGlobalValue");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let gpr = regs.class_by_name("GPR");
+    let s = regs.class_by_name("S");
+    let d = regs.class_by_name("D");
+
+    // Definitions.
+    let mut recipes = RecipeGroup::new(&shared_defs.format_registry);
+
+    // All recipes are A32 instructions with the `AL` condition, unless noted otherwise. Recipes
+    // which need a temporary register use %r12 (`ip`), which is never allocated.
+
+    // Data-processing instructions with a register operand. The encbits are `opcode | (S << 4)`.
+    recipes.push(
+        EncodingRecipeBuilder::new("DPr", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_dp(AL, bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Data-processing instructions with an 8-bit immediate operand.
+    let format = formats.get(f_binary_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("DPi", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                format, "imm", 8, 0,
+            ))
+            .emit("put_dp_imm(AL, bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Unary data-processing instructions like `mov` and `mvn`.
+    recipes.push(
+        EncodingRecipeBuilder::new("DPmov", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_dp(AL, bits, 0, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same for a GPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("DPrmov", f_reg_move, 4)
+            .operands_in(vec![gpr])
+            .emit("put_dp(AL, bits, 0, src, dst, sink);"),
+    );
+
+    // Unary data-processing instructions with an immediate operand, used for `bnot.b1`.
+    recipes.push(
+        EncodingRecipeBuilder::new("DPuimm", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_dp_imm(AL, bits, in_reg0, 1, out_reg0, sink);"),
+    );
+
+    // Shifts by an immediate amount. The encbits are the shift type, see `put_shift_imm()`.
+    recipes.push(
+        EncodingRecipeBuilder::new("DPshamt", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_shift_imm(bits, in_reg0, imm.into(), out_reg0, sink);"),
+    );
+
+    // Shifts by a register amount. A32 shifts use the low byte of the amount register, so the
+    // amount is masked into %r12 first to get the modulo semantics of the IR instructions.
+    recipes.push(
+        EncodingRecipeBuilder::new("DPshift", f_binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp_imm(AL, DP_AND, in_reg1, 31, IP, sink);
+                    put_shift_reg(bits, in_reg0, IP, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Rotate left by a register amount, which is a rotate right by the negated amount.
+    recipes.push(
+        EncodingRecipeBuilder::new("DProtl", f_binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp_imm(AL, DP_RSB, in_reg1, 0, IP, sink);
+                    put_shift_reg(bits, in_reg0, IP, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // 32-bit multiplication.
+    recipes.push(
+        EncodingRecipeBuilder::new("MUL", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_mul(in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // High half of a 64-bit multiplication. The low half is discarded in %r12.
+    recipes.push(
+        EncodingRecipeBuilder::new("MULhi", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit("put_mull(bits, in_reg0, in_reg1, IP, out_reg0, sink);"),
+    );
+
+    // Unsigned division, preceded by a check for a zero divisor.
+    recipes.push(
+        EncodingRecipeBuilder::new("DIV", f_binary, 16)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_div_check(in_reg1, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_div(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Signed division also checks for `INT_MIN / -1`, which doesn't fit in the result.
+    recipes.push(
+        EncodingRecipeBuilder::new("SDIV", f_binary, 32)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_div_check(in_reg1, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_sdiv_overflow_check(in_reg0, in_reg1, sink);
+                    sink.trap(TrapCode::IntegerOverflow, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_div(bits, in_reg0, in_reg1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Remainder computed from the quotient in %r12. A32 `sdiv` returns `INT_MIN` for
+    // `INT_MIN / -1` and the remainder comes out as 0, so only the zero check is needed.
+    recipes.push(
+        EncodingRecipeBuilder::new("REM", f_binary, 20)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_div_check(in_reg1, sink);
+                    sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
+                    put_udf(sink);
+                    put_div(bits, in_reg0, in_reg1, IP, sink);
+                    put_mls(IP, in_reg1, in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Count leading zeros.
+    recipes.push(
+        EncodingRecipeBuilder::new("CLZ", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_clz(in_reg0, out_reg0, sink);"),
+    );
+
+    // Count trailing zeros as the leading zeros of the bit-reversed value.
+    recipes.push(
+        EncodingRecipeBuilder::new("CTZ", f_unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_rbit(in_reg0, IP, sink);
+                    put_clz(IP, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Zero and sign extensions. The encbits are the `op` field of `put_ext()`.
+    recipes.push(
+        EncodingRecipeBuilder::new("EXT", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_ext(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Reductions and conversions where the value is already in the right register.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", f_unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .emit(""),
+    );
+
+    // Integer comparison producing a `b1` as 0 or 1.
+    recipes.push(
+        EncodingRecipeBuilder::new("CMP", f_int_compare, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_dp(AL, DP_CMP, in_reg0, in_reg1, 0, sink);
+                    put_dp_imm(AL, DP_MOV, 0, 0, out_reg0, sink);
+                    put_dp_imm(icc2cond(cond), DP_MOV, 0, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Integer comparison with an 8-bit immediate.
+    let format = formats.get(f_int_compare_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("CMPi", f_int_compare_imm, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                format, "imm", 8, 0,
+            ))
+            .emit(
+                r#"
+                    put_dp_imm(AL, DP_CMP, in_reg0, imm.into(), 0, sink);
+                    put_dp_imm(AL, DP_MOV, 0, 0, out_reg0, sink);
+                    put_dp_imm(icc2cond(cond), DP_MOV, 0, 1, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Select between two GPRs. The output is tied to the first value, and replaced with the
+    // second one when the condition is zero.
+    recipes.push(
+        EncodingRecipeBuilder::new("SEL", f_ternary, 8)
+            .operands_in(vec![gpr, gpr, gpr])
+            .operands_out(vec![1])
+            .emit(
+                r#"
+                    put_dp_imm(AL, DP_CMP, in_reg0, 0, 0, sink);
+                    put_dp(COND_EQ, DP_MOV, 0, in_reg2, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // Integer constants with a 16-bit unsigned immediate.
+    let format = formats.get(f_unary_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("MOVW", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                format, "imm", 16, 0,
+            ))
+            .emit("put_movw(imm.into(), out_reg0, sink);"),
+    );
+
+    // Any 32-bit integer constant.
+    recipes.push(
+        EncodingRecipeBuilder::new("MOVWT", f_unary_imm, 8)
+            .operands_out(vec![gpr])
+            .emit("put_movw_movt(imm.into(), out_reg0, sink);"),
+    );
+
+    // Boolean constants.
+    recipes.push(
+        EncodingRecipeBuilder::new("BCONST", f_unary_bool, 4)
+            .operands_out(vec![gpr])
+            .emit("put_dp_imm(AL, DP_MOV, 0, if imm { 1 } else { 0 }, out_reg0, sink);"),
+    );
+
+    // Address of a function or symbol, loaded from a literal word placed in the instruction
+    // stream:
+    //
+    //   ldr rd, [pc]
+    //   b +0
+    //   .word <symbol>
+    recipes.push(
+        EncodingRecipeBuilder::new("FNADDR", f_func_addr, 12)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_literal_prefix(out_reg0, sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("GVADDR", f_unary_global_value, 12)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_literal_prefix(out_reg0, sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.global_values[global_value].symbol_name(),
+                                        func.global_values[global_value].symbol_offset());
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // Word and unsigned byte loads and stores with a 12-bit offset. The encbits are
+    // `L | (B << 1)`, see `put_ldst()`.
+    let format = formats.get(f_load);
+    recipes.push(
+        EncodingRecipeBuilder::new("LDR", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit("put_ldst(bits, in_reg0, offset.into(), out_reg0, sink);"),
+    );
+
+    // Same with any offset, which is materialized in %r12.
+    recipes.push(
+        EncodingRecipeBuilder::new("LDRr", f_load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw_movt(offset.into(), IP, sink);
+                    put_ldst_reg(bits, in_reg0, IP, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    let format = formats.get(f_store);
+    recipes.push(
+        EncodingRecipeBuilder::new("STR", f_store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit("put_ldst(bits, in_reg1, offset.into(), in_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("STRr", f_store, 12)
+            .operands_in(vec![gpr, gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw_movt(offset.into(), IP, sink);
+                    put_ldst_reg(bits, in_reg1, IP, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Halfword and signed byte loads and stores with an 8-bit offset. The encbits are
+    // `L | (S << 1) | (H << 2)`, see `put_ldst_misc()`.
+    let format = formats.get(f_load);
+    recipes.push(
+        EncodingRecipeBuilder::new("LDRH", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 8, 0,
+            ))
+            .emit("put_ldst_misc(bits, in_reg0, offset.into(), out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("LDRHr", f_load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw_movt(offset.into(), IP, sink);
+                    put_ldst_misc_reg(bits, in_reg0, IP, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    let format = formats.get(f_store);
+    recipes.push(
+        EncodingRecipeBuilder::new("STRH", f_store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 8, 0,
+            ))
+            .emit("put_ldst_misc(bits, in_reg1, offset.into(), in_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("STRHr", f_store, 12)
+            .operands_in(vec![gpr, gpr])
+            .emit(
+                r#"
+                    let offset: i32 = offset.into();
+                    put_movw_movt(offset.into(), IP, sink);
+                    put_ldst_misc_reg(bits, in_reg1, IP, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Spill of a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("GPsp", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![Stack::new(gpr)])
+            .emit(
+                r#"
+                    let base = stk_base(out_stk0.base);
+                    put_ldst(bits, base, out_stk0.offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Fill of a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("GPfi", f_unary, 4)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    let base = stk_base(in_stk0.base);
+                    put_ldst(bits, base, in_stk0.offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("GPrsp", f_reg_spill, 4)
+            .operands_in(vec![gpr])
+            .emit(
+                r#"
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    let base = stk_base(dst.base);
+                    put_ldst(bits, base, dst.offset.into(), src, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("GPrfi", f_reg_fill, 4)
+            .operands_in(vec![Stack::new(gpr)])
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    let base = stk_base(src.base);
+                    put_ldst(bits, base, src.offset.into(), dst, sink);
+                "#,
+            ),
+    );
+
+    // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
+    recipes.push(
+        EncodingRecipeBuilder::new("stacknull", f_unary, 0)
+            .operands_in(vec![Stack::new(gpr)])
+            .operands_out(vec![Stack::new(gpr)])
+            .emit(""),
+    );
+
+    // Debug value marker, which only keeps its operand alive in a register.
+    recipes.push(
+        EncodingRecipeBuilder::new("debugvalue", f_binary_imm, 0)
+            .operands_in(vec![gpr])
+            .emit(""),
+    );
+
+    // Push and pop of a single register, `str rt, [sp, #-4]!` and `ldr rt, [sp], #4`.
+    recipes.push(
+        EncodingRecipeBuilder::new("PUSH", f_unary, 4)
+            .operands_in(vec![gpr])
+            .emit("put_push(in_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("POP", f_nullary, 4)
+            .operands_out(vec![gpr])
+            .emit("put_pop(out_reg0, sink);"),
+    );
+
+    // Stack pointer adjustments by a multiple of 4 below 1024, which fit in a rotated immediate.
+    // The encbits are the data-processing opcode.
+    let format = formats.get(f_unary_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("ADJSP", f_unary_imm, 4)
+            .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                format, "imm", 10, 2,
+            ))
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_dp_imm(AL, bits, SP, encode_imm_scaled4(imm), SP, sink);
+                "#,
+            ),
+    );
+
+    // Any other stack pointer adjustment goes through %r12.
+    recipes.push(EncodingRecipeBuilder::new("ADJSPr", f_unary_imm, 12).emit(
+        r#"
+                    put_movw_movt(imm.into(), IP, sink);
+                    put_dp(AL, bits, SP, IP, SP, sink);
+                "#,
+    ));
+
+    // Unconditional branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("B", f_jump, 4)
+            .branch_range((8, 26))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(AL, disp, sink);
+                "#,
+            ),
+    );
+
+    // Branch on a value compared to zero. The encbits are the branch condition.
+    recipes.push(
+        EncodingRecipeBuilder::new("CMPBz", f_branch, 8)
+            .operands_in(vec![gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    put_dp_imm(AL, DP_CMP, in_reg0, 0, 0, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(u32::from(bits), disp, sink);
+                "#,
+            ),
+    );
+
+    // Compare and branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("CMPB", f_branch_icmp, 8)
+            .operands_in(vec![gpr, gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    put_dp(AL, DP_CMP, in_reg0, in_reg1, 0, sink);
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(icc2cond(cond), disp, sink);
+                "#,
+            ),
+    );
+
+    // Direct call, `bl`. The link register is written with the return address.
+    recipes.push(EncodingRecipeBuilder::new("BL", f_call, 4).emit(
+        r#"
+                    sink.reloc_external(Reloc::Arm32Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put4(BL);
+                "#,
+    ));
+
+    // Indirect call, `blx rm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("BLX", f_call_indirect, 4)
+            .operands_in(vec![gpr])
+            .emit("put_blx(in_reg0, sink);"),
+    );
+
+    // Return with `bx lr`. The return address is provided as a special-purpose link argument.
+    recipes.push(EncodingRecipeBuilder::new("BXlr", f_multiary, 4).emit("sink.put4(BX_LR);"));
+
+    // Traps are permanently undefined instructions.
+    recipes.push(EncodingRecipeBuilder::new("UDF", f_trap, 4).emit(
+        r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_udf(sink);
+                "#,
+    ));
+
+    recipes.push(EncodingRecipeBuilder::new("BKPT", f_nullary, 4).emit("sink.put4(BKPT);"));
+
+    // VFP instructions. Single precision values live in the S class and double precision values
+    // in the D class, so most recipes come in pairs.
+    for &(suffix, fpr) in &[("s", s), ("d", d)] {
+        // Binary arithmetic. The encbits are described with `put_vfp()`.
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFP{}", suffix), f_binary, 4)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .emit("put_vfp(bits, in_reg0, in_reg1, out_reg0, sink);"),
+        );
+
+        // Unary arithmetic and copies. The encbits are described with `put_vfp_unary()`.
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFPun{}", suffix), f_unary, 4)
+                .operands_in(vec![fpr])
+                .operands_out(vec![fpr])
+                .emit("put_vfp_unary(bits, in_reg0, out_reg0, sink);"),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFPrmov{}", suffix), f_reg_move, 4)
+                .operands_in(vec![fpr])
+                .emit("put_vfp_unary(bits, src, dst, sink);"),
+        );
+
+        // Floating point comparison producing a `b1`. The flags are moved from the FPSCR to the
+        // APSR, and some conditions need two conditional moves.
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VCMP{}", suffix), f_float_compare, 20)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![gpr])
+                .emit(
+                    r#"
+                    put_vfp_unary(bits, in_reg1, in_reg0, sink);
+                    sink.put4(VMRS_APSR_NZCV);
+                    put_dp_imm(AL, DP_MOV, 0, 0, out_reg0, sink);
+                    let (cond1, cond2) = fcc2conds(cond);
+                    put_dp_imm(cond1, DP_MOV, 0, 1, out_reg0, sink);
+                    put_dp_imm(cond2, DP_MOV, 0, 1, out_reg0, sink);
+                "#,
+                ),
+        );
+
+        // Loads and stores with a scaled 8-bit offset. The encbits are `L | (sz << 1)`.
+        let format = formats.get(f_load);
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VLDR{}", suffix), f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 10, 2,
+                ))
+                .emit("put_vldst(bits, in_reg0, offset.into(), out_reg0, sink);"),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VLDRr{}", suffix), f_load, 16)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                    let offset: i32 = offset.into();
+                    put_movw_movt(offset.into(), IP, sink);
+                    put_dp(AL, DP_ADD, in_reg0, IP, IP, sink);
+                    put_vldst(bits, IP, 0, out_reg0, sink);
+                "#,
+                ),
+        );
+
+        let format = formats.get(f_store);
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VSTR{}", suffix), f_store, 4)
+                .operands_in(vec![fpr, gpr])
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 10, 2,
+                ))
+                .emit("put_vldst(bits, in_reg1, offset.into(), in_reg0, sink);"),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VSTRr{}", suffix), f_store, 16)
+                .operands_in(vec![fpr, gpr])
+                .emit(
+                    r#"
+                    let offset: i32 = offset.into();
+                    put_movw_movt(offset.into(), IP, sink);
+                    put_dp(AL, DP_ADD, in_reg1, IP, IP, sink);
+                    put_vldst(bits, IP, 0, in_reg0, sink);
+                "#,
+                ),
+        );
+
+        // Spills and fills compute the address in %r12, since the scaled 8-bit offset only
+        // reaches the first kilobyte of the frame.
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFPsp{}", suffix), f_unary, 12)
+                .operands_in(vec![fpr])
+                .operands_out(vec![Stack::new(fpr)])
+                .emit(
+                    r#"
+                    let base = stk_base(out_stk0.base);
+                    put_movw(out_stk0.offset.into(), IP, sink);
+                    put_dp(AL, DP_ADD, base, IP, IP, sink);
+                    put_vldst(bits, IP, 0, in_reg0, sink);
+                "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFPfi{}", suffix), f_unary, 12)
+                .operands_in(vec![Stack::new(fpr)])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                    let base = stk_base(in_stk0.base);
+                    put_movw(in_stk0.offset.into(), IP, sink);
+                    put_dp(AL, DP_ADD, base, IP, IP, sink);
+                    put_vldst(bits, IP, 0, out_reg0, sink);
+                "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFPrsp{}", suffix), f_reg_spill, 12)
+                .operands_in(vec![fpr])
+                .emit(
+                    r#"
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    let base = stk_base(dst.base);
+                    put_movw(dst.offset.into(), IP, sink);
+                    put_dp(AL, DP_ADD, base, IP, IP, sink);
+                    put_vldst(bits, IP, 0, src, sink);
+                "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VFPrfi{}", suffix), f_reg_fill, 12)
+                .operands_in(vec![Stack::new(fpr)])
+                .emit(
+                    r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    let base = stk_base(src.base);
+                    put_movw(src.offset.into(), IP, sink);
+                    put_dp(AL, DP_ADD, base, IP, IP, sink);
+                    put_vldst(bits, IP, 0, dst, sink);
+                "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("stacknull{}", suffix), f_unary, 0)
+                .operands_in(vec![Stack::new(fpr)])
+                .operands_out(vec![Stack::new(fpr)])
+                .emit(""),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("debugvalue{}", suffix), f_binary_imm, 0)
+                .operands_in(vec![fpr])
+                .emit(""),
+        );
+
+        // Conversions from a 32-bit integer go through %s14, which is never allocated.
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VCVTi{}", suffix), f_unary, 8)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .emit(
+                    r#"
+                    put_vmov_core(0, in_reg0, S14, sink);
+                    put_vfp_unary(bits, S14, out_reg0, sink);
+                "#,
+                ),
+        );
+
+        // Saturating conversions to a 32-bit integer, the rounding mode of `vcvt`.
+        recipes.push(
+            EncodingRecipeBuilder::new(format!("VCVT{}i", suffix), f_unary, 8)
+                .operands_in(vec![fpr])
+                .operands_out(vec![gpr])
+                .emit(
+                    r#"
+                    put_vfp_unary(bits, in_reg0, S14, sink);
+                    put_vmov_core(VMOV_TO_CORE, out_reg0, S14, sink);
+                "#,
+                ),
+        );
+    }
+
+    // Conversions between single and double precision.
+    recipes.push(
+        EncodingRecipeBuilder::new("VCVTsd", f_unary, 4)
+            .operands_in(vec![s])
+            .operands_out(vec![d])
+            .emit("put_vfp_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("VCVTds", f_unary, 4)
+            .operands_in(vec![d])
+            .operands_out(vec![s])
+            .emit("put_vfp_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Moves between a GPR and a single precision register, used for bitcasts.
+    recipes.push(
+        EncodingRecipeBuilder::new("VMOVsr", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![s])
+            .emit("put_vmov_core(0, in_reg0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("VMOVrs", f_unary, 4)
+            .operands_in(vec![s])
+            .operands_out(vec![gpr])
+            .emit("put_vmov_core(VMOV_TO_CORE, out_reg0, in_reg0, sink);"),
+    );
+
+    recipes
+}
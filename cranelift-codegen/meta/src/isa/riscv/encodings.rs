@@ -1,7 +1,8 @@
 use crate::cdsl::ast::{Apply, Expr, Literal, VarPool};
 use crate::cdsl::encodings::{Encoding, EncodingBuilder};
 use crate::cdsl::instructions::{
-    BoundInstruction, InstSpec, InstructionPredicateNode, InstructionPredicateRegistry,
+    BoundInstruction, InstSpec, InstructionPredicate, InstructionPredicateNode,
+    InstructionPredicateRegistry,
 };
 use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
 use crate::cdsl::settings::SettingGroup;
@@ -100,6 +101,54 @@ fn lui_bits() -> u16 {
     0b01101
 }
 
+/// A `fence` ordering the predecessor accesses `pred` before the successor accesses `succ`.
+/// Both are 4-bit `iorw` sets.
+fn fence_bits(pred: u16, succ: u16) -> u16 {
+    assert!(pred <= 0b1111 && succ <= 0b1111);
+    pred | (succ << 4)
+}
+
+/// An AMO instruction with both the `aq` and `rl` bits set.
+fn amo_bits(funct3: u16, funct5: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(funct5 <= 0b11111);
+    0b01011 | (funct3 << 5) | (funct5 << 10) | (0b11 << 8)
+}
+
+// Compressed instructions have the quadrant in the two low bits, and a function code above it.
+// Encbits for the 16-bit recipes are `op[1:0] | (funct << 2)`, where the meaning of the function
+// code depends on the format.
+
+/// CR-type: `funct` is the 4-bit funct4 field.
+fn cr_bits(funct4: u16) -> u16 {
+    assert!(funct4 <= 0b1111);
+    0b10 | (funct4 << 2)
+}
+
+/// CI-type in quadrant `op`: `funct` is the 3-bit funct3 field.
+fn ci_bits(op: u16, funct3: u16) -> u16 {
+    assert!(op <= 0b10 && funct3 <= 0b111);
+    op | (funct3 << 2)
+}
+
+/// CA-type: `funct` is `funct2 | (funct6 << 2)`.
+fn ca_bits(funct6: u16, funct2: u16) -> u16 {
+    assert!(funct6 <= 0b111111 && funct2 <= 0b11);
+    0b01 | (funct2 << 2) | (funct6 << 4)
+}
+
+/// CB-type arithmetic: `funct` is `funct3 | (funct2 << 3)`.
+fn cb_imm_bits(funct2: u16) -> u16 {
+    assert!(funct2 <= 0b11);
+    0b01 | (0b100 << 2) | (funct2 << 5)
+}
+
+/// CB-type branches and CJ-type jumps: `funct` is the 3-bit funct3 field.
+fn cb_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b01 | (funct3 << 2)
+}
+
 pub fn define<'defs>(
     shared_defs: &'defs SharedDefinitions,
     isa_settings: &SettingGroup,
@@ -119,8 +168,22 @@ pub fn define<'defs>(
     let bxor_imm = shared.by_name("bxor_imm");
     let call = shared.by_name("call");
     let call_indirect = shared.by_name("call_indirect");
+    let atomic_add = shared.by_name("atomic_add");
+    let atomic_and = shared.by_name("atomic_and");
+    let atomic_cas = shared.by_name("atomic_cas");
+    let atomic_load = shared.by_name("atomic_load");
+    let atomic_or = shared.by_name("atomic_or");
+    let atomic_smax = shared.by_name("atomic_smax");
+    let atomic_smin = shared.by_name("atomic_smin");
+    let atomic_store = shared.by_name("atomic_store");
+    let atomic_umax = shared.by_name("atomic_umax");
+    let atomic_umin = shared.by_name("atomic_umin");
+    let atomic_xchg = shared.by_name("atomic_xchg");
+    let atomic_xor = shared.by_name("atomic_xor");
     let copy = shared.by_name("copy");
     let copy_nop = shared.by_name("copy_nop");
+    let debug_value = shared.by_name("debug_value");
+    let fence = shared.by_name("fence");
     let fill = shared.by_name("fill");
     let iadd = shared.by_name("iadd");
     let iadd_imm = shared.by_name("iadd_imm");
@@ -141,8 +204,11 @@ pub fn define<'defs>(
     let return_ = shared.by_name("return");
 
     // Recipes shorthands, prefixed with r_.
+    let r_debugvalue = recipes.by_name("debugvalue");
+    let r_fence = recipes.by_name("fence");
     let r_icall = recipes.by_name("Icall");
     let r_icopy = recipes.by_name("Icopy");
+    let r_ifence = recipes.by_name("Ifence");
     let r_ii = recipes.by_name("Ii");
     let r_iicmp = recipes.by_name("Iicmp");
     let r_iret = recipes.by_name("Iret");
@@ -151,19 +217,36 @@ pub fn define<'defs>(
     let r_gp_sp = recipes.by_name("GPsp");
     let r_gp_fi = recipes.by_name("GPfi");
     let r_r = recipes.by_name("R");
+    let r_lr_sc = recipes.by_name("LRSC");
     let r_ricmp = recipes.by_name("Ricmp");
     let r_rshamt = recipes.by_name("Rshamt");
     let r_sb = recipes.by_name("SB");
     let r_sb_zero = recipes.by_name("SBzero");
+    let r_sfence = recipes.by_name("Sfence");
     let r_stacknull = recipes.by_name("stacknull");
     let r_u = recipes.by_name("U");
     let r_uj = recipes.by_name("UJ");
     let r_uj_call = recipes.by_name("UJcall");
+    let r_ca = recipes.by_name("CA");
+    let r_cb_imm = recipes.by_name("CBimm");
+    let r_cb_shamt = recipes.by_name("CBshamt");
+    let r_cb_zero = recipes.by_name("CBzero");
+    let r_ci = recipes.by_name("CI");
+    let r_ci_shamt = recipes.by_name("CIshamt");
+    let r_ci_z = recipes.by_name("CIz");
+    let r_cj = recipes.by_name("CJ");
+    let r_cr = recipes.by_name("CR");
+    let r_cr_call = recipes.by_name("CRcall");
+    let r_cr_copy = recipes.by_name("CRcopy");
+    let r_cr_ret = recipes.by_name("CRret");
 
     // Predicates shorthands.
     let use_m = isa_settings.predicate_by_name("use_m");
+    let use_a = isa_settings.predicate_by_name("use_a");
+    let use_c = isa_settings.predicate_by_name("use_c");
 
     // Definitions.
+    let formats = &shared_defs.format_registry;
     let mut e = PerCpuModeEncodings::new(&recipes.recipes);
 
     // Basic arithmetic binary instructions are encoded in an R-type instruction.
@@ -394,5 +477,141 @@ pub fn define<'defs>(
         e.add64(enc(copy_nop.bind(ty), r_stacknull, 0));
     }
 
+    // Debug value markers generate no code.
+    e.add32(enc(debug_value.bind(I32), r_debugvalue, 0));
+    e.add64(enc(debug_value.bind(I32), r_debugvalue, 0));
+    e.add64(enc(debug_value.bind(I64), r_debugvalue, 0));
+    e.add32(enc(debug_value.bind(B1), r_debugvalue, 0));
+    e.add64(enc(debug_value.bind(B1), r_debugvalue, 0));
+
+    // Memory fences are part of the base ISA.
+    e.add32(enc(fence, r_fence, fence_bits(0b0011, 0b0011)));
+    e.add64(enc(fence, r_fence, fence_bits(0b0011, 0b0011)));
+
+    // "A" Standard Extension for Atomic Instructions.
+    // Gated by the `use_a` flag.
+    //
+    // Aligned loads and stores are already atomic, so atomic loads and stores only need fences to
+    // be sequentially consistent.
+    for &(ty, f3) in &[(I8, 0b000), (I16, 0b001), (I32, 0b010)] {
+        e.add32(enc(atomic_load.bind(ty).bind_any(), r_ifence, load_bits(f3)).isa_predicate(use_a));
+        e.add64(enc(atomic_load.bind(ty).bind_any(), r_ifence, load_bits(f3)).isa_predicate(use_a));
+        e.add32(
+            enc(atomic_store.bind(ty).bind_any(), r_sfence, store_bits(f3)).isa_predicate(use_a),
+        );
+        e.add64(
+            enc(atomic_store.bind(ty).bind_any(), r_sfence, store_bits(f3)).isa_predicate(use_a),
+        );
+    }
+    e.add64(enc(atomic_load.bind(I64).bind_any(), r_ifence, load_bits(0b011)).isa_predicate(use_a));
+    e.add64(
+        enc(
+            atomic_store.bind(I64).bind_any(),
+            r_sfence,
+            store_bits(0b011),
+        )
+        .isa_predicate(use_a),
+    );
+
+    // The AMO instructions only exist for 32-bit and 64-bit values.
+    for &(inst, funct5) in &[
+        (atomic_xchg, 0b00001),
+        (atomic_add, 0b00000),
+        (atomic_xor, 0b00100),
+        (atomic_and, 0b01100),
+        (atomic_or, 0b01000),
+        (atomic_smin, 0b10000),
+        (atomic_smax, 0b10100),
+        (atomic_umin, 0b11000),
+        (atomic_umax, 0b11100),
+    ] {
+        e.add32(enc(inst.bind(I32).bind_any(), r_r, amo_bits(0b010, funct5)).isa_predicate(use_a));
+        e.add64(enc(inst.bind(I32).bind_any(), r_r, amo_bits(0b010, funct5)).isa_predicate(use_a));
+        e.add64(enc(inst.bind(I64).bind_any(), r_r, amo_bits(0b011, funct5)).isa_predicate(use_a));
+    }
+
+    // Compare-and-swap is a loop of `lr` and `sc`, encoded with the bits of the `lr`.
+    let lr_w = amo_bits(0b010, 0b00010);
+    let lr_d = amo_bits(0b011, 0b00010);
+    e.add32(enc(atomic_cas.bind(I32).bind_any(), r_lr_sc, lr_w).isa_predicate(use_a));
+    e.add64(enc(atomic_cas.bind(I32).bind_any(), r_lr_sc, lr_w).isa_predicate(use_a));
+    e.add64(enc(atomic_cas.bind(I64).bind_any(), r_lr_sc, lr_d).isa_predicate(use_a));
+
+    // "C" Standard Extension for Compressed Instructions.
+    // Gated by the `use_c` flag. These encodings must come after the 32-bit encodings of the same
+    // instructions, so they are only used by the shrink pass after register allocation.
+    //
+    // The register constraints can't express that %x0 is not allowed in some operands, but it is
+    // never allocated. An immediate of 0 in `c.addi` or the compressed shifts is a hint that
+    // doesn't modify any registers, which is also the right behavior for the tied operands.
+    e.add32(enc(iadd.bind(I32), r_cr, cr_bits(0b1001)).isa_predicate(use_c));
+    e.add64(enc(iadd.bind(I64), r_cr, cr_bits(0b1001)).isa_predicate(use_c));
+    for &(inst, funct2) in &[(isub, 0b00), (bxor, 0b01), (bor, 0b10), (band, 0b11)] {
+        e.add32(enc(inst.bind(I32), r_ca, ca_bits(0b100011, funct2)).isa_predicate(use_c));
+        e.add64(enc(inst.bind(I64), r_ca, ca_bits(0b100011, funct2)).isa_predicate(use_c));
+    }
+    // `c.subw` and `c.addw`.
+    e.add64(enc(isub.bind(I32), r_ca, ca_bits(0b100111, 0b00)).isa_predicate(use_c));
+    e.add64(enc(iadd.bind(I32), r_ca, ca_bits(0b100111, 0b01)).isa_predicate(use_c));
+
+    // `c.addi`, `c.addiw` and `c.li`.
+    e.add32(enc(iadd_imm.bind(I32), r_ci, ci_bits(0b01, 0b000)).isa_predicate(use_c));
+    e.add64(enc(iadd_imm.bind(I64), r_ci, ci_bits(0b01, 0b000)).isa_predicate(use_c));
+    e.add64(enc(iadd_imm.bind(I32), r_ci, ci_bits(0b01, 0b001)).isa_predicate(use_c));
+    e.add32(enc(iconst.bind(I32), r_ci_z, ci_bits(0b01, 0b010)).isa_predicate(use_c));
+    e.add64(enc(iconst.bind(I32), r_ci_z, ci_bits(0b01, 0b010)).isa_predicate(use_c));
+    e.add64(enc(iconst.bind(I64), r_ci_z, ci_bits(0b01, 0b010)).isa_predicate(use_c));
+
+    // Shift amounts must fit in 5 bits in RV32 and 6 bits in RV64.
+    let binary_imm = formats.get(formats.by_name("BinaryImm"));
+    let shamt5 = InstructionPredicate::new_is_unsigned_int(binary_imm, "imm", 5, 0);
+    let shamt6 = InstructionPredicate::new_is_unsigned_int(binary_imm, "imm", 6, 0);
+
+    // `c.andi`, `c.slli`, `c.srli` and `c.srai`. There are no compressed 32-bit shifts in RV64.
+    e.add32(enc(band_imm.bind(I32), r_cb_imm, cb_imm_bits(0b10)).isa_predicate(use_c));
+    e.add64(enc(band_imm.bind(I64), r_cb_imm, cb_imm_bits(0b10)).isa_predicate(use_c));
+    e.add32(
+        enc(ishl_imm.bind(I32), r_ci_shamt, ci_bits(0b10, 0b000))
+            .inst_predicate(shamt5.clone())
+            .isa_predicate(use_c),
+    );
+    e.add64(
+        enc(ishl_imm.bind(I64), r_ci_shamt, ci_bits(0b10, 0b000))
+            .inst_predicate(shamt6.clone())
+            .isa_predicate(use_c),
+    );
+    for &(inst, funct2) in &[(ushr_imm, 0b00), (sshr_imm, 0b01)] {
+        e.add32(
+            enc(inst.bind(I32), r_cb_shamt, cb_imm_bits(funct2))
+                .inst_predicate(shamt5.clone())
+                .isa_predicate(use_c),
+        );
+        e.add64(
+            enc(inst.bind(I64), r_cb_shamt, cb_imm_bits(funct2))
+                .inst_predicate(shamt6.clone())
+                .isa_predicate(use_c),
+        );
+    }
+
+    // `c.j`, `c.beqz` and `c.bnez`.
+    e.add32(enc(jump, r_cj, cb_bits(0b101)).isa_predicate(use_c));
+    e.add64(enc(jump, r_cj, cb_bits(0b101)).isa_predicate(use_c));
+    for &(inst, f3) in &[(brz, 0b110), (brnz, 0b111)] {
+        e.add32(enc(inst.bind(I32), r_cb_zero, cb_bits(f3)).isa_predicate(use_c));
+        e.add64(enc(inst.bind(I64), r_cb_zero, cb_bits(f3)).isa_predicate(use_c));
+        e.add32(enc(inst.bind(B1), r_cb_zero, cb_bits(f3)).isa_predicate(use_c));
+        e.add64(enc(inst.bind(B1), r_cb_zero, cb_bits(f3)).isa_predicate(use_c));
+    }
+
+    // `c.jr %x1`, `c.jalr` and `c.mv`.
+    e.add32(enc(return_, r_cr_ret, cr_bits(0b1000)).isa_predicate(use_c));
+    e.add64(enc(return_, r_cr_ret, cr_bits(0b1000)).isa_predicate(use_c));
+    e.add32(enc(call_indirect.bind(I32), r_cr_call, cr_bits(0b1001)).isa_predicate(use_c));
+    e.add64(enc(call_indirect.bind(I64), r_cr_call, cr_bits(0b1001)).isa_predicate(use_c));
+    e.add32(enc(copy.bind(I32), r_cr_copy, cr_bits(0b1000)).isa_predicate(use_c));
+    e.add64(enc(copy.bind(I64), r_cr_copy, cr_bits(0b1000)).isa_predicate(use_c));
+    e.add32(enc(copy.bind(B1), r_cr_copy, cr_bits(0b1000)).isa_predicate(use_c));
+    e.add64(enc(copy.bind(B1), r_cr_copy, cr_bits(0b1000)).isa_predicate(use_c));
+
     e
 }
@@ -34,6 +34,11 @@ fn define_settings(shared: &SettingGroup) -> SettingGroup {
         "CPU supports the 'D' extension (double)",
         false,
     );
+    let supports_c = setting.add_bool(
+        "supports_c",
+        "CPU supports the 'C' extension (compressed instructions)",
+        false,
+    );
 
     let enable_m = setting.add_bool(
         "enable_m",
@@ -55,6 +60,7 @@ fn define_settings(shared: &SettingGroup) -> SettingGroup {
     setting.add_predicate("use_a", predicate!(supports_a && shared_enable_atomics));
     setting.add_predicate("use_f", predicate!(supports_f && shared_enable_float));
     setting.add_predicate("use_d", predicate!(supports_d && shared_enable_float));
+    setting.add_predicate("use_c", predicate!(supports_c));
     setting.add_predicate(
         "full_float",
         predicate!(shared_enable_simd && supports_f && supports_d),
@@ -77,11 +83,15 @@ fn define_registers() -> IsaRegs {
     let float_regs = regs.add_bank(builder);
 
     let builder = RegClassBuilder::new_toplevel("GPR", int_regs);
-    regs.add_class(builder);
+    let gpr = regs.add_class(builder);
 
     let builder = RegClassBuilder::new_toplevel("FPR", float_regs);
     regs.add_class(builder);
 
+    // The registers %x8-%x15 that most compressed instructions can encode in 3 bits.
+    let builder = RegClassBuilder::subclass_of("GPRC", gpr, 8, 16);
+    regs.add_class(builder);
+
     regs.build()
 }
 
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 
 use crate::cdsl::formats::FormatRegistry;
 use crate::cdsl::instructions::InstructionPredicate;
-use crate::cdsl::recipes::{EncodingRecipeBuilder, EncodingRecipeNumber, Recipes, Stack};
+use crate::cdsl::recipes::{EncodingRecipeBuilder, EncodingRecipeNumber, Recipes, Register, Stack};
 use crate::cdsl::regs::IsaRegs;
 use crate::shared::Definitions as SharedDefinitions;
 
@@ -66,13 +66,24 @@ pub fn define<'formats>(
     let f_int_compare = formats.by_name("IntCompare");
     let f_int_compare_imm = formats.by_name("IntCompareImm");
     let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
     let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
     let f_regmove = formats.by_name("RegMove");
+    let f_store = formats.by_name("Store");
+    let f_ternary = formats.by_name("Ternary");
     let f_unary = formats.by_name("Unary");
     let f_unary_imm = formats.by_name("UnaryImm");
 
     // Register classes shorthands.
     let gpr = regs.class_by_name("GPR");
+    let gprc = regs.class_by_name("GPRC");
+
+    // Fixed registers used by the compare-and-swap loop.
+    let reg_x10 = Register::new(gpr, regs.regunit_by_name(gpr, "x10"));
+    let reg_x11 = Register::new(gpr, regs.regunit_by_name(gpr, "x11"));
+    let reg_x12 = Register::new(gpr, regs.regunit_by_name(gpr, "x12"));
+    let reg_x13 = Register::new(gpr, regs.regunit_by_name(gpr, "x13"));
 
     // Definitions.
     let mut recipes = RecipeGroup::new(&shared_defs.format_registry);
@@ -263,6 +274,176 @@ pub fn define<'formats>(
             .emit("unimplemented!();"),
     );
 
+    // Atomic memory accesses from the 'A' extension, and the fences required to make them
+    // sequentially consistent. The fences follow the mapping recommended by the ISA manual:
+    //
+    //   atomic load:  fence rw,rw; l{b|h|w|d}; fence r,rw
+    //   atomic store: fence rw,w; s{b|h|w|d}
+    //
+    // The read-modify-write instructions use the `aq` and `rl` bits instead of fences.
+
+    // A `fence` instruction. The encbits are `pred | (succ << 4)`.
+    recipes.push(EncodingRecipeBuilder::new("fence", f_nullary, 4).emit("put_fence(bits, sink);"));
+
+    // I-type load surrounded by fences.
+    let format = formats.get(f_load);
+    recipes.push(
+        EncodingRecipeBuilder::new("Ifence", f_load, 12)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit(
+                r#"
+                    put_fence(FENCE_RW | (FENCE_RW << 4), sink);
+                    put_i(bits, in_reg0, offset.into(), out_reg0, sink);
+                    put_fence(FENCE_R | (FENCE_RW << 4), sink);
+                "#,
+            ),
+    );
+
+    // S-type store preceded by a fence.
+    let format = formats.get(f_store);
+    recipes.push(
+        EncodingRecipeBuilder::new("Sfence", f_store, 8)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit(
+                r#"
+                    put_fence(FENCE_RW | (FENCE_W << 4), sink);
+                    put_s(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Compare-and-swap loop using `lr` and `sc`. The result is written before all the inputs are
+    // read for the last time, so fixed registers keep them apart.
+    recipes.push(
+        EncodingRecipeBuilder::new("LRSC", f_ternary, 20)
+            .operands_in(vec![reg_x10, reg_x11, reg_x12])
+            .operands_out(vec![reg_x13])
+            .emit(
+                r#"
+                    // p = %x10, e = %x11, x = %x12, a = %x13.
+                    put_lr_sc(bits, 10, 11, 12, 13, sink);
+                "#,
+            ),
+    );
+
+    // Compressed 16-bit instructions from the 'C' extension. They are never chosen by the
+    // legalizer, but the register constraints let the shrink pass pick them after register
+    // allocation. The encbits are `op[1:0] | (funct << 2)`, see the `c*_bits()` helpers.
+
+    // CR-type binary instructions with the destination tied to the first operand.
+    recipes.push(
+        EncodingRecipeBuilder::new("CR", f_binary, 2)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![0])
+            .emit("put_cr(bits, in_reg0, in_reg1, sink);"),
+    );
+
+    // CR-type register copy, `c.mv`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRcopy", f_unary, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit("put_cr(bits, out_reg0, in_reg0, sink);"),
+    );
+
+    // CR-type `c.jr %x1` return.
+    recipes
+        .push(EncodingRecipeBuilder::new("CRret", f_multiary, 2).emit("put_cr(bits, 1, 0, sink);"));
+
+    // CR-type `c.jalr` indirect call, which writes the return address to %x1.
+    recipes.push(
+        EncodingRecipeBuilder::new("CRcall", f_call_indirect, 2)
+            .operands_in(vec![gpr])
+            .emit("put_cr(bits, in_reg0, 0, sink);"),
+    );
+
+    // CA-type binary instructions on the compressed registers.
+    recipes.push(
+        EncodingRecipeBuilder::new("CA", f_binary, 2)
+            .operands_in(vec![gprc, gprc])
+            .operands_out(vec![0])
+            .emit("put_ca(bits, in_reg0, in_reg1, sink);"),
+    );
+
+    // CI-type instructions with a 6-bit signed immediate, like `c.addi`.
+    let format = formats.get(f_binary_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("CI", f_binary_imm, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(format, "imm", 6, 0))
+            .emit("put_ci(bits, in_reg0, imm.into(), sink);"),
+    );
+
+    // CI-type shift by an immediate amount, `c.slli`. The ISA predicate limits the amount.
+    recipes.push(
+        EncodingRecipeBuilder::new("CIshamt", f_binary_imm, 2)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .emit("put_ci(bits, in_reg0, imm.into(), sink);"),
+    );
+
+    // CI-type constant, `c.li`.
+    let format = formats.get(f_unary_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("CIz", f_unary_imm, 2)
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(format, "imm", 6, 0))
+            .emit("put_ci(bits, out_reg0, imm.into(), sink);"),
+    );
+
+    // CB-type `c.andi` with a 6-bit signed immediate.
+    let format = formats.get(f_binary_imm);
+    recipes.push(
+        EncodingRecipeBuilder::new("CBimm", f_binary_imm, 2)
+            .operands_in(vec![gprc])
+            .operands_out(vec![0])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(format, "imm", 6, 0))
+            .emit("put_cb_imm(bits, in_reg0, imm.into(), sink);"),
+    );
+
+    // CB-type shifts by an immediate amount, `c.srli` and `c.srai`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBshamt", f_binary_imm, 2)
+            .operands_in(vec![gprc])
+            .operands_out(vec![0])
+            .emit("put_cb_imm(bits, in_reg0, imm.into(), sink);"),
+    );
+
+    // CJ-type unconditional branch, `c.j`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CJ", f_jump, 2)
+            .branch_range((0, 12))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cj(bits, disp, sink);
+                "#,
+            ),
+    );
+
+    // CB-type branches comparing a compressed register to zero, `c.beqz` and `c.bnez`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBzero", f_branch, 2)
+            .operands_in(vec![gprc])
+            .branch_range((0, 9))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cb(bits, disp, in_reg0, sink);
+                "#,
+            ),
+    );
+
     // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
     recipes.push(
         EncodingRecipeBuilder::new("stacknull", f_unary, 0)
@@ -271,5 +452,12 @@ pub fn define<'formats>(
             .emit(""),
     );
 
+    // Debug value marker, which only keeps its operand alive in a register.
+    recipes.push(
+        EncodingRecipeBuilder::new("debugvalue", f_binary_imm, 0)
+            .operands_in(vec![gpr])
+            .emit(""),
+    );
+
     recipes
 }
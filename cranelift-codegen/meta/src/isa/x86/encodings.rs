@@ -2,6 +2,7 @@
 
 use std::collections::HashMap;
 
+use crate::cdsl::ast::Literal;
 use crate::cdsl::encodings::{Encoding, EncodingBuilder};
 use crate::cdsl::instructions::{
     BoundInstruction, InstSpec, Instruction, InstructionGroup, InstructionPredicate,
@@ -9,8 +10,9 @@ use crate::cdsl::instructions::{
 };
 use crate::cdsl::recipes::{EncodingRecipe, EncodingRecipeNumber, Recipes};
 use crate::cdsl::settings::{SettingGroup, SettingPredicateNumber};
+use crate::cdsl::types::LaneType;
 
-use crate::shared::types::Bool::B1;
+use crate::shared::types::Bool::{B1, B16, B32, B64, B8};
 use crate::shared::types::Float::{F32, F64};
 use crate::shared::types::Int::{I16, I32, I64, I8};
 use crate::shared::Definitions as SharedDefinitions;
@@ -230,6 +232,24 @@ impl PerCpuModeEncodings {
         self.enc32_instp(inst.clone(), template.clone(), instp.clone());
         self.enc_x86_64_instp(inst, template, instp);
     }
+    fn enc_both_instp_isap(
+        &mut self,
+        inst: BoundInstruction,
+        template: Template,
+        instp: InstructionPredicateNode,
+        isap: SettingPredicateNumber,
+    ) {
+        self.enc32_func(inst.clone(), template.clone(), |builder| {
+            builder.inst_predicate(instp.clone()).isa_predicate(isap)
+        });
+        // See above comment about the ordering of rex vs non-rex encodings.
+        self.enc64_func(inst.clone(), template.rex(), |builder| {
+            builder.inst_predicate(instp.clone()).isa_predicate(isap)
+        });
+        self.enc64_func(inst, template, |builder| {
+            builder.inst_predicate(instp).isa_predicate(isap)
+        });
+    }
 
     /// Add encodings for `inst.i32` to X86_32.
     /// Add encodings for `inst.i32` to X86_64 with and without REX.
@@ -290,9 +310,12 @@ pub fn define(
     let copy_nop = shared.by_name("copy_nop");
     let copy_special = shared.by_name("copy_special");
     let ctz = shared.by_name("ctz");
+    let debug_value = shared.by_name("debug_value");
     let debugtrap = shared.by_name("debugtrap");
     let f32const = shared.by_name("f32const");
+    let f32splat = shared.by_name("f32splat");
     let f64const = shared.by_name("f64const");
+    let f64splat = shared.by_name("f64splat");
     let fadd = shared.by_name("fadd");
     let fcmp = shared.by_name("fcmp");
     let fcvt_from_sint = shared.by_name("fcvt_from_sint");
@@ -301,19 +324,24 @@ pub fn define(
     let ffcmp = shared.by_name("ffcmp");
     let fill = shared.by_name("fill");
     let floor = shared.by_name("floor");
+    let fma = shared.by_name("fma");
     let fmul = shared.by_name("fmul");
     let fpromote = shared.by_name("fpromote");
     let fsub = shared.by_name("fsub");
     let func_addr = shared.by_name("func_addr");
     let iadd = shared.by_name("iadd");
     let iadd_imm = shared.by_name("iadd_imm");
+    let iadd_overflow = shared.by_name("iadd_overflow");
     let icmp = shared.by_name("icmp");
     let icmp_imm = shared.by_name("icmp_imm");
     let iconst = shared.by_name("iconst");
     let ifcmp = shared.by_name("ifcmp");
     let ifcmp_imm = shared.by_name("ifcmp_imm");
     let ifcmp_sp = shared.by_name("ifcmp_sp");
+    let imax = shared.by_name("imax");
+    let imin = shared.by_name("imin");
     let imul = shared.by_name("imul");
+    let imul_overflow = shared.by_name("imul_overflow");
     let indirect_jump_table_br = shared.by_name("indirect_jump_table_br");
     let ireduce = shared.by_name("ireduce");
     let ishl = shared.by_name("ishl");
@@ -325,6 +353,7 @@ pub fn define(
     let istore8 = shared.by_name("istore8");
     let istore8_complex = shared.by_name("istore8_complex");
     let isub = shared.by_name("isub");
+    let isub_overflow = shared.by_name("isub_overflow");
     let jump = shared.by_name("jump");
     let jump_table_base = shared.by_name("jump_table_base");
     let jump_table_entry = shared.by_name("jump_table_entry");
@@ -363,6 +392,8 @@ pub fn define(
     let trueif = shared.by_name("trueif");
     let trunc = shared.by_name("trunc");
     let uextend = shared.by_name("uextend");
+    let umax = shared.by_name("umax");
+    let umin = shared.by_name("umin");
     let uload16 = shared.by_name("uload16");
     let uload16_complex = shared.by_name("uload16_complex");
     let uload32 = shared.by_name("uload32");
@@ -371,11 +402,20 @@ pub fn define(
     let uload8_complex = shared.by_name("uload8_complex");
     let ushr = shared.by_name("ushr");
     let ushr_imm = shared.by_name("ushr_imm");
+    let vselect = shared.by_name("vselect");
+    let x86_band_load = x86.by_name("x86_band_load");
+    let x86_blsr = x86.by_name("x86_blsr");
     let x86_bsf = x86.by_name("x86_bsf");
     let x86_bsr = x86.by_name("x86_bsr");
     let x86_cvtt2si = x86.by_name("x86_cvtt2si");
+    let x86_elf_tls_get_addr = x86.by_name("x86_elf_tls_get_addr");
+    let x86_elf_tls_ie_addr = x86.by_name("x86_elf_tls_ie_addr");
+    let x86_elf_tls_le_addr = x86.by_name("x86_elf_tls_le_addr");
     let x86_fmax = x86.by_name("x86_fmax");
     let x86_fmin = x86.by_name("x86_fmin");
+    let x86_iadd_load = x86.by_name("x86_iadd_load");
+    let x86_ifcmp_load = x86.by_name("x86_ifcmp_load");
+    let x86_macho_tls_get_addr = x86.by_name("x86_macho_tls_get_addr");
     let x86_pop = x86.by_name("x86_pop");
     let x86_push = x86.by_name("x86_push");
     let x86_sdivmodx = x86.by_name("x86_sdivmodx");
@@ -389,6 +429,7 @@ pub fn define(
     let rec_adjustsp_id = r.template("adjustsp_id");
     let rec_allones_fnaddr4 = r.template("allones_fnaddr4");
     let rec_allones_fnaddr8 = r.template("allones_fnaddr8");
+    let rec_blend = r.template("blend");
     let rec_brfb = r.template("brfb");
     let rec_brfd = r.template("brfd");
     let rec_brib = r.template("brib");
@@ -400,13 +441,20 @@ pub fn define(
     let rec_cmov = r.template("cmov");
     let rec_copysp = r.template("copysp");
     let rec_div = r.template("div");
+    let rec_elf_tls_get_addr = r.recipe("elf_tls_get_addr");
+    let rec_elf_tls_ie_addr = r.recipe("elf_tls_ie_addr");
+    let rec_elf_tls_le_addr = r.recipe("elf_tls_le_addr");
     let rec_debugtrap = r.recipe("debugtrap");
+    let rec_debugvalue = r.recipe("debugvalue");
+    let rec_f32imm_o = r.template("f32imm_o");
     let rec_f32imm_z = r.template("f32imm_z");
+    let rec_f64imm_o = r.template("f64imm_o");
     let rec_f64imm_z = r.template("f64imm_z");
     let rec_fa = r.template("fa");
     let rec_fax = r.template("fax");
     let rec_fcmp = r.template("fcmp");
     let rec_fcscc = r.template("fcscc");
+    let rec_fdebugvalue = r.recipe("fdebugvalue");
     let rec_ffillSib32 = r.template("ffillSib32");
     let rec_fillSib32 = r.template("fillSib32");
     let rec_fld = r.template("fld");
@@ -415,6 +463,7 @@ pub fn define(
     let rec_fldWithIndex = r.template("fldWithIndex");
     let rec_fldWithIndexDisp32 = r.template("fldWithIndexDisp32");
     let rec_fldWithIndexDisp8 = r.template("fldWithIndexDisp8");
+    let rec_fma = r.template("fma");
     let rec_fnaddr4 = r.template("fnaddr4");
     let rec_fnaddr8 = r.template("fnaddr8");
     let rec_fregfill32 = r.template("fregfill32");
@@ -443,11 +492,18 @@ pub fn define(
     let rec_jt_base = r.template("jt_base");
     let rec_jt_entry = r.template("jt_entry");
     let rec_ld = r.template("ld");
+    let rec_ldcmp = r.template("ldcmp");
+    let rec_ldcmpDisp32 = r.template("ldcmpDisp32");
+    let rec_ldcmpDisp8 = r.template("ldcmpDisp8");
     let rec_ldDisp32 = r.template("ldDisp32");
     let rec_ldDisp8 = r.template("ldDisp8");
     let rec_ldWithIndex = r.template("ldWithIndex");
     let rec_ldWithIndexDisp32 = r.template("ldWithIndexDisp32");
     let rec_ldWithIndexDisp8 = r.template("ldWithIndexDisp8");
+    let rec_ldop = r.template("ldop");
+    let rec_ldopDisp32 = r.template("ldopDisp32");
+    let rec_ldopDisp8 = r.template("ldopDisp8");
+    let rec_macho_tls_get_addr = r.recipe("macho_tls_get_addr");
     let rec_mulx = r.template("mulx");
     let rec_null = r.recipe("null");
     let rec_pcrel_fnaddr8 = r.template("pcrel_fnaddr8");
@@ -472,6 +528,9 @@ pub fn define(
     let rec_rmov = r.template("rmov");
     let rec_rr = r.template("rr");
     let rec_rrx = r.template("rrx");
+    let rec_rrxseto = r.template("rrxseto");
+    let rec_rseto = r.template("rseto");
+    let rec_rvm = r.template("rvm");
     let rec_setf_abcd = r.template("setf_abcd");
     let rec_seti_abcd = r.template("seti_abcd");
     let rec_spaddr4_id = r.template("spaddr4_id");
@@ -504,6 +563,11 @@ pub fn define(
     let rec_urm = r.template("urm");
     let rec_urm_noflags = r.template("urm_noflags");
     let rec_urm_noflags_abcd = r.template("urm_noflags_abcd");
+    let rec_vfcmp = r.template("vfcmp");
+    let rec_vfcmpx = r.template("vfcmpx");
+    let rec_vicmp = r.template("vicmp");
+    let rec_vicmpx = r.template("vicmpx");
+    let rec_vm = r.template("vm");
 
     // Predicates shorthands.
     let all_ones_funcaddrs_and_not_is_pic =
@@ -512,10 +576,13 @@ pub fn define(
     let not_all_ones_funcaddrs_and_not_is_pic =
         settings.predicate_by_name("not_all_ones_funcaddrs_and_not_is_pic");
     let not_is_pic = settings.predicate_by_name("not_is_pic");
+    let not_far_calls = settings.predicate_by_name("not_far_calls");
     let use_popcnt = settings.predicate_by_name("use_popcnt");
     let use_lzcnt = settings.predicate_by_name("use_lzcnt");
     let use_bmi1 = settings.predicate_by_name("use_bmi1");
     let use_sse41 = settings.predicate_by_name("use_sse41");
+    let use_sse42 = settings.predicate_by_name("use_sse42");
+    let use_fma = settings.predicate_by_name("use_fma");
 
     // Definitions.
     let mut e = PerCpuModeEncodings::new();
@@ -526,6 +593,11 @@ pub fn define(
     e.enc_i32_i64(bor, rec_rr.opcodes(vec![0x09]));
     e.enc_i32_i64(bxor, rec_rr.opcodes(vec![0x31]));
 
+    // Arithmetic with signed overflow detection: `add`, `sub`, and `imul` followed by `seto`.
+    e.enc_i32_i64(iadd_overflow, rec_rseto.opcodes(vec![0x01]));
+    e.enc_i32_i64(isub_overflow, rec_rseto.opcodes(vec![0x29]));
+    e.enc_i32_i64(imul_overflow, rec_rrxseto.opcodes(vec![0x0f, 0xaf]));
+
     // x86 has a bitwise not instruction NOT.
     e.enc_i32_i64(bnot, rec_ur.opcodes(vec![0xf7]).rrr(2));
 
@@ -707,6 +779,40 @@ pub fn define(
         use_bmi1,
     );
 
+    // And not: `andn` computes `~vvvv & r/m`, so the negated operand goes in VEX.vvvv.
+    e.enc32_isap(
+        band_not.bind(I32),
+        rec_rvm.opcodes(vec![0x0f, 0x38, 0xf2]).vex(),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        band_not.bind(I64),
+        rec_rvm.opcodes(vec![0x0f, 0x38, 0xf2]).vex().w(),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        band_not.bind(I32),
+        rec_rvm.opcodes(vec![0x0f, 0x38, 0xf2]).vex(),
+        use_bmi1,
+    );
+
+    // Reset lowest set bit.
+    e.enc32_isap(
+        x86_blsr.bind(I32),
+        rec_vm.opcodes(vec![0x0f, 0x38, 0xf3]).rrr(1).vex(),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        x86_blsr.bind(I64),
+        rec_vm.opcodes(vec![0x0f, 0x38, 0xf3]).rrr(1).vex().w(),
+        use_bmi1,
+    );
+    e.enc64_isap(
+        x86_blsr.bind(I32),
+        rec_vm.opcodes(vec![0x0f, 0x38, 0xf3]).rrr(1).vex(),
+        use_bmi1,
+    );
+
     // Loads and stores.
     let f_load_complex = formats.get(formats.by_name("LoadComplex"));
     let is_load_complex_length_two = InstructionPredicate::new_length_equals(f_load_complex, 2);
@@ -835,6 +941,15 @@ pub fn define(
         e.enc_i32_i64_ld_st(sload8, true, recipe.opcodes(vec![0x0f, 0xbe]));
     }
 
+    // ALU operations and comparisons with a memory operand.
+    for recipe in &[rec_ldop, rec_ldopDisp8, rec_ldopDisp32] {
+        e.enc_i32_i64_ld_st(x86_iadd_load, true, recipe.opcodes(vec![0x03]));
+        e.enc_i32_i64_ld_st(x86_band_load, true, recipe.opcodes(vec![0x23]));
+    }
+    for recipe in &[rec_ldcmp, rec_ldcmpDisp8, rec_ldcmpDisp32] {
+        e.enc_i32_i64_ld_st(x86_ifcmp_load, true, recipe.opcodes(vec![0x3b]));
+    }
+
     e.enc_i32_i64(fill, rec_fillSib32.opcodes(vec![0x8b]));
     e.enc_i32_i64(regfill, rec_regfill32.opcodes(vec![0x8b]));
 
@@ -872,6 +987,37 @@ pub fn define(
         e.enc32_rec(copy_nop.bind(ty), rec_stacknull, 0);
     }
 
+    // Debug value markers generate no code. The 64-bit types are split by legalization on x86-32.
+    for &ty in &[I32, I16, I8] {
+        e.enc64_rec(debug_value.bind(ty), rec_debugvalue, 0);
+        e.enc32_rec(debug_value.bind(ty), rec_debugvalue, 0);
+    }
+    for &ty in &[B32, B16, B8, B1] {
+        e.enc64_rec(debug_value.bind(ty), rec_debugvalue, 0);
+        e.enc32_rec(debug_value.bind(ty), rec_debugvalue, 0);
+    }
+    e.enc64_rec(debug_value.bind(I64), rec_debugvalue, 0);
+    e.enc64_rec(debug_value.bind(B64), rec_debugvalue, 0);
+    for &ty in &[F64, F32] {
+        e.enc64_rec(debug_value.bind(ty), rec_fdebugvalue, 0);
+        e.enc32_rec(debug_value.bind(ty), rec_fdebugvalue, 0);
+    }
+    for &(lane_ty, lanes) in &[
+        (LaneType::from(I8), 16),
+        (LaneType::from(I16), 8),
+        (LaneType::from(I32), 4),
+        (LaneType::from(I64), 2),
+        (LaneType::from(F32), 4),
+        (LaneType::from(F64), 2),
+        (LaneType::from(B8), 16),
+        (LaneType::from(B16), 8),
+        (LaneType::from(B32), 4),
+        (LaneType::from(B64), 2),
+    ] {
+        e.enc64_rec(debug_value.bind_vector(lane_ty, lanes), rec_fdebugvalue, 0);
+        e.enc32_rec(debug_value.bind_vector(lane_ty, lanes), rec_fdebugvalue, 0);
+    }
+
     // Adjust SP down by a dynamic value (or up, with a negative operand).
     e.enc32(adjust_sp_down.bind(I32), rec_adjustsp.opcodes(vec![0x29]));
     e.enc64(
@@ -1074,13 +1220,18 @@ pub fn define(
         all_ones_funcaddrs_and_not_is_pic,
     );
 
-    // 64-bit, colocated, both PIC and non-PIC. Use the lea instruction's pc-relative field.
+    // 64-bit, colocated, both PIC and non-PIC. Use the lea instruction's pc-relative field,
+    // unless colocated functions may be out of its range.
     let f_func_addr = formats.get(formats.by_name("FuncAddr"));
     let is_colocated_func = InstructionPredicate::new_is_colocated_func(f_func_addr, "func_ref");
-    e.enc64_instp(
+    e.enc64_func(
         func_addr.bind(I64),
         rec_pcrel_fnaddr8.opcodes(vec![0x8d]).rex().w(),
-        is_colocated_func,
+        |encoding| {
+            encoding
+                .isa_predicate(not_far_calls)
+                .inst_predicate(is_colocated_func)
+        },
     );
 
     // 64-bit, non-colocated, PIC.
@@ -1122,6 +1273,13 @@ pub fn define(
         is_pic,
     );
 
+    // Thread-local storage addresses. These are only available in 64-bit mode; the `tls_value`
+    // legalization selects one of them according to the `tls_model` setting.
+    e.enc64_rec(x86_elf_tls_get_addr, rec_elf_tls_get_addr, 0);
+    e.enc64_rec(x86_elf_tls_ie_addr, rec_elf_tls_ie_addr, 0);
+    e.enc64_rec(x86_elf_tls_le_addr, rec_elf_tls_le_addr, 0);
+    e.enc64_rec(x86_macho_tls_get_addr, rec_macho_tls_get_addr, 0);
+
     // Stack addresses.
     //
     // TODO: Add encoding rules for stack_load and stack_store, so that they
@@ -1137,10 +1295,16 @@ pub fn define(
     // 32-bit, both PIC and non-PIC.
     e.enc32(call, rec_call_id.opcodes(vec![0xe8]));
 
-    // 64-bit, colocated, both PIC and non-PIC. Use the call instruction's pc-relative field.
+    // 64-bit, colocated, both PIC and non-PIC. Use the call instruction's pc-relative field,
+    // unless colocated functions may be out of its range. Far calls are lowered to
+    // func_addr+call_indirect, or use the PLT in PIC mode.
     let f_call = formats.get(formats.by_name("Call"));
     let is_colocated_func = InstructionPredicate::new_is_colocated_func(f_call, "func_ref");
-    e.enc64_instp(call, rec_call_id.opcodes(vec![0xe8]), is_colocated_func);
+    e.enc64_func(call, rec_call_id.opcodes(vec![0xe8]), |encoding| {
+        encoding
+            .isa_predicate(not_far_calls)
+            .inst_predicate(is_colocated_func)
+    });
 
     // 64-bit, non-colocated, PIC. There is no 64-bit non-colocated non-PIC version, since non-PIC
     // is currently using the large model, which requires calls be lowered to
@@ -1429,13 +1593,62 @@ pub fn define(
     e.enc_x86_64_instp(
         f32const,
         rec_f32imm_z.opcodes(vec![0x0f, 0x57]),
-        is_zero_32_bit_float,
+        is_zero_32_bit_float.clone(),
     );
     e.enc_x86_64_instp(
         f64const,
         rec_f64imm_z.opcodes(vec![0x66, 0x0f, 0x57]),
+        is_zero_64_bit_float.clone(),
+    );
+
+    // Splatted vector constants use the same `xorps` and `xorpd` idioms when all the lanes are
+    // 0.0, and `pcmpeqd` of a register with itself when all the bits are set. Other vector
+    // constants are legalized as vectors with fewer lanes.
+    let is_all_ones_32_bit_float =
+        InstructionPredicate::new_is_all_ones_32bit_float(f_unary_ieee32, "imm");
+    let is_all_ones_64_bit_float =
+        InstructionPredicate::new_is_all_ones_64bit_float(f_unary_ieee64, "imm");
+    e.enc32_instp(
+        f32splat.bind_vector(F32, 4),
+        rec_f32imm_z.opcodes(vec![0x0f, 0x57]),
+        is_zero_32_bit_float.clone(),
+    );
+    e.enc32_instp(
+        f64splat.bind_vector(F64, 2),
+        rec_f64imm_z.opcodes(vec![0x66, 0x0f, 0x57]),
+        is_zero_64_bit_float.clone(),
+    );
+    e.enc32_instp(
+        f32splat.bind_vector(F32, 4),
+        rec_f32imm_o.opcodes(vec![0x66, 0x0f, 0x76]),
+        is_all_ones_32_bit_float.clone(),
+    );
+    e.enc32_instp(
+        f64splat.bind_vector(F64, 2),
+        rec_f64imm_o.opcodes(vec![0x66, 0x0f, 0x76]),
+        is_all_ones_64_bit_float.clone(),
+    );
+
+    e.enc_x86_64_instp(
+        f32splat.bind_vector(F32, 4),
+        rec_f32imm_z.opcodes(vec![0x0f, 0x57]),
+        is_zero_32_bit_float,
+    );
+    e.enc_x86_64_instp(
+        f64splat.bind_vector(F64, 2),
+        rec_f64imm_z.opcodes(vec![0x66, 0x0f, 0x57]),
         is_zero_64_bit_float,
     );
+    e.enc_x86_64_instp(
+        f32splat.bind_vector(F32, 4),
+        rec_f32imm_o.opcodes(vec![0x66, 0x0f, 0x76]),
+        is_all_ones_32_bit_float,
+    );
+    e.enc_x86_64_instp(
+        f64splat.bind_vector(F64, 2),
+        rec_f64imm_o.opcodes(vec![0x66, 0x0f, 0x76]),
+        is_all_ones_64_bit_float,
+    );
 
     // movd
     e.enc_both(
@@ -1533,6 +1746,45 @@ pub fn define(
         );
     }
 
+    // Lane selects: `pblendvb` picks the bytes of `x` where the high bit of the corresponding mask
+    // byte in %xmm0 is set, which works for any lane size since true boolean lanes are all ones.
+    for &(lane_ty, lanes) in &[
+        (LaneType::from(I8), 16),
+        (LaneType::from(I16), 8),
+        (LaneType::from(I32), 4),
+        (LaneType::from(I64), 2),
+        (LaneType::from(F32), 4),
+        (LaneType::from(F64), 2),
+    ] {
+        e.enc_both_isap(
+            vselect.bind_vector(lane_ty, lanes),
+            rec_blend.opcodes(vec![0x66, 0x0f, 0x38, 0x10]),
+            use_sse41,
+        );
+    }
+
+    // Fused multiply-add: `vfmadd213ss/sd x, y, z` computes `x := y*x + z`.
+    e.enc32_isap(
+        fma.bind(F32),
+        rec_fma.opcodes(vec![0x66, 0x0f, 0x38, 0xa9]).vex(),
+        use_fma,
+    );
+    e.enc32_isap(
+        fma.bind(F64),
+        rec_fma.opcodes(vec![0x66, 0x0f, 0x38, 0xa9]).vex().w(),
+        use_fma,
+    );
+    e.enc64_isap(
+        fma.bind(F32),
+        rec_fma.opcodes(vec![0x66, 0x0f, 0x38, 0xa9]).vex(),
+        use_fma,
+    );
+    e.enc64_isap(
+        fma.bind(F64),
+        rec_fma.opcodes(vec![0x66, 0x0f, 0x38, 0xa9]).vex().w(),
+        use_fma,
+    );
+
     // Binary arithmetic ops.
     for &(inst, opc) in &[
         (fadd, 0x58),
@@ -1565,5 +1817,95 @@ pub fn define(
     e.enc_both(ffcmp.bind(F32), rec_fcmp.opcodes(vec![0x0f, 0x2e]));
     e.enc_both(ffcmp.bind(F64), rec_fcmp.opcodes(vec![0x66, 0x0f, 0x2e]));
 
+    // Lane-wise vector comparisons.
+    //
+    // There are only `eq` and `sgt` integer comparisons, and `slt` is `sgt` with the operands
+    // swapped. Vector comparisons without an encoding are split into comparisons of the lanes.
+    let f_int_compare = formats.get(formats.by_name("IntCompare"));
+    let intcc = shared_defs.operand_kinds.by_name("intcc");
+    let is_cond = |name: &'static str| {
+        InstructionPredicate::new_is_field_equal(
+            f_int_compare,
+            "cond",
+            Literal::enumerator_for(intcc, name).to_rust_code(),
+        )
+    };
+    for &(lane_ty, lanes, eq_op, gt_op) in &[
+        (I8, 16, 0x74, 0x64),
+        (I16, 8, 0x75, 0x65),
+        (I32, 4, 0x76, 0x66),
+    ] {
+        let inst = icmp.bind_vector(lane_ty, lanes);
+        let pcmpeq = rec_vicmp.opcodes(vec![0x66, 0x0f, eq_op]);
+        e.enc_both_instp(inst.clone(), pcmpeq, is_cond("eq"));
+        let pcmpgt = rec_vicmp.opcodes(vec![0x66, 0x0f, gt_op]);
+        e.enc_both_instp(inst.clone(), pcmpgt, is_cond("sgt"));
+        let pcmpgt = rec_vicmpx.opcodes(vec![0x66, 0x0f, gt_op]);
+        e.enc_both_instp(inst, pcmpgt, is_cond("slt"));
+    }
+
+    // `pcmpeqq` is in SSE 4.1 and `pcmpgtq` in SSE 4.2.
+    let inst = icmp.bind_vector(I64, 2);
+    let pcmpeqq = rec_vicmp.opcodes(vec![0x66, 0x0f, 0x38, 0x29]);
+    e.enc_both_instp_isap(inst.clone(), pcmpeqq, is_cond("eq"), use_sse41);
+    let pcmpgtq = rec_vicmp.opcodes(vec![0x66, 0x0f, 0x38, 0x37]);
+    e.enc_both_instp_isap(inst.clone(), pcmpgtq, is_cond("sgt"), use_sse42);
+    let pcmpgtq = rec_vicmpx.opcodes(vec![0x66, 0x0f, 0x38, 0x37]);
+    e.enc_both_instp_isap(inst, pcmpgtq, is_cond("slt"), use_sse42);
+
+    // The floating point comparisons cover the condition codes in `supported_vector_floatccs`
+    // and their reverses.
+    e.enc_both(
+        fcmp.bind_vector(F32, 4),
+        rec_vfcmp.opcodes(vec![0x0f, 0xc2]),
+    );
+    e.enc_both(
+        fcmp.bind_vector(F32, 4),
+        rec_vfcmpx.opcodes(vec![0x0f, 0xc2]),
+    );
+    e.enc_both(
+        fcmp.bind_vector(F64, 2),
+        rec_vfcmp.opcodes(vec![0x66, 0x0f, 0xc2]),
+    );
+    e.enc_both(
+        fcmp.bind_vector(F64, 2),
+        rec_vfcmpx.opcodes(vec![0x66, 0x0f, 0xc2]),
+    );
+
+    // Lane-wise integer minimum and maximum. SSE2 only has the signed 16-bit and unsigned 8-bit
+    // versions, the others are in SSE 4.1. There are none for 64-bit lanes.
+    e.enc_both(
+        umin.bind_vector(I8, 16),
+        rec_fa.opcodes(vec![0x66, 0x0f, 0xda]),
+    );
+    e.enc_both(
+        umax.bind_vector(I8, 16),
+        rec_fa.opcodes(vec![0x66, 0x0f, 0xde]),
+    );
+    e.enc_both(
+        imin.bind_vector(I16, 8),
+        rec_fa.opcodes(vec![0x66, 0x0f, 0xea]),
+    );
+    e.enc_both(
+        imax.bind_vector(I16, 8),
+        rec_fa.opcodes(vec![0x66, 0x0f, 0xee]),
+    );
+    for &(inst, lane_ty, lanes, op) in &[
+        (imin, I8, 16, 0x38),
+        (imax, I8, 16, 0x3c),
+        (umin, I16, 8, 0x3a),
+        (umax, I16, 8, 0x3e),
+        (imin, I32, 4, 0x39),
+        (imax, I32, 4, 0x3d),
+        (umin, I32, 4, 0x3b),
+        (umax, I32, 4, 0x3f),
+    ] {
+        e.enc_both_isap(
+            inst.bind_vector(lane_ty, lanes),
+            rec_fa.opcodes(vec![0x66, 0x0f, 0x38, op]),
+            use_sse41,
+        );
+    }
+
     e
 }
@@ -5,13 +5,15 @@ use crate::cdsl::instructions::{
     AllInstructions, InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
 };
 use crate::cdsl::operands::{create_operand as operand, create_operand_doc as operand_doc};
-use crate::cdsl::types::ValueType;
+use crate::cdsl::types::{LaneType, ValueType};
 use crate::cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
-use crate::shared::types;
+use crate::shared::{types, OperandKinds};
 
 pub fn define(
     mut all_instructions: &mut AllInstructions,
     format_registry: &FormatRegistry,
+    immediates: &OperandKinds,
+    entities: &OperandKinds,
 ) -> InstructionGroup {
     let mut ig = InstructionGroupBuilder::new(
         "x86",
@@ -249,5 +251,137 @@ pub fn define(
         .operands_out(vec![y, rflags]),
     );
 
+    ig.push(
+        Inst::new(
+            "x86_blsr",
+            r#"
+    Reset Lowest Set Bit -- clears the least significant 1 bit in the word,
+    computing `y = x & (x - 1)`.
+
+    This is polymorphic in i32 and i64. It is implemented for both i64 and
+    i32 in 64-bit mode, and only for i32 in 32-bit mode.
+    "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![y]),
+    );
+
+    let iAddr = &TypeVar::new(
+        "iAddr",
+        "An integer address type",
+        TypeSetBuilder::new().ints(32..64).build(),
+    );
+    let offset32 = immediates.by_name("offset32");
+    let memflags = immediates.by_name("memflags");
+    let MemFlags = &operand("MemFlags", memflags);
+    let Offset = &operand_doc("Offset", offset32, "Byte offset from base address");
+    let p = &operand("p", iAddr);
+    let a = &operand("a", iWord);
+
+    ig.push(
+        Inst::new(
+            "x86_iadd_load",
+            r#"
+    Add a value loaded from memory -- computes `a = x + load(p + Offset)`
+    with a single instruction using a memory operand.
+
+    This is polymorphic in i32 and i64. It is implemented for both i64 and
+    i32 in 64-bit mode, and only for i32 in 32-bit mode.
+    "#,
+        )
+        .operands_in(vec![MemFlags, x, p, Offset])
+        .operands_out(vec![a])
+        .can_load(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_band_load",
+            r#"
+    Bitwise and with a value loaded from memory -- computes
+    `a = x & load(p + Offset)` with a single instruction using a memory
+    operand.
+
+    This is polymorphic in i32 and i64. It is implemented for both i64 and
+    i32 in 64-bit mode, and only for i32 in 32-bit mode.
+    "#,
+        )
+        .operands_in(vec![MemFlags, x, p, Offset])
+        .operands_out(vec![a])
+        .can_load(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_ifcmp_load",
+            r#"
+    Compare with a value loaded from memory -- computes the flags of
+    `ifcmp x, load(p + Offset)` with a single instruction using a memory
+    operand.
+
+    This is polymorphic in i32 and i64. It is implemented for both i64 and
+    i32 in 64-bit mode, and only for i32 in 32-bit mode.
+    "#,
+        )
+        .operands_in(vec![MemFlags, x, p, Offset])
+        .operands_out(vec![rflags])
+        .can_load(true),
+    );
+
+    let i64_t: &TypeVar = &ValueType::from(LaneType::from(types::Int::I64)).into();
+    let global_value = entities.by_name("global_value");
+    let GV = &operand("GV", global_value);
+    let addr = &operand("addr", i64_t);
+
+    ig.push(
+        Inst::new(
+            "x86_elf_tls_get_addr",
+            r#"
+    Elf tls get addr -- This implements the GD TLS model for ELF by calling
+    `__tls_get_addr`, so it clobbers all caller-saved registers.
+    "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr])
+        .is_implicit_call(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_elf_tls_ie_addr",
+            r#"
+    Elf tls ie addr -- This implements the IE TLS model for ELF. The offset of the variable
+    from the thread pointer is loaded from the GOT.
+    "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_elf_tls_le_addr",
+            r#"
+    Elf tls le addr -- This implements the LE TLS model for ELF. The offset of the variable
+    from the thread pointer is a link-time constant.
+    "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr]),
+    );
+
+    ig.push(
+        Inst::new(
+            "x86_macho_tls_get_addr",
+            r#"
+    Mach-O tls get addr -- This implements TLS access for Mach-O by calling the
+    variable's thread-local descriptor function.
+    "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr])
+        .is_implicit_call(true),
+    );
+
     ig.build()
 }
@@ -32,6 +32,9 @@ pub fn define(shared: &mut SharedDefinitions, x86_instructions: &InstructionGrou
     let fmin = insts.by_name("fmin");
     let iadd = insts.by_name("iadd");
     let iconst = insts.by_name("iconst");
+    let ifcmp = insts.by_name("ifcmp");
+    let imax = insts.by_name("imax");
+    let imin = insts.by_name("imin");
     let imul = insts.by_name("imul");
     let isub = insts.by_name("isub");
     let popcnt = insts.by_name("popcnt");
@@ -39,7 +42,10 @@ pub fn define(shared: &mut SharedDefinitions, x86_instructions: &InstructionGrou
     let selectif = insts.by_name("selectif");
     let smulhi = insts.by_name("smulhi");
     let srem = insts.by_name("srem");
+    let tls_value = insts.by_name("tls_value");
     let udiv = insts.by_name("udiv");
+    let umax = insts.by_name("umax");
+    let umin = insts.by_name("umin");
     let umulhi = insts.by_name("umulhi");
     let ushr_imm = insts.by_name("ushr_imm");
     let urem = insts.by_name("urem");
@@ -67,6 +73,7 @@ pub fn define(shared: &mut SharedDefinitions, x86_instructions: &InstructionGrou
     let a = var("a");
     let x = var("x");
     let y = var("y");
+    let flags = var("flags");
     let a1 = var("a1");
     let a2 = var("a2");
     let res_lo = var("res_lo");
@@ -82,6 +89,18 @@ pub fn define(shared: &mut SharedDefinitions, x86_instructions: &InstructionGrou
         vec![def!((res_lo, res_hi) = x86_smulx(x, y))],
     );
 
+    // Integer minimum and maximum with a conditional move.
+    for &(inst, cc) in &[(imin, "slt"), (imax, "sgt"), (umin, "ult"), (umax, "ugt")] {
+        let cc = Literal::enumerator_for(intcc, cc);
+        group.legalize(
+            def!(a = inst(x, y)),
+            vec![
+                def!(flags = ifcmp(x, y)),
+                def!(a = selectif(cc, flags, x, y)),
+            ],
+        );
+    }
+
     // Floating point condition codes.
     //
     // The 8 condition codes in `supported_floatccs` are directly supported by a
@@ -144,6 +163,9 @@ pub fn define(shared: &mut SharedDefinitions, x86_instructions: &InstructionGrou
     group.custom_legalize(fcvt_to_sint_sat, "expand_fcvt_to_sint_sat");
     group.custom_legalize(fcvt_to_uint_sat, "expand_fcvt_to_uint_sat");
 
+    // Thread-local storage accesses depend on the `tls_model` setting.
+    group.custom_legalize(tls_value, "expand_tls_value");
+
     // Count leading and trailing zeroes, for baseline x86_64
     let c_minus_one = var("c_minus_one");
     let c_thirty_one = var("c_thirty_one");
@@ -20,6 +20,8 @@ pub fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
     let inst_group = instructions::define(
         &mut shared_defs.all_instructions,
         &shared_defs.format_registry,
+        &shared_defs.operand_kinds,
+        &shared_defs.entities,
     );
     legalize::define(shared_defs, &inst_group);
 
@@ -72,3 +74,98 @@ pub fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
         encodings_predicates,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cdsl::types::{LaneType, ValueType};
+    use crate::shared;
+    use crate::shared::types::Int::{I16, I8};
+
+    /// Instructions that are polymorphic over `i8` and `i16`, but can't be widened.
+    const NOT_WIDENED: &[&str] = &[
+        // Address computations, which always produce a pointer-sized value.
+        "adjust_sp_down",
+        "global_value",
+        "jump_table_entry",
+        "symbol_value",
+        "tls_value",
+        // There are no 8-bit or 16-bit types to bitcast from.
+        "bitcast",
+        // The flags of a wider comparison differ for signed and unsigned condition codes.
+        "ifcmp",
+        "ifcmp_imm",
+        // These need range checks in the narrow type.
+        "fcvt_to_sint",
+        "fcvt_to_sint_sat",
+        "fcvt_to_uint",
+        "fcvt_to_uint_sat",
+        // The variable number of address arguments can't be expressed in a pattern.
+        "istore8_complex",
+        "load_complex",
+        "sload8_complex",
+        "store_complex",
+        "uload8_complex",
+        // The result type is always wider than 8 bits.
+        "sextend",
+        "uextend",
+        // Vector only.
+        "vconcat",
+        // A wider atomic access would also access the neighbouring bytes in memory.
+        "atomic_load",
+        "atomic_store",
+        "atomic_xchg",
+        "atomic_add",
+        "atomic_and",
+        "atomic_or",
+        "atomic_xor",
+        "atomic_smin",
+        "atomic_smax",
+        "atomic_umin",
+        "atomic_umax",
+        "atomic_cas",
+    ];
+
+    #[test]
+    fn widen_coverage() {
+        let mut shared_defs = shared::define();
+        let isa = super::define(&mut shared_defs);
+
+        let mut missing = Vec::new();
+        for mode in &isa.cpu_modes {
+            for &(int_ty, bits) in &[(I8, 8), (I16, 16)] {
+                let ty: ValueType = LaneType::from(int_ty).into();
+                let group = shared_defs
+                    .transform_groups
+                    .get(mode.get_legalize_code_for(&Some(ty.clone())));
+
+                for inst in shared_defs.all_instructions.values() {
+                    let typeset = match &inst.polymorphic_info {
+                        Some(info) => info.ctrl_typevar.get_typeset(),
+                        None => continue,
+                    };
+                    if !typeset.lanes.contains(&1)
+                        || !typeset.ints.contains(&bits)
+                        || NOT_WIDENED.contains(&inst.name.as_str())
+                    {
+                        continue;
+                    }
+
+                    let has_encoding = mode.encodings.iter().any(|enc| {
+                        enc.inst().name == inst.name
+                            && enc.bound_type.as_ref().map_or(true, |bound| *bound == ty)
+                    });
+                    let has_legalization = group.custom_legalizes.contains_key(&inst.camel_name)
+                        || group.transforms.iter().any(|transform| {
+                            let apply = &transform.def_pool.get(transform.src).apply;
+                            apply.inst.name == inst.name
+                                && apply.value_types.first().map_or(true, |bound| *bound == ty)
+                        });
+                    if !has_encoding && !has_legalization {
+                        missing.push(format!("{}.{} in {}", inst.name, ty, mode.name));
+                    }
+                }
+            }
+        }
+        assert!(missing.is_empty(), "missing widening for {:?}", missing);
+    }
+}
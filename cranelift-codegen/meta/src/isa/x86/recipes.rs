@@ -71,8 +71,9 @@ impl<'builder> RecipeGroup<'builder> {
 // opcode format is indicated by the recipe name prefix.
 //
 // The match case below does not include the REX prefix which goes after the mandatory prefix.
-// VEX/XOP and EVEX prefixes are not yet supported. Encodings using any of these prefixes are
-// represented by separate recipes.
+// VEX prefixes are supported through the `Vex` recipes, which always use the 3-byte form. XOP and
+// EVEX prefixes are not yet supported. Encodings using any of these prefixes are represented by
+// separate recipes.
 //
 // The encoding bits are:
 //
@@ -181,6 +182,8 @@ pub struct Template<'builder> {
     // Specialized parameters.
     /// Should we include the REX prefix?
     rex: bool,
+    /// Should we use a VEX prefix instead of the legacy prefixes?
+    vex: bool,
     /// Value of the W bit (0 or 1).
     w_bit: u16,
     /// Value of the RRR bits (between 0 and 0b111).
@@ -202,6 +205,7 @@ impl<'builder> Template<'builder> {
             requires_prefix: false,
             when_prefixed: None,
             rex: false,
+            vex: false,
             w_bit: 0,
             rrr_bits: 0,
             op_bytes: Vec::new(),
@@ -258,21 +262,32 @@ impl<'builder> Template<'builder> {
             ret.rrr_bits = self.rrr_bits;
             return ret;
         }
+        assert!(!self.vex, "VEX-prefixed recipes can't have a REX prefix.");
         let mut copy = self.clone();
         copy.rex = true;
         copy
     }
+    pub fn vex(&self) -> Self {
+        assert!(!self.rex, "VEX-prefixed recipes can't have a REX prefix.");
+        let mut copy = self.clone();
+        copy.vex = true;
+        copy
+    }
 
     pub fn build(mut self) -> (EncodingRecipe, u16) {
         let (name, bits) = decode_opcodes(&self.op_bytes, self.rrr_bits, self.w_bit);
 
-        let (name, rex_prefix_size) = if self.rex {
-            ("Rex".to_string() + name, 1)
+        let (name, size_addendum) = if self.vex {
+            // The 3-byte VEX prefix encodes the mandatory prefix and the opcode map, so only the
+            // last opcode byte follows it.
+            assert!(bits & 0x0c00 != 0, "VEX encodings need an opcode map");
+            ("Vex".to_string(), 4)
+        } else if self.rex {
+            ("Rex".to_string() + name, self.op_bytes.len() as u64 + 1)
         } else {
-            (name.into(), 0)
+            (name.into(), self.op_bytes.len() as u64)
         };
 
-        let size_addendum = self.op_bytes.len() as u64 + rex_prefix_size;
         self.recipe.base_size += size_addendum;
 
         // Branch ranges are relative to the end of the instruction.
@@ -284,7 +299,8 @@ impl<'builder> Template<'builder> {
         self.recipe.emit = replace_put_op(self.recipe.emit, &name);
         self.recipe.name = name + &self.recipe.name;
 
-        if !self.rex {
+        // The VEX prefix can address all the registers, like REX.
+        if !self.rex && !self.vex {
             let operands_in = self.recipe.operands_in.unwrap_or(Vec::new());
             self.recipe.operands_in = Some(replace_nonrex_constraints(self.regs, operands_in));
             let operands_out = self.recipe.operands_out.unwrap_or(Vec::new());
@@ -351,6 +367,7 @@ pub fn define<'shared>(
     let reg_rax = Register::new(gpr, regs.regunit_by_name(gpr, "rax"));
     let reg_rcx = Register::new(gpr, regs.regunit_by_name(gpr, "rcx"));
     let reg_rdx = Register::new(gpr, regs.regunit_by_name(gpr, "rdx"));
+    let reg_xmm0 = Register::new(fpr, regs.regunit_by_name(fpr, "xmm0"));
 
     // Stack operand with a 32-bit signed displacement from either RBP or RSP.
     let stack_gpr32 = Stack::new(gpr);
@@ -418,6 +435,18 @@ pub fn define<'shared>(
             .emit(""),
     );
 
+    // Debug value markers only keep their operand alive in a register.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("debugvalue", f_binary_imm, 0)
+            .operands_in(vec![gpr])
+            .emit(""),
+    );
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("fdebugvalue", f_binary_imm, 0)
+            .operands_in(vec![fpr])
+            .emit(""),
+    );
+
     recipes
         .add_recipe(EncodingRecipeBuilder::new("debugtrap", f_nullary, 1).emit("sink.put1(0xcc);"));
 
@@ -481,6 +510,47 @@ pub fn define<'shared>(
             ),
     );
 
+    // XX /r followed by `seto` for the signed overflow flag. See the `icscc` recipe for why the
+    // `setCC` instruction is limited to the `ABCD` register class.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rseto", f_binary, 1 + 3)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![
+                OperandConstraint::TiedInput(0),
+                OperandConstraint::RegClass(abcd),
+            ])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                    // `seto` instruction, no REX.
+                    sink.put1(0x0f);
+                    sink.put1(0x90);
+                    modrm_rr(out_reg1, 0, sink);
+                "#,
+            ),
+    );
+
+    // XX /r with operands swapped, followed by `seto`.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rrxseto", f_binary, 1 + 3)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![
+                OperandConstraint::TiedInput(0),
+                OperandConstraint::RegClass(abcd),
+            ])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    // `seto` instruction, no REX.
+                    sink.put1(0x0f);
+                    sink.put1(0x90);
+                    modrm_rr(out_reg1, 0, sink);
+                "#,
+            ),
+    );
+
     // XX /r with operands swapped. (RM form).
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("rrx", f_binary, 1)
@@ -507,6 +577,25 @@ pub fn define<'shared>(
             ),
     );
 
+    // XX /r blending the FPR inputs under the control of the mask in %xmm0. The output is tied to
+    // the value selected where the mask is clear.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("blend", f_ternary, 1)
+            .operands_in(vec![
+                OperandConstraint::FixedReg(reg_xmm0),
+                OperandConstraint::RegClass(fpr),
+                OperandConstraint::RegClass(fpr),
+            ])
+            .operands_out(vec![2])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg1, in_reg2), sink);
+                    modrm_rr(in_reg1, in_reg2, sink);
+                "#,
+            ),
+    );
+
     // XX /r with FPR ins and outs. A form with input operands swapped.
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("fax", f_binary, 1)
@@ -576,6 +665,49 @@ pub fn define<'shared>(
             ),
     );
 
+    // VEX XX /r with the first input in the r/m field and the second input in VEX.vvvv.
+    // RVM form, used by `andn`. Clobbers FLAGS.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("rvm", f_binary, 1)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg0, out_reg0), in_reg1, sink);
+                    modrm_rr(in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // VEX XX /r for a ternary FPR operator, with the first input tied to the output in the reg
+    // field, the second input in VEX.vvvv, and the third input in the r/m field. This is the
+    // operand order of the `vfmadd213` family.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fma", f_ternary, 1)
+            .operands_in(vec![fpr, fpr, fpr])
+            .operands_out(vec![0])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg2, in_reg0), in_reg1, sink);
+                    modrm_rr(in_reg2, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // VEX XX /n for a unary operator with the output in VEX.vvvv.
+    // VM form, used by `blsr`. Clobbers FLAGS.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("vm", f_unary, 1)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex1(in_reg0), out_reg0, sink);
+                    modrm_r_bits(in_reg0, bits, sink);
+                "#,
+            ),
+    );
+
     // XX /r. Same as urm, but doesn't clobber FLAGS.
     let urm_noflags = recipes.add_template_recipe(
         EncodingRecipeBuilder::new("urm_noflags", f_unary, 1)
@@ -883,6 +1015,44 @@ pub fn define<'shared>(
         );
     }
 
+    // XX /r Unary with floating point 32-bit immediate with all bits set, comparing the output
+    // register with itself.
+    {
+        let format = formats.get(f_unary_ieee32);
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("f32imm_o", f_unary_ieee32, 1)
+                .operands_out(vec![fpr])
+                .inst_predicate(InstructionPredicate::new_is_all_ones_32bit_float(
+                    format, "imm",
+                ))
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(out_reg0, out_reg0), sink);
+                        modrm_rr(out_reg0, out_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // XX /r Unary with floating point 64-bit immediate with all bits set, comparing the output
+    // register with itself.
+    {
+        let format = formats.get(f_unary_ieee64);
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("f64imm_o", f_unary_ieee64, 1)
+                .operands_out(vec![fpr])
+                .inst_predicate(InstructionPredicate::new_is_all_ones_64bit_float(
+                    format, "imm",
+                ))
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex2(out_reg0, out_reg0), sink);
+                        modrm_rr(out_reg0, out_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("pushq", f_unary, 0)
             .operands_in(vec![gpr])
@@ -1067,7 +1237,7 @@ pub fn define<'shared>(
                     {{PUT_OP}}(bits | (out_reg0 & 7), rex1(out_reg0), sink);
                     sink.reloc_external(Reloc::Abs4,
                                         &func.global_values[global_value].symbol_name(),
-                                        0);
+                                        func.global_values[global_value].symbol_offset());
                     sink.put4(0);
                 "#,
             ),
@@ -1082,7 +1252,7 @@ pub fn define<'shared>(
                     {{PUT_OP}}(bits | (out_reg0 & 7), rex1(out_reg0), sink);
                     sink.reloc_external(Reloc::Abs8,
                                         &func.global_values[global_value].symbol_name(),
-                                        0);
+                                        func.global_values[global_value].symbol_offset());
                     sink.put8(0);
                 "#,
             ),
@@ -1100,13 +1270,14 @@ pub fn define<'shared>(
                     // instruction and the beginning of the immediate field.
                     sink.reloc_external(Reloc::X86PCRel4,
                                         &func.global_values[global_value].symbol_name(),
-                                        -4);
+                                        -4 + func.global_values[global_value].symbol_offset());
                     sink.put4(0);
                 "#,
             ),
     );
 
-    // XX+rd iq with Abs8 globalsym relocation.
+    // XX+rd iq with GOTPCRel4 globalsym relocation. The symbol offset can't be folded into the
+    // GOT entry, so the legalizer adds it separately.
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("got_gvaddr8", f_unary_global_value, 5)
             .operands_out(vec![gpr])
@@ -1124,6 +1295,122 @@ pub fn define<'shared>(
             ),
     );
 
+    // Thread-local storage addresses.
+
+    // ELF general-dynamic model: call `__tls_get_addr` with the address of the variable's GOT
+    // entry pair in %rdi. The data16 and rex.w prefixes pad the sequence to 16 bytes as required
+    // by the ABI, so the linker can relax it to a cheaper model.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("elf_tls_get_addr", f_unary_global_value, 16)
+            .operands_out(vec![reg_rax])
+            .emit(
+                r#"
+                    // output %rax
+                    // clobbers %rdi
+
+                    // data16 lea gv@tlsgd(%rip), %rdi
+                    sink.put1(0x66);
+                    sink.put1(0b01001000);
+                    sink.put1(0x8d);
+                    modrm_riprel(0b111, sink);
+                    sink.reloc_external(Reloc::ElfX86_64TlsGd,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+
+                    // data16 data16 rex.w call __tls_get_addr@plt
+                    sink.put1(0x66);
+                    sink.put1(0x66);
+                    sink.put1(0b01001000);
+                    sink.put1(0xe8);
+                    sink.reloc_external(Reloc::X86CallPLTRel4,
+                                        &ExternalName::LibCall(LibCall::ElfTlsGetAddr),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // ELF initial-exec model: add the variable's offset, loaded from the GOT, to the thread
+    // pointer.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("elf_tls_ie_addr", f_unary_global_value, 16)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // mov %fs:0, out
+                    sink.put1(0x64);
+                    sink.put1(rex2(0, out_reg0) | 0b1000);
+                    sink.put1(0x8b);
+                    modrm_sib(out_reg0, sink);
+                    sib_noindex(0b101, sink);
+                    sink.put4(0);
+
+                    // add gv@gottpoff(%rip), out
+                    sink.put1(rex2(0, out_reg0) | 0b1000);
+                    sink.put1(0x03);
+                    modrm_riprel(out_reg0, sink);
+                    sink.reloc_external(Reloc::ElfX86_64GotTpOff,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // ELF local-exec model: add the link-time constant offset of the variable to the thread
+    // pointer.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("elf_tls_le_addr", f_unary_global_value, 16)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // mov %fs:0, out
+                    sink.put1(0x64);
+                    sink.put1(rex2(0, out_reg0) | 0b1000);
+                    sink.put1(0x8b);
+                    modrm_sib(out_reg0, sink);
+                    sib_noindex(0b101, sink);
+                    sink.put4(0);
+
+                    // add $gv@tpoff, out
+                    sink.put1(rex2(out_reg0, 0) | 0b1000);
+                    sink.put1(0x81);
+                    modrm_r_bits(out_reg0, 0, sink);
+                    sink.reloc_external(Reloc::ElfX86_64TpOff32,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // Mach-O: load the address of the variable's TLV descriptor and call its thunk, which
+    // returns the variable's address in %rax.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("macho_tls_get_addr", f_unary_global_value, 9)
+            .operands_out(vec![reg_rax])
+            .emit(
+                r#"
+                    // output %rax
+                    // clobbers %rdi
+
+                    // movq gv@tlv(%rip), %rdi
+                    sink.put1(0b01001000);
+                    sink.put1(0x8b);
+                    modrm_riprel(0b111, sink);
+                    sink.reloc_external(Reloc::MachOX86_64Tlv,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+
+                    // callq *(%rdi)
+                    sink.put1(0xff);
+                    sink.put1(0x17);
+                "#,
+            ),
+    );
+
     // Stack addresses.
     //
     // TODO Alternative forms for 8-bit immediates, when applicable.
@@ -2022,6 +2309,169 @@ pub fn define<'shared>(
         );
     }
 
+    // Load-and-operate recipes.
+
+    {
+        // An ALU operation or comparison with a memory operand, using the Store format for the
+        // register operand and the address. The register operand is in ModR/M.reg. Clobbers FLAGS.
+        let format = formats.get(f_store);
+
+        // A predicate asking if the offset is zero.
+        let has_no_offset = InstructionPredicate::new_is_field_equal(format, "offset", "0".into());
+
+        // XX /r ALU operation with a memory operand and no offset.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("ldop", f_store, 1)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![0])
+                .inst_predicate(has_no_offset.clone())
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_1")
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else if needs_offset(in_reg1) {
+                            modrm_disp8(in_reg1, in_reg0, sink);
+                            sink.put1(0);
+                        } else {
+                            modrm_rm(in_reg1, in_reg0, sink);
+                        }
+                    "#,
+                ),
+        );
+
+        // XX /r comparison with a memory operand and no offset.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("ldcmp", f_store, 1)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![reg_rflags])
+                .inst_predicate(has_no_offset.clone())
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_1")
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else if needs_offset(in_reg1) {
+                            modrm_disp8(in_reg1, in_reg0, sink);
+                            sink.put1(0);
+                        } else {
+                            modrm_rm(in_reg1, in_reg0, sink);
+                        }
+                    "#,
+                ),
+        );
+
+        let has_small_offset = InstructionPredicate::new_is_signed_int(format, "offset", 8, 0);
+
+        // XX /r ALU operation with a memory operand and 8-bit offset.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("ldopDisp8", f_store, 2)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![0])
+                .inst_predicate(has_small_offset.clone())
+                .compute_size("size_plus_maybe_sib_for_in_reg_1")
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib_disp8(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else {
+                            modrm_disp8(in_reg1, in_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                ),
+        );
+
+        // XX /r comparison with a memory operand and 8-bit offset.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("ldcmpDisp8", f_store, 2)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![reg_rflags])
+                .inst_predicate(has_small_offset.clone())
+                .compute_size("size_plus_maybe_sib_for_in_reg_1")
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib_disp8(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else {
+                            modrm_disp8(in_reg1, in_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put1(offset as u8);
+                    "#,
+                ),
+        );
+
+        // XX /r ALU operation with a memory operand and 32-bit offset.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("ldopDisp32", f_store, 5)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![0])
+                .compute_size("size_plus_maybe_sib_for_in_reg_1")
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib_disp32(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else {
+                            modrm_disp32(in_reg1, in_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                ),
+        );
+
+        // XX /r comparison with a memory operand and 32-bit offset.
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("ldcmpDisp32", f_store, 5)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![reg_rflags])
+                .compute_size("size_plus_maybe_sib_for_in_reg_1")
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                        if needs_sib_byte(in_reg1) {
+                            modrm_sib_disp32(in_reg0, sink);
+                            sib_noindex(in_reg1, sink);
+                        } else {
+                            modrm_disp32(in_reg1, in_reg0, sink);
+                        }
+                        let offset: i32 = offset.into();
+                        sink.put4(offset as u32);
+                    "#,
+                ),
+        );
+    }
+
     // Unary fill with SIB and 32-bit displacement.
     recipes.add_template_recipe(
         EncodingRecipeBuilder::new("fillSib32", f_unary, 6)
@@ -2316,6 +2766,10 @@ pub fn define<'shared>(
         Template::new(
             EncodingRecipeBuilder::new("setf", f_float_cond, 1)
                 .operands_in(vec![reg_rflags])
+                .inst_predicate(supported_floatccs_predicate(
+                    &supported_floatccs,
+                    formats.get(f_float_cond),
+                ))
                 .operands_out(vec![gpr])
                 .clobbers_flags(false)
                 .emit(
@@ -2334,6 +2788,10 @@ pub fn define<'shared>(
         Template::new(
             EncodingRecipeBuilder::new("setf_abcd", f_float_cond, 1)
                 .operands_in(vec![reg_rflags])
+                .inst_predicate(supported_floatccs_predicate(
+                    &supported_floatccs,
+                    formats.get(f_float_cond),
+                ))
                 .operands_out(vec![abcd])
                 .clobbers_flags(false)
                 .emit(
@@ -2801,5 +3259,105 @@ pub fn define<'shared>(
             ),
     );
 
+    // Lane-wise vector comparisons, producing a mask in the output register.
+    //
+    // The integer comparisons `pcmpeq*` and `pcmpgt*` only support one condition code each, so
+    // the condition code is checked by the encoding. The `x` variant compares the operands in
+    // the opposite order, which turns `pcmpgt*` into a signed less than comparison.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("vicmp", f_int_compare, 1)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![0])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("vicmpx", f_int_compare, 1)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![1])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                "#,
+            ),
+    );
+
+    // The `cmpps` and `cmppd` instructions take the comparison predicate as an immediate. Only
+    // the condition codes in `supported_vector_floatccs` can be encoded, and the `x` variant
+    // encodes their reverses by comparing the operands in the opposite order.
+    let supported_vector_floatccs: Vec<Literal> =
+        ["eq", "lt", "le", "uno", "ne", "uge", "ugt", "ord"]
+            .iter()
+            .map(|name| Literal::enumerator_for(floatcc, name))
+            .collect();
+    let reversed_vector_floatccs: Vec<Literal> = ["gt", "ge", "ule", "ult"]
+        .iter()
+        .map(|name| Literal::enumerator_for(floatcc, name))
+        .collect();
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("vfcmp", f_float_compare, 2)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![0])
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_vector_floatccs,
+                formats.get(f_float_compare),
+            ))
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    use crate::ir::condcodes::FloatCC::*;
+                    let imm = match cond {
+                        Equal                         => 0x00, // eq
+                        LessThan                      => 0x01, // lt
+                        LessThanOrEqual               => 0x02, // le
+                        Unordered                     => 0x03, // unord
+                        NotEqual                      => 0x04, // neq
+                        UnorderedOrGreaterThanOrEqual => 0x05, // nlt
+                        UnorderedOrGreaterThan        => 0x06, // nle
+                        Ordered                       => 0x07, // ord
+                        _ => panic!("{} not supported by vfcmp", cond),
+                    };
+                    sink.put1(imm);
+                "#,
+            ),
+    );
+
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("vfcmpx", f_float_compare, 2)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![1])
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &reversed_vector_floatccs,
+                formats.get(f_float_compare),
+            ))
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg1), sink);
+                    modrm_rr(in_reg0, in_reg1, sink);
+                    use crate::ir::condcodes::FloatCC::*;
+                    let imm = match cond {
+                        GreaterThan                => 0x01, // lt
+                        GreaterThanOrEqual         => 0x02, // le
+                        UnorderedOrLessThanOrEqual => 0x05, // nlt
+                        UnorderedOrLessThan        => 0x06, // nle
+                        _ => panic!("{} not supported by vfcmpx", cond),
+                    };
+                    sink.put1(imm);
+                "#,
+            ),
+    );
+
     recipes
 }
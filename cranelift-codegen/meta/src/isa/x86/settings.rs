@@ -8,8 +8,9 @@ pub fn define(shared: &SettingGroup) -> SettingGroup {
     let has_ssse3 = settings.add_bool("has_ssse3", "SSSE3: CPUID.01H:ECX.SSSE3[bit 9]", false);
     let has_sse41 = settings.add_bool("has_sse41", "SSE4.1: CPUID.01H:ECX.SSE4_1[bit 19]", false);
     let has_sse42 = settings.add_bool("has_sse42", "SSE4.2: CPUID.01H:ECX.SSE4_2[bit 20]", false);
+    let has_fma = settings.add_bool("has_fma", "FMA: CPUID.01H:ECX.FMA[bit 12]", false);
     let has_popcnt = settings.add_bool("has_popcnt", "POPCNT: CPUID.01H:ECX.POPCNT[bit 23]", false);
-    settings.add_bool("has_avx", "AVX: CPUID.01H:ECX.AVX[bit 28]", false);
+    let has_avx = settings.add_bool("has_avx", "AVX: CPUID.01H:ECX.AVX[bit 28]", false);
 
     // CPUID.(EAX=07H, ECX=0H):EBX
     let has_bmi1 = settings.add_bool(
@@ -35,6 +36,7 @@ pub fn define(shared: &SettingGroup) -> SettingGroup {
     settings.add_predicate("use_popcnt", predicate!(has_popcnt && has_sse42));
     settings.add_predicate("use_bmi1", predicate!(has_bmi1));
     settings.add_predicate("use_lzcnt", predicate!(has_lzcnt));
+    settings.add_predicate("use_fma", predicate!(has_avx && has_fma));
 
     // Some shared boolean values are used in x86 instruction predicates, so we need to group them
     // in the same TargetIsa, for compabitibity with code generated by meta-python.
@@ -43,8 +45,10 @@ pub fn define(shared: &SettingGroup) -> SettingGroup {
 
     let is_pic = shared.get_bool("is_pic");
     let allones_funcaddrs = shared.get_bool("allones_funcaddrs");
+    let far_calls = shared.get_bool("far_calls");
     settings.add_predicate("is_pic", predicate!(is_pic));
     settings.add_predicate("not_is_pic", predicate!(!is_pic));
+    settings.add_predicate("not_far_calls", predicate!(!far_calls));
     settings.add_predicate(
         "all_ones_funcaddrs_and_not_is_pic",
         predicate!(allones_funcaddrs && !is_pic),
@@ -63,7 +67,7 @@ pub fn define(shared: &SettingGroup) -> SettingGroup {
     );
     let haswell = settings.add_preset(
         "haswell",
-        preset!(nehalem && has_bmi1 && has_bmi2 && has_lzcnt),
+        preset!(nehalem && has_avx && has_fma && has_bmi1 && has_bmi2 && has_lzcnt),
     );
     let broadwell = settings.add_preset("broadwell", preset!(haswell));
     let skylake = settings.add_preset("skylake", preset!(broadwell));
@@ -77,6 +81,8 @@ pub fn define(shared: &SettingGroup) -> SettingGroup {
                 && has_sse41
                 && has_sse42
                 && has_popcnt
+                && has_avx
+                && has_fma
                 && has_bmi1
                 && has_bmi2
                 && has_lzcnt
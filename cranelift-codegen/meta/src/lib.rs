@@ -44,6 +44,7 @@ pub fn generate(isas: &Vec<isa::Isa>, out_dir: &str) -> Result<(), error::Error>
         &isas,
         &shared_defs.format_registry,
         &shared_defs.transform_groups,
+        &shared_defs.all_instructions,
         "legalize",
         &out_dir,
     )?;
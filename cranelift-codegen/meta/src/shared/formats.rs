@@ -141,10 +141,26 @@ pub fn define(immediates: &OperandKinds, entities: &OperandKinds) -> FormatRegis
     );
 
     // Accessing a WebAssembly heap.
-    registry.insert(Builder::new("HeapAddr").imm(heap).value().imm(uimm32));
+    registry.insert(
+        Builder::new("HeapAddr")
+            .imm(memflags)
+            .imm(heap)
+            .value()
+            .imm(uimm32),
+    );
 
     // Accessing a WebAssembly table.
-    registry.insert(Builder::new("TableAddr").imm(table).value().imm(offset32));
+    registry.insert(
+        Builder::new("TableAddr")
+            .imm(memflags)
+            .imm(table)
+            .value()
+            .imm(offset32),
+    );
+
+    // Growing and filling a WebAssembly table.
+    registry.insert(Builder::new("TableGrow").imm(table).value().value());
+    registry.insert(Builder::new("TableFill").imm(table).value().varargs());
 
     registry.insert(
         Builder::new("RegMove")
@@ -53,6 +53,7 @@ pub fn define(
     let b1: &TypeVar = &ValueType::from(LaneType::from(types::Bool::B1)).into();
     let f32_: &TypeVar = &ValueType::from(LaneType::from(types::Float::F32)).into();
     let f64_: &TypeVar = &ValueType::from(LaneType::from(types::Float::F64)).into();
+    let i8: &TypeVar = &ValueType::from(LaneType::from(types::Int::I8)).into();
 
     // Starting definitions.
     let Int = &TypeVar::new(
@@ -862,6 +863,269 @@ pub fn define(
         .can_store(true),
     );
 
+    let AtomicMem = &TypeVar::new(
+        "AtomicMem",
+        "An integer type that can be accessed atomically",
+        TypeSetBuilder::new().ints(8..64).build(),
+    );
+    let x = &operand_doc("x", AtomicMem, "Value to be stored");
+    let a = &operand_doc("a", AtomicMem, "Value loaded");
+
+    ig.push(
+        Inst::new(
+            "fence",
+            r#"
+        A sequentially consistent memory fence.
+
+        No memory accesses before the fence can be reordered with memory
+        accesses after it.
+        "#,
+        )
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_load",
+            r#"
+        Atomically load from memory at ``p + Offset``.
+
+        The load is sequentially consistent with all other atomic
+        instructions.
+        "#,
+        )
+        .operands_in(vec![MemFlags, p, Offset])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_store",
+            r#"
+        Atomically store ``x`` to memory at ``p + Offset``.
+
+        The store is sequentially consistent with all other atomic
+        instructions.
+        "#,
+        )
+        .operands_in(vec![MemFlags, x, p, Offset])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    let a = &operand_doc("a", AtomicMem, "Value in memory before the update");
+
+    // The read-modify-write instructions below, including `atomic_cas`, are all sequentially
+    // consistent with other atomic instructions.
+
+    ig.push(
+        Inst::new(
+            "atomic_xchg",
+            r#"
+        Atomically store ``x`` to memory at ``p``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_add",
+            r#"
+        Atomically add ``x`` to the value in memory at ``p``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_and",
+            r#"
+        Atomically bitwise and ``x`` into the value in memory at ``p``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_or",
+            r#"
+        Atomically bitwise or ``x`` into the value in memory at ``p``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_xor",
+            r#"
+        Atomically bitwise xor ``x`` into the value in memory at ``p``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_smin",
+            r#"
+        Atomically replace the value in memory at ``p`` with its signed minimum
+        with ``x``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_smax",
+            r#"
+        Atomically replace the value in memory at ``p`` with its signed maximum
+        with ``x``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_umin",
+            r#"
+        Atomically replace the value in memory at ``p`` with its unsigned
+        minimum with ``x``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_umax",
+            r#"
+        Atomically replace the value in memory at ``p`` with its unsigned
+        maximum with ``x``.
+
+        Returns the previous value in memory.
+        "#,
+        )
+        .operands_in(vec![p, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    let e = &operand_doc("e", AtomicMem, "Expected value in memory");
+
+    ig.push(
+        Inst::new(
+            "atomic_cas",
+            r#"
+        Atomically compare the value in memory at ``p`` with ``e`` and store
+        ``x`` if they are equal.
+
+        Returns the previous value in memory, so the store happened if
+        ``a == e``.
+        "#,
+        )
+        .operands_in(vec![p, e, x])
+        .operands_out(vec![a])
+        .other_side_effects(true)
+        .can_load(true)
+        .can_store(true),
+    );
+
+    let dest = &operand_doc("dest", iAddr, "Address of the destination bytes");
+    let src = &operand_doc("src", iAddr, "Address of the source bytes");
+    let size = &operand_doc("size", iAddr, "Number of bytes");
+    let byte = &operand_doc("byte", i8, "Value to store in each byte");
+
+    ig.push(
+        Inst::new(
+            "mem_copy",
+            r#"
+        Copy ``size`` bytes from ``src`` to ``dest``.
+
+        The source and destination must not overlap.
+
+        This is expanded as loads and stores when ``size`` is a small constant,
+        and as a call to the ``Memcpy`` library routine otherwise.
+        "#,
+        )
+        .operands_in(vec![dest, src, size])
+        .can_load(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "mem_fill",
+            r#"
+        Set the ``size`` bytes starting at ``dest`` to ``byte``.
+
+        This is expanded as stores when ``size`` is a small constant, and as a
+        call to the ``Memset`` library routine otherwise.
+        "#,
+        )
+        .operands_in(vec![dest, size, byte])
+        .can_store(true),
+    );
+
     let x = &operand_doc("x", Mem, "Value to be stored");
     let a = &operand_doc("a", Mem, "Value loaded");
     let Offset = &operand_doc("Offset", offset32, "In-bounds offset into stack slot");
@@ -942,6 +1206,20 @@ pub fn define(
         .operands_out(vec![a]),
     );
 
+    ig.push(
+        Inst::new(
+            "tls_value",
+            r#"
+        Compute the value of global GV, which is a thread-local variable.
+
+        The access sequence is selected by the target ISA according to the
+        ``tls_model`` setting.
+        "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![a]),
+    );
+
     let HeapOffset = &TypeVar::new(
         "HeapOffset",
         "An unsigned heap offset",
@@ -965,9 +1243,12 @@ pub fn define(
            absolute address corresponding to a byte offset of ``p`` from the
            heap's base address.
         2. If ``p + Size`` is greater than the heap bound, generate a trap.
+
+        If the ``notrap`` flag is set, the frontend guarantees that the access
+        is in bounds, and the bounds check is omitted.
         "#,
         )
-        .operands_in(vec![H, p, Size])
+        .operands_in(vec![MemFlags, H, p, Size])
         .operands_out(vec![addr]),
     );
 
@@ -995,12 +1276,68 @@ pub fn define(
            address corresponding to a byte offset of ``p`` from the table's
            base address.
         2. If ``p`` is greater than the table bound, generate a trap.
+
+        If the ``notrap`` flag is set, the frontend guarantees that the access
+        is in bounds, and the bounds check is omitted.
         "#,
         )
-        .operands_in(vec![T, p, Offset])
+        .operands_in(vec![MemFlags, T, p, Offset])
         .operands_out(vec![addr]),
     );
 
+    let TableElem = &TypeVar::new(
+        "TableElem",
+        "A table element",
+        TypeSetBuilder::new().ints(32..64).build(),
+    );
+    let delta = &operand_doc("delta", TableOffset, "Number of elements to add");
+    let init = &operand_doc("init", TableElem, "Value of the new elements");
+    let size = &operand_doc("size", TableOffset, "Previous table size, or -1");
+
+    ig.push(
+        Inst::new(
+            "table_grow",
+            r#"
+        Grow a table by ``delta`` elements.
+
+        The new elements are initialized to ``init``. Return the previous size
+        of the table in elements, or -1 if the table can't grow to the new
+        size. The base and bound of the table can change when it grows.
+
+        This is expanded as a call to the ``TableGrow`` library routine.
+        "#,
+        )
+        .operands_in(vec![T, delta, init])
+        .operands_out(vec![size])
+        .other_side_effects(true),
+    );
+
+    let dst = &operand_doc("dst", TableOffset, "Index of the first element to fill");
+    let args = &operand_doc(
+        "args",
+        variable_args,
+        "The value to store in the elements, and the number of elements",
+    );
+
+    ig.push(
+        Inst::new(
+            "table_fill",
+            r#"
+        Set the ``n`` table elements starting at ``dst`` to ``x``, where
+        ``args`` is ``x, n``.
+
+        Trap if ``dst + n`` is greater than the table bound. The element value
+        ``x`` is a 32-bit or 64-bit integer, and ``n`` has the type of ``dst``.
+        They are kept in a value list to keep the instruction data small.
+
+        This is expanded as a call to the ``TableFill`` library routine.
+        "#,
+        )
+        .operands_in(vec![T, dst, args])
+        .can_trap(true)
+        .other_side_effects(true),
+    );
+
     let N = &operand("N", imm64);
     let a = &operand_doc("a", Int, "A constant integer scalar or vector value");
 
@@ -1050,6 +1387,60 @@ pub fn define(
         .operands_out(vec![a]),
     );
 
+    let F32xN = &TypeVar::new(
+        "F32xN",
+        "A SIMD vector type with f32 lanes",
+        TypeSetBuilder::new()
+            .floats(32..32)
+            .simd_lanes(Interval::All)
+            .includes_scalars(false)
+            .build(),
+    );
+
+    let N = &operand("N", ieee32);
+    let a = &operand_doc("a", F32xN, "A constant f32 vector value");
+
+    ig.push(
+        Inst::new(
+            "f32splat",
+            r#"
+        Floating point vector constant.
+
+        Create a vector of `f32` lanes that all have the same immediate
+        constant value.
+        "#,
+        )
+        .operands_in(vec![N])
+        .operands_out(vec![a]),
+    );
+
+    let F64xN = &TypeVar::new(
+        "F64xN",
+        "A SIMD vector type with f64 lanes",
+        TypeSetBuilder::new()
+            .floats(64..64)
+            .simd_lanes(Interval::All)
+            .includes_scalars(false)
+            .build(),
+    );
+
+    let N = &operand("N", ieee64);
+    let a = &operand_doc("a", F64xN, "A constant f64 vector value");
+
+    ig.push(
+        Inst::new(
+            "f64splat",
+            r#"
+        Floating point vector constant.
+
+        Create a vector of `f64` lanes that all have the same immediate
+        constant value.
+        "#,
+        )
+        .operands_in(vec![N])
+        .operands_out(vec![a]),
+    );
+
     let N = &operand("N", boolean);
     let a = &operand_doc("a", Bool, "A constant boolean scalar or vector value");
 
@@ -1109,6 +1500,31 @@ pub fn define(
         .operands_out(vec![a]),
     );
 
+    let c = &operand_doc("c", Any, "Controlling value to test");
+    let x = &operand_doc("x", Any, "Value to use where the bits of `c` are set");
+    let y = &operand_doc("y", Any, "Value to use where the bits of `c` are clear");
+
+    ig.push(
+        Inst::new(
+            "bitselect",
+            r#"
+        Conditional select of bits.
+
+        For each bit in `c`, this instruction selects the corresponding bit
+        from `x` if the bit in `c` is 1 and the corresponding bit from `y` if
+        the bit in `c` is 0. Computes:
+
+        ```text
+            a = (x & c) | (y & ~c)
+        ```
+
+        Use `vselect` to select whole lanes with a boolean vector.
+        "#,
+        )
+        .operands_in(vec![c, x, y])
+        .operands_out(vec![a]),
+    );
+
     let x = &operand("x", Any);
 
     ig.push(
@@ -1212,6 +1628,24 @@ pub fn define(
         .operands_out(vec![a]),
     );
 
+    let label = &operand_doc("label", imm64, "Number of the `ValueLabel` of ``x``");
+
+    ig.push(
+        Inst::new(
+            "debug_value",
+            r#"
+        Mark ``x`` as the current value of a source variable.
+
+        This instruction generates no code. After register allocation, the
+        location of ``x`` at this point is reported in the value location
+        ranges of ``label``, see ``Context::debug_info()``. The value is kept
+        alive until the instruction.
+        "#,
+        )
+        .operands_in(vec![x, label])
+        .other_side_effects(true),
+    );
+
     let delta = &operand("delta", Int);
 
     ig.push(
@@ -1464,7 +1898,9 @@ pub fn define(
         ====== ======== =========
 
         When this instruction compares integer vectors, it returns a boolean
-        vector of lane-wise comparisons.
+        vector of lane-wise comparisons. Each lane of the result has the same
+        width as the compared lanes, and is all ones when the comparison is
+        true and all zeros when it is false.
         "#,
         )
         .operands_in(vec![Cond, x, y])
@@ -1603,6 +2039,62 @@ pub fn define(
         .operands_out(vec![a]),
     );
 
+    ig.push(
+        Inst::new(
+            "imin",
+            r#"
+        Signed integer minimum: `a := min(x, y)`.
+
+        The operands are interpreted as signed integers. Vector operands are
+        compared lane by lane.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "imax",
+            r#"
+        Signed integer maximum: `a := max(x, y)`.
+
+        The operands are interpreted as signed integers. Vector operands are
+        compared lane by lane.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "umin",
+            r#"
+        Unsigned integer minimum: `a := min(x, y)`.
+
+        The operands are interpreted as unsigned integers. Vector operands are
+        compared lane by lane.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "umax",
+            r#"
+        Unsigned integer maximum: `a := max(x, y)`.
+
+        The operands are interpreted as unsigned integers. Vector operands are
+        compared lane by lane.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
     ig.push(
         Inst::new(
             "udiv",
@@ -1900,6 +2392,62 @@ pub fn define(
         .operands_out(vec![a, b_out]),
     );
 
+    let of = &operand_doc("of", b1, "Signed overflow flag");
+
+    ig.push(
+        Inst::new(
+            "iadd_overflow",
+            r#"
+        Add integers with signed overflow detection.
+
+        Same as `iadd` with an additional output which is true when the sum of
+        `x` and `y`, interpreted as signed integers, can't be represented in
+        the result type.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a, of]),
+    );
+
+    ig.push(
+        Inst::new(
+            "isub_overflow",
+            r#"
+        Subtract integers with signed overflow detection.
+
+        Same as `isub` with an additional output which is true when the
+        difference of `x` and `y`, interpreted as signed integers, can't be
+        represented in the result type.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a, of]),
+    );
+
+    ig.push(
+        Inst::new(
+            "imul_overflow",
+            r#"
+        Multiply integers with signed overflow detection.
+
+        Same as `imul` with an additional output which is true when the
+        product of `x` and `y`, interpreted as signed integers, can't be
+        represented in the result type.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a, of]),
+    );
+
     let bits = &TypeVar::new(
         "bits",
         "Any integer, float, or boolean scalar or vector type",
@@ -2349,7 +2897,9 @@ pub fn define(
         floating point comparisons of the same name.
 
         When this instruction compares floating point vectors, it returns a
-        boolean vector with the results of lane-wise comparisons.
+        boolean vector with the results of lane-wise comparisons. Each lane of
+        the result has the same width as the compared lanes, and is all ones
+        when the comparison is true and all zeros when it is false.
         "#,
         )
         .operands_in(vec![Cond, x, y])
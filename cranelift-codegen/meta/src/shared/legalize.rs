@@ -45,39 +45,53 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
     let band_imm = insts.by_name("band_imm");
     let band_not = insts.by_name("band_not");
     let bint = insts.by_name("bint");
+    let bitcast = insts.by_name("bitcast");
     let bitrev = insts.by_name("bitrev");
+    let bitselect = insts.by_name("bitselect");
+    let bmask = insts.by_name("bmask");
     let bnot = insts.by_name("bnot");
     let bor = insts.by_name("bor");
     let bor_imm = insts.by_name("bor_imm");
     let bor_not = insts.by_name("bor_not");
     let br_icmp = insts.by_name("br_icmp");
     let br_table = insts.by_name("br_table");
+    let brff = insts.by_name("brff");
+    let brnz = insts.by_name("brnz");
+    let brz = insts.by_name("brz");
     let bxor = insts.by_name("bxor");
     let bxor_imm = insts.by_name("bxor_imm");
     let bxor_not = insts.by_name("bxor_not");
     let cls = insts.by_name("cls");
     let clz = insts.by_name("clz");
     let ctz = insts.by_name("ctz");
+    let debug_value = insts.by_name("debug_value");
     let fabs = insts.by_name("fabs");
     let f32const = insts.by_name("f32const");
+    let f32splat = insts.by_name("f32splat");
     let f64const = insts.by_name("f64const");
+    let f64splat = insts.by_name("f64splat");
     let fcopysign = insts.by_name("fcopysign");
     let fcvt_from_sint = insts.by_name("fcvt_from_sint");
     let fcvt_from_uint = insts.by_name("fcvt_from_uint");
+    let fma = insts.by_name("fma");
     let fneg = insts.by_name("fneg");
     let iadd = insts.by_name("iadd");
     let iadd_carry = insts.by_name("iadd_carry");
     let iadd_cin = insts.by_name("iadd_cin");
     let iadd_cout = insts.by_name("iadd_cout");
     let iadd_imm = insts.by_name("iadd_imm");
+    let iadd_overflow = insts.by_name("iadd_overflow");
     let icmp = insts.by_name("icmp");
     let icmp_imm = insts.by_name("icmp_imm");
     let iconcat = insts.by_name("iconcat");
     let iconst = insts.by_name("iconst");
     let ifcmp = insts.by_name("ifcmp");
     let ifcmp_imm = insts.by_name("ifcmp_imm");
+    let imax = insts.by_name("imax");
+    let imin = insts.by_name("imin");
     let imul = insts.by_name("imul");
     let imul_imm = insts.by_name("imul_imm");
+    let imul_overflow = insts.by_name("imul_overflow");
     let ireduce = insts.by_name("ireduce");
     let irsub_imm = insts.by_name("irsub_imm");
     let ishl = insts.by_name("ishl");
@@ -89,6 +103,7 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
     let isub_bin = insts.by_name("isub_bin");
     let isub_borrow = insts.by_name("isub_borrow");
     let isub_bout = insts.by_name("isub_bout");
+    let isub_overflow = insts.by_name("isub_overflow");
     let load = insts.by_name("load");
     let popcnt = insts.by_name("popcnt");
     let rotl = insts.by_name("rotl");
@@ -98,29 +113,44 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
     let sdiv = insts.by_name("sdiv");
     let sdiv_imm = insts.by_name("sdiv_imm");
     let select = insts.by_name("select");
+    let selectif = insts.by_name("selectif");
     let sextend = insts.by_name("sextend");
+    let sload8 = insts.by_name("sload8");
+    let smulhi = insts.by_name("smulhi");
     let sshr = insts.by_name("sshr");
     let sshr_imm = insts.by_name("sshr_imm");
     let srem = insts.by_name("srem");
     let srem_imm = insts.by_name("srem_imm");
+    let stack_load = insts.by_name("stack_load");
+    let stack_store = insts.by_name("stack_store");
     let store = insts.by_name("store");
     let udiv = insts.by_name("udiv");
     let udiv_imm = insts.by_name("udiv_imm");
+    let umax = insts.by_name("umax");
+    let umin = insts.by_name("umin");
     let uextend = insts.by_name("uextend");
     let uload8 = insts.by_name("uload8");
     let uload16 = insts.by_name("uload16");
+    let umulhi = insts.by_name("umulhi");
     let ushr = insts.by_name("ushr");
     let ushr_imm = insts.by_name("ushr_imm");
     let urem = insts.by_name("urem");
     let urem_imm = insts.by_name("urem_imm");
+    let trapff = insts.by_name("trapff");
     let trapif = insts.by_name("trapif");
     let trapnz = insts.by_name("trapnz");
     let trapz = insts.by_name("trapz");
+    let trueff = insts.by_name("trueff");
+    let vselect = insts.by_name("vselect");
 
     // Custom expansions for memory objects.
     expand.custom_legalize(insts.by_name("global_value"), "expand_global_value");
     expand.custom_legalize(insts.by_name("heap_addr"), "expand_heap_addr");
     expand.custom_legalize(insts.by_name("table_addr"), "expand_table_addr");
+    expand.custom_legalize(insts.by_name("table_grow"), "expand_table_grow");
+    expand.custom_legalize(insts.by_name("table_fill"), "expand_table_fill");
+    expand.custom_legalize(insts.by_name("mem_copy"), "expand_mem_copy");
+    expand.custom_legalize(insts.by_name("mem_fill"), "expand_mem_fill");
 
     // Custom expansions for calls.
     expand.custom_legalize(insts.by_name("call"), "expand_call");
@@ -136,15 +166,34 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
     // These expansions require bit-casting or creating constant pool entries.
     expand.custom_legalize(f32const, "expand_fconst");
     expand.custom_legalize(f64const, "expand_fconst");
+    narrow.custom_legalize(f32splat, "narrow_fsplat");
+    narrow.custom_legalize(f64splat, "narrow_fsplat");
+
+    // Fused multiply-add without a target instruction.
+    expand.custom_legalize(fma, "expand_fma");
+    narrow.custom_legalize(fma, "narrow_fma");
+
+    // Selects of vectors the target can't encode are split into halves.
+    narrow.custom_legalize(bitselect, "narrow_bitselect");
+    narrow.custom_legalize(vselect, "narrow_vselect");
+    for &inst in &[imin, imax, umin, umax] {
+        narrow.custom_legalize(inst, "narrow_iminmax");
+    }
 
     // Custom expansions for stack memory accesses.
-    expand.custom_legalize(insts.by_name("stack_load"), "expand_stack_load");
-    expand.custom_legalize(insts.by_name("stack_store"), "expand_stack_store");
+    expand.custom_legalize(stack_load, "expand_stack_load");
+    expand.custom_legalize(stack_store, "expand_stack_store");
+
+    // Bitcasts without an encoding go through memory. The narrow group handles the ones producing
+    // integers wider than a register and vectors.
+    expand.custom_legalize(bitcast, "expand_bitcast");
+    narrow.custom_legalize(bitcast, "expand_bitcast");
 
     // List of immediates.
     let imm64 = immediates.by_name("imm64");
     let ieee32 = immediates.by_name("ieee32");
     let ieee64 = immediates.by_name("ieee64");
+    let floatcc = immediates.by_name("floatcc");
     let intcc = immediates.by_name("intcc");
 
     // List of variables to reuse in patterns.
@@ -240,6 +289,13 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         ],
     );
 
+    // A label can only be attached to one value, so only the low half of a split value is
+    // tracked.
+    narrow.legalize(
+        def!(debug_value(x, c)),
+        vec![def!((xl, xh) = isplit(x)), def!(debug_value(xl, c))],
+    );
+
     // Widen instructions with one input operand.
     for &op in &[bnot, popcnt] {
         for &int_ty in &[I8, I16] {
@@ -271,11 +327,11 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
     };
 
     for bin_op in &[
-        iadd, isub, imul, udiv, urem, band, bor, bxor, band_not, bor_not, bxor_not,
+        iadd, isub, imul, udiv, urem, band, bor, bxor, band_not, bor_not, bxor_not, umin, umax,
     ] {
         widen_two_arg(false, bin_op);
     }
-    for bin_op in &[sdiv, srem] {
+    for bin_op in &[sdiv, srem, imin, imax] {
         widen_two_arg(true, bin_op);
     }
 
@@ -341,6 +397,94 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         );
     }
 
+    // Rotates work on a 32-bit value containing copies of the narrow value, so the rotate amount
+    // doesn't have to be reduced modulo the narrow width.
+    let imm64_8 = Literal::constant(imm64, 8);
+    let imm64_16 = Literal::constant(imm64, 16);
+    for &op in &[rotl, rotl_imm, rotr, rotr_imm] {
+        widen.legalize(
+            def!(a = op.I8(b, c)),
+            vec![
+                def!(x = uextend.I32(b)),
+                def!(d1 = ishl_imm(x, imm64_8)),
+                def!(d2 = bor(x, d1)),
+                def!(d3 = ishl_imm(d2, imm64_16)),
+                def!(d4 = bor(d2, d3)),
+                def!(z = op.I32(d4, c)),
+                def!(a = ireduce.I8(z)),
+            ],
+        );
+
+        widen.legalize(
+            def!(a = op.I16(b, c)),
+            vec![
+                def!(x = uextend.I32(b)),
+                def!(d1 = ishl_imm(x, imm64_16)),
+                def!(d2 = bor(x, d1)),
+                def!(z = op.I32(d2, c)),
+                def!(a = ireduce.I16(z)),
+            ],
+        );
+    }
+
+    for &(int_ty, num) in &[(I8, 8), (I16, 16)] {
+        let imm = Literal::constant(imm64, num);
+
+        widen.legalize(
+            def!(a = umulhi.int_ty(b, c)),
+            vec![
+                def!(x = uextend.I32(b)),
+                def!(y = uextend.I32(c)),
+                def!(z = imul(x, y)),
+                def!(d = ushr_imm(z, imm)),
+                def!(a = ireduce.int_ty(d)),
+            ],
+        );
+
+        widen.legalize(
+            def!(a = smulhi.int_ty(b, c)),
+            vec![
+                def!(x = sextend.I32(b)),
+                def!(y = sextend.I32(c)),
+                def!(z = imul(x, y)),
+                def!(d = sshr_imm(z, imm)),
+                def!(a = ireduce.int_ty(d)),
+            ],
+        );
+    }
+
+    // iconcat and isplit
+    widen.legalize(
+        def!(a = iconcat.I8(xl, xh)),
+        vec![
+            def!(x = uextend.I32(xl)),
+            def!(y = uextend.I32(xh)),
+            def!(z = ishl_imm(y, imm64_8)),
+            def!(b = bor(x, z)),
+            def!(a = ireduce.I16(b)),
+        ],
+    );
+
+    widen.legalize(
+        def!(a = iconcat.I16(xl, xh)),
+        vec![
+            def!(x = uextend.I32(xl)),
+            def!(y = uextend.I32(xh)),
+            def!(z = ishl_imm(y, imm64_16)),
+            def!(a = bor(x, z)),
+        ],
+    );
+
+    widen.legalize(
+        def!((xl, xh) = isplit.I16(x)),
+        vec![
+            def!(y = uextend.I32(x)),
+            def!(xl = ireduce.I8(y)),
+            def!(z = ushr_imm(y, imm64_8)),
+            def!(xh = ireduce.I8(z)),
+        ],
+    );
+
     // iconst
     for &int_ty in &[I8, I16] {
         widen.legalize(
@@ -391,6 +535,24 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         ],
     );
 
+    for &load_op in &[uload8, sload8] {
+        widen.legalize(
+            def!(a = load_op.I16(flags, ptr, offset)),
+            vec![
+                def!(b = load_op.I32(flags, ptr, offset)),
+                def!(a = ireduce(b)),
+            ],
+        );
+    }
+
+    widen.legalize(
+        def!(istore8.I16(flags, a, ptr, offset)),
+        vec![
+            def!(b = uextend.I32(a)),
+            def!(istore8(flags, b, ptr, offset)),
+        ],
+    );
+
     for &int_ty in &[I8, I16] {
         widen.legalize(
             def!(br_table.int_ty(x, y, z)),
@@ -403,8 +565,62 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
             def!(a = bint.int_ty(b)),
             vec![def!(x = bint.I32(b)), def!(a = ireduce.int_ty(x))],
         );
+
+        widen.legalize(
+            def!(a = bmask.int_ty(b)),
+            vec![def!(x = bmask.I32(b)), def!(a = ireduce.int_ty(x))],
+        );
     }
 
+    for &int_ty in &[I8, I16] {
+        widen.legalize(
+            def!(a = select.int_ty(c, b, d)),
+            vec![
+                def!(x = uextend.I32(b)),
+                def!(y = uextend.I32(d)),
+                def!(z = select(c, x, y)),
+                def!(a = ireduce.int_ty(z)),
+            ],
+        );
+
+        widen.legalize(
+            def!(a = selectif.int_ty(cc, flags, b, d)),
+            vec![
+                def!(x = uextend.I32(b)),
+                def!(y = uextend.I32(d)),
+                def!(z = selectif(cc, flags, x, y)),
+                def!(a = ireduce.int_ty(z)),
+            ],
+        );
+    }
+
+    // Testing a value against zero doesn't depend on how it is extended.
+    for &int_ty in &[I8, I16] {
+        for &op in &[trapz, trapnz] {
+            widen.legalize(
+                def!(op.int_ty(b, c)),
+                vec![def!(x = uextend.I32(b)), def!(op(x, c))],
+            );
+        }
+    }
+
+    // The low bits of the extended value are the tracked narrow value.
+    for &int_ty in &[I8, I16] {
+        widen.legalize(
+            def!(debug_value.int_ty(b, c)),
+            vec![def!(x = uextend.I32(b)), def!(debug_value(x, c))],
+        );
+    }
+
+    // Branches with a variable number of arguments can't be expressed in a pattern.
+    widen.custom_legalize(brz, "widen_cond_branch");
+    widen.custom_legalize(brnz, "widen_cond_branch");
+    widen.custom_legalize(br_icmp, "expand_br_icmp");
+
+    // The expanded stack accesses are widened like other loads and stores.
+    widen.custom_legalize(stack_load, "expand_stack_load");
+    widen.custom_legalize(stack_store, "expand_stack_store");
+
     for &int_ty in &[I8, I16] {
         for &op in &[ishl, ishl_imm, ushr, ushr_imm] {
             widen.legalize(
@@ -514,6 +730,137 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         ],
     );
 
+    // The same expansions work for i8 and i16, and the resulting instructions are widened.
+    for &int_ty in &[I8, I16] {
+        widen.legalize(
+            def!((a, c) = iadd_cout.int_ty(x, y)),
+            vec![def!(a = iadd(x, y)), def!(c = icmp(intcc_ult, a, x))],
+        );
+
+        widen.legalize(
+            def!((a, b) = isub_bout.int_ty(x, y)),
+            vec![def!(a = isub(x, y)), def!(b = icmp(intcc_ugt, a, x))],
+        );
+
+        widen.legalize(
+            def!(a = iadd_cin.int_ty(x, y, c)),
+            vec![
+                def!(a1 = iadd(x, y)),
+                def!(c_int = bint(c)),
+                def!(a = iadd(a1, c_int)),
+            ],
+        );
+
+        widen.legalize(
+            def!(a = isub_bin.int_ty(x, y, b)),
+            vec![
+                def!(a1 = isub(x, y)),
+                def!(b_int = bint(b)),
+                def!(a = isub(a1, b_int)),
+            ],
+        );
+
+        widen.legalize(
+            def!((a, c) = iadd_carry.int_ty(x, y, c_in)),
+            vec![
+                def!((a1, c1) = iadd_cout(x, y)),
+                def!(c_int = bint(c_in)),
+                def!((a, c2) = iadd_cout(a1, c_int)),
+                def!(c = bor(c1, c2)),
+            ],
+        );
+
+        widen.legalize(
+            def!((a, b) = isub_borrow.int_ty(x, y, b_in)),
+            vec![
+                def!((a1, b1) = isub_bout(x, y)),
+                def!(b_int = bint(b_in)),
+                def!((a, b2) = isub_bout(a1, b_int)),
+                def!(b = bor(b1, b2)),
+            ],
+        );
+    }
+
+    // Expand arithmetic with signed overflow detection. The sum overflows when the result has a
+    // different sign than both operands, and the difference overflows when the operands have
+    // different signs and the result has a different sign than `x`.
+    let of = var("of");
+    let imm64_0 = Literal::constant(imm64, 0);
+    let intcc_ne = Literal::enumerator_for(intcc, "ne");
+    let intcc_slt = Literal::enumerator_for(intcc, "slt");
+
+    expand.legalize(
+        def!((a, of) = iadd_overflow(x, y)),
+        vec![
+            def!(a = iadd(x, y)),
+            def!(a1 = bxor(a, x)),
+            def!(a2 = bxor(a, y)),
+            def!(a3 = band(a1, a2)),
+            def!(of = icmp_imm(intcc_slt, a3, imm64_0)),
+        ],
+    );
+
+    expand.legalize(
+        def!((a, of) = isub_overflow(x, y)),
+        vec![
+            def!(a = isub(x, y)),
+            def!(a1 = bxor(x, y)),
+            def!(a2 = bxor(a, x)),
+            def!(a3 = band(a1, a2)),
+            def!(of = icmp_imm(intcc_slt, a3, imm64_0)),
+        ],
+    );
+
+    // The product overflows when the high half of the full product isn't the sign extension of
+    // the low half.
+    for &(int_ty, sign_bit) in &[(I32, 31), (I64, 63)] {
+        expand.legalize(
+            def!((a, of) = imul_overflow.int_ty(x, y)),
+            vec![
+                def!(a = imul(x, y)),
+                def!(a1 = smulhi(x, y)),
+                def!(a2 = sshr_imm(a, Literal::constant(imm64, sign_bit))),
+                def!(of = icmp(intcc_ne, a1, a2)),
+            ],
+        );
+    }
+
+    // The result of i8 and i16 arithmetic can't overflow when computed in i32, so it overflows
+    // in the narrow type when it doesn't survive a round trip through the narrow type.
+    for &(inst_overflow, inst) in &[
+        (iadd_overflow, iadd),
+        (isub_overflow, isub),
+        (imul_overflow, imul),
+    ] {
+        for &int_ty in &[I8, I16] {
+            widen.legalize(
+                def!((a, of) = inst_overflow.int_ty(x, y)),
+                vec![
+                    def!(a1 = sextend.I32(x)),
+                    def!(a2 = sextend.I32(y)),
+                    def!(a3 = inst(a1, a2)),
+                    def!(a = ireduce.int_ty(a3)),
+                    def!(a4 = sextend.I32(a)),
+                    def!(of = icmp(intcc_ne, a3, a4)),
+                ],
+            );
+        }
+    }
+
+    // Expand integer minimum and maximum with a comparison and a select.
+    let intcc_sgt = Literal::enumerator_for(intcc, "sgt");
+    for &(inst, cc) in &[
+        (imin, &intcc_slt),
+        (imax, &intcc_sgt),
+        (umin, &intcc_ult),
+        (umax, &intcc_ugt),
+    ] {
+        expand.legalize(
+            def!(a = inst(x, y)),
+            vec![def!(c = icmp(cc, x, y)), def!(a = select(c, x, y))],
+        );
+    }
+
     // Expansions for fcvt_from_{u,s}int for smaller integer types.
     // These use expand and not widen because the controlling type variable for
     // these instructions are f32/f64, which are legalized as part of the expand
@@ -591,6 +938,26 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         );
     }
 
+    //# Expand bitselect using bitwise operations.
+    expand.legalize(
+        def!(a = bitselect(c, x, y)),
+        vec![
+            def!(a1 = band(x, c)),
+            def!(a2 = band_not(y, c)),
+            def!(a = bor(a1, a2)),
+        ],
+    );
+    for &int_ty in &[I8, I16] {
+        widen.legalize(
+            def!(a = bitselect.int_ty(c, x, y)),
+            vec![
+                def!(a1 = band(x, c)),
+                def!(a2 = band_not(y, c)),
+                def!(a = bor(a1, a2)),
+            ],
+        );
+    }
+
     //# Expand bnot using xor.
     let minus_one = Literal::constant(imm64, -1);
     expand.legalize(
@@ -626,8 +993,6 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         ],
     );
 
-    let imm64_8 = Literal::constant(imm64, 8);
-
     widen.legalize(
         def!(a = bitrev.I16(x)),
         vec![
@@ -782,8 +1147,6 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
     )
     .chain_with(expand_id);
 
-    let imm64_0 = Literal::constant(imm64, 0);
-    let intcc_ne = Literal::enumerator_for(intcc, "ne");
     let intcc_eq = Literal::enumerator_for(intcc, "eq");
 
     expand_flags.legalize(
@@ -802,6 +1165,75 @@ pub fn define(insts: &InstructionGroup, immediates: &OperandKinds) -> TransformG
         ],
     );
 
+    // Floating point condition codes.
+    //
+    // Some ISAs can only test a subset of the floating point condition codes on the flags. The
+    // remaining condition codes are the intersection or the union of two condition codes in
+    // that subset.
+    let floatcc_eq = Literal::enumerator_for(floatcc, "eq");
+    let floatcc_ne = Literal::enumerator_for(floatcc, "ne");
+    let floatcc_lt = Literal::enumerator_for(floatcc, "lt");
+    let floatcc_le = Literal::enumerator_for(floatcc, "le");
+    let floatcc_ugt = Literal::enumerator_for(floatcc, "ugt");
+    let floatcc_uge = Literal::enumerator_for(floatcc, "uge");
+    let floatcc_ord = Literal::enumerator_for(floatcc, "ord");
+    let floatcc_uno = Literal::enumerator_for(floatcc, "uno");
+    let floatcc_one = Literal::enumerator_for(floatcc, "one");
+    let floatcc_ueq = Literal::enumerator_for(floatcc, "ueq");
+    let floatcc_ult = Literal::enumerator_for(floatcc, "ult");
+    let floatcc_ule = Literal::enumerator_for(floatcc, "ule");
+    let floatcc_gt = Literal::enumerator_for(floatcc, "gt");
+    let floatcc_ge = Literal::enumerator_for(floatcc, "ge");
+
+    // Ordered condition codes exclude UN.
+    for &(cc, unordered_cc) in &[
+        (&floatcc_eq, &floatcc_ueq),
+        (&floatcc_lt, &floatcc_ult),
+        (&floatcc_le, &floatcc_ule),
+    ] {
+        expand_flags.legalize(
+            def!(a = trueff(cc, f)),
+            vec![
+                def!(a1 = trueff(floatcc_ord, f)),
+                def!(a2 = trueff(unordered_cc, f)),
+                def!(a = band(a1, a2)),
+            ],
+        );
+    }
+
+    // Unordered condition codes include UN.
+    for &(cc, ordered_cc) in &[
+        (&floatcc_ne, &floatcc_one),
+        (&floatcc_ugt, &floatcc_gt),
+        (&floatcc_uge, &floatcc_ge),
+    ] {
+        expand_flags.legalize(
+            def!(a = trueff(cc, f)),
+            vec![
+                def!(a1 = trueff(floatcc_uno, f)),
+                def!(a2 = trueff(ordered_cc, f)),
+                def!(a = bor(a1, a2)),
+            ],
+        );
+    }
+
+    for &cc in &[
+        &floatcc_eq,
+        &floatcc_ne,
+        &floatcc_lt,
+        &floatcc_le,
+        &floatcc_ugt,
+        &floatcc_uge,
+    ] {
+        expand_flags.legalize(
+            def!(trapff(cc, f, c)),
+            vec![def!(a = trueff(cc, f)), def!(trapnz(a, c))],
+        );
+    }
+
+    // Branches with a variable number of arguments can't be expressed in a pattern.
+    expand_flags.custom_legalize(brff, "expand_brff");
+
     expand_flags.build_and_add_to(&mut groups);
 
     // TODO The order of declarations unfortunately matters to be compatible with the Python code.
@@ -19,6 +19,7 @@ pub struct Definitions {
     pub all_instructions: AllInstructions,
     pub instructions: InstructionGroup,
     pub operand_kinds: OperandKinds,
+    pub entities: OperandKinds,
     pub format_registry: FormatRegistry,
     pub transform_groups: TransformGroups,
 }
@@ -69,6 +70,7 @@ pub fn define() -> Definitions {
         all_instructions,
         instructions,
         operand_kinds: immediates,
+        entities,
         format_registry,
         transform_groups,
     }
@@ -15,6 +15,69 @@ pub fn define() -> SettingGroup {
         vec!["default", "best", "fastest"],
     );
 
+    settings.add_enum(
+        "tls_model",
+        r#"
+        Defines the model used to access thread-local storage (TLS) values:
+
+        - none: TLS values are not supported.
+        - elf_gd: ELF general-dynamic model, calling `__tls_get_addr`.
+        - elf_ie: ELF initial-exec model, loading the offset from the GOT.
+        - elf_le: ELF local-exec model, using a link-time constant offset.
+        - macho: Mach-O thread-local variable descriptors.
+        "#,
+        vec!["none", "elf_gd", "elf_ie", "elf_le", "macho"],
+    );
+
+    settings.add_enum(
+        "regalloc_algorithm",
+        r#"
+        Register allocation algorithm:
+
+        - coloring: Precise spilling and register coloring on SSA form.
+        - linear_scan: Faster spilling decisions based on a linear scan
+          over live intervals, at the cost of more spills.
+        - ssa: Experimental. Register coloring on plain SSA form without
+          coalescing EBB arguments first. The EBB arguments are moved into
+          place after coloring.
+        "#,
+        vec!["coloring", "linear_scan", "ssa"],
+    );
+
+    settings.add_enum(
+        "code_padding",
+        r#"
+        Fill used for the alignment padding between functions:
+
+        - trap: Instructions that trap when executed, like `int3` on x86. A
+          stray jump into the padding is caught immediately.
+        - nop: No-op instructions. Use this when the code before the padding
+          may fall through into the next function.
+
+        This is applied by embedders that place several functions in the same
+        code region, such as `cranelift-simplejit`. The padding in front of
+        aligned loop headers inside a function is always made of no-ops.
+        "#,
+        vec!["trap", "nop"],
+    );
+
+    settings.add_enum(
+        "determinism_check",
+        r#"
+        Check that the generated code doesn't depend on hash map iteration
+        order or entity numbering, for reproducible builds:
+
+        - none: Compile each function once.
+        - repeat: Compile each function twice, and panic if the emitted
+          code differs.
+        - shuffle: Like `repeat`, but the EBBs, instructions and values of
+          the second copy of the function are numbered in reverse order.
+
+        This is a debugging aid that doubles the compile time.
+        "#,
+        vec!["none", "repeat", "shuffle"],
+    );
+
     settings.add_bool(
         "enable_verifier",
         r#"
@@ -47,12 +110,37 @@ pub fn define() -> SettingGroup {
         false,
     );
 
+    settings.add_bool(
+        "far_calls",
+        r#"
+            Assume that colocated functions may be out of range of direct calls.
+
+            Generate code that doesn't use PC-relative offsets with a limited range
+            to call or take the address of colocated functions. This is needed when
+            the code and its callees may be placed far apart in memory. Calls to
+            functions that are not declared "colocated" never make this
+            assumption.
+            "#,
+        false,
+    );
+
     settings.add_bool(
         "avoid_div_traps",
         r#"
             Generate explicit checks around native division instructions to avoid
             their trapping.
 
+            When this is disabled, division by zero and signed division overflow
+            rely on the hardware exception raised by the native division
+            instruction (#DE on x86). The embedder must install a signal handler
+            for SIGFPE and map the faulting address to a trap code using the trap
+            table. Native division instructions are recorded there with the
+            `int_divz` trap code, which is also reported for signed division
+            overflow.
+
+            When this is enabled, explicit `trapz` and `trapif` instructions are
+            inserted before the division, so the traps are reported with the
+            `int_divz` and `int_ovf` trap codes like any other trap instruction.
             This is primarily used by SpiderMonkey which doesn't install a signal
             handler for SIGFPE, but expects a SIGILL trap for division by zero.
 
@@ -84,6 +172,19 @@ pub fn define() -> SettingGroup {
         false,
     );
 
+    settings.add_bool(
+        "enable_float_contraction",
+        r#"
+            Allow floating point results to be rounded differently than in the IR
+
+            On targets without fused multiply-add instructions, this lets `fma` be
+            legalized as a separate `fmul` and `fadd`, rounding the intermediate
+            product. When disabled, `fma` is lowered to a call to the `fmaf` or
+            `fma` library functions instead, which compute the exact result.
+            "#,
+        false,
+    );
+
     settings.add_bool("enable_simd", "Enable the use of SIMD instructions.", true);
 
     settings.add_bool(
@@ -152,6 +253,22 @@ pub fn define() -> SettingGroup {
         12,
     );
 
+    settings.add_num(
+        "loop_alignment",
+        r#"
+            Byte alignment of loop headers.
+
+            Branch relaxation pads the code with no-op instructions so that EBBs
+            which are the target of a backward branch start at a multiple of this
+            many bytes. This keeps small hot loops from straddling cache lines or
+            fetch blocks, at the cost of code size.
+
+            The value must be a power of two. The default is 0, which disables
+            loop alignment.
+            "#,
+        0,
+    );
+
     // Jump table options.
 
     settings.add_bool(
@@ -160,5 +277,20 @@ pub fn define() -> SettingGroup {
         true,
     );
 
+    settings.add_bool(
+        "enable_branch_fusion",
+        r#"
+            Keep comparisons next to the conditional branches that use their flags.
+
+            The register allocator can place copies and spills between a
+            comparison and the branch on its result. When this is enabled, the
+            peephole pass moves the comparison back down to the branch, so CPUs
+            which fuse a compare and a conditional jump into a single operation
+            can do so. Disabling this keeps the instruction order chosen by the
+            register allocator, which can be useful when debugging.
+            "#,
+        true,
+    );
+
     settings.build()
 }
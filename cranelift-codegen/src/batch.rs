@@ -0,0 +1,277 @@
+//! Compilation of many functions on a pool of threads.
+//!
+//! Each function is compiled independently with its own `Context`, so a module can be compiled in
+//! parallel. `compile_batch()` distributes the functions among worker threads, and lays out the
+//! results in a single code buffer in the order of the input functions, so the output doesn't
+//! depend on the number of threads or on the scheduling of the workers.
+
+use crate::binemit::{CodeInfo, CodeOffset, RelocRecord, RelocTarget, TrapSink, TrapTable};
+use crate::ir::{ExternalName, Function};
+use crate::isa::TargetIsa;
+use crate::result::CodegenError;
+use crate::timing;
+use crate::Context;
+use core::iter;
+use std::panic;
+use std::sync::mpsc::channel;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::vec::{self, Vec};
+
+/// Alignment of the functions in `Batch::code`.
+pub const FUNCTION_ALIGNMENT: CodeOffset = 16;
+
+/// A function compiled by `compile_batch()`.
+#[derive(Clone, Debug)]
+pub struct BatchFunction {
+    /// The name of the function.
+    pub name: ExternalName,
+    /// Offset of the function in `Batch::code`.
+    pub offset: CodeOffset,
+    /// Information about the code and read-only data of the function.
+    pub info: CodeInfo,
+}
+
+/// A relocation in the code of a `Batch`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BatchReloc {
+    /// Index of the function containing the relocation.
+    pub func: usize,
+    /// The relocation. Its offset, and the offset of an EBB target, are relative to the start of
+    /// `Batch::code`. A jump table target belongs to the function `func`.
+    pub record: RelocRecord,
+}
+
+/// The machine code of the functions compiled by `compile_batch()`.
+#[derive(Clone, Debug)]
+pub struct Batch {
+    /// The machine code and read-only data of all the functions, each aligned to
+    /// `FUNCTION_ALIGNMENT`. The gaps are filled by `TargetIsa::fill_function_padding()`.
+    pub code: Vec<u8>,
+    /// The compiled functions, in the order they were passed to `compile_batch()`.
+    pub functions: Vec<BatchFunction>,
+    /// The relocations of all the functions, in code offset order.
+    pub relocs: Vec<BatchReloc>,
+    /// The trap sites of all the functions, with offsets relative to the start of `code`.
+    pub traps: TrapTable,
+}
+
+impl Batch {
+    /// Get the index of the function containing the code at `offset`, if any.
+    pub fn function_at(&self, offset: CodeOffset) -> Option<usize> {
+        let index = match self.functions.binary_search_by_key(&offset, |f| f.offset) {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let func = &self.functions[index];
+        if offset < func.offset + func.info.total_size {
+            Some(index)
+        } else {
+            None
+        }
+    }
+}
+
+/// An error compiling one of the functions of a batch.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BatchError {
+    /// Index of the function that couldn't be compiled.
+    pub func: usize,
+    /// The compilation error.
+    pub error: CodegenError,
+}
+
+/// The output of a worker thread for one function.
+struct Compiled {
+    name: ExternalName,
+    info: CodeInfo,
+    code: Vec<u8>,
+    relocs: Vec<RelocRecord>,
+    traps: TrapTable,
+}
+
+/// Compile and emit `funcs` for `isa` on `threads` worker threads.
+///
+/// If some functions fail to compile, the error of the first one in `funcs` is returned. The pass
+/// timings of the workers are added to the ones of the current thread, see
+/// `timing::take_current()`.
+pub fn compile_batch(
+    isa: &Arc<dyn TargetIsa>,
+    funcs: Vec<Function>,
+    threads: usize,
+) -> Result<Batch, BatchError> {
+    let count = funcs.len();
+    let queue = Arc::new(Mutex::new(funcs.into_iter().enumerate()));
+    let (result_tx, result_rx) = channel();
+    let handles = (0..threads.max(1).min(count))
+        .map(|_| {
+            let isa = isa.clone();
+            let queue = queue.clone();
+            let results = result_tx.clone();
+            thread::spawn(move || {
+                worker(&*isa, &queue, |index, result| {
+                    results.send((index, result)).unwrap()
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(result_tx);
+
+    let mut results = Vec::with_capacity(count);
+    results.extend(result_rx.iter());
+    for handle in handles {
+        match handle.join() {
+            Ok(times) => timing::add_to_current(&times),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+    results.sort_by_key(|&(index, _)| index);
+
+    let mut batch = Batch {
+        code: Vec::new(),
+        functions: Vec::with_capacity(count),
+        relocs: Vec::new(),
+        traps: TrapTable::new(),
+    };
+    for (func, result) in results {
+        let compiled = result.map_err(|error| BatchError { func, error })?;
+        let start = batch.code.len();
+        let offset = (start as CodeOffset + FUNCTION_ALIGNMENT - 1) & !(FUNCTION_ALIGNMENT - 1);
+        batch.code.resize(offset as usize, 0);
+        isa.fill_function_padding(&mut batch.code[start..]);
+        batch.code.extend_from_slice(&compiled.code);
+
+        for mut record in compiled.relocs {
+            record.offset += offset;
+            if let RelocTarget::Ebb(ref mut ebb_offset) = record.target {
+                *ebb_offset += offset;
+            }
+            batch.relocs.push(BatchReloc { func, record });
+        }
+        for trap in compiled.traps.records() {
+            batch
+                .traps
+                .trap(offset + trap.offset, trap.srcloc, trap.code);
+        }
+        batch.functions.push(BatchFunction {
+            name: compiled.name,
+            offset,
+            info: compiled.info,
+        });
+    }
+    Ok(batch)
+}
+
+/// Compile the functions taken from `queue` until it is empty, and return the pass timings of
+/// the thread.
+fn worker<F>(
+    isa: &dyn TargetIsa,
+    queue: &Mutex<iter::Enumerate<vec::IntoIter<Function>>>,
+    mut done: F,
+) -> timing::PassTimes
+where
+    F: FnMut(usize, Result<Compiled, CodegenError>),
+{
+    let mut ctx = Context::new();
+    loop {
+        // Lock the queue only long enough to take a function.
+        let (index, func) = match queue.lock().unwrap().next() {
+            Some(next) => next,
+            None => break,
+        };
+        ctx.clear();
+        ctx.func = func;
+        done(index, compile_one(&mut ctx, isa));
+    }
+    timing::take_current()
+}
+
+/// Compile and emit the function of `ctx`.
+fn compile_one(ctx: &mut Context, isa: &dyn TargetIsa) -> Result<Compiled, CodegenError> {
+    let info = ctx.compile(isa)?;
+    let mut code = vec![0; info.total_size as usize];
+    let sink = ctx.emit_to_buffer(isa, &mut code);
+    let relocs = sink.relocs;
+    let traps = sink.traps;
+    Ok(Compiled {
+        name: ctx.func.name.clone(),
+        info,
+        code,
+        relocs,
+        traps,
+    })
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::*;
+    use crate::ir::{types, InstBuilder, TrapCode};
+    use crate::test_utils;
+
+    /// A function dividing its parameters if `div`, or calling `g` otherwise.
+    fn function(name: &str, div: bool) -> Function {
+        let mut func = test_utils::function(&[types::I32], &[types::I32], |pos, args| {
+            let result = if div {
+                pos.ins().udiv(args[0], args[0])
+            } else {
+                let sig = test_utils::signature(&[types::I32], &[types::I32]);
+                let callee = test_utils::import_function(pos.func, "g", sig, false);
+                let call = pos.ins().call(callee, &[args[0]]);
+                pos.func.dfg.first_result(call)
+            };
+            pos.ins().return_(&[result]);
+        });
+        func.name = ExternalName::testcase(name);
+        func
+    }
+
+    fn functions() -> Vec<Function> {
+        vec![
+            function("a", true),
+            function("b", false),
+            function("c", true),
+            function("d", false),
+        ]
+    }
+
+    #[test]
+    fn compile() {
+        let isa: Arc<dyn TargetIsa> = Arc::from(test_utils::isa("x86_64"));
+
+        let batch = compile_batch(&isa, functions(), 3).unwrap();
+        let names = batch
+            .functions
+            .iter()
+            .map(|f| f.name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["a", "b", "c", "d"]
+                .iter()
+                .map(ExternalName::testcase)
+                .collect::<Vec<_>>()
+        );
+        for (index, func) in batch.functions.iter().enumerate() {
+            assert_eq!(func.offset % FUNCTION_ALIGNMENT, 0);
+            assert_eq!(batch.function_at(func.offset), Some(index));
+        }
+
+        // The calls of `b` and `d` are relocated, and the divisions of `a` and `c` trap.
+        let funcs = batch.relocs.iter().map(|r| r.func).collect::<Vec<_>>();
+        assert_eq!(funcs, [1, 3]);
+        let traps = batch
+            .traps
+            .records()
+            .iter()
+            .filter(|t| t.code == TrapCode::IntegerDivisionByZero)
+            .map(|t| batch.function_at(t.offset).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(traps, [0, 2]);
+
+        // The output doesn't depend on the number of threads.
+        let serial = compile_batch(&isa, functions(), 1).unwrap();
+        assert_eq!(serial.code, batch.code);
+        assert_eq!(serial.relocs, batch.relocs);
+    }
+}
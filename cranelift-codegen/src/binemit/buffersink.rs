@@ -0,0 +1,269 @@
+//! Code sink that writes binary machine code into a caller-provided buffer.
+//!
+//! JIT embedders typically emit a function into a buffer that will become executable, collect its
+//! relocations and trap sites, and patch the relocations once the addresses of the referenced
+//! symbols are known. A `BufferCodeSink` does all of that without any unsafe code: it writes into
+//! a `&mut [u8]`, records the relocations and traps, and can patch call targets in place later,
+//! for example when a lazily compiled callee becomes available.
+//!
+//! Like `MemoryCodeSink`, the `BufferCodeSink` type is known to `TargetIsa`, so the machine code
+//! generation is specialized for it, see `TargetIsa::emit_function_to_buffer()`.
+
+use super::{Addend, CodeInfo, CodeOffset, CodeSink, Reloc, TrapSink, TrapTable};
+use crate::ir::{ExternalName, JumpTable, SourceLoc, TrapCode};
+use std::vec::Vec;
+
+/// The target of a relocation recorded by a `BufferCodeSink`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocTarget {
+    /// An EBB at the given offset in the function.
+    Ebb(CodeOffset),
+    /// An external symbol.
+    External(ExternalName),
+    /// A jump table.
+    JumpTable(JumpTable),
+}
+
+/// A relocation recorded by a `BufferCodeSink`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelocRecord {
+    /// Offset of the relocated field from the start of the function.
+    pub offset: CodeOffset,
+    /// The kind of relocation.
+    pub reloc: Reloc,
+    /// What the relocation refers to.
+    pub target: RelocTarget,
+    /// Addend to add to the target address.
+    pub addend: Addend,
+}
+
+impl RelocRecord {
+    /// Patch this relocation in `code`, the function's machine code placed at address
+    /// `code_addr`, so it refers to `target_addr`.
+    ///
+    /// The addend is added to `target_addr`. Returns false if the relocation kind can't be patched
+    /// directly, like GOT relocations, or if the value doesn't fit in the relocated field.
+    ///
+    /// Like the emitted code, the field is written in the native byte order of the host.
+    pub fn patch(&self, code: &mut [u8], code_addr: u64, target_addr: u64) -> bool {
+        let value = target_addr.wrapping_add(self.addend as u64);
+        let at = self.offset as usize;
+        match self.reloc {
+            Reloc::Abs4 => {
+                if value > u64::from(u32::max_value()) {
+                    return false;
+                }
+                code[at..at + 4].copy_from_slice(&(value as u32).to_ne_bytes());
+            }
+            Reloc::Abs8 => {
+                code[at..at + 8].copy_from_slice(&value.to_ne_bytes());
+            }
+            Reloc::X86PCRel4 | Reloc::X86CallPCRel4 | Reloc::X86CallPLTRel4 => {
+                let field_addr = code_addr.wrapping_add(u64::from(self.offset));
+                let pcrel = value.wrapping_sub(field_addr) as i64;
+                if pcrel != i64::from(pcrel as i32) {
+                    return false;
+                }
+                code[at..at + 4].copy_from_slice(&(pcrel as i32).to_ne_bytes());
+            }
+            Reloc::X86PCRel8 => {
+                let field_addr = code_addr.wrapping_add(u64::from(self.offset));
+                code[at..at + 8].copy_from_slice(&value.wrapping_sub(field_addr).to_ne_bytes());
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// A `CodeSink` that writes binary machine code into a buffer, and records relocations and traps.
+///
+/// The buffer must be large enough for the whole function, as returned by `Context::compile()`.
+/// Emitting past its end panics.
+pub struct BufferCodeSink<'a> {
+    buffer: &'a mut [u8],
+    offset: CodeOffset,
+
+    /// The relocations of the function, in code offset order.
+    pub relocs: Vec<RelocRecord>,
+
+    /// The trap sites of the function.
+    pub traps: TrapTable,
+
+    /// Information about the generated code and read-only data.
+    pub info: CodeInfo,
+}
+
+impl<'a> BufferCodeSink<'a> {
+    /// Create a new code sink that writes a function to the start of `buffer`.
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            offset: 0,
+            relocs: Vec::new(),
+            traps: TrapTable::new(),
+            info: CodeInfo {
+                code_size: 0,
+                jumptables_size: 0,
+                rodata_size: 0,
+                total_size: 0,
+            },
+        }
+    }
+
+    /// Get the bytes emitted so far.
+    pub fn code(&self) -> &[u8] {
+        &self.buffer[..self.offset as usize]
+    }
+
+    /// Patch every relocation referring to the external symbol `name`, assuming the code is
+    /// placed at address `code_addr` and the symbol at `target_addr`.
+    ///
+    /// Returns the number of patched relocations, or `None` if one of them couldn't be patched.
+    /// This can be called again to retarget calls, for example to replace a stub with the
+    /// compiled callee.
+    pub fn patch_external(
+        &mut self,
+        name: &ExternalName,
+        code_addr: u64,
+        target_addr: u64,
+    ) -> Option<usize> {
+        let mut count = 0;
+        for record in &self.relocs {
+            match record.target {
+                RelocTarget::External(ref n) if n == name => {}
+                _ => continue,
+            }
+            if !record.patch(self.buffer, code_addr, target_addr) {
+                return None;
+            }
+            count += 1;
+        }
+        Some(count)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        let start = self.offset as usize;
+        self.buffer[start..start + bytes.len()].copy_from_slice(bytes);
+        self.offset += bytes.len() as CodeOffset;
+    }
+
+    fn record(&mut self, reloc: Reloc, target: RelocTarget, addend: Addend) {
+        self.relocs.push(RelocRecord {
+            offset: self.offset,
+            reloc,
+            target,
+            addend,
+        });
+    }
+}
+
+impl<'a> CodeSink for BufferCodeSink<'a> {
+    fn offset(&self) -> CodeOffset {
+        self.offset
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.write(&[x]);
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.write(&x.to_ne_bytes());
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.write(&x.to_ne_bytes());
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.write(&x.to_ne_bytes());
+    }
+
+    fn reloc_ebb(&mut self, rel: Reloc, ebb_offset: CodeOffset) {
+        self.record(rel, RelocTarget::Ebb(ebb_offset), 0);
+    }
+
+    fn reloc_external(&mut self, rel: Reloc, name: &ExternalName, addend: Addend) {
+        self.record(rel, RelocTarget::External(name.clone()), addend);
+    }
+
+    fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
+        self.record(rel, RelocTarget::JumpTable(jt), 0);
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        let ofs = self.offset;
+        self.traps.trap(ofs, srcloc, code);
+    }
+
+    fn begin_jumptables(&mut self) {
+        self.info.code_size = self.offset;
+    }
+
+    fn begin_rodata(&mut self) {
+        self.info.jumptables_size = self.offset - self.info.code_size;
+    }
+
+    fn end_codegen(&mut self) {
+        self.info.rodata_size = self.offset - (self.info.jumptables_size + self.info.code_size);
+        self.info.total_size = self.offset;
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::RelocTarget;
+    use crate::binemit::Reloc;
+    use crate::ir::{types, ExternalName, InstBuilder, TrapCode};
+    use crate::test_utils;
+    use crate::Context;
+
+    #[test]
+    fn call() {
+        let isa = test_utils::isa("x86_64");
+        let func = test_utils::function(&[types::I32], &[], |pos, args| {
+            let sig = test_utils::signature(&[], &[]);
+            let callee = test_utils::import_function(pos.func, "callee", sig, true);
+            pos.ins().trapz(args[0], TrapCode::User(0));
+            pos.ins().call(callee, &[]);
+            pos.ins().return_(&[]);
+        });
+
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        let mut buf = vec![0; info.total_size as usize];
+        let mut sink = ctx.emit_to_buffer(&*isa, &mut buf);
+        assert_eq!(sink.info, info);
+        assert_eq!(sink.code().len(), info.total_size as usize);
+        // The prologue and the call also record stack overflow traps.
+        let user_traps = sink
+            .traps
+            .records()
+            .iter()
+            .filter(|r| r.code == TrapCode::User(0))
+            .count();
+        assert_eq!(user_traps, 1);
+
+        // The colocated call has a 4-byte PC-relative relocation.
+        assert_eq!(sink.relocs.len(), 1);
+        let reloc = sink.relocs[0].clone();
+        assert_eq!(reloc.reloc, Reloc::X86CallPCRel4);
+        assert_eq!(
+            reloc.target,
+            RelocTarget::External(ExternalName::testcase("callee"))
+        );
+
+        let callee = ExternalName::testcase("callee");
+        assert_eq!(sink.patch_external(&callee, 0x1000, 0x3000), Some(1));
+        let at = reloc.offset as usize;
+        let mut field = [0; 4];
+        field.copy_from_slice(&buf[at..at + 4]);
+        assert_eq!(
+            i64::from(i32::from_ne_bytes(field)),
+            0x3000 + reloc.addend - (0x1000 + i64::from(reloc.offset))
+        );
+
+        // The target is too far away for a 32-bit displacement.
+        assert!(!reloc.patch(&mut buf, 0x1000, 0x1_0000_0000_0000));
+    }
+}
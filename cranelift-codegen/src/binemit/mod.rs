@@ -3,13 +3,29 @@
 //! The `binemit` module contains code for translating Cranelift's intermediate representation into
 //! binary machine code.
 
+mod buffersink;
 mod memorysink;
+mod peephole;
 mod relaxation;
 mod shrink;
+mod size_report;
+mod sizing;
+mod split;
+mod srcloctable;
+mod traptable;
 
+pub use self::buffersink::{BufferCodeSink, RelocRecord, RelocTarget};
 pub use self::memorysink::{MemoryCodeSink, NullTrapSink, RelocSink, TrapSink};
-pub use self::relaxation::relax_branches;
+pub use self::peephole::peephole_optimize;
+pub use self::relaxation::{relax_branches, relax_split_branches};
 pub use self::shrink::shrink_instructions;
+pub use self::size_report::{code_size_report, CodeSizeReport, EbbSize, InstSize};
+pub use self::sizing::{compute_code_layout, CodeLayout, SizingCodeSink};
+pub(crate) use self::split::emit_split_function;
+pub use self::split::{CodeSection, SplitCodeInfo, SplitFixup};
+pub use self::srcloctable::{SourceLocRecord, SourceLocTable};
+pub(crate) use self::traptable::RecordingTrapSink;
+pub use self::traptable::{CompressedTrapIter, CompressedTrapTable, TrapRecord, TrapTable};
 pub use crate::regalloc::RegDiversions;
 
 use crate::ir::{ExternalName, Function, Inst, JumpTable, SourceLoc, TrapCode};
@@ -23,11 +39,17 @@ use serde::{Deserialize, Serialize};
 /// depends on the *host* platform, not the *target* platform.
 pub type CodeOffset = u32;
 
+/// The largest offset that can appear in a function, including its jump tables and read-only data.
+///
+/// Branches and jump table entries use at most 32-bit signed displacements, so larger functions
+/// are rejected with `CodegenError::CodeTooLarge` by `relax_branches()`.
+pub const MAX_CODE_SIZE: CodeOffset = 0x7fff_ffff;
+
 /// Addend to add to the symbol value.
 pub type Addend = i64;
 
 /// Relocation kinds for every ISA
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub enum Reloc {
     /// absolute 4-byte
@@ -36,6 +58,8 @@ pub enum Reloc {
     Abs8,
     /// x86 PC-relative 4-byte
     X86PCRel4,
+    /// x86 PC-relative 8-byte
+    X86PCRel8,
     /// x86 PC-relative 4-byte offset to trailing rodata
     X86PCRelRodata4,
     /// x86 call to PC-relative 4-byte
@@ -44,12 +68,33 @@ pub enum Reloc {
     X86CallPLTRel4,
     /// x86 GOT PC-relative 4-byte
     X86GOTPCRel4,
+    /// x86 8-byte offset from the GOT base
+    X86GOTOff8,
     /// Arm32 call target
     Arm32Call,
     /// Arm64 call target
     Arm64Call,
+    /// Arm64 `adrp` page of the target address
+    Arm64AdrPage21,
+    /// Arm64 `add` low 12 bits of the target address
+    Arm64AddLo12,
+    /// Arm64 `adrp` page of the target's GOT entry
+    Arm64AdrGotPage21,
+    /// Arm64 `ldr` low 12 bits of the target's GOT entry address
+    Arm64Ld64GotLo12,
     /// RISC-V call target
     RiscvCall,
+
+    /// Elf x86_64 32 bit signed PC relative offset to two GOT entries for GD symbol.
+    ElfX86_64TlsGd,
+    /// Elf x86_64 32 bit signed PC relative offset to the GOT entry holding an IE symbol's
+    /// offset from the thread pointer.
+    ElfX86_64GotTpOff,
+    /// Elf x86_64 32 bit signed offset of a LE symbol from the thread pointer.
+    ElfX86_64TpOff32,
+
+    /// Mach-O x86_64 32 bit signed PC relative offset to a `__thread_vars` entry.
+    MachOX86_64Tlv,
 }
 
 impl fmt::Display for Reloc {
@@ -60,11 +105,22 @@ impl fmt::Display for Reloc {
             Reloc::Abs4 => write!(f, "Abs4"),
             Reloc::Abs8 => write!(f, "Abs8"),
             Reloc::X86PCRel4 => write!(f, "PCRel4"),
+            Reloc::X86PCRel8 => write!(f, "PCRel8"),
             Reloc::X86PCRelRodata4 => write!(f, "PCRelRodata4"),
             Reloc::X86CallPCRel4 => write!(f, "CallPCRel4"),
             Reloc::X86CallPLTRel4 => write!(f, "CallPLTRel4"),
             Reloc::X86GOTPCRel4 => write!(f, "GOTPCRel4"),
+            Reloc::X86GOTOff8 => write!(f, "GOTOff8"),
             Reloc::Arm32Call | Reloc::Arm64Call | Reloc::RiscvCall => write!(f, "Call"),
+            Reloc::Arm64AdrPage21 => write!(f, "AdrPage21"),
+            Reloc::Arm64AddLo12 => write!(f, "AddLo12"),
+            Reloc::Arm64AdrGotPage21 => write!(f, "AdrGotPage21"),
+            Reloc::Arm64Ld64GotLo12 => write!(f, "Ld64GotLo12"),
+
+            Reloc::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
+            Reloc::ElfX86_64GotTpOff => write!(f, "ElfX86_64GotTpOff"),
+            Reloc::ElfX86_64TpOff32 => write!(f, "ElfX86_64TpOff32"),
+            Reloc::MachOX86_64Tlv => write!(f, "MachOX86_64Tlv"),
         }
     }
 }
@@ -74,7 +130,7 @@ impl fmt::Display for Reloc {
 /// The code starts at offset 0 and is followed optionally by relocatable jump tables and copyable
 /// (raw binary) read-only data.  Any padding between sections is always part of the section that
 /// precedes the boundary between the sections.
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct CodeInfo {
     /// Number of bytes of machine code (the code starts at offset 0).
     pub code_size: CodeOffset,
@@ -153,18 +209,24 @@ pub fn bad_encoding(func: &Function, inst: Inst) -> ! {
     );
 }
 
-/// Emit a function to `sink`, given an instruction emitter function.
+/// Emit a function to `sink`, given an instruction emitter function and a function emitting the
+/// given number of bytes of no-op instructions.
 ///
 /// This function is called from the `TargetIsa::emit_function()` implementations with the
-/// appropriate instruction emitter.
-pub fn emit_function<CS, EI>(func: &Function, emit_inst: EI, sink: &mut CS)
+/// appropriate emitters. The no-op emitter is used to fill the gaps in front of EBBs aligned by
+/// `relax_branches()`.
+pub fn emit_function<CS, EI, EP>(func: &Function, emit_inst: EI, emit_padding: EP, sink: &mut CS)
 where
     CS: CodeSink,
     EI: Fn(&Function, Inst, &mut RegDiversions, &mut CS),
+    EP: Fn(CodeOffset, &mut CS),
 {
     let mut divert = RegDiversions::new();
     for ebb in func.layout.ebbs() {
         divert.clear();
+        if sink.offset() < func.offsets[ebb] {
+            emit_padding(func.offsets[ebb] - sink.offset(), sink);
+        }
         debug_assert_eq!(func.offsets[ebb], sink.offset());
         for inst in func.layout.ebb_insts(ebb) {
             emit_inst(func, inst, &mut divert, sink);
@@ -172,18 +234,21 @@ where
     }
 
     sink.begin_jumptables();
+    emit_jump_tables(func, sink);
+    sink.begin_rodata();
+    // TODO: No read-only data (constant pools) at this time.
 
-    // output jump tables
+    sink.end_codegen();
+}
+
+/// Emit the jump tables of `func` to `sink`.
+fn emit_jump_tables<CS: CodeSink + ?Sized>(func: &Function, sink: &mut CS) {
     for (jt, jt_data) in func.jump_tables.iter() {
         let jt_offset = func.jt_offsets[jt];
         for ebb in jt_data.iter() {
+            // All offsets are at most `MAX_CODE_SIZE`, so the casts can't truncate.
             let rel_offset: i32 = func.offsets[*ebb] as i32 - jt_offset as i32;
             sink.put4(rel_offset as u32)
         }
     }
-
-    sink.begin_rodata();
-    // TODO: No read-only data (constant pools) at this time.
-
-    sink.end_codegen();
 }
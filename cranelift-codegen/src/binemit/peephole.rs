@@ -0,0 +1,369 @@
+//! Peephole optimizations after register allocation.
+//!
+//! The register allocator and the prologue/epilogue insertion leave behind some instruction
+//! sequences that can only be simplified once the register assignments are known:
+//!
+//! - A `copy` or `regmove` whose source and destination are the same register does nothing.
+//! - A comparison against zero whose only use is a conditional branch right after it can be
+//!   folded into a `brz` or `brnz` of the compared value, which is a single `test` instruction
+//!   on most ISAs.
+//! - Adjacent stack pointer adjustments in the same direction can be merged into one.
+//! - A comparison whose flags feed a conditional branch is moved down to the branch when the
+//!   register allocator placed other instructions between them, so the CPU can fuse the pair.
+//!   This is controlled by the `enable_branch_fusion` setting.
+//!
+//! The pass keeps the existing register assignments, and a rewrite is only made when the new
+//! instruction has an encoding whose constraints are satisfied by them.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::entity::SecondaryMap;
+use crate::ir::condcodes::IntCC;
+use crate::ir::immediates::Imm64;
+use crate::ir::instructions::InstructionData;
+use crate::ir::{Function, Inst, InstBuilder, Opcode, Value, ValueDef, ValueLoc};
+use crate::isa::{EncInfo, TargetIsa};
+use crate::regalloc::RegDiversions;
+use crate::timing;
+use log::debug;
+use std::vec::Vec;
+
+/// Run the peephole optimizations on `func`, which must have been through register allocation.
+pub fn peephole_optimize(func: &mut Function, isa: &dyn TargetIsa) {
+    let _tt = timing::peephole();
+
+    let encinfo = isa.encoding_info();
+    let fuse_branches = isa.uses_cpu_flags() && isa.flags().enable_branch_fusion();
+    let uses = count_uses(func);
+    let mut divert = RegDiversions::new();
+    let mut pos = FuncCursor::new(func);
+
+    while let Some(_ebb) = pos.next_ebb() {
+        divert.clear();
+        while let Some(inst) = pos.next_inst() {
+            let removed = match pos.func.dfg[inst] {
+                InstructionData::RegMove { src, dst, .. } => src == dst,
+                InstructionData::Unary {
+                    opcode: Opcode::Copy,
+                    arg,
+                } => remove_copy(&mut pos, inst, arg, &divert),
+                InstructionData::UnaryImm { .. } => match sp_adjustment(&pos.func.dfg[inst]) {
+                    Some(delta) => {
+                        merge_sp_adjustment(&mut pos, inst, delta, isa, &encinfo, &divert)
+                    }
+                    None => false,
+                },
+                InstructionData::Branch { .. } | InstructionData::BranchInt { .. } => {
+                    if fuse_branches {
+                        move_flags_to_branch(&mut pos, inst, isa, &divert);
+                    }
+                    fold_zero_test(&mut pos, inst, &uses, isa, &encinfo, &divert);
+                    false
+                }
+                _ => false,
+            };
+
+            if removed {
+                debug!("Removing {}", pos.func.dfg.display_inst(inst, isa));
+                pos.remove_inst_and_step_back();
+            } else {
+                divert.apply(&pos.func.dfg[inst]);
+            }
+        }
+    }
+}
+
+/// Count the number of uses of each value.
+fn count_uses(func: &Function) -> SecondaryMap<Value, u32> {
+    let mut uses = SecondaryMap::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                uses[arg] += 1;
+            }
+        }
+    }
+    uses
+}
+
+/// Find an encoding for `inst` whose constraints are satisfied by the register assignments and
+/// the diversions in effect at `inst`, and assign it. Returns false if there is no such encoding.
+fn reencode(
+    func: &mut Function,
+    inst: Inst,
+    isa: &dyn TargetIsa,
+    encinfo: &EncInfo,
+    divert: &RegDiversions,
+) -> bool {
+    let ctrl_type = func.dfg.ctrl_typevar(inst);
+    let enc = isa
+        .legal_encodings(func, &func.dfg[inst], ctrl_type)
+        .find(|e| encinfo.constraints[e.recipe()].satisfied(inst, divert, func));
+    match enc {
+        Some(enc) => {
+            func.encodings[inst] = enc;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Check if the `copy` instruction `inst` copies `arg` into the register that already holds it.
+/// If so, turn its result into an alias of `arg` and return true so `inst` is removed.
+fn remove_copy(pos: &mut FuncCursor, inst: Inst, arg: Value, divert: &RegDiversions) -> bool {
+    let result = pos.func.dfg.first_result(inst);
+    let loc = pos.func.locations[result];
+    match loc {
+        ValueLoc::Reg(_) if divert.get(arg, &pos.func.locations) == loc => {}
+        _ => return false,
+    }
+
+    // Uses of `result` keep referring to it, and it keeps its location. The alias only serves to
+    // attach it to the definition of `arg`.
+    pos.func.dfg.clear_results(inst);
+    pos.func.dfg.change_to_alias(result, arg);
+    true
+}
+
+/// Get the amount that `data` adds to the stack pointer, if it is an immediate stack pointer
+/// adjustment.
+fn sp_adjustment(data: &InstructionData) -> Option<i64> {
+    match *data {
+        InstructionData::UnaryImm {
+            opcode: Opcode::AdjustSpUpImm,
+            imm,
+        } => Some(imm.into()),
+        InstructionData::UnaryImm {
+            opcode: Opcode::AdjustSpDownImm,
+            imm,
+        } => {
+            let imm: i64 = imm.into();
+            Some(-imm)
+        }
+        _ => None,
+    }
+}
+
+/// If the instruction before `inst` also adjusts the stack pointer in the same direction, merge
+/// the `delta` adjustment of `inst` into it and return true so `inst` is removed.
+///
+/// Adjustments in opposite directions are left alone, so the frame set up by the prologue is kept
+/// even when the function body between it and the epilogue is empty.
+fn merge_sp_adjustment(
+    pos: &mut FuncCursor,
+    inst: Inst,
+    delta: i64,
+    isa: &dyn TargetIsa,
+    encinfo: &EncInfo,
+    divert: &RegDiversions,
+) -> bool {
+    let prev = match pos.func.layout.prev_inst(inst) {
+        Some(prev) => prev,
+        None => return false,
+    };
+    let sum = match sp_adjustment(&pos.func.dfg[prev]) {
+        Some(prev_delta) if (prev_delta < 0) == (delta < 0) => {
+            match prev_delta.checked_add(delta) {
+                Some(sum) => sum,
+                None => return false,
+            }
+        }
+        _ => return false,
+    };
+
+    let old = pos.func.dfg[prev].clone();
+    if sum >= 0 {
+        pos.func.dfg.replace(prev).adjust_sp_up_imm(Imm64::new(sum));
+    } else {
+        pos.func
+            .dfg
+            .replace(prev)
+            .adjust_sp_down_imm(Imm64::new(-sum));
+    }
+    if !reencode(pos.func, prev, isa, encinfo, divert) {
+        pos.func.dfg[prev] = old;
+        return false;
+    }
+    true
+}
+
+/// If `inst` is a `brif` on the flags of an integer comparison earlier in the EBB, move the
+/// comparison down to right before `inst`. This is only possible when the instructions in between
+/// don't use the flags and leave the registers read by the comparison alone.
+fn move_flags_to_branch(
+    pos: &mut FuncCursor,
+    inst: Inst,
+    isa: &dyn TargetIsa,
+    divert: &RegDiversions,
+) {
+    let flags = match pos.func.dfg[inst] {
+        InstructionData::BranchInt {
+            opcode: Opcode::Brif,
+            ref args,
+            ..
+        } => args.first(&pos.func.dfg.value_lists).unwrap(),
+        _ => return,
+    };
+    let cmp = match pos.func.dfg.value_def(flags) {
+        ValueDef::Result(cmp, _) => cmp,
+        ValueDef::Param(..) => return,
+    };
+    match pos.func.dfg[cmp].opcode() {
+        Opcode::Ifcmp | Opcode::IfcmpImm => {}
+        _ => return,
+    }
+    if pos.func.layout.prev_inst(inst) == Some(cmp)
+        || pos.func.layout.inst_ebb(cmp) != pos.func.layout.inst_ebb(inst)
+    {
+        return;
+    }
+
+    // The registers holding the compared values. They are the same at `cmp` and `inst` unless an
+    // instruction in between diverts one of the values, which is checked below.
+    let cmp_args = pos.func.dfg.inst_args(cmp).to_vec();
+    let mut arg_regs = Vec::with_capacity(cmp_args.len());
+    for &arg in &cmp_args {
+        match divert.get(arg, &pos.func.locations) {
+            ValueLoc::Reg(reg) => arg_regs.push(reg),
+            _ => return,
+        }
+    }
+
+    let mut next = pos.func.layout.next_inst(cmp);
+    while let Some(between) = next {
+        if between == inst {
+            break;
+        }
+        let dfg = &pos.func.dfg;
+        if dfg.inst_args(between).contains(&flags) {
+            return;
+        }
+        let writes_arg = match dfg[between] {
+            InstructionData::RegMove { arg, dst, .. }
+            | InstructionData::RegFill { arg, dst, .. } => {
+                cmp_args.contains(&arg) || arg_regs.contains(&dst)
+            }
+            InstructionData::RegSpill { arg, .. } => cmp_args.contains(&arg),
+            InstructionData::CopySpecial { dst, .. } => arg_regs.contains(&dst),
+            _ => dfg
+                .inst_results(between)
+                .iter()
+                .any(|&result| match pos.func.locations[result] {
+                    ValueLoc::Reg(reg) => arg_regs.contains(&reg),
+                    _ => false,
+                }),
+        };
+        if writes_arg {
+            return;
+        }
+        next = pos.func.layout.next_inst(between);
+    }
+
+    debug!(
+        "Moving {} next to {}",
+        pos.func.dfg.display_inst(cmp, isa),
+        pos.func.dfg.display_inst(inst, isa)
+    );
+    pos.func.layout.remove_inst(cmp);
+    pos.func.layout.insert_inst(cmp, inst);
+}
+
+/// If `inst` is a branch on the result of comparing a value against zero right before it, branch
+/// on the value itself instead and remove the comparison.
+fn fold_zero_test(
+    pos: &mut FuncCursor,
+    inst: Inst,
+    uses: &SecondaryMap<Value, u32>,
+    isa: &dyn TargetIsa,
+    encinfo: &EncInfo,
+    divert: &RegDiversions,
+) {
+    let (opcode, cond, cmp_value) = match pos.func.dfg[inst] {
+        InstructionData::Branch {
+            opcode: opcode @ Opcode::Brz,
+            ref args,
+            ..
+        }
+        | InstructionData::Branch {
+            opcode: opcode @ Opcode::Brnz,
+            ref args,
+            ..
+        } => (opcode, None, args.first(&pos.func.dfg.value_lists).unwrap()),
+        InstructionData::BranchInt {
+            opcode: Opcode::Brif,
+            cond,
+            ref args,
+            ..
+        } => (
+            Opcode::Brif,
+            Some(cond),
+            args.first(&pos.func.dfg.value_lists).unwrap(),
+        ),
+        _ => return,
+    };
+
+    let cmp = match pos.func.dfg.value_def(cmp_value) {
+        ValueDef::Result(cmp, 0) if pos.func.layout.prev_inst(inst) == Some(cmp) => cmp,
+        _ => return,
+    };
+    if uses[cmp_value] != 1 {
+        return;
+    }
+
+    // Find the compared value, and whether the branch is taken when it is zero.
+    let (arg, branch_if_zero) = match (pos.func.dfg[cmp].clone(), cond) {
+        (
+            InstructionData::IntCompareImm {
+                opcode: Opcode::IcmpImm,
+                cond,
+                arg,
+                imm,
+            },
+            None,
+        ) if imm == Imm64::new(0) => {
+            // The comparison writes its result to a register, which could be the register that
+            // held `arg`.
+            let arg_loc = divert.get(arg, &pos.func.locations);
+            if arg_loc == pos.func.locations[cmp_value] {
+                return;
+            }
+            match cond {
+                IntCC::Equal => (arg, opcode == Opcode::Brnz),
+                IntCC::NotEqual => (arg, opcode == Opcode::Brz),
+                _ => return,
+            }
+        }
+        (
+            InstructionData::BinaryImm {
+                opcode: Opcode::IfcmpImm,
+                arg,
+                imm,
+            },
+            Some(cond),
+        ) if imm == Imm64::new(0) => match cond {
+            IntCC::Equal => (arg, true),
+            IntCC::NotEqual => (arg, false),
+            _ => return,
+        },
+        _ => return,
+    };
+
+    let old = pos.func.dfg[inst].clone();
+    let destination = old.branch_destination().unwrap();
+    let args = pos.func.dfg.inst_variable_args(inst).to_vec();
+    if branch_if_zero {
+        pos.func.dfg.replace(inst).brz(arg, destination, &args);
+    } else {
+        pos.func.dfg.replace(inst).brnz(arg, destination, &args);
+    }
+    if !reencode(pos.func, inst, isa, encinfo, divert) {
+        pos.func.dfg[inst] = old;
+        return;
+    }
+
+    debug!(
+        "Folded {} into {}",
+        pos.func.dfg.display_inst(cmp, isa),
+        pos.func.dfg.display_inst(inst, isa)
+    );
+    pos.func.layout.remove_inst(cmp);
+}
@@ -26,16 +26,64 @@
 //!     jump ebb17
 //! ebb23:
 //! ```
+//!
+//! # Cold EBBs
+//!
+//! EBBs marked as cold in the layout, like the trap EBBs created by the legalizer, are moved to
+//! the end of the function before computing offsets, so the hot code is contiguous. A conditional
+//! branch followed by a jump to a cold EBB is inverted when possible, so its hot destination can
+//! fall through:
+//!
+//! ```clif
+//!     brz v1, ebb2
+//!     jump ebb1 ; cold
+//! ebb2:
+//! ```
+//!
+//! becomes:
+//!
+//! ```clif
+//!     brnz v1, ebb1
+//!     fallthrough ebb2
+//! ```
+//!
+//! EBBs that use a jump table or are the target of one are not moved, since jump tables are
+//! emitted with the hot code.
+//!
+//! # Hot and cold sections
+//!
+//! `relax_split_branches()` places the cold EBBs in a separate code section, see
+//! `binemit::SplitCodeInfo`. Their offsets start after the hot code and its jump tables, and no
+//! fall-through is inserted between the sections. Branches between the sections are relaxed as
+//! if their destination was as far away as possible, since the sections can be placed anywhere.
+//! Loop headers in the cold section are not aligned.
+//!
+//! # Loop alignment
+//!
+//! When the `loop_alignment` setting is non-zero, EBBs that are the target of a backward branch
+//! in the layout are placed at a multiple of that many bytes. The gap in front of such a loop
+//! header is filled with no-op instructions by `emit_function()`. The padding is included in the
+//! offsets used for branch relaxation, so branches across it are relaxed as needed.
+//!
+//! # Size limit
+//!
+//! Branches and jump table entries are encoded with at most 32-bit signed displacements, so every
+//! offset in a function, including its jump tables and read-only data, must be less than 2 GB.
+//! Functions that are larger than `MAX_CODE_SIZE` fail with `CodegenError::CodeTooLarge` instead
+//! of getting truncated offsets. So do branches that can't be relaxed to reach their destination.
 
-use crate::binemit::{CodeInfo, CodeOffset};
+use crate::binemit::{CodeInfo, CodeOffset, CodeSection, SplitCodeInfo, SplitFixup, MAX_CODE_SIZE};
 use crate::cursor::{Cursor, FuncCursor};
-use crate::ir::{Function, InstructionData, Opcode};
+use crate::ebb_layout::invert_branch;
+use crate::entity::EntitySet;
+use crate::ir::{Ebb, Function, Inst, InstructionData, Opcode};
 use crate::isa::{EncInfo, TargetIsa};
 use crate::iterators::IteratorExtras;
 use crate::regalloc::RegDiversions;
+use crate::result::{CodegenError, CodegenResult};
 use crate::timing;
-use crate::CodegenResult;
 use log::debug;
+use std::vec::Vec;
 
 /// Relax branches and compute the final layout of EBB headers in `func`.
 ///
@@ -43,14 +91,85 @@ use log::debug;
 pub fn relax_branches(func: &mut Function, isa: &dyn TargetIsa) -> CodegenResult<CodeInfo> {
     let _tt = timing::relax_branches();
 
+    // Move the cold EBBs out of the way, but keep them in the same section as the hot code.
+    move_cold_ebbs(func, isa);
+    let (info, _) = relax(func, isa, &EntitySet::new())?;
+    Ok(info)
+}
+
+/// Relax branches and compute the final layout of EBB headers in `func`, with the cold EBBs in a
+/// separate code section.
+///
+/// Fill in the `func.offsets` table so the function is ready for
+/// `TargetIsa::emit_split_function()`. The branches between the sections use 32-bit
+/// displacements, and fail with `CodegenError::ImplLimitExceeded` if the ISA can't encode them as
+/// a trailing displacement field.
+pub fn relax_split_branches(
+    func: &mut Function,
+    isa: &dyn TargetIsa,
+) -> CodegenResult<SplitCodeInfo> {
+    let _tt = timing::relax_branches();
+
+    let cold = move_cold_ebbs(func, isa);
+    let (hot, end) = relax(func, isa, &cold)?;
+    let fixups = split_fixups(func, isa, &cold, hot.total_size)?;
+    Ok(SplitCodeInfo {
+        hot,
+        cold_size: end - hot.total_size,
+        fixups,
+    })
+}
+
+/// Compute the offsets of `func` with the EBBs in `cold` placed after the jump tables.
+///
+/// Returns information about the code in front of the cold EBBs, and the end offset of the
+/// function.
+fn relax(
+    func: &mut Function,
+    isa: &dyn TargetIsa,
+    cold: &EntitySet<Ebb>,
+) -> CodegenResult<(CodeInfo, CodeOffset)> {
     let encinfo = isa.encoding_info();
 
     // Clear all offsets so we can recognize EBBs that haven't been visited yet.
     func.offsets.clear();
     func.offsets.resize(func.dfg.num_ebbs());
 
-    // Start by inserting fall through instructions.
-    fallthroughs(func);
+    // Insert fall through instructions, but not between sections.
+    fallthroughs(func, cold);
+
+    let alignment = CodeOffset::from(isa.flags().loop_alignment());
+    debug_assert!(
+        alignment == 0 || alignment.is_power_of_two(),
+        "loop_alignment must be a power of two"
+    );
+    let loop_headers = if alignment > 1 {
+        loop_headers(func)
+    } else {
+        EntitySet::new()
+    };
+
+    // The jump tables follow the hot code, so the cold EBBs start after them.
+    let mut jumptables_size = 0;
+    for jt_data in func.jump_tables.values() {
+        // TODO: this should be computed based on the min size needed to hold
+        //        the furthest branch.
+        let jt_size = (jt_data.len() as u64) * 4;
+        if jt_size > u64::from(MAX_CODE_SIZE) {
+            return Err(CodegenError::CodeTooLarge);
+        }
+        jumptables_size = add_offset(jumptables_size, jt_size as CodeOffset)?;
+    }
+    let first_cold = func.layout.ebbs().find(|&ebb| cold.contains(ebb));
+    let ebb_offset = |offset: CodeOffset, ebb: Ebb| {
+        if Some(ebb) == first_cold {
+            add_offset(offset, jumptables_size)
+        } else if loop_headers.contains(ebb) && !cold.contains(ebb) {
+            align_offset(offset, alignment)
+        } else {
+            Ok(offset)
+        }
+    };
 
     let mut offset = 0;
     let mut divert = RegDiversions::new();
@@ -60,11 +179,12 @@ pub fn relax_branches(func: &mut Function, isa: &dyn TargetIsa) -> CodegenResult
         let mut cur = FuncCursor::new(func);
         while let Some(ebb) = cur.next_ebb() {
             divert.clear();
+            offset = ebb_offset(offset, ebb)?;
             cur.func.offsets[ebb] = offset;
             while let Some(inst) = cur.next_inst() {
                 divert.apply(&cur.func.dfg[inst]);
                 let enc = cur.func.encodings[inst];
-                offset += encinfo.byte_size(enc, inst, &divert, &cur.func);
+                offset = add_offset(offset, encinfo.byte_size(enc, inst, &divert, &cur.func))?;
             }
         }
     }
@@ -80,6 +200,8 @@ pub fn relax_branches(func: &mut Function, isa: &dyn TargetIsa) -> CodegenResult
         while let Some(ebb) = cur.next_ebb() {
             divert.clear();
 
+            offset = ebb_offset(offset, ebb)?;
+
             // Record the offset for `ebb` and make sure we iterate until offsets are stable.
             if cur.func.offsets[ebb] != offset {
                 cur.func.offsets[ebb] = offset;
@@ -95,49 +217,266 @@ pub fn relax_branches(func: &mut Function, isa: &dyn TargetIsa) -> CodegenResult
                 // range.
                 if let Some(range) = encinfo.branch_range(enc) {
                     if let Some(dest) = cur.func.dfg[inst].branch_destination() {
-                        let dest_offset = cur.func.offsets[dest];
+                        // The distance to the other section is unknown, so assume the worst.
+                        let dest_offset = if cold.contains(dest) != cold.contains(ebb) {
+                            far_offset(offset)
+                        } else {
+                            cur.func.offsets[dest]
+                        };
                         if !range.contains(offset, dest_offset) {
-                            offset +=
-                                relax_branch(&mut cur, &divert, offset, dest_offset, &encinfo, isa);
+                            let size = relax_branch(
+                                &mut cur,
+                                &divert,
+                                offset,
+                                dest_offset,
+                                &encinfo,
+                                isa,
+                            )?;
+                            offset = add_offset(offset, size)?;
                             continue;
                         }
                     }
                 }
 
-                offset += encinfo.byte_size(enc, inst, &divert, &cur.func);
+                offset = add_offset(offset, encinfo.byte_size(enc, inst, &divert, &cur.func))?;
             }
         }
     }
 
-    let code_size = offset;
-    let jumptables = offset;
-
+    let code_size = match first_cold {
+        Some(ebb) => func.offsets[ebb] - jumptables_size,
+        None => offset,
+    };
+    let jumptables = code_size;
+    let mut jt_offset = jumptables;
     for (jt, jt_data) in func.jump_tables.iter() {
-        func.jt_offsets[jt] = offset;
-        // TODO: this should be computed based on the min size needed to hold
-        //        the furthest branch.
-        offset += jt_data.len() as u32 * 4;
+        func.jt_offsets[jt] = jt_offset;
+        jt_offset += (jt_data.len() as CodeOffset) * 4;
     }
-
-    let jumptables_size = offset - jumptables;
-    let rodata = offset;
+    let rodata = add_offset(jumptables, jumptables_size)?;
 
     // TODO: Once we have constant pools we'll do some processing here to update offset.
 
-    let rodata_size = offset - rodata;
+    let rodata_size = 0;
+    let total_size = rodata + rodata_size;
+    let end = if first_cold.is_some() {
+        offset
+    } else {
+        total_size
+    };
 
-    Ok(CodeInfo {
-        code_size,
-        jumptables_size,
-        rodata_size,
-        total_size: offset,
-    })
+    Ok((
+        CodeInfo {
+            code_size,
+            jumptables_size,
+            rodata_size,
+            total_size,
+        },
+        end,
+    ))
+}
+
+/// Get a branch destination offset that is as far from `offset` as possible.
+///
+/// The distance between the hot and cold sections is only known once they are placed in memory,
+/// so branches between them must be able to reach any offset in a function.
+fn far_offset(offset: CodeOffset) -> CodeOffset {
+    if offset < MAX_CODE_SIZE / 2 {
+        MAX_CODE_SIZE
+    } else {
+        0
+    }
+}
+
+/// Find the branches between the hot section and the `cold` EBBs starting at `cold_start`.
+fn split_fixups(
+    func: &Function,
+    isa: &dyn TargetIsa,
+    cold: &EntitySet<Ebb>,
+    cold_start: CodeOffset,
+) -> CodegenResult<Vec<SplitFixup>> {
+    let encinfo = isa.encoding_info();
+    let section_start = |ebb| {
+        if cold.contains(ebb) {
+            (CodeSection::Cold, cold_start)
+        } else {
+            (CodeSection::Hot, 0)
+        }
+    };
+
+    let mut fixups = Vec::new();
+    let mut divert = RegDiversions::new();
+    for ebb in func.layout.ebbs() {
+        divert.clear();
+        let (section, start) = section_start(ebb);
+        let mut offset = func.offsets[ebb];
+        for inst in func.layout.ebb_insts(ebb) {
+            divert.apply(&func.dfg[inst]);
+            let enc = func.encodings[inst];
+            let size = encinfo.byte_size(enc, inst, &divert, func);
+            offset += size;
+            let dest = match func.dfg[inst].branch_destination() {
+                Some(dest) if cold.contains(dest) != cold.contains(ebb) => dest,
+                _ => continue,
+            };
+
+            // The fixup is a 32-bit displacement at the end of the branch, relative to its end.
+            match encinfo.branch_range(enc) {
+                Some(range) if range.bits == 32 && CodeOffset::from(range.origin) == size => {}
+                _ => return Err(CodegenError::ImplLimitExceeded),
+            }
+            let (_, dest_start) = section_start(dest);
+            fixups.push(SplitFixup {
+                section,
+                offset: offset - 4 - start,
+                target: func.offsets[dest] - dest_start,
+            });
+        }
+    }
+    Ok(fixups)
+}
+
+/// Find the EBBs that are the target of a branch at or after their own position in the layout.
+fn loop_headers(func: &Function) -> EntitySet<Ebb> {
+    let mut visited = EntitySet::new();
+    let mut headers = EntitySet::new();
+    for ebb in func.layout.ebbs() {
+        visited.insert(ebb);
+        for inst in func.layout.ebb_insts(ebb) {
+            if let Some(dest) = func.dfg[inst].branch_destination() {
+                if visited.contains(dest) {
+                    headers.insert(dest);
+                }
+            }
+        }
+    }
+    headers
+}
+
+/// Round `offset` up to a multiple of `alignment`, which must be a power of two.
+fn align_offset(offset: CodeOffset, alignment: CodeOffset) -> CodegenResult<CodeOffset> {
+    let aligned = add_offset(offset, alignment - 1)? & !(alignment - 1);
+    Ok(aligned)
+}
+
+/// Advance `offset` by `size` bytes, failing if the result exceeds `MAX_CODE_SIZE`.
+fn add_offset(offset: CodeOffset, size: CodeOffset) -> CodegenResult<CodeOffset> {
+    match offset.checked_add(size) {
+        Some(end) if end <= MAX_CODE_SIZE => Ok(end),
+        _ => Err(CodegenError::CodeTooLarge),
+    }
+}
+
+/// Move the cold EBBs to the end of the layout, keeping their relative order.
+///
+/// Branches to cold EBBs are then inverted where that lets the hot path fall through. Returns the
+/// set of moved EBBs.
+fn move_cold_ebbs(func: &mut Function, isa: &dyn TargetIsa) -> EntitySet<Ebb> {
+    // The jump tables are emitted with the hot code, so the EBBs using them are kept in place.
+    let mut jt_ebbs = EntitySet::new();
+    for jt_data in func.jump_tables.values() {
+        for &ebb in jt_data.iter() {
+            jt_ebbs.insert(ebb);
+        }
+    }
+    let entry = func.layout.entry_block();
+    let cold: Vec<Ebb> = func
+        .layout
+        .ebbs()
+        .filter(|&ebb| {
+            func.layout.is_cold(ebb)
+                && Some(ebb) != entry
+                && !jt_ebbs.contains(ebb)
+                && !func
+                    .layout
+                    .ebb_insts(ebb)
+                    .any(|inst| uses_jump_table(func, inst))
+        })
+        .collect();
+    let mut moved = EntitySet::new();
+    if cold.is_empty() {
+        return moved;
+    }
+    for &ebb in &cold {
+        moved.insert(ebb);
+        let insts: Vec<Inst> = func.layout.ebb_insts(ebb).collect();
+        for &inst in &insts {
+            func.layout.remove_inst(inst);
+        }
+        func.layout.remove_ebb(ebb);
+        func.layout.append_ebb(ebb);
+        for inst in insts {
+            func.layout.append_inst(inst, ebb);
+        }
+    }
+
+    let encinfo = isa.encoding_info();
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        let jump = match func.layout.last_inst(ebb) {
+            Some(inst) if func.dfg[inst].opcode() == Opcode::Jump => inst,
+            _ => continue,
+        };
+        let branch = match func.layout.prev_inst(jump) {
+            Some(inst) => inst,
+            None => continue,
+        };
+        let cold_jump = match func.dfg[jump].branch_destination() {
+            Some(dest) => func.layout.is_cold(dest),
+            None => false,
+        };
+        let hot_branch = match func.dfg[branch].branch_destination() {
+            Some(dest) => !func.layout.is_cold(dest),
+            None => false,
+        };
+        if !cold_jump || !hot_branch || !invert_branch(func, branch, jump) {
+            continue;
+        }
+
+        // The operands are already assigned to registers, so the inverted branch must use an
+        // encoding with the same constraints.
+        let enc = func.encodings[branch];
+        let ctrl_type = func.dfg.ctrl_typevar(branch);
+        let new_enc = isa
+            .legal_encodings(func, &func.dfg[branch], ctrl_type)
+            .find(|&e| encinfo.operand_constraints(e) == encinfo.operand_constraints(enc));
+        match new_enc {
+            Some(e) => {
+                debug!("Inverted {} in {}", func.dfg.display_inst(branch, isa), ebb);
+                func.encodings[branch] = e;
+                if let Some(weight) = func.branch_weights[branch] {
+                    func.branch_weights[branch] = Some(weight.inverse());
+                }
+            }
+            None => {
+                invert_branch(func, branch, jump);
+            }
+        }
+    }
+    moved
+}
+
+/// Does `inst` refer to a jump table?
+fn uses_jump_table(func: &Function, inst: Inst) -> bool {
+    match func.dfg[inst] {
+        InstructionData::BranchTable { .. }
+        | InstructionData::BranchTableBase { .. }
+        | InstructionData::BranchTableEntry { .. }
+        | InstructionData::IndirectJump { .. } => true,
+        _ => false,
+    }
 }
 
 /// Convert `jump` instructions to `fallthrough` instructions where possible and verify that any
 /// existing `fallthrough` instructions are correct.
-fn fallthroughs(func: &mut Function) {
+///
+/// Jumps between the hot code and the `cold` EBBs are kept, since the sections are emitted
+/// separately.
+fn fallthroughs(func: &mut Function, cold: &EntitySet<Ebb>) {
     for (ebb, succ) in func.layout.ebbs().adjacent_pairs() {
+        if cold.contains(ebb) != cold.contains(succ) {
+            continue;
+        }
         let term = func.layout.last_inst(ebb).expect("EBB has no terminator.");
         if let InstructionData::Jump {
             ref mut opcode,
@@ -167,7 +506,7 @@ fn fallthroughs(func: &mut Function) {
 /// Relax the branch instruction at `cur` so it can cover the range `offset - dest_offset`.
 ///
 /// Return the size of the replacement instructions up to and including the location where `cur` is
-/// left.
+/// left, or `CodeTooLarge` if no encoding of the branch can reach its destination.
 fn relax_branch(
     cur: &mut FuncCursor,
     divert: &RegDiversions,
@@ -175,7 +514,7 @@ fn relax_branch(
     dest_offset: CodeOffset,
     encinfo: &EncInfo,
     isa: &dyn TargetIsa,
-) -> CodeOffset {
+) -> CodegenResult<CodeOffset> {
     let inst = cur.current_inst().unwrap();
     debug!(
         "Relaxing [{}] {} for {:#x}-{:#x} range",
@@ -214,7 +553,7 @@ fn relax_branch(
     {
         debug_assert!(enc != cur.func.encodings[inst]);
         cur.func.encodings[inst] = enc;
-        return encinfo.byte_size(enc, inst, &divert, &cur.func);
+        return Ok(encinfo.byte_size(enc, inst, &divert, &cur.func));
     }
 
     // Note: On some RISC ISAs, conditional branches have shorter range than unconditional
@@ -235,6 +574,36 @@ fn relax_branch(
     // predecessor could contain kill points for some values that are live in this EBB, and
     // diversions are not automatically cancelled when the live range of a value ends.
 
-    // This assumes solution 2. above:
-    panic!("No branch in range for {:#x}-{:#x}", offset, dest_offset);
+    // This assumes solution 2. above, so a branch that can't be relaxed means the function is too
+    // large for the ISA.
+    debug!("No branch in range for {:#x}-{:#x}", offset, dest_offset);
+    Err(CodegenError::CodeTooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_offset, align_offset};
+    use crate::binemit::MAX_CODE_SIZE;
+    use crate::result::CodegenError;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(add_offset(0, 4), Ok(4));
+        assert_eq!(add_offset(MAX_CODE_SIZE - 4, 4), Ok(MAX_CODE_SIZE));
+        assert_eq!(
+            add_offset(MAX_CODE_SIZE - 4, 5),
+            Err(CodegenError::CodeTooLarge)
+        );
+        assert_eq!(
+            add_offset(4, u32::max_value()),
+            Err(CodegenError::CodeTooLarge)
+        );
+
+        assert_eq!(align_offset(5, 16), Ok(16));
+        assert_eq!(align_offset(16, 16), Ok(16));
+        assert_eq!(
+            align_offset(MAX_CODE_SIZE - 3, 16),
+            Err(CodegenError::CodeTooLarge)
+        );
+    }
 }
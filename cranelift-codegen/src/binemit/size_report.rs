@@ -0,0 +1,202 @@
+//! Code size reports.
+//!
+//! Once branches have been relaxed, the encoded size of every instruction in a function is known.
+//! A `CodeSizeReport` breaks the size of the function down by EBB and by instruction, and counts
+//! the bytes taken up by jump tables, constant pools, and relocations. Each instruction is
+//! reported with its source location, so embedders can attribute changes in code size to the
+//! parts of the source program responsible for them.
+
+use crate::binemit::{
+    Addend, CodeInfo, CodeOffset, MemoryCodeSink, NullTrapSink, Reloc, RelocSink,
+};
+use crate::ir::{Ebb, ExternalName, Function, Inst, JumpTable, SourceLoc};
+use crate::isa::TargetIsa;
+use std::vec::Vec;
+
+/// The encoded size of an EBB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EbbSize {
+    /// The EBB.
+    pub ebb: Ebb,
+
+    /// Offset of the EBB header from the start of the function.
+    pub offset: CodeOffset,
+
+    /// Number of bytes of alignment padding in front of the EBB.
+    pub padding: CodeOffset,
+
+    /// Number of bytes of instructions in the EBB.
+    pub size: CodeOffset,
+}
+
+/// The encoded size of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstSize {
+    /// The instruction.
+    pub inst: Inst,
+
+    /// Offset of the instruction from the start of the function.
+    pub offset: CodeOffset,
+
+    /// Number of bytes of machine code for the instruction.
+    pub size: CodeOffset,
+
+    /// The source location of the instruction.
+    pub srcloc: SourceLoc,
+}
+
+/// A breakdown of the size of the code generated for a function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeSizeReport {
+    /// The size of each EBB, in layout order.
+    pub ebbs: Vec<EbbSize>,
+
+    /// The size of each instruction, in layout order.
+    pub insts: Vec<InstSize>,
+
+    /// Number of bytes of jump tables following the code.
+    pub jumptables_size: CodeOffset,
+
+    /// Number of bytes of constant pool data following the jump tables.
+    pub constant_pool_size: CodeOffset,
+
+    /// Number of relocations in the function.
+    pub relocations: usize,
+
+    /// Number of bytes patched by the relocations.
+    pub relocation_bytes: CodeOffset,
+}
+
+impl CodeSizeReport {
+    /// Get the number of bytes of instructions attributed to `srcloc`.
+    pub fn srcloc_size(&self, srcloc: SourceLoc) -> CodeOffset {
+        self.insts
+            .iter()
+            .filter(|i| i.srcloc == srcloc)
+            .map(|i| i.size)
+            .sum()
+    }
+}
+
+/// Compute a code size report for `func`, which must have been through branch relaxation.
+///
+/// The `info` argument is the code layout returned by `relax_branches()`. The function is emitted
+/// into a scratch buffer to count its relocations.
+pub fn code_size_report(func: &Function, info: &CodeInfo, isa: &dyn TargetIsa) -> CodeSizeReport {
+    let encinfo = isa.encoding_info();
+    let mut ebbs = Vec::new();
+    let mut insts = Vec::new();
+    let mut end = 0;
+
+    for ebb in func.layout.ebbs() {
+        let offset = func.offsets[ebb];
+        let mut size = 0;
+        for (inst_offset, inst, inst_size) in func.inst_offsets(ebb, &encinfo) {
+            insts.push(InstSize {
+                inst,
+                offset: inst_offset,
+                size: inst_size,
+                srcloc: func.srclocs[inst],
+            });
+            size += inst_size;
+        }
+        ebbs.push(EbbSize {
+            ebb,
+            offset,
+            padding: offset - end,
+            size,
+        });
+        end = offset + size;
+    }
+
+    let mut relocs = RelocCounter {
+        relocations: 0,
+        bytes: 0,
+    };
+    let mut mem = vec![0u8; info.total_size as usize];
+    let mut traps = NullTrapSink {};
+    // Safe because `mem` holds the `total_size` bytes that are emitted.
+    let mut sink = unsafe { MemoryCodeSink::new(mem.as_mut_ptr(), &mut relocs, &mut traps) };
+    isa.emit_function_to_memory(func, &mut sink);
+
+    CodeSizeReport {
+        ebbs,
+        insts,
+        jumptables_size: info.jumptables_size,
+        constant_pool_size: info.rodata_size,
+        relocations: relocs.relocations,
+        relocation_bytes: relocs.bytes,
+    }
+}
+
+/// Get the number of bytes patched by a relocation of kind `reloc`.
+fn reloc_size(reloc: Reloc) -> CodeOffset {
+    match reloc {
+        Reloc::Abs8 | Reloc::X86PCRel8 | Reloc::X86GOTOff8 => 8,
+        _ => 4,
+    }
+}
+
+/// A `RelocSink` that counts the relocations and their sizes.
+struct RelocCounter {
+    relocations: usize,
+    bytes: CodeOffset,
+}
+
+impl RelocCounter {
+    fn add(&mut self, reloc: Reloc) {
+        self.relocations += 1;
+        self.bytes += reloc_size(reloc);
+    }
+}
+
+impl RelocSink for RelocCounter {
+    fn reloc_ebb(&mut self, _: CodeOffset, reloc: Reloc, _: CodeOffset) {
+        self.add(reloc);
+    }
+
+    fn reloc_external(&mut self, _: CodeOffset, reloc: Reloc, _: &ExternalName, _: Addend) {
+        self.add(reloc);
+    }
+
+    fn reloc_jt(&mut self, _: CodeOffset, reloc: Reloc, _: JumpTable) {
+        self.add(reloc);
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use crate::cursor::Cursor;
+    use crate::ir::{InstBuilder, SourceLoc};
+    use crate::test_utils;
+    use crate::Context;
+
+    #[test]
+    fn call() {
+        let isa = test_utils::isa("x86_64");
+        let func = test_utils::function(&[], &[], |pos, _| {
+            let sig = test_utils::signature(&[], &[]);
+            let callee = test_utils::import_function(pos.func, "callee", sig, true);
+            pos.set_srcloc(SourceLoc::new(1));
+            pos.ins().call(callee, &[]);
+            pos.set_srcloc(SourceLoc::new(2));
+            pos.ins().return_(&[]);
+        });
+
+        let mut ctx = Context::for_function(func);
+        let (info, report) = ctx.compile_with_size_report(&*isa).unwrap();
+
+        assert_eq!(report.ebbs.len(), 1);
+        assert_eq!(report.ebbs[0].padding, 0);
+        assert_eq!(report.ebbs[0].size, info.code_size);
+        let insts_size: u32 = report.insts.iter().map(|i| i.size).sum();
+        assert_eq!(insts_size, info.code_size);
+
+        // The colocated call is a 5-byte `call` with a 4-byte PC-relative relocation.
+        assert_eq!(report.srcloc_size(SourceLoc::new(1)), 5);
+        assert_eq!(report.relocations, 1);
+        assert_eq!(report.relocation_bytes, 4);
+        assert_eq!(report.jumptables_size, 0);
+        assert_eq!(report.constant_pool_size, 0);
+    }
+}
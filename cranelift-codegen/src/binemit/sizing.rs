@@ -0,0 +1,174 @@
+//! Code layout without emitting machine code.
+//!
+//! Branch relaxation decides the final encoding of every branch and the offset of every EBB
+//! header, and it records its decisions in the function. Embedders that want to know how large a
+//! function is going to be, for example to pre-allocate a buffer of exactly the right size or to
+//! compare the effect of different settings on the code layout, can use `compute_code_layout()`
+//! instead. It runs the same relaxation algorithm on a scratch copy of the function, so the
+//! function itself is left unchanged.
+//!
+//! The `SizingCodeSink` is a `CodeSink` that only counts the bytes it receives. It can be used to
+//! check the size of the emitted code without writing it anywhere.
+
+use crate::binemit::{relax_branches, Addend, CodeInfo, CodeOffset, CodeSink, Reloc};
+use crate::ir::{
+    EbbOffsets, ExternalName, Function, JumpTable, JumpTableOffsets, SourceLoc, TrapCode,
+};
+use crate::isa::TargetIsa;
+use crate::CodegenResult;
+
+/// The layout of the machine code for a function, as computed by branch relaxation.
+#[derive(Clone, Debug)]
+pub struct CodeLayout {
+    /// The sizes of the code, jump tables, and read-only data.
+    pub info: CodeInfo,
+
+    /// Offset of each EBB header from the start of the function.
+    pub ebb_offsets: EbbOffsets,
+
+    /// Offset of each jump table from the start of the function.
+    pub jt_offsets: JumpTableOffsets,
+}
+
+/// Compute the layout of the machine code for `func` without changing it.
+///
+/// The function must be ready for branch relaxation, i.e. register allocation must have been
+/// completed. The returned layout is identical to what `relax_branches()` would compute for
+/// `func` with the same `isa`.
+pub fn compute_code_layout(func: &Function, isa: &dyn TargetIsa) -> CodegenResult<CodeLayout> {
+    let mut scratch = func.clone();
+    let info = relax_branches(&mut scratch, isa)?;
+    Ok(CodeLayout {
+        info,
+        ebb_offsets: scratch.offsets,
+        jt_offsets: scratch.jt_offsets,
+    })
+}
+
+/// A `CodeSink` that counts the bytes of machine code and read-only data without storing them.
+pub struct SizingCodeSink {
+    offset: CodeOffset,
+    /// Information about the generated code and read-only data.
+    pub info: CodeInfo,
+}
+
+impl SizingCodeSink {
+    /// Create a new sizing code sink.
+    pub fn new() -> Self {
+        Self {
+            offset: 0,
+            info: CodeInfo {
+                code_size: 0,
+                jumptables_size: 0,
+                rodata_size: 0,
+                total_size: 0,
+            },
+        }
+    }
+}
+
+impl CodeSink for SizingCodeSink {
+    fn offset(&self) -> CodeOffset {
+        self.offset
+    }
+
+    fn put1(&mut self, _: u8) {
+        self.offset += 1;
+    }
+
+    fn put2(&mut self, _: u16) {
+        self.offset += 2;
+    }
+
+    fn put4(&mut self, _: u32) {
+        self.offset += 4;
+    }
+
+    fn put8(&mut self, _: u64) {
+        self.offset += 8;
+    }
+
+    fn reloc_ebb(&mut self, _: Reloc, _: CodeOffset) {}
+
+    fn reloc_external(&mut self, _: Reloc, _: &ExternalName, _: Addend) {}
+
+    fn reloc_jt(&mut self, _: Reloc, _: JumpTable) {}
+
+    fn trap(&mut self, _: TrapCode, _: SourceLoc) {}
+
+    fn begin_jumptables(&mut self) {
+        self.info.code_size = self.offset;
+    }
+
+    fn begin_rodata(&mut self) {
+        self.info.jumptables_size = self.offset - self.info.code_size;
+    }
+
+    fn end_codegen(&mut self) {
+        self.info.rodata_size = self.offset - (self.info.jumptables_size + self.info.code_size);
+        self.info.total_size = self.offset;
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::compute_code_layout;
+    use crate::cursor::Cursor;
+    use crate::ir::{types, Ebb, Function, InstBuilder};
+    use crate::test_utils;
+    use crate::Context;
+
+    /// A function with a branch to the next EBB and a jump to it.
+    fn branches() -> (Function, Ebb) {
+        let mut ebb1 = None;
+        let func = test_utils::function(&[], &[], |pos, _| {
+            let ebb = pos.func.dfg.make_ebb();
+            let v0 = pos.ins().iconst(types::I32, 0);
+            pos.ins().brz(v0, ebb, &[]);
+            pos.ins().jump(ebb, &[]);
+            pos.insert_ebb(ebb);
+            pos.ins().return_(&[]);
+            ebb1 = Some(ebb);
+        });
+        (func, ebb1.unwrap())
+    }
+
+    #[test]
+    fn layout() {
+        let isa = test_utils::isa("x86_64");
+        let (func, ebb1) = branches();
+        let ebb0 = func.layout.entry_block().unwrap();
+
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        let offsets = ctx.func.offsets.clone();
+
+        // The layout is computed without touching the function.
+        ctx.func.offsets.clear();
+        let layout = compute_code_layout(&ctx.func, &*isa).unwrap();
+        assert!(ctx.func.offsets.is_empty());
+        assert!(layout.info == info);
+        assert_eq!(layout.ebb_offsets[ebb0], 0);
+        assert_eq!(layout.ebb_offsets[ebb1], offsets[ebb1]);
+    }
+
+    #[cfg(feature = "testing_hooks")]
+    #[test]
+    fn sizing_sink() {
+        use super::SizingCodeSink;
+        use crate::binemit::emit_function;
+
+        let isa = test_utils::isa("x86_64");
+        let mut ctx = Context::for_function(branches().0);
+        let info = ctx.compile(&*isa).unwrap();
+
+        let mut sink = SizingCodeSink::new();
+        emit_function(
+            &ctx.func,
+            |func, inst, divert, sink| isa.emit_inst(func, inst, divert, sink),
+            |_, _| panic!("no padding expected"),
+            &mut sink,
+        );
+        assert!(sink.info == info);
+    }
+}
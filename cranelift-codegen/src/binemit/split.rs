@@ -0,0 +1,318 @@
+//! Emission of functions split in hot and cold code sections.
+//!
+//! `relax_split_branches()` moves the cold EBBs of a function to a separate code section, so they
+//! can be placed away from the hot code, for example on other pages. The offsets in the function
+//! are computed as if the cold section followed the hot code and its jump tables directly. Every
+//! branch between the two sections is relaxed to a 32-bit displacement and recorded as a
+//! `SplitFixup`, which the embedder patches once the sections are placed in memory.
+//!
+//! Jump tables stay in the hot section, so the EBBs that are the target of a jump table or use
+//! one are never moved to the cold section.
+
+use super::{emit_jump_tables, CodeInfo, CodeOffset, CodeSink, Reloc};
+use crate::ir::{ExternalName, Function, Inst, JumpTable, SourceLoc, TrapCode};
+use crate::regalloc::RegDiversions;
+use std::vec::Vec;
+
+/// A code section of a split function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodeSection {
+    /// The hot code, followed by the jump tables and read-only data.
+    Hot,
+    /// The cold EBBs.
+    Cold,
+}
+
+/// A branch between the hot and cold sections of a function.
+///
+/// The branch ends with a 32-bit little-endian displacement that is relative to the end of the
+/// branch. The emitted displacement assumes that the cold section follows the hot section
+/// directly, so it must be patched when the sections are placed elsewhere.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SplitFixup {
+    /// The section containing the branch.
+    pub section: CodeSection,
+
+    /// Offset of the displacement from the start of `section`.
+    pub offset: CodeOffset,
+
+    /// Offset of the branch destination from the start of the other section.
+    pub target: CodeOffset,
+}
+
+impl SplitFixup {
+    /// Compute the displacement to patch in, given the addresses of the hot and cold sections.
+    pub fn displacement(&self, hot_addr: u64, cold_addr: u64) -> i64 {
+        let (from, to) = match self.section {
+            CodeSection::Hot => (hot_addr, cold_addr),
+            CodeSection::Cold => (cold_addr, hot_addr),
+        };
+        let field_end = from + u64::from(self.offset) + 4;
+        (to + u64::from(self.target)).wrapping_sub(field_end) as i64
+    }
+}
+
+/// Information about the code of a function split in hot and cold sections.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SplitCodeInfo {
+    /// The hot code with its jump tables and read-only data. The cold section starts at
+    /// `hot.total_size` in the function's offsets.
+    pub hot: CodeInfo,
+
+    /// Number of bytes of cold code.
+    pub cold_size: CodeOffset,
+
+    /// The branches between the two sections, in layout order.
+    pub fixups: Vec<SplitFixup>,
+}
+
+/// Code sink forwarding to the sink of the current section.
+///
+/// The reported offsets are the ones used by the function, so the cold section starts at
+/// `cold_start`. Both sinks must be empty when the emission starts.
+pub(crate) struct SplitSink<'a> {
+    hot: &'a mut dyn CodeSink,
+    cold: &'a mut dyn CodeSink,
+    cold_start: CodeOffset,
+    in_cold: bool,
+}
+
+impl<'a> SplitSink<'a> {
+    fn current(&mut self) -> &mut dyn CodeSink {
+        if self.in_cold {
+            &mut *self.cold
+        } else {
+            &mut *self.hot
+        }
+    }
+}
+
+impl<'a> CodeSink for SplitSink<'a> {
+    fn offset(&self) -> CodeOffset {
+        if self.in_cold {
+            self.cold_start + self.cold.offset()
+        } else {
+            self.hot.offset()
+        }
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.current().put1(x)
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.current().put2(x)
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.current().put4(x)
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.current().put8(x)
+    }
+
+    fn reloc_ebb(&mut self, rel: Reloc, ebb_offset: CodeOffset) {
+        self.current().reloc_ebb(rel, ebb_offset)
+    }
+
+    fn reloc_external(&mut self, rel: Reloc, name: &ExternalName, addend: i64) {
+        self.current().reloc_external(rel, name, addend)
+    }
+
+    fn reloc_jt(&mut self, rel: Reloc, jt: JumpTable) {
+        self.current().reloc_jt(rel, jt)
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        self.current().trap(code, srcloc)
+    }
+
+    fn begin_jumptables(&mut self) {
+        self.current().begin_jumptables()
+    }
+
+    fn begin_rodata(&mut self) {
+        self.current().begin_rodata()
+    }
+
+    fn end_codegen(&mut self) {
+        self.current().end_codegen()
+    }
+}
+
+/// Emit a function laid out by `relax_split_branches()` to the `hot` and `cold` sinks, given an
+/// instruction emitter function and a function emitting the given number of bytes of no-op
+/// instructions.
+///
+/// This function is called from the `TargetIsa::emit_split_function()` implementations with the
+/// appropriate emitters. The hot sink receives the jump tables, and both sinks are finished with
+/// `end_codegen()`.
+pub(crate) fn emit_split_function<'a, EI, EP>(
+    func: &Function,
+    info: &SplitCodeInfo,
+    emit_inst: EI,
+    emit_padding: EP,
+    hot: &'a mut dyn CodeSink,
+    cold: &'a mut dyn CodeSink,
+) where
+    EI: Fn(&Function, Inst, &mut RegDiversions, &mut SplitSink<'a>),
+    EP: Fn(CodeOffset, &mut SplitSink<'a>),
+{
+    debug_assert_eq!(hot.offset(), 0);
+    debug_assert_eq!(cold.offset(), 0);
+    let mut sink = SplitSink {
+        hot,
+        cold,
+        cold_start: info.hot.total_size,
+        in_cold: false,
+    };
+
+    let mut divert = RegDiversions::new();
+    for ebb in func.layout.ebbs() {
+        if !sink.in_cold && func.offsets[ebb] >= info.hot.total_size {
+            finish_section(func, &mut sink);
+            sink.in_cold = true;
+        }
+        divert.clear();
+        if sink.offset() < func.offsets[ebb] {
+            emit_padding(func.offsets[ebb] - sink.offset(), &mut sink);
+        }
+        debug_assert_eq!(func.offsets[ebb], sink.offset());
+        for inst in func.layout.ebb_insts(ebb) {
+            emit_inst(func, inst, &mut divert, &mut sink);
+        }
+    }
+
+    if !sink.in_cold {
+        finish_section(func, &mut sink);
+        sink.in_cold = true;
+    }
+    debug_assert_eq!(sink.offset(), info.hot.total_size + info.cold_size);
+    sink.begin_jumptables();
+    sink.begin_rodata();
+    sink.end_codegen();
+}
+
+/// Emit the jump tables of `func` and finish the hot section.
+fn finish_section(func: &Function, sink: &mut SplitSink) {
+    sink.begin_jumptables();
+    emit_jump_tables(func, sink);
+    sink.begin_rodata();
+    // TODO: No read-only data (constant pools) at this time.
+    sink.end_codegen();
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::CodeSection;
+    use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc, TrapSink, TrapTable};
+    use crate::cursor::Cursor;
+    use crate::ir::{types, ExternalName, InstBuilder, JumpTable, SourceLoc, TrapCode};
+    use crate::test_utils;
+    use crate::Context;
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct VecSink {
+        data: Vec<u8>,
+        traps: Vec<(CodeOffset, SourceLoc, TrapCode)>,
+    }
+
+    impl CodeSink for VecSink {
+        fn offset(&self) -> CodeOffset {
+            self.data.len() as CodeOffset
+        }
+
+        fn put1(&mut self, x: u8) {
+            self.data.push(x);
+        }
+
+        fn put2(&mut self, x: u16) {
+            self.data.extend_from_slice(&x.to_le_bytes());
+        }
+
+        fn put4(&mut self, x: u32) {
+            self.data.extend_from_slice(&x.to_le_bytes());
+        }
+
+        fn put8(&mut self, x: u64) {
+            self.data.extend_from_slice(&x.to_le_bytes());
+        }
+
+        fn reloc_ebb(&mut self, _: Reloc, _: CodeOffset) {}
+
+        fn reloc_external(&mut self, _: Reloc, _: &ExternalName, _: Addend) {}
+
+        fn reloc_jt(&mut self, _: Reloc, _: JumpTable) {}
+
+        fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+            let offset = self.offset();
+            self.traps.push((offset, srcloc, code));
+        }
+
+        fn begin_jumptables(&mut self) {}
+
+        fn begin_rodata(&mut self) {}
+
+        fn end_codegen(&mut self) {}
+    }
+
+    #[test]
+    fn compile_split() {
+        let isa = test_utils::isa("x86_64");
+        // x86 legalizes `trapz` to `trapif`, so the cold trap EBB is created explicitly.
+        let func = test_utils::function(&[types::I32], &[types::I32], |pos, args| {
+            let trap = pos.func.dfg.make_ebb();
+            pos.ins().brz(args[0], trap, &[]);
+            pos.ins().return_(&[args[0]]);
+            pos.insert_ebb(trap);
+            pos.func.layout.set_cold(trap);
+            pos.ins().trap(TrapCode::IntegerDivisionByZero);
+        });
+
+        let mut ctx = Context::for_function(func);
+        let mut hot = VecSink::default();
+        let mut cold = VecSink::default();
+        let info = ctx.compile_split(&*isa, &mut hot, &mut cold).unwrap();
+
+        // The trap EBB is the whole cold section.
+        assert_eq!(hot.offset(), info.hot.total_size);
+        assert_eq!(cold.offset(), info.cold_size);
+        assert!(hot
+            .traps
+            .iter()
+            .all(|&(_, _, code)| code != TrapCode::IntegerDivisionByZero));
+        assert_eq!(cold.traps.len(), 1);
+        assert_eq!(cold.traps[0].0, 0);
+        assert_eq!(cold.traps[0].2, TrapCode::IntegerDivisionByZero);
+
+        // The traps of both sections form an ordered table with the cold section after the hot
+        // code.
+        let mut table = TrapTable::new();
+        for &(offset, srcloc, code) in &hot.traps {
+            table.trap(offset, srcloc, code);
+        }
+        for &(offset, srcloc, code) in &cold.traps {
+            table.trap(info.hot.total_size + offset, srcloc, code);
+        }
+        assert_eq!(table.records().len(), hot.traps.len() + 1);
+
+        // The conditional branch to the trap is patched with the distance between the sections.
+        assert_eq!(info.fixups.len(), 1);
+        let fixup = info.fixups[0];
+        assert_eq!(fixup.section, CodeSection::Hot);
+        assert_eq!(fixup.target, 0);
+        let field = fixup.offset as usize;
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(&hot.data[field..field + 4]);
+        assert_eq!(
+            i64::from(i32::from_le_bytes(bytes)),
+            fixup.displacement(0, u64::from(info.hot.total_size))
+        );
+        assert_eq!(
+            fixup.displacement(0x1000, 0x10_0000),
+            0x10_0000 - (0x1000 + i64::from(fixup.offset) + 4)
+        );
+    }
+}
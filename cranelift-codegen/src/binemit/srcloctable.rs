@@ -0,0 +1,137 @@
+//! Mapping from code offsets to source locations.
+//!
+//! A `SourceLocTable` maps the machine code of a function back to the `SourceLoc` of the IR
+//! instructions it was generated from, so profilers and error reporters can find the original
+//! bytecode offset of a program counter. `Context::compile()` fills one in from the final code
+//! layout, see `Context::srclocs()`.
+
+use super::CodeOffset;
+use crate::ir::{Function, SourceLoc};
+use crate::isa::TargetIsa;
+use std::vec::Vec;
+
+/// The source location of the machine code starting at a code offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceLocRecord {
+    /// Offset of the first machine instruction from the start of the function.
+    pub offset: CodeOffset,
+    /// Source location of the instructions from `offset` to the next record.
+    pub srcloc: SourceLoc,
+}
+
+/// A table of the source locations of the machine code of a function.
+///
+/// A record is only added when the source location changes, so the records are sorted by code
+/// offset and each one covers the code up to the next one. Instructions without a source location
+/// have a record with the default location.
+#[derive(Clone, Debug, Default)]
+pub struct SourceLocTable {
+    records: Vec<SourceLocRecord>,
+    code_size: CodeOffset,
+}
+
+impl SourceLocTable {
+    /// Create a new empty table.
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+            code_size: 0,
+        }
+    }
+
+    /// Remove all records.
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.code_size = 0;
+    }
+
+    /// Is this table empty?
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Get the records, sorted by code offset.
+    pub fn records(&self) -> &[SourceLocRecord] {
+        &self.records
+    }
+
+    /// Get the size of the code covered by the table, excluding jump tables and read-only data.
+    pub fn code_size(&self) -> CodeOffset {
+        self.code_size
+    }
+
+    /// Find the source location of the instruction containing `offset`.
+    ///
+    /// Returns the default source location for offsets outside of the code, or in instructions
+    /// without a source location.
+    pub fn lookup(&self, offset: CodeOffset) -> SourceLoc {
+        if offset >= self.code_size {
+            return SourceLoc::default();
+        }
+        match self.records.binary_search_by_key(&offset, |r| r.offset) {
+            Ok(i) => self.records[i].srcloc,
+            Err(0) => SourceLoc::default(),
+            Err(i) => self.records[i - 1].srcloc,
+        }
+    }
+
+    /// Replace the contents of this table with the source locations of `func`.
+    ///
+    /// The code layout must have been computed by `relax_branches()`.
+    pub fn compute(&mut self, func: &Function, isa: &dyn TargetIsa) {
+        self.clear();
+        let encinfo = isa.encoding_info();
+        for ebb in func.layout.ebbs() {
+            for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+                if size == 0 {
+                    continue;
+                }
+                let srcloc = func.srclocs[inst];
+                if self.records.last().map(|r| r.srcloc) != Some(srcloc) {
+                    self.records.push(SourceLocRecord { offset, srcloc });
+                }
+                self.code_size = offset + size;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use crate::cursor::Cursor;
+    use crate::ir::types::I32;
+    use crate::ir::{InstBuilder, SourceLoc};
+    use crate::test_utils;
+    use crate::Context;
+
+    #[test]
+    fn compile() {
+        let isa = test_utils::isa("x86_64");
+        let func = test_utils::function(&[I32], &[I32], |pos, args| {
+            pos.set_srcloc(SourceLoc::new(10));
+            let sum = pos.ins().iadd(args[0], args[0]);
+            pos.set_srcloc(SourceLoc::new(20));
+            let product = pos.ins().imul(sum, args[0]);
+            pos.ins().return_(&[product]);
+        });
+
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        let table = ctx.srclocs();
+        assert_eq!(table.code_size(), info.code_size);
+
+        let records = table.records();
+        assert!(records.windows(2).all(|w| w[0].offset < w[1].offset));
+        let first = records
+            .iter()
+            .find(|r| r.srcloc == SourceLoc::new(10))
+            .unwrap();
+        assert_eq!(table.lookup(first.offset), SourceLoc::new(10));
+        let second = records
+            .iter()
+            .find(|r| r.srcloc == SourceLoc::new(20))
+            .unwrap();
+        assert_eq!(table.lookup(second.offset - 1), SourceLoc::new(10));
+        assert_eq!(table.lookup(info.code_size), SourceLoc::default());
+    }
+}
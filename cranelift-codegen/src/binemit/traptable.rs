@@ -0,0 +1,454 @@
+//! Trap metadata collected during binary emission.
+//!
+//! A `TrapTable` is a `TrapSink` that remembers every trap site reported while emitting a
+//! function, so the embedder can look up the trap code and source location corresponding to a
+//! faulting program counter after the code has been emitted.
+//!
+//! Functions with many bounds checks have large trap tables. A `CompressedTrapTable` stores the
+//! same records delta-compressed, typically in 3 bytes per trap, and can be serialized to bytes
+//! that are stored with the compiled code.
+
+use super::{CodeOffset, TrapSink};
+use crate::ir::{SourceLoc, TrapCode};
+use core::cmp::Ordering;
+use std::vec::Vec;
+
+/// A single trap site in the emitted code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrapRecord {
+    /// Offset of the trapping instruction from the start of the function.
+    pub offset: CodeOffset,
+    /// Source location of the instruction that generated the trap.
+    pub srcloc: SourceLoc,
+    /// The reason for the trap.
+    pub code: TrapCode,
+}
+
+/// A `TrapSink` that records all trap sites in a queryable buffer.
+///
+/// Traps are reported in increasing code offset order during emission, so the records are sorted
+/// by offset and can be searched efficiently.
+#[derive(Clone, Debug, Default)]
+pub struct TrapTable {
+    records: Vec<TrapRecord>,
+}
+
+impl TrapTable {
+    /// Create a new empty trap table.
+    pub fn new() -> Self {
+        Self {
+            records: Vec::new(),
+        }
+    }
+
+    /// Remove all trap records.
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Is this table empty?
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Get the number of trap records.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Get all the trap records, sorted by code offset.
+    pub fn records(&self) -> &[TrapRecord] {
+        &self.records
+    }
+
+    /// Find the trap record for the instruction at `offset`, if any.
+    pub fn lookup(&self, offset: CodeOffset) -> Option<&TrapRecord> {
+        self.records
+            .binary_search_by_key(&offset, |r| r.offset)
+            .ok()
+            .map(|i| &self.records[i])
+    }
+
+    /// Create a compressed copy of this table.
+    pub fn compress(&self) -> CompressedTrapTable {
+        CompressedTrapTable::new(&self.records)
+    }
+}
+
+impl TrapSink for TrapTable {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        debug_assert!(
+            self.records.last().map_or(true, |r| r.offset <= offset),
+            "traps must be reported in code order"
+        );
+        self.records.push(TrapRecord {
+            offset,
+            srcloc,
+            code,
+        });
+    }
+}
+
+/// A `TrapSink` that records traps in a `TrapTable` and forwards them to another sink.
+pub(crate) struct RecordingTrapSink<'a> {
+    pub table: &'a mut TrapTable,
+    pub inner: &'a mut dyn TrapSink,
+}
+
+impl<'a> TrapSink for RecordingTrapSink<'a> {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        self.table.trap(offset, srcloc, code);
+        self.inner.trap(offset, srcloc, code);
+    }
+}
+
+/// Number of records between the checkpoints of a `CompressedTrapTable`.
+const CHECKPOINT_INTERVAL: usize = 16;
+
+/// Encoding of the first user trap code. The built-in trap codes are encoded below this.
+const USER_TRAP_BASE: u32 = 32;
+
+/// A compact, read-only trap table.
+///
+/// Each record is stored as three LEB128 numbers: the code offset relative to the previous record,
+/// the source location relative to the previous record (zigzag-encoded), and the trap code. Every
+/// `CHECKPOINT_INTERVAL` records, a checkpoint remembers where decoding can start, so
+/// `lookup_trap()` only needs to binary search the checkpoints and decode a few records.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompressedTrapTable {
+    len: usize,
+    data: Vec<u8>,
+    checkpoints: Vec<Checkpoint>,
+}
+
+/// The decoder state before a record in the compressed data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Checkpoint {
+    /// Offset of the record itself.
+    offset: CodeOffset,
+    /// Position of the record in the compressed data.
+    pos: usize,
+    /// Code offset of the previous record.
+    prev_offset: CodeOffset,
+    /// Source location bits of the previous record.
+    prev_srcloc: u32,
+}
+
+impl CompressedTrapTable {
+    /// Compress `records`, which must be sorted by code offset.
+    pub fn new(records: &[TrapRecord]) -> Self {
+        let mut data = Vec::new();
+        put_uleb(&mut data, records.len() as u32);
+        let mut prev = TrapRecord {
+            offset: 0,
+            srcloc: SourceLoc::new(0),
+            code: TrapCode::User(0),
+        };
+        for r in records {
+            debug_assert!(r.offset >= prev.offset, "records must be sorted");
+            put_uleb(&mut data, r.offset - prev.offset);
+            put_uleb(
+                &mut data,
+                zigzag(r.srcloc.bits().wrapping_sub(prev.srcloc.bits())),
+            );
+            put_uleb(&mut data, encode_trap_code(r.code));
+            prev = *r;
+        }
+        Self::from_bytes(&data).expect("invalid trap table encoding")
+    }
+
+    /// Load a table that was serialized with `as_bytes()`.
+    ///
+    /// Returns `None` if `bytes` is not a valid trap table.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut pos = 0;
+        let len = get_uleb(bytes, &mut pos)? as usize;
+        let mut checkpoints = Vec::new();
+        let mut decoder = Decoder {
+            data: bytes,
+            state: Checkpoint {
+                pos,
+                ..Default::default()
+            },
+        };
+        for i in 0..len {
+            let before = decoder.state;
+            let record = decoder.next()?;
+            if i % CHECKPOINT_INTERVAL == 0 {
+                checkpoints.push(Checkpoint {
+                    offset: record.offset,
+                    ..before
+                });
+            }
+        }
+        if decoder.state.pos != bytes.len() {
+            return None;
+        }
+        Some(Self {
+            len,
+            data: bytes.to_vec(),
+            checkpoints,
+        })
+    }
+
+    /// Get the serialized form of this table.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Get the number of trap records.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Is this table empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over all the trap records, sorted by code offset.
+    pub fn iter(&self) -> CompressedTrapIter {
+        let state = self.checkpoints.first().cloned().unwrap_or_default();
+        CompressedTrapIter {
+            decoder: Decoder {
+                data: &self.data,
+                state,
+            },
+            remaining: self.len,
+        }
+    }
+
+    /// Find the first trap record for the instruction at `offset`, if any.
+    pub fn lookup_trap(&self, offset: CodeOffset) -> Option<TrapRecord> {
+        // Start decoding at the last checkpoint strictly before `offset`. Records with the same
+        // offset can straddle a checkpoint, so a checkpoint at `offset` may not be the first.
+        let block = match self.checkpoints.binary_search_by(|cp| {
+            if cp.offset < offset {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }) {
+            Ok(i) | Err(i) => i.saturating_sub(1),
+        };
+        let start = match self.checkpoints.get(block) {
+            Some(&cp) => cp,
+            None => return None,
+        };
+        let mut decoder = Decoder {
+            data: &self.data,
+            state: start,
+        };
+        for _ in block * CHECKPOINT_INTERVAL..self.len {
+            let record = decoder.next().expect("validated trap table");
+            if record.offset == offset {
+                return Some(record);
+            }
+            if record.offset > offset {
+                break;
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the records in a `CompressedTrapTable`.
+pub struct CompressedTrapIter<'a> {
+    decoder: Decoder<'a>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for CompressedTrapIter<'a> {
+    type Item = TrapRecord;
+
+    fn next(&mut self) -> Option<TrapRecord> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        self.decoder.next()
+    }
+}
+
+/// Decoder for the records in compressed trap table data.
+struct Decoder<'a> {
+    data: &'a [u8],
+    state: Checkpoint,
+}
+
+impl<'a> Decoder<'a> {
+    /// Decode the next record, or return `None` if the data is invalid.
+    fn next(&mut self) -> Option<TrapRecord> {
+        let state = &mut self.state;
+        let delta = get_uleb(self.data, &mut state.pos)?;
+        let offset = state.prev_offset.checked_add(delta)?;
+        let srcloc = state
+            .prev_srcloc
+            .wrapping_add(unzigzag(get_uleb(self.data, &mut state.pos)?));
+        let code = decode_trap_code(get_uleb(self.data, &mut state.pos)?)?;
+        state.prev_offset = offset;
+        state.prev_srcloc = srcloc;
+        Some(TrapRecord {
+            offset,
+            srcloc: SourceLoc::new(srcloc),
+            code,
+        })
+    }
+}
+
+fn encode_trap_code(code: TrapCode) -> u32 {
+    use crate::ir::TrapCode::*;
+    match code {
+        StackOverflow => 0,
+        HeapOutOfBounds => 1,
+        TableOutOfBounds => 2,
+        OutOfBounds => 3,
+        IndirectCallToNull => 4,
+        BadSignature => 5,
+        IntegerOverflow => 6,
+        IntegerDivisionByZero => 7,
+        BadConversionToInteger => 8,
+        UnreachableCodeReached => 9,
+        Interrupt => 10,
+        User(n) => USER_TRAP_BASE + u32::from(n),
+    }
+}
+
+fn decode_trap_code(bits: u32) -> Option<TrapCode> {
+    use crate::ir::TrapCode::*;
+    Some(match bits {
+        0 => StackOverflow,
+        1 => HeapOutOfBounds,
+        2 => TableOutOfBounds,
+        3 => OutOfBounds,
+        4 => IndirectCallToNull,
+        5 => BadSignature,
+        6 => IntegerOverflow,
+        7 => IntegerDivisionByZero,
+        8 => BadConversionToInteger,
+        9 => UnreachableCodeReached,
+        10 => Interrupt,
+        _ if bits >= USER_TRAP_BASE && bits - USER_TRAP_BASE <= u32::from(u16::max_value()) => {
+            User((bits - USER_TRAP_BASE) as u16)
+        }
+        _ => return None,
+    })
+}
+
+/// Map a signed difference to an unsigned number that is small when the difference is small.
+fn zigzag(x: u32) -> u32 {
+    let x = x as i32;
+    ((x << 1) ^ (x >> 31)) as u32
+}
+
+fn unzigzag(x: u32) -> u32 {
+    (x >> 1) ^ (x & 1).wrapping_neg()
+}
+
+/// Append `x` to `data` as an unsigned LEB128 number.
+fn put_uleb(data: &mut Vec<u8>, mut x: u32) {
+    while x >= 0x80 {
+        data.push((x & 0x7f) as u8 | 0x80);
+        x >>= 7;
+    }
+    data.push(x as u8);
+}
+
+/// Read an unsigned LEB128 number at `*pos`, and advance `pos` past it.
+fn get_uleb(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut x = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        let bits = u32::from(byte & 0x7f);
+        if shift == 28 && bits > 0xf {
+            return None;
+        }
+        x |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some(x);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::NullTrapSink;
+
+    #[test]
+    fn lookup() {
+        let mut table = TrapTable::new();
+        assert!(table.is_empty());
+
+        let mut null = NullTrapSink {};
+        {
+            let mut sink = RecordingTrapSink {
+                table: &mut table,
+                inner: &mut null,
+            };
+            sink.trap(4, SourceLoc::new(10), TrapCode::HeapOutOfBounds);
+            sink.trap(12, SourceLoc::new(20), TrapCode::IntegerDivisionByZero);
+        }
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            table.lookup(4).map(|r| r.code),
+            Some(TrapCode::HeapOutOfBounds)
+        );
+        assert_eq!(table.lookup(12).map(|r| r.srcloc), Some(SourceLoc::new(20)));
+        assert_eq!(table.lookup(8), None);
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn compressed() {
+        let mut table = TrapTable::new();
+        for i in 0..100 {
+            let code = match i % 3 {
+                0 => TrapCode::HeapOutOfBounds,
+                1 => TrapCode::User(1000),
+                _ => TrapCode::IntegerDivisionByZero,
+            };
+            // Source locations go up and down, and some offsets have two traps.
+            let srcloc = if i % 5 == 0 {
+                SourceLoc::default()
+            } else {
+                SourceLoc::new(1000 + i * 7 % 50)
+            };
+            table.trap(i / 2 * 6, srcloc, code);
+        }
+
+        let compressed = table.compress();
+        assert_eq!(compressed.len(), 100);
+        assert!(compressed.as_bytes().len() < 100 * 4);
+        assert!(compressed.iter().eq(table.records().iter().cloned()));
+        for r in table.records() {
+            let first = table.records().iter().find(|x| x.offset == r.offset);
+            assert_eq!(compressed.lookup_trap(r.offset).as_ref(), first);
+        }
+        assert_eq!(compressed.lookup_trap(1), None);
+        assert_eq!(compressed.lookup_trap(1000), None);
+
+        let loaded = CompressedTrapTable::from_bytes(compressed.as_bytes()).unwrap();
+        assert_eq!(loaded, compressed);
+
+        // Truncated and corrupted data is rejected.
+        let bytes = compressed.as_bytes();
+        assert_eq!(
+            CompressedTrapTable::from_bytes(&bytes[..bytes.len() - 1]),
+            None
+        );
+        let mut extra = bytes.to_vec();
+        extra.push(0);
+        assert_eq!(CompressedTrapTable::from_bytes(&extra), None);
+
+        let empty = TrapTable::new().compress();
+        assert!(empty.is_empty());
+        assert_eq!(empty.lookup_trap(0), None);
+        assert_eq!(empty.iter().count(), 0);
+    }
+}
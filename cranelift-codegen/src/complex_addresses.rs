@@ -0,0 +1,221 @@
+//! Formation of complex addressing modes.
+//!
+//! Frontends compute array element addresses as `iadd base, (ishl_imm index, scale)`, followed
+//! by a load or store. On ISAs with a register + register addressing mode, the `iadd` can be
+//! folded into the memory access by using the `*_complex` load and store instructions, which take
+//! the base and the index as separate operands. Constant additions are folded into the offset of
+//! the access.
+//!
+//! The pre-legalization pass in this module only forms complex addresses that the ISA can encode,
+//! so that the address computations which are left can still be optimized by the passes running
+//! before legalization. The post-legalization rewriting pass also uses `fold_address()` to catch
+//! the addresses formed by the legalizer.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::ir::immediates::Offset32;
+use crate::ir::{Function, Inst, InstBuilder, InstructionData, MemFlags, Opcode, Type, Value};
+use crate::isa::{match_address, AddressModes, TargetIsa};
+use crate::timing;
+use core::cmp;
+
+/// Fold address computations into the loads and stores of `func`.
+pub fn do_complex_addresses(func: &mut Function, isa: &dyn TargetIsa) {
+    let _tt = timing::complex_addresses();
+    if !isa.uses_complex_addresses() {
+        return;
+    }
+
+    let modes = isa.address_modes();
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let old_data = pos.func.dfg[inst].clone();
+            if !fold_address(pos.func, inst, modes) {
+                continue;
+            }
+            let ctrl_type = pos.func.dfg.ctrl_typevar(inst);
+            if isa
+                .encode(pos.func, &pos.func.dfg[inst], ctrl_type)
+                .is_err()
+            {
+                pos.func.dfg[inst] = old_data;
+            }
+        }
+    }
+}
+
+struct MemOpInfo {
+    opcode: Opcode,
+    itype: Type,
+    arg: Value,
+    st_arg: Option<Value>,
+    flags: MemFlags,
+    offset: Offset32,
+}
+
+/// Fold the computation of the address of the load or store `inst` into the instruction, using the
+/// addressing modes in `modes`:
+///
+/// - An address computed as the sum of two values turns `inst` into the corresponding `*_complex`
+///   instruction, with the two values as separate operands.
+/// - Constants added to the address are folded into the offset of `inst`.
+///
+/// Returns true if `inst` was changed. The caller is responsible for checking that the new
+/// instruction is legal.
+pub fn fold_address(func: &mut Function, inst: Inst, modes: AddressModes) -> bool {
+    // Look for simple loads and stores we can optimize.
+    let info = match func.dfg[inst] {
+        InstructionData::Load {
+            opcode,
+            arg,
+            flags,
+            offset,
+        } => MemOpInfo {
+            opcode,
+            itype: func.dfg.ctrl_typevar(inst),
+            arg,
+            st_arg: None,
+            flags,
+            offset,
+        },
+        InstructionData::Store {
+            opcode,
+            args,
+            flags,
+            offset,
+        } => MemOpInfo {
+            opcode,
+            itype: func.dfg.ctrl_typevar(inst),
+            arg: args[1],
+            st_arg: Some(args[0]),
+            flags,
+            offset,
+        },
+        _ => return false,
+    };
+    match info.opcode {
+        Opcode::Load
+        | Opcode::Uload8
+        | Opcode::Sload8
+        | Opcode::Uload16
+        | Opcode::Sload16
+        | Opcode::Uload32
+        | Opcode::Sload32
+        | Opcode::Store
+        | Opcode::Istore8
+        | Opcode::Istore16
+        | Opcode::Istore32 => {}
+        _ => return false,
+    }
+
+    // Match the address against the addressing modes of the target. The complex load and store
+    // instructions have no scale, and a 32-bit offset.
+    let modes = AddressModes {
+        max_scale: cmp::min(modes.max_scale, 1),
+        disp_bits: cmp::min(modes.disp_bits, 32),
+    };
+    let addr = match_address(&func.dfg, info.arg, info.offset.into(), modes);
+    let offset = Offset32::new(addr.disp as i32);
+
+    match addr.index {
+        Some((index, _)) => {
+            // Fold the base and index into a memory address with a complex address mode.
+            let args = [addr.base, index];
+            match info.opcode {
+                Opcode::Load => {
+                    func.dfg
+                        .replace(inst)
+                        .load_complex(info.itype, info.flags, &args, offset);
+                }
+                Opcode::Uload8 => {
+                    func.dfg
+                        .replace(inst)
+                        .uload8_complex(info.itype, info.flags, &args, offset);
+                }
+                Opcode::Sload8 => {
+                    func.dfg
+                        .replace(inst)
+                        .sload8_complex(info.itype, info.flags, &args, offset);
+                }
+                Opcode::Uload16 => {
+                    func.dfg
+                        .replace(inst)
+                        .uload16_complex(info.itype, info.flags, &args, offset);
+                }
+                Opcode::Sload16 => {
+                    func.dfg
+                        .replace(inst)
+                        .sload16_complex(info.itype, info.flags, &args, offset);
+                }
+                Opcode::Uload32 => {
+                    func.dfg
+                        .replace(inst)
+                        .uload32_complex(info.flags, &args, offset);
+                }
+                Opcode::Sload32 => {
+                    func.dfg
+                        .replace(inst)
+                        .sload32_complex(info.flags, &args, offset);
+                }
+                Opcode::Store => {
+                    func.dfg.replace(inst).store_complex(
+                        info.flags,
+                        info.st_arg.unwrap(),
+                        &args,
+                        offset,
+                    );
+                }
+                Opcode::Istore8 => {
+                    func.dfg.replace(inst).istore8_complex(
+                        info.flags,
+                        info.st_arg.unwrap(),
+                        &args,
+                        offset,
+                    );
+                }
+                Opcode::Istore16 => {
+                    func.dfg.replace(inst).istore16_complex(
+                        info.flags,
+                        info.st_arg.unwrap(),
+                        &args,
+                        offset,
+                    );
+                }
+                Opcode::Istore32 => {
+                    func.dfg.replace(inst).istore32_complex(
+                        info.flags,
+                        info.st_arg.unwrap(),
+                        &args,
+                        offset,
+                    );
+                }
+                _ => panic!("Unsupported load or store opcode"),
+            }
+        }
+        None if addr.base != info.arg => match func.dfg[inst] {
+            // Fold the displacement into the offset.
+            InstructionData::Load {
+                arg: ref mut load_arg,
+                offset: ref mut load_offset,
+                ..
+            } => {
+                *load_arg = addr.base;
+                *load_offset = offset;
+            }
+            InstructionData::Store {
+                args: ref mut store_args,
+                offset: ref mut store_offset,
+                ..
+            } => {
+                store_args[1] = addr.base;
+                *store_offset = offset;
+            }
+            _ => panic!(),
+        },
+        None => {
+            // Nothing to fold into the address.
+            return false;
+        }
+    }
+    true
+}
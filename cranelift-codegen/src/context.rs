@@ -10,29 +10,64 @@
 //! single ISA instance.
 
 use crate::binemit::{
-    relax_branches, shrink_instructions, CodeInfo, MemoryCodeSink, RelocSink, TrapSink,
+    code_size_report, peephole_optimize, relax_branches, relax_split_branches, shrink_instructions,
+    BufferCodeSink, CodeInfo, CodeSink, CodeSizeReport, MemoryCodeSink, RecordingTrapSink,
+    RelocRecord, RelocSink, SourceLocTable, SplitCodeInfo, TrapSink, TrapTable,
 };
+use crate::complex_addresses::do_complex_addresses;
+use crate::coverage::{instrument_coverage, CoverageMap};
 use crate::dce::do_dce;
+use crate::debuginfo::{build_debug_info, DebugInfo};
+use crate::determinism::shuffled_copy;
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
 use crate::dominator_tree::DominatorTree;
+use crate::ebb_frequency::EbbFrequencies;
+use crate::ebb_layout::{apply_branch_profile, do_ebb_layout, BranchProfile};
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::Function;
+use crate::heap_bounds::do_heap_bounds;
+use crate::inline::{do_inlining, CalleeLookup};
+use crate::ir::{Function, GlobalValue, Opcode, StackSlotKind, SymbolInfo};
 use crate::isa::TargetIsa;
-use crate::legalize_function;
+use crate::legalizer::legalize_function_with_diagnostics;
 use crate::licm::do_licm;
 use crate::loop_analysis::LoopAnalysis;
 use crate::nan_canonicalization::do_nan_canonicalization;
+use crate::pipeline::{CompileMode, Pass, Pipeline};
 use crate::postopt::do_postopt;
+use crate::prune_ebb_params::do_prune_ebb_params;
+use crate::redundant_traps::do_redundant_traps;
 use crate::regalloc;
+use crate::register_pressure::{PressureReport, RegisterPressure};
+use crate::remat_constants::do_remat_constants;
 use crate::result::CodegenResult;
-use crate::settings::{FlagsOrIsa, OptLevel};
+use crate::settings::{DeterminismCheck, FlagsOrIsa, RegallocAlgorithm};
 use crate::simple_gvn::do_simple_gvn;
 use crate::simple_preopt::do_preopt;
-use crate::timing;
+use crate::simplify_cfg::do_simplify_cfg;
+use crate::timing::{self, CompileStats, PassInstCount};
 use crate::unreachable_code::eliminate_unreachable_code;
 use crate::value_label::{build_value_labels_ranges, ComparableSourceLoc, ValueLabelsRanges};
-use crate::verifier::{verify_context, verify_locations, VerifierErrors, VerifierResult};
+use crate::verifier::{
+    verify_context, verify_context_all, verify_legalized, verify_locations, VerifierErrors,
+    VerifierResult,
+};
+use core::mem;
+use std::boxed::Box;
 use std::vec::Vec;
 
+/// Callbacks invoked by `Context` during the compilation of a function.
+///
+/// An observer can be registered with `Context::set_observer()`, for example to dump the
+/// intermediate IR or compare the effects of the passes.
+pub trait CompileObserver {
+    /// The pass `name` of the compilation pipeline has transformed `func`.
+    ///
+    /// The names of the built-in passes match the ones of the `timing` module, and custom passes
+    /// are reported with their own name, see `Pass::name()`. Only the passes run by
+    /// `Context::compile()` and `Context::compile_split()` are reported.
+    fn on_pass_end(&mut self, name: &'static str, func: &Function);
+}
+
 /// Persistent data structures and compilation pipeline.
 pub struct Context {
     /// The function we're compiling.
@@ -49,6 +84,47 @@ pub struct Context {
 
     /// Loop analysis of `func`.
     pub loop_analysis: LoopAnalysis,
+
+    /// EBB execution frequencies of `func`, used to guide spilling.
+    ///
+    /// Profile counts can be recorded here before compiling. Otherwise the frequencies are
+    /// estimated from the loop nesting depth.
+    pub ebb_frequencies: EbbFrequencies,
+
+    /// Register allocation algorithm to use instead of the `regalloc_algorithm` setting of the
+    /// ISA.
+    ///
+    /// This makes it possible to pick an algorithm for each function. It is not reset by
+    /// `clear()`.
+    pub regalloc_algorithm: Option<RegallocAlgorithm>,
+
+    /// Compilation mode, trading the quality of the generated code for compile time.
+    ///
+    /// The `Baseline` mode is meant for the first tier of a JIT. It is not reset by `clear()`.
+    pub mode: CompileMode,
+
+    /// Trap sites recorded by the last call to `compile_and_emit`.
+    traps: TrapTable,
+
+    /// Source locations of the code generated by the last call to `compile`.
+    srclocs: SourceLocTable,
+
+    /// Diagnostics collected since the last call to `compile`.
+    diagnostics: Vec<Diagnostic>,
+
+    /// Register pressure computed before and after legalization, when compiling with
+    /// `compile_with_register_pressure`.
+    register_pressure: Option<Vec<RegisterPressure>>,
+
+    /// Statistics collected when compiling with `compile_with_stats`.
+    stats: Option<CompileStats>,
+
+    /// Observer notified at the end of each compilation pass. It is not reset by `clear()`.
+    observer: Option<Box<dyn CompileObserver>>,
+
+    /// Passes run by `compile`, or `None` to select them from `mode` and the settings of the ISA.
+    /// It is not reset by `clear()`.
+    pipeline: Option<Pipeline>,
 }
 
 impl Context {
@@ -71,6 +147,16 @@ impl Context {
             domtree: DominatorTree::new(),
             regalloc: regalloc::Context::new(),
             loop_analysis: LoopAnalysis::new(),
+            ebb_frequencies: EbbFrequencies::new(),
+            regalloc_algorithm: None,
+            mode: CompileMode::Standard,
+            traps: TrapTable::new(),
+            srclocs: SourceLocTable::new(),
+            diagnostics: Vec::new(),
+            register_pressure: None,
+            stats: None,
+            observer: None,
+            pipeline: None,
         }
     }
 
@@ -81,6 +167,12 @@ impl Context {
         self.domtree.clear();
         self.regalloc.clear();
         self.loop_analysis.clear();
+        self.ebb_frequencies.clear();
+        self.traps.clear();
+        self.srclocs.clear();
+        self.diagnostics.clear();
+        self.register_pressure = None;
+        self.stats = None;
     }
 
     /// Compile the function, and emit machine code into a `Vec<u8>`.
@@ -93,6 +185,8 @@ impl Context {
     /// This function calls `compile` and `emit_to_memory`, taking care to resize `mem` as
     /// needed, so it provides a safe interface.
     ///
+    /// Trap sites are forwarded to `traps` and also recorded in the context, see `traps()`.
+    ///
     /// Returns information about the function's code and read-only data.
     pub fn compile_and_emit(
         &mut self,
@@ -104,51 +198,420 @@ impl Context {
         let info = self.compile(isa)?;
         let old_len = mem.len();
         mem.resize(old_len + info.total_size as usize, 0);
-        let new_info =
-            unsafe { self.emit_to_memory(isa, mem.as_mut_ptr().add(old_len), relocs, traps) };
+        let mut table = mem::replace(&mut self.traps, TrapTable::new());
+        table.clear();
+        let new_info = {
+            let mut traps = RecordingTrapSink {
+                table: &mut table,
+                inner: traps,
+            };
+            unsafe { self.emit_to_memory(isa, mem.as_mut_ptr().add(old_len), relocs, &mut traps) }
+        };
+        self.traps = table;
         debug_assert!(new_info == info);
         Ok(info)
     }
 
+    /// Register `observer` to be notified at the end of each compilation pass, and return the
+    /// previously registered observer.
+    pub fn set_observer(
+        &mut self,
+        observer: Option<Box<dyn CompileObserver>>,
+    ) -> Option<Box<dyn CompileObserver>> {
+        mem::replace(&mut self.observer, observer)
+    }
+
+    /// Set the passes run by `compile`, and return the previous pipeline.
+    ///
+    /// When no pipeline is set, the passes are selected by `Pipeline::for_flags()` from the
+    /// settings of the target ISA, or are the ones of `Pipeline::baseline()` in the `Baseline`
+    /// compilation mode.
+    pub fn set_pipeline(&mut self, pipeline: Option<Pipeline>) -> Option<Pipeline> {
+        mem::replace(&mut self.pipeline, pipeline)
+    }
+
+    /// Get the trap sites recorded by the last call to `compile_and_emit`.
+    ///
+    /// The records are sorted by code offset and include the trap code and source location of
+    /// each trapping instruction.
+    pub fn traps(&self) -> &TrapTable {
+        &self.traps
+    }
+
+    /// Get the source locations of the code generated by the last call to `compile`.
+    ///
+    /// The table maps the code offsets of the machine instructions to the source locations of the
+    /// IR instructions they were generated from.
+    pub fn srclocs(&self) -> &SourceLocTable {
+        &self.srclocs
+    }
+
     /// Compile the function.
     ///
     /// Run the function through all the passes necessary to generate code for the target ISA
     /// represented by `isa`. This does not include the final step of emitting machine code into a
     /// code sink.
     ///
+    /// The passes are the ones of the pipeline set with `set_pipeline`, followed by branch
+    /// relaxation.
+    ///
+    /// When the `determinism_check` setting is enabled, a copy of the function is compiled first,
+    /// and this panics if the code emitted for the two copies differs.
+    ///
     /// Returns information about the function's code and read-only data.
     pub fn compile(&mut self, isa: &dyn TargetIsa) -> CodegenResult<CodeInfo> {
+        let check = isa.flags().determinism_check();
+        if check == DeterminismCheck::None {
+            return self.compile_once(isa);
+        }
+        let expected = self.compile_reference(isa, check == DeterminismCheck::Shuffle)?;
+        let info = self.compile_once(isa)?;
+        let actual = self.emit_for_check(isa, &info);
+        if actual != expected {
+            let offset = actual
+                .0
+                .iter()
+                .zip(&expected.0)
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| actual.0.len().min(expected.0.len()));
+            panic!(
+                "nondeterministic code generation for {}: the code of the two compilations differs \
+                 at offset {:#x}, or their relocations differ",
+                self.func.name, offset
+            );
+        }
+        Ok(info)
+    }
+
+    /// Run all the passes of `compile`.
+    fn compile_once(&mut self, isa: &dyn TargetIsa) -> CodegenResult<CodeInfo> {
         let _tt = timing::compile();
+        self.compile_passes(isa)?;
+        let info = self.relax_branches(isa)?;
+        self.end_pass("relax_branches");
+        self.srclocs.compute(&self.func, isa);
+        Ok(info)
+    }
+
+    /// Compile a copy of the function for the `determinism_check` setting, renumbered by
+    /// `shuffled_copy()` if `shuffle`, and return its code and relocations.
+    ///
+    /// The copy is compiled in another context with the same pipeline, mode, register allocation
+    /// algorithm and profile counts.
+    fn compile_reference(
+        &mut self,
+        isa: &dyn TargetIsa,
+        shuffle: bool,
+    ) -> CodegenResult<(Vec<u8>, Vec<RelocRecord>)> {
+        let (func, ebbs) = if shuffle {
+            let (func, ebbs) = shuffled_copy(&self.func);
+            (func, Some(ebbs))
+        } else {
+            (self.func.clone(), None)
+        };
+        let mut reference = Self::for_function(func);
+        if self.ebb_frequencies.has_profile() {
+            for ebb in self.func.layout.ebbs() {
+                let new_ebb = ebbs.as_ref().map_or(ebb, |ebbs| ebbs[ebb].unwrap());
+                let count = self.ebb_frequencies.frequency(ebb);
+                reference.ebb_frequencies.set_profile_count(new_ebb, count);
+            }
+        }
+        reference.regalloc_algorithm = self.regalloc_algorithm;
+        reference.mode = self.mode;
+        reference.pipeline = self.pipeline.take();
+        let result = reference.compile_once(isa);
+        self.pipeline = reference.pipeline.take();
+        let info = result?;
+        Ok(reference.emit_for_check(isa, &info))
+    }
+
+    /// Emit the compiled function for the `determinism_check` setting.
+    fn emit_for_check(&self, isa: &dyn TargetIsa, info: &CodeInfo) -> (Vec<u8>, Vec<RelocRecord>) {
+        let mut code = vec![0; info.total_size as usize];
+        let relocs = self.emit_to_buffer(isa, &mut code).relocs;
+        (code, relocs)
+    }
+
+    /// Compile the function with its cold EBBs in a separate code section, and emit the machine
+    /// code of the two sections into `hot` and `cold`.
+    ///
+    /// This runs the same passes as `compile`, except that branch relaxation places the cold
+    /// EBBs, like the trap blocks created by the legalizer, after the hot code and its jump
+    /// tables. The hot sink receives the jump tables. Both sinks must be empty.
+    ///
+    /// The branches between the two sections are listed in the returned `fixups`. Their
+    /// displacements must be patched once the sections are placed in memory, see
+    /// `SplitFixup::displacement()`.
+    pub fn compile_split(
+        &mut self,
+        isa: &dyn TargetIsa,
+        hot: &mut dyn CodeSink,
+        cold: &mut dyn CodeSink,
+    ) -> CodegenResult<SplitCodeInfo> {
+        let info = {
+            let _tt = timing::compile();
+            self.compile_passes(isa)?;
+            let info = self.relax_split_branches(isa)?;
+            self.end_pass("relax_branches");
+            info
+        };
+        let _tt = timing::binemit();
+        isa.emit_split_function(&self.func, &info, hot, cold);
+        Ok(info)
+    }
+
+    /// Run the passes of `compile` up to branch relaxation.
+    fn compile_passes(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        self.diagnostics.clear();
         self.verify_if(isa)?;
 
         self.compute_cfg();
-        if isa.flags().opt_level() != OptLevel::Fastest {
-            self.preopt(isa)?;
+        let pipeline = self.pipeline.take().unwrap_or_else(|| match self.mode {
+            CompileMode::Standard => Pipeline::for_flags(isa.flags()),
+            CompileMode::Baseline => Pipeline::baseline(),
+        });
+        let result = pipeline
+            .passes()
+            .iter()
+            .try_for_each(|pass| self.run_pass(pass, isa));
+        self.pipeline = Some(pipeline);
+        result
+    }
+
+    /// Run `pass`, after computing the analyses it needs.
+    fn run_pass(&mut self, pass: &Pass, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        match *pass {
+            Pass::Preopt => self.preopt(isa)?,
+            Pass::RematConstants => self.remat_constants(isa)?,
+            Pass::PruneEbbParams => {
+                self.compute_domtree();
+                self.prune_ebb_params(isa)?
+            }
+            Pass::ComplexAddresses => self.form_complex_addresses(isa)?,
+            Pass::HeapBounds => {
+                self.ensure_domtree();
+                self.heap_bounds(isa)?
+            }
+            Pass::CanonicalizeNans => self.canonicalize_nans(isa)?,
+            Pass::Legalize => {
+                self.record_register_pressure(isa, false);
+                self.legalize(isa)?;
+                self.verify_legalized_if(isa)?;
+                self.record_register_pressure(isa, true);
+            }
+            Pass::Postopt => self.postopt(isa)?,
+            Pass::RedundantTraps => {
+                self.compute_domtree();
+                self.redundant_traps(isa)?
+            }
+            Pass::Licm => {
+                self.ensure_domtree();
+                self.compute_loop_analysis();
+                self.licm(isa)?
+            }
+            Pass::Gvn => {
+                self.ensure_domtree();
+                self.simple_gvn(isa)?
+            }
+            Pass::Dce => {
+                self.compute_domtree();
+                self.dce(isa)?
+            }
+            Pass::UnreachableCode => {
+                self.compute_domtree();
+                self.eliminate_unreachable_code(isa)?
+            }
+            Pass::EbbLayout => self.ebb_layout(isa)?,
+            Pass::Regalloc => {
+                self.ensure_domtree();
+                self.regalloc(isa)?
+            }
+            Pass::PrologueEpilogue => self.prologue_epilogue(isa)?,
+            Pass::Peephole => self.peephole(isa)?,
+            Pass::ShrinkInstructions => self.shrink_instructions(isa)?,
+            Pass::Custom(_, ref f) => {
+                f(&mut self.func, isa);
+                self.domtree.clear();
+                self.loop_analysis.clear();
+                self.compute_cfg();
+                self.verify_if(isa)?
+            }
         }
-        if isa.flags().enable_nan_canonicalization() {
-            self.canonicalize_nans(isa)?;
+        self.end_pass(pass.name());
+        Ok(())
+    }
+
+    /// Compute the dominator tree unless it is already valid.
+    fn ensure_domtree(&mut self) {
+        if !self.domtree.is_valid() {
+            self.compute_domtree();
         }
-        self.legalize(isa)?;
-        if isa.flags().opt_level() != OptLevel::Fastest {
-            self.postopt(isa)?;
+    }
+
+    /// Compile the function like `compile`, and also return diagnostics about the code
+    /// generated for it.
+    ///
+    /// The diagnostics are notes about instructions that were lowered to slow code sequences, like
+    /// calls to runtime library functions. They don't prevent the function from being compiled.
+    pub fn compile_with_diagnostics(
+        &mut self,
+        isa: &dyn TargetIsa,
+    ) -> CodegenResult<(CodeInfo, Vec<Diagnostic>)> {
+        let info = self.compile(isa)?;
+        Ok((info, mem::replace(&mut self.diagnostics, Vec::new())))
+    }
+
+    /// Compile the function like `compile`, and also return a breakdown of the size of the
+    /// generated code.
+    ///
+    /// The report lists the encoded size of every EBB and instruction along with its source
+    /// location, as well as the sizes of jump tables, constant pools, and relocations.
+    pub fn compile_with_size_report(
+        &mut self,
+        isa: &dyn TargetIsa,
+    ) -> CodegenResult<(CodeInfo, CodeSizeReport)> {
+        let info = self.compile(isa)?;
+        let report = code_size_report(&self.func, &info, isa);
+        Ok((info, report))
+    }
+
+    /// Compile the function like `compile`, and also return reports of the register pressure
+    /// in each EBB before and after legalization.
+    ///
+    /// EBBs where more values are live after legalization than there are registers to hold them
+    /// are also reported as diagnostics, see `diagnostics()`.
+    pub fn compile_with_register_pressure(
+        &mut self,
+        isa: &dyn TargetIsa,
+    ) -> CodegenResult<(CodeInfo, PressureReport)> {
+        self.register_pressure = Some(Vec::new());
+        let result = self.compile(isa);
+        let mut reports = self.register_pressure.take().unwrap();
+        let info = result?;
+        let after_legalization = reports.pop().unwrap();
+        let before_legalization = reports.pop().unwrap();
+        Ok((
+            info,
+            PressureReport {
+                before_legalization,
+                after_legalization,
+            },
+        ))
+    }
+
+    /// Compile the function like `compile`, and also return statistics about the compilation.
+    ///
+    /// The statistics include the time spent in each pass, the number of instructions before and
+    /// after each transformation pass, the number of spills and fills inserted by the register
+    /// allocator, and the size of the generated code. The pass timings of the current thread, see
+    /// `timing::take_current()`, still include this compilation.
+    pub fn compile_with_stats(
+        &mut self,
+        isa: &dyn TargetIsa,
+    ) -> CodegenResult<(CodeInfo, CompileStats)> {
+        let outer_times = timing::take_current();
+        self.stats = Some(CompileStats {
+            input_insts: self.count_insts(),
+            ..Default::default()
+        });
+        let result = self.compile(isa);
+        let times = timing::take_current();
+        timing::add_to_current(&outer_times);
+        timing::add_to_current(&times);
+        let mut stats = self.stats.take().unwrap();
+        let info = result?;
+
+        stats.timings = times.passes();
+        for ebb in self.func.layout.ebbs() {
+            for inst in self.func.layout.ebb_insts(ebb) {
+                match self.func.dfg[inst].opcode() {
+                    Opcode::Spill | Opcode::Regspill => stats.spills += 1,
+                    Opcode::Fill | Opcode::Regfill => stats.fills += 1,
+                    _ => {}
+                }
+            }
         }
-        if isa.flags().opt_level() == OptLevel::Best {
-            self.compute_domtree();
-            self.compute_loop_analysis();
-            self.licm(isa)?;
-            self.simple_gvn(isa)?;
+        stats.spill_slots = self
+            .func
+            .stack_slots
+            .values()
+            .filter(|ss| ss.kind == StackSlotKind::SpillSlot)
+            .count();
+        stats.code_size = info.code_size;
+        stats.total_size = info.total_size;
+        Ok((info, stats))
+    }
+
+    /// Count the instructions in the function.
+    fn count_insts(&self) -> usize {
+        self.func
+            .layout
+            .ebbs()
+            .map(|ebb| self.func.layout.ebb_insts(ebb).count())
+            .sum()
+    }
+
+    /// Notify the observer that the pass `name` has ended, and record the number of instructions
+    /// after it if statistics are being collected.
+    fn end_pass(&mut self, name: &'static str) {
+        if let Some(observer) = self.observer.as_mut() {
+            observer.on_pass_end(name, &self.func);
         }
-        self.compute_domtree();
-        self.eliminate_unreachable_code(isa)?;
-        if isa.flags().opt_level() != OptLevel::Fastest {
-            self.dce(isa)?;
+        if self.stats.is_none() {
+            return;
         }
-        self.regalloc(isa)?;
-        self.prologue_epilogue(isa)?;
-        if isa.flags().opt_level() == OptLevel::Best {
-            self.shrink_instructions(isa)?;
+        let after = self.count_insts();
+        let stats = self.stats.as_mut().unwrap();
+        let before = stats
+            .inst_counts
+            .last()
+            .map_or(stats.input_insts, |count| count.after);
+        stats.inst_counts.push(PassInstCount {
+            name,
+            before,
+            after,
+        });
+    }
+
+    /// Compute the register pressure of the function if it is being tracked, and report the EBBs
+    /// that will need spilling when `legalized` is true.
+    fn record_register_pressure(&mut self, isa: &dyn TargetIsa, legalized: bool) {
+        let reports = match self.register_pressure {
+            Some(ref mut reports) => reports,
+            None => return,
+        };
+        let pressure = RegisterPressure::new(&self.func, &self.cfg, isa);
+        if legalized {
+            for ebb_pressure in pressure.ebbs() {
+                for (idx, rc) in pressure.classes().iter().enumerate() {
+                    let live = ebb_pressure.max_live[idx];
+                    let available = pressure.available()[idx];
+                    if live <= available {
+                        continue;
+                    }
+                    let srcloc = match ebb_pressure.peak[idx] {
+                        Some(inst) => self.func.srclocs[inst],
+                        None => Default::default(),
+                    };
+                    self.diagnostics.push(Diagnostic {
+                        srcloc,
+                        kind: DiagnosticKind::RegisterPressure {
+                            ebb: ebb_pressure.ebb,
+                            class: rc.name,
+                            live,
+                            available,
+                        },
+                    });
+                }
+            }
         }
-        self.relax_branches(isa)
+        reports.push(pressure);
+    }
+
+    /// Get the diagnostics collected since the last call to `compile`.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
 
     /// Emit machine code directly into raw memory.
@@ -175,6 +638,40 @@ impl Context {
         sink.info
     }
 
+    /// Emit machine code into `buf`, which must be at least as large as the `total_size`
+    /// returned by `compile`.
+    ///
+    /// Returns the sink holding the relocations and trap sites of the function. It can be used to
+    /// patch the relocations once the code and the referenced symbols are placed in memory.
+    pub fn emit_to_buffer<'b>(&self, isa: &dyn TargetIsa, buf: &'b mut [u8]) -> BufferCodeSink<'b> {
+        let _tt = timing::binemit();
+        let mut sink = BufferCodeSink::new(buf);
+        isa.emit_function_to_buffer(&self.func, &mut sink);
+        sink
+    }
+
+    /// Get the symbols defined and referenced by the function, with their linkage and visibility.
+    ///
+    /// The function itself comes first, followed by the external functions it declares, without
+    /// duplicate names. Object file writers can use this to decide the binding of each symbol.
+    pub fn symbols(&self) -> Vec<SymbolInfo> {
+        let mut symbols = vec![SymbolInfo {
+            name: self.func.name.clone(),
+            linkage: self.func.linkage,
+            visibility: self.func.visibility,
+        }];
+        for ext in self.func.dfg.ext_funcs.values() {
+            if symbols.iter().all(|sym| sym.name != ext.name) {
+                symbols.push(SymbolInfo {
+                    name: ext.name.clone(),
+                    linkage: ext.linkage,
+                    visibility: ext.visibility,
+                });
+            }
+        }
+        symbols
+    }
+
     /// Run the verifier on the function.
     ///
     /// Also check that the dominator tree and control flow graph are consistent with the function.
@@ -189,6 +686,14 @@ impl Context {
         }
     }
 
+    /// Run the verifier on the function, and report all the errors that can be found.
+    ///
+    /// Unlike `verify`, this doesn't stop at the first fatal error. The errors are categorized by
+    /// their `kind`.
+    pub fn verify_all<'a, FOI: Into<FlagsOrIsa<'a>>>(&self, fisa: FOI) -> VerifierErrors {
+        verify_context_all(&self.func, &self.cfg, &self.domtree, fisa)
+    }
+
     /// Run the verifier only if the `enable_verifier` setting is true.
     pub fn verify_if<'a, FOI: Into<FlagsOrIsa<'a>>>(&self, fisa: FOI) -> CodegenResult<()> {
         let fisa = fisa.into();
@@ -218,9 +723,32 @@ impl Context {
         Ok(())
     }
 
+    /// Run the post-legalization verifier on the function.
+    ///
+    /// This checks that all reachable instructions have been encoded for `isa`, and that the
+    /// function has an ABI-legalized signature.
+    pub fn verify_legalized(&self, isa: &dyn TargetIsa) -> VerifierResult<()> {
+        let mut errors = VerifierErrors::default();
+        let _ = verify_legalized(isa, &self.func, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Run the post-legalization verifier only if the `enable_verifier` setting is true.
+    pub fn verify_legalized_if(&self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        if isa.flags().enable_verifier() {
+            self.verify_legalized(isa)?;
+        }
+        Ok(())
+    }
+
     /// Perform dead-code elimination on the function.
     pub fn dce<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
-        do_dce(&mut self.func, &mut self.domtree);
+        do_dce(&mut self.func, &mut self.cfg, &mut self.domtree);
         self.verify_if(fisa)?;
         Ok(())
     }
@@ -232,6 +760,49 @@ impl Context {
         Ok(())
     }
 
+    /// Replace the EBB parameters that always receive the same constant with local constants.
+    pub fn remat_constants<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_remat_constants(&mut self.func, &self.cfg);
+        self.verify_if(fisa)
+    }
+
+    /// Remove the EBB parameters that are unused or always receive the same value.
+    pub fn prune_ebb_params<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_prune_ebb_params(&mut self.func, &self.cfg, &self.domtree);
+        self.verify_if(fisa)
+    }
+
+    /// Fold address computations into the loads and stores of the function.
+    pub fn form_complex_addresses(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        do_complex_addresses(&mut self.func, isa);
+        self.verify_if(isa)
+    }
+
+    /// Remove the bounds checks of the heap accesses that are proven to be in bounds.
+    ///
+    /// The dominator tree must be valid.
+    pub fn heap_bounds<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
+        do_heap_bounds(&mut self.func, &self.domtree);
+        self.verify_if(fisa)
+    }
+
+    /// Remove the conditional traps that are dominated by an identical check.
+    ///
+    /// The dominator tree must be valid.
+    pub fn redundant_traps<'a, FOI: Into<FlagsOrIsa<'a>>>(
+        &mut self,
+        fisa: FOI,
+    ) -> CodegenResult<()> {
+        do_redundant_traps(&mut self.func, &self.domtree);
+        self.verify_if(fisa)
+    }
+
     /// Perform NaN canonicalizing rewrites on the function.
     pub fn canonicalize_nans(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         do_nan_canonicalization(&mut self.func);
@@ -244,7 +815,12 @@ impl Context {
         // TODO: Avoid doing this when legalization doesn't actually mutate the CFG.
         self.domtree.clear();
         self.loop_analysis.clear();
-        legalize_function(&mut self.func, &mut self.cfg, isa);
+        legalize_function_with_diagnostics(
+            &mut self.func,
+            &mut self.cfg,
+            isa,
+            &mut self.diagnostics,
+        );
         self.verify_if(isa)
     }
 
@@ -279,10 +855,38 @@ impl Context {
 
     /// Perform simple GVN on the function.
     pub fn simple_gvn<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
-        do_simple_gvn(&mut self.func, &mut self.domtree);
+        do_simple_gvn(&mut self.func, &self.cfg, &mut self.domtree);
         self.verify_if(fisa)
     }
 
+    /// Simplify the control flow graph of the function.
+    ///
+    /// This folds constant branches and removes trivial EBBs. EBBs that become unreachable are
+    /// not removed.
+    pub fn simplify_cfg<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
+        // The pass changes the CFG, which invalidates the domtree and loop analysis.
+        self.domtree.clear();
+        self.loop_analysis.clear();
+        do_simplify_cfg(&mut self.func, &mut self.cfg);
+        self.verify_if(fisa)
+    }
+
+    /// Instrument the function with coverage counters for fuzzing.
+    ///
+    /// Each EBB increments an 8-bit counter in the array whose address is given by the global
+    /// value `counters`. Counter IDs are assigned starting at `first_id`, and must fit in an `i32`.
+    /// Returns the mapping from counter IDs to EBBs.
+    pub fn instrument_coverage(
+        &mut self,
+        isa: &dyn TargetIsa,
+        counters: GlobalValue,
+        first_id: u32,
+    ) -> CodegenResult<CoverageMap> {
+        let map = instrument_coverage(&mut self.func, counters, isa.pointer_type(), first_id)?;
+        self.verify_if(isa)?;
+        Ok(map)
+    }
+
     /// Perform LICM on the function.
     pub fn licm(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         do_licm(
@@ -295,6 +899,28 @@ impl Context {
         self.verify_if(isa)
     }
 
+    /// Inline calls to the functions provided by `callees`.
+    ///
+    /// At most `budget` callee instructions are inlined in total. Returns the number of call
+    /// sites that were inlined.
+    pub fn inline<'a, FOI>(
+        &mut self,
+        callees: &dyn CalleeLookup,
+        budget: usize,
+        fisa: FOI,
+    ) -> CodegenResult<usize>
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        let inlined = do_inlining(&mut self.func, callees, budget);
+        if inlined > 0 {
+            self.cfg.clear();
+            self.domtree.clear();
+        }
+        self.verify_if(fisa)?;
+        Ok(inlined)
+    }
+
     /// Perform unreachable code elimination.
     pub fn eliminate_unreachable_code<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
     where
@@ -304,10 +930,48 @@ impl Context {
         self.verify_if(fisa)
     }
 
+    /// Record the weights of the conditional branches provided by `profile`.
+    ///
+    /// Returns the number of branches that were given a weight. The weights are used by
+    /// `compile()` to lay out the EBBs so the hot paths fall through.
+    pub fn apply_branch_profile(&mut self, profile: &dyn BranchProfile) -> usize {
+        apply_branch_profile(&mut self.func, profile)
+    }
+
+    /// Reorder the EBBs so the hot paths fall through, according to the branch weights.
+    ///
+    /// The control flow graph must be valid, and is kept up to date. The dominator tree is
+    /// invalidated when branches are inverted. This does nothing if the function has no branch
+    /// weights.
+    pub fn ebb_layout(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        if do_ebb_layout(&mut self.func, &mut self.cfg, isa) {
+            self.domtree.clear();
+        }
+        self.verify_if(isa)
+    }
+
     /// Run the register allocator.
     pub fn regalloc(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
-        self.regalloc
-            .run(isa, &mut self.func, &self.cfg, &mut self.domtree)
+        let algorithm = self.regalloc_algorithm.unwrap_or_else(|| match self.mode {
+            CompileMode::Standard => isa.flags().regalloc_algorithm(),
+            CompileMode::Baseline => RegallocAlgorithm::LinearScan,
+        });
+        if self.regalloc.split_edges(isa, &mut self.func, algorithm) {
+            self.flowgraph();
+        }
+        if !self.ebb_frequencies.has_profile() {
+            self.compute_loop_analysis();
+        }
+        self.ebb_frequencies
+            .compute(&self.func, &self.loop_analysis);
+        self.regalloc.run(
+            isa,
+            &mut self.func,
+            &self.cfg,
+            &mut self.domtree,
+            &self.ebb_frequencies,
+            algorithm,
+        )
     }
 
     /// Insert prologue and epilogues after computing the stack frame layout.
@@ -318,6 +982,14 @@ impl Context {
         Ok(())
     }
 
+    /// Run the peephole optimizations on the register allocated function.
+    pub fn peephole(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
+        peephole_optimize(&mut self.func, isa);
+        self.verify_if(isa)?;
+        self.verify_locations_if(isa)?;
+        Ok(())
+    }
+
     /// Run the instruction shrinking pass.
     pub fn shrink_instructions(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         shrink_instructions(&mut self.func, isa);
@@ -330,6 +1002,20 @@ impl Context {
     /// read-only data.
     pub fn relax_branches(&mut self, isa: &dyn TargetIsa) -> CodegenResult<CodeInfo> {
         let info = relax_branches(&mut self.func, isa)?;
+        // Moving the cold EBBs may have inverted branches, which changes the CFG edges.
+        self.compute_cfg();
+        self.domtree.clear();
+        self.verify_if(isa)?;
+        self.verify_locations_if(isa)?;
+        Ok(info)
+    }
+
+    /// Run the branch relaxation pass with the cold EBBs in a separate code section, and return
+    /// information about the two sections.
+    pub fn relax_split_branches(&mut self, isa: &dyn TargetIsa) -> CodegenResult<SplitCodeInfo> {
+        let info = relax_split_branches(&mut self.func, isa)?;
+        self.compute_cfg();
+        self.domtree.clear();
         self.verify_if(isa)?;
         self.verify_locations_if(isa)?;
         Ok(info)
@@ -346,4 +1032,137 @@ impl Context {
             isa,
         ))
     }
+
+    /// Build the line table and the locations of the values marked by `debug_value` instructions
+    /// of the compiled function.
+    pub fn debug_info(&self, isa: &dyn TargetIsa) -> DebugInfo {
+        build_debug_info(&self.func, &self.regalloc, isa)
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::{CompileObserver, Context};
+    use crate::ir::{Function, SourceLoc};
+    use crate::pipeline::{CompileMode, Pass, Pipeline};
+    use crate::settings::{self, Configurable};
+    use crate::test_utils;
+    use std::boxed::Box;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    struct PassLog(Rc<RefCell<Vec<&'static str>>>);
+
+    impl CompileObserver for PassLog {
+        fn on_pass_end(&mut self, name: &'static str, func: &Function) {
+            assert!(func.layout.entry_block().is_some());
+            self.0.borrow_mut().push(name);
+        }
+    }
+
+    #[test]
+    fn observer() {
+        let isa = test_utils::isa("x86_64");
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut ctx = Context::for_function(test_utils::double());
+        assert!(ctx
+            .set_observer(Some(Box::new(PassLog(log.clone()))))
+            .is_none());
+        ctx.compile(&*isa).unwrap();
+
+        let passes = log.borrow();
+        let position = |name| passes.iter().position(|&pass| pass == name).unwrap();
+        assert!(position("legalize") < position("regalloc"));
+        assert_eq!(passes.last(), Some(&"relax_branches"));
+
+        assert!(ctx.set_observer(None).is_some());
+    }
+
+    #[test]
+    fn custom_pipeline() {
+        let isa = test_utils::isa("x86_64");
+
+        let mut passes = Pipeline::fast().into_passes();
+        passes.insert(
+            1,
+            Pass::custom("srcloc", |func, _| {
+                let ebb = func.layout.entry_block().unwrap();
+                for inst in func.layout.ebb_insts(ebb) {
+                    func.srclocs[inst] = SourceLoc::new(42);
+                }
+            }),
+        );
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut ctx = Context::for_function(test_utils::double());
+        ctx.set_observer(Some(Box::new(PassLog(log.clone()))));
+        assert!(ctx.set_pipeline(Some(Pipeline::custom(passes))).is_none());
+        ctx.compile(&*isa).unwrap();
+
+        assert_eq!(
+            *log.borrow(),
+            [
+                "legalize",
+                "srcloc",
+                "unreachable_code",
+                "ebb_layout",
+                "regalloc",
+                "prologue_epilogue",
+                "relax_branches"
+            ]
+        );
+        assert!(ctx
+            .srclocs()
+            .records()
+            .iter()
+            .any(|r| r.srcloc == SourceLoc::new(42)));
+
+        // The pipeline is kept for the next function.
+        log.borrow_mut().clear();
+        ctx.clear();
+        ctx.func = test_utils::double();
+        ctx.compile(&*isa).unwrap();
+        assert_eq!(log.borrow()[1], "srcloc");
+    }
+
+    #[test]
+    fn baseline() {
+        let isa = test_utils::isa("x86_64");
+
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut ctx = Context::for_function(test_utils::double());
+        ctx.mode = CompileMode::Baseline;
+        ctx.set_observer(Some(Box::new(PassLog(log.clone()))));
+        let info = ctx.compile(&*isa).unwrap();
+        assert!(info.code_size > 0);
+        assert_eq!(
+            *log.borrow(),
+            [
+                "legalize",
+                "unreachable_code",
+                "regalloc",
+                "prologue_epilogue",
+                "relax_branches"
+            ]
+        );
+    }
+
+    #[test]
+    fn determinism_check() {
+        let plain = test_utils::isa("x86_64");
+        let expected = Context::for_function(test_utils::double())
+            .compile(&*plain)
+            .unwrap();
+
+        for &check in &["repeat", "shuffle"] {
+            let mut flags = settings::builder();
+            flags.set("determinism_check", check).unwrap();
+            let isa = test_utils::isa_with_flags("x86_64", flags);
+            let info = Context::for_function(test_utils::double())
+                .compile(&*isa)
+                .unwrap();
+            assert_eq!(info, expected);
+        }
+    }
 }
@@ -0,0 +1,173 @@
+//! Coverage instrumentation for fuzzing.
+//!
+//! This pass assigns a counter ID to every EBB in a function and inserts an inline increment of
+//! an 8-bit counter at the top of each EBB, in the style of SanitizerCoverage's
+//! `inline-8bit-counters` mode. The counters live in a byte array provided by the embedder
+//! through a global value, and the returned `CoverageMap` tells the embedder which EBB and source
+//! location each counter corresponds to.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::ir::{Ebb, Function, GlobalValue, InstBuilder, MemFlags, SourceLoc, Type};
+use crate::result::{CodegenError, CodegenResult};
+use crate::timing;
+use core::convert::TryFrom;
+use std::vec::Vec;
+
+/// A single coverage counter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoverageEntry {
+    /// Index of the counter in the counter array.
+    pub id: u32,
+    /// The EBB that increments the counter.
+    pub ebb: Ebb,
+    /// Source location of the first instruction in the EBB.
+    pub srcloc: SourceLoc,
+}
+
+/// The coverage counters assigned to the EBBs of a function.
+#[derive(Clone, Debug, Default)]
+pub struct CoverageMap {
+    entries: Vec<CoverageEntry>,
+}
+
+impl CoverageMap {
+    /// Get the number of counters used by the function.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Is this map empty?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get all the counters, in increasing ID order.
+    pub fn entries(&self) -> &[CoverageEntry] {
+        &self.entries
+    }
+
+    /// Find the counter ID assigned to `ebb`, if any.
+    pub fn id_of(&self, ebb: Ebb) -> Option<u32> {
+        self.entries.iter().find(|e| e.ebb == ebb).map(|e| e.id)
+    }
+}
+
+/// Insert coverage counter increments into `func`.
+///
+/// The address of the counter array is given by the global value `counters` of type `addr_ty`.
+/// Counter IDs are assigned to the EBBs in layout order, starting at `first_id`, so the embedder
+/// can share one counter array between several functions.
+///
+/// Fails with `ImplLimitExceeded` if a counter ID is too large to be used as a load offset, in
+/// which case `func` is left partially instrumented.
+pub fn instrument_coverage(
+    func: &mut Function,
+    counters: GlobalValue,
+    addr_ty: Type,
+    first_id: u32,
+) -> CodegenResult<CoverageMap> {
+    let _tt = timing::coverage();
+    let mut map = CoverageMap::default();
+
+    let entry = match func.layout.entry_block() {
+        Some(entry) => entry,
+        None => return Ok(map),
+    };
+
+    // Materialize the counter array address once in the entry block, which dominates all the
+    // other EBBs.
+    let mut pos = FuncCursor::new(func);
+    pos.goto_first_insertion_point(entry);
+    let base = pos.ins().global_value(addr_ty, counters);
+
+    // Collect the EBBs first, so the instructions inserted in the entry block are not counted.
+    let ebbs: Vec<Ebb> = pos.func.layout.ebbs().collect();
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+
+    for (ebb, id) in ebbs.into_iter().zip(first_id..) {
+        if ebb == entry {
+            pos.goto_after_inst(pos.func.dfg.value_def(base).unwrap_inst());
+        } else {
+            pos.goto_first_insertion_point(ebb);
+        }
+        let srcloc = match pos.current_inst() {
+            Some(inst) => pos.func.srclocs[inst],
+            None => SourceLoc::default(),
+        };
+        pos.set_srcloc(srcloc);
+
+        let offset = i32::try_from(id).map_err(|_| CodegenError::ImplLimitExceeded)?;
+        let count = pos.ins().uload8(addr_ty, flags, base, offset);
+        let count = pos.ins().iadd_imm(count, 1);
+        pos.ins().istore8(flags, count, base, offset);
+
+        map.entries.push(CoverageEntry { id, ebb, srcloc });
+    }
+
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::types::I64;
+    use crate::ir::{ExternalName, GlobalValueData, Opcode};
+
+    // A function with two EBBs, and the global value of its counter array.
+    fn two_ebbs() -> (Function, GlobalValue, Ebb, Ebb) {
+        let mut func = Function::new();
+        let counters = func.create_global_value(GlobalValueData::Symbol {
+            name: ExternalName::testcase("counters"),
+            offset: 0.into(),
+            colocated: false,
+        });
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.ins().jump(ebb1, &[]);
+            pos.insert_ebb(ebb1);
+            pos.ins().return_(&[]);
+        }
+        (func, counters, ebb0, ebb1)
+    }
+
+    #[test]
+    fn instrument() {
+        let (mut func, counters, ebb0, ebb1) = two_ebbs();
+        let map = instrument_coverage(&mut func, counters, I64, 10).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.id_of(ebb0), Some(10));
+        assert_eq!(map.id_of(ebb1), Some(11));
+
+        let opcodes: Vec<Opcode> = func
+            .layout
+            .ebb_insts(ebb0)
+            .map(|inst| func.dfg[inst].opcode())
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                Opcode::GlobalValue,
+                Opcode::Uload8,
+                Opcode::IaddImm,
+                Opcode::Istore8,
+                Opcode::Jump
+            ]
+        );
+        assert_eq!(func.layout.ebb_insts(ebb1).count(), 4);
+    }
+
+    #[test]
+    fn id_too_large() {
+        // The second counter ID doesn't fit in a load offset.
+        let (mut func, counters, _, _) = two_ebbs();
+        let first_id = i32::max_value() as u32;
+        assert_eq!(
+            instrument_coverage(&mut func, counters, I64, first_id).unwrap_err(),
+            CodegenError::ImplLimitExceeded
+        );
+    }
+}
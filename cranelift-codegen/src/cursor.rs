@@ -4,6 +4,7 @@
 
 use crate::ir;
 use crate::isa::TargetIsa;
+use std::vec::Vec;
 
 /// The possible positions of a cursor.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -570,33 +571,149 @@ pub trait Cursor {
 ///
 /// This cursor is for use before legalization. The inserted instructions are not given an
 /// encoding.
+///
+/// # Transactions
+///
+/// Speculative changes can be made in a transaction started by `begin_transaction()`. While the
+/// transaction is open, the cursor records the instructions and EBBs it inserts and the
+/// instructions it removes. Instructions that are changed in place through `cur.func.dfg` must be
+/// recorded with `save_inst()` before they are changed. The transaction ends with either
+/// `commit()`, which keeps the changes, or `rollback()`, which undoes them without cloning the
+/// function.
+///
+/// Changes made directly to `cur.func` without going through the cursor are not recorded.
 pub struct FuncCursor<'f> {
     pos: CursorPosition,
     srcloc: ir::SourceLoc,
+    provenance: Option<ir::ProvenanceId>,
+    transaction: Option<Transaction>,
 
     /// The referenced function.
     pub func: &'f mut ir::Function,
 }
 
+/// The changes recorded in an open `FuncCursor` transaction.
+struct Transaction {
+    /// Cursor position when the transaction began.
+    start: CursorPosition,
+    /// Changes in the order they were made.
+    undo: Vec<Undo>,
+}
+
+/// A recorded change, and how to undo it.
+enum Undo {
+    /// An instruction was inserted. Remove it.
+    Insert(ir::Inst),
+    /// An instruction was removed. Put it back before the instruction or at the end of the EBB
+    /// indicated by the position.
+    Remove(ir::Inst, CursorPosition),
+    /// An EBB was inserted, possibly by splitting another EBB. Move its instructions back to the
+    /// EBB it was split from, and remove it.
+    InsertEbb(ir::Ebb, Option<ir::Ebb>),
+    /// An instruction was changed in place. Restore it.
+    Change(ir::SavedInst),
+}
+
 impl<'f> FuncCursor<'f> {
     /// Create a new `FuncCursor` pointing nowhere.
     pub fn new(func: &'f mut ir::Function) -> Self {
         Self {
             pos: CursorPosition::Nowhere,
             srcloc: Default::default(),
+            provenance: None,
+            transaction: None,
             func,
         }
     }
 
     /// Use the source location of `inst` for future instructions.
+    ///
+    /// If provenance is being collected, future instructions also inherit the provenance of
+    /// `inst`.
     pub fn use_srcloc(&mut self, inst: ir::Inst) {
         self.srcloc = self.func.srclocs[inst];
+        self.provenance = self.func.dfg.inst_provenance(inst);
     }
 
     /// Create an instruction builder that inserts an instruction at the current position.
     pub fn ins(&mut self) -> ir::InsertBuilder<&mut FuncCursor<'f>> {
         ir::InsertBuilder::new(self)
     }
+
+    /// Begin a transaction that can be undone with `rollback()`.
+    ///
+    /// Transactions can't be nested.
+    pub fn begin_transaction(&mut self) {
+        assert!(self.transaction.is_none(), "Transaction already open");
+        self.transaction = Some(Transaction {
+            start: self.pos,
+            undo: Vec::new(),
+        });
+    }
+
+    /// Is there an open transaction?
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Save `inst` before changing it through `self.func.dfg`, so a rollback of the open
+    /// transaction restores it.
+    ///
+    /// Does nothing if there is no open transaction.
+    pub fn save_inst(&mut self, inst: ir::Inst) {
+        if let Some(ref mut txn) = self.transaction {
+            txn.undo.push(Undo::Change(self.func.dfg.save_inst(inst)));
+        }
+    }
+
+    /// End the open transaction and keep all the changes made since it began.
+    pub fn commit(&mut self) {
+        self.transaction.take().expect("No transaction to commit");
+    }
+
+    /// End the open transaction and undo all the changes made since it began.
+    ///
+    /// The cursor is moved back to the position it had when the transaction began. Instructions
+    /// and EBBs created during the transaction are removed from the layout, but they remain in the
+    /// data flow graph.
+    pub fn rollback(&mut self) {
+        let txn = self
+            .transaction
+            .take()
+            .expect("No transaction to roll back");
+        let layout = &mut self.func.layout;
+        for undo in txn.undo.into_iter().rev() {
+            match undo {
+                Undo::Insert(inst) => layout.remove_inst(inst),
+                Undo::Remove(inst, CursorPosition::At(next)) => layout.insert_inst(inst, next),
+                Undo::Remove(inst, CursorPosition::After(ebb)) => layout.append_inst(inst, ebb),
+                Undo::Remove(..) => unreachable!(),
+                Undo::InsertEbb(ebb, split_from) => {
+                    if let Some(orig) = split_from {
+                        while let Some(inst) = layout.first_inst(ebb) {
+                            layout.remove_inst(inst);
+                            layout.append_inst(inst, orig);
+                        }
+                    }
+                    layout.remove_ebb(ebb);
+                }
+                Undo::Change(saved) => self.func.dfg.restore_inst(saved),
+            }
+        }
+        self.pos = txn.start;
+    }
+
+    /// Record that `inst` is about to be removed from the layout.
+    fn record_removal(&mut self, inst: ir::Inst) {
+        if let Some(ref mut txn) = self.transaction {
+            let layout = &self.func.layout;
+            let place = match layout.next_inst(inst) {
+                Some(next) => CursorPosition::At(next),
+                None => CursorPosition::After(layout.inst_ebb(inst).expect("Inst not in layout")),
+            };
+            txn.undo.push(Undo::Remove(inst, place));
+        }
+    }
 }
 
 impl<'f> Cursor for FuncCursor<'f> {
@@ -623,6 +740,56 @@ impl<'f> Cursor for FuncCursor<'f> {
     fn layout_mut(&mut self) -> &mut ir::Layout {
         &mut self.func.layout
     }
+
+    fn insert_inst(&mut self, inst: ir::Inst) {
+        match self.pos {
+            CursorPosition::Nowhere | CursorPosition::Before(..) => {
+                panic!("Invalid insert_inst position")
+            }
+            CursorPosition::At(cur) => self.func.layout.insert_inst(inst, cur),
+            CursorPosition::After(ebb) => self.func.layout.append_inst(inst, ebb),
+        }
+        if let Some(ref mut txn) = self.transaction {
+            txn.undo.push(Undo::Insert(inst));
+        }
+    }
+
+    fn remove_inst(&mut self) -> ir::Inst {
+        let inst = self.current_inst().expect("No instruction to remove");
+        self.record_removal(inst);
+        self.next_inst();
+        self.func.layout.remove_inst(inst);
+        inst
+    }
+
+    fn remove_inst_and_step_back(&mut self) -> ir::Inst {
+        let inst = self.current_inst().expect("No instruction to remove");
+        self.record_removal(inst);
+        self.prev_inst();
+        self.func.layout.remove_inst(inst);
+        inst
+    }
+
+    fn insert_ebb(&mut self, new_ebb: ir::Ebb) {
+        if let Some(ref mut txn) = self.transaction {
+            let split_from = match self.pos {
+                CursorPosition::At(inst) => self.func.layout.inst_ebb(inst),
+                _ => None,
+            };
+            txn.undo.push(Undo::InsertEbb(new_ebb, split_from));
+        }
+        match self.pos {
+            CursorPosition::At(inst) => {
+                self.func.layout.split_ebb(new_ebb, inst);
+                // All other cases move to `After(ebb)`, but in this case we'll stay `At(inst)`.
+                return;
+            }
+            CursorPosition::Nowhere => self.func.layout.append_ebb(new_ebb),
+            CursorPosition::Before(ebb) => self.func.layout.insert_ebb(new_ebb, ebb),
+            CursorPosition::After(ebb) => self.func.layout.insert_ebb_after(new_ebb, ebb),
+        }
+        self.pos = CursorPosition::After(new_ebb);
+    }
 }
 
 impl<'c, 'f> ir::InstInserterBase<'c> for &'c mut FuncCursor<'f> {
@@ -639,6 +806,9 @@ impl<'c, 'f> ir::InstInserterBase<'c> for &'c mut FuncCursor<'f> {
         if !self.srcloc.is_default() {
             self.func.srclocs[inst] = self.srcloc;
         }
+        if let Some(id) = self.provenance {
+            self.func.dfg.set_inst_provenance(inst, id);
+        }
         &mut self.func.dfg
     }
 }
@@ -763,3 +933,53 @@ impl<'c, 'f> ir::InstInserterBase<'c> for &'c mut EncCursor<'f> {
         &mut self.func.dfg
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Cursor, FuncCursor};
+    use crate::ir::{types, Function, InstBuilder, TrapCode};
+    use std::string::{String, ToString};
+
+    fn text(func: &Function) -> String {
+        func.display(None).to_string()
+    }
+
+    #[test]
+    fn rollback() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb0, types::I32);
+        let ebb1 = func.dfg.make_ebb();
+        let (add, ret) = {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v = pos.ins().iadd(arg, arg);
+            let add = pos.func.dfg.value_def(v).unwrap_inst();
+            let ret = pos.ins().return_(&[v]);
+            (add, ret)
+        };
+        let before = text(&func);
+
+        let mut pos = FuncCursor::new(&mut func).at_inst(add);
+        pos.begin_transaction();
+        // Insert and remove instructions, split the EBB, and change an instruction in place.
+        let c = pos.ins().iconst(types::I32, 2);
+        pos.save_inst(add);
+        pos.func.dfg.replace(add).imul(arg, c);
+        pos.insert_ebb(ebb1);
+        pos.goto_inst(ret);
+        pos.remove_inst();
+        pos.ins().trap(TrapCode::User(0));
+        assert!(pos.in_transaction());
+        pos.rollback();
+        assert!(!pos.in_transaction());
+        assert_eq!(pos.current_inst(), Some(add));
+        assert_eq!(text(pos.func), before);
+
+        // A committed transaction keeps the changes.
+        pos.begin_transaction();
+        pos.ins().iconst(types::I32, 2);
+        pos.commit();
+        assert_ne!(text(pos.func), before);
+    }
+}
@@ -1,25 +1,19 @@
 //! A Dead-Code Elimination (DCE) pass.
 //!
-//! Dead code here means instructions that have no side effects and have no
-//! result values used by other instructions.
+//! Dead code here means EBBs that can't be reached from the entry block, and
+//! instructions that have no side effects and have no result values used by
+//! other instructions. Whether an instruction has side effects is decided by
+//! the opcode metadata, so instructions like `global_value` and `heap_addr`
+//! are removed when their results are unused.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::dominator_tree::DominatorTree;
 use crate::entity::EntityRef;
+use crate::flowgraph::ControlFlowGraph;
 use crate::ir::instructions::InstructionData;
 use crate::ir::{DataFlowGraph, Function, Inst, Opcode};
 use crate::timing;
-
-/// Test whether the given opcode is unsafe to even consider for DCE.
-fn trivially_unsafe_for_dce(opcode: Opcode) -> bool {
-    opcode.is_call()
-        || opcode.is_branch()
-        || opcode.is_terminator()
-        || opcode.is_return()
-        || opcode.can_trap()
-        || opcode.other_side_effects()
-        || opcode.can_store()
-}
+use crate::unreachable_code::eliminate_unreachable_code;
 
 /// Preserve instructions with used result values.
 fn any_inst_results_used(inst: Inst, live: &[bool], dfg: &DataFlowGraph) -> bool {
@@ -30,7 +24,7 @@ fn any_inst_results_used(inst: Inst, live: &[bool], dfg: &DataFlowGraph) -> bool
 /// operating on inaccessible memory, so we can't DCE them even if the
 /// loaded value is unused.
 fn is_load_with_defined_trapping(opcode: Opcode, data: &InstructionData) -> bool {
-    if !opcode.can_load() {
+    if !opcode.effects().reads_memory {
         return false;
     }
     match *data {
@@ -41,10 +35,14 @@ fn is_load_with_defined_trapping(opcode: Opcode, data: &InstructionData) -> bool
 }
 
 /// Perform DCE on `func`.
-pub fn do_dce(func: &mut Function, domtree: &mut DominatorTree) {
+///
+/// The unreachable EBBs are removed first, since the values they use aren't kept alive.
+pub fn do_dce(func: &mut Function, cfg: &mut ControlFlowGraph, domtree: &mut DominatorTree) {
     let _tt = timing::dce();
     debug_assert!(domtree.is_valid());
 
+    eliminate_unreachable_code(func, cfg, domtree);
+
     let mut live = vec![false; func.dfg.num_values()];
     for &ebb in domtree.cfg_postorder() {
         let mut pos = FuncCursor::new(func).at_bottom(ebb);
@@ -52,7 +50,7 @@ pub fn do_dce(func: &mut Function, domtree: &mut DominatorTree) {
             {
                 let data = &pos.func.dfg[inst];
                 let opcode = data.opcode();
-                if trivially_unsafe_for_dce(opcode)
+                if opcode.effects().has_side_effects()
                     || is_load_with_defined_trapping(opcode, &data)
                     || any_inst_results_used(inst, &live, &pos.func.dfg)
                 {
@@ -0,0 +1,213 @@
+//! Debug information for the generated machine code.
+//!
+//! After a function is compiled, `build_debug_info()` collects what an embedder needs to produce
+//! DWARF for JIT-compiled code:
+//!
+//! - A line table mapping the code offset of each emitted machine instruction to the `SourceLoc`
+//!   of the IR instruction it was generated from.
+//! - The locations of source variables. The frontend marks the value of a variable with a
+//!   `debug_value x, label` instruction, and after register allocation the register or stack slot
+//!   holding `x` is reported for the code following that instruction.
+//!
+//! The location of a variable is tracked from its `debug_value` until the next `debug_value` of
+//! the same label, the end of the live range of the value, or the end of the EBB, whichever comes
+//! first. Copies, spills and fills of the value are followed.
+
+use crate::binemit::{CodeOffset, SourceLocTable};
+use crate::ir::{Ebb, Function, Inst, InstructionData, Opcode, Value, ValueLabel, ValueLoc};
+use crate::isa::TargetIsa;
+use crate::regalloc::{Context, RegDiversions};
+use crate::value_label::{ValueLabelsRanges, ValueLocRange};
+use std::vec::Vec;
+
+/// Debug information of a compiled function.
+#[derive(Clone, Debug)]
+pub struct DebugInfo {
+    /// The line table, mapping code offsets to source locations.
+    pub lines: SourceLocTable,
+
+    /// The locations of the labels set with `debug_value` instructions, sorted by code offset.
+    pub values: ValueLabelsRanges,
+}
+
+/// A label whose value is being tracked.
+#[derive(Clone, Copy)]
+struct Tracked {
+    label: ValueLabel,
+    value: Value,
+    start: CodeOffset,
+    loc: ValueLoc,
+}
+
+/// Build the debug information of `func`, after register allocation and branch relaxation.
+///
+/// The live ranges computed by the register allocator in `regalloc` are used to end the locations
+/// of the variables.
+pub fn build_debug_info(func: &Function, regalloc: &Context, isa: &dyn TargetIsa) -> DebugInfo {
+    let mut ebbs = func.layout.ebbs().collect::<Vec<_>>();
+    ebbs.sort_by_key(|ebb| func.offsets[*ebb]);
+    let encinfo = isa.encoding_info();
+
+    let mut info = DebugInfo {
+        lines: SourceLocTable::new(),
+        values: ValueLabelsRanges::new(),
+    };
+    info.lines.compute(func, isa);
+    let mut tracked: Vec<Tracked> = Vec::new();
+    let mut divert = RegDiversions::new();
+    for ebb in ebbs {
+        divert.clear();
+        let mut end = func.offsets[ebb];
+        for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+            end = offset + size;
+
+            // Follow the tracked values into their copies, and restart their ranges when the
+            // register allocator moves them.
+            divert.apply(&func.dfg[inst]);
+            let mut i = 0;
+            while i < tracked.len() {
+                let t = tracked[i];
+                if killed_at(regalloc, func, t.value, inst, ebb) {
+                    tracked.swap_remove(i);
+                    add_range(&mut info.values, t, end);
+                    if let Some(copy) = copy_of(func, inst, t.value) {
+                        tracked.push(Tracked {
+                            value: copy,
+                            start: end,
+                            loc: divert.get(copy, &func.locations),
+                            ..t
+                        });
+                    }
+                    continue;
+                }
+                let loc = divert.get(t.value, &func.locations);
+                if loc != t.loc {
+                    add_range(&mut info.values, t, end);
+                    tracked[i].start = end;
+                    tracked[i].loc = loc;
+                }
+                i += 1;
+            }
+
+            if let InstructionData::BinaryImm {
+                opcode: Opcode::DebugValue,
+                arg,
+                imm,
+            } = func.dfg[inst]
+            {
+                let imm: i64 = imm.into();
+                let label = ValueLabel::from_u32(imm as u32);
+                if let Some(i) = tracked.iter().position(|t| t.label == label) {
+                    let t = tracked.swap_remove(i);
+                    add_range(&mut info.values, t, offset);
+                }
+                let value = func.dfg.resolve_aliases(arg);
+                if !killed_at(regalloc, func, value, inst, ebb) {
+                    tracked.push(Tracked {
+                        label,
+                        value,
+                        start: offset,
+                        loc: divert.get(value, &func.locations),
+                    });
+                }
+            }
+        }
+        for t in tracked.drain(..) {
+            add_range(&mut info.values, t, end);
+        }
+    }
+
+    for ranges in info.values.values_mut() {
+        ranges.sort_by_key(|range| range.start);
+    }
+    info
+}
+
+/// Is the live range of `value` ending at `inst`?
+fn killed_at(regalloc: &Context, func: &Function, value: Value, inst: Inst, ebb: Ebb) -> bool {
+    let liveness = regalloc.liveness();
+    match liveness.get(value) {
+        Some(lr) => lr.killed_at(inst, ebb, liveness.context(&func.layout)),
+        None => true,
+    }
+}
+
+/// Get the result of `inst` if it is a copy, spill or fill of `value`.
+fn copy_of(func: &Function, inst: Inst, value: Value) -> Option<Value> {
+    match func.dfg[inst] {
+        InstructionData::Unary {
+            opcode: Opcode::Copy,
+            arg,
+        }
+        | InstructionData::Unary {
+            opcode: Opcode::Spill,
+            arg,
+        }
+        | InstructionData::Unary {
+            opcode: Opcode::Fill,
+            arg,
+        } if func.dfg.resolve_aliases(arg) == value => Some(func.dfg.first_result(inst)),
+        _ => None,
+    }
+}
+
+/// Record the range of `tracked` ending at `end`, unless it is empty or has no location.
+fn add_range(values: &mut ValueLabelsRanges, tracked: Tracked, end: CodeOffset) {
+    if tracked.start >= end || !tracked.loc.is_assigned() {
+        return;
+    }
+    values
+        .entry(tracked.label)
+        .or_default()
+        .push(ValueLocRange {
+            loc: tracked.loc,
+            start: tracked.start,
+            end,
+        });
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use crate::cursor::Cursor;
+    use crate::ir::types::I32;
+    use crate::ir::{InstBuilder, SourceLoc, ValueLabel, ValueLoc};
+    use crate::test_utils;
+    use crate::Context;
+
+    #[test]
+    fn debug_value() {
+        let isa = test_utils::isa("x86_64");
+        let func = test_utils::function(&[I32, I32], &[I32], |pos, args| {
+            pos.set_srcloc(SourceLoc::new(1));
+            let sum = pos.ins().iadd(args[0], args[1]);
+            pos.ins().debug_value(sum, 7);
+            pos.set_srcloc(SourceLoc::new(2));
+            let product = pos.ins().imul(sum, args[0]);
+            pos.ins().return_(&[product]);
+        });
+
+        let mut ctx = Context::for_function(func);
+        let info = ctx.compile(&*isa).unwrap();
+        let debug = ctx.debug_info(&*isa);
+
+        // The line table covers both source locations.
+        let lines = debug.lines.records();
+        let row = |srcloc: SourceLoc| lines.iter().position(|row| row.srcloc == srcloc);
+        let (row1, row2) = (
+            row(SourceLoc::new(1)).unwrap(),
+            row(SourceLoc::new(2)).unwrap(),
+        );
+        assert!(row1 < row2);
+        assert_eq!(debug.lines.code_size(), info.code_size);
+
+        // The sum lives in a register from the `debug_value` to the multiplication.
+        let ranges = &debug.values[&ValueLabel::from_u32(7)];
+        assert_eq!(ranges.len(), 1);
+        match ranges[0].loc {
+            ValueLoc::Reg(_) => {}
+            loc => panic!("unexpected location {:?}", loc),
+        }
+        assert_eq!(ranges[0].start, lines[row2].offset);
+        assert!(ranges[0].end > ranges[0].start);
+    }
+}
@@ -0,0 +1,153 @@
+//! Support for the `determinism_check` setting.
+//!
+//! When the setting is enabled, `Context::compile()` also compiles a reference copy of the
+//! function and checks that the emitted code is identical. Nondeterminism typically comes from
+//! iterating over a hash map, whose order can change from one run to the next, or with the
+//! numbering of the entities used as keys.
+//!
+//! With `determinism_check = "shuffle"`, the reference copy is renumbered by `shuffled_copy()`:
+//! its EBBs, instructions and values are created in the reverse layout order. The copy is
+//! otherwise identical, so the generated code should be the same.
+
+use crate::entity::SecondaryMap;
+use crate::ir::{Ebb, Function, Inst, InstructionData, JumpTableData, Value, ValueList};
+use crate::packed_option::PackedOption;
+use std::vec::Vec;
+
+/// Create a copy of `func` with its EBBs, instructions and values numbered in reverse layout
+/// order.
+///
+/// Returns the copy and the mapping from the EBBs of `func` to the EBBs of the copy. The value
+/// labels of `func` are not copied.
+pub fn shuffled_copy(func: &Function) -> (Function, SecondaryMap<Ebb, PackedOption<Ebb>>) {
+    let mut copy = Function::with_name_signature(func.name.clone(), func.signature.clone());
+    copy.linkage = func.linkage;
+    copy.visibility = func.visibility;
+    copy.stack_slots = func.stack_slots.clone();
+    copy.global_values = func.global_values.clone();
+    copy.heaps = func.heaps.clone();
+    copy.tables = func.tables.clone();
+    copy.dfg.signatures = func.dfg.signatures.clone();
+    copy.dfg.ext_funcs = func.dfg.ext_funcs.clone();
+
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    let mut ebb_map: SecondaryMap<Ebb, PackedOption<Ebb>> = SecondaryMap::new();
+    let mut values: SecondaryMap<Value, PackedOption<Value>> = SecondaryMap::new();
+    for &ebb in ebbs.iter().rev() {
+        let new_ebb = copy.dfg.make_ebb();
+        ebb_map[ebb] = new_ebb.into();
+        for &param in func.dfg.ebb_params(ebb) {
+            let ty = func.dfg.value_type(param);
+            values[param] = copy.dfg.append_ebb_param(new_ebb, ty).into();
+        }
+    }
+    let map_ebb = |ebb: Ebb| ebb_map[ebb].expect("EBB not in the layout");
+
+    for data in func.jump_tables.values() {
+        let mut new_data = JumpTableData::with_capacity(data.len());
+        for &dest in data.iter() {
+            new_data.push_entry(map_ebb(dest));
+        }
+        copy.create_jump_table(new_data);
+    }
+
+    // Create the instructions in reverse order, then lay them out in the original order. Their
+    // arguments are remapped once all the results have been created.
+    let mut insts: SecondaryMap<Inst, PackedOption<Inst>> = SecondaryMap::new();
+    for &ebb in ebbs.iter().rev() {
+        for inst in func.layout.ebb_insts(ebb).rev() {
+            let mut data = func.dfg[inst].clone();
+            if let Some(list) = data.take_value_list() {
+                let args = list.as_slice(&func.dfg.value_lists);
+                data.put_value_list(ValueList::from_slice(args, &mut copy.dfg.value_lists));
+            }
+            if let Some(dest) = data.branch_destination_mut() {
+                *dest = map_ebb(*dest);
+            }
+            if let InstructionData::BranchTable {
+                ref mut destination,
+                ..
+            } = data
+            {
+                *destination = map_ebb(*destination);
+            }
+            let new_inst = copy.dfg.make_inst(data);
+            copy.dfg
+                .make_inst_results(new_inst, func.dfg.ctrl_typevar(inst));
+            for (&old, &new) in func
+                .dfg
+                .inst_results(inst)
+                .iter()
+                .zip(copy.dfg.inst_results(new_inst))
+            {
+                values[old] = new.into();
+            }
+            insts[inst] = new_inst.into();
+        }
+    }
+
+    for &ebb in &ebbs {
+        let new_ebb = map_ebb(ebb);
+        copy.layout.append_ebb(new_ebb);
+        if func.layout.is_cold(ebb) {
+            copy.layout.set_cold(new_ebb);
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            let new_inst = insts[inst].unwrap();
+            copy.layout.append_inst(new_inst, new_ebb);
+            copy.srclocs[new_inst] = func.srclocs[inst];
+            copy.branch_weights[new_inst] = func.branch_weights[inst];
+            for arg in copy.dfg.inst_args_mut(new_inst) {
+                *arg = values[func.dfg.resolve_aliases(*arg)].expect("value not defined");
+            }
+        }
+    }
+
+    (copy, ebb_map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::shuffled_copy;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::types::I32;
+    use crate::ir::{ExternalName, Function, InstBuilder, Signature, SourceLoc};
+    use crate::isa::CallConv;
+    use crate::settings;
+    use crate::verifier::verify_function;
+    use std::string::ToString;
+
+    #[test]
+    fn shuffle() {
+        let mut func = Function::with_name_signature(
+            ExternalName::testcase("f"),
+            Signature::new(CallConv::SystemV),
+        );
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb1, I32);
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            pos.set_srcloc(SourceLoc::new(3));
+            let v = pos.ins().iconst(I32, 7);
+            pos.ins().jump(ebb1, &[v]);
+            pos.insert_ebb(ebb1);
+            pos.ins().iadd(arg, arg);
+            pos.ins().return_(&[]);
+        }
+
+        let (copy, ebbs) = shuffled_copy(&func);
+        verify_function(&copy, &settings::Flags::new(settings::builder())).unwrap();
+        assert_eq!(ebbs[ebb0].unwrap().to_string(), "ebb1");
+        assert_eq!(ebbs[ebb1].unwrap().to_string(), "ebb0");
+        assert_eq!(copy.layout.entry_block(), ebbs[ebb0].expand());
+
+        let first = copy.layout.first_inst(ebbs[ebb0].unwrap()).unwrap();
+        assert_eq!(copy.srclocs[first], SourceLoc::new(3));
+        assert_eq!(
+            copy.dfg.display_inst(first, None).to_string(),
+            "v2 = iconst.i32 7"
+        );
+    }
+}
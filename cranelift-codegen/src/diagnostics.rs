@@ -0,0 +1,139 @@
+//! Non-fatal notes about the code generated for a function.
+//!
+//! Some lowering decisions produce correct but slow code, for example when an instruction is
+//! turned into a call to a runtime library function. The compiler records a `Diagnostic` for each
+//! such decision so a frontend can point its users at the performance-relevant parts of their
+//! code. See `Context::compile_with_diagnostics()`.
+
+use crate::ir::{Ebb, LibCall, Opcode, SourceLoc, Type};
+use core::fmt;
+
+/// A note about a lowering decision made for an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The source location of the instruction.
+    pub srcloc: SourceLoc,
+
+    /// What happened to the instruction.
+    pub kind: DiagnosticKind,
+}
+
+/// The kinds of diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// An instruction with no encoding was lowered to a call to a runtime library function.
+    Libcall {
+        /// The opcode of the instruction.
+        opcode: Opcode,
+        /// The called library function.
+        libcall: LibCall,
+    },
+
+    /// An instruction with no encoding was lowered to a call to a function registered in the
+    /// ISA's `LibCallRegistry`.
+    CustomLibcall {
+        /// The opcode of the instruction.
+        opcode: Opcode,
+        /// The controlling type of the instruction.
+        ty: Type,
+    },
+
+    /// A `br_table` was lowered to a chain of compares because the `jump_tables_enabled` setting
+    /// is off.
+    CompareChain,
+
+    /// A vector comparison without an encoding was split into comparisons of fewer lanes.
+    SplitVector {
+        /// The opcode of the comparison.
+        opcode: Opcode,
+        /// The controlling type of the comparison.
+        ty: Type,
+    },
+
+    /// More values are live at the same time in an EBB than there are registers to hold them, so
+    /// some of them will be spilled. The source location is the instruction where the most
+    /// values are live.
+    ///
+    /// This is only reported by `Context::compile_with_register_pressure()`.
+    RegisterPressure {
+        /// The EBB.
+        ebb: Ebb,
+        /// The name of the top-level register class.
+        class: &'static str,
+        /// The maximum number of live values in the register class.
+        live: usize,
+        /// The number of allocatable registers in the register class.
+        available: usize,
+    },
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.srcloc, self.kind)
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DiagnosticKind::Libcall { opcode, libcall } => {
+                write!(f, "{} lowered to a call to {}", opcode, libcall)
+            }
+            DiagnosticKind::CustomLibcall { opcode, ty } => write!(
+                f,
+                "{}.{} lowered to a call to a registered library function",
+                opcode, ty
+            ),
+            DiagnosticKind::CompareChain => {
+                write!(
+                    f,
+                    "jump tables disabled, br_table lowered to a compare chain"
+                )
+            }
+            DiagnosticKind::SplitVector { opcode, ty } => {
+                write!(f, "{}.{} split into narrower vector operations", opcode, ty)
+            }
+            DiagnosticKind::RegisterPressure {
+                ebb,
+                class,
+                live,
+                available,
+            } => write!(
+                f,
+                "{} values live in {} registers in {}, {} available",
+                live, class, ebb, available
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, DiagnosticKind};
+    use crate::ir::{types, LibCall, Opcode, SourceLoc};
+    use std::string::ToString;
+
+    #[test]
+    fn display() {
+        let d = Diagnostic {
+            srcloc: SourceLoc::new(0x12),
+            kind: DiagnosticKind::Libcall {
+                opcode: Opcode::Ceil,
+                libcall: LibCall::CeilF32,
+            },
+        };
+        assert_eq!(d.to_string(), "@0012: ceil lowered to a call to CeilF32");
+
+        let d = Diagnostic {
+            srcloc: SourceLoc::default(),
+            kind: DiagnosticKind::SplitVector {
+                opcode: Opcode::Icmp,
+                ty: types::I32X4,
+            },
+        };
+        assert_eq!(
+            d.to_string(),
+            "@-: icmp.i32x4 split into narrower vector operations"
+        );
+    }
+}
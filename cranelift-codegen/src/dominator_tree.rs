@@ -469,6 +469,48 @@ impl DominatorTree {
         };
     }
 
+    /// When the edge from `branch` to `to` is split by a new EBB `mid`, you can use this method to
+    /// update the dominator tree locally rather than recomputing it.
+    ///
+    /// `branch` now goes to `mid` instead of `to`, and `mid` contains only the jump `mid_jump` to
+    /// `to`. The control flow graph `cfg` must already reflect the new edges, see
+    /// `ControlFlowGraph::splice_edge`.
+    ///
+    /// The new EBB is immediately dominated by `branch`. The only other EBB whose immediate
+    /// dominator can change is `to`, when it was immediately dominated by `branch`.
+    ///
+    /// The new EBB is placed immediately after the EBB containing `branch` in the reverse
+    /// post-order. This is a valid order for dominance queries, but it can differ from the
+    /// `cfg_postorder()` of a dominator tree computed from scratch.
+    pub fn recompute_spliced_edge(
+        &mut self,
+        cfg: &ControlFlowGraph,
+        layout: &Layout,
+        branch: Inst,
+        mid: Ebb,
+        to: Ebb,
+    ) {
+        let from = layout.inst_ebb(branch).expect("branch must be inserted");
+        if !self.is_reachable(from) {
+            // The edge is unreachable, and so is the new EBB.
+            self.nodes[mid] = Default::default();
+            return;
+        }
+        let from_postorder_index = self
+            .postorder
+            .as_slice()
+            .binary_search_by(|probe| self.rpo_cmp_ebb(from, *probe))
+            .expect("the branch ebb is not declared to the dominator tree");
+        let mid_rpo = self.insert_after_rpo(from, from_postorder_index, mid);
+        self.nodes[mid] = DomNode {
+            rpo_number: mid_rpo,
+            idom: Some(branch).into(),
+        };
+        if self.nodes[to].idom.expand() == Some(branch) {
+            self.nodes[to].idom = self.compute_idom(to, cfg, layout).into();
+        }
+    }
+
     // Insert new_ebb just after ebb in the RPO. This function checks
     // if there is a gap in rpo numbers; if yes it returns the number in the gap and if
     // not it renumbers.
@@ -940,4 +982,44 @@ mod tests {
 
         assert!(errors.0.is_empty());
     }
+
+    #[test]
+    fn spliced_edge() {
+        let mut func = Function::new();
+        let entry = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let mid = func.dfg.make_ebb();
+
+        let mut cur = FuncCursor::new(&mut func);
+
+        cur.insert_ebb(entry);
+        let cond = cur.ins().iconst(I32, 0);
+        let br = cur.ins().brnz(cond, ebb1, &[]);
+        cur.ins().jump(ebb2, &[]);
+        cur.insert_ebb(ebb1);
+        cur.ins().return_(&[]);
+        cur.insert_ebb(ebb2);
+        cur.ins().return_(&[]);
+
+        let mut cfg = ControlFlowGraph::with_function(cur.func);
+        let mut dt = DominatorTree::with_function(cur.func, &cfg);
+        assert_eq!(dt.idom(ebb1), Some(br));
+
+        cur.insert_ebb(mid);
+        let mid_jump = cur.ins().jump(ebb1, &[]);
+        *cur.func.dfg[br].branch_destination_mut().unwrap() = mid;
+        cfg.splice_edge(entry, br, mid, mid_jump, ebb1);
+        dt.recompute_spliced_edge(&cfg, &cur.func.layout, br, mid, ebb1);
+
+        assert_eq!(dt.idom(mid), Some(br));
+        assert_eq!(dt.idom(ebb1), Some(mid_jump));
+        assert_eq!(dt.rpo_cmp(entry, mid, &cur.func.layout), Ordering::Less);
+        assert_eq!(dt.rpo_cmp(mid, ebb1, &cur.func.layout), Ordering::Less);
+
+        let flags = settings::Flags::new(settings::builder());
+        let mut errors = VerifierErrors::default();
+        verify_context(cur.func, &cfg, &dt, &flags, &mut errors).unwrap();
+        assert!(errors.0.is_empty());
+    }
 }
@@ -0,0 +1,133 @@
+//! EBB execution frequencies.
+//!
+//! The frequency of an EBB is a relative measure of how often it is executed. Frequencies can be
+//! supplied by the embedder as profile counts, for example from a profile-guided optimization
+//! run. When no profile is available, frequencies are estimated from the loop nesting depth of
+//! each EBB, assuming that every loop executes `LOOP_WEIGHT` iterations.
+//!
+//! The register allocator uses the frequencies to prefer spilling values that are only used in
+//! cold EBBs.
+
+use crate::entity::SecondaryMap;
+use crate::ir::{Ebb, Function};
+use crate::loop_analysis::LoopAnalysis;
+
+/// The estimated number of iterations of a loop.
+const LOOP_WEIGHT: u64 = 8;
+
+/// Relative execution frequencies of the EBBs in a function.
+pub struct EbbFrequencies {
+    freqs: SecondaryMap<Ebb, u64>,
+    profiled: bool,
+}
+
+impl EbbFrequencies {
+    /// Create a new blank frequency table.
+    pub fn new() -> Self {
+        Self {
+            freqs: SecondaryMap::new(),
+            profiled: false,
+        }
+    }
+
+    /// Clear all frequencies, including any profile counts.
+    pub fn clear(&mut self) {
+        self.freqs.clear();
+        self.profiled = false;
+    }
+
+    /// Record the profile count of `ebb`.
+    ///
+    /// Once a profile count has been recorded, `compute()` no longer estimates frequencies from
+    /// the loop nesting depth. EBBs without a profile count are assumed to never execute.
+    pub fn set_profile_count(&mut self, ebb: Ebb, count: u64) {
+        self.freqs[ebb] = count;
+        self.profiled = true;
+    }
+
+    /// Have profile counts been recorded for this function?
+    pub fn has_profile(&self) -> bool {
+        self.profiled
+    }
+
+    /// Estimate the frequencies of the EBBs in `func` from their loop nesting depth.
+    ///
+    /// This does nothing when profile counts have been recorded.
+    pub fn compute(&mut self, func: &Function, loop_analysis: &LoopAnalysis) {
+        if self.profiled {
+            return;
+        }
+        self.freqs.clear();
+        for ebb in func.layout.ebbs() {
+            self.freqs[ebb] = (0..loop_analysis.loop_depth(ebb))
+                .fold(1, |freq: u64, _| freq.saturating_mul(LOOP_WEIGHT));
+        }
+    }
+
+    /// Get the relative execution frequency of `ebb`.
+    pub fn frequency(&self, ebb: Ebb) -> u64 {
+        self.freqs[ebb]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EbbFrequencies;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::dominator_tree::DominatorTree;
+    use crate::flowgraph::ControlFlowGraph;
+    use crate::ir::{types, Function, InstBuilder};
+    use crate::loop_analysis::LoopAnalysis;
+
+    #[test]
+    fn loop_depth_estimate() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+
+        {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            cur.ins().jump(ebb1, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb2, &[]);
+
+            cur.insert_ebb(ebb2);
+            cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().jump(ebb3, &[]);
+
+            cur.insert_ebb(ebb3);
+            cur.ins().return_(&[]);
+        }
+
+        let mut cfg = ControlFlowGraph::new();
+        let mut domtree = DominatorTree::new();
+        let mut loop_analysis = LoopAnalysis::new();
+        cfg.compute(&func);
+        domtree.compute(&func, &cfg);
+        loop_analysis.compute(&func, &cfg, &domtree);
+
+        let mut freqs = EbbFrequencies::new();
+        freqs.compute(&func, &loop_analysis);
+        assert!(!freqs.has_profile());
+        assert_eq!(freqs.frequency(ebb0), 1);
+        assert_eq!(freqs.frequency(ebb1), 8);
+        assert_eq!(freqs.frequency(ebb2), 8);
+        assert_eq!(freqs.frequency(ebb3), 1);
+
+        // Profile counts take precedence over the estimate.
+        freqs.clear();
+        freqs.set_profile_count(ebb0, 1);
+        freqs.set_profile_count(ebb3, 1000);
+        freqs.compute(&func, &loop_analysis);
+        assert!(freqs.has_profile());
+        assert_eq!(freqs.frequency(ebb0), 1);
+        assert_eq!(freqs.frequency(ebb1), 0);
+        assert_eq!(freqs.frequency(ebb3), 1000);
+    }
+}
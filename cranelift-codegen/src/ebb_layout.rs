@@ -0,0 +1,302 @@
+//! Profile-guided EBB layout.
+//!
+//! When the conditional branches of a function have weights in `Function::branch_weights`, this
+//! pass reorders the EBBs so the hot paths fall through:
+//!
+//! 1. A conditional branch that is usually taken is inverted when it is followed by a `jump`, so
+//!    the hot destination becomes the `jump` destination.
+//! 2. The EBBs are laid out in chains, each EBB followed by its `jump` destination when that EBB
+//!    hasn't been placed yet. Branch relaxation then turns these jumps into fall-throughs.
+//!
+//! The entry block stays first, and the chains are started in the original layout order. Functions
+//! without branch weights are left alone.
+//!
+//! The weights can be supplied by the embedder directly, or through the `BranchProfile` trait.
+
+use crate::entity::EntitySet;
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::condcodes::CondCode;
+use crate::ir::{BranchWeight, Ebb, Function, Inst, InstructionData, Layout, Opcode, ValueList};
+use crate::isa::TargetIsa;
+use crate::timing;
+use core::mem;
+use log::debug;
+use std::vec::Vec;
+
+/// Callback trait used to find the profile weights of conditional branches.
+pub trait BranchProfile {
+    /// Get the weight of the conditional branch `inst` in `func`.
+    ///
+    /// Return `None` if the profile has no data for the branch.
+    fn branch_weight(&self, func: &Function, inst: Inst) -> Option<BranchWeight>;
+}
+
+/// Record the branch weights provided by `profile` in `func.branch_weights`.
+///
+/// Returns the number of conditional branches that were given a weight. Existing weights of
+/// branches missing from the profile are kept.
+pub fn apply_branch_profile(func: &mut Function, profile: &dyn BranchProfile) -> usize {
+    let mut count = 0;
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            let opcode = func.dfg[inst].opcode();
+            if !opcode.is_branch() || opcode.is_terminator() {
+                continue;
+            }
+            if let Some(weight) = profile.branch_weight(func, inst) {
+                func.branch_weights[inst] = Some(weight);
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Reorder the EBBs of `func` so the hot paths fall through.
+///
+/// Inverted branches are re-encoded for `isa` if they were encoded. The control flow graph is
+/// kept up to date, but the edges of the inverted branches now come from other instructions, so a
+/// dominator tree must be recomputed.
+///
+/// Returns true if some branches were inverted.
+pub fn do_ebb_layout(func: &mut Function, cfg: &mut ControlFlowGraph, isa: &dyn TargetIsa) -> bool {
+    let _tt = timing::ebb_layout();
+    debug_assert!(cfg.is_valid());
+
+    let has_weights = func.layout.ebbs().any(|ebb| {
+        func.layout
+            .ebb_insts(ebb)
+            .any(|inst| func.branch_weights[inst].is_some())
+    });
+    if !has_weights {
+        return false;
+    }
+
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    let mut inverted = false;
+    for &ebb in &ebbs {
+        if invert_likely_branch(func, ebb, isa) {
+            cfg.recompute_ebb(func, ebb);
+            inverted = true;
+        }
+    }
+
+    let mut layout = Layout::new();
+    let mut placed = EntitySet::new();
+    for &start in &ebbs {
+        let mut ebb = start;
+        while !placed.contains(ebb) {
+            placed.insert(ebb);
+            layout.append_ebb(ebb);
+            if func.layout.is_cold(ebb) {
+                layout.set_cold(ebb);
+            }
+            for inst in func.layout.ebb_insts(ebb) {
+                layout.append_inst(inst, ebb);
+            }
+            ebb = match func.layout.last_inst(ebb).map(|inst| &func.dfg[inst]) {
+                Some(&InstructionData::Jump {
+                    opcode: Opcode::Jump,
+                    destination,
+                    ..
+                }) => destination,
+                _ => break,
+            };
+        }
+    }
+    func.layout = layout;
+    inverted
+}
+
+/// Invert the conditional branch before the final `jump` of `ebb` if it is usually taken.
+///
+/// Returns true if the branch was inverted.
+fn invert_likely_branch(func: &mut Function, ebb: Ebb, isa: &dyn TargetIsa) -> bool {
+    let jump = match func.layout.last_inst(ebb) {
+        Some(inst) if func.dfg[inst].opcode() == Opcode::Jump => inst,
+        _ => return false,
+    };
+    let branch = match func.layout.prev_inst(jump) {
+        Some(inst) => inst,
+        None => return false,
+    };
+    let weight = match func.branch_weights[branch] {
+        Some(weight) if weight.is_likely() => weight,
+        _ => return false,
+    };
+    if !invert_branch(func, branch, jump) {
+        return false;
+    }
+
+    // Not every inverted condition can be encoded, for example some floating point conditions on
+    // x86. Inverting the branch again restores it.
+    if func.encodings[branch].is_legal() && func.update_encoding(branch, isa).is_err() {
+        invert_branch(func, branch, jump);
+        return false;
+    }
+
+    debug!(
+        "Inverted likely {} in {}",
+        func.dfg.display_inst(branch, isa),
+        ebb
+    );
+    func.branch_weights[branch] = Some(weight.inverse());
+    true
+}
+
+/// Invert the condition of `branch` and swap its destination and arguments with the ones of the
+/// following `jump`.
+///
+/// Returns false if `branch` isn't a conditional branch that can be inverted.
+pub(crate) fn invert_branch(func: &mut Function, branch: Inst, jump: Inst) -> bool {
+    match func.dfg[branch] {
+        InstructionData::Branch { ref mut opcode, .. } => {
+            *opcode = match *opcode {
+                Opcode::Brz => Opcode::Brnz,
+                Opcode::Brnz => Opcode::Brz,
+                _ => return false,
+            };
+        }
+        InstructionData::BranchIcmp { ref mut cond, .. }
+        | InstructionData::BranchInt { ref mut cond, .. } => *cond = cond.inverse(),
+        InstructionData::BranchFloat { ref mut cond, .. } => *cond = cond.inverse(),
+        _ => return false,
+    }
+
+    let jump_dest = func.dfg[jump]
+        .branch_destination()
+        .expect("jumps have a destination");
+    let branch_dest = mem::replace(
+        func.dfg[branch]
+            .branch_destination_mut()
+            .expect("branches have a destination"),
+        jump_dest,
+    );
+    *func.dfg[jump].branch_destination_mut().unwrap() = branch_dest;
+
+    // Swap the EBB arguments, which follow the fixed arguments of the branch.
+    let num_fixed = func.dfg[branch]
+        .opcode()
+        .constraints()
+        .num_fixed_value_arguments();
+    let mut branch_list = func.dfg[branch]
+        .take_value_list()
+        .expect("branches have a value list");
+    let mut jump_list = func.dfg[jump]
+        .take_value_list()
+        .expect("jumps have a value list");
+    let pool = &mut func.dfg.value_lists;
+    let branch_args = branch_list.as_slice(pool).to_vec();
+    let jump_args = jump_list.as_slice(pool).to_vec();
+    branch_list.clear(pool);
+    jump_list.clear(pool);
+
+    let mut new_branch_list = ValueList::from_slice(&branch_args[..num_fixed], pool);
+    new_branch_list.extend(jump_args, pool);
+    let new_jump_list = ValueList::from_slice(&branch_args[num_fixed..], pool);
+    func.dfg[branch].put_value_list(new_branch_list);
+    func.dfg[jump].put_value_list(new_jump_list);
+    true
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::{do_ebb_layout, BranchProfile};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::flowgraph::ControlFlowGraph;
+    use crate::ir::{types, BranchWeight, Ebb, ExternalName, Function, Inst, InstBuilder, Opcode};
+    use crate::test_utils;
+    use crate::Context;
+    use std::vec::Vec;
+
+    struct AlwaysTaken;
+
+    impl BranchProfile for AlwaysTaken {
+        fn branch_weight(&self, _func: &Function, _inst: Inst) -> Option<BranchWeight> {
+            Some(BranchWeight::new(100, 1))
+        }
+    }
+
+    /// Build a diamond where the conditional branch goes to `ebb1`.
+    fn diamond() -> (Function, [Ebb; 4], Inst) {
+        let sig = test_utils::signature(&[types::I32], &[types::I32]);
+        let mut func = Function::with_name_signature(ExternalName::testcase("f"), sig);
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let arg = func.dfg.append_ebb_param(ebb3, types::I32);
+        let branch;
+        {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            let one = cur.ins().iconst(types::I32, 1);
+            branch = cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().jump(ebb2, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb3, &[cond]);
+
+            cur.insert_ebb(ebb2);
+            cur.ins().jump(ebb3, &[one]);
+
+            cur.insert_ebb(ebb3);
+            cur.ins().return_(&[arg]);
+        }
+        (func, [ebb0, ebb1, ebb2, ebb3], branch)
+    }
+
+    #[test]
+    fn likely_branch() {
+        let isa = test_utils::isa("x86_64");
+        let (mut func, [ebb0, ebb1, ebb2, ebb3], branch) = diamond();
+        func.branch_weights[branch] = Some(BranchWeight::new(90, 10));
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        do_ebb_layout(&mut func, &mut cfg, &*isa);
+
+        // The branch is inverted so the likely destination follows it.
+        assert_eq!(func.dfg[branch].opcode(), Opcode::Brz);
+        assert_eq!(func.dfg[branch].branch_destination(), Some(ebb2));
+        assert_eq!(func.branch_weights[branch], Some(BranchWeight::new(10, 90)));
+        let jump = func.layout.last_inst(ebb0).unwrap();
+        assert_eq!(func.dfg[jump].branch_destination(), Some(ebb1));
+        assert_eq!(
+            func.layout.ebbs().collect::<Vec<_>>(),
+            [ebb0, ebb1, ebb3, ebb2]
+        );
+        assert!(cfg.pred_iter(ebb1).any(|pred| pred.inst == jump));
+    }
+
+    #[test]
+    fn unlikely_branch() {
+        let isa = test_utils::isa("x86_64");
+        let (mut func, [ebb0, ebb1, ebb2, ebb3], branch) = diamond();
+        func.branch_weights[branch] = Some(BranchWeight::new(1, 1000));
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        do_ebb_layout(&mut func, &mut cfg, &*isa);
+
+        // The cold EBB is moved after the hot path.
+        assert_eq!(func.dfg[branch].opcode(), Opcode::Brnz);
+        assert_eq!(
+            func.layout.ebbs().collect::<Vec<_>>(),
+            [ebb0, ebb2, ebb3, ebb1]
+        );
+    }
+
+    #[test]
+    fn compile_with_profile() {
+        let isa = test_utils::isa("x86_64");
+        let (func, [ebb0, ebb1, _, _], branch) = diamond();
+
+        let mut ctx = Context::for_function(func);
+        assert_eq!(ctx.apply_branch_profile(&AlwaysTaken), 1);
+        ctx.compile(&*isa).unwrap();
+
+        assert_eq!(ctx.func.dfg[branch].opcode(), Opcode::Brz);
+        assert_eq!(ctx.func.layout.entry_block(), Some(ebb0));
+        assert_eq!(ctx.func.layout.next_ebb(ebb0), Some(ebb1));
+    }
+}
@@ -22,13 +22,19 @@
 //!
 //! Here `Ebb1` and `Ebb2` would each have a single predecessor denoted as `(Ebb0, brz)`
 //! and `(Ebb0, jmp Ebb2)` respectively.
+//!
+//! Passes that edit the control flow can keep the graph up to date with `recompute_ebb()`,
+//! `split_ebb()`, and `splice_edge()` instead of recomputing it from scratch. Passes that change
+//! the same EBBs many times can call `begin_batch()` first, which defers the recomputation of
+//! changed EBBs until `flush()` or `end_batch()` is called.
 
 use crate::bforest;
-use crate::entity::SecondaryMap;
+use crate::entity::{EntitySet, SecondaryMap};
 use crate::ir::instructions::BranchInfo;
 use crate::ir::{Ebb, Function, Inst};
 use crate::timing;
 use core::mem;
+use std::vec::Vec;
 
 /// A basic block denoted by its enclosing Ebb and last instruction.
 #[derive(PartialEq, Eq)]
@@ -76,6 +82,12 @@ pub struct ControlFlowGraph {
     pred_forest: bforest::MapForest<Inst, Ebb>,
     succ_forest: bforest::SetForest<Ebb>,
     valid: bool,
+
+    /// Are updates being batched?
+    batching: bool,
+    /// EBBs whose outgoing edges must be recomputed by `flush()`, and the same EBBs as a set.
+    dirty: Vec<Ebb>,
+    dirty_set: EntitySet<Ebb>,
 }
 
 impl ControlFlowGraph {
@@ -86,6 +98,9 @@ impl ControlFlowGraph {
             valid: false,
             pred_forest: bforest::MapForest::new(),
             succ_forest: bforest::SetForest::new(),
+            batching: false,
+            dirty: Vec::new(),
+            dirty_set: EntitySet::new(),
         }
     }
 
@@ -95,6 +110,9 @@ impl ControlFlowGraph {
         self.pred_forest.clear();
         self.succ_forest.clear();
         self.valid = false;
+        self.batching = false;
+        self.dirty.clear();
+        self.dirty_set.clear();
     }
 
     /// Allocate and compute the control flow graph for `func`.
@@ -157,10 +175,87 @@ impl ControlFlowGraph {
     /// from `ebb` while leaving edges to `ebb` intact. Its functionality a subset of that of the
     /// more expensive `compute`, and should be used when we know we don't need to recompute the CFG
     /// from scratch, but rather that our changes have been restricted to specific EBBs.
+    ///
+    /// When updates are being batched, this only marks `ebb` as changed.
     pub fn recompute_ebb(&mut self, func: &Function, ebb: Ebb) {
         debug_assert!(self.is_valid());
-        self.invalidate_ebb_successors(ebb);
-        self.compute_ebb(func, ebb);
+        if self.batching {
+            if self.dirty_set.insert(ebb) {
+                self.dirty.push(ebb);
+            }
+        } else {
+            self.invalidate_ebb_successors(ebb);
+            self.compute_ebb(func, ebb);
+        }
+    }
+
+    /// Update the control flow graph after `Layout::split_ebb()`.
+    ///
+    /// The instructions after the split point have been moved from `old_ebb` to `new_ebb`, and
+    /// `old_ebb` has been terminated by a jump to `new_ebb`. The edges out of both EBBs are
+    /// recomputed, and edges to `old_ebb` are left intact.
+    pub fn split_ebb(&mut self, func: &Function, old_ebb: Ebb, new_ebb: Ebb) {
+        self.recompute_ebb(func, old_ebb);
+        self.recompute_ebb(func, new_ebb);
+    }
+
+    /// Update the control flow graph after the edge from `branch` to `to` was split by a new
+    /// EBB.
+    ///
+    /// `branch` is a branch in `from` that used to go to `to`, and now goes to `mid` instead.
+    /// `mid` contains no other branches than its terminator `mid_jump`, which jumps to `to`. This
+    /// takes time logarithmic in the number of edges of the three EBBs, and it doesn't need to
+    /// look at their instructions.
+    pub fn splice_edge(&mut self, from: Ebb, branch: Inst, mid: Ebb, mid_jump: Inst, to: Ebb) {
+        debug_assert!(self.is_valid());
+        self.data[to]
+            .predecessors
+            .remove(branch, &mut self.pred_forest, &());
+        let still_succ = self.data[to]
+            .predecessors
+            .iter(&self.pred_forest)
+            .any(|(_, pred)| pred == from);
+        if !still_succ {
+            self.data[from]
+                .successors
+                .remove(to, &mut self.succ_forest, &());
+        }
+        self.add_edge(from, branch, mid);
+        self.add_edge(mid, mid_jump, to);
+    }
+
+    /// Start batching updates.
+    ///
+    /// Until the batch ends, `recompute_ebb()` and `split_ebb()` only record which EBBs have
+    /// changed. The predecessors and successors of EBBs must not be queried while there are
+    /// pending updates; call `flush()` first.
+    pub fn begin_batch(&mut self) {
+        debug_assert!(self.is_valid());
+        self.batching = true;
+    }
+
+    /// Recompute the edges out of all the EBBs that were changed since the batch began, or since
+    /// the last flush.
+    pub fn flush(&mut self, func: &Function) {
+        let mut dirty = mem::replace(&mut self.dirty, Vec::new());
+        for &ebb in &dirty {
+            self.invalidate_ebb_successors(ebb);
+            self.compute_ebb(func, ebb);
+        }
+        dirty.clear();
+        self.dirty = dirty;
+        self.dirty_set.clear();
+    }
+
+    /// Recompute all changed EBBs, and stop batching updates.
+    pub fn end_batch(&mut self, func: &Function) {
+        self.flush(func);
+        self.batching = false;
+    }
+
+    /// Are there changed EBBs that haven't been recomputed yet?
+    pub fn has_pending_updates(&self) -> bool {
+        !self.dirty.is_empty()
     }
 
     fn add_edge(&mut self, from: Ebb, from_inst: Inst, to: Ebb) {
@@ -174,12 +269,14 @@ impl ControlFlowGraph {
 
     /// Get an iterator over the CFG predecessors to `ebb`.
     pub fn pred_iter(&self, ebb: Ebb) -> PredIter {
+        debug_assert!(!self.has_pending_updates());
         PredIter(self.data[ebb].predecessors.iter(&self.pred_forest))
     }
 
     /// Get an iterator over the CFG successors to `ebb`.
     pub fn succ_iter(&self, ebb: Ebb) -> SuccIter {
         debug_assert!(self.is_valid());
+        debug_assert!(!self.has_pending_updates());
         self.data[ebb].successors.iter(&self.succ_forest)
     }
 
@@ -347,4 +444,90 @@ mod tests {
             assert_eq!(ebb2_successors.collect::<Vec<_>>(), []);
         }
     }
+
+    #[test]
+    fn batch() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+
+        let br_ebb0;
+        let jmp_ebb0;
+        {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            br_ebb0 = cur.ins().brnz(cond, ebb2, &[]);
+            jmp_ebb0 = cur.ins().jump(ebb1, &[]);
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb2, &[]);
+            cur.insert_ebb(ebb2);
+            cur.ins().return_(&[]);
+        }
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        cfg.begin_batch();
+
+        // Recomputing the same EBB twice only records it once.
+        func.dfg.replace(br_ebb0).brnz(cond, ebb1, &[]);
+        cfg.recompute_ebb(&func, ebb0);
+        func.dfg.replace(jmp_ebb0).jump(ebb2, &[]);
+        cfg.recompute_ebb(&func, ebb0);
+        assert!(cfg.has_pending_updates());
+        assert_eq!(cfg.dirty, [ebb0]);
+
+        cfg.end_batch(&func);
+        assert!(!cfg.has_pending_updates());
+        assert_eq!(cfg.succ_iter(ebb0).collect::<Vec<_>>(), [ebb1, ebb2]);
+        assert_eq!(cfg.pred_iter(ebb1).count(), 1);
+        assert_eq!(cfg.pred_iter(ebb2).count(), 2);
+        assert!(cfg
+            .pred_iter(ebb1)
+            .any(|pred| pred == BasicBlock::new(ebb0, br_ebb0)));
+
+        // Updates are no longer deferred.
+        func.dfg.replace(jmp_ebb0).jump(ebb1, &[]);
+        cfg.recompute_ebb(&func, ebb0);
+        assert!(!cfg.has_pending_updates());
+        assert_eq!(cfg.succ_iter(ebb0).collect::<Vec<_>>(), [ebb1]);
+    }
+
+    #[test]
+    fn splice_edge() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+
+        let br_ebb0;
+        {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            br_ebb0 = cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().jump(ebb1, &[]);
+            cur.insert_ebb(ebb1);
+            cur.ins().return_(&[]);
+        }
+        let mut cfg = ControlFlowGraph::with_function(&func);
+
+        // Split the edge from `br_ebb0` to `ebb1`.
+        let jmp_ebb2 = {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb2);
+            cur.ins().jump(ebb1, &[])
+        };
+        *func.dfg[br_ebb0].branch_destination_mut().unwrap() = ebb2;
+        cfg.splice_edge(ebb0, br_ebb0, ebb2, jmp_ebb2, ebb1);
+
+        // `ebb0` still jumps to `ebb1`, so it remains a successor.
+        assert_eq!(cfg.succ_iter(ebb0).collect::<Vec<_>>(), [ebb1, ebb2]);
+        assert_eq!(cfg.succ_iter(ebb2).collect::<Vec<_>>(), [ebb1]);
+        assert!(cfg
+            .pred_iter(ebb2)
+            .eq(Some(BasicBlock::new(ebb0, br_ebb0)).into_iter()));
+        assert_eq!(cfg.pred_iter(ebb1).count(), 2);
+        assert!(cfg.pred_iter(ebb1).all(|pred| pred.inst != br_ebb0));
+    }
 }
@@ -0,0 +1,143 @@
+//! Heap bounds check elimination.
+//!
+//! The legalizer expands every `heap_addr` instruction into a bounds check followed by the
+//! address computation, unless the instruction has the `notrap` flag. This pass sets the `notrap`
+//! flag on the `heap_addr` instructions whose index is proven to be in bounds:
+//!
+//! - When a dominating `heap_addr` for the same heap and index has already checked an access at
+//!   least as large. Heaps never shrink, so the earlier check still holds. This only applies to
+//!   heaps with the `trap` violation policy: the other policies let execution continue past an
+//!   out-of-bounds access, so the earlier check proves nothing.
+//! - When the largest value the index can take, computed from constants, `band_imm` masks,
+//!   `uextend` and `ushr_imm`, is within the bound of a static heap or the minimum size of a
+//!   dynamic heap.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::ir::dfg::ValueDef;
+use crate::ir::{
+    DataFlowGraph, Function, Heap, HeapStyle, HeapViolation, Inst, InstructionData, Opcode, Value,
+};
+use crate::timing;
+use crate::HashMap;
+use log::debug;
+use std::vec::Vec;
+
+/// Number of instructions to look through when computing the range of a heap index.
+const MAX_DEPTH: u32 = 4;
+
+/// Remove the redundant bounds checks of the `heap_addr` instructions in `func`.
+pub fn do_heap_bounds(func: &mut Function, domtree: &DominatorTree) {
+    let _tt = timing::heap_bounds();
+    debug_assert!(domtree.is_valid());
+
+    // The checks seen so far for each heap and index, with their access size. The EBBs are
+    // visited in reverse post-order, so dominating checks are seen first.
+    let mut checks: HashMap<(Heap, Value), Vec<(Inst, u32)>> = HashMap::new();
+    let mut pos = FuncCursor::new(func);
+    for &ebb in domtree.cfg_postorder().iter().rev() {
+        pos.goto_top(ebb);
+        while let Some(inst) = pos.next_inst() {
+            let (notrap, heap, index, size) = match pos.func.dfg[inst] {
+                InstructionData::HeapAddr {
+                    flags,
+                    heap,
+                    arg,
+                    imm,
+                    ..
+                } => (
+                    flags.notrap(),
+                    heap,
+                    pos.func.dfg.resolve_aliases(arg),
+                    imm.into(),
+                ),
+                _ => continue,
+            };
+
+            let checked = if pos.func.heaps[heap].violation == HeapViolation::Trap {
+                let seen = checks.entry((heap, index)).or_default();
+                let checked = seen.iter().any(|&(check, checked_size)| {
+                    checked_size >= size && domtree.dominates(check, inst, &pos.func.layout)
+                });
+                seen.push((inst, size));
+                checked
+            } else {
+                false
+            };
+
+            if notrap || !(checked || in_bounds(pos.func, heap, index, size)) {
+                continue;
+            }
+            debug!(
+                "Removing the bounds check of {}",
+                pos.func.dfg.display_inst(inst, None)
+            );
+            if let InstructionData::HeapAddr { ref mut flags, .. } = pos.func.dfg[inst] {
+                flags.set_notrap();
+            }
+        }
+    }
+}
+
+/// Is an access of `size` bytes at every possible value of `index` within the bounds of `heap`?
+fn in_bounds(func: &Function, heap: Heap, index: Value, size: u32) -> bool {
+    let bound: u64 = match func.heaps[heap].style {
+        HeapStyle::Static { bound } => bound.into(),
+        HeapStyle::Dynamic { .. } => func.heaps[heap].min_size.into(),
+    };
+    match max_value(&func.dfg, index, MAX_DEPTH).checked_add(u64::from(size)) {
+        Some(end) => end <= bound,
+        None => false,
+    }
+}
+
+/// Get an upper bound of the unsigned integer `value`, looking through at most `depth`
+/// instructions.
+fn max_value(dfg: &DataFlowGraph, value: Value, depth: u32) -> u64 {
+    let value = dfg.resolve_aliases(value);
+    let bits = dfg.value_type(value).bits();
+    let type_max = if bits >= 64 {
+        u64::max_value()
+    } else {
+        (1 << bits) - 1
+    };
+
+    let inst = match dfg.value_def(value) {
+        ValueDef::Result(inst, _) if depth > 0 => inst,
+        _ => return type_max,
+    };
+    match dfg[inst] {
+        InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } => {
+            let imm: i64 = imm.into();
+            imm as u64 & type_max
+        }
+        InstructionData::Unary {
+            opcode: Opcode::Uextend,
+            arg,
+        } => max_value(dfg, arg, depth - 1),
+        InstructionData::BinaryImm {
+            opcode: Opcode::BandImm,
+            arg,
+            imm,
+        } => {
+            let imm: i64 = imm.into();
+            (imm as u64 & type_max).min(max_value(dfg, arg, depth - 1))
+        }
+        InstructionData::BinaryImm {
+            opcode: Opcode::UshrImm,
+            arg,
+            imm,
+        } => {
+            let amount: i64 = imm.into();
+            max_value(dfg, arg, depth - 1) >> (amount as u32 & (bits as u32 - 1))
+        }
+        InstructionData::Binary {
+            opcode: Opcode::Band,
+            args,
+        } => max_value(dfg, args[0], depth - 1).min(max_value(dfg, args[1], depth - 1)),
+        _ => type_max,
+    }
+}
@@ -0,0 +1,535 @@
+//! Function inlining.
+//!
+//! The inliner replaces direct `call` instructions with a copy of the callee's body. The callee
+//! EBBs are spliced into the caller between the call site and the instructions following it:
+//!
+//! - The caller EBB containing the call is split after the call, and the call results become the
+//!   parameters of the new EBB.
+//! - The call itself is replaced by a `jump` to the copy of the callee's entry EBB, passing the
+//!   call arguments as EBB arguments.
+//! - Every `return` in the callee becomes a `jump` to the new EBB.
+//!
+//! All the entities referenced by the callee (stack slots, global values, heaps, tables, jump
+//! tables, signatures and external functions) are imported into the caller and renumbered.
+//!
+//! Cranelift doesn't know anything about the functions a call refers to, so the embedder provides
+//! callee bodies through the `CalleeLookup` trait. The inliner only operates on IR that hasn't
+//! been legalized yet, and it only inlines a single level: calls in the inlined bodies are left
+//! alone.
+
+use crate::entity::{EntityRef, SecondaryMap};
+use crate::ir::instructions::InstructionData;
+use crate::ir::{
+    AbiParam, Ebb, FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapStyle, Inst,
+    InstBuilder, JumpTable, JumpTableData, Opcode, SigRef, StackSlot, StackSlotKind, Table, Value,
+    ValueList,
+};
+use crate::packed_option::PackedOption;
+use crate::timing;
+use std::vec::Vec;
+
+/// Callback trait used by the inliner to find the IR of called functions.
+pub trait CalleeLookup {
+    /// Get the body of the function referenced by `func_ref` in `caller`.
+    ///
+    /// Return `None` if the callee is unknown or must not be inlined.
+    fn lookup_callee(&self, caller: &Function, func_ref: FuncRef) -> Option<&Function>;
+}
+
+/// Inline direct calls in `func` to the functions provided by `callees`.
+///
+/// The cost of inlining a callee is the number of instructions in its body. Calls are inlined in
+/// layout order for as long as their cost fits in the remaining `budget`.
+///
+/// Returns the number of call sites that were inlined. The control flow graph and dominator tree
+/// of `func` must be recomputed when this is not zero.
+pub fn do_inlining(func: &mut Function, callees: &dyn CalleeLookup, budget: usize) -> usize {
+    let _tt = timing::inline();
+    let mut remaining = budget;
+    let mut inlined = 0;
+
+    // Collect the call sites up front so calls in inlined code aren't considered.
+    let mut calls = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let InstructionData::Call { func_ref, .. } = func.dfg[inst] {
+                calls.push((inst, func_ref));
+            }
+        }
+    }
+
+    for (call, func_ref) in calls {
+        let callee = match callees.lookup_callee(func, func_ref) {
+            Some(callee) => callee,
+            None => continue,
+        };
+        let cost = callee
+            .layout
+            .ebbs()
+            .map(|ebb| callee.layout.ebb_insts(ebb).count())
+            .sum::<usize>();
+        if cost > remaining || !can_inline(func, func_ref, callee) {
+            continue;
+        }
+        inline_call(func, call, callee);
+        remaining -= cost;
+        inlined += 1;
+    }
+
+    inlined
+}
+
+/// Check that `callee` is a body we know how to inline at a call to `func_ref`.
+fn can_inline(func: &Function, func_ref: FuncRef, callee: &Function) -> bool {
+    if callee.layout.entry_block().is_none() {
+        return false;
+    }
+
+    // The call signature must match the callee's, otherwise the arguments can't be mapped.
+    let sig = &func.dfg.signatures[func.dfg.ext_funcs[func_ref].signature];
+    let same_types = |a: &[AbiParam], b: &[AbiParam]| {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.value_type == b.value_type)
+    };
+    if !same_types(&sig.params, &callee.signature.params)
+        || !same_types(&sig.returns, &callee.signature.returns)
+    {
+        return false;
+    }
+
+    // Stack slots other than explicit slots are created by ABI lowering and register allocation.
+    if callee
+        .stack_slots
+        .values()
+        .any(|ss| ss.kind != StackSlotKind::ExplicitSlot)
+    {
+        return false;
+    }
+
+    // The callee's VM context is its own `vmctx` parameter which we can't refer to from a global
+    // value in the caller.
+    if callee.global_values.values().any(|gv| match *gv {
+        GlobalValueData::VMContext => true,
+        _ => false,
+    }) {
+        return false;
+    }
+
+    true
+}
+
+/// Mapping of callee entities to their copies in the caller.
+struct EntityMap {
+    ebbs: SecondaryMap<Ebb, PackedOption<Ebb>>,
+    values: SecondaryMap<Value, PackedOption<Value>>,
+    stack_slots: Vec<StackSlot>,
+    global_values: Vec<GlobalValue>,
+    heaps: Vec<Heap>,
+    tables: Vec<Table>,
+    jump_tables: Vec<JumpTable>,
+    sigrefs: Vec<SigRef>,
+    func_refs: Vec<FuncRef>,
+}
+
+impl EntityMap {
+    fn ebb(&self, ebb: Ebb) -> Ebb {
+        self.ebbs[ebb].expect("EBB not in callee layout")
+    }
+
+    fn value(&self, callee: &Function, value: Value) -> Value {
+        self.values[callee.dfg.resolve_aliases(value)].expect("value not defined in callee")
+    }
+}
+
+/// Replace `call` in `func` with a copy of `callee`.
+fn inline_call(func: &mut Function, call: Inst, callee: &Function) {
+    let srcloc = func.srclocs[call];
+    let mut map = import_entities(func, callee);
+
+    // Split the caller EBB after the call and turn the call results into parameters of the new
+    // EBB. Returns from the callee will jump there.
+    let return_ebb = func.dfg.make_ebb();
+    let next = func
+        .layout
+        .next_inst(call)
+        .expect("call can't terminate an EBB");
+    func.layout.split_ebb(return_ebb, next);
+    let results = func.dfg.detach_results(call);
+    for i in 0..results.len(&func.dfg.value_lists) {
+        let result = results.get(i, &func.dfg.value_lists).unwrap();
+        func.dfg.attach_ebb_param(return_ebb, result);
+    }
+
+    // Create copies of the callee EBBs and their parameters, in layout order.
+    for ebb in callee.layout.ebbs() {
+        let new_ebb = func.dfg.make_ebb();
+        func.layout.insert_ebb(new_ebb, return_ebb);
+        if callee.layout.is_cold(ebb) {
+            func.layout.set_cold(new_ebb);
+        }
+        map.ebbs[ebb] = new_ebb.into();
+        for &param in callee.dfg.ebb_params(ebb) {
+            let ty = callee.dfg.value_type(param);
+            map.values[param] = func.dfg.append_ebb_param(new_ebb, ty).into();
+        }
+    }
+    for (jt, data) in callee.jump_tables.iter() {
+        let new_jt = map.jump_tables[jt.index()];
+        for &dest in data.iter() {
+            func.jump_tables[new_jt].push_entry(map.ebb(dest));
+        }
+    }
+
+    // Copy the instructions. Arguments are remapped in a second pass since the layout order of
+    // the callee doesn't guarantee that definitions are seen before uses.
+    let mut new_insts = Vec::new();
+    for ebb in callee.layout.ebbs() {
+        let new_ebb = map.ebb(ebb);
+        for inst in callee.layout.ebb_insts(ebb) {
+            let new_inst = copy_inst(func, callee, inst, &map, return_ebb);
+            func.layout.append_inst(new_inst, new_ebb);
+            func.srclocs[new_inst] = srcloc;
+            func.branch_weights[new_inst] = callee.branch_weights[inst];
+            for (&old, &new) in callee
+                .dfg
+                .inst_results(inst)
+                .iter()
+                .zip(func.dfg.inst_results(new_inst))
+            {
+                map.values[old] = new.into();
+            }
+            new_insts.push(new_inst);
+        }
+    }
+    for new_inst in new_insts {
+        for arg in func.dfg.inst_args_mut(new_inst) {
+            *arg = map.value(callee, *arg);
+        }
+    }
+
+    // Finally, replace the call with a jump into the inlined entry EBB.
+    let entry = map.ebb(callee.layout.entry_block().unwrap());
+    let args = func.dfg.inst_args(call).to_vec();
+    func.dfg.replace(call).jump(entry, &args);
+}
+
+/// Import all the entities referenced by `callee` into `func`.
+fn import_entities(func: &mut Function, callee: &Function) -> EntityMap {
+    let stack_slots = callee
+        .stack_slots
+        .values()
+        .map(|ss| func.create_stack_slot(ss.clone()))
+        .collect();
+
+    // Global values can refer to each other, so create them all before fixing up the bases.
+    let global_values: Vec<GlobalValue> = callee
+        .global_values
+        .values()
+        .map(|gv| func.create_global_value(gv.clone()))
+        .collect();
+    for &gv in &global_values {
+        match func.global_values[gv] {
+            GlobalValueData::Load { ref mut base, .. }
+            | GlobalValueData::IAddImm { ref mut base, .. } => {
+                *base = global_values[base.index()];
+            }
+            _ => {}
+        }
+    }
+
+    let heaps = callee
+        .heaps
+        .values()
+        .map(|heap| {
+            let mut data = heap.clone();
+            data.base = global_values[data.base.index()];
+            if let HeapStyle::Dynamic { ref mut bound_gv } = data.style {
+                *bound_gv = global_values[bound_gv.index()];
+            }
+            func.create_heap(data)
+        })
+        .collect();
+
+    let tables = callee
+        .tables
+        .values()
+        .map(|table| {
+            let mut data = table.clone();
+            data.base_gv = global_values[data.base_gv.index()];
+            data.bound_gv = global_values[data.bound_gv.index()];
+            func.create_table(data)
+        })
+        .collect();
+
+    // Jump tables are filled in once the EBBs have been created.
+    let jump_tables = callee
+        .jump_tables
+        .values()
+        .map(|_| func.create_jump_table(JumpTableData::new()))
+        .collect();
+
+    let sigrefs: Vec<SigRef> = callee
+        .dfg
+        .signatures
+        .values()
+        .map(|sig| func.import_signature(sig.clone()))
+        .collect();
+
+    let func_refs = callee
+        .dfg
+        .ext_funcs
+        .values()
+        .map(|ext| {
+            let mut data = ext.clone();
+            data.signature = sigrefs[data.signature.index()];
+            func.import_function(data)
+        })
+        .collect();
+
+    EntityMap {
+        ebbs: SecondaryMap::new(),
+        values: SecondaryMap::new(),
+        stack_slots,
+        global_values,
+        heaps,
+        tables,
+        jump_tables,
+        sigrefs,
+        func_refs,
+    }
+}
+
+/// Create a copy of the callee instruction `inst` in `func`, along with its results.
+///
+/// Entity references and branch destinations are remapped, but the value arguments still refer to
+/// callee values. Returns, including fallthrough returns, are replaced with jumps to `return_ebb`,
+/// after the remapping.
+fn copy_inst(
+    func: &mut Function,
+    callee: &Function,
+    inst: Inst,
+    map: &EntityMap,
+    return_ebb: Ebb,
+) -> Inst {
+    let mut data = callee.dfg[inst].clone();
+    if let Some(dest) = data.branch_destination_mut() {
+        *dest = map.ebb(*dest);
+    }
+
+    // Move any variable arguments into the caller's value list pool.
+    if let Some(list) = data.take_value_list() {
+        let args = list.as_slice(&callee.dfg.value_lists);
+        let args = ValueList::from_slice(args, &mut func.dfg.value_lists);
+        if data.opcode().is_return() {
+            data = InstructionData::Jump {
+                opcode: Opcode::Jump,
+                destination: return_ebb,
+                args,
+            };
+        } else {
+            data.put_value_list(args);
+        }
+    }
+
+    match data {
+        InstructionData::Call {
+            ref mut func_ref, ..
+        }
+        | InstructionData::FuncAddr {
+            ref mut func_ref, ..
+        } => *func_ref = map.func_refs[func_ref.index()],
+        InstructionData::CallIndirect {
+            ref mut sig_ref, ..
+        } => *sig_ref = map.sigrefs[sig_ref.index()],
+        InstructionData::StackLoad {
+            ref mut stack_slot, ..
+        }
+        | InstructionData::StackStore {
+            ref mut stack_slot, ..
+        } => *stack_slot = map.stack_slots[stack_slot.index()],
+        InstructionData::UnaryGlobalValue {
+            ref mut global_value,
+            ..
+        } => *global_value = map.global_values[global_value.index()],
+        InstructionData::HeapAddr { ref mut heap, .. } => *heap = map.heaps[heap.index()],
+        InstructionData::TableAddr { ref mut table, .. }
+        | InstructionData::TableGrow { ref mut table, .. }
+        | InstructionData::TableFill { ref mut table, .. } => *table = map.tables[table.index()],
+        InstructionData::BranchTable {
+            ref mut destination,
+            ref mut table,
+            ..
+        } => {
+            *destination = map.ebb(*destination);
+            *table = map.jump_tables[table.index()];
+        }
+        InstructionData::BranchTableEntry { ref mut table, .. }
+        | InstructionData::BranchTableBase { ref mut table, .. }
+        | InstructionData::IndirectJump { ref mut table, .. } => {
+            *table = map.jump_tables[table.index()]
+        }
+        _ => {}
+    }
+
+    let ctrl_typevar = callee.dfg.ctrl_typevar(inst);
+    let new_inst = func.dfg.make_inst(data);
+    func.dfg.make_inst_results(new_inst, ctrl_typevar);
+    new_inst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::types::I32;
+    use crate::ir::{ExtFuncData, ExternalName, Linkage, Signature, Visibility};
+    use crate::isa::CallConv;
+    use crate::settings;
+    use crate::verifier::verify_function;
+
+    struct Callees(Vec<Function>);
+
+    impl CalleeLookup for Callees {
+        fn lookup_callee(&self, caller: &Function, func_ref: FuncRef) -> Option<&Function> {
+            let name = &caller.dfg.ext_funcs[func_ref].name;
+            self.0.iter().find(|f| f.name == *name)
+        }
+    }
+
+    fn binary_sig() -> Signature {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        sig.params.push(AbiParam::new(I32));
+        sig.returns.push(AbiParam::new(I32));
+        sig
+    }
+
+    // A callee with two returns: `if a { a + b } else { b }`.
+    fn callee() -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::testcase("callee"), binary_sig());
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let a = func.dfg.append_ebb_param(ebb0, I32);
+        let b = func.dfg.append_ebb_param(ebb0, I32);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().brz(a, ebb1, &[]);
+        let sum = pos.ins().iadd(a, b);
+        pos.ins().return_(&[sum]);
+        pos.insert_ebb(ebb1);
+        pos.ins().return_(&[b]);
+        func
+    }
+
+    // A callee with a branch table and a fallthrough return: `match a { 0 => a + b, _ => b }`.
+    fn table_callee() -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::testcase("callee"), binary_sig());
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let a = func.dfg.append_ebb_param(ebb0, I32);
+        let b = func.dfg.append_ebb_param(ebb0, I32);
+        let mut jt_data = JumpTableData::new();
+        jt_data.push_entry(ebb1);
+        let jt = func.create_jump_table(jt_data);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().br_table(a, ebb2, jt);
+        pos.insert_ebb(ebb1);
+        let sum = pos.ins().iadd(a, b);
+        pos.ins().return_(&[sum]);
+        pos.insert_ebb(ebb2);
+        pos.ins().fallthrough_return(&[b]);
+        func
+    }
+
+    fn caller() -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::testcase("caller"), binary_sig());
+        let sig = func.import_signature(binary_sig());
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature: sig,
+            colocated: true,
+            linkage: Linkage::Import,
+            visibility: Visibility::Default,
+        });
+        let ebb0 = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb0, I32);
+        let y = func.dfg.append_ebb_param(ebb0, I32);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let call = pos.ins().call(callee, &[x, y]);
+        let r = pos.func.dfg.first_result(call);
+        let r2 = pos.ins().imul(r, x);
+        pos.ins().return_(&[r2]);
+        func
+    }
+
+    #[test]
+    fn inline_call() {
+        let callees = Callees(vec![callee()]);
+        let flags = settings::Flags::new(settings::builder());
+
+        // The budget is too small.
+        let mut func = caller();
+        assert_eq!(do_inlining(&mut func, &callees, 3), 0);
+
+        assert_eq!(do_inlining(&mut func, &callees, 4), 1);
+        verify_function(&func, &flags).unwrap();
+        assert_eq!(func.layout.ebbs().count(), 4);
+        let has_call = func
+            .layout
+            .ebbs()
+            .flat_map(|ebb| func.layout.ebb_insts(ebb))
+            .any(|inst| func.dfg[inst].opcode().is_call());
+        assert!(!has_call);
+    }
+
+    #[test]
+    fn inline_branch_table() {
+        let callees = Callees(vec![table_callee()]);
+        let flags = settings::Flags::new(settings::builder());
+
+        let mut func = caller();
+        assert_eq!(do_inlining(&mut func, &callees, 4), 1);
+        verify_function(&func, &flags).unwrap();
+
+        // The three callee EBBs are inserted between the two halves of the caller's EBB.
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        assert_eq!(ebbs.len(), 5);
+        let insts: Vec<Inst> = ebbs
+            .iter()
+            .flat_map(|&ebb| func.layout.ebb_insts(ebb))
+            .collect();
+
+        // The branch table and its default destination refer to the inlined EBBs.
+        let (destination, table) = insts
+            .iter()
+            .find_map(|&inst| match func.dfg[inst] {
+                InstructionData::BranchTable {
+                    destination, table, ..
+                } => Some((destination, table)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(destination, ebbs[3]);
+        assert_eq!(func.jump_tables[table].as_slice(), &[ebbs[2]]);
+
+        // Both callee returns jump to the caller's continuation.
+        let returns = insts
+            .iter()
+            .filter(|&&inst| func.dfg[inst].opcode().is_return())
+            .count();
+        assert_eq!(returns, 1);
+        let jumps_back = insts
+            .iter()
+            .filter(|&&inst| func.dfg[inst].branch_destination() == Some(ebbs[4]))
+            .count();
+        assert_eq!(jumps_back, 2);
+    }
+}
@@ -0,0 +1,43 @@
+//! Branch weights.
+//!
+//! The embedder can attach profile weights to the conditional branches of a function, telling
+//! Cranelift how often each branch was taken. The weights are used to lay out the EBBs so the hot
+//! path falls through. Like source locations, they are not interpreted in any other way, and they
+//! are not included in the textual IR format.
+
+use core::fmt;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// The relative number of times a conditional branch was taken and not taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct BranchWeight {
+    /// Weight of the edge to the branch destination.
+    pub taken: u64,
+    /// Weight of the edge to the instruction following the branch.
+    pub not_taken: u64,
+}
+
+impl BranchWeight {
+    /// Create a new branch weight.
+    pub fn new(taken: u64, not_taken: u64) -> Self {
+        Self { taken, not_taken }
+    }
+
+    /// Get the weight of the branch with the opposite condition.
+    pub fn inverse(self) -> Self {
+        Self::new(self.not_taken, self.taken)
+    }
+
+    /// Is the branch taken more often than not?
+    pub fn is_likely(self) -> bool {
+        self.taken > self.not_taken
+    }
+}
+
+impl fmt::Display for BranchWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.taken, self.not_taken)
+    }
+}
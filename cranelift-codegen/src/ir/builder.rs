@@ -4,6 +4,8 @@
 //! function. Many of its methods are generated from the meta language instruction definitions.
 
 use crate::ir;
+#[cfg(feature = "builder_typecheck")]
+use crate::ir::instructions::ResolvedConstraint;
 use crate::ir::types;
 use crate::ir::{DataFlowGraph, InstructionData};
 use crate::ir::{Inst, Opcode, Type, Value};
@@ -128,6 +130,7 @@ impl<'f, IIB: InstInserterBase<'f>> InstBuilderBase<'f> for InsertBuilder<'f, II
             let dfg = self.inserter.data_flow_graph_mut();
             inst = dfg.make_inst(data);
             dfg.make_inst_results(inst, ctrl_typevar);
+            typecheck(dfg, inst, ctrl_typevar);
         }
         (inst, self.inserter.insert_built_inst(inst, ctrl_typevar))
     }
@@ -165,6 +168,7 @@ where
             // Make an `Iterator<Item = Option<Value>>`.
             let ru = self.reuse.as_ref().iter().cloned();
             dfg.make_inst_results_reusing(inst, ctrl_typevar, ru);
+            typecheck(dfg, inst, ctrl_typevar);
         }
         (inst, self.inserter.insert_built_inst(inst, ctrl_typevar))
     }
@@ -208,11 +212,60 @@ impl<'f> InstBuilderBase<'f> for ReplaceBuilder<'f> {
             // Construct new ones.
             self.dfg.make_inst_results(self.inst, ctrl_typevar);
         }
+        typecheck(self.dfg, self.inst, ctrl_typevar);
 
         (self.inst, self.dfg)
     }
 }
 
+/// Check the controlling type and the fixed value arguments of the newly built `inst` against the
+/// type constraints of its opcode.
+///
+/// This is enabled by the `builder_typecheck` feature, and panics on the first type error. Run with
+/// `RUST_BACKTRACE=1` to find the builder call that created the instruction.
+#[cfg(feature = "builder_typecheck")]
+fn typecheck(dfg: &DataFlowGraph, inst: Inst, ctrl_typevar: Type) {
+    let constraints = dfg[inst].opcode().constraints();
+    if let Some(typeset) = constraints.ctrl_typeset() {
+        assert!(
+            typeset.contains(ctrl_typevar),
+            "{} has an invalid controlling type {}",
+            dfg.display_inst(inst, None),
+            ctrl_typevar
+        );
+    }
+
+    for (i, &arg) in dfg.inst_fixed_args(inst).iter().enumerate() {
+        let arg_type = dfg.value_type(arg);
+        match constraints.value_argument_constraint(i, ctrl_typevar) {
+            ResolvedConstraint::Bound(expected_type) => assert_eq!(
+                arg_type,
+                expected_type,
+                "{}: arg {} ({}) has type {}, expected {}",
+                dfg.display_inst(inst, None),
+                i,
+                arg,
+                arg_type,
+                expected_type
+            ),
+            ResolvedConstraint::Free(typeset) => assert!(
+                typeset.contains(arg_type),
+                "{}: arg {} ({}) with type {} failed to satisfy type set {:?}",
+                dfg.display_inst(inst, None),
+                i,
+                arg,
+                arg_type,
+                typeset
+            ),
+        }
+    }
+}
+
+/// Operand types are only checked by the verifier without the `builder_typecheck` feature.
+#[cfg(not(feature = "builder_typecheck"))]
+#[inline(always)]
+fn typecheck(_dfg: &DataFlowGraph, _inst: Inst, _ctrl_typevar: Type) {}
+
 #[cfg(test)]
 mod tests {
     use crate::cursor::{Cursor, FuncCursor};
@@ -263,4 +316,19 @@ mod tests {
         assert!(iadd != iconst);
         assert_eq!(pos.func.dfg.value_def(v0), ValueDef::Result(iconst, 0));
     }
+
+    #[test]
+    #[cfg(feature = "builder_typecheck")]
+    #[should_panic(expected = "arg 1 (v1) has type i64, expected i32")]
+    fn typecheck_args() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let arg0 = func.dfg.append_ebb_param(ebb0, I32);
+        let arg1 = func.dfg.append_ebb_param(ebb0, I64);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+
+        // The controlling type is taken from the first argument.
+        pos.ins().iadd(arg0, arg1);
+    }
 }
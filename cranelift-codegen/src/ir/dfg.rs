@@ -5,13 +5,14 @@ use crate::ir;
 use crate::ir::builder::ReplaceBuilder;
 use crate::ir::extfunc::ExtFuncData;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionData};
+use crate::ir::provenance::{ProvenanceId, ProvenanceTable};
 use crate::ir::types;
 use crate::ir::{
-    Ebb, FuncRef, Inst, SigRef, Signature, Type, Value, ValueLabelAssignments, ValueList,
-    ValueListPool,
+    Ebb, FuncRef, Inst, Layout, SigRef, Signature, SourceLoc, Type, Value, ValueLabelAssignments,
+    ValueList, ValueListPool,
 };
 use crate::isa::TargetIsa;
-use crate::packed_option::ReservedValue;
+use crate::packed_option::{PackedOption, ReservedValue};
 use crate::write::write_operands;
 use core::fmt;
 use core::iter;
@@ -19,6 +20,7 @@ use core::mem;
 use core::ops::{Index, IndexMut};
 use core::u16;
 use std::collections::HashMap;
+use std::vec::Vec;
 
 /// A data flow graph defines all instructions and extended basic blocks in a function as well as
 /// the data flow dependencies between them. The DFG also tracks values which can be either
@@ -67,6 +69,9 @@ pub struct DataFlowGraph {
 
     /// Saves Value labels.
     pub values_labels: Option<HashMap<Value, ValueLabelAssignments>>,
+
+    /// Frontend provenance of values and instructions, when collection is enabled.
+    pub provenance: Option<ProvenanceTable>,
 }
 
 impl DataFlowGraph {
@@ -81,6 +86,7 @@ impl DataFlowGraph {
             signatures: PrimaryMap::new(),
             ext_funcs: PrimaryMap::new(),
             values_labels: None,
+            provenance: None,
         }
     }
 
@@ -94,6 +100,7 @@ impl DataFlowGraph {
         self.signatures.clear();
         self.ext_funcs.clear();
         self.values_labels = None;
+        self.provenance = None;
     }
 
     /// Get the total number of instructions created in this function, whether they are currently
@@ -133,6 +140,74 @@ impl DataFlowGraph {
             self.values_labels = Some(HashMap::new());
         }
     }
+
+    /// Record that `copy` holds the same variable as the labeled value `original`.
+    ///
+    /// This is used by passes that split live ranges, like the register allocator's copies,
+    /// spills and fills, so the debug variables assigned to `original` can be followed into the
+    /// new value's location. This does nothing unless `original` has value labels.
+    pub fn copy_value_labels(&mut self, original: Value, copy: Value) {
+        if let Some(values_labels) = self.values_labels.as_mut() {
+            if values_labels.contains_key(&original) {
+                values_labels
+                    .entry(copy)
+                    .or_insert(ValueLabelAssignments::Alias {
+                        from: SourceLoc::default(),
+                        value: original,
+                    });
+            }
+        }
+    }
+
+    /// Starts collection of provenance information.
+    pub fn collect_provenance(&mut self) {
+        if self.provenance.is_none() {
+            self.provenance = Some(ProvenanceTable::new());
+        }
+    }
+
+    /// Record that `value` came from the frontend entity `id`.
+    ///
+    /// This does nothing unless provenance collection has been enabled with
+    /// `collect_provenance()`.
+    pub fn set_value_provenance(&mut self, value: Value, id: ProvenanceId) {
+        if let Some(table) = self.provenance.as_mut() {
+            table.set_value(value, id);
+        }
+    }
+
+    /// Record that `inst` came from the frontend entity `id`.
+    ///
+    /// This does nothing unless provenance collection has been enabled with
+    /// `collect_provenance()`.
+    pub fn set_inst_provenance(&mut self, inst: Inst, id: ProvenanceId) {
+        if let Some(table) = self.provenance.as_mut() {
+            table.set_inst(inst, id);
+        }
+    }
+
+    /// Get the frontend entity that `value` came from, if known.
+    ///
+    /// Value aliases are resolved, so a value that was replaced by an alias reports the
+    /// provenance of the alias or of the original value.
+    pub fn value_provenance(&self, value: Value) -> Option<ProvenanceId> {
+        let table = self.provenance.as_ref()?;
+        table
+            .value(value)
+            .or_else(|| table.value(self.resolve_aliases(value)))
+    }
+
+    /// Get the frontend entity that `inst` came from, if known.
+    pub fn inst_provenance(&self, inst: Inst) -> Option<ProvenanceId> {
+        self.provenance.as_ref().and_then(|table| table.inst(inst))
+    }
+
+    /// Propagate provenance from a value that is being replaced to its replacement.
+    fn transfer_provenance(&mut self, from: Value, to: Value) {
+        if let Some(table) = self.provenance.as_mut() {
+            table.transfer_value(from, to);
+        }
+    }
 }
 
 /// Resolve value aliases.
@@ -304,6 +379,7 @@ impl DataFlowGraph {
         );
         debug_assert_ne!(ty, types::INVALID);
 
+        self.transfer_provenance(dest, original);
         self.values[dest] = ValueData::Alias { ty, original };
     }
 
@@ -348,6 +424,9 @@ impl DataFlowGraph {
             );
             debug_assert_ne!(ty, types::INVALID);
 
+            if let Some(table) = self.provenance.as_mut() {
+                table.transfer_value(dest, original);
+            }
             self.values[dest] = ValueData::Alias { ty, original };
         }
 
@@ -397,6 +476,21 @@ impl ValueDef {
     }
 }
 
+/// A copy of an instruction and its results, created by `DataFlowGraph::save_inst()`.
+#[derive(Clone, Debug)]
+pub struct SavedInst {
+    inst: Inst,
+    data: InstructionData,
+    results: Vec<(Value, ValueData)>,
+}
+
+impl SavedInst {
+    /// The saved instruction.
+    pub fn inst(&self) -> Inst {
+        self.inst
+    }
+}
+
 /// Internal table storage for extended values.
 #[derive(Clone, Debug)]
 enum ValueData {
@@ -554,6 +648,48 @@ impl DataFlowGraph {
         ReplaceBuilder::new(self, inst)
     }
 
+    /// Save a copy of `inst` and its result values, so later changes can be undone by
+    /// `restore_inst()`.
+    ///
+    /// The saved copy has its own value list, so it isn't affected by changes to the arguments of
+    /// `inst`. Result values that are turned into aliases, or that are replaced or detached, are
+    /// restored too.
+    pub fn save_inst(&mut self, inst: Inst) -> SavedInst {
+        let mut data = self.insts[inst].clone();
+        if let Some(vlist) = data.take_value_list() {
+            let args: Vec<Value> = vlist.as_slice(&self.value_lists).to_vec();
+            data.put_value_list(ValueList::from_slice(&args, &mut self.value_lists));
+        }
+        let results = self
+            .inst_results(inst)
+            .iter()
+            .map(|&v| (v, self.values[v].clone()))
+            .collect();
+        SavedInst {
+            inst,
+            data,
+            results,
+        }
+    }
+
+    /// Restore an instruction and its result values to the state saved by `save_inst()`.
+    ///
+    /// Values that were created after the instruction was saved are left unchanged, but they are
+    /// no longer attached to the instruction.
+    pub fn restore_inst(&mut self, saved: SavedInst) {
+        let SavedInst {
+            inst,
+            data,
+            results,
+        } = saved;
+        self.insts[inst] = data;
+        self.results[inst].clear(&mut self.value_lists);
+        for (v, vdata) in results {
+            self.values[v] = vdata;
+            self.results[inst].push(v, &mut self.value_lists);
+        }
+    }
+
     /// Detach the list of result values from `inst` and return it.
     ///
     /// This leaves `inst` without any result values. New result values can be created by calling
@@ -619,6 +755,7 @@ impl DataFlowGraph {
             old_value,
             self.display_inst(inst, None)
         );
+        self.transfer_provenance(old_value, new_value);
         new_value
     }
 
@@ -876,6 +1013,7 @@ impl DataFlowGraph {
         });
 
         self.ebbs[ebb].params.as_mut_slice(&mut self.value_lists)[num as usize] = new_arg;
+        self.transfer_provenance(old_value, new_arg);
         new_arg
     }
 
@@ -908,6 +1046,176 @@ impl EbbData {
     }
 }
 
+/// Mapping from old to new entity numbers, computed by `DataFlowGraph::renumber`.
+pub struct Renumbering {
+    ebbs: SecondaryMap<Ebb, PackedOption<Ebb>>,
+    insts: SecondaryMap<Inst, PackedOption<Inst>>,
+    values: SecondaryMap<Value, PackedOption<Value>>,
+}
+
+impl Renumbering {
+    /// Get the new number of `ebb`, or `None` if it was removed.
+    pub fn ebb(&self, ebb: Ebb) -> Option<Ebb> {
+        self.ebbs[ebb].expand()
+    }
+
+    /// Get the new number of `inst`, or `None` if it was removed.
+    pub fn inst(&self, inst: Inst) -> Option<Inst> {
+        self.insts[inst].expand()
+    }
+
+    /// Get the new number of `value`, or `None` if it was removed.
+    ///
+    /// Value aliases are always removed.
+    pub fn value(&self, value: Value) -> Option<Value> {
+        self.values[value].expand()
+    }
+}
+
+/// Get the new number of `value` in `map`, resolving aliases in the old `values` table.
+fn renumbered_value(
+    values: &PrimaryMap<Value, ValueData>,
+    map: &Renumbering,
+    value: Value,
+) -> Option<Value> {
+    match values[value] {
+        // Invalid values only pad the value numbers of parsed functions.
+        ValueData::Alias { ty, .. } if ty == types::INVALID => None,
+        _ => map.value(maybe_resolve_aliases(values, value)?),
+    }
+}
+
+/// Renumbering.
+impl DataFlowGraph {
+    /// Renumber the EBBs, instructions and values in the order they appear in `layout`.
+    ///
+    /// EBBs and instructions that are not in the layout are removed along with their values.
+    /// Value aliases are resolved and removed, and the value labels and provenance of an alias
+    /// move to the value it resolves to unless that value has its own.
+    ///
+    /// All references to EBBs and values in the instructions of the data flow graph are updated,
+    /// but the caller must update `layout` and any other data structure referring to the old
+    /// numbers, using the returned `Renumbering`.
+    pub fn renumber(&mut self, layout: &Layout) -> Renumbering {
+        let mut map = Renumbering {
+            ebbs: SecondaryMap::new(),
+            insts: SecondaryMap::new(),
+            values: SecondaryMap::new(),
+        };
+        let mut ebbs: PrimaryMap<Ebb, EbbData> = PrimaryMap::new();
+        let mut insts: PrimaryMap<Inst, InstructionData> = PrimaryMap::new();
+        let mut values: PrimaryMap<Value, ValueData> = PrimaryMap::new();
+        let mut results = SecondaryMap::new();
+
+        // Number the entities in layout order, EBB parameters before the instructions.
+        for ebb in layout.ebbs() {
+            let new_ebb = ebbs.push(self.ebbs[ebb].clone());
+            map.ebbs[ebb] = new_ebb.into();
+            for &param in self.ebb_params(ebb) {
+                map.values[param] = values.push(self.values[param].clone()).into();
+            }
+            for inst in layout.ebb_insts(ebb) {
+                let new_inst = insts.push(self.insts[inst].clone());
+                map.insts[inst] = new_inst.into();
+                results[new_inst] = self.results[inst].clone();
+                for &result in self.inst_results(inst) {
+                    map.values[result] = values.push(self.values[result].clone()).into();
+                }
+            }
+        }
+
+        // Update the references in the renumbered entities.
+        for data in values.values_mut() {
+            match *data {
+                ValueData::Inst { ref mut inst, .. } => *inst = map.insts[*inst].unwrap(),
+                ValueData::Param { ref mut ebb, .. } => *ebb = map.ebbs[*ebb].unwrap(),
+                ValueData::Alias { .. } => panic!("aliases are not renumbered"),
+            }
+        }
+        for data in ebbs.values_mut() {
+            for param in data.params.as_mut_slice(&mut self.value_lists) {
+                *param = map.values[*param].unwrap();
+            }
+        }
+        for list in results.values_mut() {
+            for result in list.as_mut_slice(&mut self.value_lists) {
+                *result = map.values[*result].unwrap();
+            }
+        }
+        for data in insts.values_mut() {
+            for arg in data.arguments_mut(&mut self.value_lists) {
+                *arg = renumbered_value(&self.values, &map, *arg)
+                    .expect("argument defined outside the layout");
+            }
+            if let Some(dest) = data.branch_destination_mut() {
+                *dest = map.ebbs[*dest].expect("branch to an EBB outside the layout");
+            }
+        }
+
+        // Side tables keep the entry of a value over the entries of its aliases.
+        if let Some(labels) = self.values_labels.take() {
+            let mut new_labels = HashMap::new();
+            for value in self.alias_order() {
+                let new_value = renumbered_value(&self.values, &map, value);
+                let (new_value, assignments) = match (new_value, labels.get(&value)) {
+                    (Some(new_value), Some(assignments)) => (new_value, assignments),
+                    _ => continue,
+                };
+                let assignments = match *assignments {
+                    ValueLabelAssignments::Starts(ref starts) => {
+                        ValueLabelAssignments::Starts(starts.clone())
+                    }
+                    ValueLabelAssignments::Alias { from, value } => {
+                        match renumbered_value(&self.values, &map, value) {
+                            Some(value) => ValueLabelAssignments::Alias { from, value },
+                            None => continue,
+                        }
+                    }
+                };
+                new_labels.entry(new_value).or_insert(assignments);
+            }
+            self.values_labels = Some(new_labels);
+        }
+        if let Some(table) = self.provenance.take() {
+            let mut new_table = ProvenanceTable::new();
+            for value in self.alias_order() {
+                let new_value = renumbered_value(&self.values, &map, value);
+                if let (Some(new_value), Some(id)) = (new_value, table.value(value)) {
+                    if new_table.value(new_value).is_none() {
+                        new_table.set_value(new_value, id);
+                    }
+                }
+            }
+            for inst in layout.ebbs().flat_map(|ebb| layout.ebb_insts(ebb)) {
+                if let Some(id) = table.inst(inst) {
+                    new_table.set_inst(map.insts[inst].unwrap(), id);
+                }
+            }
+            self.provenance = Some(new_table);
+        }
+
+        results.resize(insts.len());
+        self.ebbs = ebbs;
+        self.insts = insts;
+        self.values = values;
+        self.results = results;
+        map
+    }
+
+    /// Get all the values, with the aliases after the other values.
+    fn alias_order(&self) -> Vec<Value> {
+        let (mut order, aliases): (Vec<Value>, Vec<Value>) =
+            self.values
+                .keys()
+                .partition(|&value| match self.values[value] {
+                    ValueData::Alias { .. } => false,
+                    _ => true,
+                });
+        order.extend(aliases);
+        order
+    }
+}
+
 /// Object that can display an instruction.
 pub struct DisplayInst<'a>(&'a DataFlowGraph, Option<&'a dyn TargetIsa>, Inst);
 
@@ -1272,4 +1580,124 @@ mod tests {
         // This does not see through copies.
         assert_eq!(pos.func.dfg.resolve_aliases(c3), c3);
     }
+
+    #[test]
+    fn provenance() {
+        use crate::ir::{InstBuilder, ProvenanceId};
+
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let arg0 = func.dfg.append_ebb_param(ebb0, types::I32);
+
+        // Nothing is recorded until collection is enabled.
+        func.dfg.set_value_provenance(arg0, ProvenanceId(1));
+        assert_eq!(func.dfg.value_provenance(arg0), None);
+
+        func.dfg.collect_provenance();
+        func.dfg.set_value_provenance(arg0, ProvenanceId(1));
+        assert_eq!(func.dfg.value_provenance(arg0), Some(ProvenanceId(1)));
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let v1 = pos.ins().iadd_imm(arg0, 1);
+        let iadd = pos.func.dfg.value_def(v1).unwrap_inst();
+        pos.func.dfg.set_value_provenance(v1, ProvenanceId(2));
+        pos.func.dfg.set_inst_provenance(iadd, ProvenanceId(3));
+
+        // Instructions inserted on behalf of `iadd` inherit its provenance.
+        pos.goto_inst(iadd);
+        pos.use_srcloc(iadd);
+        let imm = pos.ins().iconst(types::I32, 1);
+        let iconst = pos.func.dfg.value_def(imm).unwrap_inst();
+        assert_eq!(pos.func.dfg.inst_provenance(iconst), Some(ProvenanceId(3)));
+
+        // Replacing a result keeps its provenance.
+        pos.func.dfg.replace(iadd).iadd(arg0, imm);
+        let v2 = pos.func.dfg.replace_result(v1, types::I32);
+        assert_eq!(pos.func.dfg.value_provenance(v2), Some(ProvenanceId(2)));
+
+        // So does turning a value into an alias.
+        let v3 = pos.ins().iconst(types::I32, 0);
+        let v3_inst = pos.func.dfg.value_def(v3).unwrap_inst();
+        pos.func.dfg.clear_results(v3_inst);
+        pos.func.dfg.set_value_provenance(v3, ProvenanceId(4));
+        let v4 = pos.ins().iconst(types::I32, 0);
+        pos.func.dfg.change_to_alias(v3, v4);
+        assert_eq!(pos.func.dfg.value_provenance(v4), Some(ProvenanceId(4)));
+        assert_eq!(pos.func.dfg.value_provenance(v3), Some(ProvenanceId(4)));
+    }
+
+    #[test]
+    fn copy_value_labels() {
+        use crate::ir::{ValueLabel, ValueLabelStart};
+
+        let mut dfg = DataFlowGraph::new();
+        let ebb = dfg.make_ebb();
+        let v0 = dfg.append_ebb_param(ebb, types::I32);
+        let v1 = dfg.append_ebb_param(ebb, types::I32);
+        let v2 = dfg.append_ebb_param(ebb, types::I32);
+
+        dfg.collect_debug_info();
+        dfg.values_labels.as_mut().unwrap().insert(
+            v0,
+            ValueLabelAssignments::Starts(vec![ValueLabelStart {
+                from: SourceLoc::new(1),
+                label: ValueLabel::from_u32(0),
+            }]),
+        );
+
+        // Only labeled values are followed.
+        dfg.copy_value_labels(v1, v2);
+        dfg.copy_value_labels(v0, v1);
+        let labels = dfg.values_labels.as_ref().unwrap();
+        assert!(labels.get(&v2).is_none());
+        match labels.get(&v1) {
+            Some(ValueLabelAssignments::Alias { from, value }) => {
+                assert!(from.is_default());
+                assert_eq!(*value, v0);
+            }
+            _ => panic!("expected an alias"),
+        }
+    }
+
+    #[test]
+    fn renumber() {
+        use crate::ir::InstBuilder;
+        use std::string::String;
+
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let arg0 = func.dfg.append_ebb_param(ebb0, types::I32);
+
+        // Lay out the EBBs in the opposite order of their numbers.
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb1);
+        let v1 = pos.ins().iconst(types::I32, 1);
+        let v2 = pos.ins().iconst(types::I32, 2);
+        pos.ins().jump(ebb0, &[v1]);
+        pos.insert_ebb(ebb0);
+        pos.ins().return_(&[arg0]);
+
+        // Replace `v1` with an alias, leaving its instruction out of the layout.
+        let v1_inst = pos.func.dfg.value_def(v1).unwrap_inst();
+        pos.func.dfg.clear_results(v1_inst);
+        pos.func.dfg.change_to_alias(v1, v2);
+        pos.func.layout.remove_inst(v1_inst);
+
+        func.renumber();
+        assert_eq!(func.dfg.num_ebbs(), 2);
+        assert_eq!(func.dfg.num_insts(), 3);
+        assert_eq!(func.dfg.num_values(), 2);
+
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        assert_eq!(ebbs, [Ebb::from_u32(0), Ebb::from_u32(1)]);
+        assert_eq!(func.dfg.ebb_params(ebbs[1]), [Value::from_u32(1)]);
+        let insts: Vec<String> = ebbs
+            .iter()
+            .flat_map(|&ebb| func.layout.ebb_insts(ebb))
+            .map(|inst| func.dfg.display_inst(inst, None).to_string())
+            .collect();
+        assert_eq!(insts, ["v0 = iconst.i32 2", "jump ebb1(v0)", "return v1"]);
+    }
 }
@@ -5,7 +5,7 @@
 //!
 //! This module declares the data types used to represent external functions and call signatures.
 
-use crate::ir::{ArgumentLoc, ExternalName, SigRef, Type};
+use crate::ir::{ArgumentLoc, ExternalName, Linkage, SigRef, Type, Visibility};
 use crate::isa::{CallConv, RegInfo, RegUnit};
 use core::fmt;
 use core::str::FromStr;
@@ -27,6 +27,13 @@ pub struct Signature {
 
     /// Calling convention.
     pub call_conv: CallConv,
+
+    /// Is this a call to a variadic function?
+    ///
+    /// The `params` of a variadic signature include both the fixed parameters and the variadic
+    /// arguments passed by a particular call, so each call site with a different set of variadic
+    /// arguments needs its own signature.
+    pub variadic: bool,
 }
 
 impl Signature {
@@ -36,6 +43,7 @@ impl Signature {
             params: Vec::new(),
             returns: Vec::new(),
             call_conv,
+            variadic: false,
         }
     }
 
@@ -44,6 +52,7 @@ impl Signature {
         self.params.clear();
         self.returns.clear();
         self.call_conv = call_conv;
+        self.variadic = false;
     }
 
     /// Return an object that can display `self` with correct register names.
@@ -82,7 +91,11 @@ impl<'a> fmt::Display for DisplaySignature<'a> {
             write!(f, " -> ")?;
             write_list(f, &self.0.returns, self.1)?;
         }
-        write!(f, " {}", self.0.call_conv)
+        write!(f, " {}", self.0.call_conv)?;
+        if self.0.variadic {
+            write!(f, " variadic")?;
+        }
+        Ok(())
     }
 }
 
@@ -270,10 +283,25 @@ pub enum ArgumentPurpose {
     /// This is a pointer to a stack limit. It is used to check the current stack pointer
     /// against. Can only appear once in a signature.
     StackLimit,
+
+    /// The number of vector registers used by a variadic call.
+    ///
+    /// Some ABIs require the caller of a variadic function to pass an upper bound on the number of
+    /// vector registers holding arguments, so the callee knows which registers to spill. This is
+    /// added by the ABI legalizer and can only appear once in a signature.
+    VectorRegCount,
+
+    /// A copy of a floating point argument of a variadic call, in an integer register.
+    ///
+    /// Some ABIs pass the floating point arguments of a variadic call in both a floating point
+    /// and an integer register, since the callee reads the variadic arguments from the integer
+    /// registers. These are added by the ABI legalizer after the other parameters, one for each
+    /// floating point argument passed in a register, in the same order.
+    FloatArgCopy,
 }
 
 /// Text format names of the `ArgumentPurpose` variants.
-static PURPOSE_NAMES: [&str; 8] = [
+static PURPOSE_NAMES: [&str; 10] = [
     "normal",
     "sret",
     "link",
@@ -282,6 +310,8 @@ static PURPOSE_NAMES: [&str; 8] = [
     "vmctx",
     "sigid",
     "stack_limit",
+    "vreg_count",
+    "float_copy",
 ];
 
 impl fmt::Display for ArgumentPurpose {
@@ -302,6 +332,8 @@ impl FromStr for ArgumentPurpose {
             "vmctx" => Ok(ArgumentPurpose::VMContext),
             "sigid" => Ok(ArgumentPurpose::SignatureId),
             "stack_limit" => Ok(ArgumentPurpose::StackLimit),
+            "vreg_count" => Ok(ArgumentPurpose::VectorRegCount),
+            "float_copy" => Ok(ArgumentPurpose::FloatArgCopy),
             _ => Err(()),
         }
     }
@@ -320,6 +352,11 @@ pub struct ExtFuncData {
     /// after linking? If so, references to it can avoid going through a GOT or PLT. Note that
     /// symbols meant to be preemptible cannot be considered colocated.
     pub colocated: bool,
+    /// Linkage of the function's symbol. This is `Linkage::Import` unless the function is defined
+    /// in the same object.
+    pub linkage: Linkage,
+    /// Visibility of the function's symbol.
+    pub visibility: Visibility,
 }
 
 impl fmt::Display for ExtFuncData {
@@ -327,6 +364,12 @@ impl fmt::Display for ExtFuncData {
         if self.colocated {
             write!(f, "colocated ")?;
         }
+        if self.linkage != Linkage::Import {
+            write!(f, "{} ", self.linkage)?;
+        }
+        if self.visibility != Visibility::Default {
+            write!(f, "{} ", self.visibility)?;
+        }
         write!(f, "{} {}", self.name, self.signature)
     }
 }
@@ -359,6 +402,8 @@ mod tests {
             ArgumentPurpose::VMContext,
             ArgumentPurpose::SignatureId,
             ArgumentPurpose::StackLimit,
+            ArgumentPurpose::VectorRegCount,
+            ArgumentPurpose::FloatArgCopy,
         ];
         for (&e, &n) in all_purpose.iter().zip(PURPOSE_NAMES.iter()) {
             assert_eq!(e.to_string(), n);
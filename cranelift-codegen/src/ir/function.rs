@@ -4,17 +4,19 @@
 //! instructions.
 
 use crate::binemit::CodeOffset;
-use crate::entity::{PrimaryMap, SecondaryMap};
+use crate::entity::{EntityRef, PrimaryMap, SecondaryMap};
+use crate::flowgraph::ControlFlowGraph;
 use crate::ir;
-use crate::ir::{DataFlowGraph, ExternalName, Layout, Signature};
+use crate::ir::{BranchWeights, EbbOffsets, InstEncodings, SourceLocs, StackSlots, ValueLocations};
+use crate::ir::{DataFlowGraph, ExternalName, Layout, Linkage, Signature, Visibility};
 use crate::ir::{
     Ebb, ExtFuncData, FuncRef, GlobalValue, GlobalValueData, Heap, HeapData, JumpTable,
     JumpTableData, SigRef, StackSlot, StackSlotData, Table, TableData,
 };
-use crate::ir::{EbbOffsets, InstEncodings, SourceLocs, StackSlots, ValueLocations};
 use crate::ir::{JumpTableOffsets, JumpTables};
 use crate::isa::{CallConv, EncInfo, Encoding, Legalize, TargetIsa};
 use crate::regalloc::RegDiversions;
+use crate::register_pressure::RegisterPressure;
 use crate::value_label::ValueLabelsRanges;
 use crate::write::write_function;
 use core::fmt;
@@ -31,6 +33,15 @@ pub struct Function {
     /// Signature of this function.
     pub signature: Signature,
 
+    /// Linkage of this function's symbol.
+    ///
+    /// Like the visibility, it is only used by object file writers, and it is not included in the
+    /// textual IR format.
+    pub linkage: Linkage,
+
+    /// Visibility of this function's symbol.
+    pub visibility: Visibility,
+
     /// Stack slots allocated in this function.
     pub stack_slots: StackSlots,
 
@@ -74,6 +85,12 @@ pub struct Function {
     /// Track the original source location for each instruction. The source locations are not
     /// interpreted by Cranelift, only preserved.
     pub srclocs: SourceLocs,
+
+    /// Profile weights of the conditional branches.
+    ///
+    /// Branches without a weight have the `None` value. The weights are used to lay out the EBBs
+    /// so the hot paths fall through. They are not included in the textual IR format.
+    pub branch_weights: BranchWeights,
 }
 
 impl Function {
@@ -82,6 +99,8 @@ impl Function {
         Self {
             name,
             signature: sig,
+            linkage: Linkage::Export,
+            visibility: Visibility::Default,
             stack_slots: StackSlots::new(),
             global_values: PrimaryMap::new(),
             heaps: PrimaryMap::new(),
@@ -94,12 +113,15 @@ impl Function {
             offsets: SecondaryMap::new(),
             jt_offsets: SecondaryMap::new(),
             srclocs: SecondaryMap::new(),
+            branch_weights: SecondaryMap::new(),
         }
     }
 
     /// Clear all data structures in this function.
     pub fn clear(&mut self) {
         self.signature.clear(CallConv::Fast);
+        self.linkage = Linkage::Export;
+        self.visibility = Visibility::Default;
         self.stack_slots.clear();
         self.global_values.clear();
         self.heaps.clear();
@@ -111,6 +133,7 @@ impl Function {
         self.locations.clear();
         self.offsets.clear();
         self.srclocs.clear();
+        self.branch_weights.clear();
     }
 
     /// Create a new empty, anonymous function with a Fast calling convention.
@@ -144,6 +167,23 @@ impl Function {
         self.global_values.push(data)
     }
 
+    /// Get the number of global values that `gv` is computed from, following the bases of `load`
+    /// and `iadd_imm` global values.
+    ///
+    /// Returns `None` if the chain has a cycle.
+    pub fn global_value_depth(&self, gv: GlobalValue) -> Option<usize> {
+        let mut depth = 0;
+        let mut cur = gv;
+        while let Some(base) = self.global_values[cur].base() {
+            depth += 1;
+            if depth > self.global_values.len() {
+                return None;
+            }
+            cur = base;
+        }
+        Some(depth)
+    }
+
     /// Declares a heap accessible to the function.
     pub fn create_heap(&mut self, data: HeapData) -> Heap {
         self.heaps.push(data)
@@ -215,10 +255,90 @@ impl Function {
         isa.encode(&self, &self.dfg[inst], self.dfg.ctrl_typevar(inst))
     }
 
+    /// Compute statistics about the instructions, EBBs, stack slots and jump tables in this
+    /// function.
+    pub fn compute_stats(&self) -> ir::FunctionStats {
+        ir::FunctionStats::new(self)
+    }
+
+    /// Compute the maximum number of simultaneously live values per register class in each EBB
+    /// of this function.
+    ///
+    /// The control flow graph `cfg` must be up to date.
+    pub fn compute_register_pressure(
+        &self,
+        cfg: &ControlFlowGraph,
+        isa: &dyn TargetIsa,
+    ) -> RegisterPressure {
+        RegisterPressure::new(self, cfg, isa)
+    }
+
     /// Starts collection of debug information.
     pub fn collect_debug_info(&mut self) {
         self.dfg.collect_debug_info();
     }
+
+    /// Renumber the EBBs, instructions and values of this function in layout order.
+    ///
+    /// The EBBs are numbered in layout order, and the values are numbered in the order they are
+    /// defined: EBB parameters first, then the results of each instruction. EBBs and instructions
+    /// that are not in the layout are removed, and value aliases are resolved. The result is a
+    /// canonical form of the function, so two functions that only differ in their entity numbers
+    /// print identically after renumbering.
+    ///
+    /// Stack slots, global values, heaps, tables, jump tables, signatures and external functions
+    /// keep their numbers.
+    pub fn renumber(&mut self) {
+        let map = self.dfg.renumber(&self.layout);
+
+        let mut layout = Layout::new();
+        for ebb in self.layout.ebbs() {
+            let new_ebb = map.ebb(ebb).unwrap();
+            layout.append_ebb(new_ebb);
+            if self.layout.is_cold(ebb) {
+                layout.set_cold(new_ebb);
+            }
+            for inst in self.layout.ebb_insts(ebb) {
+                layout.append_inst(map.inst(inst).unwrap(), new_ebb);
+            }
+        }
+        self.layout = layout;
+
+        // A jump table referring to a removed EBB can't be used by the instructions in the layout,
+        // so it is emptied.
+        for jt in self.jump_tables.values_mut() {
+            if jt.iter().all(|&dest| map.ebb(dest).is_some()) {
+                for dest in jt.iter_mut() {
+                    *dest = map.ebb(*dest).unwrap();
+                }
+            } else {
+                *jt = JumpTableData::new();
+            }
+        }
+
+        renumber_map(&mut self.encodings, |inst| map.inst(inst));
+        renumber_map(&mut self.srclocs, |inst| map.inst(inst));
+        renumber_map(&mut self.branch_weights, |inst| map.inst(inst));
+        renumber_map(&mut self.locations, |value| map.value(value));
+        renumber_map(&mut self.offsets, |ebb| map.ebb(ebb));
+    }
+}
+
+/// Move the entries of `map` to the new entity numbers given by `renumber`, dropping the entries
+/// of removed entities.
+fn renumber_map<K, V, F>(map: &mut SecondaryMap<K, V>, renumber: F)
+where
+    K: EntityRef,
+    V: Clone,
+    F: Fn(K) -> Option<K>,
+{
+    let mut new_map = SecondaryMap::with_default(map.get_default().clone());
+    for (key, value) in map.iter() {
+        if let Some(new_key) = renumber(key) {
+            new_map[new_key] = value.clone();
+        }
+    }
+    *map = new_map;
 }
 
 /// Additional annotations for function display.
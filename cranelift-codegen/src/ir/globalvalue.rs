@@ -5,6 +5,13 @@ use crate::ir::{ExternalName, GlobalValue, Type};
 use crate::isa::TargetIsa;
 use core::fmt;
 
+/// The maximum number of `load` and `iadd_imm` global values that a global value can be computed
+/// from.
+///
+/// The verifier rejects longer chains, since each `global_value` instruction is expanded into the
+/// whole chain.
+pub const MAX_GLOBAL_VALUE_DEPTH: usize = 32;
+
 /// Information about a global value declaration.
 #[derive(Clone)]
 pub enum GlobalValueData {
@@ -64,23 +71,58 @@ pub enum GlobalValueData {
         /// symbols meant to be preemptible cannot be colocated.
         colocated: bool,
     },
+
+    /// Value is the address of a thread-local variable.
+    ///
+    /// Like `Symbol`, the name is resolved later by the linker or loader. The instruction
+    /// sequence used to compute the address depends on the `tls_model` setting.
+    TlsValue {
+        /// The symbolic name of the thread-local variable.
+        name: ExternalName,
+
+        /// Will this symbol be defined in the same module as the current function? This allows
+        /// some TLS models to avoid a dynamic lookup.
+        colocated: bool,
+    },
 }
 
 impl GlobalValueData {
-    /// Assume that `self` is an `GlobalValueData::Symbol` and return its name.
+    /// Assume that `self` is an `GlobalValueData::Symbol` or `GlobalValueData::TlsValue` and
+    /// return its name.
     pub fn symbol_name(&self) -> &ExternalName {
         match *self {
-            GlobalValueData::Symbol { ref name, .. } => name,
+            GlobalValueData::Symbol { ref name, .. }
+            | GlobalValueData::TlsValue { ref name, .. } => name,
             _ => panic!("only symbols have names"),
         }
     }
 
+    /// Assume that `self` is an `GlobalValueData::Symbol` or `GlobalValueData::TlsValue` and
+    /// return the byte offset that should be added to the symbol's address in a relocation.
+    pub fn symbol_offset(&self) -> i64 {
+        match *self {
+            GlobalValueData::Symbol { offset, .. } => offset.into(),
+            GlobalValueData::TlsValue { .. } => 0,
+            _ => panic!("only symbols have offsets"),
+        }
+    }
+
+    /// Get the global value that this one is computed from, if any.
+    pub fn base(&self) -> Option<GlobalValue> {
+        match *self {
+            GlobalValueData::Load { base, .. } | GlobalValueData::IAddImm { base, .. } => {
+                Some(base)
+            }
+            _ => None,
+        }
+    }
+
     /// Return the type of this global.
     pub fn global_type(&self, isa: &dyn TargetIsa) -> Type {
         match *self {
-            GlobalValueData::VMContext { .. } | GlobalValueData::Symbol { .. } => {
-                isa.pointer_type()
-            }
+            GlobalValueData::VMContext { .. }
+            | GlobalValueData::Symbol { .. }
+            | GlobalValueData::TlsValue { .. } => isa.pointer_type(),
             GlobalValueData::IAddImm { global_type, .. }
             | GlobalValueData::Load { global_type, .. } => global_type,
         }
@@ -129,6 +171,15 @@ impl fmt::Display for GlobalValueData {
                 }
                 Ok(())
             }
+            GlobalValueData::TlsValue {
+                ref name,
+                colocated,
+            } => write!(
+                f,
+                "tls_value {}{}",
+                if colocated { "colocated " } else { "" },
+                name
+            ),
         }
     }
 }
@@ -1,7 +1,7 @@
 //! Heaps.
 
 use crate::ir::immediates::Uimm64;
-use crate::ir::{GlobalValue, Type};
+use crate::ir::{FuncRef, GlobalValue, TrapCode, Type};
 use core::fmt;
 
 /// Information about a heap declaration.
@@ -20,8 +20,29 @@ pub struct HeapData {
     /// Heap style, with additional style-specific info.
     pub style: HeapStyle,
 
-    /// The index type for the heap.
+    /// The index type for the heap. This is `i32` for most heaps, and `i64` for heaps that can be
+    /// larger than 4 GB.
     pub index_type: Type,
+
+    /// The trap code used by the bounds checks of the heap. This is normally `heap_oob`, but
+    /// functions accessing several heaps can use a different code for each of them.
+    pub trap_code: TrapCode,
+
+    /// What happens when an access to the heap is out of bounds.
+    pub violation: HeapViolation,
+}
+
+impl HeapData {
+    /// Get the largest index that can be represented by the heap's index type.
+    ///
+    /// Heaps with a 64-bit index type can span the whole 64-bit address range, so bounds checks
+    /// must be computed in `u64` without assuming that indexes fit in 32 bits.
+    pub fn max_index(&self) -> u64 {
+        match self.index_type.bits() {
+            bits if bits >= 64 => u64::max_value(),
+            bits => (1 << bits) - 1,
+        }
+    }
 }
 
 /// Style of heap including style-specific information.
@@ -41,6 +62,31 @@ pub enum HeapStyle {
     },
 }
 
+/// Policy for handling out-of-bounds heap accesses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeapViolation {
+    /// Trap with the heap's `trap_code`. This is the default, and the semantics of WebAssembly.
+    Trap,
+
+    /// Clamp the index to the last position where the access is within the heap bound. An access
+    /// larger than the heap still traps with the heap's `trap_code`.
+    Clamp,
+
+    /// Call a host function with the out-of-bounds index, and use the address it returns for the
+    /// access instead. The function takes the heap index type and returns a pointer.
+    CustomLibcall(FuncRef),
+}
+
+impl fmt::Display for HeapViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HeapViolation::Trap => f.write_str("trap"),
+            HeapViolation::Clamp => f.write_str("clamp"),
+            HeapViolation::CustomLibcall(callee) => write!(f, "libcall {}", callee),
+        }
+    }
+}
+
 impl fmt::Display for HeapData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str(match self.style {
@@ -57,6 +103,13 @@ impl fmt::Display for HeapData {
             f,
             ", offset_guard {}, index_type {}",
             self.offset_guard_size, self.index_type
-        )
+        )?;
+        if self.trap_code != TrapCode::HeapOutOfBounds {
+            write!(f, ", trap_code {}", self.trap_code)?;
+        }
+        if self.violation != HeapViolation::Trap {
+            write!(f, ", violation {}", self.violation)?;
+        }
+        Ok(())
     }
 }
@@ -34,6 +34,7 @@ pub type ValueListPool = entity::ListPool<Value>;
 // - The `pub enum InstructionData` enum with all the instruction data fields.
 // - The `pub enum Opcode` definition with all known opcodes,
 // - The `const OPCODE_FORMAT: [InstructionFormat; N]` table.
+// - The `const OPCODE_EFFECTS: [OpcodeEffects; N]` table.
 // - The private `fn opcode_name(Opcode) -> &'static str` function, and
 // - The hash table `const OPCODE_HASH_TABLE: [Opcode; N]`.
 //
@@ -62,6 +63,78 @@ impl Opcode {
     pub fn constraints(self) -> OpcodeConstraints {
         OPCODE_CONSTRAINTS[self as usize - 1]
     }
+
+    /// Get the effects of executing an instruction with this opcode.
+    pub fn effects(self) -> OpcodeEffects {
+        OPCODE_EFFECTS[self as usize - 1]
+    }
+}
+
+/// The effects of an opcode that matter to passes moving, merging, or removing instructions.
+///
+/// This is the same information as the `Opcode::can_*` and `Opcode::is_*` predicates, gathered in
+/// a single table. Passes should prefer the summary queries like `has_side_effects()` to listing
+/// the individual effects, so they keep working as new kinds of instructions are added.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct OpcodeEffects {
+    /// Can the instruction read from memory?
+    pub reads_memory: bool,
+    /// Can the instruction write to memory?
+    pub writes_memory: bool,
+    /// Can the instruction cause a trap?
+    pub can_trap: bool,
+    /// Is the instruction a call?
+    pub is_call: bool,
+    /// Is the instruction a branch or jump?
+    pub is_branch: bool,
+    /// Does the instruction terminate its EBB?
+    pub is_terminator: bool,
+    /// Is the instruction a return?
+    pub is_return: bool,
+    /// Does the instruction write to CPU flags?
+    pub writes_cpu_flags: bool,
+    /// Does the instruction have other side effects, like fences and stack pointer adjustments?
+    pub other_side_effects: bool,
+}
+
+impl OpcodeEffects {
+    /// Must the instruction be kept even when its results are unused?
+    ///
+    /// Loads are not considered to have side effects, even though they can trap when their
+    /// memory flags allow it.
+    pub fn has_side_effects(self) -> bool {
+        self.writes_memory
+            || self.can_trap
+            || self.is_call
+            || self.is_branch
+            || self.is_terminator
+            || self.is_return
+            || self.other_side_effects
+    }
+
+    /// Can the instruction be moved, or merged with an identical instruction?
+    ///
+    /// This is true for instructions without side effects that don't write CPU flags. Loads must
+    /// additionally be checked for stores to the memory they read between the old and new
+    /// positions.
+    pub fn can_move(self) -> bool {
+        !self.has_side_effects() && !self.writes_cpu_flags
+    }
+
+    /// Can the instruction modify memory that is read by loads?
+    ///
+    /// Calls and instructions with other side effects are assumed to modify any memory.
+    pub fn may_clobber_memory(self) -> bool {
+        self.writes_memory || self.is_call || self.other_side_effects
+    }
+
+    /// Is the instruction a safepoint, where the embedder can observe the function's frame?
+    ///
+    /// Calls can run arbitrary code, and instructions that can trap transfer control to the
+    /// embedder's trap handler. Passes inserting stack maps or interrupt checks use this.
+    pub fn is_safepoint(self) -> bool {
+        self.is_call || self.can_trap
+    }
 }
 
 // This trait really belongs in cranelift-reader where it is used by the `.clif` file parser, but since
@@ -588,6 +661,36 @@ mod tests {
         assert_eq!(mem::size_of::<Opcode>(), mem::size_of::<Option<Opcode>>());
     }
 
+    #[test]
+    fn opcode_effects() {
+        let iadd = Opcode::Iadd.effects();
+        assert!(!iadd.has_side_effects());
+        assert!(iadd.can_move());
+
+        let load = Opcode::Load.effects();
+        assert!(load.reads_memory);
+        assert!(load.can_move());
+        assert!(!load.may_clobber_memory());
+
+        let store = Opcode::Store.effects();
+        assert!(store.writes_memory);
+        assert!(store.has_side_effects());
+        assert!(store.may_clobber_memory());
+
+        let call = Opcode::Call.effects();
+        assert!(call.is_call);
+        assert!(call.may_clobber_memory());
+
+        assert!(call.is_safepoint());
+
+        assert!(Opcode::Return.effects().is_terminator);
+        assert!(Opcode::Trap.effects().can_trap);
+        assert!(Opcode::Trap.effects().is_safepoint());
+        assert!(!iadd.is_safepoint());
+        assert!(!Opcode::Ifcmp.effects().can_move());
+        assert!(Opcode::Jump.effects().has_side_effects());
+    }
+
     #[test]
     fn instruction_data() {
         use core::mem;
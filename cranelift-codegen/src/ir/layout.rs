@@ -459,6 +459,19 @@ impl Layout {
     pub fn next_ebb(&self, ebb: Ebb) -> Option<Ebb> {
         self.ebbs[ebb].next.expand()
     }
+
+    /// Mark `ebb` as cold.
+    ///
+    /// Cold EBBs are rarely executed, like the EBBs ending in a `trap` that the legalizer creates
+    /// for conditional traps. Branch relaxation moves them to the end of the function.
+    pub fn set_cold(&mut self, ebb: Ebb) {
+        self.ebbs[ebb].cold = true;
+    }
+
+    /// Is `ebb` marked as cold?
+    pub fn is_cold(&self, ebb: Ebb) -> bool {
+        self.ebbs[ebb].cold
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -468,6 +481,7 @@ struct EbbNode {
     first_inst: PackedOption<Inst>,
     last_inst: PackedOption<Inst>,
     seq: SequenceNumber,
+    cold: bool,
 }
 
 /// Iterate over EBBs in layout order. See `Layout::ebbs()`.
@@ -1,8 +1,8 @@
 //! Naming well-known routines in the runtime library.
 
 use crate::ir::{
-    types, AbiParam, ArgumentPurpose, ExtFuncData, ExternalName, FuncRef, Function, Inst, Opcode,
-    Signature, Type,
+    types, AbiParam, ArgumentPurpose, ExtFuncData, ExternalName, FuncRef, Function, Inst, Linkage,
+    Opcode, Signature, Type, Visibility,
 };
 use crate::isa::{CallConv, RegUnit, TargetIsa};
 use core::fmt;
@@ -18,7 +18,7 @@ use serde::{Deserialize, Serialize};
 /// convention in the embedding VM's runtime library.
 ///
 /// This list is likely to grow over time.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
 pub enum LibCall {
     /// probe for stack overflow. These are emitted for functions which need
@@ -34,18 +34,29 @@ pub enum LibCall {
     FloorF64,
     /// trunc.f32
     TruncF32,
-    /// frunc.f64
+    /// trunc.f64
     TruncF64,
     /// nearest.f32
     NearestF32,
     /// nearest.f64
     NearestF64,
+    /// fma.f32
+    FmaF32,
+    /// fma.f64
+    FmaF64,
     /// libc.memcpy
     Memcpy,
     /// libc.memset
     Memset,
     /// libc.memmove
     Memmove,
+    /// table_grow
+    TableGrow,
+    /// table_fill
+    TableFill,
+
+    /// Elf __tls_get_addr
+    ElfTlsGetAddr,
 }
 
 impl fmt::Display for LibCall {
@@ -68,9 +79,15 @@ impl FromStr for LibCall {
             "TruncF64" => Ok(LibCall::TruncF64),
             "NearestF32" => Ok(LibCall::NearestF32),
             "NearestF64" => Ok(LibCall::NearestF64),
+            "FmaF32" => Ok(LibCall::FmaF32),
+            "FmaF64" => Ok(LibCall::FmaF64),
             "Memcpy" => Ok(LibCall::Memcpy),
             "Memset" => Ok(LibCall::Memset),
             "Memmove" => Ok(LibCall::Memmove),
+            "TableGrow" => Ok(LibCall::TableGrow),
+            "TableFill" => Ok(LibCall::TableFill),
+
+            "ElfTlsGetAddr" => Ok(LibCall::ElfTlsGetAddr),
             _ => Err(()),
         }
     }
@@ -88,6 +105,7 @@ impl LibCall {
                 Opcode::Floor => LibCall::FloorF32,
                 Opcode::Trunc => LibCall::TruncF32,
                 Opcode::Nearest => LibCall::NearestF32,
+                Opcode::Fma => LibCall::FmaF32,
                 _ => return None,
             },
             types::F64 => match opcode {
@@ -95,6 +113,7 @@ impl LibCall {
                 Opcode::Floor => LibCall::FloorF64,
                 Opcode::Trunc => LibCall::TruncF64,
                 Opcode::Nearest => LibCall::NearestF64,
+                Opcode::Fma => LibCall::FmaF64,
                 _ => return None,
             },
             _ => return None,
@@ -105,48 +124,101 @@ impl LibCall {
 /// Get a function reference for `libcall` in `func`, following the signature
 /// for `inst`.
 ///
-/// If there is an existing reference, use it, otherwise make a new one.
+/// The name and signature registered for `libcall` in the ISA's `LibCallRegistry` take
+/// precedence. If there is an existing reference, use it, otherwise make a new one.
 pub fn get_libcall_funcref(
     libcall: LibCall,
     func: &mut Function,
     inst: Inst,
     isa: &dyn TargetIsa,
 ) -> FuncRef {
-    find_funcref(libcall, func).unwrap_or_else(|| make_funcref_for_inst(libcall, func, inst, isa))
+    let target = isa.libcalls().resolve(libcall);
+    find_funcref(&target.name, func).unwrap_or_else(|| {
+        let sig = target
+            .signature
+            .unwrap_or_else(|| signature_for_inst(func, inst, isa));
+        make_funcref(target.name, func, sig, isa)
+    })
+}
+
+/// Get a function reference for the library call registered for the opcode and controlling type
+/// of `inst` in the ISA's `LibCallRegistry`, following the signature for `inst` unless the
+/// registry provides one.
+///
+/// Returns `None` if no library call is registered for the instruction.
+pub fn get_inst_libcall_funcref(
+    func: &mut Function,
+    inst: Inst,
+    isa: &dyn TargetIsa,
+) -> Option<FuncRef> {
+    let opcode = func.dfg[inst].opcode();
+    let ctrl_type = func.dfg.ctrl_typevar(inst);
+    let target = isa.libcalls().inst_libcall(opcode, ctrl_type)?.clone();
+    Some(find_funcref(&target.name, func).unwrap_or_else(|| {
+        let sig = target
+            .signature
+            .unwrap_or_else(|| signature_for_inst(func, inst, isa));
+        make_funcref(target.name, func, sig, isa)
+    }))
+}
+
+/// Get a function reference for `libcall` in `func`, with a signature taking `params` and
+/// returning `returns`.
+///
+/// This is used for instructions whose operands don't map directly to the library call arguments.
+/// The name and signature registered for `libcall` in the ISA's `LibCallRegistry` take
+/// precedence. If there is an existing reference, use it, otherwise make a new one.
+pub fn get_libcall_funcref_with_types(
+    libcall: LibCall,
+    func: &mut Function,
+    params: &[Type],
+    returns: &[Type],
+    isa: &dyn TargetIsa,
+) -> FuncRef {
+    let target = isa.libcalls().resolve(libcall);
+    find_funcref(&target.name, func).unwrap_or_else(|| {
+        let sig = target.signature.unwrap_or_else(|| {
+            let mut sig = Signature::new(isa.default_call_conv());
+            sig.params
+                .extend(params.iter().map(|&ty| AbiParam::new(ty)));
+            sig.returns
+                .extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+            sig
+        });
+        make_funcref(target.name, func, sig, isa)
+    })
 }
 
 /// Get a function reference for the probestack function in `func`.
 ///
-/// If there is an existing reference, use it, otherwise make a new one.
+/// The probestack function can be renamed in the ISA's `LibCallRegistry`, but its signature is
+/// always the special probestack convention. If there is an existing reference, use it, otherwise
+/// make a new one.
 pub fn get_probestack_funcref(
     func: &mut Function,
     reg_type: Type,
     arg_reg: RegUnit,
     isa: &dyn TargetIsa,
 ) -> FuncRef {
-    find_funcref(LibCall::Probestack, func)
-        .unwrap_or_else(|| make_funcref_for_probestack(func, reg_type, arg_reg, isa))
+    let name = isa.libcalls().resolve(LibCall::Probestack).name;
+    find_funcref(&name, func)
+        .unwrap_or_else(|| make_funcref_for_probestack(name, func, reg_type, arg_reg, isa))
 }
 
-/// Get the existing function reference for `libcall` in `func` if it exists.
-fn find_funcref(libcall: LibCall, func: &Function) -> Option<FuncRef> {
-    // We're assuming that all libcall function decls are at the end.
-    // If we get this wrong, worst case we'll have duplicate libcall decls which is harmless.
-    for (fref, func_data) in func.dfg.ext_funcs.iter().rev() {
-        match func_data.name {
-            ExternalName::LibCall(lc) => {
-                if lc == libcall {
-                    return Some(fref);
-                }
-            }
-            _ => break,
-        }
-    }
-    None
+/// Get the existing function reference for `name` in `func` if it exists.
+fn find_funcref(name: &ExternalName, func: &Function) -> Option<FuncRef> {
+    // Library call decls are usually at the end, so search backwards.
+    func.dfg
+        .ext_funcs
+        .iter()
+        .rev()
+        .find(|&(_, func_data)| func_data.name == *name)
+        .map(|(fref, _)| fref)
 }
 
 /// Create a funcref for `LibCall::Probestack`.
 fn make_funcref_for_probestack(
+    name: ExternalName,
     func: &mut Function,
     reg_type: Type,
     arg_reg: RegUnit,
@@ -158,16 +230,11 @@ fn make_funcref_for_probestack(
     if !isa.flags().probestack_func_adjusts_sp() {
         sig.returns.push(rax);
     }
-    make_funcref(LibCall::Probestack, func, sig, isa)
+    make_funcref(name, func, sig, isa)
 }
 
-/// Create a funcref for `libcall` with a signature matching `inst`.
-fn make_funcref_for_inst(
-    libcall: LibCall,
-    func: &mut Function,
-    inst: Inst,
-    isa: &dyn TargetIsa,
-) -> FuncRef {
+/// Get a library call signature matching `inst`.
+fn signature_for_inst(func: &Function, inst: Inst, isa: &dyn TargetIsa) -> Signature {
     let mut sig = Signature::new(isa.default_call_conv());
     for &v in func.dfg.inst_args(inst) {
         sig.params.push(AbiParam::new(func.dfg.value_type(v)));
@@ -175,13 +242,12 @@ fn make_funcref_for_inst(
     for &v in func.dfg.inst_results(inst) {
         sig.returns.push(AbiParam::new(func.dfg.value_type(v)));
     }
-
-    make_funcref(libcall, func, sig, isa)
+    sig
 }
 
-/// Create a funcref for `libcall`.
+/// Create a funcref for the library call `name`.
 fn make_funcref(
-    libcall: LibCall,
+    name: ExternalName,
     func: &mut Function,
     sig: Signature,
     isa: &dyn TargetIsa,
@@ -189,9 +255,11 @@ fn make_funcref(
     let sigref = func.import_signature(sig);
 
     func.import_function(ExtFuncData {
-        name: ExternalName::LibCall(libcall),
+        name,
         signature: sigref,
         colocated: isa.flags().colocated_libcalls(),
+        linkage: Linkage::Import,
+        visibility: Visibility::Default,
     })
 }
 
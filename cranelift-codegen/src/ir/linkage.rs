@@ -0,0 +1,166 @@
+//! Symbol linkage and visibility.
+//!
+//! Functions and their external function declarations carry the linkage and visibility of their
+//! symbols, so object file writers can decide the binding of each symbol without keeping a
+//! separate table. Cranelift doesn't interpret them otherwise.
+
+use crate::ir::ExternalName;
+use core::fmt::{self, Display, Formatter};
+use core::str::FromStr;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// The linkage of a symbol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum Linkage {
+    /// Defined outside of the current object, and imported.
+    Import,
+    /// Defined in the current object, and not visible outside of it.
+    Local,
+    /// Defined in the current object, and visible outside of it.
+    Export,
+    /// Defined in the current object and visible outside of it, but may be overridden by a
+    /// definition in another object.
+    Weak,
+}
+
+impl Linkage {
+    /// Is the symbol defined in the current object?
+    pub fn is_definable(self) -> bool {
+        self != Linkage::Import
+    }
+}
+
+impl Display for Linkage {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Linkage::Import => "import",
+            Linkage::Local => "local",
+            Linkage::Export => "export",
+            Linkage::Weak => "weak",
+        })
+    }
+}
+
+impl FromStr for Linkage {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "import" => Ok(Linkage::Import),
+            "local" => Ok(Linkage::Local),
+            "export" => Ok(Linkage::Export),
+            "weak" => Ok(Linkage::Weak),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The visibility of a symbol outside of the linked module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub enum Visibility {
+    /// Visible to other modules, and may be preempted by them.
+    Default,
+    /// Not visible to other modules.
+    Hidden,
+    /// Visible to other modules, but references from the defining module can't be preempted.
+    Protected,
+}
+
+impl Display for Visibility {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Visibility::Default => "default",
+            Visibility::Hidden => "hidden",
+            Visibility::Protected => "protected",
+        })
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Visibility::Default),
+            "hidden" => Ok(Visibility::Hidden),
+            "protected" => Ok(Visibility::Protected),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A symbol referenced or defined by a function, as returned by `Context::symbols()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SymbolInfo {
+    /// The name of the symbol.
+    pub name: ExternalName,
+    /// The linkage of the symbol.
+    pub linkage: Linkage,
+    /// The visibility of the symbol.
+    pub visibility: Visibility,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{ExtFuncData, Function, Signature};
+    use crate::isa::CallConv;
+    use crate::Context;
+    use std::string::ToString;
+
+    #[test]
+    fn display() {
+        for &linkage in &[
+            Linkage::Import,
+            Linkage::Local,
+            Linkage::Export,
+            Linkage::Weak,
+        ] {
+            assert_eq!(linkage.to_string().parse(), Ok(linkage));
+        }
+        for &visibility in &[
+            Visibility::Default,
+            Visibility::Hidden,
+            Visibility::Protected,
+        ] {
+            assert_eq!(visibility.to_string().parse(), Ok(visibility));
+        }
+        assert_eq!("global".parse::<Linkage>(), Err(()));
+    }
+
+    #[test]
+    fn context_symbols() {
+        let mut func = Function::with_name_signature(
+            ExternalName::testcase("f"),
+            Signature::new(CallConv::SystemV),
+        );
+        func.visibility = Visibility::Hidden;
+        let sig = func.import_signature(Signature::new(CallConv::SystemV));
+        for &(name, linkage) in &[
+            ("g", Linkage::Import),
+            ("h", Linkage::Local),
+            ("g", Linkage::Import),
+        ] {
+            func.import_function(ExtFuncData {
+                name: ExternalName::testcase(name),
+                signature: sig,
+                colocated: linkage.is_definable(),
+                linkage,
+                visibility: Visibility::Default,
+            });
+        }
+
+        let ctx = Context::for_function(func);
+        let symbols = ctx.symbols();
+        assert_eq!(symbols.len(), 3);
+        assert_eq!(symbols[0].name, ExternalName::testcase("f"));
+        assert_eq!(symbols[0].linkage, Linkage::Export);
+        assert_eq!(symbols[0].visibility, Visibility::Hidden);
+        assert_eq!(symbols[1].linkage, Linkage::Import);
+        assert_eq!(symbols[2].name, ExternalName::testcase("h"));
+        assert_eq!(symbols[2].linkage, Linkage::Local);
+    }
+}
@@ -1,5 +1,6 @@
 //! Representation of Cranelift IR functions.
 
+mod branchweight;
 mod builder;
 pub mod condcodes;
 pub mod dfg;
@@ -14,17 +15,22 @@ pub mod instructions;
 pub mod jumptable;
 pub mod layout;
 mod libcall;
+mod linkage;
 mod memflags;
 mod progpoint;
+mod provenance;
+mod siginterner;
 mod sourceloc;
 pub mod stackslot;
+mod stats;
 mod table;
 mod trapcode;
 pub mod types;
 mod valueloc;
 
+pub use crate::ir::branchweight::BranchWeight;
 pub use crate::ir::builder::{InsertBuilder, InstBuilder, InstBuilderBase, InstInserterBase};
-pub use crate::ir::dfg::{DataFlowGraph, ValueDef};
+pub use crate::ir::dfg::{DataFlowGraph, SavedInst, ValueDef};
 pub use crate::ir::entities::{
     Ebb, FuncRef, GlobalValue, Heap, Inst, JumpTable, SigRef, StackSlot, Table, Value,
 };
@@ -33,18 +39,25 @@ pub use crate::ir::extfunc::{
 };
 pub use crate::ir::extname::ExternalName;
 pub use crate::ir::function::{DisplayFunctionAnnotations, Function};
-pub use crate::ir::globalvalue::GlobalValueData;
-pub use crate::ir::heap::{HeapData, HeapStyle};
+pub use crate::ir::globalvalue::{GlobalValueData, MAX_GLOBAL_VALUE_DEPTH};
+pub use crate::ir::heap::{HeapData, HeapStyle, HeapViolation};
 pub use crate::ir::instructions::{
-    InstructionData, Opcode, ValueList, ValueListPool, VariableArgs,
+    InstructionData, Opcode, OpcodeEffects, ValueList, ValueListPool, VariableArgs,
 };
 pub use crate::ir::jumptable::JumpTableData;
 pub use crate::ir::layout::Layout;
-pub use crate::ir::libcall::{get_libcall_funcref, get_probestack_funcref, LibCall};
+pub use crate::ir::libcall::{
+    get_inst_libcall_funcref, get_libcall_funcref, get_libcall_funcref_with_types,
+    get_probestack_funcref, LibCall,
+};
+pub use crate::ir::linkage::{Linkage, SymbolInfo, Visibility};
 pub use crate::ir::memflags::MemFlags;
 pub use crate::ir::progpoint::{ExpandedProgramPoint, ProgramOrder, ProgramPoint};
+pub use crate::ir::provenance::{ProvenanceId, ProvenanceTable};
+pub use crate::ir::siginterner::{SigId, SignatureInterner};
 pub use crate::ir::sourceloc::SourceLoc;
 pub use crate::ir::stackslot::{StackSlotData, StackSlotKind, StackSlots};
+pub use crate::ir::stats::FunctionStats;
 pub use crate::ir::table::TableData;
 pub use crate::ir::trapcode::TrapCode;
 pub use crate::ir::types::Type;
@@ -72,6 +85,9 @@ pub type JumpTableOffsets = SecondaryMap<JumpTable, binemit::CodeOffset>;
 /// Source locations for instructions.
 pub type SourceLocs = SecondaryMap<Inst, SourceLoc>;
 
+/// Profile weights of conditional branches.
+pub type BranchWeights = SecondaryMap<Inst, Option<BranchWeight>>;
+
 /// Marked with a label value.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct ValueLabel(u32);
@@ -0,0 +1,109 @@
+//! Provenance of values and instructions.
+//!
+//! Frontends can attach opaque identifiers to the values and instructions they create, such as a
+//! WebAssembly local index or a reference to an AST node. Cranelift doesn't interpret these
+//! identifiers, but the data flow graph carries them through IR rewrites like legalization so
+//! diagnostics and debuggers can map the generated code back to the source program.
+
+use crate::ir::{Inst, Value};
+use crate::HashMap;
+use core::fmt;
+#[cfg(feature = "enable-serde")]
+use serde::{Deserialize, Serialize};
+
+/// An opaque identifier for a frontend entity.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct ProvenanceId(pub u64);
+
+impl fmt::Display for ProvenanceId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "prov{}", self.0)
+    }
+}
+
+/// Side table mapping values and instructions to the frontend entities they came from.
+#[derive(Clone, Default)]
+pub struct ProvenanceTable {
+    values: HashMap<Value, ProvenanceId>,
+    insts: HashMap<Inst, ProvenanceId>,
+}
+
+impl ProvenanceTable {
+    /// Create a new empty table.
+    pub fn new() -> Self {
+        Self {
+            values: HashMap::new(),
+            insts: HashMap::new(),
+        }
+    }
+
+    /// Clear all entries.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.insts.clear();
+    }
+
+    /// Is this table empty?
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.insts.is_empty()
+    }
+
+    /// Get the provenance recorded for `value`, if any.
+    ///
+    /// This does not resolve value aliases, see `DataFlowGraph::value_provenance`.
+    pub fn value(&self, value: Value) -> Option<ProvenanceId> {
+        self.values.get(&value).cloned()
+    }
+
+    /// Record the provenance of `value`, replacing any existing entry.
+    pub fn set_value(&mut self, value: Value, id: ProvenanceId) {
+        self.values.insert(value, id);
+    }
+
+    /// Get the provenance recorded for `inst`, if any.
+    pub fn inst(&self, inst: Inst) -> Option<ProvenanceId> {
+        self.insts.get(&inst).cloned()
+    }
+
+    /// Record the provenance of `inst`, replacing any existing entry.
+    pub fn set_inst(&mut self, inst: Inst, id: ProvenanceId) {
+        self.insts.insert(inst, id);
+    }
+
+    /// Propagate the provenance of `from` to `to` when `from` is being replaced by `to`.
+    ///
+    /// An existing entry for `to` is kept since it is more precise than the inherited one.
+    pub fn transfer_value(&mut self, from: Value, to: Value) {
+        if let Some(id) = self.value(from) {
+            self.values.entry(to).or_insert(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityRef;
+    use std::string::ToString;
+
+    #[test]
+    fn transfer() {
+        let v0 = Value::new(0);
+        let v1 = Value::new(1);
+        let v2 = Value::new(2);
+        let mut table = ProvenanceTable::new();
+        assert!(table.is_empty());
+
+        table.set_value(v0, ProvenanceId(7));
+        table.set_value(v2, ProvenanceId(9));
+        table.transfer_value(v0, v1);
+        table.transfer_value(v0, v2);
+        assert_eq!(table.value(v1), Some(ProvenanceId(7)));
+        assert_eq!(table.value(v2), Some(ProvenanceId(9)));
+
+        table.transfer_value(v1, v0);
+        assert_eq!(table.value(v0), Some(ProvenanceId(7)));
+        assert_eq!(ProvenanceId(7).to_string(), "prov7");
+    }
+}
@@ -0,0 +1,147 @@
+//! Signatures shared by many functions.
+//!
+//! Every function has its own table of signatures, referenced by `SigRef`. Modules with many
+//! functions calling the same imports would otherwise store and legalize a copy of the same
+//! signature for every call site. A `SignatureInterner` keeps one copy of each distinct signature
+//! for a whole module, and imports it at most once into each function.
+
+use crate::entity::{entity_impl, PrimaryMap, SecondaryMap};
+use crate::ir::{Function, SigRef, Signature};
+use crate::isa::TargetIsa;
+use crate::legalizer::legalize_libcall_signature;
+use crate::HashMap;
+
+/// An opaque reference to a signature interned in a `SignatureInterner`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SigId(u32);
+entity_impl!(SigId, "sigid");
+
+/// A table of distinct signatures, shared by the functions of a module.
+#[derive(Clone, Debug)]
+pub struct SignatureInterner {
+    /// The interned signatures.
+    signatures: PrimaryMap<SigId, Signature>,
+
+    /// Map from signature to its id, for deduplication.
+    ids: HashMap<Signature, SigId>,
+
+    /// Legalized callee signatures, computed on demand.
+    legalized: SecondaryMap<SigId, Option<Signature>>,
+}
+
+impl SignatureInterner {
+    /// Create an empty interner.
+    pub fn new() -> Self {
+        Self {
+            signatures: PrimaryMap::new(),
+            ids: HashMap::new(),
+            legalized: SecondaryMap::new(),
+        }
+    }
+
+    /// Clear all the interned signatures.
+    pub fn clear(&mut self) {
+        self.signatures.clear();
+        self.ids.clear();
+        self.legalized.clear();
+    }
+
+    /// Get the number of distinct signatures.
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    /// Are there no interned signatures?
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Intern `sig`, returning the id of an identical signature if there is one already.
+    pub fn intern(&mut self, sig: &Signature) -> SigId {
+        if let Some(&id) = self.ids.get(sig) {
+            return id;
+        }
+        let id = self.signatures.push(sig.clone());
+        self.ids.insert(sig.clone(), id);
+        id
+    }
+
+    /// Get the interned signature `id`.
+    pub fn get(&self, id: SigId) -> &Signature {
+        &self.signatures[id]
+    }
+
+    /// Get the signature `id` as legalized for calls to it on `isa`.
+    ///
+    /// The legalized signature is computed the first time it is requested and cached afterwards,
+    /// so it must always be requested for the same ISA.
+    pub fn legalized(&mut self, id: SigId, isa: &dyn TargetIsa) -> &Signature {
+        if self.legalized[id].is_none() {
+            let mut sig = self.signatures[id].clone();
+            legalize_libcall_signature(&mut sig, isa);
+            self.legalized[id] = Some(sig);
+        }
+        self.legalized[id].as_ref().unwrap()
+    }
+
+    /// Import the signature `id` into `func`.
+    ///
+    /// If `func` already has an identical signature, its `SigRef` is reused instead of importing
+    /// a new copy.
+    pub fn import(&self, id: SigId, func: &mut Function) -> SigRef {
+        let sig = &self.signatures[id];
+        // Functions usually import a signature right before using it, so look at the most recent
+        // imports first.
+        match func
+            .dfg
+            .signatures
+            .iter()
+            .rev()
+            .find(|&(_, existing)| existing == sig)
+        {
+            Some((sig_ref, _)) => sig_ref,
+            None => func.import_signature(sig.clone()),
+        }
+    }
+}
+
+impl Default for SignatureInterner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignatureInterner;
+    use crate::ir::{types, AbiParam, Function, Signature};
+    use crate::isa::CallConv;
+
+    #[test]
+    fn intern() {
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(types::I32));
+        let mut other = sig.clone();
+        other.returns.push(AbiParam::new(types::I64));
+
+        let mut interner = SignatureInterner::new();
+        let id = interner.intern(&sig);
+        assert_eq!(interner.intern(&sig.clone()), id);
+        let other_id = interner.intern(&other);
+        assert_ne!(other_id, id);
+        assert_eq!(interner.len(), 2);
+        assert_eq!(interner.get(other_id), &other);
+
+        let mut f1 = Function::new();
+        let mut f2 = Function::new();
+        let r1 = interner.import(id, &mut f1);
+        assert_eq!(interner.import(id, &mut f1), r1);
+        let r2 = interner.import(other_id, &mut f1);
+        assert_ne!(r1, r2);
+        assert_eq!(f1.dfg.signatures.len(), 2);
+        assert_eq!(f1.dfg.signatures[r2], other);
+
+        interner.import(other_id, &mut f2);
+        assert_eq!(f2.dfg.signatures.len(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+//! Function statistics.
+//!
+//! Embedders can use the statistics computed by `Function::compute_stats()` to drive heuristics
+//! like tier-up decisions, and tests can use them to check the effect of optimization passes.
+
+use crate::ir::{Function, Opcode, Type};
+use crate::HashMap;
+
+/// Counts of the entities in a function.
+///
+/// Only instructions and EBBs that are inserted in the layout are counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    /// Number of EBBs.
+    pub ebbs: usize,
+
+    /// Number of instructions.
+    pub insts: usize,
+
+    /// Number of stack slots of all kinds.
+    pub stack_slots: usize,
+
+    /// Number of jump tables.
+    pub jump_tables: usize,
+
+    /// Total number of entries in all jump tables.
+    pub jump_table_entries: usize,
+
+    /// Number of instructions with each opcode.
+    pub opcodes: HashMap<Opcode, usize>,
+
+    /// Number of instructions with each controlling type variable.
+    ///
+    /// Non-polymorphic instructions are counted under `types::INVALID`.
+    pub types: HashMap<Type, usize>,
+}
+
+impl FunctionStats {
+    /// Compute the statistics for `func`.
+    pub fn new(func: &Function) -> Self {
+        let mut stats = Self {
+            stack_slots: func.stack_slots.values().len(),
+            jump_tables: func.jump_tables.len(),
+            jump_table_entries: func.jump_tables.values().map(|jt| jt.len()).sum(),
+            ..Self::default()
+        };
+
+        for ebb in func.layout.ebbs() {
+            stats.ebbs += 1;
+            for inst in func.layout.ebb_insts(ebb) {
+                stats.insts += 1;
+                *stats.opcodes.entry(func.dfg[inst].opcode()).or_insert(0) += 1;
+                *stats.types.entry(func.dfg.ctrl_typevar(inst)).or_insert(0) += 1;
+            }
+        }
+        stats
+    }
+
+    /// Get the number of instructions with `opcode`.
+    pub fn opcode_count(&self, opcode: Opcode) -> usize {
+        self.opcodes.get(&opcode).cloned().unwrap_or(0)
+    }
+
+    /// Get the number of instructions with the controlling type variable `ty`.
+    pub fn type_count(&self, ty: Type) -> usize {
+        self.types.get(&ty).cloned().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::types::{I32, I64, INVALID};
+    use crate::ir::{Function, InstBuilder, JumpTableData, Opcode};
+
+    #[test]
+    fn stats() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let mut jt = JumpTableData::new();
+        jt.push_entry(ebb1);
+        jt.push_entry(ebb1);
+        let jt = func.create_jump_table(jt);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let v0 = pos.ins().iconst(I32, 1);
+        let v1 = pos.ins().iconst(I32, 2);
+        let v2 = pos.ins().iadd(v0, v1);
+        pos.ins().uextend(I64, v2);
+        pos.ins().br_table(v2, ebb1, jt);
+        pos.insert_ebb(ebb1);
+        pos.ins().return_(&[]);
+
+        let stats = func.compute_stats();
+        assert_eq!(stats.ebbs, 2);
+        assert_eq!(stats.insts, 6);
+        assert_eq!(stats.stack_slots, 0);
+        assert_eq!(stats.jump_tables, 1);
+        assert_eq!(stats.jump_table_entries, 2);
+        assert_eq!(stats.opcode_count(Opcode::Iconst), 2);
+        assert_eq!(stats.opcode_count(Opcode::Isub), 0);
+        assert_eq!(stats.type_count(I32), 4);
+        assert_eq!(stats.type_count(I64), 1);
+        assert_eq!(stats.type_count(INVALID), 1);
+    }
+}
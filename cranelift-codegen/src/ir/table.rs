@@ -1,7 +1,7 @@
 //! Tables.
 
-use crate::ir::immediates::Uimm64;
-use crate::ir::{GlobalValue, Type};
+use crate::ir::immediates::{Uimm32, Uimm64};
+use crate::ir::{GlobalValue, TrapCode, Type};
 use core::fmt;
 
 /// Information about a table declaration.
@@ -15,6 +15,9 @@ pub struct TableData {
     pub min_size: Uimm64,
 
     /// Global value giving the current bound of the table, in elements.
+    ///
+    /// Tables grown by `table_grow` change their bound, and may move to a different base address.
+    /// Their base and bound must then not be loaded from `readonly` memory.
     pub bound_gv: GlobalValue,
 
     /// The size of a table element, in bytes.
@@ -22,6 +25,14 @@ pub struct TableData {
 
     /// The index type for the table.
     pub index_type: Type,
+
+    /// The trap code used by the bounds checks of the table. This is normally `table_oob`, but
+    /// functions accessing several tables can use a different code for each of them.
+    pub trap_code: TrapCode,
+
+    /// Identifier of the table passed to the library routines that grow and fill it, so the
+    /// runtime can tell the tables of a function apart. WebAssembly uses the table index.
+    pub id: Uimm32,
 }
 
 impl fmt::Display for TableData {
@@ -31,6 +42,13 @@ impl fmt::Display for TableData {
             f,
             " {}, min {}, bound {}, element_size {}, index_type {}",
             self.base_gv, self.min_size, self.bound_gv, self.element_size, self.index_type
-        )
+        )?;
+        if self.trap_code != TrapCode::TableOutOfBounds {
+            write!(f, ", trap_code {}", self.trap_code)?;
+        }
+        if self.id != Uimm32::from(0) {
+            write!(f, ", id {}", self.id)?;
+        }
+        Ok(())
     }
 }
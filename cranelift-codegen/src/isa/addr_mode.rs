@@ -0,0 +1,251 @@
+//! Matching of memory addresses against the addressing modes of a target.
+//!
+//! Many ISAs can compute an address of the form `base + index * scale + disp` as part of a load
+//! or store instruction. The functions in this module recognize that pattern in the IR, given a
+//! description of the addressing modes supported by the target, so that the passes forming
+//! complex loads and stores don't need to know about a specific ISA.
+
+use crate::ir::dfg::ValueDef;
+use crate::ir::{DataFlowGraph, InstructionData, Opcode, Value};
+
+/// Description of the `base + index * scale + disp` addressing modes supported by a target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressModes {
+    /// The largest power-of-two scale that can be applied to an index register, or 0 if the
+    /// target doesn't support an index register.
+    pub max_scale: u8,
+
+    /// The number of bits in the signed displacement.
+    pub disp_bits: u8,
+}
+
+impl AddressModes {
+    /// Can the signed displacement `disp` be encoded?
+    pub fn disp_fits(self, disp: i64) -> bool {
+        match self.disp_bits {
+            0 => disp == 0,
+            1..=63 => {
+                let limit = 1i64 << (self.disp_bits - 1);
+                disp >= -limit && disp < limit
+            }
+            _ => true,
+        }
+    }
+
+    /// Can an index register be multiplied by `scale`?
+    pub fn scale_fits(self, scale: i64) -> bool {
+        scale > 0 && scale <= i64::from(self.max_scale) && (scale as u64).is_power_of_two()
+    }
+}
+
+/// A memory address decomposed as `base + index * scale + disp`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressMode {
+    /// The base address.
+    pub base: Value,
+
+    /// The index register and the scale applied to it, if any.
+    pub index: Option<(Value, u8)>,
+
+    /// The displacement.
+    pub disp: i64,
+}
+
+/// Match the address `addr + disp` against the addressing modes described by `modes`.
+///
+/// The returned address computes the same value as `addr + disp`, using only the scales and
+/// displacements allowed by `modes`. `iadd_imm` instructions are folded into the displacement as
+/// long as it stays in range, and an `iadd` of the base address is split into a base and an
+/// index. An index defined by `ishl_imm` or `imul_imm` is folded into the scale.
+pub fn match_address(
+    dfg: &DataFlowGraph,
+    addr: Value,
+    disp: i64,
+    modes: AddressModes,
+) -> AddressMode {
+    let (base, disp) = fold_displacement(dfg, addr, disp, modes);
+    let mut mode = AddressMode {
+        base,
+        index: None,
+        disp,
+    };
+    if modes.max_scale == 0 {
+        return mode;
+    }
+
+    if let Some([x, y]) = iadd_args(dfg, base) {
+        // Prefer a scaled operand as the index.
+        let (base, index) = if let Some(index) = scaled_index(dfg, y, modes) {
+            (x, index)
+        } else if let Some(index) = scaled_index(dfg, x, modes) {
+            (y, index)
+        } else {
+            (x, (y, 1))
+        };
+        let (base, disp) = fold_displacement(dfg, base, mode.disp, modes);
+        mode = AddressMode {
+            base,
+            index: Some(index),
+            disp,
+        };
+    }
+    mode
+}
+
+/// Get the definition of `value`, if it is an instruction result.
+fn def_data(dfg: &DataFlowGraph, value: Value) -> Option<&InstructionData> {
+    match dfg.value_def(value) {
+        ValueDef::Result(inst, _) => Some(&dfg[inst]),
+        ValueDef::Param(..) => None,
+    }
+}
+
+/// Fold the `iadd_imm` instructions defining `addr` into the displacement `disp`.
+fn fold_displacement(
+    dfg: &DataFlowGraph,
+    mut addr: Value,
+    mut disp: i64,
+    modes: AddressModes,
+) -> (Value, i64) {
+    while let Some(&InstructionData::BinaryImm {
+        opcode: Opcode::IaddImm,
+        arg,
+        imm,
+    }) = def_data(dfg, addr)
+    {
+        match disp.checked_add(imm.into()) {
+            Some(sum) if modes.disp_fits(sum) => {
+                addr = arg;
+                disp = sum;
+            }
+            _ => break,
+        }
+    }
+    (addr, disp)
+}
+
+/// Get the arguments of `value` if it is defined by an `iadd` instruction.
+fn iadd_args(dfg: &DataFlowGraph, value: Value) -> Option<[Value; 2]> {
+    match def_data(dfg, value) {
+        Some(&InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        }) => Some(args),
+        _ => None,
+    }
+}
+
+/// Match `value` as an index register multiplied by a scale.
+fn scaled_index(dfg: &DataFlowGraph, value: Value, modes: AddressModes) -> Option<(Value, u8)> {
+    let (arg, scale) = match def_data(dfg, value) {
+        Some(&InstructionData::BinaryImm {
+            opcode: Opcode::IshlImm,
+            arg,
+            imm,
+        }) => {
+            let amount: i64 = imm.into();
+            if !(0..8).contains(&amount) {
+                return None;
+            }
+            (arg, 1 << amount)
+        }
+        Some(&InstructionData::BinaryImm {
+            opcode: Opcode::ImulImm,
+            arg,
+            imm,
+        }) => (arg, imm.into()),
+        _ => return None,
+    };
+    if modes.scale_fits(scale) {
+        Some((arg, scale as u8))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::types::I64;
+    use crate::ir::{Function, InstBuilder};
+
+    const X86: AddressModes = AddressModes {
+        max_scale: 8,
+        disp_bits: 32,
+    };
+
+    const BASE_DISP12: AddressModes = AddressModes {
+        max_scale: 0,
+        disp_bits: 12,
+    };
+
+    #[test]
+    fn displacement() {
+        assert!(X86.disp_fits(-0x8000_0000));
+        assert!(!X86.disp_fits(0x8000_0000));
+        assert!(BASE_DISP12.disp_fits(2047));
+        assert!(!BASE_DISP12.disp_fits(2048));
+        assert!(X86.scale_fits(8));
+        assert!(!X86.scale_fits(3));
+        assert!(!BASE_DISP12.scale_fits(1));
+    }
+
+    #[test]
+    fn base_index_scale_disp() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let base = func.dfg.append_ebb_param(ebb0, I64);
+        let idx = func.dfg.append_ebb_param(ebb0, I64);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+
+        let scaled = pos.ins().ishl_imm(idx, 2);
+        let sum = pos.ins().iadd(scaled, base);
+        let addr = pos.ins().iadd_imm(sum, 16);
+        let far = pos.ins().iadd_imm(base, 4000);
+
+        assert_eq!(
+            match_address(&pos.func.dfg, addr, 4, X86),
+            AddressMode {
+                base,
+                index: Some((idx, 4)),
+                disp: 20,
+            }
+        );
+
+        // Without an index register, only the displacement is folded.
+        assert_eq!(
+            match_address(&pos.func.dfg, addr, 4, BASE_DISP12),
+            AddressMode {
+                base: sum,
+                index: None,
+                disp: 20,
+            }
+        );
+
+        // The scale doesn't fit, so the `iadd` operands are used as they are.
+        let modes = AddressModes {
+            max_scale: 1,
+            ..X86
+        };
+        assert_eq!(
+            match_address(&pos.func.dfg, sum, 0, modes),
+            AddressMode {
+                base: scaled,
+                index: Some((base, 1)),
+                disp: 0,
+            }
+        );
+
+        // The displacement doesn't fit.
+        assert_eq!(
+            match_address(&pos.func.dfg, far, 0, BASE_DISP12),
+            AddressMode {
+                base: far,
+                index: None,
+                disp: 0,
+            }
+        );
+    }
+}
@@ -1,20 +1,51 @@
 //! ARM ABI implementation.
-//! This is from the RISC-V target and will need to be updated for ARM32.
+//!
+//! This follows the AAPCS with the VFP variant, where floating point arguments are passed in
+//! VFP registers.
 
 use super::registers::{D, GPR, Q, S};
 use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
-use crate::ir::{self, AbiParam, ArgumentExtension, ArgumentLoc, Type};
-use crate::isa::RegClass;
+use crate::cursor::{Cursor, CursorPosition, EncCursor};
+use crate::ir::immediates::Imm64;
+use crate::ir::{
+    self, AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, Type, ValueLoc,
+};
+use crate::isa::{RegClass, TargetIsa};
 use crate::regalloc::RegisterSet;
+use crate::result::{CodegenError, CodegenResult};
+use crate::stack_layout::layout_stack;
 use core::i32;
 use target_lexicon::Triple;
 
+/// The intra-procedure-call scratch register `ip`, reserved for the encoding recipes.
+const IP: usize = 12;
+
+/// The stack pointer.
+const SP: usize = 13;
+
+/// The link register holding the return address.
+const LR: usize = 14;
+
+/// The program counter.
+const PC: usize = 15;
+
+/// Callee-saved registers that can be allocated.
+const CALLEE_SAVED_GPRS: [usize; 8] = [4, 5, 6, 7, 8, 9, 10, 11];
+
+/// Number of single precision registers used for arguments, `s0`-`s15`.
+const FPR_ARGS: u32 = 16;
+
+/// The first single precision register which is not allocated. `d7` is used as a temporary by the
+/// encoding recipes, and `d8`-`d15` are callee-saved.
+const FPR_RESERVED: usize = 14;
+
 struct Args {
     pointer_bits: u8,
     pointer_bytes: u8,
     pointer_type: Type,
     regs: u32,
     reg_limit: u32,
+    fpr_used: u32,
     offset: u32,
 }
 
@@ -25,7 +56,8 @@ impl Args {
             pointer_bytes: bits / 8,
             pointer_type: Type::int(u16::from(bits)).unwrap(),
             regs: 0,
-            reg_limit: 8,
+            reg_limit: 4,
+            fpr_used: 0,
             offset: 0,
         }
     }
@@ -62,9 +94,31 @@ impl ArgAssigner for Args {
             }
         }
 
+        if ty.is_float() {
+            let size = ty.bytes();
+            // Double precision values use an even pair of single precision registers.
+            let first = align(self.fpr_used, size / 4);
+            if first < FPR_ARGS {
+                self.fpr_used = first + size / 4;
+                let reg = if size == 8 {
+                    D.unit(first as usize / 2)
+                } else {
+                    S.unit(first as usize)
+                };
+                return ArgumentLoc::Reg(reg).into();
+            }
+            // Once a floating point value has gone to the stack, the remaining ones follow.
+            self.fpr_used = FPR_ARGS;
+            self.offset = align(self.offset, size);
+            let loc = ArgumentLoc::Stack(self.offset as i32);
+            self.offset += size;
+            debug_assert!(self.offset <= i32::MAX as u32);
+            return loc.into();
+        }
+
         if self.regs < self.reg_limit {
             // Assign to a register.
-            let reg = GPR.unit(10 + self.regs as usize);
+            let reg = GPR.unit(self.regs as usize);
             self.regs += 1;
             ArgumentLoc::Reg(reg).into()
         } else {
@@ -78,16 +132,29 @@ impl ArgAssigner for Args {
 }
 
 /// Legalize `sig`.
-pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bool) {
+pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, current: bool) {
     let bits = triple.pointer_width().unwrap().bits();
 
     let mut args = Args::new(bits);
     legalize_args(&mut sig.params, &mut args);
+
+    let mut rets = Args::new(bits);
+    legalize_args(&mut sig.returns, &mut rets);
+
+    if current {
+        let ptr = Type::int(u16::from(bits)).unwrap();
+
+        // Add the link register as an argument and return value, so the return instruction can
+        // branch to it.
+        let link = AbiParam::special_reg(ptr, ArgumentPurpose::Link, GPR.unit(LR));
+        sig.params.push(link);
+        sig.returns.push(link);
+    }
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
+    if ty.is_int() || ty.is_bool() {
         GPR
     } else {
         match ty.bits() {
@@ -101,5 +168,130 @@ pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    regs.take(GPR, GPR.unit(IP));
+    regs.take(GPR, GPR.unit(SP));
+    // %r14 is the link register which is available for allocation.
+    regs.take(GPR, GPR.unit(PC));
+
+    for u in FPR_RESERVED..32 {
+        regs.take(S, S.unit(u));
+    }
+    // %d16-%d31 don't overlap any single precision register.
+    for u in 16..32 {
+        regs.take(D, D.unit(u));
+    }
+
+    regs
+}
+
+/// Get the set of callee-saved registers that are used.
+fn callee_saved_gprs_used(func: &ir::Function) -> RegisterSet {
+    let mut all_callee_saved = RegisterSet::empty();
+    for &reg in &CALLEE_SAVED_GPRS {
+        all_callee_saved.free(GPR, GPR.unit(reg));
+    }
+
+    let mut used = RegisterSet::empty();
+    for value_loc in func.locations.values() {
+        if let ValueLoc::Reg(ru) = *value_loc {
+            if GPR.contains(ru) && !used.is_avail(GPR, ru) {
+                used.free(GPR, ru);
+            }
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::instructions::InstructionData::RegMove { dst, .. }
+                | ir::instructions::InstructionData::RegFill { dst, .. }
+                    if GPR.contains(dst) && !used.is_avail(GPR, dst) =>
+                {
+                    used.free(GPR, dst);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    used.intersect(&all_callee_saved);
+    used
+}
+
+/// Compute the stack layout and insert the prologue and epilogues.
+///
+/// The frame is addressed from the stack pointer, and there is no frame pointer. The used
+/// callee-saved registers are pushed, then the stack pointer is decremented to make room for the
+/// local stack slots.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    // The AAPCS requires an 8-byte aligned stack pointer at public interfaces.
+    let stack_align = 8;
+    let word_size = isa.pointer_bytes() as usize;
+    let reg_type = isa.pointer_type();
+
+    let csrs = callee_saved_gprs_used(func);
+
+    let csr_stack_size = (csrs.iter(GPR).len() * word_size) as i32;
+    if csr_stack_size > 0 {
+        func.create_stack_slot(ir::StackSlotData {
+            kind: ir::StackSlotKind::IncomingArg,
+            size: csr_stack_size as u32,
+            offset: Some(-csr_stack_size),
+        });
+    }
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size);
+
+    // Spills and fills use a 12-bit offset from the stack pointer.
+    if total_stack_size >= 4096 {
+        return Err(CodegenError::ImplLimitExceeded);
+    }
+
+    // Add CSRs to function signature
+    for csr in csrs.iter(GPR) {
+        let csr_arg = ir::AbiParam::special_reg(reg_type, ir::ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
+    // Set up the cursor and insert the prologue
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    for reg in csrs.iter(GPR) {
+        let csr_arg = pos.func.dfg.append_ebb_param(entry_ebb, reg_type);
+        pos.func.locations[csr_arg] = ir::ValueLoc::Reg(reg);
+        pos.ins().arm32_push(csr_arg);
+    }
+    if local_stack_size > 0 {
+        pos.ins().adjust_sp_down_imm(Imm64::new(local_stack_size));
+    }
+
+    // Reset the cursor and insert the epilogues
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                if local_stack_size > 0 {
+                    pos.ins().adjust_sp_up_imm(Imm64::new(local_stack_size));
+                }
+
+                // Pop the callee-saved registers, stepping backward each time to restore them
+                // in the reverse order.
+                for reg in csrs.iter(GPR) {
+                    let csr_ret = pos.ins().arm32_pop();
+                    pos.prev_inst();
+
+                    pos.func.locations[csr_ret] = ir::ValueLoc::Reg(reg);
+                    pos.func.dfg.append_inst_arg(inst, csr_ret);
+                }
+            }
+        }
+    }
+
+    Ok(())
 }
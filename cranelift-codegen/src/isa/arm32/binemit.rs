@@ -1,7 +1,654 @@
 //! Emitting binary ARM32 machine code.
 
-use crate::binemit::{bad_encoding, CodeSink};
-use crate::ir::{Function, Inst};
+use crate::binemit::{bad_encoding, CodeOffset, CodeSink, Reloc};
+use crate::ir::condcodes::{FloatCC, IntCC};
+use crate::ir::{Function, Inst, InstructionData, TrapCode};
+use crate::isa::{RegUnit, StackBase, StackBaseMask, StackRef};
 use crate::regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm32.rs"));
+
+/// Emit `size` bytes of no-op instructions, using `nop` (A32 encoding).
+pub fn emit_padding<CS: CodeSink + ?Sized>(size: CodeOffset, sink: &mut CS) {
+    debug_assert_eq!(
+        size % 4,
+        0,
+        "{} bytes of padding is not a whole instruction",
+        size
+    );
+    for _ in 0..size / 4 {
+        sink.put4(0xe320_f000);
+    }
+}
+
+// Condition codes in bits 31:28 of A32 instructions.
+const COND_EQ: u32 = 0b0000;
+const COND_NE: u32 = 0b0001;
+const COND_HS: u32 = 0b0010;
+const COND_LO: u32 = 0b0011;
+const COND_MI: u32 = 0b0100;
+const COND_PL: u32 = 0b0101;
+const COND_VS: u32 = 0b0110;
+const COND_VC: u32 = 0b0111;
+const COND_HI: u32 = 0b1000;
+const COND_LS: u32 = 0b1001;
+const COND_GE: u32 = 0b1010;
+const COND_LT: u32 = 0b1011;
+const COND_GT: u32 = 0b1100;
+const COND_LE: u32 = 0b1101;
+const AL: u32 = 0b1110;
+
+// Data-processing opcodes used by the recipes. Comparisons always set the flags.
+const DP_AND: u16 = 0b0000;
+const DP_RSB: u16 = 0b0011;
+const DP_ADD: u16 = 0b0100;
+const DP_CMP: u16 = 0b1010 | 0x10;
+const DP_CMN: u16 = 0b1011 | 0x10;
+const DP_MOV: u16 = 0b1101;
+
+// Fixed registers, as the low 4 bits of their register units.
+const IP: RegUnit = 12;
+const SP: RegUnit = 13;
+
+// The single precision register used as a temporary for integer conversions.
+const S14: RegUnit = 14;
+
+// Instructions without any operands.
+const BL: u32 = 0xeb00_0000;
+const BX_LR: u32 = 0xe12f_ff1e;
+const BKPT: u32 = 0xe120_0070;
+const VMRS_APSR_NZCV: u32 = 0xeef1_fa10;
+
+// The `op` bit of `put_vmov_core()`.
+const VMOV_TO_CORE: u16 = 1;
+
+/// Convert an integer condition code to the A32 condition after a `cmp`.
+fn icc2cond(cond: IntCC) -> u32 {
+    use crate::ir::condcodes::IntCC::*;
+    match cond {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        SignedLessThan => COND_LT,
+        SignedGreaterThanOrEqual => COND_GE,
+        SignedGreaterThan => COND_GT,
+        SignedLessThanOrEqual => COND_LE,
+        UnsignedLessThan => COND_LO,
+        UnsignedGreaterThanOrEqual => COND_HS,
+        UnsignedGreaterThan => COND_HI,
+        UnsignedLessThanOrEqual => COND_LS,
+    }
+}
+
+/// Convert a floating point condition code to A32 conditions after a `vcmp` and a `vmrs`.
+///
+/// The condition is true when either of the returned conditions hold. Only `one` and `ueq` need
+/// two different conditions, the others return the same one twice.
+fn fcc2conds(cond: FloatCC) -> (u32, u32) {
+    use crate::ir::condcodes::FloatCC::*;
+    let c = match cond {
+        Ordered => COND_VC,
+        Unordered => COND_VS,
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        LessThan => COND_MI,
+        LessThanOrEqual => COND_LS,
+        GreaterThan => COND_GT,
+        GreaterThanOrEqual => COND_GE,
+        UnorderedOrLessThan => COND_LT,
+        UnorderedOrLessThanOrEqual => COND_LE,
+        UnorderedOrGreaterThan => COND_HI,
+        UnorderedOrGreaterThanOrEqual => COND_PL,
+        OrderedNotEqual => return (COND_MI, COND_GT),
+        UnorderedOrEqual => return (COND_EQ, COND_VS),
+    };
+    (c, c)
+}
+
+/// Convert a stack base to the corresponding register.
+fn stk_base(base: StackBase) -> RegUnit {
+    match base {
+        StackBase::SP => SP,
+        _ => unimplemented!(),
+    }
+}
+
+/// Encode the adjustment of the stack pointer by a multiple of 4 below 1024 as a rotated
+/// immediate: the 8-bit value is rotated right by 30 bits.
+fn encode_imm_scaled4(imm: i64) -> i64 {
+    debug_assert!((0..1024).contains(&imm) && imm % 4 == 0);
+    (15 << 8) | (imm >> 2)
+}
+
+/// Data-processing instructions with a register operand.
+///
+///   31   27  24     20 19 15 11      3
+///   cond 000 opcode S  Rn Rd 00000000 Rm
+///     28  25     21 20 16 12        4  0
+///
+/// Encoding bits: `opcode | (S << 4)`.
+fn put_dp<CS: CodeSink + ?Sized>(
+    cond: u32,
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+
+    let mut i = cond << 28;
+    i |= (bits & 0xf) << 21;
+    i |= ((bits >> 4) & 1) << 20;
+    i |= rn << 16;
+    i |= rd << 12;
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// Data-processing instructions with an immediate operand.
+///
+///   31   27  24     20 19 15 11
+///   cond 001 opcode S  Rn Rd imm12
+///     28  25     21 20 16 12     0
+///
+/// The 12-bit immediate is a rotation and an 8-bit value. Callers only pass 8-bit values or
+/// pre-rotated immediates.
+///
+/// Encoding bits: `opcode | (S << 4)`.
+fn put_dp_imm<CS: CodeSink + ?Sized>(
+    cond: u32,
+    bits: u16,
+    rn: RegUnit,
+    imm: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert!((0..=0xfff).contains(&imm), "bad imm12 {}", imm);
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+
+    let mut i = (cond << 28) | (1 << 25);
+    i |= (bits & 0xf) << 21;
+    i |= ((bits >> 4) & 1) << 20;
+    i |= rn << 16;
+    i |= rd << 12;
+    i |= imm as u32 & 0xfff;
+
+    sink.put4(i);
+}
+
+/// Shift by an immediate amount, as a `mov` with a shifted register operand.
+///
+///   31   27        20 19   15 11   6    4 3
+///   cond 0001101 S  0000 Rd imm5 type 0 Rm
+///     28      21 20   16 12    7    5    0
+///
+/// The amount is taken modulo 32 like the IR instructions. A zero amount is encoded as `lsl #0`,
+/// since the other shift types would shift by 32.
+///
+/// Encoding bits: `type | (rotl << 2)`, where `rotl` rotates left by using the negated amount.
+fn put_shift_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rm: RegUnit,
+    amount: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+
+    let mut amount = amount as u32 & 31;
+    if bits & 0b100 != 0 {
+        amount = amount.wrapping_neg() & 31;
+    }
+    let shift = if amount == 0 { 0 } else { bits & 0b11 };
+
+    let mut i = 0xe1a0_0000;
+    i |= rd << 12;
+    i |= amount << 7;
+    i |= shift << 5;
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// Shift by a register amount, as a `mov` with a register-shifted register operand.
+///
+///   31   27        20 19   15 11 7 6    4 3
+///   cond 0001101 S  0000 Rd Rs 0 type 1 Rm
+///     28      21 20   16 12  8    5    0
+///
+/// Encoding bits: `type`.
+fn put_shift_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rm: RegUnit,
+    rs: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rm = u32::from(rm) & 0xf;
+    let rs = u32::from(rs) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+
+    let mut i = 0xe1a0_0010;
+    i |= rd << 12;
+    i |= rs << 8;
+    i |= (bits & 0b11) << 5;
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// 32-bit multiplication, `mul rd, rn, rm`.
+fn put_mul<CS: CodeSink + ?Sized>(rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe000_0090 | (rd << 16) | (rm << 8) | rn);
+}
+
+/// 64-bit multiplication, `umull rdlo, rdhi, rn, rm` or `smull`.
+///
+/// Encoding bits: 1 for the signed variant.
+fn put_mull<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rdlo: RegUnit,
+    rdhi: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let rdlo = u32::from(rdlo) & 0xf;
+    let rdhi = u32::from(rdhi) & 0xf;
+
+    let mut i = 0xe080_0090;
+    i |= (bits & 1) << 22;
+    i |= rdhi << 16;
+    i |= rdlo << 12;
+    i |= rm << 8;
+    i |= rn;
+
+    sink.put4(i);
+}
+
+/// Division, `sdiv rd, rn, rm` or `udiv`.
+///
+/// Encoding bits: 1 for the unsigned variant.
+fn put_div<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe710_f010 | ((bits & 1) << 21) | (rd << 16) | (rm << 8) | rn);
+}
+
+/// Multiply and subtract, `mls rd, rn, rm, ra` computing `ra - rn * rm`.
+fn put_mls<CS: CodeSink + ?Sized>(
+    rn: RegUnit,
+    rm: RegUnit,
+    ra: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let ra = u32::from(ra) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe060_0090 | (rd << 16) | (ra << 12) | (rm << 8) | rn);
+}
+
+/// Compare a divisor to zero and branch over the following trap instruction when it isn't.
+fn put_div_check<CS: CodeSink + ?Sized>(rm: RegUnit, sink: &mut CS) {
+    put_dp_imm(AL, DP_CMP, rm, 0, 0, sink);
+    put_b(COND_NE, 8, sink);
+}
+
+/// Check for a `INT_MIN / -1` division, and branch over the following trap instruction when the
+/// operands are anything else.
+fn put_sdiv_overflow_check<CS: CodeSink + ?Sized>(rn: RegUnit, rm: RegUnit, sink: &mut CS) {
+    // cmn rm, #1
+    put_dp_imm(AL, DP_CMN, rm, 1, 0, sink);
+    // cmpeq rn, #0x80000000, which is 2 rotated right by 2.
+    put_dp_imm(COND_EQ, DP_CMP, rn, 0x102, 0, sink);
+    put_b(COND_NE, 8, sink);
+}
+
+/// Permanently undefined instruction, `udf #0`.
+fn put_udf<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xe7f0_00f0);
+}
+
+/// Count leading zeros, `clz rd, rm`.
+fn put_clz<CS: CodeSink + ?Sized>(rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe16f_0f10 | (rd << 12) | rm);
+}
+
+/// Reverse bits, `rbit rd, rm`.
+fn put_rbit<CS: CodeSink + ?Sized>(rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe6ff_0f30 | (rd << 12) | rm);
+}
+
+/// Extensions, `sxtb`, `sxth`, `uxtb` and `uxth`.
+///
+///   31   27    22 19   15 11     3
+///   cond 01101 op 1111 Rd 000111 Rm
+///     28    23 20   16 12      4  0
+///
+/// Encoding bits: `op`.
+fn put_ext<CS: CodeSink + ?Sized>(bits: u16, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rm = u32::from(rm) & 0xf;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe68f_0070 | ((bits & 0b111) << 20) | (rd << 12) | rm);
+}
+
+/// Move a 16-bit immediate, `movw rd, #imm`.
+fn put_movw<CS: CodeSink + ?Sized>(imm: i64, rd: RegUnit, sink: &mut CS) {
+    debug_assert!((0..=0xffff).contains(&imm), "bad imm16 {}", imm);
+    let imm = imm as u32;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe300_0000 | ((imm & 0xf000) << 4) | (rd << 12) | (imm & 0xfff));
+}
+
+/// Move any 32-bit immediate with a `movw` and a `movt`.
+fn put_movw_movt<CS: CodeSink + ?Sized>(imm: i64, rd: RegUnit, sink: &mut CS) {
+    let imm = imm as u32;
+    put_movw(i64::from(imm & 0xffff), rd, sink);
+    let hi = imm >> 16;
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe340_0000 | ((hi & 0xf000) << 4) | (rd << 12) | (hi & 0xfff));
+}
+
+/// Load a literal word placed right after this prefix, `ldr rd, [pc]; b +0`.
+fn put_literal_prefix<CS: CodeSink + ?Sized>(rd: RegUnit, sink: &mut CS) {
+    let rd = u32::from(rd) & 0xf;
+    sink.put4(0xe59f_0000 | (rd << 12));
+    put_b(AL, 8, sink);
+}
+
+/// Word and unsigned byte loads and stores with an immediate offset.
+///
+///   31   27  24 23 22 21 20 19 15 11
+///   cond 010 P  U  B  W  L  Rn Rt imm12
+///     28  25 24 23 22 21 20 16 12     0
+///
+/// Encoding bits: `L | (B << 1)`.
+fn put_ldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i64,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert!(offset > -4096 && offset < 4096, "bad offset {}", offset);
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rt = u32::from(rt) & 0xf;
+    let (up, magnitude) = split_offset(offset);
+
+    let mut i = 0xe500_0000;
+    i |= up << 23;
+    i |= ((bits >> 1) & 1) << 22;
+    i |= (bits & 1) << 20;
+    i |= rn << 16;
+    i |= rt << 12;
+    i |= magnitude & 0xfff;
+
+    sink.put4(i);
+}
+
+/// Word and unsigned byte loads and stores with a register offset, `ldr rt, [rn, rm]`.
+///
+/// Encoding bits: `L | (B << 1)`.
+fn put_ldst_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let rt = u32::from(rt) & 0xf;
+
+    let mut i = 0xe780_0000;
+    i |= ((bits >> 1) & 1) << 22;
+    i |= (bits & 1) << 20;
+    i |= rn << 16;
+    i |= rt << 12;
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// Halfword and signed byte loads and stores with an immediate offset.
+///
+///   31   27  24 23 22 21 20 19 15 11    7 6 5 4 3
+///   cond 000 P  U  1  W  L  Rn Rt imm4H 1 S H 1 imm4L
+///     28  25 24 23 22 21 20 16 12     8           0
+///
+/// Encoding bits: `L | (S << 1) | (H << 2)`.
+fn put_ldst_misc<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i64,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert!(offset > -256 && offset < 256, "bad offset {}", offset);
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rt = u32::from(rt) & 0xf;
+    let (up, magnitude) = split_offset(offset);
+    let imm = magnitude & 0xff;
+
+    let mut i = 0xe140_0090;
+    i |= up << 23;
+    i |= (bits & 1) << 20;
+    i |= rn << 16;
+    i |= rt << 12;
+    i |= (imm >> 4) << 8;
+    i |= ((bits >> 1) & 1) << 6;
+    i |= ((bits >> 2) & 1) << 5;
+    i |= imm & 0xf;
+
+    sink.put4(i);
+}
+
+/// Halfword and signed byte loads and stores with a register offset, `ldrh rt, [rn, rm]`.
+///
+/// Encoding bits: `L | (S << 1) | (H << 2)`.
+fn put_ldst_misc_reg<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let rm = u32::from(rm) & 0xf;
+    let rt = u32::from(rt) & 0xf;
+
+    let mut i = 0xe180_0090;
+    i |= (bits & 1) << 20;
+    i |= rn << 16;
+    i |= rt << 12;
+    i |= ((bits >> 1) & 1) << 6;
+    i |= ((bits >> 2) & 1) << 5;
+    i |= rm;
+
+    sink.put4(i);
+}
+
+/// Push a register, `str rt, [sp, #-4]!`.
+fn put_push<CS: CodeSink + ?Sized>(rt: RegUnit, sink: &mut CS) {
+    let rt = u32::from(rt) & 0xf;
+    sink.put4(0xe52d_0004 | (rt << 12));
+}
+
+/// Pop a register, `ldr rt, [sp], #4`.
+fn put_pop<CS: CodeSink + ?Sized>(rt: RegUnit, sink: &mut CS) {
+    let rt = u32::from(rt) & 0xf;
+    sink.put4(0xe49d_0004 | (rt << 12));
+}
+
+/// Conditional branch.
+///
+///   31   27  24 23
+///   cond 101 0  imm24
+///     28  25 24     0
+///
+/// The displacement `disp` is relative to the branch instruction. The A32 `pc` reads as the
+/// address of the current instruction plus 8.
+fn put_b<CS: CodeSink + ?Sized>(cond: u32, disp: i64, sink: &mut CS) {
+    let disp = disp - 8;
+    debug_assert!(disp % 4 == 0, "unaligned branch displacement {}", disp);
+    let imm24 = (disp >> 2) as u32 & 0xff_ffff;
+    sink.put4((cond << 28) | 0x0a00_0000 | imm24);
+}
+
+/// Indirect call, `blx rm`.
+fn put_blx<CS: CodeSink + ?Sized>(rm: RegUnit, sink: &mut CS) {
+    let rm = u32::from(rm) & 0xf;
+    sink.put4(0xe12f_ff30 | rm);
+}
+
+/// Split a memory offset into the U bit, set when the offset is added to the base register, and
+/// the magnitude of the offset.
+fn split_offset(offset: i64) -> (u32, u32) {
+    if offset >= 0 {
+        (1, offset as u32)
+    } else {
+        (0, (-offset) as u32)
+    }
+}
+
+/// Split a VFP register unit into the 4-bit field and the extra bit used in instructions.
+///
+/// S registers put the extra bit at the bottom of the register number, while D registers put it
+/// at the top.
+fn vfp_reg(reg: RegUnit, double: bool) -> (u32, u32) {
+    let reg = u32::from(reg);
+    if double {
+        let d = reg / 2;
+        (d & 0xf, d >> 4)
+    } else {
+        (reg >> 1, reg & 1)
+    }
+}
+
+/// VFP binary arithmetic.
+///
+///   31   27   23 22 21 19 15 11  8  7 6  5 4 3
+///   cond 1110 o1 D  o1 Vn Vd 101 sz N op M 0 Vm
+///     28   24 23 22 20 16 12   9  8 7 6  5    0
+///
+/// Encoding bits: `opc1 | (op << 3) | (sz << 4)`, where `opc1` is made of bits 23, 21 and 20.
+fn put_vfp<CS: CodeSink + ?Sized>(bits: u16, vn: RegUnit, vm: RegUnit, vd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let double = bits & 0x10 != 0;
+    let (vn, n) = vfp_reg(vn, double);
+    let (vm, m) = vfp_reg(vm, double);
+    let (vd, d) = vfp_reg(vd, double);
+
+    let mut i = 0xee00_0a00;
+    i |= ((bits >> 2) & 1) << 23;
+    i |= d << 22;
+    i |= (bits & 0b11) << 20;
+    i |= vn << 16;
+    i |= vd << 12;
+    i |= ((bits >> 4) & 1) << 8;
+    i |= n << 7;
+    i |= ((bits >> 3) & 1) << 6;
+    i |= m << 5;
+    i |= vm;
+
+    sink.put4(i);
+}
+
+/// VFP unary arithmetic, comparisons and conversions.
+///
+///   31   27    23 22 21 19   15 11  8  7  6 5 4 3
+///   cond 11101 D  11 opc2 Vd 101 sz op 1 M 0 Vm
+///     28    23 22 20   16 12   9  8  7  6 5    0
+///
+/// Encoding bits: `opc2 | (op << 4) | (sz << 5) | (Dd << 6) | (Dm << 7)`, where `Dd` and `Dm`
+/// tell if the `Vd` and `Vm` operands are double precision registers.
+fn put_vfp_unary<CS: CodeSink + ?Sized>(bits: u16, vm: RegUnit, vd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let (vm, m) = vfp_reg(vm, bits & 0x80 != 0);
+    let (vd, d) = vfp_reg(vd, bits & 0x40 != 0);
+
+    let mut i = 0xeeb0_0a40;
+    i |= d << 22;
+    i |= (bits & 0xf) << 16;
+    i |= vd << 12;
+    i |= ((bits >> 5) & 1) << 8;
+    i |= ((bits >> 4) & 1) << 7;
+    i |= m << 5;
+    i |= vm;
+
+    sink.put4(i);
+}
+
+/// VFP loads and stores, `vldr` and `vstr`.
+///
+///   31   27   23 22 21 20 19 15 11  8  7
+///   cond 1101 U  D  0  L  Rn Vd 101 sz imm8
+///     28   24 23 22 21 20 16 12   9  8    0
+///
+/// Encoding bits: `L | (sz << 1)`.
+fn put_vldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i64,
+    vd: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert!(
+        offset > -1024 && offset < 1024 && offset % 4 == 0,
+        "bad offset {}",
+        offset
+    );
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0xf;
+    let (vd, d) = vfp_reg(vd, bits & 0b10 != 0);
+    let (up, magnitude) = split_offset(offset);
+
+    let mut i = 0xed00_0a00;
+    i |= up << 23;
+    i |= d << 22;
+    i |= (bits & 1) << 20;
+    i |= rn << 16;
+    i |= vd << 12;
+    i |= ((bits >> 1) & 1) << 8;
+    i |= (magnitude >> 2) & 0xff;
+
+    sink.put4(i);
+}
+
+/// Move between a GPR and a single precision register, `vmov sn, rt` or `vmov rt, sn`.
+///
+/// Encoding bits: `op`, which is 1 for moves to the GPR.
+fn put_vmov_core<CS: CodeSink + ?Sized>(bits: u16, rt: RegUnit, sn: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rt = u32::from(rt) & 0xf;
+    let (vn, n) = vfp_reg(sn, false);
+    sink.put4(0xee00_0a10 | ((bits & 1) << 20) | (vn << 16) | (rt << 12) | (n << 7));
+}
@@ -1,10 +1,17 @@
 //! Encoding tables for ARM32 ISA.
 
+use super::registers::*;
 use crate::ir;
 use crate::isa;
 use crate::isa::constraints::*;
 use crate::isa::enc_tables::*;
-use crate::isa::encoding::RecipeSizing;
+use crate::isa::encoding::{base_size, RecipeSizing};
+use crate::predicates;
 
+// Include the generated encoding tables:
+// - `LEVEL1_A32`
+// - `LEVEL2`
+// - `ENCLIST`
+// - `INFO`
 include!(concat!(env!("OUT_DIR"), "/encoding-arm32.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-arm32.rs"));
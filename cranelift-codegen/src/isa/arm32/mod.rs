@@ -8,22 +8,31 @@ pub mod settings;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
-use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::CodeOffset;
+use crate::binemit::{
+    emit_function, emit_split_function, BufferCodeSink, CodeSink, MemoryCodeSink, Reloc,
+    SplitCodeInfo,
+};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{EncInfo, LibCallRegistry, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
+use crate::result::CodegenResult;
+use crate::timing;
 use core::fmt;
 use std::boxed::Box;
 use target_lexicon::{Architecture, Triple};
 
+/// The relocations emitted by the ARM32 encoding recipes.
+const RELOCS: &[Reloc] = &[Reloc::Abs4, Reloc::Arm32Call];
+
 #[allow(dead_code)]
 struct Isa {
     triple: Triple,
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    libcalls: LibCallRegistry,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
 }
 
@@ -32,6 +41,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        libcalls: LibCallRegistry::new(),
         constructor: isa_constructor,
     }
 }
@@ -40,11 +50,9 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    libcalls: LibCallRegistry,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.architecture {
-        Architecture::Thumbv6m | Architecture::Thumbv7em | Architecture::Thumbv7m => {
-            &enc_tables::LEVEL1_T32[..]
-        }
         Architecture::Arm
         | Architecture::Armv4t
         | Architecture::Armv5te
@@ -56,6 +64,7 @@ fn isa_constructor(
         triple,
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        libcalls,
         cpumode: level1,
     })
 }
@@ -73,6 +82,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn libcalls(&self) -> &LibCallRegistry {
+        &self.libcalls
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -113,6 +126,11 @@ impl TargetIsa for Isa {
         abi::allocatable_registers(func)
     }
 
+    fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
+        let _tt = timing::prologue_epilogue();
+        abi::prologue_epilogue(func, self)
+    }
+
     #[cfg(feature = "testing_hooks")]
     fn emit_inst(
         &self,
@@ -124,8 +142,38 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    #[cfg(feature = "testing_hooks")]
+    fn emit_padding(&self, size: CodeOffset, sink: &mut dyn CodeSink) {
+        binemit::emit_padding(size, sink)
+    }
+
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn emit_function_to_buffer(&self, func: &ir::Function, sink: &mut BufferCodeSink) {
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn supported_relocs(&self) -> &'static [Reloc] {
+        RELOCS
+    }
+
+    fn emit_split_function(
+        &self,
+        func: &ir::Function,
+        info: &SplitCodeInfo,
+        hot: &mut dyn CodeSink,
+        cold: &mut dyn CodeSink,
+    ) {
+        emit_split_function(
+            func,
+            info,
+            binemit::emit_inst,
+            binemit::emit_padding,
+            hot,
+            cold,
+        )
     }
 }
 
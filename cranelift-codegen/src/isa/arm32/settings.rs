@@ -7,3 +7,39 @@ use core::fmt;
 // public `Flags` struct with an impl for all of the settings defined in
 // `cranelift-codegen/meta/src/isa/arm32/mod.rs`.
 include!(concat!(env!("OUT_DIR"), "/settings-arm32.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::{builder, Flags};
+    use crate::settings::{self, Configurable};
+    use std::string::ToString;
+
+    #[test]
+    fn display_default() {
+        let shared = settings::Flags::new(settings::builder());
+        let b = builder();
+        let f = Flags::new(&shared, b);
+        assert_eq!(f.to_string(), "[arm32]\nhas_vfp = false\nhas_div = false\n");
+        assert!(!f.use_vfp());
+        assert!(!f.use_div());
+    }
+
+    #[test]
+    fn predicates() {
+        let shared = settings::Flags::new(settings::builder());
+        let mut b = builder();
+        b.enable("has_vfp").unwrap();
+        b.enable("has_div").unwrap();
+        let f = Flags::new(&shared, b);
+        assert!(f.use_vfp());
+        assert!(f.use_div());
+
+        let mut sb = settings::builder();
+        sb.set("enable_float", "false").unwrap();
+        let shared = settings::Flags::new(sb);
+        let mut b = builder();
+        b.enable("has_vfp").unwrap();
+        let f = Flags::new(&shared, b);
+        assert!(!f.use_vfp());
+    }
+}
@@ -1,7 +1,20 @@
 //! Emitting binary ARM64 machine code.
 
-use crate::binemit::{bad_encoding, CodeSink};
+use crate::binemit::{bad_encoding, CodeOffset, CodeSink};
 use crate::ir::{Function, Inst};
 use crate::regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm64.rs"));
+
+/// Emit `size` bytes of no-op instructions, using `nop`.
+pub fn emit_padding<CS: CodeSink + ?Sized>(size: CodeOffset, sink: &mut CS) {
+    debug_assert_eq!(
+        size % 4,
+        0,
+        "{} bytes of padding is not a whole instruction",
+        size
+    );
+    for _ in 0..size / 4 {
+        sink.put4(0xd503_201f);
+    }
+}
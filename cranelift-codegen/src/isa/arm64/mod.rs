@@ -8,12 +8,15 @@ pub mod settings;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
-use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::CodeOffset;
+use crate::binemit::{
+    emit_function, emit_split_function, BufferCodeSink, CodeSink, MemoryCodeSink, Reloc,
+    SplitCodeInfo,
+};
 use crate::ir;
 use crate::isa::enc_tables::{lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{EncInfo, LibCallRegistry, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use core::fmt;
 use std::boxed::Box;
@@ -24,6 +27,7 @@ struct Isa {
     triple: Triple,
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    libcalls: LibCallRegistry,
 }
 
 /// Get an ISA builder for creating ARM64 targets.
@@ -31,6 +35,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        libcalls: LibCallRegistry::new(),
         constructor: isa_constructor,
     }
 }
@@ -39,11 +44,13 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    libcalls: LibCallRegistry,
 ) -> Box<dyn TargetIsa> {
     Box::new(Isa {
         triple,
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        libcalls,
     })
 }
 
@@ -60,6 +67,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn libcalls(&self) -> &LibCallRegistry {
+        &self.libcalls
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -111,8 +122,39 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    #[cfg(feature = "testing_hooks")]
+    fn emit_padding(&self, size: CodeOffset, sink: &mut dyn CodeSink) {
+        binemit::emit_padding(size, sink)
+    }
+
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn emit_function_to_buffer(&self, func: &ir::Function, sink: &mut BufferCodeSink) {
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn supported_relocs(&self) -> &'static [Reloc] {
+        // No encoding recipe emits relocations yet.
+        &[]
+    }
+
+    fn emit_split_function(
+        &self,
+        func: &ir::Function,
+        info: &SplitCodeInfo,
+        hot: &mut dyn CodeSink,
+        cold: &mut dyn CodeSink,
+    ) {
+        emit_split_function(
+            func,
+            info,
+            binemit::emit_inst,
+            binemit::emit_padding,
+            hot,
+            cold,
+        )
     }
 }
 
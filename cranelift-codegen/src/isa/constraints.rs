@@ -175,7 +175,8 @@ impl BranchRange {
     /// `branch` is the code offset of the branch instruction itself and `dest` is the code offset
     /// of the destination EBB header.
     ///
-    /// This method does not detect if the range is larger than 2 GB.
+    /// This method does not detect if the range is larger than 2 GB, but `relax_branches()` never
+    /// produces offsets larger than `binemit::MAX_CODE_SIZE`.
     pub fn contains(self, branch: CodeOffset, dest: CodeOffset) -> bool {
         let d = dest.wrapping_sub(branch + CodeOffset::from(self.origin)) as i32;
         let s = 32 - self.bits;
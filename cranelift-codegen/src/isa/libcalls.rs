@@ -0,0 +1,128 @@
+//! Customization of runtime library calls.
+//!
+//! The legalizer expands the instructions that the target can't implement, like `ceil` without
+//! SSE 4.1 or `table_grow`, as calls to runtime library routines. By default, the called
+//! functions are named by `ExternalName::LibCall` and their signature is derived from the
+//! instruction operands.
+//!
+//! A `LibCallRegistry` attached to an `isa::Builder` lets embedders change this:
+//!
+//! - Any `LibCall` can be redirected to another function name, optionally with a different
+//!   signature, for runtimes that provide their own soft-float or memory routines.
+//! - Instructions without a well-known `LibCall` can be expanded as calls to a function provided
+//!   by the embedder, when the target has no encoding for them.
+
+use crate::ir::{ExternalName, LibCall, Opcode, Signature, Type};
+use crate::HashMap;
+
+/// The function called for a library call.
+#[derive(Clone, Debug)]
+pub struct LibCallTarget {
+    /// The name of the called function.
+    pub name: ExternalName,
+
+    /// The signature of the called function, before legalization. When `None`, the signature is
+    /// derived from the arguments and results of the expanded instruction.
+    pub signature: Option<Signature>,
+}
+
+impl LibCallTarget {
+    /// Create a target calling `name` with the signature of the expanded instruction.
+    pub fn new(name: ExternalName) -> Self {
+        Self {
+            name,
+            signature: None,
+        }
+    }
+
+    /// Create a target calling `name` with an explicit `signature`.
+    pub fn with_signature(name: ExternalName, signature: Signature) -> Self {
+        Self {
+            name,
+            signature: Some(signature),
+        }
+    }
+}
+
+/// Library calls configured for a target ISA.
+///
+/// The registry is empty by default, which makes the legalizer use the well-known `LibCall`
+/// names.
+#[derive(Clone, Debug, Default)]
+pub struct LibCallRegistry {
+    libcalls: HashMap<LibCall, LibCallTarget>,
+    insts: HashMap<(Opcode, Type), LibCallTarget>,
+}
+
+impl LibCallRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `target` instead of the well-known `libcall`.
+    pub fn set_libcall(&mut self, libcall: LibCall, target: LibCallTarget) {
+        self.libcalls.insert(libcall, target);
+    }
+
+    /// Expand the `opcode` instructions with the controlling type `ctrl_type` as calls to
+    /// `target` when the target ISA can't encode them.
+    ///
+    /// This takes precedence over the well-known `LibCall` for the instruction, if there is one.
+    pub fn set_inst_libcall(&mut self, opcode: Opcode, ctrl_type: Type, target: LibCallTarget) {
+        self.insts.insert((opcode, ctrl_type), target);
+    }
+
+    /// Get the function called instead of the well-known `libcall`, if any.
+    pub fn libcall(&self, libcall: LibCall) -> Option<&LibCallTarget> {
+        self.libcalls.get(&libcall)
+    }
+
+    /// Get the function called for the `opcode` instructions with the controlling type
+    /// `ctrl_type`, if any.
+    pub fn inst_libcall(&self, opcode: Opcode, ctrl_type: Type) -> Option<&LibCallTarget> {
+        self.insts.get(&(opcode, ctrl_type))
+    }
+
+    /// Get the name and signature to use for `libcall`.
+    ///
+    /// This is the registered target, or the well-known name with no signature.
+    pub fn resolve(&self, libcall: LibCall) -> LibCallTarget {
+        self.libcall(libcall)
+            .cloned()
+            .unwrap_or_else(|| LibCallTarget::new(ExternalName::LibCall(libcall)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::types;
+
+    #[test]
+    fn resolve() {
+        let mut registry = LibCallRegistry::new();
+        assert_eq!(
+            registry.resolve(LibCall::Memcpy).name,
+            ExternalName::LibCall(LibCall::Memcpy)
+        );
+
+        registry.set_libcall(
+            LibCall::Memcpy,
+            LibCallTarget::new(ExternalName::testcase("my_memcpy")),
+        );
+        assert_eq!(
+            registry.resolve(LibCall::Memcpy).name,
+            ExternalName::testcase("my_memcpy")
+        );
+        assert!(registry.libcall(LibCall::Memset).is_none());
+
+        registry.set_inst_libcall(
+            Opcode::Fadd,
+            types::F32,
+            LibCallTarget::new(ExternalName::testcase("__addsf3")),
+        );
+        assert!(registry.inst_libcall(Opcode::Fadd, types::F32).is_some());
+        assert!(registry.inst_libcall(Opcode::Fadd, types::F64).is_none());
+    }
+}
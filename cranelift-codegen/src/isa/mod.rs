@@ -45,12 +45,19 @@
 //!
 //! The configured target ISA trait object is a `Box<TargetIsa>` which can be used for multiple
 //! concurrent function compilations.
+//!
+//! ISAs that are not part of this crate can be added at runtime with `isa::register_plugin()`,
+//! after which `isa::lookup()` will find them. See the `plugin` module for the interface a
+//! backend crate must implement.
 
+pub use crate::isa::addr_mode::{match_address, AddressMode, AddressModes};
 pub use crate::isa::call_conv::CallConv;
 pub use crate::isa::constraints::{
     BranchRange, ConstraintKind, OperandConstraint, RecipeConstraints,
 };
 pub use crate::isa::encoding::{base_size, EncInfo, Encoding};
+pub use crate::isa::libcalls::{LibCallRegistry, LibCallTarget};
+pub use crate::isa::plugin::{register_plugin, RegisterError, TargetIsaPlugin, ISA_PLUGIN_VERSION};
 pub use crate::isa::registers::{regs_overlap, RegClass, RegClassIndex, RegInfo, RegUnit};
 pub use crate::isa::stack::{StackBase, StackBaseMask, StackRef};
 
@@ -80,10 +87,13 @@ mod arm32;
 #[cfg(feature = "arm64")]
 mod arm64;
 
+mod addr_mode;
 mod call_conv;
 mod constraints;
 mod enc_tables;
 mod encoding;
+mod libcalls;
+pub mod plugin;
 pub mod registers;
 mod stack;
 
@@ -105,16 +115,20 @@ macro_rules! isa_builder {
 
 /// Look for a supported ISA with the given `name`.
 /// Return a builder that can create a corresponding `TargetIsa`.
+///
+/// Registered plugins are consulted when the ISA is not built into this crate.
 pub fn lookup(triple: Triple) -> Result<Builder, LookupError> {
+    lookup_builtin(triple.clone()).or_else(|err| plugin::lookup(triple).ok_or(err))
+}
+
+/// Look for an ISA that is built into this crate.
+fn lookup_builtin(triple: Triple) -> Result<Builder, LookupError> {
     match triple.architecture {
         Architecture::Riscv32 | Architecture::Riscv64 => isa_builder!(riscv, "riscv")(triple),
         Architecture::I386 | Architecture::I586 | Architecture::I686 | Architecture::X86_64 => {
             isa_builder!(x86, "x86")(triple)
         }
-        Architecture::Thumbv6m
-        | Architecture::Thumbv7em
-        | Architecture::Thumbv7m
-        | Architecture::Arm
+        Architecture::Arm
         | Architecture::Armv4t
         | Architecture::Armv5te
         | Architecture::Armv7
@@ -141,14 +155,26 @@ pub enum LookupError {
 pub struct Builder {
     triple: Triple,
     setup: settings::Builder,
-    constructor: fn(Triple, settings::Flags, settings::Builder) -> Box<dyn TargetIsa>,
+    libcalls: LibCallRegistry,
+    constructor:
+        fn(Triple, settings::Flags, settings::Builder, LibCallRegistry) -> Box<dyn TargetIsa>,
 }
 
 impl Builder {
     /// Combine the ISA-specific settings with the provided ISA-independent settings and allocate a
     /// fully configured `TargetIsa` trait object.
     pub fn finish(self, shared_flags: settings::Flags) -> Box<dyn TargetIsa> {
-        (self.constructor)(self.triple, shared_flags, self.setup)
+        (self.constructor)(self.triple, shared_flags, self.setup, self.libcalls)
+    }
+
+    /// Get the library calls used by the legalizer for the ISA, to customize them.
+    pub fn libcalls_mut(&mut self) -> &mut LibCallRegistry {
+        &mut self.libcalls
+    }
+
+    /// Iterate over the ISA-specific settings and presets.
+    pub fn iter(&self) -> impl Iterator<Item = settings::Setting> {
+        self.setup.iter()
     }
 }
 
@@ -199,7 +225,9 @@ impl TargetFrontendConfig {
 
 /// Methods that are specialized to a target ISA. Implies a Display trait that shows the
 /// shared flags, as well as any isa-specific flags.
-pub trait TargetIsa: fmt::Display + Sync {
+///
+/// An ISA can be shared between threads, for example by `batch::compile_batch()`.
+pub trait TargetIsa: fmt::Display + Send + Sync {
     /// Get the name of this ISA.
     fn name(&self) -> &'static str;
 
@@ -209,6 +237,9 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// Get the ISA-independent flags that were used to make this trait object.
     fn flags(&self) -> &settings::Flags;
 
+    /// Get the library calls that the legalizer uses for instructions this ISA can't encode.
+    fn libcalls(&self) -> &LibCallRegistry;
+
     /// Get the default calling convention of this target.
     fn default_call_conv(&self) -> CallConv {
         CallConv::triple_default(self.triple())
@@ -247,9 +278,22 @@ pub trait TargetIsa: fmt::Display + Sync {
         false
     }
 
+    /// Can the CPU's arithmetic and comparison instructions read one operand from memory?
+    fn uses_memory_operands(&self) -> bool {
+        false
+    }
+
+    /// Get the `base + index * scale + disp` addressing modes implemented by the CPU.
+    fn address_modes(&self) -> AddressModes {
+        AddressModes {
+            max_scale: 0,
+            disp_bits: 32,
+        }
+    }
+
     /// Does the CPU implement multi-register addressing?
     fn uses_complex_addresses(&self) -> bool {
-        false
+        self.address_modes().max_scale > 0
     }
 
     /// Get a data structure describing the registers in this ISA.
@@ -279,6 +323,23 @@ pub trait TargetIsa: fmt::Display + Sync {
         iter.next().ok_or_else(|| iter.legalize())
     }
 
+    /// Can `inst` be encoded directly in this ISA without legalization?
+    ///
+    /// This only consults the encoding tables and predicates, so it can be used to choose between
+    /// different IR forms of a computation before inserting any of them into `func`. The
+    /// instruction data doesn't need to be part of `func`, but its value operands must be defined
+    /// there since some predicates depend on their types.
+    fn can_encode(
+        &self,
+        func: &ir::Function,
+        inst: &ir::InstructionData,
+        ctrl_typevar: ir::Type,
+    ) -> bool {
+        self.legal_encodings(func, inst, ctrl_typevar)
+            .next()
+            .is_some()
+    }
+
     /// Get a data structure describing the instruction encodings in this ISA.
     fn encoding_info(&self) -> EncInfo;
 
@@ -370,6 +431,63 @@ pub trait TargetIsa: fmt::Display + Sync {
         sink: &mut dyn binemit::CodeSink,
     );
 
+    /// Emit `size` bytes of no-op instructions into the `sink` trait object.
+    ///
+    /// This is used to fill the gaps in front of EBBs aligned by branch relaxation. It is under
+    /// the "testing_hooks" feature for the same reasons as `emit_inst()`.
+    #[cfg(feature = "testing_hooks")]
+    fn emit_padding(&self, size: binemit::CodeOffset, sink: &mut dyn binemit::CodeSink);
+
     /// Emit a whole function into memory.
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut binemit::MemoryCodeSink);
+
+    /// Emit a whole function into the buffer of a `BufferCodeSink`.
+    fn emit_function_to_buffer(&self, func: &ir::Function, sink: &mut binemit::BufferCodeSink);
+
+    /// Emit a whole function laid out by `binemit::relax_split_branches()`, with the hot code and
+    /// jump tables going to `hot` and the cold EBBs going to `cold`.
+    ///
+    /// Both sinks must be empty. The branches between them are described by `info.fixups`.
+    fn emit_split_function(
+        &self,
+        func: &ir::Function,
+        info: &binemit::SplitCodeInfo,
+        hot: &mut dyn binemit::CodeSink,
+        cold: &mut dyn binemit::CodeSink,
+    );
+
+    /// Fill `buf` with the padding placed between functions, as selected by the `code_padding`
+    /// setting.
+    ///
+    /// Embedders that lay out several functions in the same code region should call this for the
+    /// gaps between them. The default implementation fills the buffer with zero bytes.
+    fn fill_function_padding(&self, buf: &mut [u8]) {
+        for byte in buf {
+            *byte = 0;
+        }
+    }
+
+    /// Get the relocation kinds that code generated for this ISA can contain.
+    ///
+    /// Embedders can check these against the relocations supported by their object file format or
+    /// loader, instead of finding an unsupported relocation in the emitted code. The list doesn't
+    /// depend on the settings, so it includes relocations that are only used with some of them,
+    /// like `is_pic` or `tls_model`.
+    fn supported_relocs(&self) -> &'static [binemit::Reloc];
+
+    /// Can code generated for this ISA contain the relocation `reloc`?
+    fn can_emit_reloc(&self, reloc: binemit::Reloc) -> bool {
+        self.supported_relocs().contains(&reloc)
+    }
+
+    /// Get the relocation that will be used to materialize the callee address of the `func_addr`
+    /// instruction `inst`.
+    ///
+    /// The choice depends on the settings, like `is_pic`, and on whether the callee is colocated.
+    /// Some relocations only reach a limited distance, so embedders can use this to decide where
+    /// the callee must be placed. Returns `None` if `inst` is not a `func_addr` instruction that
+    /// can be encoded, or the ISA doesn't provide this information.
+    fn func_addr_reloc(&self, _func: &ir::Function, _inst: ir::Inst) -> Option<binemit::Reloc> {
+        None
+    }
 }
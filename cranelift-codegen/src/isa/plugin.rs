@@ -0,0 +1,182 @@
+//! Out-of-tree ISA backends.
+//!
+//! A crate outside this repository can provide a target ISA by describing it with a static
+//! `TargetIsaPlugin` and calling `isa::register_plugin()` before looking up targets. Once
+//! registered, a plugin is consulted by `isa::lookup()` for any triple that isn't handled by the
+//! ISAs built into this crate. Plugins stay registered for the lifetime of the process.
+//!
+//! # Plugin interface
+//!
+//! A plugin consists of:
+//!
+//! - A `matches` function that decides which target triples the plugin handles.
+//! - A settings schema in the form of a `settings::detail::Template`. This is normally generated
+//!   by the meta crate, just like the settings of the built-in ISAs. The `isa::Builder` returned
+//!   by `isa::lookup()` is configured against this template.
+//! - A factory function that creates the `TargetIsa` trait object from the triple, the shared
+//!   flags, the ISA-specific settings builder, and the library calls configured on the builder.
+//!
+//! The interface is versioned by `ISA_PLUGIN_VERSION`, which is incremented whenever the
+//! `TargetIsaPlugin` struct or the meaning of its fields changes. Plugins must set the `version`
+//! field to the value of the constant they were compiled against, and registering a plugin with a
+//! different version fails. The `TargetIsa` trait itself is not covered by the version, so a
+//! plugin must be built against the same release of `cranelift-codegen` as the code using it. The
+//! exception is its `Send` and `Sync` bounds, which decide the ISAs a plugin can provide at all:
+//! version 3 added the `Send` bound, so an ISA can be shared with the worker threads of
+//! `batch::compile_batch()`.
+
+use crate::isa::{Builder, LibCallRegistry, TargetIsa};
+use crate::settings;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+use failure_derive::Fail;
+use std::boxed::Box;
+use target_lexicon::Triple;
+
+/// The version of the plugin interface implemented by this crate.
+pub const ISA_PLUGIN_VERSION: u32 = 3;
+
+/// Description of a target ISA provided by another crate.
+pub struct TargetIsaPlugin {
+    /// The version of the plugin interface, which must be `ISA_PLUGIN_VERSION`.
+    pub version: u32,
+
+    /// Name of the ISA.
+    pub name: &'static str,
+
+    /// Does this plugin handle the given target triple?
+    pub matches: fn(&Triple) -> bool,
+
+    /// The ISA-specific settings.
+    pub settings: &'static settings::detail::Template,
+
+    /// Create the `TargetIsa` trait object, which must be `Send` and `Sync`.
+    ///
+    /// This receives the triple passed to `isa::lookup()`, the shared flags passed to
+    /// `isa::Builder::finish()`, a settings builder using the `settings` template, and the
+    /// registry to return from `TargetIsa::libcalls()`.
+    pub constructor:
+        fn(Triple, settings::Flags, settings::Builder, LibCallRegistry) -> Box<dyn TargetIsa>,
+}
+
+/// Reason for failing to register a plugin.
+#[derive(Fail, PartialEq, Eq, Copy, Clone, Debug)]
+pub enum RegisterError {
+    /// The plugin was built for a different version of the plugin interface.
+    #[fail(display = "ISA plugin implements interface version {}", _0)]
+    IncompatibleVersion(u32),
+}
+
+/// A node in the list of registered plugins.
+struct Node {
+    plugin: &'static TargetIsaPlugin,
+    next: *mut Node,
+}
+
+/// The most recently registered plugin. Nodes are never freed.
+static PLUGINS: AtomicPtr<Node> = AtomicPtr::new(ptr::null_mut());
+
+/// Register an ISA plugin so `isa::lookup()` can find it.
+///
+/// When more than one registered plugin matches a triple, the most recently registered plugin is
+/// used.
+pub fn register_plugin(plugin: &'static TargetIsaPlugin) -> Result<(), RegisterError> {
+    if plugin.version != ISA_PLUGIN_VERSION {
+        return Err(RegisterError::IncompatibleVersion(plugin.version));
+    }
+
+    let node = Box::into_raw(Box::new(Node {
+        plugin,
+        next: PLUGINS.load(Ordering::Acquire),
+    }));
+    loop {
+        // Safe because `node` is not visible to other threads until the exchange succeeds.
+        let next = unsafe { (*node).next };
+        match PLUGINS.compare_exchange_weak(next, node, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => return Ok(()),
+            Err(head) => unsafe { (*node).next = head },
+        }
+    }
+}
+
+/// Find a registered plugin for `triple` and return a builder for it.
+pub(super) fn lookup(triple: Triple) -> Option<Builder> {
+    let mut node = PLUGINS.load(Ordering::Acquire);
+    while !node.is_null() {
+        // Safe because registered nodes are never modified or freed.
+        let Node { plugin, next } = unsafe { &*node };
+        if (plugin.matches)(&triple) {
+            return Some(Builder {
+                triple,
+                setup: settings::Builder::new(plugin.settings),
+                libcalls: LibCallRegistry::new(),
+                constructor: plugin.constructor,
+            });
+        }
+        node = *next;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isa::{self, LookupError};
+    use crate::settings::detail::Template;
+    use core::str::FromStr;
+    use target_lexicon::{triple, Architecture};
+
+    static TEMPLATE: Template = Template {
+        name: "test",
+        descriptors: &[],
+        enumerators: &[],
+        hash_table: &[],
+        defaults: &[],
+        presets: &[],
+    };
+
+    fn is_powerpc(triple: &Triple) -> bool {
+        triple.architecture == Architecture::Powerpc
+    }
+
+    fn constructor(
+        _: Triple,
+        _: settings::Flags,
+        _: settings::Builder,
+        _: LibCallRegistry,
+    ) -> Box<dyn TargetIsa> {
+        panic!("Not used in this test");
+    }
+
+    static POWERPC: TargetIsaPlugin = TargetIsaPlugin {
+        version: ISA_PLUGIN_VERSION,
+        name: "powerpc",
+        matches: is_powerpc,
+        settings: &TEMPLATE,
+        constructor,
+    };
+
+    static OLD_POWERPC: TargetIsaPlugin = TargetIsaPlugin {
+        version: 0,
+        name: "powerpc",
+        matches: is_powerpc,
+        settings: &TEMPLATE,
+        constructor,
+    };
+
+    #[test]
+    fn register() {
+        assert_eq!(
+            register_plugin(&OLD_POWERPC),
+            Err(RegisterError::IncompatibleVersion(0))
+        );
+        assert!(isa::lookup(triple!("powerpc")).is_err());
+
+        assert_eq!(register_plugin(&POWERPC), Ok(()));
+        assert!(isa::lookup(triple!("powerpc")).is_ok());
+        assert_eq!(
+            isa::lookup(triple!("sparc")).err(),
+            Some(LookupError::Unsupported)
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! Emitting binary RISC-V machine code.
 
-use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::binemit::{bad_encoding, CodeOffset, CodeSink, Reloc};
 use crate::ir::{Function, Inst, InstructionData};
 use crate::isa::{RegUnit, StackBaseMask, StackRef};
 use crate::predicates::is_signed_int;
@@ -9,6 +9,25 @@ use core::u32;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-riscv.rs"));
 
+/// Emit `size` bytes of no-op instructions, using `addi x0, x0, 0`.
+///
+/// Code is only misaligned by 2 bytes when compressed instructions are used, so a `c.nop` can fill
+/// the gap.
+pub fn emit_padding<CS: CodeSink + ?Sized>(size: CodeOffset, sink: &mut CS) {
+    debug_assert_eq!(
+        size % 2,
+        0,
+        "{} bytes of padding is not a whole instruction",
+        size
+    );
+    if size % 4 != 0 {
+        sink.put2(0x0001);
+    }
+    for _ in 0..size / 4 {
+        sink.put4(0x0000_0013);
+    }
+}
+
 /// R-type instructions.
 ///
 ///   31     24  19  14     11 6
@@ -98,6 +117,33 @@ fn put_i<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rd: RegUnit,
     sink.put4(i);
 }
 
+/// S-type store instructions.
+///
+///   31  24  19  14     11  6
+///   imm rs2 rs1 funct3 imm opcode
+///    25  20  15     12   7      0
+///
+/// Encoding bits: `opcode[6:2] | (funct3 << 5)`
+fn put_s<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rs2: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let opcode5 = bits & 0x1f;
+    let funct3 = (bits >> 5) & 0x7;
+    let rs1 = u32::from(rs1) & 0x1f;
+    let rs2 = u32::from(rs2) & 0x1f;
+    let imm = imm as u32;
+
+    // 0-6: opcode
+    let mut i = 0x3;
+    i |= opcode5 << 2;
+    i |= (imm & 0x1f) << 7;
+    i |= funct3 << 12;
+    i |= rs1 << 15;
+    i |= rs2 << 20;
+    i |= ((imm >> 5) & 0x7f) << 25;
+
+    sink.put4(i);
+}
+
 /// U-type instructions.
 ///
 ///   31  11 6
@@ -180,3 +226,217 @@ fn put_uj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS
 
     sink.put4(i);
 }
+
+/// Fence ordering sets, combined in the encoding bits of a `fence` instruction.
+const FENCE_R: u16 = 0b0010;
+const FENCE_W: u16 = 0b0001;
+const FENCE_RW: u16 = FENCE_R | FENCE_W;
+
+/// The `fence` instruction.
+///
+///   31 27   23   19  14     11 6
+///   fm pred succ rs1 funct3 rd opcode
+///   28   24   20  15     12  7      0
+///
+/// Encoding bits: `pred | (succ << 4)`
+fn put_fence<CS: CodeSink + ?Sized>(bits: u16, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let pred = bits & 0xf;
+    let succ = (bits >> 4) & 0xf;
+
+    // The MISC-MEM opcode with rd = rs1 = %x0.
+    let mut i = 0x0f;
+    i |= succ << 20;
+    i |= pred << 24;
+
+    sink.put4(i);
+}
+
+/// A compare-and-swap loop using `lr` and `sc`:
+///
+///   loop: lr.aqrl a, (p)
+///         bne a, e, done
+///         sc.rl a, x, (p)
+///         bnez a, loop
+///         mv a, e
+///   done:
+///
+/// The `sc` status is written to `a`, which must be different from all the inputs. When the store
+/// succeeds, the loaded value was equal to `e`.
+///
+/// Encoding bits: the R-type bits of the `lr` instruction.
+fn put_lr_sc<CS: CodeSink + ?Sized>(
+    bits: u16,
+    p: RegUnit,
+    e: RegUnit,
+    x: RegUnit,
+    a: RegUnit,
+    sink: &mut CS,
+) {
+    // Change the funct5 field from `lr` to `sc`, and only keep the release bit.
+    let sc_bits = (bits & 0xff) | (0b00011 << 10) | (0b01 << 8);
+    // The `bne` and `addi` opcodes.
+    let bne_bits = 0b11000 | (0b001 << 5);
+    let addi_bits = 0b00100;
+
+    put_r(bits, p, 0, a, sink);
+    put_sb(bne_bits, 16, a, e, sink);
+    put_r(sc_bits, p, x, a, sink);
+    put_sb(bne_bits, -12, a, 0, sink);
+    put_i(addi_bits, e, 0, a, sink);
+}
+
+/// Get the 3-bit encoding of one of the registers %x8-%x15 used by compressed instructions.
+fn creg(reg: RegUnit) -> u16 {
+    debug_assert!(
+        (8..16).contains(&reg),
+        "%x{} is not a compressed register",
+        reg
+    );
+    (reg - 8) & 0x7
+}
+
+/// CR-type compressed instructions.
+///
+///   15     11     6   1
+///   funct4 rd/rs1 rs2 op
+///       12      7   2  0
+///
+/// Encoding bits: `op[1:0] | (funct4 << 2)`.
+fn put_cr<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let op = bits & 0x3;
+    let funct4 = (bits >> 2) & 0xf;
+    let rd = rd & 0x1f;
+    let rs2 = rs2 & 0x1f;
+
+    let mut i = op;
+    i |= rs2 << 2;
+    i |= rd << 7;
+    i |= funct4 << 12;
+
+    sink.put2(i);
+}
+
+/// CI-type compressed instructions with a 6-bit immediate.
+///
+///   15     12     11     6        1
+///   funct3 imm[5] rd/rs1 imm[4:0] op
+///       13     12      7        2  0
+///
+/// Encoding bits: `op[1:0] | (funct3 << 2)`.
+fn put_ci<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, imm: i64, sink: &mut CS) {
+    let op = bits & 0x3;
+    let funct3 = (bits >> 2) & 0x7;
+    let rd = rd & 0x1f;
+    let imm = imm as u16;
+
+    let mut i = op;
+    i |= (imm & 0x1f) << 2;
+    i |= rd << 7;
+    i |= ((imm >> 5) & 0x1) << 12;
+    i |= funct3 << 13;
+
+    sink.put2(i);
+}
+
+/// CA-type compressed instructions on the compressed registers.
+///
+///   15     9       6      4    1
+///   funct6 rd'/rs1' funct2 rs2' op
+///       10       7      5    2  0
+///
+/// Encoding bits: `op[1:0] | (funct2 << 2) | (funct6 << 4)`.
+fn put_ca<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let op = bits & 0x3;
+    let funct2 = (bits >> 2) & 0x3;
+    let funct6 = (bits >> 4) & 0x3f;
+
+    let mut i = op;
+    i |= creg(rs2) << 2;
+    i |= funct2 << 5;
+    i |= creg(rd) << 7;
+    i |= funct6 << 10;
+
+    sink.put2(i);
+}
+
+/// CB-type compressed arithmetic instructions with a 6-bit immediate.
+///
+///   15     12     11     9        6        1
+///   funct3 imm[5] funct2 rd'/rs1' imm[4:0] op
+///       13     12     10        7        2  0
+///
+/// Encoding bits: `op[1:0] | (funct3 << 2) | (funct2 << 5)`.
+fn put_cb_imm<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, imm: i64, sink: &mut CS) {
+    let op = bits & 0x3;
+    let funct3 = (bits >> 2) & 0x7;
+    let funct2 = (bits >> 5) & 0x3;
+    let imm = imm as u16;
+
+    let mut i = op;
+    i |= (imm & 0x1f) << 2;
+    i |= creg(rd) << 7;
+    i |= funct2 << 10;
+    i |= ((imm >> 5) & 0x1) << 12;
+    i |= funct3 << 13;
+
+    sink.put2(i);
+}
+
+/// CB-type compressed branches.
+///
+///   15     12     9    6      1
+///   funct3 offset rs1' offset op
+///       13     10    7      2  0
+///
+/// Encoding bits: `op[1:0] | (funct3 << 2)`.
+fn put_cb<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rs1: RegUnit, sink: &mut CS) {
+    let op = bits & 0x3;
+    let funct3 = (bits >> 2) & 0x7;
+
+    debug_assert!(is_signed_int(imm, 9, 1), "CB out of range {:#x}", imm);
+    let imm = imm as u16;
+
+    let mut i = op;
+    i |= creg(rs1) << 7;
+    i |= funct3 << 13;
+
+    // The displacement is completely hashed up.
+    i |= ((imm >> 5) & 0x1) << 2;
+    i |= ((imm >> 1) & 0x3) << 3;
+    i |= ((imm >> 6) & 0x3) << 5;
+    i |= ((imm >> 3) & 0x3) << 10;
+    i |= ((imm >> 8) & 0x1) << 12;
+
+    sink.put2(i);
+}
+
+/// CJ-type compressed jumps.
+///
+///   15     12     1
+///   funct3 offset op
+///       13      2  0
+///
+/// Encoding bits: `op[1:0] | (funct3 << 2)`.
+fn put_cj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, sink: &mut CS) {
+    let op = bits & 0x3;
+    let funct3 = (bits >> 2) & 0x7;
+
+    debug_assert!(is_signed_int(imm, 12, 1), "CJ out of range {:#x}", imm);
+    let imm = imm as u16;
+
+    let mut i = op;
+    i |= funct3 << 13;
+
+    // The displacement is completely hashed up.
+    i |= ((imm >> 5) & 0x1) << 2;
+    i |= ((imm >> 1) & 0x7) << 3;
+    i |= ((imm >> 7) & 0x1) << 6;
+    i |= ((imm >> 6) & 0x1) << 7;
+    i |= ((imm >> 10) & 0x1) << 8;
+    i |= ((imm >> 8) & 0x3) << 9;
+    i |= ((imm >> 4) & 0x1) << 11;
+    i |= ((imm >> 11) & 0x1) << 12;
+
+    sink.put2(i);
+}
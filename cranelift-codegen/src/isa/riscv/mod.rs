@@ -8,22 +8,29 @@ pub mod settings;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
-use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::CodeOffset;
+use crate::binemit::{
+    emit_function, emit_split_function, BufferCodeSink, CodeSink, MemoryCodeSink, Reloc,
+    SplitCodeInfo,
+};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{EncInfo, LibCallRegistry, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use core::fmt;
 use std::boxed::Box;
 use target_lexicon::{PointerWidth, Triple};
 
+/// The relocations emitted by the RISC-V encoding recipes.
+const RELOCS: &[Reloc] = &[Reloc::RiscvCall];
+
 #[allow(dead_code)]
 struct Isa {
     triple: Triple,
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    libcalls: LibCallRegistry,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
 }
 
@@ -32,6 +39,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        libcalls: LibCallRegistry::new(),
         constructor: isa_constructor,
     }
 }
@@ -40,6 +48,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    libcalls: LibCallRegistry,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => panic!("16-bit RISC-V unrecognized"),
@@ -50,6 +59,7 @@ fn isa_constructor(
         triple,
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        libcalls,
         cpumode: level1,
     })
 }
@@ -67,6 +77,10 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn libcalls(&self) -> &LibCallRegistry {
+        &self.libcalls
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -118,8 +132,38 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    #[cfg(feature = "testing_hooks")]
+    fn emit_padding(&self, size: CodeOffset, sink: &mut dyn CodeSink) {
+        binemit::emit_padding(size, sink)
+    }
+
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn emit_function_to_buffer(&self, func: &ir::Function, sink: &mut BufferCodeSink) {
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn supported_relocs(&self) -> &'static [Reloc] {
+        RELOCS
+    }
+
+    fn emit_split_function(
+        &self,
+        func: &ir::Function,
+        info: &SplitCodeInfo,
+        hot: &mut dyn CodeSink,
+        cold: &mut dyn CodeSink,
+    ) {
+        emit_split_function(
+            func,
+            info,
+            binemit::emit_inst,
+            binemit::emit_padding,
+            hot,
+            cold,
+        )
     }
 }
 
@@ -272,6 +316,44 @@ mod tests {
             "R#10c"
         );
     }
+
+    #[test]
+    fn test_can_encode() {
+        let shared_builder = settings::builder();
+        let shared_flags = settings::Flags::new(shared_builder);
+        let isa = isa::lookup(triple!("riscv32"))
+            .unwrap()
+            .finish(shared_flags);
+
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let arg32 = func.dfg.append_ebb_param(ebb, types::I32);
+
+        // ADDI only has a 12-bit immediate.
+        let small = InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg: arg32,
+            imm: immediates::Imm64::new(2047),
+        };
+        let large = InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg: arg32,
+            imm: immediates::Imm64::new(2048),
+        };
+        assert!(isa.can_encode(&func, &small, types::I32));
+        assert!(!isa.can_encode(&func, &large, types::I32));
+
+        // The register form is always available, but i64 needs to be narrowed in RV32.
+        let add32 = InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args: [arg32, arg32],
+        };
+        assert!(isa.can_encode(&func, &add32, types::I32));
+        assert!(!isa.can_encode(&func, &add32, types::I64));
+
+        // Querying doesn't modify the function.
+        assert_eq!(func.dfg.num_insts(), 0);
+    }
 }
 
 impl fmt::Display for Isa {
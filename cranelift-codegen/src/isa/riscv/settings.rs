@@ -26,6 +26,7 @@ mod tests {
              supports_a = false\n\
              supports_f = false\n\
              supports_d = false\n\
+             supports_c = false\n\
              enable_m = true\n\
              enable_e = false\n"
         );
@@ -15,6 +15,7 @@ use crate::regalloc::RegisterSet;
 use crate::result::CodegenResult;
 use crate::stack_layout::layout_stack;
 use core::i32;
+use std::vec::Vec;
 use target_lexicon::{PointerWidth, Triple};
 
 /// Argument registers for x86-64
@@ -139,7 +140,7 @@ impl ArgAssigner for Args {
 }
 
 /// Legalize `sig`.
-pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bool) {
+pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, current: bool) {
     let bits;
     let mut args;
 
@@ -161,6 +162,55 @@ pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bo
 
     legalize_args(&mut sig.params, &mut args);
 
+    // A variadic callee on x86-64 System V reads the number of vector registers used for arguments
+    // from `%al`, so it knows which XMM registers to save in the register save area.
+    if sig.variadic
+        && !current
+        && bits == 64
+        && sig.call_conv == CallConv::SystemV
+        && sig
+            .special_param_index(ArgumentPurpose::VectorRegCount)
+            .is_none()
+    {
+        sig.params.push(AbiParam::special_reg(
+            ir::types::I32,
+            ArgumentPurpose::VectorRegCount,
+            RU::rax as RegUnit,
+        ));
+    }
+
+    // On Windows x64, the floating point arguments of a variadic call passed in `%xmm0`-`%xmm3`
+    // are also passed in the integer register of the same position, where the callee expects
+    // the variadic arguments.
+    if sig.variadic
+        && !current
+        && bits == 64
+        && sig.call_conv == CallConv::WindowsFastcall
+        && sig
+            .special_param_index(ArgumentPurpose::FloatArgCopy)
+            .is_none()
+    {
+        let copies: Vec<AbiParam> = sig
+            .params
+            .iter()
+            .filter(|arg| arg.value_type.is_float())
+            .filter_map(|arg| match arg.location {
+                ArgumentLoc::Reg(reg) => {
+                    let index = (0..ARG_GPRS_WIN_FASTCALL_X64.len())
+                        .find(|&i| FPR.unit(i) == reg)
+                        .expect("float argument in an unexpected register");
+                    Some(AbiParam::special_reg(
+                        ir::Type::int(arg.value_type.bits()).unwrap(),
+                        ArgumentPurpose::FloatArgCopy,
+                        ARG_GPRS_WIN_FASTCALL_X64[index] as RegUnit,
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        sig.params.extend(copies);
+    }
+
     let (regs, fpr_limit) = if sig.call_conv == CallConv::WindowsFastcall {
         // windows-x64 calling convention only uses XMM0 or RAX for return values
         (&RET_GPRS_WIN_FASTCALL_X64[..], 1)
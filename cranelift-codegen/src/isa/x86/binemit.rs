@@ -2,14 +2,59 @@
 
 use super::enc_tables::{needs_offset, needs_sib_byte};
 use super::registers::RU;
-use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::binemit::{bad_encoding, CodeOffset, CodeSink, Reloc};
 use crate::ir::condcodes::{CondCode, FloatCC, IntCC};
-use crate::ir::{Ebb, Function, Inst, InstructionData, JumpTable, Opcode, TrapCode};
+use crate::ir::{
+    Ebb, ExternalName, Function, Inst, InstructionData, JumpTable, LibCall, Opcode, TrapCode,
+};
 use crate::isa::{RegUnit, StackBase, StackBaseMask, StackRef};
 use crate::regalloc::RegDiversions;
+use crate::settings::CodePadding;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-x86.rs"));
 
+// The recommended multi-byte NOP sequences, indexed by length - 1.
+const NOPS: [&[u8]; 9] = [
+    &[0x90],
+    &[0x66, 0x90],
+    &[0x0f, 0x1f, 0x00],
+    &[0x0f, 0x1f, 0x40, 0x00],
+    &[0x0f, 0x1f, 0x44, 0x00, 0x00],
+    &[0x66, 0x0f, 0x1f, 0x44, 0x00, 0x00],
+    &[0x0f, 0x1f, 0x80, 0x00, 0x00, 0x00, 0x00],
+    &[0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    &[0x66, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+/// Emit `size` bytes of no-op instructions, using as few instructions as possible.
+pub fn emit_padding<CS: CodeSink + ?Sized>(size: CodeOffset, sink: &mut CS) {
+    let mut remaining = size as usize;
+    while remaining > 0 {
+        let len = remaining.min(NOPS.len());
+        for &byte in NOPS[len - 1] {
+            sink.put1(byte);
+        }
+        remaining -= len;
+    }
+}
+
+/// Fill `buf` with trap instructions or no-op instructions.
+pub fn fill_padding(buf: &mut [u8], fill: CodePadding) {
+    match fill {
+        CodePadding::Trap => {
+            // `int3` is a single byte, so a stray jump to any offset in the padding traps.
+            for byte in buf {
+                *byte = 0xcc;
+            }
+        }
+        CodePadding::Nop => {
+            for chunk in buf.chunks_mut(NOPS.len()) {
+                chunk.copy_from_slice(NOPS[chunk.len() - 1]);
+            }
+        }
+    }
+}
+
 // Convert a stack base to the corresponding register.
 fn stk_base(base: StackBase) -> RegUnit {
     let ru = match base {
@@ -161,6 +206,20 @@ fn put_rexmp3<CS: CodeSink + ?Sized>(bits: u16, rex: u8, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit a 3-byte VEX prefix and the opcode byte. The REX bits are stored inverted in the prefix
+// along with the opcode map, and `vvvv` names an additional register operand.
+fn put_vex<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, sink: &mut CS) {
+    debug_assert_ne!(bits & 0x0c00, 0, "Invalid encoding bits for Vex*");
+    debug_assert_eq!(rex & 0xf8, BASE_REX);
+    let pp = ((bits >> 8) & 3) as u8;
+    let mm = ((bits >> 10) & 3) as u8;
+    let w = ((bits >> 15) & 1) as u8;
+    sink.put1(0xc4);
+    sink.put1(((!rex & 7) << 5) | mm);
+    sink.put1((w << 7) | ((!(vvvv as u8) & 0xf) << 3) | pp);
+    sink.put1(bits as u8);
+}
+
 /// Emit a ModR/M byte for reg-reg operands.
 fn modrm_rr<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     let reg = reg as u8 & 7;
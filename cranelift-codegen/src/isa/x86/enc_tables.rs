@@ -893,3 +893,41 @@ fn expand_fcvt_to_uint_sat(
     cfg.recompute_ebb(pos.func, uint_large_ebb);
     cfg.recompute_ebb(pos.func, done);
 }
+
+/// Expand a `tls_value` instruction into the access sequence selected by the `tls_model` setting.
+fn expand_tls_value(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    use crate::settings::TlsModel;
+
+    // The verifier rejects thread-local global values on 32-bit targets.
+    debug_assert_eq!(isa.pointer_bits(), 64);
+
+    let global_value = match func.dfg[inst] {
+        ir::InstructionData::UnaryGlobalValue {
+            opcode: ir::Opcode::TlsValue,
+            global_value,
+        } => global_value,
+        _ => panic!("Need tls_value: {}", func.dfg.display_inst(inst, None)),
+    };
+    debug_assert_eq!(func.dfg.ctrl_typevar(inst), ir::types::I64);
+
+    match isa.flags().tls_model() {
+        TlsModel::None => panic!("tls_value requires the tls_model setting"),
+        TlsModel::ElfGd => {
+            func.dfg.replace(inst).x86_elf_tls_get_addr(global_value);
+        }
+        TlsModel::ElfIe => {
+            func.dfg.replace(inst).x86_elf_tls_ie_addr(global_value);
+        }
+        TlsModel::ElfLe => {
+            func.dfg.replace(inst).x86_elf_tls_le_addr(global_value);
+        }
+        TlsModel::Macho => {
+            func.dfg.replace(inst).x86_macho_tls_get_addr(global_value);
+        }
+    }
+}
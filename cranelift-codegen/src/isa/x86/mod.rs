@@ -8,12 +8,15 @@ pub mod settings;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
-use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::CodeOffset;
+use crate::binemit::{
+    emit_function, emit_split_function, BufferCodeSink, CodeSink, MemoryCodeSink, Reloc,
+    SplitCodeInfo,
+};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{AddressModes, EncInfo, LibCallRegistry, RegClass, RegInfo, TargetIsa};
 use crate::regalloc;
 use crate::result::CodegenResult;
 use crate::timing;
@@ -21,11 +24,27 @@ use core::fmt;
 use std::boxed::Box;
 use target_lexicon::{PointerWidth, Triple};
 
+/// The relocations emitted by the x86 encoding recipes.
+const RELOCS: &[Reloc] = &[
+    Reloc::Abs4,
+    Reloc::Abs8,
+    Reloc::X86PCRel4,
+    Reloc::X86PCRelRodata4,
+    Reloc::X86CallPCRel4,
+    Reloc::X86CallPLTRel4,
+    Reloc::X86GOTPCRel4,
+    Reloc::ElfX86_64TlsGd,
+    Reloc::ElfX86_64GotTpOff,
+    Reloc::ElfX86_64TpOff32,
+    Reloc::MachOX86_64Tlv,
+];
+
 #[allow(dead_code)]
 struct Isa {
     triple: Triple,
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    libcalls: LibCallRegistry,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
 }
 
@@ -34,6 +53,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
     IsaBuilder {
         triple,
         setup: settings::builder(),
+        libcalls: LibCallRegistry::new(),
         constructor: isa_constructor,
     }
 }
@@ -42,6 +62,7 @@ fn isa_constructor(
     triple: Triple,
     shared_flags: shared_settings::Flags,
     builder: shared_settings::Builder,
+    libcalls: LibCallRegistry,
 ) -> Box<dyn TargetIsa> {
     let level1 = match triple.pointer_width().unwrap() {
         PointerWidth::U16 => unimplemented!("x86-16"),
@@ -52,6 +73,7 @@ fn isa_constructor(
         triple,
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        libcalls,
         cpumode: level1,
     })
 }
@@ -69,14 +91,25 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn libcalls(&self) -> &LibCallRegistry {
+        &self.libcalls
+    }
+
     fn uses_cpu_flags(&self) -> bool {
         true
     }
 
-    fn uses_complex_addresses(&self) -> bool {
+    fn uses_memory_operands(&self) -> bool {
         true
     }
 
+    fn address_modes(&self) -> AddressModes {
+        AddressModes {
+            max_scale: 8,
+            disp_bits: 32,
+        }
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -128,14 +161,73 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    #[cfg(feature = "testing_hooks")]
+    fn emit_padding(&self, size: CodeOffset, sink: &mut dyn CodeSink) {
+        binemit::emit_padding(size, sink)
+    }
+
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn emit_function_to_buffer(&self, func: &ir::Function, sink: &mut BufferCodeSink) {
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+    }
+
+    fn emit_split_function(
+        &self,
+        func: &ir::Function,
+        info: &SplitCodeInfo,
+        hot: &mut dyn CodeSink,
+        cold: &mut dyn CodeSink,
+    ) {
+        emit_split_function(
+            func,
+            info,
+            binemit::emit_inst,
+            binemit::emit_padding,
+            hot,
+            cold,
+        )
+    }
+
+    fn supported_relocs(&self) -> &'static [Reloc] {
+        RELOCS
+    }
+
+    fn fill_function_padding(&self, buf: &mut [u8]) {
+        binemit::fill_padding(buf, self.shared_flags.code_padding())
     }
 
     fn prologue_epilogue(&self, func: &mut ir::Function) -> CodegenResult<()> {
         let _tt = timing::prologue_epilogue();
         abi::prologue_epilogue(func, self)
     }
+
+    fn func_addr_reloc(&self, func: &ir::Function, inst: ir::Inst) -> Option<Reloc> {
+        if func.dfg[inst].opcode() != ir::Opcode::FuncAddr {
+            return None;
+        }
+
+        // Use the relocation emitted by the recipe of the encoding that will be selected, so the
+        // answer follows the `is_pic`, `allones_funcaddrs` and `far_calls` settings. Recipe
+        // names are prefixed with the opcode prefix, like `RexOp1pcrel_fnaddr8`.
+        let enc = self
+            .encode(func, &func.dfg[inst], func.dfg.ctrl_typevar(inst))
+            .ok()?;
+        let recipe = self.encoding_info().names[enc.recipe()];
+        if recipe.ends_with("pcrel_fnaddr8") {
+            Some(Reloc::X86PCRel4)
+        } else if recipe.ends_with("got_fnaddr8") {
+            Some(Reloc::X86GOTPCRel4)
+        } else if recipe.ends_with("fnaddr8") {
+            Some(Reloc::Abs8)
+        } else if recipe.ends_with("fnaddr4") {
+            Some(Reloc::Abs4)
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Display for Isa {
@@ -143,3 +235,99 @@ impl fmt::Display for Isa {
         write!(f, "{}\n{}", self.shared_flags, self.isa_flags)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::binemit::Reloc;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, Ebb, Function, Inst, InstBuilder};
+    use crate::isa::TargetIsa;
+    use crate::settings::{self, Configurable};
+    use crate::test_utils;
+    use std::boxed::Box;
+    use std::string::ToString;
+
+    fn isa(triple: &str, flags: &[&str]) -> Box<dyn TargetIsa> {
+        let mut shared_builder = settings::builder();
+        for flag in flags {
+            shared_builder.enable(flag).unwrap();
+        }
+        test_utils::isa_with_flags(triple, shared_builder)
+    }
+
+    fn func_addr(func: &mut Function, ebb: Ebb, ty: types::Type, colocated: bool) -> Inst {
+        let sig = test_utils::signature(&[], &[]);
+        let callee = test_utils::import_function(func, "callee", sig, colocated);
+        let mut pos = FuncCursor::new(func);
+        pos.goto_bottom(ebb);
+        let value = pos.ins().func_addr(ty, callee);
+        pos.func.dfg.value_def(value).unwrap_inst()
+    }
+
+    #[test]
+    fn func_addr_reloc() {
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        func.layout.append_ebb(ebb);
+        let near = func_addr(&mut func, ebb, types::I64, true);
+        let far = func_addr(&mut func, ebb, types::I64, false);
+        let near32 = func_addr(&mut func, ebb, types::I32, true);
+
+        for &(triple, flags, inst, reloc, recipe) in &[
+            ("x86_64", &[][..], near, Reloc::Abs8, "fnaddr8"),
+            ("x86_64", &[], far, Reloc::Abs8, "fnaddr8"),
+            ("x86_64", &["far_calls"], near, Reloc::Abs8, "fnaddr8"),
+            (
+                "x86_64",
+                &["allones_funcaddrs"],
+                far,
+                Reloc::Abs8,
+                "allones_fnaddr8",
+            ),
+            (
+                "x86_64",
+                &["is_pic"],
+                near,
+                Reloc::X86PCRel4,
+                "pcrel_fnaddr8",
+            ),
+            (
+                "x86_64",
+                &["is_pic"],
+                far,
+                Reloc::X86GOTPCRel4,
+                "got_fnaddr8",
+            ),
+            (
+                "x86_64",
+                &["is_pic", "far_calls"],
+                near,
+                Reloc::X86GOTPCRel4,
+                "got_fnaddr8",
+            ),
+            ("i686", &[], near32, Reloc::Abs4, "fnaddr4"),
+            (
+                "i686",
+                &["allones_funcaddrs"],
+                near32,
+                Reloc::Abs4,
+                "allones_fnaddr4",
+            ),
+        ] {
+            let isa = isa(triple, flags);
+            assert_eq!(isa.func_addr_reloc(&func, inst), Some(reloc));
+            assert!(isa.can_emit_reloc(reloc));
+
+            let ty = func.dfg.ctrl_typevar(inst);
+            let enc = isa.encode(&func, &func.dfg[inst], ty).unwrap();
+            let name = isa.encoding_info().display(enc).to_string();
+            assert!(name.contains(recipe), "{} doesn't use {}", name, recipe);
+        }
+
+        // There is no `func_addr` encoding for 32-bit PIC code.
+        assert_eq!(
+            isa("i686", &["is_pic"]).func_addr_reloc(&func, near32),
+            None
+        );
+    }
+}
@@ -16,6 +16,11 @@
 //!
 //! Between the two phases, preamble signatures and call/return arguments don't match. This
 //! intermediate state doesn't type check.
+//!
+//! When a function returns more values than the ISA has return registers for, all of its return
+//! values are passed in memory instead. The legalized signature gets a hidden `sret` parameter
+//! pointing to a memory area where the callee stores its return values, and the caller allocates
+//! that area in a stack slot and loads the return values from it after the call.
 
 use crate::abi::{legalize_abi_value, ValueConversion};
 use crate::cursor::{Cursor, FuncCursor};
@@ -23,10 +28,11 @@ use crate::flowgraph::ControlFlowGraph;
 use crate::ir::instructions::CallInfo;
 use crate::ir::{
     AbiParam, ArgumentLoc, ArgumentPurpose, DataFlowGraph, Ebb, Function, Inst, InstBuilder,
-    SigRef, Signature, Type, Value, ValueLoc,
+    MemFlags, SigRef, Signature, StackSlotData, StackSlotKind, Type, Value, ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::legalizer::split::{isplit, vsplit};
+use core::iter;
 use log::debug;
 use std::vec::Vec;
 
@@ -57,9 +63,71 @@ pub fn legalize_libcall_signature(signature: &mut Signature, isa: &dyn TargetIsa
 ///
 /// `current` is true if this is the signature for the current function.
 fn legalize_signature(signature: &mut Signature, current: bool, isa: &dyn TargetIsa) {
+    if needs_sret_returns(signature, current, isa) {
+        // Return all the values in memory through a hidden `sret` pointer instead.
+        signature
+            .returns
+            .retain(|rt| rt.purpose != ArgumentPurpose::Normal);
+        signature.params.push(AbiParam::special(
+            isa.pointer_type(),
+            ArgumentPurpose::StructReturn,
+        ));
+    }
     isa.legalize_signature(signature, current);
 }
 
+/// Does `signature` return more values than the ISA has registers for?
+///
+/// Signatures that already have an `sret` parameter or assigned return locations are left alone.
+fn needs_sret_returns(signature: &Signature, current: bool, isa: &dyn TargetIsa) -> bool {
+    if signature.returns.is_empty()
+        || signature
+            .special_param_index(ArgumentPurpose::StructReturn)
+            .is_some()
+        || signature.returns.iter().any(|rt| rt.location.is_assigned())
+    {
+        return false;
+    }
+
+    let mut legalized = signature.clone();
+    isa.legalize_signature(&mut legalized, current);
+    legalized
+        .returns
+        .iter()
+        .any(|rt| rt.purpose == ArgumentPurpose::Normal && rt.location.is_stack())
+}
+
+/// Are the `num_values` return values of a call or return with the legalized signature `sig`
+/// passed in memory through a hidden `sret` pointer, as set up by `legalize_signature`?
+fn returns_in_memory(sig: &Signature, num_values: usize) -> bool {
+    num_values > 0
+        && sig
+            .special_param_index(ArgumentPurpose::StructReturn)
+            .is_some()
+        && sig.returns.iter().all(|rt| {
+            rt.purpose != ArgumentPurpose::Normal && rt.purpose != ArgumentPurpose::StructReturn
+        })
+}
+
+/// Compute the offsets of return values of the types `types` in the memory area pointed to by a
+/// hidden `sret` pointer. Each value is naturally aligned.
+///
+/// Returns the offsets and the total size of the area.
+fn sret_layout<I>(types: I) -> (Vec<u32>, u32)
+where
+    I: Iterator<Item = Type>,
+{
+    let mut offsets = Vec::new();
+    let mut size = 0;
+    for ty in types {
+        let bytes = ty.bytes();
+        size = (size + bytes - 1) & !(bytes - 1);
+        offsets.push(size);
+        size += bytes;
+    }
+    (offsets, size)
+}
+
 /// Legalize the entry block parameters after `func`'s signature has been legalized.
 ///
 /// The legalized signature may contain more parameters than the original signature, and the
@@ -116,6 +184,8 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                     debug_assert!(!has_stack_limit, "Multiple stack_limit arguments found");
                     has_stack_limit = true;
                 }
+                // Reported by the verifier, see below.
+                ArgumentPurpose::VectorRegCount | ArgumentPurpose::FloatArgCopy => {}
                 _ => panic!("Unexpected special-purpose arg {}", abi_type),
             }
             abi_arg += 1;
@@ -176,6 +246,9 @@ fn legalize_entry_params(func: &mut Function, entry: Ebb) {
                 debug_assert!(!has_stack_limit, "Multiple stack_limit parameters found");
                 has_stack_limit = true;
             }
+            // These are only passed to variadic functions, which we don't compile. The verifier
+            // reports them, but they are harmless parameters otherwise.
+            ArgumentPurpose::VectorRegCount | ArgumentPurpose::FloatArgCopy => {}
         }
 
         // Just create entry block values to match here. We will use them in `handle_return_abi()`
@@ -339,7 +412,7 @@ where
 ///
 fn convert_to_abi<PutArg>(
     pos: &mut FuncCursor,
-    cfg: &ControlFlowGraph,
+    cfg: &mut ControlFlowGraph,
     value: Value,
     put_arg: &mut PutArg,
 ) where
@@ -429,7 +502,7 @@ fn check_return_signature(dfg: &DataFlowGraph, inst: Inst, sig: &Signature) -> b
 ///
 fn legalize_inst_arguments<ArgType>(
     pos: &mut FuncCursor,
-    cfg: &ControlFlowGraph,
+    cfg: &mut ControlFlowGraph,
     abi_args: usize,
     mut get_abi_type: ArgType,
 ) where
@@ -518,21 +591,45 @@ fn legalize_inst_arguments<ArgType>(
 /// original return values. The call's result values will be adapted to match the new signature.
 ///
 /// Returns `true` if any instructions were inserted.
-pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGraph) -> bool {
+pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
     let pos = &mut FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
+    // Load the return values from memory if the callee can't return them in registers.
+    let loaded = load_sret_results(pos, inst);
+
     // Start by checking if the argument types already match the signature.
     let sig_ref = match check_call_signature(&pos.func.dfg, inst) {
-        Ok(_) => return spill_call_arguments(pos),
+        Ok(_) => {
+            let spilled = spill_call_arguments(pos);
+            return spilled || loaded;
+        }
         Err(s) => s,
     };
 
-    // OK, we need to fix the call arguments to match the ABI signature.
-    let abi_args = pos.func.dfg.signatures[sig_ref].params.len();
+    // OK, we need to fix the call arguments to match the ABI signature. The vector register
+    // count and the float argument copies of a variadic call are not computed from the call
+    // arguments one by one, so they are appended separately.
+    let abi_args = pos.func.dfg.signatures[sig_ref]
+        .params
+        .iter()
+        .position(|arg| match arg.purpose {
+            ArgumentPurpose::VectorRegCount | ArgumentPurpose::FloatArgCopy => true,
+            _ => false,
+        })
+        .unwrap_or_else(|| pos.func.dfg.signatures[sig_ref].params.len());
     legalize_inst_arguments(pos, cfg, abi_args, |func, abi_arg| {
         func.dfg.signatures[sig_ref].params[abi_arg]
     });
+    match pos.func.dfg.signatures[sig_ref].params.get(abi_args) {
+        Some(arg) if arg.purpose == ArgumentPurpose::VectorRegCount => {
+            pass_vector_reg_count(pos, inst, sig_ref)
+        }
+        Some(arg) if arg.purpose == ArgumentPurpose::FloatArgCopy => {
+            pass_float_arg_copies(pos, inst, sig_ref, abi_args)
+        }
+        _ => {}
+    }
 
     if !pos.func.dfg.signatures[sig_ref].returns.is_empty() {
         inst = legalize_inst_results(pos, |func, abi_res| {
@@ -556,10 +653,209 @@ pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGra
     true
 }
 
+/// Append the number of vector registers used by the arguments of the variadic call `inst` as its
+/// last argument, as required by the `VectorRegCount` parameter of its signature `sig_ref`.
+fn pass_vector_reg_count(pos: &mut FuncCursor, inst: Inst, sig_ref: SigRef) {
+    let sig = &pos.func.dfg.signatures[sig_ref];
+    let index = sig
+        .special_param_index(ArgumentPurpose::VectorRegCount)
+        .expect("Variadic call signature expected.");
+    debug_assert_eq!(
+        index + 1,
+        sig.params.len(),
+        "vreg_count must be the last parameter"
+    );
+    let ty = sig.params[index].value_type;
+    let count = sig.params[..index]
+        .iter()
+        .filter(|arg| {
+            arg.location.is_reg() && (arg.value_type.is_float() || arg.value_type.is_vector())
+        })
+        .count();
+
+    pos.goto_inst(inst);
+    let count = pos.ins().iconst(ty, count as i64);
+    pos.func.dfg.append_inst_arg(inst, count);
+}
+
+/// Append copies of the floating point register arguments of the variadic call `inst`, as
+/// required by the `FloatArgCopy` parameters of its signature `sig_ref`. The first of them is
+/// parameter `first_copy`.
+fn pass_float_arg_copies(pos: &mut FuncCursor, inst: Inst, sig_ref: SigRef, first_copy: usize) {
+    let sig = &pos.func.dfg.signatures[sig_ref];
+    let copies: Vec<(Value, Type)> = pos
+        .func
+        .dfg
+        .inst_variable_args(inst)
+        .iter()
+        .zip(&sig.params[..first_copy])
+        .filter(|(_, arg)| arg.value_type.is_float() && arg.location.is_reg())
+        .map(|(&value, _)| value)
+        .zip(&sig.params[first_copy..])
+        .map(|(value, copy)| {
+            debug_assert_eq!(copy.purpose, ArgumentPurpose::FloatArgCopy);
+            (value, copy.value_type)
+        })
+        .collect();
+    debug_assert_eq!(
+        first_copy + copies.len(),
+        sig.params.len(),
+        "float_copy parameters must match the float register arguments"
+    );
+
+    pos.goto_inst(inst);
+    for (value, ty) in copies {
+        let copy = pos.ins().bitcast(ty, value);
+        pos.func.dfg.append_inst_arg(inst, copy);
+    }
+}
+
+/// Pass the return values of the call `inst` in memory if its signature requires it.
+///
+/// A stack slot is allocated for the return values, and its address is passed as the hidden
+/// `sret` argument. The results of the call are replaced by loads from the stack slot inserted
+/// after the call. The cursor is left pointing at the call.
+///
+/// Returns `true` if any instructions were inserted.
+fn load_sret_results(pos: &mut FuncCursor, inst: Inst) -> bool {
+    let sig_ref = pos
+        .func
+        .dfg
+        .call_signature(inst)
+        .expect("Call instruction expected.");
+    let num_results = pos.func.dfg.inst_results(inst).len();
+    if !returns_in_memory(&pos.func.dfg.signatures[sig_ref], num_results) {
+        return false;
+    }
+
+    let results = pos.func.dfg.detach_results(inst);
+    let results: Vec<Value> = results.as_slice(&pos.func.dfg.value_lists).to_vec();
+    let (offsets, size) = sret_layout(results.iter().flat_map(|&v| {
+        let ty = pos.func.dfg.value_type(v);
+        iter::repeat(ty.lane_type()).take(ty.lane_count() as usize)
+    }));
+    debug!(
+        "Returning {} values in memory from {}",
+        results.len(),
+        pos.func.dfg.display_inst(inst, None)
+    );
+
+    let sig = &pos.func.dfg.signatures[sig_ref];
+    let ptr_type = sig.params[sig
+        .special_param_index(ArgumentPurpose::StructReturn)
+        .unwrap()]
+    .value_type;
+    let ss = pos
+        .func
+        .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+    let addr = pos.ins().stack_addr(ptr_type, ss, 0);
+    pos.func.dfg.append_inst_arg(inst, addr);
+
+    pos.goto_after_inst(inst);
+    let mut offsets = offsets.into_iter();
+    for &result in &results {
+        let ty = pos.func.dfg.value_type(result);
+        if ty.is_vector() {
+            // Vectors are loaded one lane at a time, like they are passed in registers.
+            let lanes: Vec<Value> = offsets
+                .by_ref()
+                .take(ty.lane_count() as usize)
+                .map(|offset| {
+                    pos.ins()
+                        .load(ty.lane_type(), MemFlags::trusted(), addr, offset as i32)
+                })
+                .collect();
+            let value = vconcat_lanes(pos, &lanes);
+            pos.func.dfg.change_to_alias(result, value);
+        } else {
+            let offset = offsets.next().unwrap();
+            pos.ins()
+                .with_result(result)
+                .load(ty, MemFlags::trusted(), addr, offset as i32);
+        }
+    }
+    pos.goto_inst(inst);
+    true
+}
+
+/// Build a vector from its scalar `lanes` with a tree of `vconcat` instructions.
+fn vconcat_lanes(pos: &mut FuncCursor, lanes: &[Value]) -> Value {
+    if lanes.len() == 1 {
+        return lanes[0];
+    }
+    let (lo, hi) = lanes.split_at(lanes.len() / 2);
+    let lo = vconcat_lanes(pos, lo);
+    let hi = vconcat_lanes(pos, hi);
+    pos.ins().vconcat(lo, hi)
+}
+
+/// Split the vector `value` into its scalar lanes with `vsplit`, and append them to `lanes`.
+/// A scalar `value` is appended as is.
+fn vsplit_lanes(
+    pos: &mut FuncCursor,
+    cfg: &mut ControlFlowGraph,
+    value: Value,
+    lanes: &mut Vec<Value>,
+) {
+    if !pos.func.dfg.value_type(value).is_vector() {
+        lanes.push(value);
+        return;
+    }
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (lo, hi) = vsplit(&mut pos.func, cfg, curpos, srcloc, value);
+    vsplit_lanes(pos, cfg, lo, lanes);
+    vsplit_lanes(pos, cfg, hi, lanes);
+}
+
+/// Store the return values of the return instruction `inst` in memory if the function signature
+/// requires it.
+///
+/// The values are stored through the hidden `sret` parameter, and removed from the return
+/// instruction. Returns `true` if any instructions were inserted.
+fn store_sret_returns(inst: Inst, func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    let num_values = func.dfg.inst_variable_args(inst).len();
+    if !returns_in_memory(&func.signature, num_values) {
+        return false;
+    }
+
+    let values = func.dfg.inst_variable_args(inst).to_vec();
+    let idx = func
+        .signature
+        .special_param_index(ArgumentPurpose::StructReturn)
+        .unwrap();
+    let sret = func.dfg.ebb_params(func.layout.entry_block().unwrap())[idx];
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Vectors are stored one lane at a time, like they are passed in registers.
+    let mut lanes = Vec::new();
+    for value in values {
+        vsplit_lanes(&mut pos, cfg, value, &mut lanes);
+    }
+    let (offsets, _) = sret_layout(lanes.iter().map(|&v| pos.func.dfg.value_type(v)));
+    for (&value, &offset) in lanes.iter().zip(&offsets) {
+        pos.ins()
+            .store(MemFlags::trusted(), value, sret, offset as i32);
+    }
+
+    // A return instruction has no fixed arguments, so this removes all the values.
+    let mut vlist = pos.func.dfg[inst].take_value_list().unwrap();
+    vlist.clear(&mut pos.func.dfg.value_lists);
+    pos.func.dfg[inst].put_value_list(vlist);
+    true
+}
+
 /// Insert ABI conversion code before and after the return instruction at `inst`.
 ///
 /// Return `true` if any instructions were inserted.
-pub fn handle_return_abi(inst: Inst, func: &mut Function, cfg: &ControlFlowGraph) -> bool {
+pub fn handle_return_abi(inst: Inst, func: &mut Function, cfg: &mut ControlFlowGraph) -> bool {
+    // Store the return values in memory if they can't be returned in registers.
+    if store_sret_returns(inst, func, cfg) {
+        return true;
+    }
+
     // Check if the returned types already match the signature.
     if check_return_signature(&func.dfg, inst, &func.signature) {
         return false;
@@ -27,6 +27,13 @@ pub fn expand_global_value(
         _ => panic!("Wanted global_value: {}", func.dfg.display_inst(inst, None)),
     };
 
+    // The verifier rejects these chains, but legalizing them anyway would never terminate.
+    match func.global_value_depth(gv) {
+        Some(depth) if depth <= ir::MAX_GLOBAL_VALUE_DEPTH => {}
+        Some(_) => panic!("{} is computed from too many global values", gv),
+        None => panic!("{} is computed from a global value cycle", gv),
+    }
+
     match func.global_values[gv] {
         ir::GlobalValueData::VMContext => vmctx_addr(inst, func),
         ir::GlobalValueData::IAddImm {
@@ -41,6 +48,7 @@ pub fn expand_global_value(
             readonly,
         } => load_addr(inst, func, base, offset, global_type, readonly, isa),
         ir::GlobalValueData::Symbol { .. } => symbol(inst, func, gv, isa),
+        ir::GlobalValueData::TlsValue { .. } => tls_value(inst, func, gv, isa),
     }
 }
 
@@ -66,6 +74,12 @@ fn iadd_imm_addr(
     offset: i64,
     global_type: ir::Type,
 ) {
+    // Fold the offset into a symbol, so the addition is done by the relocation.
+    if let Some(symbol) = offset_symbol(func, base, offset) {
+        func.dfg.replace(inst).global_value(global_type, symbol);
+        return;
+    }
+
     let mut pos = FuncCursor::new(func).at_inst(inst);
 
     // Get the value for the lhs. For tidiness, expand VMContext here so that we avoid
@@ -82,6 +96,46 @@ fn iadd_imm_addr(
     pos.func.dfg.replace(inst).iadd_imm(lhs, offset);
 }
 
+/// Get a symbol global value for the address `offset` bytes after the symbol `base`.
+///
+/// Returns `None` if `base` isn't a symbol or the offset doesn't fit. An existing global value is
+/// reused if possible.
+fn offset_symbol(
+    func: &mut ir::Function,
+    base: ir::GlobalValue,
+    offset: i64,
+) -> Option<ir::GlobalValue> {
+    let (name, offset, colocated) = match func.global_values[base] {
+        ir::GlobalValueData::Symbol {
+            ref name,
+            offset: base_offset,
+            colocated,
+        } => (
+            name.clone(),
+            i64::checked_add(base_offset.into(), offset)?,
+            colocated,
+        ),
+        _ => return None,
+    };
+
+    let existing = func.global_values.iter().find(|&(_, data)| match *data {
+        ir::GlobalValueData::Symbol {
+            name: ref n,
+            offset: o,
+            colocated: c,
+        } => *n == name && o == offset.into() && c == colocated,
+        _ => false,
+    });
+    Some(match existing {
+        Some((gv, _)) => gv,
+        None => func.create_global_value(ir::GlobalValueData::Symbol {
+            name,
+            offset: offset.into(),
+            colocated,
+        }),
+    })
+}
+
 /// Expand a `global_value` instruction for a load global.
 fn load_addr(
     inst: ir::Inst,
@@ -125,5 +179,34 @@ fn load_addr(
 /// Expand a `global_value` instruction for a symbolic name global.
 fn symbol(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalValue, isa: &dyn TargetIsa) {
     let ptr_ty = isa.pointer_type();
-    func.dfg.replace(inst).symbol_value(ptr_ty, gv);
+    let (offset, colocated) = match func.global_values[gv] {
+        ir::GlobalValueData::Symbol {
+            offset, colocated, ..
+        } => {
+            let offset: i64 = offset.into();
+            (offset, colocated)
+        }
+        _ => panic!("Wanted symbol global value"),
+    };
+
+    // In PIC mode, the address of a non-colocated symbol is loaded from the GOT. The GOT entry
+    // holds the address of the symbol itself, so any offset can't be folded into the relocation
+    // and has to be added separately.
+    if isa.flags().is_pic() && !colocated && offset != 0 {
+        let mut pos = FuncCursor::new(func).at_inst(inst);
+        pos.use_srcloc(inst);
+        let base = pos.ins().symbol_value(ptr_ty, gv);
+        pos.func.dfg.replace(inst).iadd_imm(base, offset);
+    } else {
+        func.dfg.replace(inst).symbol_value(ptr_ty, gv);
+    }
+}
+
+/// Expand a `global_value` instruction for a thread-local variable.
+///
+/// The `tls_value` instruction is further legalized by the target ISA according to the
+/// `tls_model` setting.
+fn tls_value(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalValue, isa: &dyn TargetIsa) {
+    let ptr_ty = isa.pointer_type();
+    func.dfg.replace(inst).tls_value(ptr_ty, gv);
 }
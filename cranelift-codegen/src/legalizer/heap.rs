@@ -2,6 +2,14 @@
 //!
 //! This module exports the `expand_heap_addr` function which transforms a `heap_addr`
 //! instruction into code that depends on the kind of heap referenced.
+//!
+//! An out-of-bounds access is handled according to the heap's `HeapViolation` policy. It either
+//! traps, has its index clamped to the heap bound, or branches to a call to a host function that
+//! provides the address to access.
+//!
+//! Heap indexes can be 32 or 64 bits wide independently of the address type. All bounds
+//! arithmetic is done in `u64`, and a 64-bit index is only narrowed to a 32-bit address after it
+//! has been checked against the heap bound.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
@@ -17,27 +25,133 @@ pub fn expand_heap_addr(
     _isa: &dyn TargetIsa,
 ) {
     // Unpack the instruction.
-    let (heap, offset, access_size) = match func.dfg[inst] {
+    let (flags, heap, offset, access_size) = match func.dfg[inst] {
         ir::InstructionData::HeapAddr {
             opcode,
+            flags,
             heap,
             arg,
             imm,
         } => {
             debug_assert_eq!(opcode, ir::Opcode::HeapAddr);
-            (heap, arg, imm.into())
+            (flags, heap, arg, imm.into())
         }
         _ => panic!("Wanted heap_addr: {}", func.dfg.display_inst(inst, None)),
     };
 
+    // The frontend has proven that this access is in bounds, so skip the bounds check.
+    if flags.notrap() {
+        let offset_ty = func.dfg.value_type(offset);
+        let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+        compute_addr(inst, heap, addr_ty, offset, offset_ty, func);
+        return;
+    }
+
+    // Out-of-bounds accesses branch to an EBB calling the libcall instead of trapping.
+    let oob_ebb = match func.heaps[heap].violation {
+        ir::HeapViolation::CustomLibcall(callee) => {
+            Some(insert_libcall(inst, offset, callee, func, cfg))
+        }
+        ir::HeapViolation::Trap | ir::HeapViolation::Clamp => None,
+    };
+
     match func.heaps[heap].style {
         ir::HeapStyle::Dynamic { bound_gv } => {
-            dynamic_addr(inst, heap, offset, access_size, bound_gv, func)
-        }
-        ir::HeapStyle::Static { bound } => {
-            static_addr(inst, heap, offset, access_size, bound.into(), func, cfg)
+            dynamic_addr(inst, heap, offset, access_size, bound_gv, oob_ebb, func)
         }
+        ir::HeapStyle::Static { bound } => static_addr(
+            inst,
+            heap,
+            offset,
+            access_size,
+            bound.into(),
+            oob_ebb,
+            func,
+            cfg,
+        ),
     }
+
+    if oob_ebb.is_some() {
+        // The bounds checks branch from the EBB of `inst`.
+        let ebb = func.layout.pp_ebb(inst);
+        cfg.recompute_ebb(func, ebb);
+    }
+}
+
+/// Insert the call to the violation libcall `callee` for the `heap_addr` instruction `inst`.
+///
+/// Returns the EBB calling the libcall, which the bounds checks of `inst` branch to.
+fn insert_libcall(
+    inst: ir::Inst,
+    offset: ir::Value,
+    callee: ir::FuncRef,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+) -> ir::Ebb {
+    // Split the EBB after `inst`:
+    //
+    //     v1 = heap_addr v0
+    //     ..
+    //
+    // Becomes:
+    //
+    //     v2 = heap_addr v0
+    //     jump new_ebb_resume(v2)
+    //
+    //   new_ebb_oob:
+    //     v3 = call callee(v0)
+    //     jump new_ebb_resume(v3)
+    //
+    //   new_ebb_resume(v1):
+    //     ..
+    let old_ebb = func.layout.pp_ebb(inst);
+    let new_ebb_oob = func.dfg.make_ebb();
+    let new_ebb_resume = func.dfg.make_ebb();
+
+    // The users of `inst` now use the parameter of `new_ebb_resume`.
+    let result = func.dfg.first_result(inst);
+    let addr_ty = func.dfg.value_type(result);
+    let addr = func.dfg.replace_result(result, addr_ty);
+    func.dfg.attach_ebb_param(new_ebb_resume, result);
+
+    let mut pos = FuncCursor::new(func).after_inst(inst);
+    pos.use_srcloc(inst);
+    pos.insert_ebb(new_ebb_resume);
+
+    pos.goto_bottom(old_ebb);
+    pos.ins().jump(new_ebb_resume, &[addr]);
+
+    pos.insert_ebb(new_ebb_oob);
+    pos.func.layout.set_cold(new_ebb_oob);
+    let call = pos.ins().call(callee, &[offset]);
+    let oob_addr = pos.func.dfg.first_result(call);
+    pos.ins().jump(new_ebb_resume, &[oob_addr]);
+
+    cfg.recompute_ebb(pos.func, old_ebb);
+    cfg.recompute_ebb(pos.func, new_ebb_oob);
+    cfg.recompute_ebb(pos.func, new_ebb_resume);
+    new_ebb_oob
+}
+
+/// Emit the bounds check for the out-of-bounds condition `oob`.
+///
+/// This traps with `trap_code`, or branches to `oob_ebb` when the heap has a violation libcall.
+fn check_oob(
+    pos: &mut FuncCursor,
+    oob: ir::Value,
+    trap_code: ir::TrapCode,
+    oob_ebb: Option<ir::Ebb>,
+) {
+    match oob_ebb {
+        Some(ebb) => pos.ins().brnz(oob, ebb, &[]),
+        None => pos.ins().trapnz(oob, trap_code),
+    };
+}
+
+/// Clamp `offset` to `limit`, the largest offset where the access is in bounds.
+fn clamp_offset(pos: &mut FuncCursor, offset: ir::Value, limit: ir::Value) -> ir::Value {
+    let oob = pos.ins().icmp(IntCC::UnsignedGreaterThan, offset, limit);
+    pos.ins().select(oob, limit, offset)
 }
 
 /// Expand a `heap_addr` for a dynamic heap.
@@ -47,17 +161,34 @@ fn dynamic_addr(
     offset: ir::Value,
     access_size: u32,
     bound_gv: ir::GlobalValue,
+    oob_ebb: Option<ir::Ebb>,
     func: &mut ir::Function,
 ) {
     let access_size = u64::from(access_size);
     let offset_ty = func.dfg.value_type(offset);
     let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
     let min_size = func.heaps[heap].min_size.into();
+    let trap_code = func.heaps[heap].trap_code;
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
     // Start with the bounds check. Trap if `offset + access_size > bound`.
     let bound = pos.ins().global_value(offset_ty, bound_gv);
+
+    if pos.func.heaps[heap].violation == ir::HeapViolation::Clamp {
+        if access_size > min_size {
+            // The heap can be smaller than the access, leaving no offset to clamp to.
+            let too_small = pos
+                .ins()
+                .icmp_imm(IntCC::UnsignedLessThan, bound, access_size as i64);
+            pos.ins().trapnz(too_small, trap_code);
+        }
+        let limit = pos.ins().iadd_imm(bound, -(access_size as i64));
+        let offset = clamp_offset(&mut pos, offset, limit);
+        compute_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+        return;
+    }
+
     let oob;
     if access_size == 1 {
         // `offset > bound - 1` is the same as `offset >= bound`.
@@ -75,12 +206,12 @@ fn dynamic_addr(
         // We need an overflow check for the adjusted offset.
         let access_size_val = pos.ins().iconst(offset_ty, access_size as i64);
         let (adj_offset, overflow) = pos.ins().iadd_cout(offset, access_size_val);
-        pos.ins().trapnz(overflow, ir::TrapCode::HeapOutOfBounds);
+        check_oob(&mut pos, overflow, trap_code, oob_ebb);
         oob = pos
             .ins()
             .icmp(IntCC::UnsignedGreaterThan, adj_offset, bound);
     }
-    pos.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
+    check_oob(&mut pos, oob, trap_code, oob_ebb);
 
     compute_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
 }
@@ -92,19 +223,24 @@ fn static_addr(
     offset: ir::Value,
     access_size: u32,
     bound: u64,
+    oob_ebb: Option<ir::Ebb>,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
 ) {
     let access_size = u64::from(access_size);
     let offset_ty = func.dfg.value_type(offset);
     let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+    let trap_code = func.heaps[heap].trap_code;
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
     // Start with the bounds check. Trap if `offset + access_size > bound`.
     if access_size > bound {
-        // This will simply always trap since `offset >= 0`.
-        pos.ins().trap(ir::TrapCode::HeapOutOfBounds);
+        // This will simply always trap since `offset >= 0`. Clamping can't help either.
+        match oob_ebb {
+            Some(ebb) => pos.ins().jump(ebb, &[]),
+            None => pos.ins().trap(trap_code),
+        };
         pos.func.dfg.replace(inst).iconst(addr_ty, 0);
 
         // Split Ebb, as the trap is a terminator instruction.
@@ -119,9 +255,19 @@ fn static_addr(
     // Check `offset > limit` which is now known non-negative.
     let limit = bound - access_size;
 
-    // We may be able to omit the check entirely for 32-bit offsets if the heap bound is 4 GB or
-    // more.
-    if offset_ty != ir::types::I32 || limit < 0xffff_ffff {
+    // We may be able to omit the check entirely if every value of the index type is within the
+    // limit. This happens for 32-bit offsets when the heap bound is 4 GB or more, and for 64-bit
+    // offsets when the heap covers the whole address space.
+    if limit < pos.func.heaps[heap].max_index() {
+        if pos.func.heaps[heap].violation == ir::HeapViolation::Clamp {
+            let limit = pos.ins().iconst(offset_ty, limit as i64);
+            let offset = clamp_offset(&mut pos, offset, limit);
+            compute_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+            return;
+        }
+
+        // The immediates are reinterpreted as unsigned values by the comparisons, so limits at or
+        // above 2^63 are represented by their two's complement bit pattern.
         let oob = if limit & 1 == 1 {
             // Prefer testing `offset >= limit - 1` when limit is odd because an even number is
             // likely to be a convenient constant on ARM and other RISC architectures.
@@ -131,7 +277,7 @@ fn static_addr(
             pos.ins()
                 .icmp_imm(IntCC::UnsignedGreaterThan, offset, limit as i64)
         };
-        pos.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
+        check_oob(&mut pos, oob, trap_code, oob_ebb);
     }
 
     compute_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
@@ -149,10 +295,15 @@ fn compute_addr(
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
-    // Convert `offset` to `addr_ty`.
+    // Convert `offset` to `addr_ty`. A 64-bit offset used with 32-bit addresses has been bounds
+    // checked, or was proven in bounds by the frontend, so it fits in the address type.
     if offset_ty != addr_ty {
         let labels_value = offset;
-        offset = pos.ins().uextend(addr_ty, offset);
+        offset = if offset_ty.bits() > addr_ty.bits() {
+            pos.ins().ireduce(addr_ty, offset)
+        } else {
+            pos.ins().uextend(addr_ty, offset)
+        };
         if let Some(values_labels) = pos.func.dfg.values_labels.as_mut() {
             values_labels.insert(
                 offset,
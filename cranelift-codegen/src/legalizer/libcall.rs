@@ -1,25 +1,48 @@
 //! Expanding instructions as runtime library calls.
 
+use crate::diagnostics::DiagnosticKind;
 use crate::ir;
-use crate::ir::{get_libcall_funcref, InstBuilder};
+use crate::ir::{get_inst_libcall_funcref, get_libcall_funcref, InstBuilder};
 use crate::isa::TargetIsa;
 use crate::legalizer::boundary::legalize_libcall_signature;
 use std::vec::Vec;
 
-/// Try to expand `inst` as a library call, returning true is successful.
-pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &dyn TargetIsa) -> bool {
-    // Does the opcode/ctrl_type combo even have a well-known runtime library name.
-    let libcall = match ir::LibCall::for_inst(func.dfg[inst].opcode(), func.dfg.ctrl_typevar(inst))
-    {
-        Some(lc) => lc,
-        None => return false,
-    };
+/// Try to expand `inst` as a library call, returning a diagnostic describing the call if
+/// successful.
+///
+/// A library call registered for the instruction in the ISA's `LibCallRegistry` is preferred over
+/// the well-known `LibCall`.
+pub fn expand_as_libcall(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    isa: &dyn TargetIsa,
+) -> Option<DiagnosticKind> {
+    let opcode = func.dfg[inst].opcode();
+    let ctrl_type = func.dfg.ctrl_typevar(inst);
 
-    // Now we convert `inst` to a call. First save the arguments.
+    // Save the arguments before `inst` is converted to a call.
     let mut args = Vec::new();
     args.extend_from_slice(func.dfg.inst_args(inst));
+
+    let (funcref, kind) = match get_inst_libcall_funcref(func, inst, isa) {
+        Some(funcref) => (
+            funcref,
+            DiagnosticKind::CustomLibcall {
+                opcode,
+                ty: ctrl_type,
+            },
+        ),
+        None => {
+            // Does the opcode/ctrl_type combo even have a well-known runtime library name.
+            let libcall = ir::LibCall::for_inst(opcode, ctrl_type)?;
+            (
+                get_libcall_funcref(libcall, func, inst, isa),
+                DiagnosticKind::Libcall { opcode, libcall },
+            )
+        }
+    };
+
     // The replace builder will preserve the instruction result values.
-    let funcref = get_libcall_funcref(libcall, func, inst, isa);
     func.dfg.replace(inst).call(funcref, &args);
 
     // Ask the ISA to legalize the signature.
@@ -27,5 +50,5 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &dyn Targ
     let sig_data = &mut func.dfg.signatures[fn_data.signature];
     legalize_libcall_signature(sig_data, isa);
 
-    true
+    Some(kind)
 }
@@ -0,0 +1,160 @@
+//! Legalization of bulk memory operations.
+//!
+//! The `mem_copy` and `mem_fill` instructions are expanded as a few loads and stores when their
+//! size is a small constant, and as calls to the `Memcpy` and `Memset` library routines
+//! otherwise.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::{self, get_libcall_funcref_with_types, InstBuilder};
+use crate::isa::TargetIsa;
+use crate::legalizer::boundary::legalize_libcall_signature;
+use crate::remat_constants::iconst_value;
+use core::cmp;
+use std::vec::Vec;
+
+/// The maximum number of stores in an inline expansion. Larger operations call the library
+/// routine.
+const MAX_INLINE_STORES: u64 = 4;
+
+/// Expand a `mem_copy` instruction.
+pub fn expand_mem_copy(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    let (dest, src, size) = match func.dfg[inst] {
+        ir::InstructionData::Ternary { opcode, args } => {
+            debug_assert_eq!(opcode, ir::Opcode::MemCopy);
+            (args[0], args[1], args[2])
+        }
+        _ => panic!("Wanted mem_copy: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    match inline_accesses(&pos.func.dfg, size, isa) {
+        Some((ty, count)) => {
+            let flags = ir::MemFlags::new();
+            for i in 0..count {
+                let offset = (i * u64::from(ty.bytes())) as i32;
+                let value = pos.ins().load(ty, flags, src, offset);
+                pos.ins().store(flags, value, dest, offset);
+            }
+            pos.remove_inst();
+        }
+        None => expand_as_mem_libcall(inst, ir::LibCall::Memcpy, &[dest, src, size], &mut pos, isa),
+    }
+}
+
+/// Expand a `mem_fill` instruction.
+pub fn expand_mem_fill(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    let (dest, size, byte) = match func.dfg[inst] {
+        ir::InstructionData::Ternary { opcode, args } => {
+            debug_assert_eq!(opcode, ir::Opcode::MemFill);
+            (args[0], args[1], args[2])
+        }
+        _ => panic!("Wanted mem_fill: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    match inline_accesses(&pos.func.dfg, size, isa) {
+        Some((ty, count)) => {
+            let flags = ir::MemFlags::new();
+            let value = splat_byte(&mut pos, byte, ty);
+            for i in 0..count {
+                let offset = (i * u64::from(ty.bytes())) as i32;
+                pos.ins().store(flags, value, dest, offset);
+            }
+            pos.remove_inst();
+        }
+        None => {
+            // `memset` takes the byte as an `int`.
+            let byte = pos.ins().uextend(ir::types::I32, byte);
+            expand_as_mem_libcall(
+                inst,
+                ir::LibCall::Memset,
+                &[dest, byte, size],
+                &mut pos,
+                isa,
+            );
+        }
+    }
+}
+
+/// Get the type and number of the accesses for an inline expansion of an operation on `size`
+/// bytes, or `None` if `size` isn't a small enough constant.
+///
+/// The accesses are as wide as possible, up to the pointer width, without going past the end.
+fn inline_accesses(
+    dfg: &ir::DataFlowGraph,
+    size: ir::Value,
+    isa: &dyn TargetIsa,
+) -> Option<(ir::Type, u64)> {
+    let bytes: i64 = iconst_value(dfg, size)?.into();
+    let bytes = bytes as u64;
+    let width = cmp::min(bytes & bytes.wrapping_neg(), u64::from(isa.pointer_bytes())).max(1);
+    let count = bytes / width;
+    if count > MAX_INLINE_STORES {
+        return None;
+    }
+    Some((ir::Type::int(width as u16 * 8).unwrap(), count))
+}
+
+/// Get a value of type `ty` with `byte` in every byte.
+fn splat_byte(pos: &mut FuncCursor, byte: ir::Value, ty: ir::Type) -> ir::Value {
+    let ones = (u64::max_value() / 0xff) >> (64 - ty.bits());
+    if let Some(imm) = byte_const(&pos.func.dfg, byte) {
+        return pos.ins().iconst(ty, (u64::from(imm) * ones) as i64);
+    }
+    if ty == ir::types::I8 {
+        return byte;
+    }
+    let wide = pos.ins().uextend(ty, byte);
+    pos.ins().imul_imm(wide, ones as i64)
+}
+
+/// Get the constant value of `byte`, if known.
+///
+/// A narrow constant may already have been widened to an `ireduce` of a wider `iconst`.
+fn byte_const(dfg: &ir::DataFlowGraph, byte: ir::Value) -> Option<u8> {
+    let byte = match dfg.value_def(dfg.resolve_aliases(byte)) {
+        ir::ValueDef::Result(inst, _) => match dfg[inst] {
+            ir::InstructionData::Unary {
+                opcode: ir::Opcode::Ireduce,
+                arg,
+            } => arg,
+            _ => byte,
+        },
+        ir::ValueDef::Param(..) => byte,
+    };
+    let imm: i64 = iconst_value(dfg, byte)?.into();
+    Some(imm as u8)
+}
+
+/// Replace `inst` with a call to `libcall` with the arguments `args`.
+fn expand_as_mem_libcall(
+    inst: ir::Inst,
+    libcall: ir::LibCall,
+    args: &[ir::Value],
+    pos: &mut FuncCursor,
+    isa: &dyn TargetIsa,
+) {
+    let params: Vec<ir::Type> = args.iter().map(|&v| pos.func.dfg.value_type(v)).collect();
+    let funcref = get_libcall_funcref_with_types(libcall, pos.func, &params, &[], isa);
+    pos.func.dfg.replace(inst).call(funcref, args);
+
+    // Ask the ISA to legalize the signature.
+    let fn_data = &pos.func.dfg.ext_funcs[funcref];
+    let sig_data = &mut pos.func.dfg.signatures[fn_data.signature];
+    legalize_libcall_signature(sig_data, isa);
+}
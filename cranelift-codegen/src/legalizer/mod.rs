@@ -10,31 +10,57 @@
 //! Besides transforming instructions, the legalizer also fills out the `function.encodings` map
 //! which provides a legal encoding recipe for every instruction.
 //!
+//! Embedders that need to bound the time spent in a single call can use
+//! `legalize_function_resumable()` instead, which can stop between EBBs and pick up again later.
+//!
 //! The legalizer does not deal with register allocation constraints. These constraints are derived
 //! from the encoding recipes, and solved later by the register allocator.
 
 use crate::bitset::BitSet;
-use crate::cursor::{Cursor, FuncCursor};
+use crate::cursor::{Cursor, CursorPosition, FuncCursor};
+use crate::diagnostics::{Diagnostic, DiagnosticKind};
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::types::I32;
 use crate::ir::{self, InstBuilder, MemFlags};
 use crate::isa::TargetIsa;
 use crate::predicates;
 use crate::timing;
+use std::vec::Vec;
+use target_lexicon::Endianness;
 
 mod boundary;
 mod call;
 mod globalvalue;
 mod heap;
 mod libcall;
+mod memory;
 mod split;
 mod table;
+mod vector;
 
 use self::call::expand_call;
 use self::globalvalue::expand_global_value;
 use self::heap::expand_heap_addr;
 use self::libcall::expand_as_libcall;
-use self::table::expand_table_addr;
+use self::memory::{expand_mem_copy, expand_mem_fill};
+use self::table::{expand_table_addr, expand_table_fill, expand_table_grow};
+use self::vector::{
+    narrow_bitselect, narrow_fma, narrow_fsplat, narrow_iminmax, narrow_vselect,
+    split_vector_compare,
+};
+
+pub(crate) use self::boundary::legalize_libcall_signature;
+
+/// Check if `opcode` is in a bitset of opcodes generated by `gen_legalizer.rs`.
+///
+/// Every transform group has such a set containing the opcodes that the group or any of the
+/// groups it chains to can legalize. The generated group functions use it to bail out early
+/// instead of trying each group in the chain.
+#[inline]
+pub(crate) fn opcode_set_contains(set: &[u64], opcode: ir::Opcode) -> bool {
+    let n = opcode as usize;
+    set[n / 64] & (1 << (n % 64)) != 0
+}
 
 /// Legalize `inst` for `isa`. Return true if any changes to the code were
 /// made; return false if the instruction was successfully encoded as is.
@@ -43,6 +69,7 @@ fn legalize_inst(
     pos: &mut FuncCursor,
     cfg: &mut ControlFlowGraph,
     isa: &dyn TargetIsa,
+    diagnostics: &mut Vec<Diagnostic>,
 ) -> bool {
     let opcode = pos.func.dfg[inst].opcode();
 
@@ -68,13 +95,36 @@ fn legalize_inst(
             // expand further.
             // There's a risk of infinite looping here if the legalization patterns are
             // unsound. Should we attempt to detect that?
+            let srcloc = pos.func.srclocs[inst];
             if action(inst, pos.func, cfg, isa) {
+                if opcode == ir::Opcode::BrTable && !isa.flags().jump_tables_enabled() {
+                    diagnostics.push(Diagnostic {
+                        srcloc,
+                        kind: DiagnosticKind::CompareChain,
+                    });
+                }
+                return true;
+            }
+
+            // Vector comparisons can always be split into smaller vectors.
+            let ty = pos.func.dfg.ctrl_typevar(inst);
+            if split_vector_compare(inst, pos.func, cfg) {
+                diagnostics.push(Diagnostic {
+                    srcloc,
+                    kind: DiagnosticKind::SplitVector { opcode, ty },
+                });
                 return true;
             }
 
             // We don't have any pattern expansion for this instruction either.
             // Try converting it to a library call as a last resort.
-            expand_as_libcall(inst, pos.func, isa)
+            match expand_as_libcall(inst, pos.func, isa) {
+                Some(kind) => {
+                    diagnostics.push(Diagnostic { srcloc, kind });
+                    true
+                }
+                None => false,
+            }
         }
     }
 }
@@ -85,14 +135,114 @@ fn legalize_inst(
 /// - Fill out `func.encodings`.
 ///
 pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, isa: &dyn TargetIsa) {
+    legalize_function_with_diagnostics(func, cfg, isa, &mut Vec::new())
+}
+
+/// Legalize `func` for `isa` like `legalize_function`, and append notes about instructions that
+/// were lowered to slow code sequences to `diagnostics`.
+pub(crate) fn legalize_function_with_diagnostics(
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut progress = LegalizeProgress::new();
+    resume_legalization(func, cfg, isa, &mut progress, diagnostics, |_| {
+        LegalizeControl::Continue
+    });
+    debug_assert!(progress.is_done());
+}
+
+/// Tells `legalize_function_resumable()` whether to keep going after legalizing an EBB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LegalizeControl {
+    /// Go on with the next EBB.
+    Continue,
+    /// Return to the caller, which can resume the legalization later.
+    Stop,
+}
+
+/// How far a resumable legalization has progressed.
+///
+/// A fresh `LegalizeProgress` starts at the beginning of the function. It is updated by each call
+/// to `legalize_function_resumable()`, which picks up where the previous call stopped.
+#[derive(Clone, Copy, Debug)]
+pub struct LegalizeProgress {
+    /// The cursor position after the last legalized EBB, or `None` if nothing has been done yet.
+    position: Option<CursorPosition>,
+    done: bool,
+}
+
+impl LegalizeProgress {
+    /// Create a progress marker for a legalization that hasn't started.
+    pub fn new() -> Self {
+        Self {
+            position: None,
+            done: false,
+        }
+    }
+
+    /// Has the whole function been legalized?
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+/// Legalize `func` for `isa` like `legalize_function`, but call `control` after each EBB and
+/// return early when it asks to stop.
+///
+/// Returns true once the whole function has been legalized. Otherwise, `progress` records where
+/// to resume, and the function must be passed again unchanged along with the same `cfg`. A
+/// legalization that is never resumed leaves `func` partially legalized, so a cancelled
+/// compilation must discard it.
+pub fn legalize_function_resumable<F>(
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+    progress: &mut LegalizeProgress,
+    control: F,
+) -> bool
+where
+    F: FnMut(ir::Ebb) -> LegalizeControl,
+{
+    resume_legalization(func, cfg, isa, progress, &mut Vec::new(), control);
+    progress.is_done()
+}
+
+/// Legalize EBBs starting after the position recorded in `progress`, until `control` returns
+/// `Stop` or the whole function is done.
+fn resume_legalization<F>(
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+    progress: &mut LegalizeProgress,
+    diagnostics: &mut Vec<Diagnostic>,
+    mut control: F,
+) where
+    F: FnMut(ir::Ebb) -> LegalizeControl,
+{
+    if progress.done {
+        return;
+    }
+
     let _tt = timing::legalize();
     debug_assert!(cfg.is_valid());
 
-    boundary::legalize_signatures(func, isa);
+    let start = match progress.position {
+        Some(position) => position,
+        None => {
+            boundary::legalize_signatures(func, isa);
+            func.encodings.resize(func.dfg.num_insts());
+            CursorPosition::Nowhere
+        }
+    };
 
-    func.encodings.resize(func.dfg.num_insts());
+    // Expansions that insert control flow recompute the CFG of the EBBs they touch. Many of them
+    // split the same EBB repeatedly, so only recompute the changed EBBs once.
+    cfg.begin_batch();
 
-    let mut pos = FuncCursor::new(func);
+    let mut pos = FuncCursor::new(func).at_position(start);
+    let mut stopped = false;
 
     // Process EBBs in layout order. Some legalization actions may split the current EBB or append
     // new ones to the end. We need to make sure we visit those new EBBs too.
@@ -102,7 +252,7 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
         let mut prev_pos = pos.position();
 
         while let Some(inst) = pos.next_inst() {
-            if legalize_inst(inst, &mut pos, cfg, isa) {
+            if legalize_inst(inst, &mut pos, cfg, isa, diagnostics) {
                 // Go back and legalize the inserted return value conversion instructions.
                 pos.set_position(prev_pos);
             } else {
@@ -110,7 +260,28 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
                 prev_pos = pos.position();
             }
         }
+
+        // The cursor is now after the last EBB that was legalized, which may not be the one we
+        // started with if it was split. There is nothing to resume after the last EBB.
+        let done_ebb = pos.current_ebb().expect("cursor left the layout");
+        if control(done_ebb) == LegalizeControl::Stop
+            && pos.func.layout.next_ebb(done_ebb).is_some()
+        {
+            stopped = true;
+            break;
+        }
+    }
+
+    cfg.end_batch(pos.func);
+
+    if stopped {
+        progress.position = Some(pos.position());
+        return;
     }
+    progress.done = true;
+
+    // The narrowed code can still split values right after concatenating them.
+    split::eliminate_split_pairs(pos.func);
 
     // Now that we've lowered all br_tables, we don't need the jump tables anymore.
     if !isa.flags().jump_tables_enabled() {
@@ -178,8 +349,10 @@ fn expand_cond_trap(
     pos.use_srcloc(inst);
     pos.ins().jump(new_ebb_trap, &[]);
 
-    // Insert the new label and the unconditional trap terminator.
+    // Insert the new label and the unconditional trap terminator. The trap is rarely taken, so
+    // the EBB is moved out of the way by branch relaxation.
     pos.insert_ebb(new_ebb_trap);
+    pos.func.layout.set_cold(new_ebb_trap);
     pos.ins().trap(code);
 
     // Insert the new label and resume the execution when the trap fails.
@@ -326,7 +499,8 @@ fn expand_br_table_conds(
 
 /// Expand the select instruction.
 ///
-/// Conditional moves are available in some ISAs for some register classes. The remaining selects
+/// Conditional moves are available in some ISAs for some register classes. Selects on the sign of
+/// an integer are turned into arithmetic on a sign mask when possible, and the remaining selects
 /// are handled by a branch.
 fn expand_select(
     inst: ir::Inst,
@@ -342,6 +516,10 @@ fn expand_select(
         _ => panic!("Expected select: {}", func.dfg.display_inst(inst, None)),
     };
 
+    if expand_select_sign_mask(inst, func, ctrl, tval, fval) {
+        return;
+    }
+
     // Replace `result = select ctrl, tval, fval` with:
     //
     //   brnz ctrl, new_ebb(tval)
@@ -363,6 +541,108 @@ fn expand_select(
     cfg.recompute_ebb(pos.func, old_ebb);
 }
 
+/// Expand `select ctrl, tval, fval` without a branch when `ctrl` tests the sign of an integer `x`
+/// of the same type as the selected values. With `m = sshr_imm x, bits - 1`, which is all ones
+/// when `x` is negative and zero otherwise:
+///
+/// - `select (x < 0), (0 - y), y` becomes `(y ^ m) - m`. This is `abs` when `x` is `y`.
+/// - `select (x < 0), y, (0 - y)` becomes `m - (y ^ m)`.
+/// - `select (x < 0), y, 0` becomes `band y, m`.
+/// - `select (x < 0), 0, y` becomes `band_not y, m`.
+///
+/// Returns false if `ctrl` and the selected values don't match one of these patterns.
+fn expand_select_sign_mask(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    ctrl: ir::Value,
+    tval: ir::Value,
+    fval: ir::Value,
+) -> bool {
+    use crate::ir::condcodes::IntCC;
+
+    let (cond, x, imm): (_, _, i64) = match value_inst_data(&func.dfg, ctrl) {
+        Some(&ir::InstructionData::IntCompareImm {
+            opcode: ir::Opcode::IcmpImm,
+            cond,
+            arg,
+            imm,
+        }) => (cond, arg, imm.into()),
+        _ => return false,
+    };
+    // Order the selected values as (value when `x` is negative, value when it isn't).
+    let (neg_val, pos_val) = match (cond, imm) {
+        (IntCC::SignedLessThan, 0) | (IntCC::SignedLessThanOrEqual, -1) => (tval, fval),
+        (IntCC::SignedGreaterThanOrEqual, 0) | (IntCC::SignedGreaterThan, -1) => (fval, tval),
+        _ => return false,
+    };
+    let neg_val = func.dfg.resolve_aliases(neg_val);
+    let pos_val = func.dfg.resolve_aliases(pos_val);
+
+    let ty = func.dfg.value_type(x);
+    if !ty.is_int() || ty.is_vector() || func.dfg.ctrl_typevar(inst) != ty {
+        return false;
+    }
+
+    let negate_if_neg = negated_value(&func.dfg, neg_val) == Some(pos_val);
+    let negate_if_pos = negated_value(&func.dfg, pos_val) == Some(neg_val);
+    let zero_if_pos = is_zero_constant(&func.dfg, pos_val);
+    let zero_if_neg = is_zero_constant(&func.dfg, neg_val);
+    if !(negate_if_neg || negate_if_pos || zero_if_pos || zero_if_neg) {
+        return false;
+    }
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let mask = pos.ins().sshr_imm(x, i64::from(ty.lane_bits() - 1));
+    if negate_if_neg {
+        let flipped = pos.ins().bxor(pos_val, mask);
+        pos.func.dfg.replace(inst).isub(flipped, mask);
+    } else if negate_if_pos {
+        let flipped = pos.ins().bxor(neg_val, mask);
+        pos.func.dfg.replace(inst).isub(mask, flipped);
+    } else if zero_if_pos {
+        pos.func.dfg.replace(inst).band(neg_val, mask);
+    } else {
+        pos.func.dfg.replace(inst).band_not(pos_val, mask);
+    }
+    true
+}
+
+/// Get the data of the instruction defining `value`, if it isn't an EBB parameter.
+fn value_inst_data(dfg: &ir::DataFlowGraph, value: ir::Value) -> Option<&ir::InstructionData> {
+    match dfg.value_def(value) {
+        ir::ValueDef::Result(inst, _) => Some(&dfg[inst]),
+        ir::ValueDef::Param(..) => None,
+    }
+}
+
+/// If `value` is computed as `0 - y`, return `y`.
+fn negated_value(dfg: &ir::DataFlowGraph, value: ir::Value) -> Option<ir::Value> {
+    match *value_inst_data(dfg, value)? {
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Isub,
+            args,
+        } if is_zero_constant(dfg, args[0]) => Some(dfg.resolve_aliases(args[1])),
+        ir::InstructionData::BinaryImm {
+            opcode: ir::Opcode::IrsubImm,
+            arg,
+            imm,
+        } if imm == ir::immediates::Imm64::new(0) => Some(dfg.resolve_aliases(arg)),
+        _ => None,
+    }
+}
+
+/// Is `value` an integer constant zero?
+fn is_zero_constant(dfg: &ir::DataFlowGraph, value: ir::Value) -> bool {
+    match value_inst_data(dfg, value) {
+        Some(&ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        }) => imm == ir::immediates::Imm64::new(0),
+        _ => false,
+    }
+}
+
 fn expand_br_icmp(
     inst: ir::Inst,
     func: &mut ir::Function,
@@ -397,6 +677,52 @@ fn expand_br_icmp(
     cfg.recompute_ebb(pos.func, old_ebb);
 }
 
+/// Widen the controlling argument of an `i8` or `i16` `brz` or `brnz` instruction.
+fn widen_cond_branch(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Zero extension preserves the comparison against zero.
+    let arg = pos.func.dfg.inst_args(inst)[0];
+    let wide = pos.ins().uextend(I32, arg);
+    pos.func.dfg.inst_args_mut(inst)[0] = wide;
+}
+
+/// Expand a `brff` instruction whose condition code can't be tested directly on the flags.
+///
+/// The condition is computed as a `b1` value with `trueff`, which can be legalized further.
+fn expand_brff(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (cond, flags, destination, ebb_args) = match func.dfg[inst] {
+        ir::InstructionData::BranchFloat {
+            cond,
+            destination,
+            ref args,
+            ..
+        } => (
+            cond,
+            args.get(0, &func.dfg.value_lists).unwrap(),
+            destination,
+            args.as_slice(&func.dfg.value_lists)[1..].to_vec(),
+        ),
+        _ => panic!("Expected brff {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let cmp = pos.ins().trueff(cond, flags);
+    pos.func.dfg.replace(inst).brnz(cmp, destination, &ebb_args);
+}
+
 /// Expand illegal `f32const` and `f64const` instructions.
 fn expand_fconst(
     inst: ir::Inst,
@@ -425,6 +751,44 @@ fn expand_fconst(
     pos.func.dfg.replace(inst).bitcast(ty, ival);
 }
 
+/// Expand an `fma` instruction that the target can't encode.
+///
+/// When `enable_float_contraction` is set, the product is computed and rounded by a separate
+/// `fmul`. Otherwise, the exact result is computed by a call to the `fmaf` or `fma` library
+/// function.
+fn expand_fma(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    let ty = func.dfg.ctrl_typevar(inst);
+    debug_assert!(
+        !ty.is_vector(),
+        "Vector fma should be narrowed by narrow_fma: {}",
+        ty
+    );
+
+    if !isa.flags().enable_float_contraction() {
+        expand_as_libcall(inst, func, isa)
+            .unwrap_or_else(|| panic!("No library call for fma.{}", ty));
+        return;
+    }
+
+    let (x, y, z) = match func.dfg[inst] {
+        ir::InstructionData::Ternary {
+            opcode: ir::Opcode::Fma,
+            args,
+        } => (args[0], args[1], args[2]),
+        _ => panic!("Expected fma: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let product = pos.ins().fmul(x, y);
+    pos.func.dfg.replace(inst).fadd(product, z);
+}
+
 /// Expand illegal `stack_load` instructions.
 fn expand_stack_load(
     inst: ir::Inst,
@@ -490,3 +854,301 @@ fn expand_stack_store(
     mflags.set_aligned();
     pos.func.dfg.replace(inst).store(mflags, val, addr, 0);
 }
+
+/// Expand illegal `bitcast` instructions.
+///
+/// A bitcast is equivalent to storing the input and loading the output type from the same
+/// address, so bitcasts without an encoding go through a stack slot. Vectors are stored and loaded
+/// one half at a time, and so are integers wider than a pointer. This works for types the target
+/// only supports after splitting, like `i64` on 32-bit targets.
+fn expand_bitcast(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    let arg = func.dfg.inst_args(inst)[0];
+    let result = func.dfg.first_result(inst);
+    let ty = func.dfg.value_type(result);
+    debug_assert_eq!(
+        func.dfg.value_type(arg).bytes(),
+        ty.bytes(),
+        "Bitcast changes the size: {}",
+        func.dfg.display_inst(inst, None)
+    );
+
+    let slot = func.create_stack_slot(ir::StackSlotData::new(
+        ir::StackSlotKind::ExplicitSlot,
+        ty.bytes(),
+    ));
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let mut bitcast = Bitcast {
+        pos: &mut pos,
+        isa,
+        slot,
+        big_endian: isa.triple().endianness() == Ok(Endianness::Big),
+    };
+    bitcast.store(cfg, arg, 0);
+    let value = bitcast.load(ty, 0);
+
+    pos.func.dfg.clear_results(inst);
+    pos.func.dfg.change_to_alias(result, value);
+    pos.remove_inst();
+}
+
+/// State for moving a value through a stack slot in `expand_bitcast()`.
+struct Bitcast<'a, 'f: 'a> {
+    pos: &'a mut FuncCursor<'f>,
+    isa: &'a dyn TargetIsa,
+    slot: ir::StackSlot,
+    big_endian: bool,
+}
+
+impl<'a, 'f> Bitcast<'a, 'f> {
+    /// Get the type of the halves that a value of type `ty` must be stored and loaded as, if any.
+    fn half_type(&self, ty: ir::Type) -> Option<ir::Type> {
+        if ty.is_vector() {
+            ty.half_vector()
+        } else if ty.is_int() && ty.bits() > u16::from(self.isa.pointer_bits()) {
+            ty.half_width()
+        } else {
+            None
+        }
+    }
+
+    /// Are the halves of a value of type `ty` stored with the high half first?
+    fn high_first(&self, ty: ir::Type) -> bool {
+        // Vector lanes are always stored in increasing order.
+        self.big_endian && !ty.is_vector()
+    }
+
+    /// Store `value` at `offset` in the stack slot.
+    fn store(&mut self, cfg: &mut ControlFlowGraph, value: ir::Value, offset: i32) {
+        let ty = self.pos.func.dfg.value_type(value);
+        let half = match self.half_type(ty) {
+            Some(half) => half,
+            None => {
+                self.pos.ins().stack_store(value, self.slot, offset);
+                return;
+            }
+        };
+
+        let curpos = self.pos.position();
+        let srcloc = self.pos.srcloc();
+        let (lo, hi) = if ty.is_vector() {
+            split::vsplit(self.pos.func, cfg, curpos, srcloc, value)
+        } else {
+            split::isplit(self.pos.func, cfg, curpos, srcloc, value)
+        };
+        let (first, second) = if self.high_first(ty) {
+            (hi, lo)
+        } else {
+            (lo, hi)
+        };
+        self.store(cfg, first, offset);
+        self.store(cfg, second, offset + half.bytes() as i32);
+    }
+
+    /// Load a value of type `ty` from `offset` in the stack slot.
+    fn load(&mut self, ty: ir::Type, offset: i32) -> ir::Value {
+        let half = match self.half_type(ty) {
+            Some(half) => half,
+            None => return self.pos.ins().stack_load(ty, self.slot, offset),
+        };
+
+        let first = self.load(half, offset);
+        let second = self.load(half, offset + half.bytes() as i32);
+        if ty.is_vector() {
+            self.pos.ins().vconcat(first, second)
+        } else if self.high_first(ty) {
+            self.pos.ins().iconcat(second, first)
+        } else {
+            self.pos.ins().iconcat(first, second)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::{
+        legalize_function, legalize_function_resumable, legalize_inst, LegalizeControl,
+        LegalizeProgress,
+    };
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::flowgraph::ControlFlowGraph;
+    use crate::ir::types::I32;
+    use crate::ir::{AbiParam, Function, InstBuilder, Opcode, Type, Value};
+    use crate::settings::{self, Configurable};
+    use crate::test_utils;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    /// Legalize the instructions inserted by `build` for the ISA described by `triple`, with the
+    /// shared boolean settings in `enable` turned on.
+    ///
+    /// The instructions are inserted in an EBB with parameters of the types in `params`, followed
+    /// by a `return`. Returns the legalized instructions, not including the `return`, formatted
+    /// without encodings.
+    fn legalize<F>(triple: &str, enable: &[&str], params: &[Type], build: F) -> Vec<String>
+    where
+        F: FnOnce(&mut FuncCursor, &[Value]),
+    {
+        let mut flag_builder = settings::builder();
+        for name in enable {
+            flag_builder.enable(name).unwrap();
+        }
+        let isa = test_utils::isa_with_flags(triple, flag_builder);
+
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let args: Vec<Value> = params
+            .iter()
+            .map(|&ty| func.dfg.append_ebb_param(ebb, ty))
+            .collect();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb);
+            build(&mut pos, &args);
+            pos.ins().return_(&[]);
+        }
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        func.encodings.resize(func.dfg.num_insts());
+        let mut pos = FuncCursor::new(&mut func);
+        while let Some(_ebb) = pos.next_ebb() {
+            let mut prev_pos = pos.position();
+            while let Some(inst) = pos.next_inst() {
+                if legalize_inst(inst, &mut pos, &mut cfg, &*isa, &mut Vec::new()) {
+                    pos.set_position(prev_pos);
+                } else {
+                    prev_pos = pos.position();
+                }
+            }
+        }
+
+        let mut insts = Vec::new();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if func.dfg[inst].opcode() != Opcode::Return {
+                    insts.push(func.dfg.display_inst(inst, None).to_string());
+                }
+            }
+        }
+        insts
+    }
+
+    #[test]
+    fn legal_inst() {
+        let insts = legalize("x86_64", &[], &[I32, I32], |pos, args| {
+            pos.ins().iadd(args[0], args[1]);
+        });
+        assert_eq!(insts, ["v2 = iadd.i32 v0, v1"]);
+    }
+
+    #[test]
+    fn band_not() {
+        let insts = legalize("x86_64", &[], &[I32, I32], |pos, args| {
+            pos.ins().band_not(args[0], args[1]);
+        });
+        assert_eq!(insts, ["v3 = bnot.i32 v1", "v2 = band.i32 v0, v3"]);
+    }
+
+    #[test]
+    fn udiv_imm() {
+        let insts = legalize("x86_64", &[], &[I32], |pos, args| {
+            pos.ins().udiv_imm(args[0], 7);
+        });
+        assert_eq!(
+            insts,
+            [
+                "v2 = iconst.i32 7",
+                "v3 = iconst.i32 0",
+                "v1, v4 = x86_udivmodx.i32 v0, v3, v2",
+            ]
+        );
+    }
+
+    #[test]
+    fn udiv_native_trap() {
+        let insts = legalize("x86_64", &[], &[I32, I32], |pos, args| {
+            pos.ins().udiv(args[0], args[1]);
+        });
+        assert_eq!(
+            insts,
+            ["v3 = iconst.i32 0", "v2, v4 = x86_udivmodx.i32 v0, v3, v1"]
+        );
+    }
+
+    #[test]
+    fn udiv_explicit_trap() {
+        let insts = legalize("x86_64", &["avoid_div_traps"], &[I32, I32], |pos, args| {
+            pos.ins().udiv(args[0], args[1]);
+        });
+        assert_eq!(
+            insts,
+            [
+                "v5 = ifcmp_imm.i32 v1, 0",
+                "trapif eq v5, int_divz",
+                "v3 = iconst.i32 0",
+                "v2, v4 = x86_udivmodx.i32 v0, v3, v1",
+            ]
+        );
+    }
+
+    #[test]
+    fn resumable() {
+        let isa = test_utils::isa("x86_64");
+
+        // Three EBBs in a chain, each with an instruction that needs to be expanded.
+        let make_func = || {
+            let mut func = Function::new();
+            func.signature.params.push(AbiParam::new(I32));
+            func.signature.params.push(AbiParam::new(I32));
+            let ebbs = [
+                func.dfg.make_ebb(),
+                func.dfg.make_ebb(),
+                func.dfg.make_ebb(),
+            ];
+            let x = func.dfg.append_ebb_param(ebbs[0], I32);
+            let y = func.dfg.append_ebb_param(ebbs[0], I32);
+            {
+                let mut pos = FuncCursor::new(&mut func);
+                for (i, &ebb) in ebbs.iter().enumerate() {
+                    pos.insert_ebb(ebb);
+                    pos.ins().band_not(x, y);
+                    match ebbs.get(i + 1) {
+                        Some(&next) => pos.ins().jump(next, &[]),
+                        None => pos.ins().return_(&[]),
+                    };
+                }
+            }
+            (func, ebbs)
+        };
+
+        let (mut expected, _) = make_func();
+        let mut cfg = ControlFlowGraph::with_function(&expected);
+        legalize_function(&mut expected, &mut cfg, &*isa);
+
+        let (mut func, ebbs) = make_func();
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        let mut progress = LegalizeProgress::new();
+        let mut visited = Vec::new();
+        let mut calls = 1;
+        while !legalize_function_resumable(&mut func, &mut cfg, &*isa, &mut progress, |ebb| {
+            visited.push(ebb);
+            LegalizeControl::Stop
+        }) {
+            calls += 1;
+        }
+
+        // There is nothing left to resume after the last EBB.
+        assert_eq!(calls, 3);
+        assert_eq!(visited, ebbs);
+        assert!(progress.is_done());
+        assert_eq!(
+            func.display(&*isa).to_string(),
+            expected.display(&*isa).to_string()
+        );
+    }
+}
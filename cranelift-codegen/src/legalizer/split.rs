@@ -74,7 +74,7 @@ use std::vec::Vec;
 /// if possible.
 pub fn isplit(
     func: &mut ir::Function,
-    cfg: &ControlFlowGraph,
+    cfg: &mut ControlFlowGraph,
     pos: CursorPosition,
     srcloc: ir::SourceLoc,
     value: Value,
@@ -86,7 +86,7 @@ pub fn isplit(
 /// possible.
 pub fn vsplit(
     func: &mut ir::Function,
-    cfg: &ControlFlowGraph,
+    cfg: &mut ControlFlowGraph,
     pos: CursorPosition,
     srcloc: ir::SourceLoc,
     value: Value,
@@ -114,7 +114,7 @@ struct Repair {
 /// Generic version of `isplit` and `vsplit` controlled by the `concat` opcode.
 fn split_any(
     func: &mut ir::Function,
-    cfg: &ControlFlowGraph,
+    cfg: &mut ControlFlowGraph,
     pos: CursorPosition,
     srcloc: ir::SourceLoc,
     value: Value,
@@ -124,6 +124,11 @@ fn split_any(
     let pos = &mut FuncCursor::new(func).at_position(pos).with_srcloc(srcloc);
     let result = split_value(pos, value, concat, &mut repairs);
 
+    // The repairs below need the predecessors of EBBs.
+    if !repairs.is_empty() {
+        cfg.flush(pos.func);
+    }
+
     // We have split the value requested, and now we may need to fix some EBB predecessors.
     while let Some(repair) = repairs.pop() {
         for BasicBlock { inst, .. } in cfg.pred_iter(repair.ebb) {
@@ -343,3 +348,88 @@ pub fn simplify_branch_arguments(dfg: &mut ir::DataFlowGraph, branch: Inst) {
 
     dfg.inst_args_mut(branch).copy_from_slice(&new_args);
 }
+
+/// Cancel the `isplit` and `iconcat` pairs that are left in `func` after legalization.
+///
+/// The narrowing expansions reuse the operands of an `iconcat` when they need to split its
+/// result, but an `isplit` inserted before the corresponding `iconcat` was created remains in the
+/// code, typically when an EBB argument is split on a loop back edge. This removes:
+///
+/// - `isplit` instructions whose argument is defined by an `iconcat`. The results are replaced by
+///   the `iconcat` operands.
+/// - `iconcat` instructions whose operands are the two halves of the same `isplit`. The result is
+///   replaced by the `isplit` argument.
+///
+/// The replaced values become aliases, which are resolved in the arguments of all instructions
+/// when anything changed.
+pub fn eliminate_split_pairs(func: &mut ir::Function) {
+    let mut changed = false;
+    let mut pos = FuncCursor::new(func);
+    while let Some(_ebb) = pos.next_ebb() {
+        while let Some(inst) = pos.next_inst() {
+            let replacements = match pos.func.dfg[inst] {
+                InstructionData::Unary {
+                    opcode: Opcode::Isplit,
+                    arg,
+                } => match concat_args(&pos.func.dfg, arg) {
+                    Some((lo, hi)) => vec![lo, hi],
+                    None => continue,
+                },
+                InstructionData::Binary {
+                    opcode: Opcode::Iconcat,
+                    args,
+                } => match split_arg(&pos.func.dfg, args[0], args[1]) {
+                    Some(value) => vec![value],
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            let results = pos.func.dfg.inst_results(inst).to_vec();
+            pos.func.dfg.clear_results(inst);
+            for (result, replacement) in results.into_iter().zip(replacements) {
+                pos.func.dfg.change_to_alias(result, replacement);
+            }
+            pos.remove_inst_and_step_back();
+            changed = true;
+        }
+    }
+
+    if changed {
+        let mut pos = FuncCursor::new(func);
+        while let Some(_ebb) = pos.next_ebb() {
+            while let Some(inst) = pos.next_inst() {
+                pos.func.dfg.resolve_aliases_in_arguments(inst);
+            }
+        }
+    }
+}
+
+/// Get the operands of the `iconcat` instruction defining `value`, if any.
+fn concat_args(dfg: &ir::DataFlowGraph, value: Value) -> Option<(Value, Value)> {
+    if let ValueDef::Result(inst, _) = dfg.value_def(dfg.resolve_aliases(value)) {
+        if let InstructionData::Binary {
+            opcode: Opcode::Iconcat,
+            args,
+        } = dfg[inst]
+        {
+            return Some((args[0], args[1]));
+        }
+    }
+    None
+}
+
+/// Get the argument of the `isplit` instruction producing `lo` and `hi` as its two results, if
+/// any.
+fn split_arg(dfg: &ir::DataFlowGraph, lo: Value, hi: Value) -> Option<Value> {
+    let lo = dfg.resolve_aliases(lo);
+    let hi = dfg.resolve_aliases(hi);
+    match (dfg.value_def(lo), dfg.value_def(hi)) {
+        (ValueDef::Result(split, 0), ValueDef::Result(split_hi, 1))
+            if split == split_hi && dfg[split].opcode() == Opcode::Isplit =>
+        {
+            Some(dfg.inst_args(split)[0])
+        }
+        _ => None,
+    }
+}
@@ -2,13 +2,18 @@
 //!
 //! This module exports the `expand_table_addr` function which transforms a `table_addr`
 //! instruction into code that depends on the kind of table referenced.
+//!
+//! The `table_grow` and `table_fill` instructions are expanded as calls to the `TableGrow` and
+//! `TableFill` library routines, which take the id of the table before the instruction operands.
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::condcodes::IntCC;
 use crate::ir::immediates::Offset32;
-use crate::ir::{self, InstBuilder};
+use crate::ir::{self, get_libcall_funcref_with_types, InstBuilder};
 use crate::isa::TargetIsa;
+use crate::legalizer::boundary::legalize_libcall_signature;
+use std::vec::Vec;
 
 /// Expand a `table_addr` instruction according to the definition of the table.
 pub fn expand_table_addr(
@@ -18,19 +23,28 @@ pub fn expand_table_addr(
     _isa: &dyn TargetIsa,
 ) {
     // Unpack the instruction.
-    let (table, index, element_offset) = match func.dfg[inst] {
+    let (flags, table, index, element_offset) = match func.dfg[inst] {
         ir::InstructionData::TableAddr {
             opcode,
+            flags,
             table,
             arg,
             offset,
         } => {
             debug_assert_eq!(opcode, ir::Opcode::TableAddr);
-            (table, arg, offset)
+            (flags, table, arg, offset)
         }
         _ => panic!("Wanted table_addr: {}", func.dfg.display_inst(inst, None)),
     };
 
+    // The frontend has proven that this access is in bounds, so skip the bounds check.
+    if flags.notrap() {
+        let index_ty = func.dfg.value_type(index);
+        let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+        compute_addr(inst, table, addr_ty, index, index_ty, element_offset, func);
+        return;
+    }
+
     dynamic_addr(inst, table, index, element_offset, func);
 }
 
@@ -43,6 +57,7 @@ fn dynamic_addr(
     func: &mut ir::Function,
 ) {
     let bound_gv = func.tables[table].bound_gv;
+    let trap_code = func.tables[table].trap_code;
     let index_ty = func.dfg.value_type(index);
     let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
     let mut pos = FuncCursor::new(func).at_inst(inst);
@@ -55,7 +70,7 @@ fn dynamic_addr(
     let oob = pos
         .ins()
         .icmp(IntCC::UnsignedGreaterThanOrEqual, index, bound);
-    pos.ins().trapnz(oob, ir::TrapCode::TableOutOfBounds);
+    pos.ins().trapnz(oob, trap_code);
 
     compute_addr(
         inst,
@@ -111,3 +126,65 @@ fn compute_addr(
         pos.func.dfg.replace(inst).iadd_imm(offset, imm);
     }
 }
+
+/// Expand a `table_grow` instruction as a call to the `TableGrow` library routine.
+pub fn expand_table_grow(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    expand_as_table_libcall(inst, ir::LibCall::TableGrow, func, isa);
+}
+
+/// Expand a `table_fill` instruction as a call to the `TableFill` library routine.
+pub fn expand_table_fill(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    expand_as_table_libcall(inst, ir::LibCall::TableFill, func, isa);
+}
+
+/// Replace `inst` with a call to `libcall`, passing the table id followed by the arguments of
+/// `inst`.
+fn expand_as_table_libcall(
+    inst: ir::Inst,
+    libcall: ir::LibCall,
+    func: &mut ir::Function,
+    isa: &dyn TargetIsa,
+) {
+    let table = match func.dfg[inst] {
+        ir::InstructionData::TableGrow { table, .. }
+        | ir::InstructionData::TableFill { table, .. } => table,
+        _ => panic!(
+            "Wanted table_grow or table_fill: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+    let id: u32 = func.tables[table].id.into();
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let mut args = Vec::new();
+    args.push(pos.ins().iconst(ir::types::I32, i64::from(id)));
+    args.extend_from_slice(pos.func.dfg.inst_args(inst));
+    let params: Vec<ir::Type> = args.iter().map(|&v| pos.func.dfg.value_type(v)).collect();
+    let returns: Vec<ir::Type> = pos
+        .func
+        .dfg
+        .inst_results(inst)
+        .iter()
+        .map(|&v| pos.func.dfg.value_type(v))
+        .collect();
+
+    // The replace builder will preserve the instruction result values.
+    let funcref = get_libcall_funcref_with_types(libcall, pos.func, &params, &returns, isa);
+    pos.func.dfg.replace(inst).call(funcref, &args);
+
+    // Ask the ISA to legalize the signature.
+    let fn_data = &pos.func.dfg.ext_funcs[funcref];
+    let sig_data = &mut pos.func.dfg.signatures[fn_data.signature];
+    legalize_libcall_signature(sig_data, isa);
+}
@@ -0,0 +1,278 @@
+//! Splitting vector instructions that the target can't encode.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::condcodes::IntCC;
+use crate::ir::{self, InstBuilder};
+use crate::isa::TargetIsa;
+use crate::legalizer::split::vsplit;
+
+/// Try to split the vector comparison `inst` into two comparisons of half the number of lanes,
+/// returning true if successful.
+///
+/// The halves are split again when they are legalized, so a target without any vector
+/// comparisons ends up comparing the lanes one by one. A scalar `b1` result is extended to the
+/// boolean lane type of the vector, which makes each lane all ones or all zeros.
+pub fn split_vector_compare(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+) -> bool {
+    let data = func.dfg[inst].clone();
+    let (x, y) = match data {
+        ir::InstructionData::IntCompare { args, .. }
+        | ir::InstructionData::FloatCompare { args, .. } => (args[0], args[1]),
+        _ => return false,
+    };
+    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+    let half_ty = match ty.half_vector() {
+        Some(ty) => ty,
+        None => return false,
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (xl, xh) = vsplit(pos.func, cfg, curpos, srcloc, x);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (yl, yh) = vsplit(pos.func, cfg, curpos, srcloc, y);
+
+    let al = compare_half(&mut pos, &data, half_ty, xl, yl);
+    let ah = compare_half(&mut pos, &data, half_ty, xh, yh);
+    pos.func.dfg.replace(inst).vconcat(al, ah);
+    true
+}
+
+/// Insert a copy of the comparison `data` with the arguments `x` and `y`, producing a result of
+/// type `ty`.
+fn compare_half(
+    pos: &mut FuncCursor,
+    data: &ir::InstructionData,
+    ty: ir::Type,
+    x: ir::Value,
+    y: ir::Value,
+) -> ir::Value {
+    let cmp = match *data {
+        ir::InstructionData::IntCompare { cond, .. } => pos.ins().icmp(cond, x, y),
+        ir::InstructionData::FloatCompare { cond, .. } => pos.ins().fcmp(cond, x, y),
+        _ => panic!("Expected a comparison"),
+    };
+    if pos.func.dfg.value_type(cmp) == ty {
+        cmp
+    } else {
+        pos.ins().bextend(ty, cmp)
+    }
+}
+
+/// Split an `f32splat` or `f64splat` vector constant into two constants with half the lanes.
+///
+/// Targets encode the splatted constants they can materialize cheaply, like all lanes zero. The
+/// other constants end up being built from scalar `f32const` and `f64const` instructions.
+pub fn narrow_fsplat(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let half_ty = func
+        .dfg
+        .ctrl_typevar(inst)
+        .half_vector()
+        .expect("fsplat of a vector");
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let (lo, hi) = match pos.func.dfg[inst] {
+        ir::InstructionData::UnaryIeee32 {
+            opcode: ir::Opcode::F32splat,
+            imm,
+        } => {
+            if half_ty.is_vector() {
+                (
+                    pos.ins().f32splat(half_ty, imm),
+                    pos.ins().f32splat(half_ty, imm),
+                )
+            } else {
+                (pos.ins().f32const(imm), pos.ins().f32const(imm))
+            }
+        }
+        ir::InstructionData::UnaryIeee64 {
+            opcode: ir::Opcode::F64splat,
+            imm,
+        } => {
+            if half_ty.is_vector() {
+                (
+                    pos.ins().f64splat(half_ty, imm),
+                    pos.ins().f64splat(half_ty, imm),
+                )
+            } else {
+                (pos.ins().f64const(imm), pos.ins().f64const(imm))
+            }
+        }
+        _ => panic!("Expected fsplat: {}", pos.func.dfg.display_inst(inst, None)),
+    };
+    pos.func.dfg.replace(inst).vconcat(lo, hi);
+}
+
+/// Split a vector `fma` into two `fma` instructions with half the lanes.
+///
+/// The halves are split again until the target can encode them, so a target with scalar
+/// fused multiply-add instructions uses them for each lane. Scalars are expanded by
+/// `expand_fma()`, since this is used for every type legalized with the `narrow` group.
+pub fn narrow_fma(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    let ty = func.dfg.ctrl_typevar(inst);
+    if ty.half_vector().is_none() {
+        return super::expand_fma(inst, func, cfg, isa);
+    }
+
+    let (x, y, z) = match func.dfg[inst] {
+        ir::InstructionData::Ternary {
+            opcode: ir::Opcode::Fma,
+            args,
+        } => (args[0], args[1], args[2]),
+        _ => panic!("Expected fma: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (xl, xh) = vsplit(pos.func, cfg, curpos, srcloc, x);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (yl, yh) = vsplit(pos.func, cfg, curpos, srcloc, y);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (zl, zh) = vsplit(pos.func, cfg, curpos, srcloc, z);
+
+    let lo = pos.ins().fma(xl, yl, zl);
+    let hi = pos.ins().fma(xh, yh, zh);
+    pos.func.dfg.replace(inst).vconcat(lo, hi);
+}
+
+/// Split a vector `bitselect` into two `bitselect` instructions with half the lanes.
+///
+/// Scalars are only narrowed when they are wider than the target registers, and they are
+/// expanded as `y ^ ((x ^ y) & c)` which only uses instructions that can be split in halves.
+pub fn narrow_bitselect(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (c, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Ternary {
+            opcode: ir::Opcode::Bitselect,
+            args,
+        } => (args[0], args[1], args[2]),
+        _ => panic!("Expected bitselect: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    if pos.func.dfg.ctrl_typevar(inst).half_vector().is_none() {
+        let diff = pos.ins().bxor(x, y);
+        let mask = pos.ins().band(diff, c);
+        pos.func.dfg.replace(inst).bxor(y, mask);
+        return;
+    }
+
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (cl, ch) = vsplit(pos.func, cfg, curpos, srcloc, c);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (xl, xh) = vsplit(pos.func, cfg, curpos, srcloc, x);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (yl, yh) = vsplit(pos.func, cfg, curpos, srcloc, y);
+
+    let lo = pos.ins().bitselect(cl, xl, yl);
+    let hi = pos.ins().bitselect(ch, xh, yh);
+    pos.func.dfg.replace(inst).vconcat(lo, hi);
+}
+
+/// Split a `vselect` into two `vselect` instructions with half the lanes.
+///
+/// Vectors with two lanes are split into scalars, which are selected with `select`.
+pub fn narrow_vselect(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (c, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Ternary {
+            opcode: ir::Opcode::Vselect,
+            args,
+        } => (args[0], args[1], args[2]),
+        _ => panic!("Expected vselect: {}", func.dfg.display_inst(inst, None)),
+    };
+    let half_ty = func
+        .dfg
+        .ctrl_typevar(inst)
+        .half_vector()
+        .expect("vselect of a vector");
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (cl, ch) = vsplit(pos.func, cfg, curpos, srcloc, c);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (xl, xh) = vsplit(pos.func, cfg, curpos, srcloc, x);
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (yl, yh) = vsplit(pos.func, cfg, curpos, srcloc, y);
+
+    let (lo, hi) = if half_ty.is_vector() {
+        (pos.ins().vselect(cl, xl, yl), pos.ins().vselect(ch, xh, yh))
+    } else {
+        (pos.ins().select(cl, xl, yl), pos.ins().select(ch, xh, yh))
+    };
+    pos.func.dfg.replace(inst).vconcat(lo, hi);
+}
+
+/// Expand an integer minimum or maximum that the target can't encode with a comparison and a
+/// select.
+///
+/// Vectors use `vselect` with the lane mask produced by the comparison, and both instructions are
+/// split again if needed.
+pub fn narrow_iminmax(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (cond, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => {
+            let cond = match opcode {
+                ir::Opcode::Imin => IntCC::SignedLessThan,
+                ir::Opcode::Imax => IntCC::SignedGreaterThan,
+                ir::Opcode::Umin => IntCC::UnsignedLessThan,
+                ir::Opcode::Umax => IntCC::UnsignedGreaterThan,
+                _ => panic!("Expected min/max: {}", func.dfg.display_inst(inst, None)),
+            };
+            (cond, args[0], args[1])
+        }
+        _ => panic!("Expected min/max: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let c = pos.ins().icmp(cond, x, y);
+    if pos.func.dfg.ctrl_typevar(inst).is_vector() {
+        pos.func.dfg.replace(inst).vselect(c, x, y);
+    } else {
+        pos.func.dfg.replace(inst).select(c, x, y);
+    }
+}
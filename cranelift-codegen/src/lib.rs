@@ -55,8 +55,11 @@ use hashmap_core::{map as hash_map, HashMap, HashSet};
 #[cfg(feature = "std")]
 use std::collections::{hash_map, HashMap, HashSet};
 
-pub use crate::context::Context;
-pub use crate::legalizer::legalize_function;
+pub use crate::context::{CompileObserver, Context};
+pub use crate::coverage::{CoverageEntry, CoverageMap};
+pub use crate::legalizer::{
+    legalize_function, legalize_function_resumable, LegalizeControl, LegalizeProgress,
+};
 pub use crate::value_label::{ValueLabelsRanges, ValueLocRange};
 pub use crate::verifier::verify_function;
 pub use crate::write::write_function;
@@ -64,16 +67,28 @@ pub use crate::write::write_function;
 pub use cranelift_bforest as bforest;
 pub use cranelift_entity as entity;
 
+#[cfg(feature = "std")]
+pub mod batch;
 pub mod binemit;
 pub mod cfg_printer;
 pub mod cursor;
 pub mod dbg;
+pub mod debuginfo;
+pub mod diagnostics;
 pub mod dominator_tree;
+pub mod ebb_frequency;
+pub mod ebb_layout;
 pub mod flowgraph;
+pub mod inline;
 pub mod ir;
 pub mod isa;
+pub mod live_sets;
 pub mod loop_analysis;
+#[cfg(feature = "parse")]
+pub mod parse;
+pub mod pipeline;
 pub mod print_errors;
+pub mod register_pressure;
 pub mod settings;
 pub mod timing;
 pub mod verifier;
@@ -83,11 +98,15 @@ pub use crate::entity::packed_option;
 
 mod abi;
 mod bitset;
+mod complex_addresses;
 mod constant_hash;
 mod context;
+mod coverage;
 mod dce;
+mod determinism;
 mod divconst_magic_numbers;
 mod fx;
+mod heap_bounds;
 mod iterators;
 mod legalizer;
 mod licm;
@@ -95,13 +114,19 @@ mod nan_canonicalization;
 mod partition_slice;
 mod postopt;
 mod predicates;
+mod prune_ebb_params;
+mod redundant_traps;
 mod ref_slice;
 mod regalloc;
+mod remat_constants;
 mod result;
 mod scoped_hash_map;
 mod simple_gvn;
 mod simple_preopt;
+mod simplify_cfg;
 mod stack_layout;
+#[cfg(all(test, feature = "x86"))]
+mod test_utils;
 mod topo_order;
 mod unreachable_code;
 mod value_label;
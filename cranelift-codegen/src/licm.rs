@@ -6,7 +6,7 @@ use crate::entity::{EntityList, ListPool};
 use crate::flowgraph::{BasicBlock, ControlFlowGraph};
 use crate::fx::FxHashSet;
 use crate::ir::{
-    DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Layout, Opcode, Type, Value,
+    DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Layout, Type, Value,
 };
 use crate::isa::TargetIsa;
 use crate::loop_analysis::{Loop, LoopAnalysis};
@@ -145,34 +145,32 @@ fn change_branch_jump_destination(inst: Inst, new_ebb: Ebb, func: &mut Function)
     }
 }
 
-/// Test whether the given opcode is unsafe to even consider for LICM.
-fn trivially_unsafe_for_licm(opcode: Opcode) -> bool {
-    opcode.can_store()
-        || opcode.is_call()
-        || opcode.is_branch()
-        || opcode.is_terminator()
-        || opcode.is_return()
-        || opcode.can_trap()
-        || opcode.other_side_effects()
-        || opcode.writes_cpu_flags()
-}
-
-fn is_unsafe_load(inst_data: &InstructionData) -> bool {
+/// Test whether a load can't be hoisted out of its loop.
+///
+/// Only loads that can't trap are hoisted, since the loop body may not execute them at all. If
+/// the loop writes to memory, the load must also be `readonly`; we don't have any alias
+/// information to tell if the store and the load could overlap.
+fn is_unsafe_load(inst_data: &InstructionData, loop_stores: bool) -> bool {
     match *inst_data {
         InstructionData::Load { flags, .. } | InstructionData::LoadComplex { flags, .. } => {
-            !flags.readonly() || !flags.notrap()
+            !flags.notrap() || (loop_stores && !flags.readonly())
         }
-        _ => inst_data.opcode().can_load(),
+        _ => inst_data.opcode().effects().reads_memory,
     }
 }
 
 /// Test whether the given instruction is loop-invariant.
-fn is_loop_invariant(inst: Inst, dfg: &DataFlowGraph, loop_values: &FxHashSet<Value>) -> bool {
-    if trivially_unsafe_for_licm(dfg[inst].opcode()) {
+fn is_loop_invariant(
+    inst: Inst,
+    dfg: &DataFlowGraph,
+    loop_values: &FxHashSet<Value>,
+    loop_stores: bool,
+) -> bool {
+    if !dfg[inst].opcode().effects().can_move() {
         return false;
     }
 
-    if is_unsafe_load(&dfg[inst]) {
+    if is_unsafe_load(&dfg[inst], loop_stores) {
         return false;
     }
 
@@ -197,9 +195,18 @@ fn remove_loop_invariant_instructions(
 ) -> Vec<Inst> {
     let mut loop_values: FxHashSet<Value> = FxHashSet();
     let mut invariant_insts: Vec<Inst> = Vec::new();
+    let loop_ebbs = postorder_ebbs_loop(loop_analysis, cfg, lp);
+
+    // Check if anything in the loop may write to memory.
+    let loop_stores = loop_ebbs.iter().any(|&ebb| {
+        func.layout
+            .ebb_insts(ebb)
+            .any(|inst| func.dfg[inst].opcode().effects().may_clobber_memory())
+    });
+
     let mut pos = FuncCursor::new(func);
     // We traverse the loop EBB in reverse post-order.
-    for ebb in postorder_ebbs_loop(loop_analysis, cfg, lp).iter().rev() {
+    for ebb in loop_ebbs.iter().rev() {
         // Arguments of the EBB are loop values
         for val in pos.func.dfg.ebb_params(*ebb) {
             loop_values.insert(*val);
@@ -207,7 +214,7 @@ fn remove_loop_invariant_instructions(
         pos.goto_top(*ebb);
         #[cfg_attr(feature = "cargo-clippy", allow(clippy::block_in_if_condition_stmt))]
         while let Some(inst) = pos.next_inst() {
-            if is_loop_invariant(inst, &pos.func.dfg, &loop_values) {
+            if is_loop_invariant(inst, &pos.func.dfg, &loop_values, loop_stores) {
                 // If all the instruction's argument are defined outside the loop
                 // then this instruction is loop-invariant
                 invariant_insts.push(inst);
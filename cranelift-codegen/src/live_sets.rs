@@ -0,0 +1,236 @@
+//! Public liveness information: live-in and live-out sets per EBB and def-use chains.
+//!
+//! The register allocator computes precise live ranges for every SSA value, but those live ranges
+//! are tied to the register allocator's internal data structures and are only valid while it is
+//! running. This module computes a simpler, stable view of liveness that other consumers can use
+//! at any point in the pipeline: safepoint insertion, sanitizer instrumentation, custom
+//! schedulers, and so on.
+//!
+//! The analysis depends only on the function and its control flow graph. It uses the classic SSA
+//! path exploration algorithm: starting from each use of a value, walk backwards through the CFG
+//! predecessors until the defining EBB is reached, marking every EBB on the way as live-in.
+//!
+//! A value is considered *live-out* of an EBB if it is live-in to any CFG successor of the EBB.
+//! Since EBBs can branch from the middle, this is the union of the values live across all of the
+//! EBB's outgoing edges. Values passed as branch arguments are uses at the branch instruction and
+//! are not live-out unless they are also live-in to a successor.
+
+use crate::entity::SecondaryMap;
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::dfg::ValueDef;
+use crate::ir::{Ebb, Function, Inst, Value};
+use crate::timing;
+use std::vec::Vec;
+
+/// Live-in and live-out sets for every EBB in a function, along with def-use chains.
+///
+/// All the value sets are sorted by value number, so membership queries use binary search.
+pub struct LiveSets {
+    /// Values live on entry to each EBB, not including the EBB's own parameters.
+    livein: SecondaryMap<Ebb, Vec<Value>>,
+
+    /// Values live on at least one outgoing CFG edge of each EBB.
+    liveout: SecondaryMap<Ebb, Vec<Value>>,
+
+    /// Instructions using each value, in layout order. Aliases are resolved.
+    uses: SecondaryMap<Value, Vec<Inst>>,
+
+    /// Scratch work-list of EBBs used while propagating liveness.
+    worklist: Vec<Ebb>,
+
+    valid: bool,
+}
+
+impl LiveSets {
+    /// Create a new empty analysis.
+    pub fn new() -> Self {
+        Self {
+            livein: SecondaryMap::new(),
+            liveout: SecondaryMap::new(),
+            uses: SecondaryMap::new(),
+            worklist: Vec::new(),
+            valid: false,
+        }
+    }
+
+    /// Allocate and compute the live sets for `func`.
+    pub fn with_function(func: &Function, cfg: &ControlFlowGraph) -> Self {
+        let mut sets = Self::new();
+        sets.compute(func, cfg);
+        sets
+    }
+
+    /// Clear all data structures in this analysis.
+    pub fn clear(&mut self) {
+        self.livein.clear();
+        self.liveout.clear();
+        self.uses.clear();
+        self.worklist.clear();
+        self.valid = false;
+    }
+
+    /// Check if the live sets are in a valid state.
+    ///
+    /// This only checks that `compute()` has been called since the last `clear()`. It does not
+    /// check that the analysis is consistent with the function.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Compute the live sets and def-use chains of `func`.
+    ///
+    /// The control flow graph `cfg` must be up to date. This clears out any existing analysis.
+    pub fn compute(&mut self, func: &Function, cfg: &ControlFlowGraph) {
+        let _tt = timing::live_sets();
+        debug_assert!(cfg.is_valid());
+        self.clear();
+
+        // Build the def-use chains first, visiting instructions in layout order.
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                for &arg in func.dfg.inst_args(inst) {
+                    let arg = func.dfg.resolve_aliases(arg);
+                    let uses = &mut self.uses[arg];
+                    // An instruction can use the same value more than once.
+                    if uses.last() != Some(&inst) {
+                        uses.push(inst);
+                    }
+                }
+            }
+        }
+
+        // Values are visited in increasing order, so the value being propagated is always the
+        // last element of any set it has already been added to. This gives us constant-time
+        // membership tests and sorted sets.
+        for value in func.dfg.values() {
+            let def_ebb = match func.dfg.value_def(value) {
+                ValueDef::Result(inst, _) => match func.layout.inst_ebb(inst) {
+                    Some(ebb) => ebb,
+                    None => continue,
+                },
+                ValueDef::Param(ebb, _) => ebb,
+            };
+
+            for &user in self.uses[value].iter() {
+                let ebb = func.layout.inst_ebb(user).expect("use not in layout");
+                if ebb != def_ebb && self.livein[ebb].last() != Some(&value) {
+                    self.livein[ebb].push(value);
+                    self.worklist.push(ebb);
+                }
+            }
+
+            while let Some(ebb) = self.worklist.pop() {
+                for pred in cfg.pred_iter(ebb) {
+                    if self.liveout[pred.ebb].last() != Some(&value) {
+                        self.liveout[pred.ebb].push(value);
+                    }
+                    if pred.ebb != def_ebb && self.livein[pred.ebb].last() != Some(&value) {
+                        self.livein[pred.ebb].push(value);
+                        self.worklist.push(pred.ebb);
+                    }
+                }
+            }
+        }
+
+        self.valid = true;
+    }
+
+    /// Get the values that are live on entry to `ebb`, sorted by value number.
+    ///
+    /// The EBB's own parameters are not included since they are defined by the EBB.
+    pub fn live_in(&self, ebb: Ebb) -> &[Value] {
+        &self.livein[ebb]
+    }
+
+    /// Get the values that are live on at least one outgoing CFG edge of `ebb`, sorted by value
+    /// number.
+    pub fn live_out(&self, ebb: Ebb) -> &[Value] {
+        &self.liveout[ebb]
+    }
+
+    /// Is `value` live on entry to `ebb`?
+    pub fn is_live_in(&self, ebb: Ebb, value: Value) -> bool {
+        self.livein[ebb].binary_search(&value).is_ok()
+    }
+
+    /// Is `value` live on at least one outgoing CFG edge of `ebb`?
+    pub fn is_live_out(&self, ebb: Ebb, value: Value) -> bool {
+        self.liveout[ebb].binary_search(&value).is_ok()
+    }
+
+    /// Get the instructions using `value`, in layout order.
+    ///
+    /// Each instruction appears once even if it uses `value` more than once. Uses through value
+    /// aliases are attributed to the resolved value.
+    pub fn uses(&self, value: Value) -> &[Inst] {
+        &self.uses[value]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{types, Function, InstBuilder};
+
+    #[test]
+    fn empty() {
+        let func = Function::new();
+        let cfg = ControlFlowGraph::with_function(&func);
+        let sets = LiveSets::with_function(&func, &cfg);
+        assert!(sets.is_valid());
+    }
+
+    #[test]
+    fn diamond() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let arg = func.dfg.append_ebb_param(ebb3, types::I32);
+
+        let (v1, add, ret);
+        {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            v1 = cur.ins().iconst(types::I32, 1);
+            cur.ins().brnz(cond, ebb2, &[]);
+            cur.ins().jump(ebb1, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb3, &[cond]);
+
+            cur.insert_ebb(ebb2);
+            let v2 = cur.ins().iadd(v1, v1);
+            add = cur.func.dfg.value_def(v2).unwrap_inst();
+            cur.ins().jump(ebb3, &[v2]);
+
+            cur.insert_ebb(ebb3);
+            let v3 = cur.ins().iadd(arg, v1);
+            ret = cur.ins().return_(&[v3]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        let sets = LiveSets::with_function(&func, &cfg);
+
+        assert_eq!(sets.live_in(ebb0), []);
+        assert_eq!(sets.live_in(ebb1), [cond, v1]);
+        assert_eq!(sets.live_in(ebb2), [v1]);
+        assert_eq!(sets.live_in(ebb3), [v1]);
+        assert_eq!(sets.live_out(ebb0), [cond, v1]);
+        assert_eq!(sets.live_out(ebb1), [v1]);
+        assert_eq!(sets.live_out(ebb2), [v1]);
+        assert_eq!(sets.live_out(ebb3), []);
+
+        assert!(sets.is_live_in(ebb3, v1));
+        assert!(!sets.is_live_in(ebb3, arg));
+        assert!(!sets.is_live_out(ebb3, v1));
+
+        assert_eq!(sets.uses(v1).len(), 2);
+        assert_eq!(sets.uses(v1)[0], add);
+        assert_eq!(sets.uses(func.dfg.inst_args(ret)[0]), [ret]);
+    }
+}
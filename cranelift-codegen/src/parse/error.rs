@@ -3,6 +3,7 @@
 #![macro_use]
 
 use std::fmt;
+use std::string::String;
 
 /// The location of a `Token` or `Error`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -39,7 +40,7 @@ pub type ParseResult<T> = Result<T, ParseError>;
 // Create an `Err` variant of `ParseResult<X>` from a location and `format!` args.
 macro_rules! err {
     ( $loc:expr, $msg:expr ) => {
-        Err($crate::ParseError {
+        Err($crate::parse::ParseError {
             location: $loc.clone(),
             message: $msg.to_string(),
             is_warning: false,
@@ -47,7 +48,7 @@ macro_rules! err {
     };
 
     ( $loc:expr, $fmt:expr, $( $arg:expr ),+ ) => {
-        Err($crate::ParseError {
+        Err($crate::parse::ParseError {
             location: $loc.clone(),
             message: format!( $fmt, $( $arg ),+ ),
             is_warning: false,
@@ -57,7 +58,7 @@ macro_rules! err {
 
 macro_rules! warn {
     ( $loc:expr, $fmt:expr, $( $arg:expr ),+ ) => {
-        Err($crate::ParseError {
+        Err($crate::parse::ParseError {
             location: $loc.clone(),
             message: format!($fmt, $( $arg ),+ ),
             is_warning: true,
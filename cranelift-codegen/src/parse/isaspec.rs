@@ -6,10 +6,12 @@
 //! If a test case file contains `isa` commands, the tests will only be run against the specified
 //! ISAs. If the file contains no `isa` commands, the tests will be run against all supported ISAs.
 
-use crate::error::{Location, ParseResult};
-use crate::testcommand::TestOption;
-use cranelift_codegen::isa::TargetIsa;
-use cranelift_codegen::settings::{Configurable, Flags, SetError};
+use crate::isa::TargetIsa;
+use crate::parse::error::{Location, ParseResult};
+use crate::parse::testcommand::TestOption;
+use crate::settings::{Configurable, Flags, SetError};
+use std::boxed::Box;
+use std::vec::Vec;
 
 /// The ISA specifications in a `.clif` file.
 pub enum IsaSpec {
@@ -0,0 +1,73 @@
+//! Parser for the textual `.clif` format.
+//!
+//! This module is only available with the `parse` feature. It is also re-exported by the
+//! `cranelift-reader` crate, which additionally reads whole test files.
+//!
+//! A single function can be parsed with `str::parse`:
+//!
+//! ```
+//! use cranelift_codegen::ir::Function;
+//!
+//! let func: Function = "function %f() { ebb0: return }".parse().unwrap();
+//! assert_eq!(func.layout.ebbs().count(), 1);
+//! ```
+
+pub use self::error::{Location, ParseError, ParseResult};
+pub use self::isaspec::{parse_options, IsaSpec};
+pub use self::parser::{parse_functions, parse_test};
+pub use self::sourcemap::SourceMap;
+pub use self::testcommand::{TestCommand, TestOption};
+pub use self::testfile::{Comment, Details, TestFile};
+
+use crate::ir::Function;
+use core::str::FromStr;
+
+mod error;
+mod isaspec;
+mod lexer;
+mod parser;
+mod sourcemap;
+mod testcommand;
+mod testfile;
+
+impl FromStr for Function {
+    type Err = ParseError;
+
+    /// Parse the text of exactly one function.
+    fn from_str(text: &str) -> ParseResult<Self> {
+        let mut functions = parse_functions(text)?;
+        if functions.len() != 1 {
+            return err!(
+                Location::default(),
+                "expected a single function, found {}",
+                functions.len()
+            );
+        }
+        Ok(functions.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ir::Function;
+    use std::string::ToString;
+
+    #[test]
+    fn from_str() {
+        let func: Function = "function %f(i32) -> i32 {
+                              ebb0(v0: i32):
+                                  return v0
+                              }"
+        .parse()
+        .unwrap();
+        assert_eq!(func.name.to_string(), "%f");
+        assert_eq!(func.signature.params.len(), 1);
+
+        let err = "".parse::<Function>().unwrap_err();
+        assert_eq!(err.message, "expected a single function, found 0");
+        assert!("function %a() { ebb0: return }
+                 function %b() { ebb0: return }"
+            .parse::<Function>()
+            .is_err());
+    }
+}
@@ -1,28 +1,30 @@
 //! Parser for .clif files.
 
-use crate::error::{Location, ParseError, ParseResult};
-use crate::isaspec;
-use crate::lexer::{LexError, Lexer, LocatedError, LocatedToken, Token};
-use crate::sourcemap::SourceMap;
-use crate::testcommand::TestCommand;
-use crate::testfile::{Comment, Details, TestFile};
-use cranelift_codegen::entity::EntityRef;
-use cranelift_codegen::ir;
-use cranelift_codegen::ir::entities::AnyEntity;
-use cranelift_codegen::ir::immediates::{Ieee32, Ieee64, Imm64, Offset32, Uimm32, Uimm64};
-use cranelift_codegen::ir::instructions::{InstructionData, InstructionFormat, VariableArgs};
-use cranelift_codegen::ir::types::INVALID;
-use cranelift_codegen::ir::{
+use crate::entity::EntityRef;
+use crate::ir;
+use crate::ir::entities::AnyEntity;
+use crate::ir::immediates::{Ieee32, Ieee64, Imm64, Offset32, Uimm32, Uimm64};
+use crate::ir::instructions::{InstructionData, InstructionFormat, VariableArgs};
+use crate::ir::types::INVALID;
+use crate::ir::{
     AbiParam, ArgumentExtension, ArgumentLoc, Ebb, ExtFuncData, ExternalName, FuncRef, Function,
-    GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle, JumpTable, JumpTableData, MemFlags,
-    Opcode, SigRef, Signature, StackSlot, StackSlotData, StackSlotKind, Table, TableData, Type,
-    Value, ValueLoc,
+    GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle, HeapViolation, JumpTable,
+    JumpTableData, Linkage, MemFlags, Opcode, SigRef, Signature, StackSlot, StackSlotData,
+    StackSlotKind, Table, TableData, Type, Value, ValueLoc, Visibility,
 };
-use cranelift_codegen::isa::{self, CallConv, Encoding, RegUnit, TargetIsa};
-use cranelift_codegen::packed_option::ReservedValue;
-use cranelift_codegen::{settings, timing};
+use crate::isa::{self, CallConv, Encoding, RegUnit, TargetIsa};
+use crate::packed_option::ReservedValue;
+use crate::parse::error::{Location, ParseError, ParseResult};
+use crate::parse::isaspec;
+use crate::parse::lexer::{LexError, Lexer, LocatedError, LocatedToken, Token};
+use crate::parse::sourcemap::SourceMap;
+use crate::parse::testcommand::TestCommand;
+use crate::parse::testfile::{Comment, Details, TestFile};
+use crate::{settings, timing};
 use std::mem;
 use std::str::FromStr;
+use std::string::{String, ToString};
+use std::vec::Vec;
 use std::{u16, u32};
 use target_lexicon::Triple;
 
@@ -195,6 +197,8 @@ impl<'a> Context<'a> {
                     bound: Uimm64::new(0),
                 },
                 index_type: INVALID,
+                trap_code: ir::TrapCode::HeapOutOfBounds,
+                violation: HeapViolation::Trap,
             });
         }
         self.function.heaps[heap] = data;
@@ -219,6 +223,8 @@ impl<'a> Context<'a> {
                 bound_gv: GlobalValue::reserved_value(),
                 element_size: Uimm64::new(0),
                 index_type: INVALID,
+                trap_code: ir::TrapCode::TableOutOfBounds,
+                id: 0.into(),
             });
         }
         self.function.tables[table] = data;
@@ -262,6 +268,8 @@ impl<'a> Context<'a> {
                 name: ExternalName::testcase(""),
                 signature: SigRef::reserved_value(),
                 colocated: false,
+                linkage: Linkage::Import,
+                visibility: Visibility::Default,
             });
         }
         self.function.dfg.ext_funcs[fn_] = data;
@@ -1004,7 +1012,7 @@ impl<'a> Parser<'a> {
         let mut sig = Signature::new(CallConv::Fast);
 
         self.match_token(Token::LPar, "expected function signature: ( args... )")?;
-        // signature ::=  "(" * [abi-param-list] ")" ["->" retlist] [callconv]
+        // signature ::=  "(" * [abi-param-list] ")" ["->" retlist] [callconv] ["variadic"]
         if self.token() != Some(Token::RPar) {
             sig.params = self.parse_abi_param_list(unique_isa)?;
         }
@@ -1014,14 +1022,20 @@ impl<'a> Parser<'a> {
         }
 
         // The calling convention is optional.
-        if let Some(Token::Identifier(text)) = self.token() {
-            match text.parse() {
+        match self.token() {
+            Some(Token::Identifier("variadic")) => {}
+            Some(Token::Identifier(text)) => match text.parse() {
                 Ok(cc) => {
                     self.consume();
                     sig.call_conv = cc;
                 }
                 _ => return err!(self.loc, "unknown calling convention: {}", text),
-            }
+            },
+            _ => {}
+        }
+
+        if self.optional(Token::Identifier("variadic")) {
+            sig.variadic = true;
         }
 
         Ok(sig)
@@ -1218,6 +1232,7 @@ impl<'a> Parser<'a> {
     //                   | "load" "." type "notrap" "aligned" GlobalValue(base) [offset]
     //                   | "iadd_imm" "(" GlobalValue(base) ")" imm64
     //                   | "symbol" ["colocated"] name + imm64
+    //                   | "tls_value" ["colocated"] name
     //
     fn parse_global_value_decl(&mut self) -> ParseResult<(GlobalValue, GlobalValueData)> {
         let gv = self.match_gv("expected global value number: gv«n»")?;
@@ -1274,6 +1289,11 @@ impl<'a> Parser<'a> {
                     colocated,
                 }
             }
+            "tls_value" => {
+                let colocated = self.optional(Token::Identifier("colocated"));
+                let name = self.parse_external_name()?;
+                GlobalValueData::TlsValue { name, colocated }
+            }
             other => return err!(self.loc, "Unknown global value kind '{}'", other),
         };
 
@@ -1294,6 +1314,9 @@ impl<'a> Parser<'a> {
     //             | "bound" Imm64(bytes)
     //             | "offset_guard" Imm64(bytes)
     //             | "index_type" type
+    //             | "trap_code" TrapCode
+    //             | "violation" heap-violation
+    // heap-violation ::= "trap" | "clamp" | "libcall" FuncRef(callee)
     //
     fn parse_heap_decl(&mut self) -> ParseResult<(Heap, HeapData)> {
         let heap = self.match_heap("expected heap number: heap«n»")?;
@@ -1318,6 +1341,8 @@ impl<'a> Parser<'a> {
             offset_guard_size: 0.into(),
             style: HeapStyle::Static { bound: 0.into() },
             index_type: ir::types::I32,
+            trap_code: ir::TrapCode::HeapOutOfBounds,
+            violation: HeapViolation::Trap,
         };
 
         // heap-desc ::= heap-style heap-base * { "," heap-attr }
@@ -1344,6 +1369,19 @@ impl<'a> Parser<'a> {
                 "index_type" => {
                     data.index_type = self.match_type("expected index type")?;
                 }
+                "trap_code" => {
+                    data.trap_code = self.match_enum("expected trap code")?;
+                }
+                "violation" => {
+                    data.violation = match self.match_any_identifier("expected heap violation")? {
+                        "trap" => HeapViolation::Trap,
+                        "clamp" => HeapViolation::Clamp,
+                        "libcall" => HeapViolation::CustomLibcall(
+                            self.match_fn("expected libcall function reference")?,
+                        ),
+                        t => return err!(self.loc, "unknown heap violation '{}'", t),
+                    };
+                }
                 t => return err!(self.loc, "unknown heap attribute '{}'", t),
             }
         }
@@ -1365,6 +1403,8 @@ impl<'a> Parser<'a> {
     //              | "bound" Imm64(bytes)
     //              | "element_size" Imm64(bytes)
     //              | "index_type" type
+    //              | "trap_code" TrapCode
+    //              | "id" Uimm32
     //
     fn parse_table_decl(&mut self) -> ParseResult<(Table, TableData)> {
         let table = self.match_table("expected table number: table«n»")?;
@@ -1389,6 +1429,8 @@ impl<'a> Parser<'a> {
             bound_gv: GlobalValue::reserved_value(),
             element_size: 0.into(),
             index_type: ir::types::I32,
+            trap_code: ir::TrapCode::TableOutOfBounds,
+            id: 0.into(),
         };
 
         // table-desc ::= * { "," table-attr }
@@ -1409,6 +1451,12 @@ impl<'a> Parser<'a> {
                 "index_type" => {
                     data.index_type = self.match_type("expected index type")?;
                 }
+                "trap_code" => {
+                    data.trap_code = self.match_enum("expected trap code")?;
+                }
+                "id" => {
+                    data.id = self.match_uimm32("expected 32-bit table id")?;
+                }
                 t => return err!(self.loc, "unknown table attribute '{}'", t),
             }
         }
@@ -1443,7 +1491,8 @@ impl<'a> Parser<'a> {
     //
     // Two variants:
     //
-    // function-decl ::= FuncRef(fnref) "=" ["colocated"]" name function-decl-sig
+    // function-decl ::= FuncRef(fnref) "=" ["colocated"] [linkage] [visibility] name
+    //                   function-decl-sig
     // function-decl-sig ::= SigRef(sig) | signature
     //
     // The first variant allocates a new signature reference. The second references an existing
@@ -1455,16 +1504,37 @@ impl<'a> Parser<'a> {
 
         let loc = self.loc;
 
-        // function-decl ::= FuncRef(fnref) "=" * ["colocated"] name function-decl-sig
+        // function-decl ::= FuncRef(fnref) "=" * ["colocated"] [linkage] [visibility] name ...
         let colocated = self.optional(Token::Identifier("colocated"));
 
-        // function-decl ::= FuncRef(fnref) "=" ["colocated"] * name function-decl-sig
+        // function-decl ::= FuncRef(fnref) "=" ["colocated"] * [linkage] [visibility] name ...
+        let mut linkage = Linkage::Import;
+        if let Some(Token::Identifier(text)) = self.token() {
+            if let Ok(l) = text.parse() {
+                self.consume();
+                linkage = l;
+            }
+        }
+
+        // function-decl ::= FuncRef(fnref) "=" ["colocated"] [linkage] * [visibility] name ...
+        let mut visibility = Visibility::Default;
+        if let Some(Token::Identifier(text)) = self.token() {
+            match text.parse() {
+                Ok(v) => {
+                    self.consume();
+                    visibility = v;
+                }
+                _ => return err!(self.loc, "unknown linkage or visibility: {}", text),
+            }
+        }
+
+        // function-decl ::= FuncRef(fnref) "=" ["colocated"] [linkage] [visibility] * name ...
         let name = self.parse_external_name()?;
 
-        // function-decl ::= FuncRef(fnref) "=" ["colocated"] name * function-decl-sig
+        // function-decl ::= FuncRef(fnref) "=" ... name * function-decl-sig
         let data = match self.token() {
             Some(Token::LPar) => {
-                // function-decl ::= FuncRef(fnref) "=" ["colocated"] name * signature
+                // function-decl ::= FuncRef(fnref) "=" ... name * signature
                 let sig = self.parse_signature(ctx.unique_isa)?;
                 let sigref = ctx.function.import_signature(sig);
                 ctx.map
@@ -1474,6 +1544,8 @@ impl<'a> Parser<'a> {
                     name,
                     signature: sigref,
                     colocated,
+                    linkage,
+                    visibility,
                 }
             }
             Some(Token::SigRef(sig_src)) => {
@@ -1489,6 +1561,8 @@ impl<'a> Parser<'a> {
                     name,
                     signature: sig,
                     colocated,
+                    linkage,
+                    visibility,
                 }
             }
             _ => return err!(self.loc, "expected 'function' or sig«n» in function decl"),
@@ -2403,6 +2477,7 @@ impl<'a> Parser<'a> {
                 }
             }
             InstructionFormat::HeapAddr => {
+                let flags = self.optional_memflags();
                 let heap = self.match_heap("expected heap identifier")?;
                 ctx.check_heap(heap, self.loc)?;
                 self.match_token(Token::Comma, "expected ',' between operands")?;
@@ -2411,12 +2486,14 @@ impl<'a> Parser<'a> {
                 let imm = self.match_uimm32("expected 32-bit integer size")?;
                 InstructionData::HeapAddr {
                     opcode,
+                    flags,
                     heap,
                     arg,
                     imm,
                 }
             }
             InstructionFormat::TableAddr => {
+                let flags = self.optional_memflags();
                 let table = self.match_table("expected table identifier")?;
                 ctx.check_table(table, self.loc)?;
                 self.match_token(Token::Comma, "expected ',' between operands")?;
@@ -2425,11 +2502,41 @@ impl<'a> Parser<'a> {
                 let offset = self.optional_offset32()?;
                 InstructionData::TableAddr {
                     opcode,
+                    flags,
                     table,
                     arg,
                     offset,
                 }
             }
+            InstructionFormat::TableGrow => {
+                let table = self.match_table("expected table identifier")?;
+                ctx.check_table(table, self.loc)?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let delta = self.match_value("expected SSA value table delta")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let init = self.match_value("expected SSA value table element")?;
+                InstructionData::TableGrow {
+                    opcode,
+                    table,
+                    args: [delta, init],
+                }
+            }
+            InstructionFormat::TableFill => {
+                let table = self.match_table("expected table identifier")?;
+                ctx.check_table(table, self.loc)?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let dst = self.match_value("expected SSA value table index")?;
+                let mut args = VariableArgs::new();
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                args.push(self.match_value("expected SSA value table element")?);
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                args.push(self.match_value("expected SSA value element count")?);
+                InstructionData::TableFill {
+                    opcode,
+                    table,
+                    args: args.into_value_list(&[dst], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::Load => {
                 let flags = self.optional_memflags();
                 let addr = self.match_value("expected SSA value address")?;
@@ -2571,14 +2678,14 @@ impl<'a> Parser<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::ParseError;
-    use crate::isaspec::IsaSpec;
-    use crate::testfile::{Comment, Details};
-    use cranelift_codegen::ir::entities::AnyEntity;
-    use cranelift_codegen::ir::types;
-    use cranelift_codegen::ir::StackSlotKind;
-    use cranelift_codegen::ir::{ArgumentExtension, ArgumentPurpose};
-    use cranelift_codegen::isa::CallConv;
+    use crate::ir::entities::AnyEntity;
+    use crate::ir::types;
+    use crate::ir::StackSlotKind;
+    use crate::ir::{ArgumentExtension, ArgumentPurpose};
+    use crate::isa::CallConv;
+    use crate::parse::error::ParseError;
+    use crate::parse::isaspec::IsaSpec;
+    use crate::parse::testfile::{Comment, Details};
 
     #[test]
     fn argument_type() {
@@ -2638,6 +2745,21 @@ mod tests {
             "(i8 uext, f32, f64, i32 sret) -> i32 sext, f64 baldrdash"
         );
         assert_eq!(sig2.call_conv, CallConv::Baldrdash);
+        assert!(!sig2.variadic);
+
+        let sig3 = Parser::new("(i64, f64) -> i32 system_v variadic")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig3.call_conv, CallConv::SystemV);
+        assert!(sig3.variadic);
+        assert_eq!(sig3.to_string(), "(i64, f64) -> i32 system_v variadic");
+        assert_eq!(
+            Parser::new("(i64) variadic")
+                .parse_signature(None)
+                .unwrap()
+                .to_string(),
+            "(i64) fast variadic"
+        );
 
         // Old-style signature without a calling convention.
         assert_eq!(
@@ -2873,6 +2995,43 @@ mod tests {
         assert!(!is_warning);
     }
 
+    #[test]
+    fn function_decl_linkage() {
+        let func = Parser::new(
+            "function %f() system_v {
+                sig0 = ()
+                fn0 = %foo sig0
+                fn1 = colocated local hidden %bar sig0
+                fn2 = weak %baz sig0
+            ebb0:
+                return
+            }",
+        )
+        .parse_function(None)
+        .unwrap()
+        .0;
+        let fn0 = &func.dfg.ext_funcs[FuncRef::with_number(0).unwrap()];
+        assert_eq!(fn0.linkage, Linkage::Import);
+        assert_eq!(fn0.visibility, Visibility::Default);
+        let fn1 = &func.dfg.ext_funcs[FuncRef::with_number(1).unwrap()];
+        assert!(fn1.colocated);
+        assert_eq!(fn1.linkage, Linkage::Local);
+        assert_eq!(fn1.visibility, Visibility::Hidden);
+        assert_eq!(fn1.to_string(), "colocated local hidden %bar sig0");
+        let fn2 = &func.dfg.ext_funcs[FuncRef::with_number(2).unwrap()];
+        assert_eq!(fn2.linkage, Linkage::Weak);
+        assert_eq!(fn2.to_string(), "weak %baz sig0");
+
+        let ParseError { message, .. } = Parser::new(
+            "function %f() system_v {
+                sig0 = ()
+                fn0 = global %foo sig0",
+        )
+        .parse_function(None)
+        .unwrap_err();
+        assert_eq!(message, "unknown linkage or visibility: global");
+    }
+
     #[test]
     fn comments() {
         let (func, Details { comments, .. }) = Parser::new(
@@ -2949,6 +3108,8 @@ mod tests {
         assert!(parse_test(
             "target
                             function %foo() system_v {}",
+            None,
+            None,
         )
         .is_err());
 
@@ -2956,13 +3117,17 @@ mod tests {
             "target riscv32
                             set enable_float=false
                             function %foo() system_v {}",
+            None,
+            None,
         )
         .is_err());
 
         match parse_test(
             "set enable_float=false
-                          isa riscv
+                          target riscv32
                           function %foo() system_v {}",
+            None,
+            None,
         )
         .unwrap()
         .isa_spec
@@ -6,13 +6,11 @@
 //! The `SourceMap` struct defined in this module makes this mapping available
 //! to parser clients.
 
-use crate::error::{Location, ParseResult};
-use crate::lexer::split_entity_name;
-use cranelift_codegen::ir::entities::AnyEntity;
-use cranelift_codegen::ir::{
-    Ebb, FuncRef, GlobalValue, Heap, JumpTable, SigRef, StackSlot, Table, Value,
-};
-use std::collections::HashMap;
+use crate::ir::entities::AnyEntity;
+use crate::ir::{Ebb, FuncRef, GlobalValue, Heap, JumpTable, SigRef, StackSlot, Table, Value};
+use crate::parse::error::{Location, ParseResult};
+use crate::parse::lexer::split_entity_name;
+use crate::HashMap;
 
 /// Mapping from entity names to source locations.
 #[derive(Debug, Default)]
@@ -211,7 +209,8 @@ impl SourceMap {
 
 #[cfg(test)]
 mod tests {
-    use crate::parse_test;
+    use crate::parse::parse_test;
+    use std::string::ToString;
 
     #[test]
     fn details() {
@@ -13,6 +13,7 @@
 //! the general format into a `TestCommand` data structure.
 
 use std::fmt::{self, Display, Formatter};
+use std::vec::Vec;
 
 /// A command appearing in a test file.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -81,6 +82,7 @@ impl<'a> Display for TestOption<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::string::ToString;
 
     #[test]
     fn parse_option() {
@@ -4,12 +4,13 @@
 //! file-based test case.
 //!
 
-use crate::error::Location;
-use crate::isaspec::IsaSpec;
-use crate::sourcemap::SourceMap;
-use crate::testcommand::TestCommand;
-use cranelift_codegen::ir::entities::AnyEntity;
-use cranelift_codegen::ir::Function;
+use crate::ir::entities::AnyEntity;
+use crate::ir::Function;
+use crate::parse::error::Location;
+use crate::parse::isaspec::IsaSpec;
+use crate::parse::sourcemap::SourceMap;
+use crate::parse::testcommand::TestCommand;
+use std::vec::Vec;
 
 /// A parsed test case.
 ///
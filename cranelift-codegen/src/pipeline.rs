@@ -0,0 +1,281 @@
+//! Ordering of the compilation passes.
+//!
+//! `Context::compile()` runs the passes of a `Pipeline`. By default, the pipeline is chosen from
+//! the `opt_level` and `enable_nan_canonicalization` settings of the target ISA, see
+//! `Pipeline::for_flags()`. Embedders can register another pipeline with
+//! `Context::set_pipeline()`, for example to skip the expensive optimizations in a fast-startup
+//! JIT tier, or to insert their own passes.
+//!
+//! Branch relaxation always runs after the passes of the pipeline.
+//!
+//! The `Baseline` compilation mode selects the shortest pipeline, for the first tier of a JIT
+//! that needs code quickly and recompiles the hot functions later.
+
+use crate::ir::Function;
+use crate::isa::TargetIsa;
+use crate::settings::{Flags, OptLevel};
+use core::fmt;
+use std::boxed::Box;
+use std::vec::Vec;
+
+/// How much effort `Context::compile()` spends on the generated code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompileMode {
+    /// Run the pipeline selected by the settings of the target ISA, or set with
+    /// `Context::set_pipeline()`.
+    Standard,
+    /// Generate straightforward code as quickly as possible.
+    ///
+    /// This runs the `Pipeline::baseline()` passes and allocates registers with the linear scan
+    /// algorithm, unless another pipeline or algorithm has been set on the `Context`.
+    Baseline,
+}
+
+impl Default for CompileMode {
+    fn default() -> Self {
+        CompileMode::Standard
+    }
+}
+
+/// A pass run by a user-defined function.
+pub type CustomPass = dyn Fn(&mut Function, &dyn TargetIsa);
+
+/// A compilation pass.
+///
+/// The analyses needed by the built-in passes, like the dominator tree, are computed by
+/// `Context` before running them.
+pub enum Pass {
+    /// Pre-legalization rewrites.
+    Preopt,
+    /// Rematerialize constant EBB parameters.
+    RematConstants,
+    /// Remove unused and constant EBB parameters.
+    PruneEbbParams,
+    /// Fold address computations into loads and stores.
+    ComplexAddresses,
+    /// Remove the heap bounds checks that are proven to be in bounds.
+    HeapBounds,
+    /// Canonicalize the NaNs produced by floating point instructions.
+    CanonicalizeNans,
+    /// Legalization for the target ISA. This is required for code generation.
+    Legalize,
+    /// Post-legalization rewrites.
+    Postopt,
+    /// Remove the conditional traps dominated by an identical check.
+    RedundantTraps,
+    /// Loop invariant code motion.
+    Licm,
+    /// Global value numbering.
+    Gvn,
+    /// Dead code elimination, including unreachable code.
+    Dce,
+    /// Unreachable code elimination.
+    UnreachableCode,
+    /// Reorder the EBBs according to the branch weights.
+    EbbLayout,
+    /// Register allocation. This is required for code generation.
+    Regalloc,
+    /// Prologue and epilogue insertion. This is required for code generation.
+    PrologueEpilogue,
+    /// Peephole optimizations on the register allocated code.
+    Peephole,
+    /// Shrink the instruction encodings.
+    ShrinkInstructions,
+    /// A pass defined by the embedder, with its name.
+    ///
+    /// The function may change the control flow graph, which is recomputed after it runs. It is
+    /// responsible for leaving the function in a state expected by the following passes, for
+    /// example legal for the target ISA when it runs after `Legalize`.
+    Custom(&'static str, Box<CustomPass>),
+}
+
+impl Pass {
+    /// Create a pass named `name` running `f`.
+    pub fn custom<F>(name: &'static str, f: F) -> Self
+    where
+        F: Fn(&mut Function, &dyn TargetIsa) + 'static,
+    {
+        Pass::Custom(name, Box::new(f))
+    }
+
+    /// Get the name of this pass, as reported to the `CompileObserver`.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Pass::Preopt => "preopt",
+            Pass::RematConstants => "remat_constants",
+            Pass::PruneEbbParams => "prune_ebb_params",
+            Pass::ComplexAddresses => "complex_addresses",
+            Pass::HeapBounds => "heap_bounds",
+            Pass::CanonicalizeNans => "canonicalize_nans",
+            Pass::Legalize => "legalize",
+            Pass::Postopt => "postopt",
+            Pass::RedundantTraps => "redundant_traps",
+            Pass::Licm => "licm",
+            Pass::Gvn => "gvn",
+            Pass::Dce => "dce",
+            Pass::UnreachableCode => "unreachable_code",
+            Pass::EbbLayout => "ebb_layout",
+            Pass::Regalloc => "regalloc",
+            Pass::PrologueEpilogue => "prologue_epilogue",
+            Pass::Peephole => "peephole",
+            Pass::ShrinkInstructions => "shrink_instructions",
+            Pass::Custom(name, _) => name,
+        }
+    }
+}
+
+impl fmt::Debug for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Pass::Custom(name, _) => write!(f, "Custom({:?})", name),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+/// An ordered list of compilation passes.
+#[derive(Debug)]
+pub struct Pipeline {
+    passes: Vec<Pass>,
+}
+
+impl Pipeline {
+    /// The pipeline for the `best` optimization level, running all the optimizations.
+    pub fn standard() -> Self {
+        Self::for_opt_level(OptLevel::Best)
+    }
+
+    /// The pipeline for the `fastest` optimization level, running only the passes required for
+    /// code generation.
+    pub fn fast() -> Self {
+        Self::for_opt_level(OptLevel::Fastest)
+    }
+
+    /// The pipeline of the `Baseline` compilation mode.
+    ///
+    /// Only the passes required for code generation run, each of them once over the function.
+    /// Unlike `fast()`, the EBBs are not reordered according to the branch weights.
+    pub fn baseline() -> Self {
+        Self {
+            passes: vec![
+                Pass::Legalize,
+                Pass::UnreachableCode,
+                Pass::Regalloc,
+                Pass::PrologueEpilogue,
+            ],
+        }
+    }
+
+    /// A pipeline running `passes` in order.
+    pub fn custom(passes: Vec<Pass>) -> Self {
+        Self { passes }
+    }
+
+    /// The pipeline selected by the `opt_level` and `enable_nan_canonicalization` settings.
+    ///
+    /// This is used by `Context::compile()` when no pipeline has been set.
+    pub fn for_flags(flags: &Flags) -> Self {
+        let mut pipeline = Self::for_opt_level(flags.opt_level());
+        if flags.enable_nan_canonicalization() {
+            let legalize = pipeline.position("legalize").unwrap();
+            pipeline.passes.insert(legalize, Pass::CanonicalizeNans);
+        }
+        pipeline
+    }
+
+    fn for_opt_level(opt_level: OptLevel) -> Self {
+        let mut passes = Vec::new();
+        if opt_level != OptLevel::Fastest {
+            passes.extend(vec![
+                Pass::Preopt,
+                Pass::RematConstants,
+                Pass::PruneEbbParams,
+                Pass::ComplexAddresses,
+                Pass::HeapBounds,
+            ]);
+        }
+        passes.push(Pass::Legalize);
+        if opt_level != OptLevel::Fastest {
+            passes.push(Pass::Postopt);
+            passes.push(Pass::RedundantTraps);
+        }
+        if opt_level == OptLevel::Best {
+            passes.push(Pass::Licm);
+            passes.push(Pass::Gvn);
+        }
+        if opt_level != OptLevel::Fastest {
+            passes.push(Pass::Dce);
+        } else {
+            passes.push(Pass::UnreachableCode);
+        }
+        passes.extend(vec![
+            Pass::EbbLayout,
+            Pass::Regalloc,
+            Pass::PrologueEpilogue,
+        ]);
+        if opt_level != OptLevel::Fastest {
+            passes.push(Pass::Peephole);
+        }
+        if opt_level == OptLevel::Best {
+            passes.push(Pass::ShrinkInstructions);
+        }
+        Self { passes }
+    }
+
+    /// Get the passes of this pipeline, in order.
+    pub fn passes(&self) -> &[Pass] {
+        &self.passes
+    }
+
+    /// Get the passes of this pipeline, so they can be modified and passed to `custom()`.
+    pub fn into_passes(self) -> Vec<Pass> {
+        self.passes
+    }
+
+    /// Get the position of the first pass named `name`.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.passes.iter().position(|pass| pass.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::{self, Configurable};
+
+    fn names(pipeline: &Pipeline) -> Vec<&'static str> {
+        pipeline.passes().iter().map(Pass::name).collect()
+    }
+
+    #[test]
+    fn pipelines() {
+        assert_eq!(
+            names(&Pipeline::fast()),
+            [
+                "legalize",
+                "unreachable_code",
+                "ebb_layout",
+                "regalloc",
+                "prologue_epilogue"
+            ]
+        );
+        assert_eq!(Pipeline::baseline().position("ebb_layout"), None);
+        let standard = Pipeline::standard();
+        assert!(standard.position("licm").unwrap() < standard.position("gvn").unwrap());
+        assert_eq!(standard.position("canonicalize_nans"), None);
+
+        let mut builder = settings::builder();
+        builder.set("opt_level", "default").unwrap();
+        builder.enable("enable_nan_canonicalization").unwrap();
+        let pipeline = Pipeline::for_flags(&settings::Flags::new(builder));
+        assert_eq!(pipeline.position("licm"), None);
+        assert_eq!(
+            pipeline.position("canonicalize_nans").unwrap() + 1,
+            pipeline.position("legalize").unwrap()
+        );
+
+        let mut passes = Pipeline::fast().into_passes();
+        passes.insert(1, Pass::custom("nop", |_, _| {}));
+        assert_eq!(names(&Pipeline::custom(passes))[1], "nop");
+    }
+}
@@ -2,12 +2,14 @@
 
 #![allow(non_snake_case)]
 
-use crate::cursor::{Cursor, EncCursor};
+use crate::complex_addresses::fold_address;
+use crate::cursor::{Cursor, CursorPosition, EncCursor};
+use crate::entity::SecondaryMap;
 use crate::ir::condcodes::{CondCode, FloatCC, IntCC};
 use crate::ir::dfg::ValueDef;
-use crate::ir::immediates::{Imm64, Offset32};
+use crate::ir::immediates::Imm64;
 use crate::ir::instructions::{Opcode, ValueList};
-use crate::ir::{Ebb, Function, Inst, InstBuilder, InstructionData, MemFlags, Type, Value};
+use crate::ir::{Ebb, Function, Inst, InstBuilder, InstructionData, MemFlags, Value};
 use crate::isa::TargetIsa;
 use crate::timing;
 
@@ -170,187 +172,181 @@ fn optimize_cpu_flags(
     debug_assert!(ok);
 }
 
-struct MemOpInfo {
-    opcode: Opcode,
-    itype: Type,
-    arg: Value,
-    st_arg: Option<Value>,
-    flags: MemFlags,
-    offset: Offset32,
+/// Fold the computation of the address of a load or store into the instruction itself.
+fn optimize_complex_addresses(pos: &mut EncCursor, inst: Inst, isa: &dyn TargetIsa) {
+    if fold_address(pos.func, inst, isa.address_modes()) {
+        let ok = pos.func.update_encoding(inst, isa).is_ok();
+        debug_assert!(ok);
+    }
 }
 
-fn optimize_complex_addresses(pos: &mut EncCursor, inst: Inst, isa: &dyn TargetIsa) {
-    // Look for simple loads and stores we can optimize.
-    let info = match pos.func.dfg[inst] {
-        InstructionData::Load {
-            opcode,
-            arg,
-            flags,
-            offset,
-        } => MemOpInfo {
-            opcode,
-            itype: pos.func.dfg.ctrl_typevar(inst),
-            arg,
-            st_arg: None,
-            flags,
-            offset,
-        },
-        InstructionData::Store {
-            opcode,
+//----------------------------------------------------------------------
+//
+// Bit manipulation patterns.
+
+/// Fold a `band` and the instruction computing one of its operands into a single bit manipulation
+/// instruction, if the ISA has an encoding for it:
+///
+/// - `band x, (bnot y)` becomes `band_not x, y`.
+/// - `band x, (iadd_imm x, -1)` becomes `x86_blsr x`, which clears the lowest set bit of `x`.
+///
+/// The instruction computing the operand is left in place, and removed by DCE if it has no other
+/// uses.
+fn optimize_bit_manipulation(pos: &mut EncCursor, inst: Inst, isa: &dyn TargetIsa) {
+    let args = match pos.func.dfg[inst] {
+        InstructionData::Binary {
+            opcode: Opcode::Band,
             args,
-            flags,
-            offset,
-        } => MemOpInfo {
-            opcode,
-            itype: pos.func.dfg.ctrl_typevar(inst),
-            arg: args[1],
-            st_arg: Some(args[0]),
-            flags,
-            offset,
-        },
+        } => args,
         _ => return,
     };
 
-    // Examine the instruction that defines the address operand.
-    if let ValueDef::Result(result_inst, _) = pos.func.dfg.value_def(info.arg) {
-        match pos.func.dfg[result_inst] {
-            InstructionData::Binary {
-                opcode: Opcode::Iadd,
-                args,
-            } => match info.opcode {
-                // Operand is an iadd. Fold it into a memory address with a complex address mode.
-                Opcode::Load => {
-                    pos.func.dfg.replace(inst).load_complex(
-                        info.itype,
-                        info.flags,
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Uload8 => {
-                    pos.func.dfg.replace(inst).uload8_complex(
-                        info.itype,
-                        info.flags,
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Sload8 => {
-                    pos.func.dfg.replace(inst).sload8_complex(
-                        info.itype,
-                        info.flags,
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Uload16 => {
-                    pos.func.dfg.replace(inst).uload16_complex(
-                        info.itype,
-                        info.flags,
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Sload16 => {
-                    pos.func.dfg.replace(inst).sload16_complex(
-                        info.itype,
-                        info.flags,
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Uload32 => {
-                    pos.func
-                        .dfg
-                        .replace(inst)
-                        .uload32_complex(info.flags, &args, info.offset);
-                }
-                Opcode::Sload32 => {
-                    pos.func
-                        .dfg
-                        .replace(inst)
-                        .sload32_complex(info.flags, &args, info.offset);
-                }
-                Opcode::Store => {
-                    pos.func.dfg.replace(inst).store_complex(
-                        info.flags,
-                        info.st_arg.unwrap(),
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Istore8 => {
-                    pos.func.dfg.replace(inst).istore8_complex(
-                        info.flags,
-                        info.st_arg.unwrap(),
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Istore16 => {
-                    pos.func.dfg.replace(inst).istore16_complex(
-                        info.flags,
-                        info.st_arg.unwrap(),
-                        &args,
-                        info.offset,
-                    );
-                }
-                Opcode::Istore32 => {
-                    pos.func.dfg.replace(inst).istore32_complex(
-                        info.flags,
-                        info.st_arg.unwrap(),
-                        &args,
-                        info.offset,
-                    );
-                }
-                _ => panic!("Unsupported load or store opcode"),
-            },
+    let old_data = pos.func.dfg[inst].clone();
+    for &(x, other) in &[(args[0], args[1]), (args[1], args[0])] {
+        let def_inst = match pos.func.dfg.value_def(other) {
+            ValueDef::Result(def_inst, _) => def_inst,
+            ValueDef::Param(..) => continue,
+        };
+        match pos.func.dfg[def_inst] {
+            InstructionData::Unary {
+                opcode: Opcode::Bnot,
+                arg,
+            } => {
+                pos.func.dfg.replace(inst).band_not(x, arg);
+            }
             InstructionData::BinaryImm {
                 opcode: Opcode::IaddImm,
                 arg,
                 imm,
-            } => match pos.func.dfg[inst] {
-                // Operand is an iadd_imm. Fold the immediate into the offset if possible.
-                InstructionData::Load {
-                    arg: ref mut load_arg,
-                    ref mut offset,
-                    ..
-                } => {
-                    if let Some(imm) = offset.try_add_i64(imm.into()) {
-                        *load_arg = arg;
-                        *offset = imm;
-                    } else {
-                        // Overflow.
-                        return;
-                    }
-                }
-                InstructionData::Store {
-                    args: ref mut store_args,
-                    ref mut offset,
-                    ..
-                } => {
-                    if let Some(imm) = offset.try_add_i64(imm.into()) {
-                        store_args[1] = arg;
-                        *offset = imm;
-                    } else {
-                        // Overflow.
-                        return;
-                    }
-                }
-                _ => panic!(),
-            },
-            _ => {
-                // Address value is defined by some other kind of instruction.
-                return;
+            } if imm == Imm64::new(-1)
+                && pos.func.dfg.resolve_aliases(arg) == pos.func.dfg.resolve_aliases(x) =>
+            {
+                pos.func.dfg.replace(inst).x86_blsr(x);
             }
+            _ => continue,
         }
-    } else {
-        // Address value is not the result of an instruction.
-        return;
+
+        if pos.func.update_encoding(inst, isa).is_ok() {
+            return;
+        }
+        pos.func.dfg[inst] = old_data.clone();
     }
+}
 
-    let ok = pos.func.update_encoding(inst, isa).is_ok();
-    debug_assert!(ok);
+//----------------------------------------------------------------------
+//
+// Memory operands.
+
+/// Count the number of uses of each value.
+fn count_uses(func: &Function) -> SecondaryMap<Value, u32> {
+    let mut uses = SecondaryMap::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                uses[arg] += 1;
+            }
+        }
+    }
+    uses
+}
+
+/// Can `load` be moved down to `inst` in the same EBB? The instructions in between must not write
+/// to memory. If the load can trap, they must not trap or branch either, so the load still traps
+/// exactly when it did before.
+fn can_move_load(func: &Function, load: Inst, inst: Inst, flags: MemFlags) -> bool {
+    let mut next = func.layout.next_inst(load);
+    while let Some(between) = next {
+        if between == inst {
+            return true;
+        }
+        let effects = func.dfg[between].opcode().effects();
+        if effects.may_clobber_memory() {
+            return false;
+        }
+        if !flags.notrap() && (effects.can_trap || effects.is_branch) {
+            return false;
+        }
+        next = func.layout.next_inst(between);
+    }
+    false
+}
+
+/// Fold a `load` whose only use is an `iadd`, `band`, or `ifcmp` into that instruction as a memory
+/// operand:
+///
+/// - `iadd x, (load p+off)` becomes `x86_iadd_load x, p+off`, and likewise for `band`.
+/// - `ifcmp x, (load p+off)` becomes `x86_ifcmp_load x, p+off`.
+///
+/// This saves an instruction and the register holding the loaded value. The load is removed, and
+/// the folded instruction takes over its memory flags and source location, so it traps in its
+/// place.
+fn fold_memory_operand(
+    pos: &mut EncCursor,
+    inst: Inst,
+    uses: &SecondaryMap<Value, u32>,
+    isa: &dyn TargetIsa,
+) {
+    let (opcode, args) = match pos.func.dfg[inst] {
+        InstructionData::Binary { opcode, args } => (opcode, args),
+        _ => return,
+    };
+    // The loaded value must be the second operand of the comparison, since swapping the operands
+    // would change the meaning of the flags.
+    let candidates = match opcode {
+        Opcode::Iadd | Opcode::Band => 2,
+        Opcode::Ifcmp => 1,
+        _ => return,
+    };
+
+    let old_data = pos.func.dfg[inst].clone();
+    for &(x, loaded) in [(args[0], args[1]), (args[1], args[0])][..candidates].iter() {
+        if uses[loaded] != 1 {
+            continue;
+        }
+        let load = match pos.func.dfg.value_def(loaded) {
+            ValueDef::Result(load, _) => load,
+            ValueDef::Param(..) => continue,
+        };
+        let (flags, addr, offset) = match pos.func.dfg[load] {
+            InstructionData::Load {
+                opcode: Opcode::Load,
+                flags,
+                arg,
+                offset,
+            } => (flags, arg, offset),
+            _ => continue,
+        };
+        if !can_move_load(pos.func, load, inst, flags) {
+            continue;
+        }
+
+        match opcode {
+            Opcode::Iadd => pos
+                .func
+                .dfg
+                .replace(inst)
+                .x86_iadd_load(flags, x, addr, offset),
+            Opcode::Band => pos
+                .func
+                .dfg
+                .replace(inst)
+                .x86_band_load(flags, x, addr, offset),
+            _ => pos
+                .func
+                .dfg
+                .replace(inst)
+                .x86_ifcmp_load(flags, x, addr, offset),
+        };
+        if pos.func.update_encoding(inst, isa).is_ok() {
+            // Report a trap at the source location of the load.
+            if !flags.notrap() {
+                pos.func.srclocs[inst] = pos.func.srclocs[load];
+            }
+            pos.func.layout.remove_inst(load);
+            return;
+        }
+        pos.func.dfg[inst] = old_data.clone();
+    }
 }
 
 //----------------------------------------------------------------------
@@ -381,6 +377,20 @@ pub fn do_postopt(func: &mut Function, isa: &dyn TargetIsa) {
             if isa.uses_complex_addresses() {
                 optimize_complex_addresses(&mut pos, inst, isa);
             }
+
+            optimize_bit_manipulation(&mut pos, inst, isa);
+        }
+    }
+
+    // Fold loads into the instructions using them in a separate sweep, after the comparisons above
+    // have been turned into flags.
+    if isa.uses_memory_operands() {
+        let uses = count_uses(pos.func);
+        pos.set_position(CursorPosition::Nowhere);
+        while let Some(_ebb) = pos.next_ebb() {
+            while let Some(inst) = pos.next_inst() {
+                fold_memory_operand(&mut pos, inst, &uses, isa);
+            }
         }
     }
 }
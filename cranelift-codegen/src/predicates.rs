@@ -25,6 +25,20 @@ pub fn is_zero_32_bit_float<T: Into<ir::immediates::Ieee32>>(x: T) -> bool {
     x32.bits() == 0
 }
 
+/// Check that a 64-bit floating point value has all its bits set.
+#[allow(dead_code)]
+pub fn is_all_ones_64_bit_float<T: Into<ir::immediates::Ieee64>>(x: T) -> bool {
+    let x64 = x.into();
+    x64.bits() == u64::max_value()
+}
+
+/// Check that a 32-bit floating point value has all its bits set.
+#[allow(dead_code)]
+pub fn is_all_ones_32_bit_float<T: Into<ir::immediates::Ieee32>>(x: T) -> bool {
+    let x32 = x.into();
+    x32.bits() == u32::max_value()
+}
+
 /// Check that `x` is the same as `y`.
 #[allow(dead_code)]
 pub fn is_equal<T: Eq + Copy, O: Into<T> + Copy>(x: T, y: O) -> bool {
@@ -55,7 +69,8 @@ pub fn is_colocated_func(func_ref: ir::FuncRef, func: &ir::Function) -> bool {
 #[allow(dead_code)]
 pub fn is_colocated_data(global_value: ir::GlobalValue, func: &ir::Function) -> bool {
     match func.global_values[global_value] {
-        ir::GlobalValueData::Symbol { colocated, .. } => colocated,
+        ir::GlobalValueData::Symbol { colocated, .. }
+        | ir::GlobalValueData::TlsValue { colocated, .. } => colocated,
         _ => panic!("is_colocated_data only makes sense for data with symbolic addresses"),
     }
 }
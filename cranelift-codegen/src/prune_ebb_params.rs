@@ -0,0 +1,165 @@
+//! Pruning of redundant EBB parameters.
+//!
+//! Frontends that build SSA form from variables create an EBB parameter for every variable that
+//! is live into a join point, whether or not its value differs between the predecessors. This
+//! pass removes two kinds of redundant parameters:
+//!
+//! - Parameters that are never used, except by branches passing them back to the same EBB.
+//! - Parameters that receive the same value from every predecessor, not counting branches that
+//!   pass the parameter back to itself. The parameter becomes an alias of that value.
+//!
+//! Removing a parameter removes an argument from every branch to its EBB, which can make other
+//! parameters redundant, so the pass iterates until nothing changes. The control flow graph and
+//! the dominator tree are not changed by this pass.
+
+use crate::dominator_tree::DominatorTree;
+use crate::entity::SecondaryMap;
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::dfg::ValueDef;
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{Ebb, Function, Inst, Value};
+use crate::timing;
+use log::debug;
+use std::vec::Vec;
+
+/// Remove the EBB parameters of `func` that are unused or always receive the same value.
+pub fn do_prune_ebb_params(func: &mut Function, cfg: &ControlFlowGraph, domtree: &DominatorTree) {
+    let _tt = timing::prune_ebb_params();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+
+    let mut uses = count_uses(func);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        for ebb in ebbs {
+            changed |= prune_ebb_params(func, cfg, domtree, &mut uses, ebb);
+        }
+    }
+}
+
+/// Count the uses of each value by the instructions in the layout, after resolving aliases.
+fn count_uses(func: &Function) -> SecondaryMap<Value, u32> {
+    let mut uses = SecondaryMap::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                uses[func.dfg.resolve_aliases(arg)] += 1;
+            }
+        }
+    }
+    uses
+}
+
+/// Remove the redundant parameters of `ebb`, keeping `uses` up to date. Returns true if any
+/// parameter was removed.
+fn prune_ebb_params(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    uses: &mut SecondaryMap<Value, u32>,
+    ebb: Ebb,
+) -> bool {
+    // The parameters of the entry block are the function arguments, and dominance is ill defined
+    // for unreachable EBBs.
+    if func.layout.entry_block() == Some(ebb)
+        || func.dfg.num_ebb_params(ebb) == 0
+        || !domtree.is_reachable(ebb)
+    {
+        return false;
+    }
+
+    // Every predecessor must pass arguments to the parameters.
+    let branches: Vec<Inst> = cfg.pred_iter(ebb).map(|pred| pred.inst).collect();
+    for &inst in &branches {
+        match func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(dest, _) if dest == ebb => {}
+            _ => return false,
+        }
+    }
+
+    let mut changed = false;
+    let mut num = 0;
+    while num < func.dfg.num_ebb_params(ebb) {
+        let param = func.dfg.ebb_params(ebb)[num];
+        let args: Vec<Value> = branches
+            .iter()
+            .map(|&inst| match func.dfg.analyze_branch(inst) {
+                BranchInfo::SingleDest(_, args) => func.dfg.resolve_aliases(args[num]),
+                _ => unreachable!(),
+            })
+            .collect();
+        let self_uses = args.iter().filter(|&&arg| arg == param).count() as u32;
+
+        let replacement = if uses[param] == self_uses {
+            None
+        } else {
+            match incoming_value(func, domtree, &args, param, ebb) {
+                Some(value) => Some(value),
+                None => {
+                    num += 1;
+                    continue;
+                }
+            }
+        };
+        changed = true;
+
+        match replacement {
+            Some(value) => debug!("Replacing {} with {} in {}", param, value, ebb),
+            None => debug!("Removing unused {} from {}", param, ebb),
+        }
+
+        // Remove the argument from every branch to `ebb`.
+        for (&inst, &arg) in branches.iter().zip(&args) {
+            let index = func.dfg[inst]
+                .opcode()
+                .constraints()
+                .num_fixed_value_arguments()
+                + num;
+            let mut list = func.dfg[inst]
+                .take_value_list()
+                .expect("branches have a value list");
+            list.remove(index, &mut func.dfg.value_lists);
+            func.dfg[inst].put_value_list(list);
+            uses[arg] -= 1;
+        }
+
+        func.dfg.remove_ebb_param(param);
+        if let Some(value) = replacement {
+            uses[value] += uses[param];
+            func.dfg.change_to_alias(param, value);
+        }
+    }
+    changed
+}
+
+/// Get the value passed to `param` by every branch, ignoring branches that pass `param` itself.
+///
+/// Returns `None` if the branches pass different values, or if the value isn't available at the
+/// top of `ebb`.
+fn incoming_value(
+    func: &Function,
+    domtree: &DominatorTree,
+    args: &[Value],
+    param: Value,
+    ebb: Ebb,
+) -> Option<Value> {
+    let mut value = None;
+    for &arg in args {
+        if arg == param {
+            continue;
+        }
+        match value {
+            Some(v) if v != arg => return None,
+            _ => value = Some(arg),
+        }
+    }
+
+    let value = value?;
+    match func.dfg.value_def(value) {
+        ValueDef::Param(def_ebb, _) if def_ebb == ebb => None,
+        def if domtree.dominates(def, ebb, &func.layout) => Some(value),
+        _ => None,
+    }
+}
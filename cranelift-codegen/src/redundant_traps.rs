@@ -0,0 +1,122 @@
+//! Redundant trap elimination.
+//!
+//! A conditional trap can be removed when it is dominated by a trap with the same condition on
+//! the same value: if the condition held, the dominating trap has already been taken. This is
+//! common in WebAssembly code, which checks the same heap bound again for each access.
+//!
+//! Two values are considered the same when they are computed by identical instructions without
+//! side effects from the same operands, up to a small depth. This catches comparisons that are
+//! recomputed before each check.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::ir::condcodes::{FloatCC, IntCC};
+use crate::ir::dfg::ValueDef;
+use crate::ir::{DataFlowGraph, Function, Inst, InstructionData, Opcode, Value, ValueListPool};
+use crate::timing;
+use crate::HashMap;
+use log::debug;
+use std::vec::Vec;
+
+/// Number of instructions to look through when comparing values.
+const MAX_DEPTH: u32 = 4;
+
+/// The condition tested by a trap instruction, without its value.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Condition {
+    Zero,
+    NonZero,
+    Int(IntCC),
+    Float(FloatCC),
+}
+
+/// Remove the conditional traps in `func` that are dominated by the same check.
+pub fn do_redundant_traps(func: &mut Function, domtree: &DominatorTree) {
+    let _tt = timing::redundant_traps();
+    debug_assert!(domtree.is_valid());
+
+    // The traps seen so far for each condition, with their tested value. The EBBs are visited in
+    // reverse post-order, so dominating traps are seen first.
+    let mut checks: HashMap<Condition, Vec<(Inst, Value)>> = HashMap::new();
+    let mut pos = FuncCursor::new(func);
+    for &ebb in domtree.cfg_postorder().iter().rev() {
+        pos.goto_top(ebb);
+        while let Some(inst) = pos.next_inst() {
+            let (condition, arg) = match pos.func.dfg[inst] {
+                InstructionData::CondTrap { opcode, arg, .. } => match opcode {
+                    Opcode::Trapz => (Condition::Zero, arg),
+                    Opcode::Trapnz => (Condition::NonZero, arg),
+                    _ => continue,
+                },
+                InstructionData::IntCondTrap { cond, arg, .. } => (Condition::Int(cond), arg),
+                InstructionData::FloatCondTrap { cond, arg, .. } => (Condition::Float(cond), arg),
+                _ => continue,
+            };
+
+            let seen = checks.entry(condition).or_default();
+            let checked = seen.iter().any(|&(check, value)| {
+                same_value(&pos.func.dfg, value, arg, MAX_DEPTH)
+                    && domtree.dominates(check, inst, &pos.func.layout)
+            });
+            if !checked {
+                seen.push((inst, arg));
+                continue;
+            }
+
+            debug!(
+                "Removing redundant {}",
+                pos.func.dfg.display_inst(inst, None)
+            );
+            pos.remove_inst_and_step_back();
+        }
+    }
+}
+
+/// Do `a` and `b` always have the same value?
+///
+/// This looks through up to `depth` levels of instructions without side effects.
+fn same_value(dfg: &DataFlowGraph, a: Value, b: Value, depth: u32) -> bool {
+    let a = dfg.resolve_aliases(a);
+    let b = dfg.resolve_aliases(b);
+    if a == b {
+        return true;
+    }
+    if depth == 0 {
+        return false;
+    }
+
+    let (a_inst, b_inst) = match (dfg.value_def(a), dfg.value_def(b)) {
+        (ValueDef::Result(a_inst, a_num), ValueDef::Result(b_inst, b_num)) if a_num == b_num => {
+            (a_inst, b_inst)
+        }
+        _ => return false,
+    };
+    let effects = dfg[a_inst].opcode().effects();
+    if effects.has_side_effects()
+        || effects.reads_memory
+        || dfg.ctrl_typevar(a_inst) != dfg.ctrl_typevar(b_inst)
+    {
+        return false;
+    }
+
+    // Substitute the arguments of `b_inst` into a copy of `a_inst` once they are known to be the
+    // same, so the remaining fields can be compared directly. Instructions with value lists are
+    // not compared, since their arguments live in the function's pool.
+    let mut data = dfg[a_inst].clone();
+    if data.take_value_list().is_some() {
+        return false;
+    }
+    let b_args = dfg.inst_args(b_inst);
+    let mut scratch = ValueListPool::new();
+    let args = data.arguments_mut(&mut scratch);
+    if args.len() != b_args.len() {
+        return false;
+    }
+    for (arg, &b_arg) in args.iter_mut().zip(b_args) {
+        if !same_value(dfg, *arg, b_arg, depth - 1) {
+            return false;
+        }
+        *arg = b_arg;
+    }
+    data.eq(&dfg[b_inst], &dfg.value_lists)
+}
@@ -289,6 +289,7 @@ impl<'a> Context<'a> {
         );
         let ty = self.func.dfg.value_type(param);
         let new_val = self.func.dfg.replace_ebb_param(param, ty);
+        self.func.dfg.copy_value_labels(param, new_val);
 
         // Insert a copy instruction at the top of `ebb`.
         let mut pos = EncCursor::new(self.func, self.isa).at_first_inst(ebb);
@@ -353,6 +354,7 @@ impl<'a> Context<'a> {
         pos.use_srcloc(pred_inst);
         let copy = pos.ins().copy(pred_val);
         let inst = pos.built_inst();
+        pos.func.dfg.copy_value_labels(pred_val, copy);
 
         // Create a live range for the new value.
         // TODO: Handle affinity for ghost values.
@@ -41,9 +41,17 @@
 //! been visited before the destination EBB. Therefore, the EBB's arguments are already colored.
 //!
 //! The exception is the entry block whose arguments are colored from the ABI requirements.
+//!
+//! # Coalescing branch arguments
+//!
+//! When a value is passed as a branch argument to an EBB whose parameters have already been
+//! colored, the value must be moved into the parameter's register before the branch. This is
+//! typical for the back edges of loops. To avoid these moves, a value that is passed to an EBB
+//! parameter is defined in the parameter's register if it is available at the definition.
 
 use crate::cursor::{Cursor, EncCursor};
 use crate::dominator_tree::DominatorTree;
+use crate::entity::SecondaryMap;
 use crate::ir::{AbiParam, ArgumentLoc, InstBuilder, ValueDef};
 use crate::ir::{Ebb, Function, Inst, Layout, SigRef, Value, ValueLoc};
 use crate::isa::{regs_overlap, RegClass, RegInfo, RegUnit};
@@ -66,6 +74,7 @@ use log::debug;
 pub struct Coloring {
     divert: RegDiversions,
     solver: Solver,
+    branch_params: SecondaryMap<Value, PackedOption<Value>>,
 }
 
 /// Bundle of references that the coloring algorithm needs.
@@ -95,6 +104,10 @@ struct Context<'a> {
     divert: &'a mut RegDiversions,
     solver: &'a mut Solver,
 
+    // The EBB parameter that each branch argument is passed to. When a value is passed to more
+    // than one parameter, the first branch in layout order wins.
+    branch_params: &'a mut SecondaryMap<Value, PackedOption<Value>>,
+
     // Pristine set of registers that the allocator can use.
     // This set remains immutable, we make clones.
     usable_regs: RegisterSet,
@@ -106,6 +119,7 @@ impl Coloring {
         Self {
             divert: RegDiversions::new(),
             solver: Solver::new(),
+            branch_params: SecondaryMap::new(),
         }
     }
 
@@ -113,6 +127,7 @@ impl Coloring {
     pub fn clear(&mut self) {
         self.divert.clear();
         self.solver.clear();
+        self.branch_params.clear();
     }
 
     /// Run the coloring algorithm over `func`.
@@ -135,6 +150,7 @@ impl Coloring {
             liveness,
             divert: &mut self.divert,
             solver: &mut self.solver,
+            branch_params: &mut self.branch_params,
         };
         ctx.run(tracker)
     }
@@ -147,6 +163,7 @@ impl<'a> Context<'a> {
             .func
             .locations
             .resize(self.cur.func.dfg.num_values());
+        self.find_branch_params();
 
         // Visit blocks in reverse post-order. We need to ensure that at least one predecessor has
         // been visited before each EBB. That guarantees that the EBB arguments have been colored.
@@ -155,6 +172,34 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Find the EBB parameter that each branch argument is passed to.
+    fn find_branch_params(&mut self) {
+        use crate::ir::instructions::BranchInfo::*;
+
+        self.branch_params.clear();
+        let func = &self.cur.func;
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let SingleDest(dest, args) = func.dfg.analyze_branch(inst) {
+                    for (&arg, &param) in args.iter().zip(func.dfg.ebb_params(dest)) {
+                        if self.branch_params[arg].is_none() {
+                            self.branch_params[arg] = param.into();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Get the register of the EBB parameter that `value` is passed to, if it has been colored.
+    fn branch_param_reg(&self, value: Value) -> Option<RegUnit> {
+        let param = self.branch_params[value].expand()?;
+        match self.cur.func.locations[param] {
+            ValueLoc::Reg(reg) => Some(reg),
+            _ => None,
+        }
+    }
+
     /// Visit `ebb`, assuming that the immediate dominator has already been visited.
     fn visit_ebb(&mut self, ebb: Ebb, tracker: &mut LiveValueTracker) {
         debug!("Coloring {}:", ebb);
@@ -808,7 +853,9 @@ impl<'a> Context<'a> {
                 | ConstraintKind::FixedTied(_)
                 | ConstraintKind::Stack => continue,
                 ConstraintKind::Reg => {
-                    self.solver.add_def(lv.value, op.regclass, !lv.is_local);
+                    let hint = self.branch_param_reg(lv.value);
+                    self.solver
+                        .add_def(lv.value, op.regclass, !lv.is_local, hint);
                 }
                 ConstraintKind::Tied(num) => {
                     // Find the input operand we're tied to.
@@ -1031,6 +1078,7 @@ impl<'a> Context<'a> {
             // range that is copied after `inst`.
             let ty = self.cur.func.dfg.value_type(lv.value);
             let local = self.cur.func.dfg.replace_result(lv.value, ty);
+            self.cur.func.dfg.copy_value_labels(lv.value, local);
             self.cur.ins().with_result(lv.value).copy(local);
             let copy = self.cur.built_inst();
 
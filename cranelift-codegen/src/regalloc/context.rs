@@ -5,17 +5,22 @@
 //! avoids allocating data structures independently for each function begin compiled.
 
 use crate::dominator_tree::DominatorTree;
+use crate::ebb_frequency::EbbFrequencies;
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::Function;
 use crate::isa::TargetIsa;
 use crate::regalloc::coalescing::Coalescing;
 use crate::regalloc::coloring::Coloring;
+use crate::regalloc::linear_scan::LinearScan;
 use crate::regalloc::live_value_tracker::LiveValueTracker;
 use crate::regalloc::liveness::Liveness;
 use crate::regalloc::reload::Reload;
+use crate::regalloc::spill_slots::SlotCoalescing;
 use crate::regalloc::spilling::Spilling;
+use crate::regalloc::ssa_destruction::SsaDestruction;
 use crate::regalloc::virtregs::VirtRegs;
 use crate::result::CodegenResult;
+use crate::settings::RegallocAlgorithm;
 use crate::timing;
 use crate::topo_order::TopoOrder;
 use crate::verifier::{
@@ -30,8 +35,11 @@ pub struct Context {
     topo: TopoOrder,
     tracker: LiveValueTracker,
     spilling: Spilling,
+    linear_scan: LinearScan,
+    ssa_destruction: SsaDestruction,
     reload: Reload,
     coloring: Coloring,
+    slot_coalescing: SlotCoalescing,
 }
 
 impl Context {
@@ -47,8 +55,11 @@ impl Context {
             topo: TopoOrder::new(),
             tracker: LiveValueTracker::new(),
             spilling: Spilling::new(),
+            linear_scan: LinearScan::new(),
+            ssa_destruction: SsaDestruction::new(),
             reload: Reload::new(),
             coloring: Coloring::new(),
+            slot_coalescing: SlotCoalescing::new(),
         }
     }
 
@@ -60,8 +71,11 @@ impl Context {
         self.topo.clear();
         self.tracker.clear();
         self.spilling.clear();
+        self.linear_scan.clear();
+        self.ssa_destruction.clear();
         self.reload.clear();
         self.coloring.clear();
+        self.slot_coalescing.clear();
     }
 
     /// Current values liveness state.
@@ -69,16 +83,38 @@ impl Context {
         &self.liveness
     }
 
+    /// Split the CFG edges that the `algorithm` can't pass EBB arguments on.
+    ///
+    /// This must be called before `run()`. Returns true if `func` was changed, and the control flow
+    /// graph and dominator tree need to be recomputed.
+    pub fn split_edges(
+        &mut self,
+        isa: &dyn TargetIsa,
+        func: &mut Function,
+        algorithm: RegallocAlgorithm,
+    ) -> bool {
+        match algorithm {
+            RegallocAlgorithm::Ssa => self.ssa_destruction.split_edges(isa, func),
+            RegallocAlgorithm::Coloring | RegallocAlgorithm::LinearScan => false,
+        }
+    }
+
     /// Allocate registers in `func`.
     ///
     /// After register allocation, all values in `func` have been assigned to a register or stack
     /// location that is consistent with instruction encoding constraints.
+    ///
+    /// The `algorithm` selects how spilling decisions are made, see the `regalloc_algorithm`
+    /// setting. The spilling pass prefers to spill values that are only used in EBBs with a low
+    /// frequency in `freqs`.
     pub fn run(
         &mut self,
         isa: &dyn TargetIsa,
         func: &mut Function,
         cfg: &ControlFlowGraph,
         domtree: &mut DominatorTree,
+        freqs: &EbbFrequencies,
+        algorithm: RegallocAlgorithm,
     ) -> CodegenResult<()> {
         let _tt = timing::regalloc();
         debug_assert!(domtree.is_valid());
@@ -92,6 +128,14 @@ impl Context {
         // phases.
         self.tracker.clear();
 
+        // The `ssa` algorithm doesn't build virtual registers, so there is no CSSA form to verify.
+        let cssa = algorithm != RegallocAlgorithm::Ssa;
+
+        // Pass: Isolate EBB arguments for SSA destruction.
+        if !cssa {
+            self.ssa_destruction.isolate_args(isa, func);
+        }
+
         // Pass: Liveness analysis.
         self.liveness.compute(isa, func, cfg);
 
@@ -104,27 +148,30 @@ impl Context {
         }
 
         // Pass: Coalesce and create Conventional SSA form.
-        self.coalescing.conventional_ssa(
-            isa,
-            func,
-            cfg,
-            domtree,
-            &mut self.liveness,
-            &mut self.virtregs,
-        );
+        if cssa {
+            self.coalescing.conventional_ssa(
+                isa,
+                func,
+                cfg,
+                domtree,
+                &mut self.liveness,
+                &mut self.virtregs,
+            );
+        }
 
         if isa.flags().enable_verifier() {
             let ok = verify_context(func, cfg, domtree, isa, &mut errors).is_ok()
                 && verify_liveness(isa, func, cfg, &self.liveness, &mut errors).is_ok()
-                && verify_cssa(
-                    func,
-                    cfg,
-                    domtree,
-                    &self.liveness,
-                    &self.virtregs,
-                    &mut errors,
-                )
-                .is_ok();
+                && (!cssa
+                    || verify_cssa(
+                        func,
+                        cfg,
+                        domtree,
+                        &self.liveness,
+                        &self.virtregs,
+                        &mut errors,
+                    )
+                    .is_ok());
 
             if !ok {
                 return Err(errors.into());
@@ -132,28 +179,42 @@ impl Context {
         }
 
         // Pass: Spilling.
-        self.spilling.run(
-            isa,
-            func,
-            domtree,
-            &mut self.liveness,
-            &self.virtregs,
-            &mut self.topo,
-            &mut self.tracker,
-        );
+        match algorithm {
+            RegallocAlgorithm::Coloring | RegallocAlgorithm::Ssa => self.spilling.run(
+                isa,
+                func,
+                domtree,
+                freqs,
+                &mut self.liveness,
+                &self.virtregs,
+                &mut self.topo,
+                &mut self.tracker,
+            ),
+            RegallocAlgorithm::LinearScan => {
+                self.linear_scan
+                    .run(isa, func, &mut self.liveness, &self.virtregs)
+            }
+        }
+
+        // Pass: Reconcile the stack locations of EBB arguments and parameters.
+        if !cssa {
+            self.ssa_destruction
+                .place_args(isa, func, &mut self.liveness);
+        }
 
         if isa.flags().enable_verifier() {
             let ok = verify_context(func, cfg, domtree, isa, &mut errors).is_ok()
                 && verify_liveness(isa, func, cfg, &self.liveness, &mut errors).is_ok()
-                && verify_cssa(
-                    func,
-                    cfg,
-                    domtree,
-                    &self.liveness,
-                    &self.virtregs,
-                    &mut errors,
-                )
-                .is_ok();
+                && (!cssa
+                    || verify_cssa(
+                        func,
+                        cfg,
+                        domtree,
+                        &self.liveness,
+                        &self.virtregs,
+                        &mut errors,
+                    )
+                    .is_ok());
 
             if !ok {
                 return Err(errors.into());
@@ -173,15 +234,16 @@ impl Context {
         if isa.flags().enable_verifier() {
             let ok = verify_context(func, cfg, domtree, isa, &mut errors).is_ok()
                 && verify_liveness(isa, func, cfg, &self.liveness, &mut errors).is_ok()
-                && verify_cssa(
-                    func,
-                    cfg,
-                    domtree,
-                    &self.liveness,
-                    &self.virtregs,
-                    &mut errors,
-                )
-                .is_ok();
+                && (!cssa
+                    || verify_cssa(
+                        func,
+                        cfg,
+                        domtree,
+                        &self.liveness,
+                        &self.virtregs,
+                        &mut errors,
+                    )
+                    .is_ok());
 
             if !ok {
                 return Err(errors.into());
@@ -196,15 +258,27 @@ impl Context {
             let ok = verify_context(func, cfg, domtree, isa, &mut errors).is_ok()
                 && verify_liveness(isa, func, cfg, &self.liveness, &mut errors).is_ok()
                 && verify_locations(isa, func, Some(&self.liveness), &mut errors).is_ok()
-                && verify_cssa(
-                    func,
-                    cfg,
-                    domtree,
-                    &self.liveness,
-                    &self.virtregs,
-                    &mut errors,
-                )
-                .is_ok();
+                && (!cssa
+                    || verify_cssa(
+                        func,
+                        cfg,
+                        domtree,
+                        &self.liveness,
+                        &self.virtregs,
+                        &mut errors,
+                    )
+                    .is_ok());
+
+            if !ok {
+                return Err(errors.into());
+            }
+        }
+
+        // Pass: Spill slot coalescing.
+        self.slot_coalescing.run(func, &self.liveness);
+
+        if isa.flags().enable_verifier() {
+            let ok = verify_locations(isa, func, Some(&self.liveness), &mut errors).is_ok();
 
             if !ok {
                 return Err(errors.into());
@@ -0,0 +1,596 @@
+//! Linear scan spilling.
+//!
+//! This pass replaces the spilling pass when the `regalloc_algorithm` setting is `linear_scan`. It
+//! trades some code quality for compile time by making all the spilling decisions in a single
+//! scan over live intervals, in the style of Poletto and Sarkar's linear scan register allocator:
+//!
+//! 1. Number all the EBB headers and instructions in layout order.
+//! 2. Approximate the live range of each virtual register with a single interval covering all the
+//!    program points where any of its values are live.
+//! 3. Visit the intervals in order of increasing start point, keeping track of the register
+//!    pressure of the active intervals. When there are not enough registers, spill the active
+//!    interval that ends last.
+//!
+//! The spilling pass tracks the live values precisely at each instruction, while the intervals
+//! used here overestimate the register pressure, so some values may be spilled needlessly. The
+//! assignment of registers is still done by the reload and coloring passes, which only depend on
+//! the register pressure never exceeding the available registers.
+//!
+//! A spilled value still needs a register at the instructions that use or define it in a
+//! register, since the reload pass will insert `fill` and `spill` instructions there. These short
+//! temporary intervals are added to the scan when the value is spilled, and they can't be spilled
+//! themselves.
+//!
+//! Like the spilling pass, this pass also inserts copies to resolve tied and fixed register
+//! operand constraints, and it spills all the values that are live across a call.
+
+use crate::cursor::{Cursor, EncCursor};
+use crate::entity::{SecondaryMap, SparseMapValue};
+use crate::ir::{
+    ArgumentLoc, Ebb, ExpandedProgramPoint, Function, Inst, InstBuilder, ProgramPoint, Value,
+    ValueLoc,
+};
+use crate::isa::registers::{RegClass, RegClassIndex, RegClassMask};
+use crate::isa::{ConstraintKind, EncInfo, RegInfo, TargetIsa};
+use crate::regalloc::affinity::Affinity;
+use crate::regalloc::liveness::Liveness;
+use crate::regalloc::pressure::Pressure;
+use crate::regalloc::virtregs::VirtRegs;
+use crate::timing;
+use core::cmp::{max, min, Ordering, Reverse};
+use log::debug;
+use std::collections::BinaryHeap;
+use std::vec::Vec;
+
+/// Persistent data structures for the linear scan spilling pass.
+pub struct LinearScan {
+    ebb_pos: SecondaryMap<Ebb, u32>,
+    inst_pos: SecondaryMap<Inst, u32>,
+    calls: Vec<u32>,
+    reg_uses: Vec<RegUse>,
+    intervals: Vec<Interval>,
+    active: Vec<Active>,
+    temps: BinaryHeap<Reverse<(u32, u8)>>,
+    constrained: Vec<ConstrainedUse>,
+}
+
+/// Context data structure that gets instantiated once per pass.
+struct Context<'a> {
+    cur: EncCursor<'a>,
+
+    // Cached ISA information.
+    reginfo: RegInfo,
+    encinfo: EncInfo,
+
+    // References to contextual data structures we need.
+    liveness: &'a mut Liveness,
+    virtregs: &'a VirtRegs,
+
+    // Register pressure of the active intervals.
+    pressure: Pressure,
+
+    // Position of every EBB header and instruction in the linear order.
+    ebb_pos: &'a mut SecondaryMap<Ebb, u32>,
+    inst_pos: &'a mut SecondaryMap<Inst, u32>,
+
+    // Positions of the instructions that clobber the caller-saved registers, in increasing order.
+    calls: &'a mut Vec<u32>,
+
+    // Register uses and defs, sorted by value and position.
+    reg_uses: &'a mut Vec<RegUse>,
+
+    // Live intervals of the virtual registers, sorted by start position.
+    intervals: &'a mut Vec<Interval>,
+
+    // Intervals and temporaries currently holding a register.
+    active: &'a mut Vec<Active>,
+
+    // Pending temporary registers as `(position, register class index)`, earliest first.
+    temps: &'a mut BinaryHeap<Reverse<(u32, u8)>>,
+
+    // Operands with fixed or tied constraints of the current instruction.
+    constrained: &'a mut Vec<ConstrainedUse>,
+}
+
+impl LinearScan {
+    /// Create a new linear scan data structure.
+    pub fn new() -> Self {
+        Self {
+            ebb_pos: SecondaryMap::new(),
+            inst_pos: SecondaryMap::new(),
+            calls: Vec::new(),
+            reg_uses: Vec::new(),
+            intervals: Vec::new(),
+            active: Vec::new(),
+            temps: BinaryHeap::new(),
+            constrained: Vec::new(),
+        }
+    }
+
+    /// Clear all data structures in this linear scan pass.
+    pub fn clear(&mut self) {
+        self.ebb_pos.clear();
+        self.inst_pos.clear();
+        self.calls.clear();
+        self.reg_uses.clear();
+        self.intervals.clear();
+        self.active.clear();
+        self.temps.clear();
+        self.constrained.clear();
+    }
+
+    /// Run the linear scan spilling algorithm over `func`.
+    pub fn run(
+        &mut self,
+        isa: &dyn TargetIsa,
+        func: &mut Function,
+        liveness: &mut Liveness,
+        virtregs: &VirtRegs,
+    ) {
+        let _tt = timing::ra_linear_scan();
+        debug!("Linear scan spilling for:\n{}", func.display(isa));
+        self.clear();
+        let reginfo = isa.register_info();
+        let usable_regs = isa.allocatable_registers(func);
+        let mut ctx = Context {
+            cur: EncCursor::new(func, isa),
+            reginfo: isa.register_info(),
+            encinfo: isa.encoding_info(),
+            liveness,
+            virtregs,
+            pressure: Pressure::new(&reginfo, &usable_regs),
+            ebb_pos: &mut self.ebb_pos,
+            inst_pos: &mut self.inst_pos,
+            calls: &mut self.calls,
+            reg_uses: &mut self.reg_uses,
+            intervals: &mut self.intervals,
+            active: &mut self.active,
+            temps: &mut self.temps,
+            constrained: &mut self.constrained,
+        };
+        ctx.run()
+    }
+}
+
+impl<'a> Context<'a> {
+    fn run(&mut self) {
+        self.number_program_points();
+        self.build_intervals();
+        self.scan();
+    }
+
+    /// Number the EBB headers and instructions in layout order, and collect the register uses and
+    /// calls at each position.
+    ///
+    /// The copies inserted to resolve operand constraints get the same position as the
+    /// instruction they were inserted for.
+    fn number_program_points(&mut self) {
+        let mut pos = 0;
+        while let Some(ebb) = self.cur.next_ebb() {
+            self.ebb_pos[ebb] = pos;
+            if self.cur.func.layout.entry_block() == Some(ebb) {
+                self.collect_entry_params(ebb, pos);
+            }
+            pos += 1;
+
+            while let Some(inst) = self.cur.next_inst() {
+                let opcode = self.cur.func.dfg[inst].opcode();
+                if !opcode.is_ghost() {
+                    self.resolve_constraints(inst, ebb, pos);
+                    self.collect_reg_uses(inst, pos);
+                    if self.cur.func.dfg.call_signature(inst).is_some() || opcode.is_implicit_call()
+                    {
+                        self.calls.push(pos);
+                    }
+                }
+                self.inst_pos[inst] = pos;
+                pos += 1;
+            }
+        }
+        self.reg_uses.sort_unstable_by_key(|u| (u.value, u.pos));
+    }
+
+    /// Entry block parameters passed in registers need a register at the EBB header.
+    fn collect_entry_params(&mut self, ebb: Ebb, pos: u32) {
+        let func = &self.cur.func;
+        for (&value, abi) in func.dfg.ebb_params(ebb).iter().zip(&func.signature.params) {
+            if abi.location.is_reg() {
+                self.reg_uses.push(RegUse {
+                    value,
+                    pos,
+                    rc: self.cur.isa.regclass_for_abi_type(abi.value_type),
+                });
+            }
+        }
+    }
+
+    /// Collect the register operands and register defs of `inst`.
+    fn collect_reg_uses(&mut self, inst: Inst, pos: u32) {
+        let func = &self.cur.func;
+        if let Some(constraints) = self.encinfo.operand_constraints(func.encodings[inst]) {
+            for (op, &value) in constraints.ins.iter().zip(func.dfg.inst_args(inst)) {
+                if op.kind != ConstraintKind::Stack {
+                    self.reg_uses.push(RegUse {
+                        value,
+                        pos,
+                        rc: op.regclass,
+                    });
+                }
+            }
+            for (op, &value) in constraints.outs.iter().zip(func.dfg.inst_results(inst)) {
+                if op.kind != ConstraintKind::Stack {
+                    self.reg_uses.push(RegUse {
+                        value,
+                        pos,
+                        rc: op.regclass,
+                    });
+                }
+            }
+        }
+
+        // ABI arguments and return values passed in registers.
+        let (abi_args, abi_results) = match func.dfg.call_signature(inst) {
+            Some(sig) => {
+                let sig = &func.dfg.signatures[sig];
+                (&sig.params[..], &sig.returns[..])
+            }
+            None if func.dfg[inst].opcode().is_return() => (&func.signature.returns[..], &[][..]),
+            None => return,
+        };
+        let abi_values = func
+            .dfg
+            .inst_variable_args(inst)
+            .iter()
+            .zip(abi_args)
+            .chain(func.dfg.inst_results(inst).iter().zip(abi_results));
+        for (&value, abi) in abi_values {
+            if let ArgumentLoc::Reg(_) = abi.location {
+                self.reg_uses.push(RegUse {
+                    value,
+                    pos,
+                    rc: self.cur.isa.regclass_for_abi_type(abi.value_type),
+                });
+            }
+        }
+    }
+
+    /// Insert copies before `inst` for tied operands that don't kill their value, and for
+    /// repeated uses of a value by fixed register operands.
+    fn resolve_constraints(&mut self, inst: Inst, ebb: Ebb, pos: u32) {
+        debug_assert!(self.constrained.is_empty());
+        let func = &self.cur.func;
+        let args = func.dfg.inst_args(inst);
+        let ctx = self.liveness.context(&func.layout);
+
+        if let Some(constraints) = self.encinfo.operand_constraints(func.encodings[inst]) {
+            for (idx, (op, &value)) in constraints.ins.iter().zip(args).enumerate() {
+                let (fixed, tied) = match op.kind {
+                    ConstraintKind::FixedReg(_) => (true, false),
+                    ConstraintKind::Tied(_) => (false, true),
+                    ConstraintKind::FixedTied(_) => (true, true),
+                    ConstraintKind::Reg | ConstraintKind::Stack => continue,
+                };
+                // A tied operand must kill the used value.
+                let tied = tied && !self.liveness[value].killed_at(inst, ebb, ctx);
+                if fixed || tied {
+                    self.constrained.push(ConstrainedUse {
+                        value,
+                        opidx: idx,
+                        rci: op.regclass.into(),
+                        tied,
+                    });
+                }
+            }
+        }
+
+        // Return values and call arguments in registers are fixed operands too.
+        let abi_params = match func.dfg.call_signature(inst) {
+            Some(sig) => Some(&func.dfg.signatures[sig].params[..]),
+            None if func.dfg[inst].opcode().is_return() => Some(&func.signature.returns[..]),
+            None => None,
+        };
+        if let Some(abi_params) = abi_params {
+            let first = func.dfg[inst]
+                .opcode()
+                .constraints()
+                .num_fixed_value_arguments();
+            for (idx, (abi, &value)) in abi_params.iter().zip(&args[first..]).enumerate() {
+                if abi.location.is_reg() {
+                    self.constrained.push(ConstrainedUse {
+                        value,
+                        opidx: first + idx,
+                        rci: self.cur.isa.regclass_for_abi_type(abi.value_type).into(),
+                        tied: false,
+                    });
+                }
+            }
+        }
+
+        if self.constrained.is_empty() {
+            return;
+        }
+
+        // Look for repeated uses of the same value, like the spilling pass does.
+        self.constrained
+            .sort_unstable_by_key(|u| (u.value, u.opidx));
+        self.cur.use_srcloc(inst);
+        for i in 0..self.constrained.len() {
+            let cu = self.constrained[i];
+            let repeated = i > 0 && self.constrained[i - 1].value == cu.value;
+            if cu.tied || repeated {
+                let copy = self.insert_copy(cu.value, cu.rci, pos);
+                self.cur.func.dfg.inst_args_mut(inst)[cu.opidx] = copy;
+            }
+        }
+        self.constrained.clear();
+    }
+
+    /// Insert a `copy value` before the current instruction and give it a live range extending to
+    /// the current instruction.
+    fn insert_copy(&mut self, value: Value, rci: RegClassIndex, pos: u32) -> Value {
+        let copy = self.cur.ins().copy(value);
+        let inst = self.cur.built_inst();
+        self.cur.func.dfg.copy_value_labels(value, copy);
+        self.inst_pos[inst] = pos;
+        self.collect_reg_uses(inst, pos);
+
+        self.liveness.create_dead(copy, inst, Affinity::Reg(rci));
+        self.liveness.extend_locally(
+            copy,
+            self.cur.func.layout.pp_ebb(inst),
+            self.cur.current_inst().expect("must be at an instruction"),
+            &self.cur.func.layout,
+        );
+
+        copy
+    }
+
+    /// Get the position of `pp` in the linear order.
+    fn position(&self, pp: ProgramPoint) -> u32 {
+        match ExpandedProgramPoint::from(pp) {
+            ExpandedProgramPoint::Ebb(ebb) => self.ebb_pos[ebb],
+            ExpandedProgramPoint::Inst(inst) => self.inst_pos[inst],
+        }
+    }
+
+    /// Compute a live interval for every virtual register with a register affinity.
+    fn build_intervals(&mut self) {
+        let ctx = self.liveness.context(&self.cur.func.layout);
+        let mut intervals = Vec::new();
+        for lr in self.liveness.ranges().values() {
+            let rc = match lr.affinity {
+                Affinity::Reg(rci) => self.reginfo.rc(rci),
+                _ => continue,
+            };
+            let mut start = self.position(lr.def());
+            let mut end = self.position(lr.def_local_end());
+            for (ebb, inst) in lr.liveins(ctx) {
+                start = min(start, self.ebb_pos[ebb]);
+                end = max(end, self.inst_pos[inst]);
+            }
+            intervals.push(Interval {
+                value: self.virtregs.congruence_class(&lr.key())[0],
+                start,
+                end,
+                rc,
+            });
+        }
+
+        // The values in a virtual register don't interfere, so they can share an interval.
+        intervals.sort_unstable_by_key(|iv| iv.value);
+        intervals.dedup_by(|iv, prev| {
+            if iv.value == prev.value {
+                prev.start = min(prev.start, iv.start);
+                prev.end = max(prev.end, iv.end);
+                true
+            } else {
+                false
+            }
+        });
+        intervals.sort_unstable_by_key(|iv| (iv.start, iv.value));
+        *self.intervals = intervals;
+
+        // Values that are already on the stack need a temporary register at their register uses.
+        for u in self.reg_uses.iter() {
+            if self
+                .liveness
+                .get(u.value)
+                .map_or(false, |lr| lr.affinity.is_stack())
+            {
+                self.temps.push(Reverse((u.pos, u.rc.index)));
+            }
+        }
+    }
+
+    /// Scan the intervals and temporaries in order, spilling intervals when the register pressure
+    /// gets too high.
+    fn scan(&mut self) {
+        let mut next = 0;
+        loop {
+            let interval_start = self.intervals.get(next).map(|iv| iv.start);
+            let temp_pos = self.temps.peek().map(|&Reverse((pos, _))| pos);
+            match (temp_pos, interval_start) {
+                // Temporaries come first since they are used by the instruction at their position.
+                (Some(tpos), Some(start)) if tpos <= start => self.activate_temp(),
+                (Some(_), None) => self.activate_temp(),
+                (_, Some(start)) => {
+                    self.expire(start);
+                    self.activate_interval(next);
+                    next += 1;
+                }
+                (None, None) => break,
+            }
+        }
+    }
+
+    /// Free the registers of the active intervals that end before `pos`.
+    fn expire(&mut self, pos: u32) {
+        let pressure = &mut self.pressure;
+        self.active.retain(|a| {
+            if a.end < pos {
+                pressure.free(a.rc);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Allocate the next pending temporary register.
+    fn activate_temp(&mut self) {
+        let Reverse((pos, rc_index)) = self.temps.pop().expect("no pending temporary");
+        let rc = self.reginfo.classes[rc_index as usize];
+        self.expire(pos);
+        while let Err(mask) = self.take(rc) {
+            match self.spill_candidate(mask, pos) {
+                Some(a) => self.spill_active(a, pos),
+                None => panic!("Ran out of {} registers at position {}", rc, pos),
+            }
+        }
+        self.active.push(Active {
+            end: pos,
+            rc,
+            interval: None,
+        });
+    }
+
+    /// Allocate a register for the interval at index `idx`, or spill it.
+    fn activate_interval(&mut self, idx: usize) {
+        let Interval {
+            value,
+            start,
+            end,
+            rc,
+        } = self.intervals[idx];
+
+        // Values live across calls are always spilled.
+        if self.crosses_call(start, end) {
+            debug!("Spilling {} which is live across a call", value);
+            self.spill_interval(idx, start);
+            return;
+        }
+
+        while let Err(mask) = self.take(rc) {
+            let cand = self.spill_candidate(mask, start);
+            let cand_end = cand.map(|a| self.active[a].end);
+            if end > start && cand_end.map_or(true, |e| e <= end) {
+                debug!("Spilling {} which ends last at {}", value, end);
+                self.spill_interval(idx, start);
+                return;
+            }
+            match cand {
+                Some(a) => self.spill_active(a, start),
+                None => panic!("Ran out of {} registers for {}", rc, value),
+            }
+        }
+        self.active.push(Active {
+            end,
+            rc,
+            interval: Some(idx),
+        });
+    }
+
+    /// Take a register from `rc` if one is available.
+    fn take(&mut self, rc: RegClass) -> Result<(), RegClassMask> {
+        self.pressure.take_transient(rc)?;
+        self.pressure.preserve_transient();
+        Ok(())
+    }
+
+    /// Does the interval `start..end` cross a call instruction?
+    fn crosses_call(&self, start: u32, end: u32) -> bool {
+        let idx = match self.calls.binary_search(&start) {
+            Ok(idx) => idx + 1,
+            Err(idx) => idx,
+        };
+        self.calls.get(idx).map_or(false, |&call| call < end)
+    }
+
+    /// Find the active interval in one of the `mask` classes that ends last after `pos`, and
+    /// return its index in `self.active`.
+    ///
+    /// Intervals ending at `pos` are not considered since spilling them wouldn't free a register
+    /// at `pos`.
+    fn spill_candidate(&self, mask: RegClassMask, pos: u32) -> Option<usize> {
+        self.active
+            .iter()
+            .enumerate()
+            .filter(|&(_, a)| {
+                a.interval.is_some() && a.end > pos && (mask & (1 << a.rc.toprc)) != 0
+            })
+            .max_by_key(|&(_, a)| a.end)
+            .map(|(idx, _)| idx)
+    }
+
+    /// Spill the active interval at index `a` in `self.active`.
+    fn spill_active(&mut self, a: usize, pos: u32) {
+        let active = self.active.swap_remove(a);
+        self.pressure.free(active.rc);
+        let idx = active.interval.expect("temporaries can't be spilled");
+        debug!(
+            "Spilling {} to make room at {}",
+            self.intervals[idx].value, pos
+        );
+        self.spill_interval(idx, pos);
+    }
+
+    /// Spill the virtual register of the interval at index `idx`, and add temporaries for its
+    /// register uses from `pos` onwards.
+    fn spill_interval(&mut self, idx: usize, pos: u32) {
+        let value = self.intervals[idx].value;
+        let ss = self
+            .cur
+            .func
+            .stack_slots
+            .make_spill_slot(self.cur.func.dfg.value_type(value));
+        for &v in self.virtregs.congruence_class(&value) {
+            self.liveness.spill(v);
+            self.cur.func.locations[v] = ValueLoc::Stack(ss);
+
+            // Find the first use of `v` at or after `pos`.
+            let first = self
+                .reg_uses
+                .binary_search_by(|u| (u.value, u.pos).cmp(&(v, pos)).then(Ordering::Greater))
+                .unwrap_or_else(|idx| idx);
+            for u in self.reg_uses[first..].iter().take_while(|u| u.value == v) {
+                self.temps.push(Reverse((u.pos, u.rc.index)));
+            }
+        }
+    }
+}
+
+/// A register operand or register def of an instruction.
+#[derive(Clone, Copy)]
+struct RegUse {
+    value: Value,
+    pos: u32,
+    rc: RegClass,
+}
+
+/// A live interval covering all the values in a virtual register.
+#[derive(Clone, Copy)]
+struct Interval {
+    /// The first value in the virtual register.
+    value: Value,
+    start: u32,
+    end: u32,
+    rc: RegClass,
+}
+
+/// An interval or temporary that currently holds a register.
+struct Active {
+    end: u32,
+    rc: RegClass,
+    /// Index into the intervals, or `None` for a temporary.
+    interval: Option<usize>,
+}
+
+/// An operand with a fixed or tied register constraint.
+#[derive(Clone, Copy)]
+struct ConstrainedUse {
+    value: Value,
+    opidx: usize,
+    rci: RegClassIndex,
+    // A tied operand whose value isn't killed by the instruction.
+    tied: bool,
+}
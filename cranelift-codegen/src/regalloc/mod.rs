@@ -13,10 +13,13 @@ mod affinity;
 mod coalescing;
 mod context;
 mod diversion;
+mod linear_scan;
 mod pressure;
 mod reload;
 mod solver;
+mod spill_slots;
 mod spilling;
+mod ssa_destruction;
 
 pub use self::context::Context;
 pub use self::diversion::RegDiversions;
@@ -350,6 +350,7 @@ impl<'a> Context<'a> {
 
             let reg = self.cur.ins().fill(cand.value);
             let fill = self.cur.built_inst();
+            self.cur.func.dfg.copy_value_labels(cand.value, reg);
 
             self.reloads.insert(ReloadedValue {
                 stack: cand.value,
@@ -451,6 +452,7 @@ impl<'a> Context<'a> {
     fn insert_spill(&mut self, ebb: Ebb, stack: Value, reg: Value) {
         self.cur.ins().with_result(stack).spill(reg);
         let inst = self.cur.built_inst();
+        self.cur.func.dfg.copy_value_labels(stack, reg);
 
         // Update live ranges.
         self.liveness.move_def_locally(stack, inst);
@@ -149,6 +149,9 @@ pub struct Variable {
 
     /// Any solution must belong to the constraint register class.
     constraint: RegClass,
+
+    /// Preferred register for this variable, used when it is available.
+    hint: Option<RegUnit>,
 }
 
 impl Variable {
@@ -162,6 +165,7 @@ impl Variable {
             is_global: false,
             domain: 0,
             solution: !0,
+            hint: None,
         }
     }
 
@@ -175,6 +179,7 @@ impl Variable {
             is_global,
             domain: 0,
             solution: !0,
+            hint: None,
         }
     }
 
@@ -810,10 +815,19 @@ impl Solver {
     /// Add a defined output value.
     ///
     /// This is similar to `add_var`, except the value doesn't have a prior register assignment.
-    pub fn add_def(&mut self, value: Value, constraint: RegClass, is_global: bool) {
+    ///
+    /// The solver assigns the `hint` register to the value if it is available.
+    pub fn add_def(
+        &mut self,
+        value: Value,
+        constraint: RegClass,
+        is_global: bool,
+        hint: Option<RegUnit>,
+    ) {
         debug_assert!(self.inputs_done);
-        self.vars
-            .push(Variable::new_def(value, constraint, is_global));
+        let mut v = Variable::new_def(value, constraint, is_global);
+        v.hint = hint;
+        self.vars.push(v);
     }
 
     /// Clear the `is_global` flag on all solver variables.
@@ -917,7 +931,10 @@ impl Solver {
 
         for v in &mut self.vars {
             let rc = v.constraint;
-            let reg = match v.iter(&iregs, &oregs, &gregs).next() {
+            let hint = v
+                .hint
+                .filter(|&h| v.iter(&iregs, &oregs, &gregs).any(|reg| reg == h));
+            let reg = match hint.or_else(|| v.iter(&iregs, &oregs, &gregs).next()) {
                 Some(reg) => reg,
                 None => {
                     // If `v` must avoid global interference, there is not point in requesting
@@ -1376,4 +1393,36 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn hinted_defs() {
+        let isa = arm32().expect("This test requires arm32 support");
+        let reginfo = isa.register_info();
+        let gpr = rc_by_name(&reginfo, "GPR");
+        let r0 = gpr.unit(0);
+        let r1 = gpr.unit(1);
+        let r2 = gpr.unit(2);
+        let gregs = RegisterSet::new();
+        let mut regs = RegisterSet::new();
+        let mut solver = Solver::new();
+        let v10 = Value::new(10);
+        let v11 = Value::new(11);
+
+        // The hint is used when it is available.
+        regs.take(gpr, r0);
+        solver.reset(&regs);
+        solver.inputs_done();
+        solver.add_def(v10, gpr, false, Some(r2));
+        assert!(solver.quick_solve(&gregs).is_ok());
+        assert_eq!(solver.vars()[0].solution, r2);
+
+        // An unavailable hint is ignored, and so is a hint taken by an earlier define.
+        solver.reset(&regs);
+        solver.inputs_done();
+        solver.add_def(v10, gpr, false, Some(r0));
+        solver.add_def(v11, gpr, false, Some(r1));
+        assert!(solver.quick_solve(&gregs).is_ok());
+        assert_eq!(solver.vars()[0].solution, r1);
+        assert_eq!(solver.vars()[1].solution, r2);
+    }
 }
@@ -0,0 +1,221 @@
+//! Spill slot coalescing.
+//!
+//! The spilling pass allocates a new spill slot for every virtual register it spills, so functions
+//! with a lot of spilling end up with large stack frames. This pass runs after the registers have
+//! been assigned, and it merges spill slots holding values whose live ranges don't overlap before
+//! the stack frame is laid out.
+//!
+//! The live ranges are mapped onto a numbering of the EBB headers and instructions in layout
+//! order. A live range consists of segments that are each contained in a single EBB, so two live
+//! ranges overlap exactly when one of their segments overlap. Segments that meet at an
+//! instruction are considered overlapping since the instruction may read one value and write the
+//! other.
+//!
+//! The slots that are merged into another slot are kept with a size of 0, so they don't take up
+//! any space in the stack frame.
+
+use crate::entity::{EntityRef, SecondaryMap, SparseMapValue};
+use crate::ir::{
+    Ebb, ExpandedProgramPoint, Function, Inst, ProgramPoint, StackSlot, StackSlotKind, ValueLoc,
+};
+use crate::packed_option::PackedOption;
+use crate::regalloc::liveness::Liveness;
+use crate::timing;
+use log::debug;
+use std::vec::Vec;
+
+/// Persistent data structures for the spill slot coalescing pass.
+pub struct SlotCoalescing {
+    ebb_pos: SecondaryMap<Ebb, u32>,
+    inst_pos: SecondaryMap<Inst, u32>,
+
+    // Live range segments of all the values in spill slots, sorted by slot and start.
+    segments: Vec<Segment>,
+
+    // Slots that other slots have been merged into, and the segments of all their values.
+    groups: Vec<Group>,
+
+    // The slot that each merged slot was merged into.
+    merged: SecondaryMap<StackSlot, PackedOption<StackSlot>>,
+}
+
+impl SlotCoalescing {
+    /// Create a new spill slot coalescing pass.
+    pub fn new() -> Self {
+        Self {
+            ebb_pos: SecondaryMap::new(),
+            inst_pos: SecondaryMap::new(),
+            segments: Vec::new(),
+            groups: Vec::new(),
+            merged: SecondaryMap::new(),
+        }
+    }
+
+    /// Clear all data structures in this pass.
+    pub fn clear(&mut self) {
+        self.ebb_pos.clear();
+        self.inst_pos.clear();
+        self.segments.clear();
+        self.groups.clear();
+        self.merged.clear();
+    }
+
+    /// Merge the spill slots in `func` whose values don't interfere, and assign the new slots to
+    /// the values.
+    pub fn run(&mut self, func: &mut Function, liveness: &Liveness) {
+        let _tt = timing::ra_spill_slots();
+        debug!("Coalescing spill slots for {}", func.name);
+        self.clear();
+        self.number_program_points(func);
+        self.collect_segments(func, liveness);
+        self.merge_slots(func);
+        self.rename_slots(func);
+    }
+
+    /// Number the EBB headers and instructions in layout order.
+    fn number_program_points(&mut self, func: &Function) {
+        let mut pos = 0;
+        for ebb in func.layout.ebbs() {
+            self.ebb_pos[ebb] = pos;
+            pos += 1;
+            for inst in func.layout.ebb_insts(ebb) {
+                self.inst_pos[inst] = pos;
+                pos += 1;
+            }
+        }
+    }
+
+    /// Get the position of `pp` in the layout order.
+    fn position(&self, pp: ProgramPoint) -> u32 {
+        match ExpandedProgramPoint::from(pp) {
+            ExpandedProgramPoint::Ebb(ebb) => self.ebb_pos[ebb],
+            ExpandedProgramPoint::Inst(inst) => self.inst_pos[inst],
+        }
+    }
+
+    /// Collect the live range segments of all the values assigned to spill slots.
+    fn collect_segments(&mut self, func: &Function, liveness: &Liveness) {
+        let ctx = liveness.context(&func.layout);
+        for lr in liveness.ranges().values() {
+            let slot = match func.locations[lr.key()] {
+                ValueLoc::Stack(ss) if func.stack_slots[ss].kind == StackSlotKind::SpillSlot => ss,
+                _ => continue,
+            };
+            self.segments.push(Segment {
+                slot,
+                start: self.position(lr.def()),
+                end: self.position(lr.def_local_end()),
+            });
+            for (ebb, inst) in lr.liveins(ctx) {
+                self.segments.push(Segment {
+                    slot,
+                    start: self.ebb_pos[ebb],
+                    end: self.inst_pos[inst],
+                });
+            }
+        }
+        self.segments
+            .sort_unstable_by_key(|s| (s.slot.index(), s.start));
+    }
+
+    /// Merge each slot into the first slot of the same size that doesn't interfere with it.
+    fn merge_slots(&mut self, func: &mut Function) {
+        let mut rest = &self.segments[..];
+        while let Some(first) = rest.first() {
+            let slot = first.slot;
+            let len = rest.iter().take_while(|s| s.slot == slot).count();
+            let (segments, tail) = rest.split_at(len);
+            rest = tail;
+
+            let size = func.stack_slots[slot].size;
+            let stack_slots = &func.stack_slots;
+            let fits = |g: &&mut Group| {
+                stack_slots[g.slot].size == size && !overlaps(&g.segments, segments)
+            };
+            match self.groups.iter_mut().find(fits) {
+                Some(group) => {
+                    debug!("Merging {} into {}", slot, group.slot);
+                    group.segments.extend_from_slice(segments);
+                    group.segments.sort_unstable_by_key(|s| s.start);
+                    self.merged[slot] = group.slot.into();
+                    func.stack_slots[slot].size = 0;
+                }
+                None => self.groups.push(Group {
+                    slot,
+                    segments: segments.to_vec(),
+                }),
+            }
+        }
+    }
+
+    /// Update the locations of the values in merged slots.
+    fn rename_slots(&self, func: &mut Function) {
+        for loc in func.locations.values_mut() {
+            if let ValueLoc::Stack(ss) = *loc {
+                if let Some(slot) = self.merged[ss].expand() {
+                    *loc = ValueLoc::Stack(slot);
+                }
+            }
+        }
+    }
+}
+
+/// A part of a live range contained in a single EBB.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    slot: StackSlot,
+    start: u32,
+    end: u32,
+}
+
+/// A spill slot and the live range segments of all the values assigned to it after merging.
+struct Group {
+    slot: StackSlot,
+    // Sorted by start.
+    segments: Vec<Segment>,
+}
+
+/// Does any segment in `a` overlap any segment in `b`? Both lists must be sorted by start.
+fn overlaps(a: &[Segment], b: &[Segment]) -> bool {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i].end < b[j].start {
+            i += 1;
+        } else if b[j].end < a[i].start {
+            j += 1;
+        } else {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{overlaps, Segment};
+    use crate::entity::EntityRef;
+    use crate::ir::StackSlot;
+    use std::vec::Vec;
+
+    fn segs(ranges: &[(u32, u32)]) -> Vec<Segment> {
+        ranges
+            .iter()
+            .map(|&(start, end)| Segment {
+                slot: StackSlot::new(0),
+                start,
+                end,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_segments() {
+        let a = segs(&[(0, 3), (1, 9), (20, 25)]);
+        assert!(!overlaps(&a, &segs(&[])));
+        assert!(!overlaps(&a, &segs(&[(10, 19), (26, 30)])));
+        assert!(overlaps(&a, &segs(&[(10, 19), (25, 30)])));
+        assert!(overlaps(&a, &segs(&[(5, 6)])));
+        assert!(overlaps(&segs(&[(5, 6)]), &a));
+        assert!(overlaps(&a, &segs(&[(9, 12)])));
+    }
+}
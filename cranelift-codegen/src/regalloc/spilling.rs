@@ -17,6 +17,8 @@
 
 use crate::cursor::{Cursor, EncCursor};
 use crate::dominator_tree::DominatorTree;
+use crate::ebb_frequency::EbbFrequencies;
+use crate::entity::SecondaryMap;
 use crate::ir::{ArgumentLoc, Ebb, Function, Inst, InstBuilder, SigRef, Value, ValueLoc};
 use crate::isa::registers::{RegClass, RegClassIndex, RegClassMask, RegUnit};
 use crate::isa::{ConstraintKind, EncInfo, RecipeConstraints, RegInfo, TargetIsa};
@@ -44,6 +46,7 @@ fn toprc_containing_regunit(unit: RegUnit, reginfo: &RegInfo) -> RegClass {
 pub struct Spilling {
     spills: Vec<Value>,
     reg_uses: Vec<RegUse>,
+    heat: SecondaryMap<Value, u64>,
 }
 
 /// Context data structure that gets instantiated once per pass.
@@ -71,6 +74,9 @@ struct Context<'a> {
 
     // Uses of register values in the current instruction.
     reg_uses: &'a mut Vec<RegUse>,
+
+    // The highest frequency of the EBBs where each value is defined or used.
+    heat: &'a mut SecondaryMap<Value, u64>,
 }
 
 impl Spilling {
@@ -79,6 +85,7 @@ impl Spilling {
         Self {
             spills: Vec::new(),
             reg_uses: Vec::new(),
+            heat: SecondaryMap::new(),
         }
     }
 
@@ -86,6 +93,7 @@ impl Spilling {
     pub fn clear(&mut self) {
         self.spills.clear();
         self.reg_uses.clear();
+        self.heat.clear();
     }
 
     /// Run the spilling algorithm over `func`.
@@ -94,6 +102,7 @@ impl Spilling {
         isa: &dyn TargetIsa,
         func: &mut Function,
         domtree: &DominatorTree,
+        freqs: &EbbFrequencies,
         liveness: &mut Liveness,
         virtregs: &VirtRegs,
         topo: &mut TopoOrder,
@@ -101,6 +110,7 @@ impl Spilling {
     ) {
         let _tt = timing::ra_spilling();
         debug!("Spilling for:\n{}", func.display(isa));
+        compute_heat(func, freqs, &mut self.heat);
         let reginfo = isa.register_info();
         let usable_regs = isa.allocatable_registers(func);
         let mut ctx = Context {
@@ -114,11 +124,36 @@ impl Spilling {
             pressure: Pressure::new(&reginfo, &usable_regs),
             spills: &mut self.spills,
             reg_uses: &mut self.reg_uses,
+            heat: &mut self.heat,
         };
         ctx.run(tracker)
     }
 }
 
+/// Compute the highest frequency of the EBBs where each value in `func` is defined or used.
+///
+/// Spilling a value costs a spill after its def and a fill before its uses, so a value with a low
+/// heat is cheap to spill.
+fn compute_heat(func: &Function, freqs: &EbbFrequencies, heat: &mut SecondaryMap<Value, u64>) {
+    heat.clear();
+    for ebb in func.layout.ebbs() {
+        let freq = freqs.frequency(ebb);
+        for &value in func.dfg.ebb_params(ebb) {
+            heat[value] = heat[value].max(freq);
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            for &value in func
+                .dfg
+                .inst_args(inst)
+                .iter()
+                .chain(func.dfg.inst_results(inst))
+            {
+                heat[value] = heat[value].max(freq);
+            }
+        }
+    }
+}
+
 impl<'a> Context<'a> {
     fn run(&mut self, tracker: &mut LiveValueTracker) {
         self.topo.reset(self.cur.func.layout.ebbs());
@@ -267,7 +302,7 @@ impl<'a> Context<'a> {
         // If inst is a call, spill all register values that are live across the call.
         // This means that we don't currently take advantage of callee-saved registers.
         // TODO: Be more sophisticated.
-        if call_sig.is_some() {
+        if call_sig.is_some() || self.cur.func.dfg[inst].opcode().is_implicit_call() {
             for lv in throughs {
                 if lv.affinity.is_reg() && !self.spills.contains(&lv.value) {
                     self.spill_reg(lv.value);
@@ -487,9 +522,10 @@ impl<'a> Context<'a> {
     {
         // Find the best viable spill candidate.
         //
-        // The very simple strategy implemented here is to spill the value with the earliest def in
-        // the reverse post-order. This strategy depends on a good reload pass to generate good
-        // code.
+        // Values that are only defined and used in cold EBBs are preferred, so spill and fill
+        // instructions stay out of hot loops. Among candidates with the same heat, the very simple
+        // strategy implemented here is to spill the value with the earliest def in the reverse
+        // post-order. This strategy depends on a good reload pass to generate good code.
         //
         // We know that all candidate defs dominate the current instruction, so one of them will
         // dominate the others. That is the earliest def.
@@ -508,12 +544,14 @@ impl<'a> Context<'a> {
                 None
             })
             .min_by(|&a, &b| {
-                // Find the minimum candidate according to the RPO of their defs.
-                self.domtree.rpo_cmp(
-                    self.cur.func.dfg.value_def(a),
-                    self.cur.func.dfg.value_def(b),
-                    &self.cur.func.layout,
-                )
+                // Find the minimum candidate according to their heat, then the RPO of their defs.
+                self.heat[a].cmp(&self.heat[b]).then_with(|| {
+                    self.domtree.rpo_cmp(
+                        self.cur.func.dfg.value_def(a),
+                        self.cur.func.dfg.value_def(b),
+                        &self.cur.func.layout,
+                    )
+                })
             })
     }
 
@@ -568,6 +606,8 @@ impl<'a> Context<'a> {
     fn insert_copy(&mut self, value: Value, rci: RegClassIndex) -> Value {
         let copy = self.cur.ins().copy(value);
         let inst = self.cur.built_inst();
+        self.cur.func.dfg.copy_value_labels(value, copy);
+        self.heat[copy] = self.heat[value];
 
         // Update live ranges.
         self.liveness.create_dead(copy, inst, Affinity::Reg(rci));
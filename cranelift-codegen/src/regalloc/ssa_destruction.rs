@@ -0,0 +1,263 @@
+//! SSA destruction for the `ssa` register allocation algorithm.
+//!
+//! The default register allocator converts the function to Conventional SSA form before spilling
+//! and coloring. EBB parameters are joined with their branch arguments into virtual registers
+//! that share a single register or spill slot, so EBB arguments never have to be moved.
+//!
+//! The `ssa` algorithm colors the function on plain SSA form instead, where every value gets its
+//! own register. This leaves the coloring pass free to pick the best register for each value,
+//! which pays off in straight-line code where there are few EBB arguments to reconcile. The EBB
+//! arguments on each CFG edge then form a parallel copy from the argument locations to the
+//! parameter locations. The parallel copies are sequentialized after the fact:
+//!
+//! - Register arguments are moved into the parameter registers by the coloring pass, which already
+//!   schedules `regmove` instructions for branch arguments that aren't in the right register.
+//! - Stack arguments and parameters are reconciled by this module after spilling, by inserting
+//!   `fill` and `spill` instructions before the branch.
+//!
+//! This requires every branch argument to be a local value that is killed by its branch, and all
+//! branches with EBB arguments to be terminators. Two passes run before the liveness analysis to
+//! establish these properties:
+//!
+//! 1. `split_edges` moves the EBB arguments of conditional branches into new EBBs that contain a
+//!    single `jump` instruction.
+//! 2. `isolate_args` replaces every branch argument with a copy inserted right before the branch.
+//!    When an argument is itself a parameter of the destination EBB, it is copied first so the
+//!    parameter is dead by the time the other copies are defined.
+
+use crate::cursor::{Cursor, EncCursor};
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{Ebb, Function, Inst, InstBuilder, Value, ValueDef, ValueLoc};
+use crate::isa::TargetIsa;
+use crate::regalloc::affinity::Affinity;
+use crate::regalloc::liveness::Liveness;
+use crate::simplify_cfg::retarget_branch;
+use crate::timing;
+use log::debug;
+use std::vec::Vec;
+
+/// Persistent data structures for SSA destruction.
+pub struct SsaDestruction {
+    // Conditional branches with EBB arguments.
+    branches: Vec<Inst>,
+
+    // EBB arguments of the current branch.
+    args: Vec<Value>,
+
+    // EBB parameters of the current branch destination.
+    params: Vec<Value>,
+
+    // Stack arguments that must be copied to the parameter's spill slot before the branch.
+    transfers: Vec<usize>,
+
+    // Stack arguments that must be filled into a register before the branch.
+    fills: Vec<usize>,
+}
+
+impl SsaDestruction {
+    /// Create a new SSA destruction pass.
+    pub fn new() -> Self {
+        Self {
+            branches: Vec::new(),
+            args: Vec::new(),
+            params: Vec::new(),
+            transfers: Vec::new(),
+            fills: Vec::new(),
+        }
+    }
+
+    /// Clear all data structures in this pass.
+    pub fn clear(&mut self) {
+        self.branches.clear();
+        self.args.clear();
+        self.params.clear();
+        self.transfers.clear();
+        self.fills.clear();
+    }
+
+    /// Split the CFG edges of conditional branches that pass EBB arguments.
+    ///
+    /// The branch is redirected to a new EBB at the end of the layout, which jumps to the original
+    /// destination with the original arguments. Returns true if any edges were split, so the
+    /// control flow graph and dominator tree need to be recomputed.
+    pub fn split_edges(&mut self, isa: &dyn TargetIsa, func: &mut Function) -> bool {
+        let _tt = timing::ra_ssa_destruction();
+        self.branches.clear();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if !func.dfg[inst].opcode().is_terminator()
+                    && branch_with_args(func, inst).is_some()
+                {
+                    self.branches.push(inst);
+                }
+            }
+        }
+
+        for &inst in &self.branches {
+            let (dest, args) = match func.dfg.analyze_branch(inst) {
+                BranchInfo::SingleDest(dest, args) => (dest, args.to_vec()),
+                _ => unreachable!(),
+            };
+            let edge = func.dfg.make_ebb();
+            func.layout.append_ebb(edge);
+            let mut pos = EncCursor::new(func, isa).at_bottom(edge);
+            pos.use_srcloc(inst);
+            pos.ins().jump(dest, &args);
+            retarget_branch(func, inst, edge, &[]);
+            debug!("Split edge to {} through {}", dest, edge);
+        }
+
+        !self.branches.is_empty()
+    }
+
+    /// Replace all EBB arguments on branches with copies inserted right before the branch.
+    ///
+    /// This must run after `split_edges`, so all branches with EBB arguments are terminators.
+    pub fn isolate_args(&mut self, isa: &dyn TargetIsa, func: &mut Function) {
+        let _tt = timing::ra_ssa_destruction();
+        let mut pos = EncCursor::new(func, isa);
+        while let Some(ebb) = pos.next_ebb() {
+            let inst = match pos.func.layout.last_inst(ebb) {
+                Some(inst) => inst,
+                None => continue,
+            };
+            let dest = match branch_with_args(pos.func, inst) {
+                Some(dest) => dest,
+                None => continue,
+            };
+
+            self.args.clear();
+            self.args
+                .extend_from_slice(pos.func.dfg.inst_variable_args(inst));
+            pos.goto_inst(inst);
+            pos.use_srcloc(inst);
+
+            // Copy the parameters of `dest` first, then all other arguments.
+            for &copy_params in &[true, false] {
+                for (argnum, &arg) in self.args.iter().enumerate() {
+                    let is_param = match pos.func.dfg.value_def(arg) {
+                        ValueDef::Param(param_ebb, _) => param_ebb == dest,
+                        ValueDef::Result(..) => false,
+                    };
+                    if is_param != copy_params {
+                        continue;
+                    }
+                    let copy = pos.ins().copy(arg);
+                    pos.func.dfg.copy_value_labels(arg, copy);
+                    pos.func.dfg.inst_variable_args_mut(inst)[argnum] = copy;
+                }
+            }
+        }
+    }
+
+    /// Make the stack locations of EBB arguments agree with the EBB parameters.
+    ///
+    /// This runs after spilling, when it is known which EBB parameters and arguments live on the
+    /// stack:
+    ///
+    /// - A register argument passed to a spilled parameter is spilled, preferably directly into
+    ///   the parameter's spill slot.
+    /// - A spilled argument passed to a spilled parameter is also given the parameter's spill
+    ///   slot, unless the parameter is still live where the argument is defined. Then it is copied
+    ///   to the parameter's slot through a register right before the branch.
+    /// - A spilled argument passed to a register parameter is filled right before the branch.
+    ///
+    /// The fills are inserted after all the argument copies, where the only other live values are
+    /// the values that are live-in to the destination. The register pressure there doesn't exceed
+    /// the pressure at the destination EBB header.
+    pub fn place_args(
+        &mut self,
+        isa: &dyn TargetIsa,
+        func: &mut Function,
+        liveness: &mut Liveness,
+    ) {
+        let _tt = timing::ra_ssa_destruction();
+        let encinfo = isa.encoding_info();
+        let mut pos = EncCursor::new(func, isa);
+        while let Some(ebb) = pos.next_ebb() {
+            let inst = match pos.func.layout.last_inst(ebb) {
+                Some(inst) => inst,
+                None => continue,
+            };
+            let dest = match branch_with_args(pos.func, inst) {
+                Some(dest) => dest,
+                None => continue,
+            };
+
+            self.args.clear();
+            self.args
+                .extend_from_slice(pos.func.dfg.inst_variable_args(inst));
+            self.params.clear();
+            self.params.extend_from_slice(pos.func.dfg.ebb_params(dest));
+            self.transfers.clear();
+            self.fills.clear();
+
+            for (argnum, (&arg, &param)) in self.args.iter().zip(&self.params).enumerate() {
+                let param_affinity = liveness[param].affinity;
+                if param_affinity.is_stack() {
+                    if liveness[arg].affinity.is_reg() {
+                        liveness.spill(arg);
+                    }
+                    let ctx = liveness.context(&pos.func.layout);
+                    let def = pos.func.dfg.value_def(arg).pp();
+                    if liveness[param].overlaps_def(def, ebb, ctx) {
+                        // The parameter is still needed when `arg` is defined, so `arg` can't be
+                        // defined in the parameter's slot.
+                        let ss = pos
+                            .func
+                            .stack_slots
+                            .make_spill_slot(pos.func.dfg.value_type(arg));
+                        pos.func.locations[arg] = ValueLoc::Stack(ss);
+                        self.transfers.push(argnum);
+                    } else {
+                        pos.func.locations[arg] = pos.func.locations[param];
+                    }
+                } else if param_affinity.is_reg() && liveness[arg].affinity.is_stack() {
+                    self.fills.push(argnum);
+                }
+            }
+
+            pos.goto_inst(inst);
+            pos.use_srcloc(inst);
+            for &argnum in &self.transfers {
+                let arg = self.args[argnum];
+                let param = self.params[argnum];
+                let reg = pos.ins().fill(arg);
+                let fill = pos.built_inst();
+                let affinity = Affinity::new(
+                    &encinfo
+                        .operand_constraints(pos.func.encodings[fill])
+                        .expect("Bad fill encoding")
+                        .outs[0],
+                );
+                let stack = pos.ins().spill(reg);
+                let spill = pos.built_inst();
+                liveness.create_dead(reg, fill, affinity);
+                liveness.extend_locally(reg, ebb, spill, &pos.func.layout);
+                liveness.create_dead(stack, spill, Affinity::Stack);
+                liveness.extend_locally(stack, ebb, inst, &pos.func.layout);
+                pos.func.locations[stack] = pos.func.locations[param];
+                pos.func.dfg.inst_variable_args_mut(inst)[argnum] = stack;
+                debug!("Transferred {} to {} through {}", arg, stack, reg);
+            }
+            for &argnum in &self.fills {
+                let arg = self.args[argnum];
+                let affinity = liveness[self.params[argnum]].affinity;
+                let reg = pos.ins().fill(arg);
+                let fill = pos.built_inst();
+                liveness.create_dead(reg, fill, affinity);
+                liveness.extend_locally(reg, ebb, inst, &pos.func.layout);
+                pos.func.dfg.inst_variable_args_mut(inst)[argnum] = reg;
+                debug!("Filled {} into {}", arg, reg);
+            }
+        }
+    }
+}
+
+/// Get the destination of `inst` if it is a branch with EBB arguments.
+fn branch_with_args(func: &Function, inst: Inst) -> Option<Ebb> {
+    match func.dfg.analyze_branch(inst) {
+        BranchInfo::SingleDest(dest, args) if !args.is_empty() => Some(dest),
+        _ => None,
+    }
+}
@@ -0,0 +1,300 @@
+//! Register pressure reports.
+//!
+//! The register allocator spills values when more of them are live at the same time than there
+//! are registers to hold them. A `RegisterPressure` report counts the maximum number of values
+//! that are simultaneously live in each EBB, per top-level register class, so embedders can find
+//! the parts of a function that are likely to cause spilling.
+//!
+//! The report only depends on the function and its control flow graph, so it can be computed at
+//! any point in the pipeline. `Context::compile_with_register_pressure()` computes it both before
+//! and after legalization, since legalization can change the number of values a lot, for example
+//! when splitting wide integers into register-sized halves.
+//!
+//! Values are assigned to register classes by their type, the same way as ABI arguments. The
+//! counts are values, not register units, and CPU flags values are not counted.
+
+use crate::entity::{EntitySet, SecondaryMap};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::{Ebb, Function, Inst, Value};
+use crate::isa::{RegClass, TargetIsa};
+use crate::live_sets::LiveSets;
+use core::fmt;
+use std::vec::Vec;
+
+/// The maximum register pressure in an EBB.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EbbPressure {
+    /// The EBB.
+    pub ebb: Ebb,
+
+    /// Maximum number of simultaneously live values in each top-level register class, indexed
+    /// like `RegisterPressure::classes()`.
+    pub max_live: Vec<usize>,
+
+    /// The first instruction where the maximum of each register class is reached, or `None` if it
+    /// is reached on entry to the EBB.
+    pub peak: Vec<Option<Inst>>,
+}
+
+/// The maximum register pressure of every EBB in a function.
+#[derive(Clone, Debug)]
+pub struct RegisterPressure {
+    /// The top-level register classes of the ISA.
+    classes: Vec<RegClass>,
+
+    /// Number of allocatable registers in each class.
+    available: Vec<usize>,
+
+    /// The pressure of each EBB, in layout order.
+    ebbs: Vec<EbbPressure>,
+}
+
+impl RegisterPressure {
+    /// Compute the register pressure of `func` for `isa`.
+    ///
+    /// The control flow graph `cfg` must be up to date.
+    pub fn new(func: &Function, cfg: &ControlFlowGraph, isa: &dyn TargetIsa) -> Self {
+        let reginfo = isa.register_info();
+        let classes: Vec<RegClass> = reginfo
+            .classes
+            .iter()
+            .cloned()
+            .filter(|rc| rc.toprc == rc.index)
+            .collect();
+        let regs = isa.allocatable_registers(func);
+        let available = classes.iter().map(|&rc| regs.iter(rc).count()).collect();
+
+        let mut pressure = Self {
+            classes,
+            available,
+            ebbs: Vec::new(),
+        };
+
+        let sets = LiveSets::with_function(func, cfg);
+        let mut kills = SecondaryMap::<Inst, Vec<Value>>::new();
+        let mut seen = EntitySet::new();
+        for ebb in func.layout.ebbs() {
+            pressure.compute_ebb(func, isa, &sets, &mut kills, &mut seen, ebb);
+        }
+        pressure
+    }
+
+    /// Count the live values in `ebb`.
+    fn compute_ebb(
+        &mut self,
+        func: &Function,
+        isa: &dyn TargetIsa,
+        sets: &LiveSets,
+        kills: &mut SecondaryMap<Inst, Vec<Value>>,
+        seen: &mut EntitySet<Value>,
+        ebb: Ebb,
+    ) {
+        // Walk backwards to find the last use of each value in the EBB. Values that are live out
+        // of the EBB are conservatively kept live until its end, even if they are only live out
+        // through an earlier branch.
+        seen.clear();
+        for &value in sets.live_out(ebb) {
+            seen.insert(value);
+        }
+        let mut inst = func.layout.last_inst(ebb);
+        while let Some(i) = inst {
+            kills[i].clear();
+            for &arg in func.dfg.inst_args(i) {
+                let arg = func.dfg.resolve_aliases(arg);
+                if seen.insert(arg) {
+                    kills[i].push(arg);
+                }
+            }
+            inst = func.layout.prev_inst(i);
+        }
+
+        let mut live = vec![0; self.classes.len()];
+        for &value in sets.live_in(ebb).iter().chain(func.dfg.ebb_params(ebb)) {
+            if let Some(idx) = self.class_index(func, isa, value) {
+                live[idx] += 1;
+            }
+        }
+        let mut max_live = live.clone();
+        let mut peak = vec![None; self.classes.len()];
+
+        for inst in func.layout.ebb_insts(ebb) {
+            for &value in &kills[inst] {
+                if let Some(idx) = self.class_index(func, isa, value) {
+                    live[idx] -= 1;
+                }
+            }
+
+            // The results are live when the instruction is executed, even if they are never used.
+            let results = func.dfg.inst_results(inst);
+            for &value in results {
+                if let Some(idx) = self.class_index(func, isa, value) {
+                    live[idx] += 1;
+                }
+            }
+            for idx in 0..live.len() {
+                if live[idx] > max_live[idx] {
+                    max_live[idx] = live[idx];
+                    peak[idx] = Some(inst);
+                }
+            }
+            for &value in results {
+                if !seen.contains(value) {
+                    if let Some(idx) = self.class_index(func, isa, value) {
+                        live[idx] -= 1;
+                    }
+                }
+            }
+        }
+
+        self.ebbs.push(EbbPressure {
+            ebb,
+            max_live,
+            peak,
+        });
+    }
+
+    /// Get the index of the top-level register class holding `value`, if it is counted.
+    fn class_index(&self, func: &Function, isa: &dyn TargetIsa, value: Value) -> Option<usize> {
+        let ty = func.dfg.value_type(value);
+        if ty.is_flags() {
+            return None;
+        }
+        let toprc = isa.regclass_for_abi_type(ty).toprc();
+        self.classes.iter().position(|rc| rc.index == toprc.index)
+    }
+
+    /// Get the top-level register classes that values are counted in.
+    pub fn classes(&self) -> &[RegClass] {
+        &self.classes
+    }
+
+    /// Get the number of allocatable registers in each class, indexed like `classes()`.
+    pub fn available(&self) -> &[usize] {
+        &self.available
+    }
+
+    /// Get the pressure of each EBB, in layout order.
+    pub fn ebbs(&self) -> &[EbbPressure] {
+        &self.ebbs
+    }
+
+    /// Get the maximum number of simultaneously live values in the register class named
+    /// `class` in `ebb`.
+    ///
+    /// Returns 0 if the EBB is not in the layout or there is no such register class.
+    pub fn max_live(&self, ebb: Ebb, class: &str) -> usize {
+        match (
+            self.ebbs.iter().find(|e| e.ebb == ebb),
+            self.classes.iter().position(|rc| rc.name == class),
+        ) {
+            (Some(e), Some(idx)) => e.max_live[idx],
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for RegisterPressure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for e in &self.ebbs {
+            write!(f, "{}:", e.ebb)?;
+            for (idx, rc) in self.classes.iter().enumerate() {
+                write!(
+                    f,
+                    " {} {}/{}",
+                    rc.name, e.max_live[idx], self.available[idx]
+                )?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Register pressure reports computed by `Context::compile_with_register_pressure()`.
+#[derive(Clone, Debug)]
+pub struct PressureReport {
+    /// The pressure of the function before legalization.
+    pub before_legalization: RegisterPressure,
+
+    /// The pressure of the function after legalization, right before register allocation.
+    pub after_legalization: RegisterPressure,
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::RegisterPressure;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::flowgraph::ControlFlowGraph;
+    use crate::ir::immediates::Ieee64;
+    use crate::ir::{types, Function, InstBuilder};
+    use crate::test_utils;
+    use crate::Context;
+
+    #[test]
+    fn straight_line() {
+        let isa = test_utils::isa("x86_64");
+
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let x = func.dfg.append_ebb_param(ebb0, types::I32);
+        let (v2, mul);
+        {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            let v1 = cur.ins().iconst(types::I32, 1);
+            v2 = cur.ins().iconst(types::I32, 2);
+            let v3 = cur.ins().iadd(v1, v2);
+            let v4 = cur.ins().iadd(v3, x);
+            let f = cur.ins().f64const(Ieee64::with_bits(0));
+            let g = cur.ins().fmul(f, f);
+            mul = cur.func.dfg.value_def(g).unwrap_inst();
+            // The result is never used, so it is only live at its definition.
+            cur.ins().fadd(g, f);
+            cur.ins().jump(ebb1, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().return_(&[v4]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        let pressure = RegisterPressure::new(&func, &cfg, &*isa);
+
+        // `x`, `v1`, and `v2` before the first `iadd` kills `v1` and `v2`.
+        assert_eq!(pressure.max_live(ebb0, "GPR"), 3);
+        // `f` and `g`.
+        assert_eq!(pressure.max_live(ebb0, "FPR"), 2);
+        assert_eq!(pressure.max_live(ebb1, "GPR"), 1);
+        assert_eq!(pressure.max_live(ebb1, "FPR"), 0);
+
+        let classes = pressure.classes();
+        let gpr = classes.iter().position(|rc| rc.name == "GPR").unwrap();
+        let fpr = classes.iter().position(|rc| rc.name == "FPR").unwrap();
+        let ebb0_pressure = &pressure.ebbs()[0];
+        assert_eq!(
+            ebb0_pressure.peak[gpr],
+            Some(func.dfg.value_def(v2).unwrap_inst())
+        );
+        assert_eq!(ebb0_pressure.peak[fpr], Some(mul));
+        assert_eq!(pressure.available()[gpr], 14);
+    }
+
+    #[test]
+    fn compile() {
+        // Use a 32-bit target, where `i64` values are split in two registers by legalization.
+        let isa = test_utils::isa("i686");
+        let func = test_utils::function(&[types::I64, types::I64], &[types::I64], |cur, args| {
+            let z = cur.ins().band(args[0], args[1]);
+            cur.ins().return_(&[z]);
+        });
+        let ebb0 = func.layout.entry_block().unwrap();
+
+        let mut ctx = Context::for_function(func);
+        let (_, report) = ctx.compile_with_register_pressure(&*isa).unwrap();
+
+        // The `i64` values are split into two registers by legalization.
+        assert_eq!(report.before_legalization.max_live(ebb0, "GPR"), 2);
+        assert!(report.after_legalization.max_live(ebb0, "GPR") >= 4);
+        assert!(ctx.diagnostics().is_empty());
+    }
+}
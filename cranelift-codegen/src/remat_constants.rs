@@ -0,0 +1,127 @@
+//! Rematerialization of constant EBB parameters.
+//!
+//! Frontends often pass constants to an EBB as EBB arguments, for example when a variable is
+//! assigned a constant on every path into a join point. When every predecessor passes the same
+//! `iconst` for a parameter, this pass removes the parameter and defines its value with a new
+//! `iconst` at the top of the EBB instead. This shrinks the argument lists of the branches, and
+//! the constant no longer occupies a register across the edges.
+//!
+//! The control flow graph is not changed by this pass.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::dfg::ValueDef;
+use crate::ir::immediates::Imm64;
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value};
+use crate::timing;
+use log::debug;
+use std::vec::Vec;
+
+/// Replace the EBB parameters of `func` that always receive the same constant.
+pub fn do_remat_constants(func: &mut Function, cfg: &ControlFlowGraph) {
+    let _tt = timing::remat_constants();
+    debug_assert!(cfg.is_valid());
+
+    // Rematerializing a parameter can make the arguments of other branches constant, so iterate
+    // until nothing changes.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        for ebb in ebbs {
+            changed |= remat_ebb_params(func, cfg, ebb);
+        }
+    }
+}
+
+/// Get the value of `value` if it is defined by an `iconst` instruction.
+pub(crate) fn iconst_value(dfg: &DataFlowGraph, value: Value) -> Option<Imm64> {
+    match dfg.value_def(dfg.resolve_aliases(value)) {
+        ValueDef::Result(inst, _) => match dfg[inst] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => Some(imm),
+            _ => None,
+        },
+        ValueDef::Param(..) => None,
+    }
+}
+
+/// Get the constant passed as parameter `num` of `ebb` by every branch in `branches`.
+///
+/// A branch passing the parameter to itself doesn't change its value, so it is ignored.
+fn constant_param(dfg: &DataFlowGraph, branches: &[Inst], ebb: Ebb, num: usize) -> Option<Imm64> {
+    let param = dfg.ebb_params(ebb)[num];
+    let mut constant = None;
+    for &inst in branches {
+        let arg = match dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(dest, args) if dest == ebb => args[num],
+            _ => return None,
+        };
+        if dfg.resolve_aliases(arg) == param {
+            continue;
+        }
+        let imm = iconst_value(dfg, arg)?;
+        match constant {
+            Some(c) if c != imm => return None,
+            _ => constant = Some(imm),
+        }
+    }
+    constant
+}
+
+/// Rematerialize the constant parameters of `ebb`. Returns true if any parameter was removed.
+fn remat_ebb_params(func: &mut Function, cfg: &ControlFlowGraph, ebb: Ebb) -> bool {
+    // The parameters of the entry block are the function arguments.
+    if func.layout.entry_block() == Some(ebb) || func.dfg.num_ebb_params(ebb) == 0 {
+        return false;
+    }
+
+    // The parameters of an unreachable EBB have no value to rematerialize.
+    let branches: Vec<Inst> = cfg.pred_iter(ebb).map(|pred| pred.inst).collect();
+    if branches.is_empty() {
+        return false;
+    }
+
+    let mut changed = false;
+    let mut num = 0;
+    while num < func.dfg.num_ebb_params(ebb) {
+        let imm = match constant_param(&func.dfg, &branches, ebb, num) {
+            Some(imm) => imm,
+            None => {
+                num += 1;
+                continue;
+            }
+        };
+        changed = true;
+
+        let param = func.dfg.ebb_params(ebb)[num];
+        let ty = func.dfg.value_type(param);
+        debug!(
+            "Rematerializing {} = iconst.{} {} in {}",
+            param, ty, imm, ebb
+        );
+
+        // Remove the argument from every branch to `ebb`.
+        for &inst in &branches {
+            let index = func.dfg[inst]
+                .opcode()
+                .constraints()
+                .num_fixed_value_arguments()
+                + num;
+            let mut list = func.dfg[inst]
+                .take_value_list()
+                .expect("branches have a value list");
+            list.remove(index, &mut func.dfg.value_lists);
+            func.dfg[inst].put_value_list(list);
+        }
+
+        // Define the parameter value with a local constant instead.
+        func.dfg.remove_ebb_param(param);
+        let mut pos = FuncCursor::new(func).at_first_insertion_point(ebb);
+        pos.ins().with_result(param).iconst(ty, imm);
+    }
+    changed
+}
@@ -84,6 +84,41 @@ impl Builder {
         }
     }
 
+    /// Iterate over the settings and presets in this group.
+    ///
+    /// The reported defaults are the values before any settings were changed on this builder.
+    pub fn iter(&self) -> impl Iterator<Item = Setting> {
+        let template = self.template;
+        template.descriptors.iter().map(move |d| {
+            let byte = || template.defaults[d.offset as usize];
+            let (kind, values, default) = match d.detail {
+                detail::Detail::Bool { bit } => (
+                    SettingKind::Bool,
+                    None,
+                    Some(SettingValue::Bool(byte() & (1 << bit) != 0)),
+                ),
+                detail::Detail::Num => (SettingKind::Num, None, Some(SettingValue::Num(byte()))),
+                detail::Detail::Enum { last, enumerators } => {
+                    let values = template.enums(last, enumerators);
+                    let default = values[usize::from(byte())];
+                    (
+                        SettingKind::Enum,
+                        Some(values),
+                        Some(SettingValue::Enum(default)),
+                    )
+                }
+                detail::Detail::Preset => (SettingKind::Preset, None, None),
+            };
+            Setting {
+                name: d.name,
+                description: d.description,
+                kind,
+                values,
+                default,
+            }
+        })
+    }
+
     /// Look up a descriptor by name.
     fn lookup(&self, name: &str) -> SetResult<(usize, detail::Detail)> {
         match probe(self.template, name, simple_hash(name)) {
@@ -164,6 +199,55 @@ impl Configurable for Builder {
     }
 }
 
+/// The kind of a setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingKind {
+    /// A boolean setting.
+    Bool,
+    /// A numerical setting.
+    Num,
+    /// A setting that takes one of a fixed set of values.
+    Enum,
+    /// A preset, which can only be enabled. It enables a collection of other settings.
+    Preset,
+}
+
+/// The value of a setting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingValue {
+    /// Value of a boolean setting.
+    Bool(bool),
+    /// Value of a numerical setting.
+    Num(u8),
+    /// Value of an enumerated setting.
+    Enum(&'static str),
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SettingValue::Bool(b) => write!(f, "{}", b),
+            SettingValue::Num(n) => write!(f, "{}", n),
+            SettingValue::Enum(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Description of a setting or preset, as returned by `Builder::iter()`.
+#[derive(Clone, Copy, Debug)]
+pub struct Setting {
+    /// Name of the setting, as accepted by `Configurable::set()` and `Configurable::enable()`.
+    pub name: &'static str,
+    /// Description of the setting. This is empty for presets.
+    pub description: &'static str,
+    /// The kind of setting.
+    pub kind: SettingKind,
+    /// The values accepted by an enumerated setting.
+    pub values: Option<&'static [&'static str]>,
+    /// The default value of the setting. Presets don't have a value.
+    pub default: Option<SettingValue>,
+}
+
 /// An error produced when changing a setting.
 #[derive(Fail, Debug, PartialEq, Eq)]
 pub enum SetError {
@@ -285,6 +369,9 @@ pub mod detail {
         /// Lower snake-case name of setting as defined in meta.
         pub name: &'static str,
 
+        /// Description of the setting from meta, or an empty string for presets.
+        pub description: &'static str,
+
         /// Offset of byte containing this setting.
         pub offset: u32,
 
@@ -368,7 +455,7 @@ impl<'a> From<&'a dyn TargetIsa> for FlagsOrIsa<'a> {
 mod tests {
     use super::Configurable;
     use super::SetError::*;
-    use super::{builder, Flags};
+    use super::{builder, Flags, SettingKind, SettingValue};
     use std::string::ToString;
 
     #[test]
@@ -379,22 +466,34 @@ mod tests {
             f.to_string(),
             "[shared]\n\
              opt_level = \"default\"\n\
+             tls_model = \"none\"\n\
+             regalloc_algorithm = \"coloring\"\n\
+             code_padding = \"trap\"\n\
+             determinism_check = \"none\"\n\
              baldrdash_prologue_words = 0\n\
              probestack_size_log2 = 12\n\
+             loop_alignment = 0\n\
              enable_verifier = true\n\
              is_pic = false\n\
              colocated_libcalls = false\n\
+             far_calls = false\n\
              avoid_div_traps = false\n\
              enable_float = true\n\
              enable_nan_canonicalization = false\n\
+             enable_float_contraction = false\n\
              enable_simd = true\n\
              enable_atomics = true\n\
              allones_funcaddrs = false\n\
              probestack_enabled = true\n\
              probestack_func_adjusts_sp = false\n\
-             jump_tables_enabled = true\n"
+             jump_tables_enabled = true\n\
+             enable_branch_fusion = true\n"
         );
         assert_eq!(f.opt_level(), super::OptLevel::Default);
+        assert_eq!(f.tls_model(), super::TlsModel::None);
+        assert_eq!(f.regalloc_algorithm(), super::RegallocAlgorithm::Coloring);
+        assert_eq!(f.code_padding(), super::CodePadding::Trap);
+        assert_eq!(f.determinism_check(), super::DeterminismCheck::None);
         assert_eq!(f.enable_simd(), true);
         assert_eq!(f.baldrdash_prologue_words(), 0);
     }
@@ -433,4 +532,27 @@ mod tests {
         assert_eq!(f.enable_simd(), false);
         assert_eq!(f.opt_level(), super::OptLevel::Best);
     }
+
+    #[test]
+    fn enumerate() {
+        let mut b = builder();
+        assert_eq!(b.set("opt_level", "best"), Ok(()));
+
+        let opt_level = b.iter().find(|s| s.name == "opt_level").unwrap();
+        assert_eq!(opt_level.kind, SettingKind::Enum);
+        assert_eq!(opt_level.values, Some(&["default", "best", "fastest"][..]));
+        assert_eq!(opt_level.default, Some(SettingValue::Enum("default")));
+        assert!(!opt_level.description.is_empty());
+
+        let simd = b.iter().find(|s| s.name == "enable_simd").unwrap();
+        assert_eq!(simd.kind, SettingKind::Bool);
+        assert_eq!(simd.default, Some(SettingValue::Bool(true)));
+
+        let words = b
+            .iter()
+            .find(|s| s.name == "baldrdash_prologue_words")
+            .unwrap();
+        assert_eq!(words.default, Some(SettingValue::Num(0)));
+        assert_eq!(words.default.unwrap().to_string(), "0");
+    }
 }
@@ -2,32 +2,23 @@
 
 use crate::cursor::{Cursor, FuncCursor};
 use crate::dominator_tree::DominatorTree;
-use crate::ir::{Function, Inst, InstructionData, Opcode, Type};
+use crate::entity::SecondaryMap;
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::immediates::Offset32;
+use crate::ir::{Function, Inst, InstructionData, Opcode, Type, Value};
 use crate::scoped_hash_map::ScopedHashMap;
 use crate::timing;
 use core::cell::{Ref, RefCell};
 use core::hash::{Hash, Hasher};
 use std::vec::Vec;
 
-/// Test whether the given opcode is unsafe to even consider for GVN.
-fn trivially_unsafe_for_gvn(opcode: Opcode) -> bool {
-    opcode.is_call()
-        || opcode.is_branch()
-        || opcode.is_terminator()
-        || opcode.is_return()
-        || opcode.can_trap()
-        || opcode.other_side_effects()
-        || opcode.can_store()
-        || opcode.writes_cpu_flags()
-}
-
 /// Test that, if the specified instruction is a load, it doesn't have the `readonly` memflag.
 fn is_load_and_not_readonly(inst_data: &InstructionData) -> bool {
     match *inst_data {
         InstructionData::Load { flags, .. } | InstructionData::LoadComplex { flags, .. } => {
             !flags.readonly()
         }
-        _ => inst_data.opcode().can_load(),
+        _ => inst_data.opcode().effects().reads_memory,
     }
 }
 
@@ -53,10 +44,28 @@ impl<'a, 'f: 'a> PartialEq for HashKey<'a, 'f> {
 }
 impl<'a, 'f: 'a> Eq for HashKey<'a, 'f> {}
 
+/// Key identifying the contents of a memory location in a given memory state.
+///
+/// Loads and stores that access the same address and type in the same memory state see the same
+/// value. The memory state changes at every instruction that may write to memory, and we have no
+/// alias information, so a store to one address invalidates all known memory contents.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct MemKey {
+    addr: Value,
+    offset: Offset32,
+    ty: Type,
+    state: u32,
+}
+
 /// Perform simple GVN on `func`.
 ///
-pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
+/// Besides pure instructions and `readonly` loads, redundant loads are also eliminated when no
+/// instruction that may write memory executes between them and a dominating load or store to
+/// the same address. This forwards values across EBBs only when the EBB has a single predecessor,
+/// since the memory state is unknown at merge points.
+pub fn do_simple_gvn(func: &mut Function, cfg: &ControlFlowGraph, domtree: &mut DominatorTree) {
     let _tt = timing::gvn();
+    debug_assert!(cfg.is_valid());
     debug_assert!(domtree.is_valid());
 
     // Visit EBBs in a reverse post-order.
@@ -66,8 +75,15 @@ pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
     let pos = RefCell::new(FuncCursor::new(func));
 
     let mut visible_values: ScopedHashMap<HashKey, Inst> = ScopedHashMap::new();
+    let mut memory_values: ScopedHashMap<MemKey, Value> = ScopedHashMap::new();
     let mut scope_stack: Vec<Inst> = Vec::new();
 
+    // Current memory state, and the memory state at each branch instruction visited.
+    let mut num_states = 0;
+    let mut mem_state;
+    let mut branch_states: SecondaryMap<Inst, u32> = SecondaryMap::new();
+
+    use crate::scoped_hash_map::Entry::*;
     for &ebb in domtree.cfg_postorder().iter().rev() {
         {
             // Pop any scopes that we just exited.
@@ -82,13 +98,25 @@ pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
                 }
                 scope_stack.pop();
                 visible_values.decrement_depth();
+                memory_values.decrement_depth();
             }
 
             // Push a scope for the current block.
             scope_stack.push(layout.first_inst(ebb).unwrap());
             visible_values.increment_depth();
+            memory_values.increment_depth();
         }
 
+        // The memory state is inherited from a single predecessor. Otherwise start a new one.
+        let mut preds = cfg.pred_iter(ebb);
+        mem_state = match (preds.next(), preds.next()) {
+            (Some(pred), None) if branch_states[pred.inst] != 0 => branch_states[pred.inst],
+            _ => {
+                num_states += 1;
+                num_states
+            }
+        };
+
         pos.borrow_mut().goto_top(ebb);
         while let Some(inst) = {
             let mut pos = pos.borrow_mut();
@@ -101,17 +129,78 @@ pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
 
             let opcode = func.dfg[inst].opcode();
 
-            if opcode.is_branch() && !opcode.is_terminator() {
-                scope_stack.push(func.layout.next_inst(inst).unwrap());
-                visible_values.increment_depth();
+            if opcode.is_branch() {
+                branch_states[inst] = mem_state;
+                if !opcode.is_terminator() {
+                    scope_stack.push(func.layout.next_inst(inst).unwrap());
+                    visible_values.increment_depth();
+                    memory_values.increment_depth();
+                }
+            }
+
+            if opcode.effects().may_clobber_memory() {
+                num_states += 1;
+                mem_state = num_states;
+
+                // The stored value is now known to be in memory.
+                if let InstructionData::Store {
+                    opcode: Opcode::Store,
+                    args,
+                    offset,
+                    ..
+                } = func.dfg[inst]
+                {
+                    let key = MemKey {
+                        addr: args[1],
+                        offset,
+                        ty: func.dfg.value_type(args[0]),
+                        state: mem_state,
+                    };
+                    if let Vacant(entry) = memory_values.entry(key) {
+                        entry.insert(args[0]);
+                    }
+                }
             }
 
-            if trivially_unsafe_for_gvn(opcode) {
+            // Instructions that can't be merged are unsafe to even consider for GVN.
+            if !opcode.effects().can_move() {
                 continue;
             }
 
             // These are split up to separate concerns.
             if is_load_and_not_readonly(&func.dfg[inst]) {
+                // A plain load can reuse the value of an earlier access in the same memory state.
+                if let InstructionData::Load {
+                    opcode: Opcode::Load,
+                    arg,
+                    offset,
+                    ..
+                } = func.dfg[inst]
+                {
+                    let result = func.dfg.first_result(inst);
+                    let key = MemKey {
+                        addr: arg,
+                        offset,
+                        ty: func.dfg.value_type(result),
+                        state: mem_state,
+                    };
+                    let value = match memory_values.entry(key) {
+                        Occupied(entry) => *entry.get(),
+                        Vacant(entry) => {
+                            entry.insert(result);
+                            continue;
+                        }
+                    };
+                    let old = scope_stack.last_mut().unwrap();
+                    if *old == inst {
+                        *old = func.layout.next_inst(inst).unwrap();
+                    }
+                    drop(func);
+                    let mut pos = pos.borrow_mut();
+                    pos.func.dfg.clear_results(inst);
+                    pos.func.dfg.change_to_alias(result, value);
+                    pos.remove_inst_and_step_back();
+                }
                 continue;
             }
 
@@ -121,7 +210,6 @@ pub fn do_simple_gvn(func: &mut Function, domtree: &mut DominatorTree) {
                 ty: ctrl_typevar,
                 pos: &pos,
             };
-            use crate::scoped_hash_map::Entry::*;
             match visible_values.entry(key) {
                 Occupied(entry) => {
                     debug_assert!(domtree.dominates(*entry.get(), inst, &func.layout));
@@ -10,10 +10,13 @@ use crate::divconst_magic_numbers::{MS32, MS64, MU32, MU64};
 use crate::flowgraph::ControlFlowGraph;
 use crate::ir::condcodes::{CondCode, IntCC};
 use crate::ir::dfg::ValueDef;
+use crate::ir::immediates::Imm64;
 use crate::ir::instructions::{Opcode, ValueList};
-use crate::ir::types::{I32, I64};
+use crate::ir::types::{I16, I32, I64, I8};
 use crate::ir::Inst;
-use crate::ir::{DataFlowGraph, Ebb, Function, InstBuilder, InstructionData, Type, Value};
+use crate::ir::{
+    BranchWeight, DataFlowGraph, Ebb, Function, InstBuilder, InstructionData, Type, Value,
+};
 use crate::timing;
 
 //----------------------------------------------------------------------
@@ -140,6 +143,54 @@ fn get_div_info(inst: Inst, dfg: &DataFlowGraph) -> Option<DivRemByConstInfo> {
     None
 }
 
+/// Rewrite a div or rem of an `i8` or `i16` value by a constant as the same operation on the
+/// value extended to `i32`, followed by an `ireduce`, so that it can be strength reduced by
+/// `do_divrem_transformation`. Returns the new 32-bit div or rem instruction.
+///
+/// Unsigned immediates only keep their low bits, the same as the narrow operation sees them, so
+/// `udiv_imm.i8 v0, -1` divides by 255. Divisions that can trap, by zero or signed by -1, are left
+/// unchanged, as are signed immediates that don't fit in the narrow type.
+fn widen_narrow_divrem(pos: &mut FuncCursor, inst: Inst) -> Option<Inst> {
+    let (opcode, arg, imm) = match pos.func.dfg[inst] {
+        InstructionData::BinaryImm { opcode, arg, imm } => (opcode, arg, imm),
+        _ => return None,
+    };
+    let is_signed = match opcode {
+        Opcode::UdivImm | Opcode::UremImm => false,
+        Opcode::SdivImm | Opcode::SremImm => true,
+        _ => return None,
+    };
+
+    let ty = pos.func.dfg.value_type(arg);
+    if ty != I8 && ty != I16 {
+        return None;
+    }
+    let bits = ty.bits();
+    let imm_i64: i64 = imm.into();
+    let imm_i64 = if is_signed {
+        if imm_i64 < -(1 << (bits - 1)) || imm_i64 >= (1 << (bits - 1)) || imm_i64 == -1 {
+            return None;
+        }
+        imm_i64
+    } else {
+        imm_i64 & ((1 << bits) - 1)
+    };
+    if imm_i64 == 0 {
+        return None;
+    }
+    let imm = Imm64::new(imm_i64);
+
+    let wide_arg = if is_signed {
+        pos.ins().sextend(I32, arg)
+    } else {
+        pos.ins().uextend(I32, arg)
+    };
+    let (wide_inst, dfg) = pos.ins().BinaryImm(opcode, I32, imm, wide_arg);
+    let wide_result = dfg.first_result(wide_inst);
+    pos.func.dfg.replace(inst).ireduce(ty, wide_result);
+    Some(wide_inst)
+}
+
 /// Actually do the transformation given a bundle containing the relevant information.
 /// `divrem_info` describes a div or rem by a constant, that `pos` currently points at, and `inst`
 /// is the associated instruction.  `inst` is replaced by a sequence of other operations that
@@ -762,6 +813,10 @@ fn branch_order(pos: &mut FuncCursor, cfg: &mut ControlFlowGraph, ebb: Ebb, inst
         }
     }
 
+    // The conditional branch now goes to the other destination.
+    let weight = pos.func.branch_weights[cond_inst];
+    pos.func.branch_weights[cond_inst] = weight.map(BranchWeight::inverse);
+
     cfg.recompute_ebb(pos.func, ebb);
 }
 
@@ -774,9 +829,13 @@ pub fn do_preopt(func: &mut Function, cfg: &mut ControlFlowGraph) {
             // Apply basic simplifications.
             simplify(&mut pos, inst);
 
-            // Try to transform divide-by-constant into simpler operations.
-            if let Some(divrem_info) = get_div_info(inst, &pos.func.dfg) {
-                do_divrem_transformation(&divrem_info, &mut pos, inst);
+            // Try to transform divide-by-constant into simpler operations. Narrow divisions are
+            // widened to 32 bits first.
+            let divrem_inst = widen_narrow_divrem(&mut pos, inst).unwrap_or(inst);
+            if let Some(divrem_info) = get_div_info(divrem_inst, &pos.func.dfg) {
+                pos.goto_inst(divrem_inst);
+                do_divrem_transformation(&divrem_info, &mut pos, divrem_inst);
+                pos.goto_inst(inst);
                 continue;
             }
 
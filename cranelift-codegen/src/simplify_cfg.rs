@@ -0,0 +1,227 @@
+//! Control flow graph simplification.
+//!
+//! This pass cleans up the control flow graph by:
+//!
+//! - Folding `brz` and `brnz` instructions whose condition is a constant.
+//! - Bypassing EBBs that contain nothing but an unconditional jump.
+//! - Merging EBBs into their single predecessor when it ends in an unconditional jump.
+//!
+//! The layout and the control flow graph are updated in place. EBBs that become unreachable are
+//! left in the layout; they are removed by the unreachable code elimination pass.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::dfg::ValueDef;
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value};
+use crate::timing;
+use log::debug;
+use std::vec::Vec;
+
+/// Simplify the control flow graph of `func`.
+pub fn do_simplify_cfg(func: &mut Function, cfg: &mut ControlFlowGraph) {
+    let _tt = timing::simplify_cfg();
+    debug_assert!(cfg.is_valid());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+        for ebb in ebbs {
+            // The EBB may have been removed while simplifying an earlier EBB.
+            if !func.layout.is_ebb_inserted(ebb) {
+                continue;
+            }
+
+            changed |= fold_constant_branches(func, cfg, ebb);
+
+            if bypass_forwarding_ebb(func, cfg, ebb) {
+                changed = true;
+                continue;
+            }
+
+            while merge_successor(func, cfg, ebb) {
+                changed = true;
+            }
+        }
+    }
+}
+
+/// Get the direction taken by the `brz` or `brnz` instruction `inst` if its condition is a
+/// constant.
+fn constant_branch_direction(dfg: &DataFlowGraph, inst: Inst) -> Option<bool> {
+    let opcode = match dfg[inst] {
+        InstructionData::Branch { opcode, .. } => opcode,
+        _ => return None,
+    };
+
+    let cond = dfg.resolve_aliases(dfg.inst_args(inst)[0]);
+    let nonzero = match dfg.value_def(cond) {
+        ValueDef::Result(def, _) => match dfg[def] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                imm != 0
+            }
+            InstructionData::UnaryBool {
+                opcode: Opcode::Bconst,
+                imm,
+            } => imm,
+            _ => return None,
+        },
+        ValueDef::Param(..) => return None,
+    };
+
+    match opcode {
+        Opcode::Brz => Some(!nonzero),
+        Opcode::Brnz => Some(nonzero),
+        _ => None,
+    }
+}
+
+/// Fold the branches in `ebb` that have a constant condition.
+///
+/// A branch that is always taken becomes a `jump`, and the rest of the EBB is removed. A branch
+/// that is never taken is removed.
+fn fold_constant_branches(func: &mut Function, cfg: &mut ControlFlowGraph, ebb: Ebb) -> bool {
+    let mut changed = false;
+    let mut pos = FuncCursor::new(func).at_top(ebb);
+
+    while let Some(inst) = pos.next_inst() {
+        let taken = match constant_branch_direction(&pos.func.dfg, inst) {
+            Some(taken) => taken,
+            None => continue,
+        };
+        changed = true;
+
+        if taken {
+            let (dest, args) = match pos.func.dfg.analyze_branch(inst) {
+                BranchInfo::SingleDest(dest, args) => (dest, args.to_vec()),
+                _ => panic!("brz/brnz must have a single destination"),
+            };
+            debug!(
+                "Folding {} into a jump",
+                pos.func.dfg.display_inst(inst, None)
+            );
+            pos.func.dfg.replace(inst).jump(dest, &args);
+
+            // The rest of the EBB is now unreachable.
+            while let Some(next) = pos.func.layout.next_inst(inst) {
+                pos.func.layout.remove_inst(next);
+            }
+            break;
+        } else {
+            debug!("Removing {}", pos.func.dfg.display_inst(inst, None));
+            pos.remove_inst_and_step_back();
+        }
+    }
+
+    if changed {
+        cfg.recompute_ebb(pos.func, ebb);
+    }
+    changed
+}
+
+/// Change the destination of the branch `inst` to `dest`, passing `args` as EBB arguments.
+pub fn retarget_branch(func: &mut Function, inst: Inst, dest: Ebb, args: &[Value]) {
+    let num_fixed = func.dfg[inst]
+        .opcode()
+        .constraints()
+        .num_fixed_value_arguments();
+    let mut list = func.dfg[inst]
+        .take_value_list()
+        .expect("branches have a value list");
+    let pool = &mut func.dfg.value_lists;
+    let fixed: Vec<Value> = list.as_slice(pool)[..num_fixed].to_vec();
+    list.clear(pool);
+    list.extend(fixed.into_iter().chain(args.iter().cloned()), pool);
+    func.dfg[inst].put_value_list(list);
+    *func.dfg[inst]
+        .branch_destination_mut()
+        .expect("branches have a destination") = dest;
+}
+
+/// If `ebb` contains nothing but an unconditional jump, redirect all its predecessors to the
+/// jump destination and remove it.
+fn bypass_forwarding_ebb(func: &mut Function, cfg: &mut ControlFlowGraph, ebb: Ebb) -> bool {
+    if func.layout.entry_block() == Some(ebb) || !func.dfg.ebb_params(ebb).is_empty() {
+        return false;
+    }
+
+    let jump = match func.layout.first_inst(ebb) {
+        Some(inst) if func.dfg[inst].opcode() == Opcode::Jump => inst,
+        _ => return false,
+    };
+    let (dest, args) = match func.dfg.analyze_branch(jump) {
+        BranchInfo::SingleDest(dest, args) => (dest, args.to_vec()),
+        _ => return false,
+    };
+    if dest == ebb {
+        return false;
+    }
+
+    let preds: Vec<(Ebb, Inst)> = cfg.pred_iter(ebb).map(|p| (p.ebb, p.inst)).collect();
+    if preds.is_empty() {
+        return false;
+    }
+
+    // Jump tables can't pass EBB arguments, and they are shared between instructions.
+    if preds
+        .iter()
+        .any(|&(_, inst)| match func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(..) => false,
+            _ => true,
+        })
+    {
+        return false;
+    }
+
+    debug!("Bypassing forwarding {} to {}", ebb, dest);
+    for (pred_ebb, inst) in preds {
+        retarget_branch(func, inst, dest, &args);
+        cfg.recompute_ebb(func, pred_ebb);
+    }
+
+    func.layout.remove_inst(jump);
+    cfg.recompute_ebb(func, ebb);
+    func.layout.remove_ebb(ebb);
+    true
+}
+
+/// If `ebb` ends in an unconditional jump to an EBB with no other predecessors, move the
+/// instructions of that EBB into `ebb`.
+fn merge_successor(func: &mut Function, cfg: &mut ControlFlowGraph, ebb: Ebb) -> bool {
+    let jump = match func.layout.last_inst(ebb) {
+        Some(inst) if func.dfg[inst].opcode() == Opcode::Jump => inst,
+        _ => return false,
+    };
+    let (succ, args) = match func.dfg.analyze_branch(jump) {
+        BranchInfo::SingleDest(dest, args) => (dest, args.to_vec()),
+        _ => return false,
+    };
+    if succ == ebb || func.layout.entry_block() == Some(succ) || cfg.pred_iter(succ).count() != 1 {
+        return false;
+    }
+
+    debug!("Merging {} into {}", succ, ebb);
+
+    // The EBB parameters of `succ` become aliases of the jump arguments.
+    let params = func.dfg.detach_ebb_params(succ);
+    let params: Vec<Value> = params.as_slice(&func.dfg.value_lists).to_vec();
+    for (param, arg) in params.into_iter().zip(args) {
+        func.dfg.change_to_alias(param, arg);
+    }
+
+    func.layout.remove_inst(jump);
+    while let Some(inst) = func.layout.first_inst(succ) {
+        func.layout.remove_inst(inst);
+        func.layout.append_inst(inst, ebb);
+    }
+
+    cfg.recompute_ebb(func, succ);
+    func.layout.remove_ebb(succ);
+    cfg.recompute_ebb(func, ebb);
+    true
+}
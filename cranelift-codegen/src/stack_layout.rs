@@ -84,6 +84,13 @@ pub fn layout_stack(frame: &mut StackSlots, alignment: StackSize) -> CodegenResu
                 StackSlotKind::IncomingArg | StackSlotKind::OutgoingArg => continue,
             }
 
+            // Empty slots, like spill slots that were merged into other slots, don't need to be
+            // aligned.
+            if slot.size == 0 {
+                slot.offset = Some(offset);
+                continue;
+            }
+
             offset = offset
                 .checked_sub(slot.size as StackOffset)
                 .ok_or(CodegenError::ImplLimitExceeded)?;
@@ -208,4 +215,19 @@ mod tests {
         assert_eq!(sss[ss1].offset, Some(-8));
         assert_eq!(sss[ss2].offset, Some(-12));
     }
+
+    #[test]
+    fn empty_slots() {
+        let sss = &mut StackSlots::new();
+
+        let ss0 = sss.make_spill_slot(types::I32);
+        let ss1 = sss.push(StackSlotData::new(StackSlotKind::SpillSlot, 0));
+        let out0 = sss.get_outgoing_arg(types::I32, 0);
+
+        // The empty slot doesn't cause any padding.
+        assert_eq!(layout_stack(sss, 16), Ok(16));
+        assert_eq!(sss[ss0].offset, Some(-4));
+        assert_eq!(sss[ss1].offset, Some(-4));
+        assert_eq!(sss[out0].offset, Some(0));
+    }
 }
@@ -0,0 +1,81 @@
+//! Fixtures shared by the unit tests compiling functions for a target ISA.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::ir::{
+    types, AbiParam, ExtFuncData, ExternalName, FuncRef, Function, InstBuilder, Linkage, Signature,
+    Type, Value, Visibility,
+};
+use crate::isa::{self, CallConv, TargetIsa};
+use crate::settings;
+use core::str::FromStr;
+use std::boxed::Box;
+use std::vec::Vec;
+use target_lexicon::Triple;
+
+/// Look up the ISA for `triple`, with the default shared settings.
+pub fn isa(triple: &str) -> Box<dyn TargetIsa> {
+    isa_with_flags(triple, settings::builder())
+}
+
+/// Look up the ISA for `triple`, with the shared settings of `flags`.
+pub fn isa_with_flags(triple: &str, flags: settings::Builder) -> Box<dyn TargetIsa> {
+    isa::lookup(Triple::from_str(triple).unwrap())
+        .unwrap()
+        .finish(settings::Flags::new(flags))
+}
+
+/// Create a System V signature with `params` and `returns`.
+pub fn signature(params: &[Type], returns: &[Type]) -> Signature {
+    let mut sig = Signature::new(CallConv::SystemV);
+    sig.params
+        .extend(params.iter().map(|&ty| AbiParam::new(ty)));
+    sig.returns
+        .extend(returns.iter().map(|&ty| AbiParam::new(ty)));
+    sig
+}
+
+/// Create a function named `f` taking `params` and returning `returns`.
+///
+/// The entry EBB is inserted with one parameter for each of `params`, and `build` is called with
+/// a cursor at its end and the parameters to fill it.
+pub fn function<F>(params: &[Type], returns: &[Type], build: F) -> Function
+where
+    F: FnOnce(&mut FuncCursor, &[Value]),
+{
+    let sig = signature(params, returns);
+    let mut func = Function::with_name_signature(ExternalName::testcase("f"), sig);
+    let ebb = func.dfg.make_ebb();
+    let args = params
+        .iter()
+        .map(|&ty| func.dfg.append_ebb_param(ebb, ty))
+        .collect::<Vec<_>>();
+    let mut pos = FuncCursor::new(&mut func);
+    pos.insert_ebb(ebb);
+    build(&mut pos, &args);
+    func
+}
+
+/// Create a function returning twice its `i32` parameter.
+pub fn double() -> Function {
+    function(&[types::I32], &[types::I32], |pos, args| {
+        let sum = pos.ins().iadd(args[0], args[0]);
+        pos.ins().return_(&[sum]);
+    })
+}
+
+/// Import the function `name` with the signature `sig` into `func`.
+pub fn import_function(
+    func: &mut Function,
+    name: &str,
+    sig: Signature,
+    colocated: bool,
+) -> FuncRef {
+    let signature = func.import_signature(sig);
+    func.import_function(ExtFuncData {
+        name: ExternalName::testcase(name),
+        signature,
+        colocated,
+        linkage: Linkage::Import,
+        visibility: Visibility::Default,
+    })
+}
@@ -1,10 +1,23 @@
 //! Pass timing.
 //!
 //! This modules provides facilities for timing the execution of individual compilation passes.
+//!
+//! Besides the built-in aggregate timers, embedders can register `PassHooks` to be notified when
+//! each pass begins and ends, for example to emit tracing spans or profiler markers.
+//!
+//! `Context::compile_with_stats()` returns the timings of a single compilation in a
+//! `CompileStats` along with other statistics, so they can be processed by tools. With the
+//! `stats-json` feature, the statistics can be serialized as JSON.
 
+use crate::binemit::CodeOffset;
 use core::fmt;
+use core::time::Duration;
+use std::boxed::Box;
+#[cfg(feature = "stats-json")]
+use std::string::String;
+use std::vec::Vec;
 
-pub use self::details::{add_to_current, take_current, PassTimes, TimingToken};
+pub use self::details::{add_to_current, set_pass_hooks, take_current, PassTimes, TimingToken};
 
 // Each pass that can be timed is predefined with the `define_passes!` macro. Each pass has a
 // snake_case name and a plain text description used when printing out the timing report.
@@ -14,9 +27,10 @@ pub use self::details::{add_to_current, take_current, PassTimes, TimingToken};
 // - A C-style enum containing all the pass names and a `None` variant.
 // - A usize constant with the number of defined passes.
 // - A const array of pass descriptions.
+// - A const array of pass names.
 // - A public function per pass used to start the timing of that pass.
 macro_rules! define_passes {
-    { $enum:ident, $num_passes:ident, $descriptions:ident;
+    { $enum:ident, $num_passes:ident, $descriptions:ident, $names:ident;
       $($pass:ident: $desc:expr,)+
     } => {
         #[allow(non_camel_case_types)]
@@ -27,6 +41,8 @@ macro_rules! define_passes {
 
         const $descriptions: [&str; $num_passes] = [ $($desc),+ ];
 
+        const $names: [&str; $num_passes] = [ $(stringify!($pass)),+ ];
+
         $(
             #[doc=$desc]
             pub fn $pass() -> TimingToken {
@@ -38,7 +54,7 @@ macro_rules! define_passes {
 
 // Pass definitions.
 define_passes! {
-    Pass, NUM_PASSES, DESCRIPTIONS;
+    Pass, NUM_PASSES, DESCRIPTIONS, NAMES;
 
     process_file: "Processing test file",
     parse_text: "Parsing textual Cranelift IR",
@@ -49,28 +65,43 @@ define_passes! {
     verify_cssa: "Verify CSSA",
     verify_liveness: "Verify live ranges",
     verify_locations: "Verify value locations",
+    verify_legalized: "Verify legalized instructions",
     verify_flags: "Verify CPU flags",
 
     compile: "Compilation passes",
     flowgraph: "Control flow graph",
     domtree: "Dominator tree",
     loop_analysis: "Loop analysis",
+    live_sets: "Live-in/live-out sets",
     postopt: "Post-legalization rewriting",
     preopt: "Pre-legalization rewriting",
+    complex_addresses: "Form complex addresses",
+    remat_constants: "Rematerialize constant EBB parameters",
+    prune_ebb_params: "Remove redundant EBB parameters",
+    heap_bounds: "Heap bounds check elimination",
+    redundant_traps: "Redundant trap elimination",
     dce: "Dead code elimination",
     legalize: "Legalization",
     gvn: "Global value numbering",
     licm: "Loop invariant code motion",
+    inline: "Function inlining",
+    coverage: "Coverage instrumentation",
     unreachable_code: "Remove unreachable blocks",
+    ebb_layout: "Profile-guided EBB layout",
+    simplify_cfg: "Simplify control flow graph",
 
     regalloc: "Register allocation",
     ra_liveness: "RA liveness analysis",
     ra_cssa: "RA coalescing CSSA",
+    ra_ssa_destruction: "RA SSA destruction",
     ra_spilling: "RA spilling",
+    ra_linear_scan: "RA linear scan spilling",
     ra_reload: "RA reloading",
     ra_coloring: "RA coloring",
+    ra_spill_slots: "RA spill slot coalescing",
 
     prologue_epilogue: "Prologue/epilogue insertion",
+    peephole: "Post-regalloc peephole optimizations",
     shrink_instructions: "Instruction encoding shrinking",
     relax_branches: "Branch relaxation",
     binemit: "Binary machine code emission",
@@ -83,14 +114,132 @@ impl Pass {
     pub fn idx(self) -> usize {
         self as usize
     }
+
+    /// Get the snake_case name of this pass.
+    pub fn name(self) -> &'static str {
+        NAMES.get(self.idx()).cloned().unwrap_or("none")
+    }
+
+    /// Get the plain text description of this pass.
+    pub fn description(self) -> &'static str {
+        DESCRIPTIONS.get(self.idx()).cloned().unwrap_or("<no pass>")
+    }
 }
 
 impl fmt::Display for Pass {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match DESCRIPTIONS.get(self.idx()) {
-            Some(s) => f.write_str(s),
-            None => f.write_str("<no pass>"),
+        f.write_str(self.description())
+    }
+}
+
+/// Callbacks invoked when a timed pass begins and ends.
+///
+/// Passes are identified by their snake_case `name`, which matches the function in this module
+/// that starts the pass, and a plain text `description`. Passes can be nested, and the callbacks
+/// are always invoked in LIFO order.
+pub trait PassHooks {
+    /// The pass `name` is about to begin.
+    fn begin(&self, name: &'static str, description: &'static str);
+
+    /// The pass `name` has ended.
+    fn end(&self, name: &'static str, description: &'static str);
+}
+
+/// Type of the hooks registered with `set_pass_hooks`.
+pub type BoxedPassHooks = Box<dyn PassHooks>;
+
+/// The accumulated time spent in a pass, as returned by `PassTimes::passes()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassTiming {
+    /// The snake_case name of the pass.
+    pub name: &'static str,
+    /// The plain text description of the pass.
+    pub description: &'static str,
+    /// Total time spent running the pass, including its child passes.
+    pub total: Duration,
+    /// Time spent running child passes.
+    pub child: Duration,
+}
+
+impl PassTiming {
+    /// Get the time spent in the pass itself, excluding its child passes.
+    pub fn self_time(&self) -> Duration {
+        self.total.checked_sub(self.child).unwrap_or_default()
+    }
+}
+
+/// The number of instructions in the function before and after a compilation pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassInstCount {
+    /// The snake_case name of the pass, like the one of its timing.
+    pub name: &'static str,
+    /// Number of instructions before the pass.
+    pub before: usize,
+    /// Number of instructions after the pass.
+    pub after: usize,
+}
+
+/// Statistics about the compilation of a function, returned by `Context::compile_with_stats()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompileStats {
+    /// Number of instructions in the function before compiling it.
+    pub input_insts: usize,
+    /// The instruction counts around each pass that ran, in order.
+    pub inst_counts: Vec<PassInstCount>,
+    /// The time spent in the passes that ran. This is empty without the `std` feature.
+    pub timings: Vec<PassTiming>,
+    /// Number of `spill` and `regspill` instructions inserted by the register allocator.
+    pub spills: usize,
+    /// Number of `fill` and `regfill` instructions inserted by the register allocator.
+    pub fills: usize,
+    /// Number of spill slots.
+    pub spill_slots: usize,
+    /// Size of the machine code, excluding jump tables and read-only data.
+    pub code_size: CodeOffset,
+    /// Total size of the function, including jump tables and read-only data.
+    pub total_size: CodeOffset,
+}
+
+#[cfg(feature = "stats-json")]
+impl CompileStats {
+    /// Serialize the statistics as a JSON object.
+    ///
+    /// Passes are identified by their snake_case names, and durations are in nanoseconds.
+    pub fn to_json(&self) -> String {
+        use core::fmt::Write;
+        let mut s = String::new();
+        let _ = write!(
+            s,
+            "{{\"input_insts\":{},\"inst_counts\":[",
+            self.input_insts
+        );
+        for (i, count) in self.inst_counts.iter().enumerate() {
+            let _ = write!(
+                s,
+                "{}{{\"name\":\"{}\",\"before\":{},\"after\":{}}}",
+                if i > 0 { "," } else { "" },
+                count.name,
+                count.before,
+                count.after
+            );
+        }
+        s.push_str("],\"timings\":[");
+        for (i, timing) in self.timings.iter().enumerate() {
+            let _ = write!(
+                s,
+                "{}{{\"name\":\"{}\",\"total_ns\":{},\"self_ns\":{}}}",
+                if i > 0 { "," } else { "" },
+                timing.name,
+                timing.total.as_nanos(),
+                timing.self_time().as_nanos()
+            );
         }
+        let _ = write!(
+            s,
+            "],\"spills\":{},\"fills\":{},\"spill_slots\":{},\"code_size\":{},\"total_size\":{}}}",
+            self.spills, self.fills, self.spill_slots, self.code_size, self.total_size
+        );
+        s
     }
 }
 
@@ -98,15 +247,17 @@ impl fmt::Display for Pass {
 ///
 /// This whole module can be gated on a `cfg` feature to provide a dummy implementation for
 /// performance-sensitive builds or restricted environments. The dummy implementation must provide
-/// `TimingToken` and `PassTimes` types and `take_current`, `add_to_current`, and `start_pass` funcs
+/// `TimingToken` and `PassTimes` types, a `PassTimes::passes` method, and `take_current`,
+/// `add_to_current`, `set_pass_hooks`, and `start_pass` funcs
 #[cfg(feature = "std")]
 mod details {
-    use super::{Pass, DESCRIPTIONS, NUM_PASSES};
+    use super::{BoxedPassHooks, Pass, PassTiming, DESCRIPTIONS, NAMES, NUM_PASSES};
     use log::debug;
     use std::cell::{Cell, RefCell};
     use std::fmt;
     use std::mem;
     use std::time::{Duration, Instant};
+    use std::vec::Vec;
 
     /// A timing token is responsible for timing the currently running pass. Timing starts when it
     /// is created and ends when it is dropped.
@@ -147,17 +298,29 @@ mod details {
         }
     }
 
+    impl PassTimes {
+        /// Get the timings of the passes that have run, in the order the passes are defined.
+        pub fn passes(&self) -> Vec<PassTiming> {
+            self.pass
+                .iter()
+                .zip(NAMES.iter().zip(&DESCRIPTIONS[..]))
+                .filter(|(time, _)| time.total != Duration::default())
+                .map(|(time, (&name, &description))| PassTiming {
+                    name,
+                    description,
+                    total: time.total,
+                    child: time.child,
+                })
+                .collect()
+        }
+    }
+
     impl fmt::Display for PassTimes {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             writeln!(f, "======== ========  ==================================")?;
             writeln!(f, "   Total     Self  Pass")?;
             writeln!(f, "-------- --------  ----------------------------------")?;
-            for (time, desc) in self.pass.iter().zip(&DESCRIPTIONS[..]) {
-                // Omit passes that haven't run.
-                if time.total == Duration::default() {
-                    continue;
-                }
-
+            for pass in self.passes() {
                 // Write a duration as secs.millis, trailing space.
                 fn fmtdur(mut dur: Duration, f: &mut fmt::Formatter) -> fmt::Result {
                     // Round to nearest ms by adding 500us.
@@ -166,11 +329,11 @@ mod details {
                     write!(f, "{:4}.{:03} ", dur.as_secs(), ms)
                 }
 
-                fmtdur(time.total, f)?;
-                if let Some(s) = time.total.checked_sub(time.child) {
+                fmtdur(pass.total, f)?;
+                if let Some(s) = pass.total.checked_sub(pass.child) {
                     fmtdur(s, f)?;
                 }
-                writeln!(f, " {}", desc)?;
+                writeln!(f, " {}", pass.description)?;
             }
             writeln!(f, "======== ========  ==================================")
         }
@@ -180,6 +343,15 @@ mod details {
     thread_local! {
         static CURRENT_PASS: Cell<Pass> = Cell::new(Pass::None);
         static PASS_TIME: RefCell<PassTimes> = RefCell::new(Default::default());
+        static PASS_HOOKS: RefCell<Option<BoxedPassHooks>> = RefCell::new(None);
+    }
+
+    /// Register `hooks` to be invoked when passes begin and end on the current thread, and return
+    /// the previously registered hooks.
+    ///
+    /// The hooks are not allowed to call this function themselves.
+    pub fn set_pass_hooks(hooks: Option<BoxedPassHooks>) -> Option<BoxedPassHooks> {
+        PASS_HOOKS.with(|rc| mem::replace(&mut *rc.borrow_mut(), hooks))
     }
 
     /// Start timing `pass` as a child of the currently running pass, if any.
@@ -188,6 +360,11 @@ mod details {
     pub(super) fn start_pass(pass: Pass) -> TimingToken {
         let prev = CURRENT_PASS.with(|p| p.replace(pass));
         debug!("timing: Starting {}, (during {})", pass, prev);
+        PASS_HOOKS.with(|rc| {
+            if let Some(ref hooks) = *rc.borrow() {
+                hooks.begin(pass.name(), pass.description());
+            }
+        });
         TimingToken {
             start: Instant::now(),
             pass,
@@ -200,6 +377,11 @@ mod details {
         fn drop(&mut self) {
             let duration = self.start.elapsed();
             debug!("timing: Ending {}", self.pass);
+            PASS_HOOKS.with(|rc| {
+                if let Some(ref hooks) = *rc.borrow() {
+                    hooks.end(self.pass.name(), self.pass.description());
+                }
+            });
             let old_cur = CURRENT_PASS.with(|p| p.replace(self.prev));
             debug_assert_eq!(self.pass, old_cur, "Timing tokens dropped out of order");
             PASS_TIME.with(|rc| {
@@ -231,17 +413,29 @@ mod details {
 /// Dummy `debug` implementation
 #[cfg(not(feature = "std"))]
 mod details {
-    use super::Pass;
+    use super::{BoxedPassHooks, Pass, PassTiming};
+    use std::vec::Vec;
     /// Dummy `TimingToken`
     pub struct TimingToken;
     /// Dummy `PassTimes`
     pub struct PassTimes;
+    impl PassTimes {
+        /// Returns no timings
+        pub fn passes(&self) -> Vec<PassTiming> {
+            Vec::new()
+        }
+    }
     /// Returns dummy `PassTimes`
     pub fn take_current() -> PassTimes {
         PassTimes
     }
     /// does nothing
-    pub fn add_to_current(_times: PassTimes) {}
+    pub fn add_to_current(_times: &PassTimes) {}
+
+    /// Drops the hooks, they are never invoked
+    pub fn set_pass_hooks(_hooks: Option<BoxedPassHooks>) -> Option<BoxedPassHooks> {
+        None
+    }
 
     /// does nothing
     pub(super) fn start_pass(_pass: Pass) -> TimingToken {
@@ -252,11 +446,104 @@ mod details {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::string::ToString;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
 
     #[test]
     fn display() {
         assert_eq!(Pass::None.to_string(), "<no pass>");
         assert_eq!(Pass::regalloc.to_string(), "Register allocation");
+        assert_eq!(Pass::regalloc.name(), "regalloc");
+        assert_eq!(Pass::None.name(), "none");
+    }
+
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl PassHooks for Recorder {
+        fn begin(&self, name: &'static str, _description: &'static str) {
+            self.0.borrow_mut().push(format!("begin {}", name));
+        }
+
+        fn end(&self, name: &'static str, _description: &'static str) {
+            self.0.borrow_mut().push(format!("end {}", name));
+        }
+    }
+
+    #[test]
+    fn hooks() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let prev = set_pass_hooks(Some(Box::new(Recorder(events.clone()))));
+        assert!(prev.is_none());
+
+        {
+            let _compile = compile();
+            let _regalloc = regalloc();
+        }
+        assert!(set_pass_hooks(None).is_some());
+        let _ignored = dce();
+
+        assert_eq!(
+            *events.borrow(),
+            [
+                "begin compile",
+                "begin regalloc",
+                "end regalloc",
+                "end compile"
+            ]
+        );
+    }
+
+    #[cfg(feature = "x86")]
+    #[test]
+    fn compile_stats() {
+        use crate::test_utils;
+        use crate::Context;
+
+        let isa = test_utils::isa("x86_64");
+        let mut ctx = Context::for_function(test_utils::double());
+        let (info, stats) = ctx.compile_with_stats(&*isa).unwrap();
+        assert_eq!(stats.input_insts, 2);
+        assert_eq!(stats.inst_counts[0].before, 2);
+        assert!(stats
+            .inst_counts
+            .windows(2)
+            .all(|w| w[0].after == w[1].before));
+        assert!(stats.inst_counts.iter().any(|c| c.name == "regalloc"));
+        assert!(stats.timings.iter().any(|t| t.name == "regalloc"));
+        assert_eq!(stats.spills, 0);
+        assert_eq!(stats.code_size, info.code_size);
+        assert_eq!(stats.total_size, info.total_size);
+    }
+
+    #[cfg(feature = "stats-json")]
+    #[test]
+    fn stats_json() {
+        let stats = CompileStats {
+            input_insts: 2,
+            inst_counts: vec![PassInstCount {
+                name: "legalize",
+                before: 2,
+                after: 3,
+            }],
+            timings: vec![PassTiming {
+                name: "compile",
+                description: "Compilation passes",
+                total: Duration::from_nanos(30),
+                child: Duration::from_nanos(20),
+            }],
+            spills: 1,
+            fills: 2,
+            spill_slots: 1,
+            code_size: 16,
+            total_size: 24,
+        };
+        assert_eq!(
+            stats.to_json(),
+            "{\"input_insts\":2,\"inst_counts\":[{\"name\":\"legalize\",\"before\":2,\"after\":3}],\
+             \"timings\":[{\"name\":\"compile\",\"total_ns\":30,\"self_ns\":10}],\
+             \"spills\":1,\"fills\":2,\"spill_slots\":1,\"code_size\":16,\"total_size\":24}"
+        );
     }
 }
@@ -78,6 +78,11 @@ where
 
 /// Builds ranges and location for specified value labels.
 /// The labels specified at DataFlowGraph's values_labels collection.
+///
+/// Values created by the register allocator to split a labeled value's live range are recorded as
+/// aliases with a default source location, see `DataFlowGraph::copy_value_labels`. The labels
+/// follow the value into these copies so a variable's location is tracked across spills, fills
+/// and register moves.
 pub fn build_value_labels_ranges<T>(
     func: &Function,
     regalloc: &Context,
@@ -119,6 +124,30 @@ where
         for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
             divert.apply(&func.dfg[inst]);
             end_offset = offset + size;
+
+            // Follow tracked values into the copies, spills and fills inserted by the register
+            // allocator. This happens before killed values are removed since the copy is often
+            // the last use of the original value.
+            if let Some(labels) = func.dfg.values_labels.as_ref() {
+                for &result in func.dfg.inst_results(inst) {
+                    let original = match labels.get(&result) {
+                        Some(ValueLabelAssignments::Alias { from, value }) if from.is_default() => {
+                            *value
+                        }
+                        _ => continue,
+                    };
+                    let loc = divert.get(result, values_locations);
+                    let copied = tracked_values
+                        .iter()
+                        .filter(|(v, ..)| *v == original)
+                        .map(|(_, label, ..)| *label)
+                        .collect::<Vec<_>>();
+                    for label in copied {
+                        tracked_values.push((result, label, end_offset, loc));
+                    }
+                }
+            }
+
             // Remove killed values.
             tracked_values.retain(|(x, label, start_offset, last_loc)| {
                 let range = liveness_ranges.get(*x);
@@ -7,7 +7,7 @@ use crate::ir::{ExpandedProgramPoint, Function};
 use crate::regalloc::liveness::Liveness;
 use crate::regalloc::virtregs::VirtRegs;
 use crate::timing;
-use crate::verifier::{VerifierErrors, VerifierStepResult};
+use crate::verifier::{categorized, VerifierErrorKind, VerifierErrors, VerifierStepResult};
 
 /// Verify conventional SSA form for `func`.
 ///
@@ -44,9 +44,10 @@ pub fn verify_cssa(
         liveness,
         preorder,
     };
-    verifier.check_virtregs(errors)?;
-    verifier.check_cssa(errors)?;
-    Ok(())
+    categorized(VerifierErrorKind::Ssa, errors, |errors| {
+        verifier.check_virtregs(errors)?;
+        verifier.check_cssa(errors)
+    })
 }
 
 struct CssaVerifier<'a> {
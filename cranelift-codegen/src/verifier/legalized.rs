@@ -0,0 +1,197 @@
+//! Verify the invariants established by the legalizer.
+
+use crate::dominator_tree::DominatorTree;
+use crate::entity::EntitySet;
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::entities::AnyEntity;
+use crate::ir::{ArgumentPurpose, Function, Inst, Opcode, Signature, Value};
+use crate::isa::TargetIsa;
+use crate::timing;
+use crate::verifier::{categorized, VerifierErrorKind, VerifierErrors, VerifierStepResult};
+
+/// Verify that `func` has been legalized for `isa`.
+///
+/// After legalization:
+///
+/// - Every reachable instruction must have an encoding, except ghost instructions.
+/// - No `isplit` or `iconcat` instructions with used results can remain. Legalization can leave
+///   dead ones behind, which are not encoded.
+/// - The function signature and all signature references must be ABI-legalized, so every
+///   argument and return value has an assigned location.
+/// - Return instructions must pass the `link`, `sret`, and `vmctx` parameters of the function
+///   back as the corresponding special-purpose return values.
+///
+/// The encodings themselves are checked against the ISA by `verify_function`.
+pub fn verify_legalized(
+    isa: &dyn TargetIsa,
+    func: &Function,
+    errors: &mut VerifierErrors,
+) -> VerifierStepResult<()> {
+    let _tt = timing::verify_legalized();
+    let cfg = ControlFlowGraph::with_function(func);
+    let domtree = DominatorTree::with_function(func, &cfg);
+    let verifier = LegalizedVerifier {
+        isa,
+        func,
+        used: used_values(func),
+    };
+    categorized(VerifierErrorKind::Encoding, errors, |errors| {
+        verifier.check_signature(&func.signature, AnyEntity::Function, errors);
+        for (sig_ref, sig) in func.dfg.signatures.iter() {
+            verifier.check_signature(sig, sig_ref.into(), errors);
+        }
+        for ebb in func.layout.ebbs() {
+            if !domtree.is_reachable(ebb) {
+                continue;
+            }
+            for inst in func.layout.ebb_insts(ebb) {
+                verifier.check_inst(inst, errors);
+            }
+        }
+        errors.as_result()
+    })
+}
+
+/// Get the set of values used as instruction arguments in `func`, with aliases resolved.
+fn used_values(func: &Function) -> EntitySet<Value> {
+    let mut used = EntitySet::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                used.insert(func.dfg.resolve_aliases(arg));
+            }
+        }
+    }
+    used
+}
+
+struct LegalizedVerifier<'a> {
+    isa: &'a dyn TargetIsa,
+    func: &'a Function,
+    used: EntitySet<Value>,
+}
+
+impl<'a> LegalizedVerifier<'a> {
+    /// Check that all arguments and return values in `sig` have been assigned a location.
+    fn check_signature(&self, sig: &Signature, loc: AnyEntity, errors: &mut VerifierErrors) {
+        for (kind, params) in &[("argument", &sig.params), ("return value", &sig.returns)] {
+            for (num, param) in params.iter().enumerate() {
+                if !param.location.is_assigned() {
+                    report!(
+                        errors,
+                        loc,
+                        "{} {} ({}) has no location in the legalized signature",
+                        kind,
+                        num,
+                        param
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_inst(&self, inst: Inst, errors: &mut VerifierErrors) {
+        let opcode = self.func.dfg[inst].opcode();
+        match opcode {
+            Opcode::Isplit | Opcode::Iconcat => {
+                let results = self.func.dfg.inst_results(inst);
+                if results.iter().any(|&result| self.used.contains(result)) {
+                    report!(errors, inst, "{} must be removed by legalization", opcode);
+                }
+                return;
+            }
+            // A `nop` has no encoding, and is removed by dead code elimination.
+            Opcode::Fallthrough | Opcode::FallthroughReturn | Opcode::Nop => {}
+            _ if opcode.is_ghost() => {}
+            _ => {
+                if !self.func.encodings[inst].is_legal() {
+                    report!(
+                        errors,
+                        inst,
+                        "{} has no encoding for {} after legalization",
+                        self.func.dfg.display_inst(inst, self.isa),
+                        self.isa.name()
+                    );
+                }
+            }
+        }
+
+        if opcode.is_return() {
+            self.check_special_returns(inst, errors);
+        }
+    }
+
+    /// Check that the special-purpose return values of `inst` are the matching parameters of the
+    /// function.
+    fn check_special_returns(&self, inst: Inst, errors: &mut VerifierErrors) {
+        let returns = &self.func.signature.returns;
+        let args = self.func.dfg.inst_variable_args(inst);
+        if args.len() != returns.len() {
+            // The type checker reports this.
+            return;
+        }
+        let entry_params = match self.func.layout.entry_block() {
+            Some(entry) => self.func.dfg.ebb_params(entry),
+            None => return,
+        };
+
+        for (&arg, ret) in args.iter().zip(returns) {
+            match ret.purpose {
+                ArgumentPurpose::Link
+                | ArgumentPurpose::StructReturn
+                | ArgumentPurpose::VMContext => {}
+                _ => continue,
+            }
+            let param = self
+                .func
+                .signature
+                .special_param_index(ret.purpose)
+                .and_then(|idx| entry_params.get(idx));
+            match param {
+                Some(&param) if param == arg => {}
+                Some(&param) => report!(
+                    errors,
+                    inst,
+                    "{} return value {} must be the {} parameter {}",
+                    ret.purpose,
+                    arg,
+                    ret.purpose,
+                    param
+                ),
+                None => report!(
+                    errors,
+                    inst,
+                    "{} return value {} has no matching parameter",
+                    ret.purpose,
+                    arg
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "x86"))]
+mod tests {
+    use super::verify_legalized;
+    use crate::test_utils;
+    use crate::verifier::VerifierErrors;
+    use crate::Context;
+
+    #[test]
+    fn legalized() {
+        let isa = test_utils::isa("x86_64");
+        let func = test_utils::double();
+
+        // Neither the signature nor the instructions have been legalized yet.
+        let mut errors = VerifierErrors::default();
+        assert!(verify_legalized(&*isa, &func, &mut errors).is_err());
+        assert_eq!(errors.0.len(), 4);
+
+        let mut ctx = Context::for_function(func);
+        ctx.compute_cfg();
+        ctx.legalize(&*isa).unwrap();
+        let mut errors = VerifierErrors::default();
+        assert!(verify_legalized(&*isa, &ctx.func, &mut errors).is_ok());
+        assert!(errors.is_empty());
+    }
+}
@@ -7,7 +7,7 @@ use crate::isa::TargetIsa;
 use crate::regalloc::liveness::Liveness;
 use crate::regalloc::liverange::LiveRange;
 use crate::timing;
-use crate::verifier::{VerifierErrors, VerifierStepResult};
+use crate::verifier::{categorized, VerifierErrorKind, VerifierErrors, VerifierStepResult};
 use core::cmp::Ordering;
 
 /// Verify liveness information for `func`.
@@ -36,9 +36,10 @@ pub fn verify_liveness(
         cfg,
         liveness,
     };
-    verifier.check_ebbs(errors)?;
-    verifier.check_insts(errors)?;
-    Ok(())
+    categorized(VerifierErrorKind::Ssa, errors, |errors| {
+        verifier.check_ebbs(errors)?;
+        verifier.check_insts(errors)
+    })
 }
 
 struct LivenessVerifier<'a> {
@@ -5,7 +5,7 @@ use crate::isa;
 use crate::regalloc::liveness::Liveness;
 use crate::regalloc::RegDiversions;
 use crate::timing;
-use crate::verifier::{VerifierErrors, VerifierStepResult};
+use crate::verifier::{categorized, VerifierErrorKind, VerifierErrors, VerifierStepResult};
 
 /// Verify value locations for `func`.
 ///
@@ -32,8 +32,9 @@ pub fn verify_locations(
         encinfo: isa.encoding_info(),
         liveness,
     };
-    verifier.check_constraints(errors)?;
-    Ok(())
+    categorized(VerifierErrorKind::Encoding, errors, |errors| {
+        verifier.check_constraints(errors)
+    })
 }
 
 struct LocationVerifier<'a> {
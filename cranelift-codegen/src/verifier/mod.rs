@@ -46,6 +46,10 @@
 //! - Detect cycles in global values.
 //! - Detect use of 'vmctx' global value when no corresponding parameter is defined.
 //!
+//! Every error is filed under a `VerifierErrorKind` according to the group of checks above that
+//! reported it. `verify_context_all` keeps checking the remaining instructions after an
+//! instruction fails a check, so all problems in a function can be reported at once.
+//!
 //! TODO:
 //! Ad hoc checking
 //!
@@ -66,11 +70,12 @@ use crate::ir::entities::AnyEntity;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionFormat, ResolvedConstraint};
 use crate::ir::{
     types, ArgumentLoc, Ebb, FuncRef, Function, GlobalValue, Inst, InstructionData, JumpTable,
-    Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList, ValueLoc,
+    Opcode, SigRef, SourceLoc, StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList,
+    ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::iterators::IteratorExtras;
-use crate::settings::FlagsOrIsa;
+use crate::settings::{FlagsOrIsa, TlsModel};
 use crate::timing;
 use core::cmp::Ordering;
 use core::fmt::{self, Display, Formatter, Write};
@@ -80,6 +85,7 @@ use std::string::String;
 use std::vec::Vec;
 
 pub use self::cssa::verify_cssa;
+pub use self::legalized::verify_legalized;
 pub use self::liveness::verify_liveness;
 pub use self::locations::verify_locations;
 
@@ -93,6 +99,7 @@ macro_rules! report {
     ( $errors: expr, $loc: expr, $msg: tt ) => {
         $errors.0.push(crate::verifier::VerifierError {
             location: $loc.into(),
+            kind: crate::verifier::VerifierErrorKind::Structure,
             message: String::from($msg),
         })
     };
@@ -100,6 +107,7 @@ macro_rules! report {
     ( $errors: expr, $loc: expr, $fmt: tt, $( $arg: expr ),+ ) => {
         $errors.0.push(crate::verifier::VerifierError {
             location: $loc.into(),
+            kind: crate::verifier::VerifierErrorKind::Structure,
             message: format!( $fmt, $( $arg ),+ ),
         })
     };
@@ -123,18 +131,83 @@ macro_rules! nonfatal {
 
 mod cssa;
 mod flags;
+mod legalized;
 mod liveness;
 mod locations;
 
+/// The group of checks that reported a verifier error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VerifierErrorKind {
+    /// EBB and instruction integrity, entity references, and other well-formedness checks.
+    Structure,
+    /// SSA form: values must be defined before use, and live ranges must be consistent.
+    Ssa,
+    /// Type checking of instruction operands, results, and immediates.
+    Typing,
+    /// Consistency of the control flow graph and dominator tree with the function.
+    Cfg,
+    /// Instruction encodings and value locations.
+    Encoding,
+}
+
+impl Display for VerifierErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            VerifierErrorKind::Structure => "structure",
+            VerifierErrorKind::Ssa => "ssa",
+            VerifierErrorKind::Typing => "typing",
+            VerifierErrorKind::Cfg => "cfg",
+            VerifierErrorKind::Encoding => "encoding",
+        })
+    }
+}
+
 /// A verifier error.
 #[derive(Fail, Debug, PartialEq, Eq)]
 pub struct VerifierError {
     /// The entity causing the verifier error.
     pub location: AnyEntity,
+    /// The group of checks that reported the error.
+    pub kind: VerifierErrorKind,
     /// The error message.
     pub message: String,
 }
 
+impl VerifierError {
+    /// The value the error is about, if any.
+    pub fn value(&self) -> Option<Value> {
+        match self.location {
+            AnyEntity::Value(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The instruction the error is about, if any.
+    ///
+    /// For errors about a value, this is the instruction defining the value.
+    pub fn inst(&self, func: &Function) -> Option<Inst> {
+        match self.location {
+            AnyEntity::Inst(inst) => Some(inst),
+            AnyEntity::Value(v) if func.dfg.value_is_valid(v) => match func.dfg.value_def(v) {
+                ValueDef::Result(inst, _) => Some(inst),
+                ValueDef::Param(_, _) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The source location of the instruction the error is about.
+    ///
+    /// Returns the default source location if the error is not about an instruction, or if the
+    /// instruction doesn't have a source location.
+    pub fn srcloc(&self, func: &Function) -> SourceLoc {
+        match self.inst(func) {
+            Some(inst) if func.dfg.inst_is_valid(inst) => func.srclocs[inst],
+            _ => SourceLoc::default(),
+        }
+    }
+}
+
 impl Display for VerifierError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "{}: {}", self.location, self.message)
@@ -182,6 +255,11 @@ impl VerifierErrors {
         !self.0.is_empty()
     }
 
+    /// Iterate over the errors of the given kind.
+    pub fn of_kind(&self, kind: VerifierErrorKind) -> impl Iterator<Item = &VerifierError> {
+        self.0.iter().filter(move |err| err.kind == kind)
+    }
+
     /// Return a `VerifierStepResult` that is fatal if at least one error was reported,
     /// and non-fatal otherwise.
     #[inline]
@@ -225,6 +303,23 @@ impl Display for VerifierErrors {
     }
 }
 
+/// Run one verification step, and file all the errors it reports under `kind`.
+fn categorized<F>(
+    kind: VerifierErrorKind,
+    errors: &mut VerifierErrors,
+    step: F,
+) -> VerifierStepResult<()>
+where
+    F: FnOnce(&mut VerifierErrors) -> VerifierStepResult<()>,
+{
+    let first = errors.0.len();
+    let result = step(errors);
+    for err in &mut errors.0[first..] {
+        err.kind = kind;
+    }
+    result
+}
+
 /// Verify `func`.
 pub fn verify_function<'a, FOI: Into<FlagsOrIsa<'a>>>(
     func: &Function,
@@ -253,15 +348,49 @@ pub fn verify_context<'a, FOI: Into<FlagsOrIsa<'a>>>(
 ) -> VerifierStepResult<()> {
     let _tt = timing::verifier();
     let verifier = Verifier::new(func, fisa.into());
-    if cfg.is_valid() {
-        verifier.cfg_integrity(cfg, errors)?;
-    }
-    if domtree.is_valid() {
-        verifier.domtree_integrity(domtree, errors)?;
-    }
+    categorized(VerifierErrorKind::Cfg, errors, |errors| {
+        if cfg.is_valid() {
+            verifier.cfg_integrity(cfg, errors)?;
+        }
+        if domtree.is_valid() {
+            verifier.domtree_integrity(domtree, errors)?;
+        }
+        Ok(())
+    })?;
     verifier.run(errors)
 }
 
+/// Verify `func` like `verify_context`, but report as many errors as possible.
+///
+/// `verify_context` stops at the first fatal error. This function only stops checking an
+/// instruction when it fails a check, and then continues with the next instruction, so the
+/// returned list contains all the problems that can be found in the function. Use
+/// `VerifierError::inst` and `VerifierError::srcloc` to map the errors back to the source.
+pub fn verify_context_all<'a, FOI: Into<FlagsOrIsa<'a>>>(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    fisa: FOI,
+) -> VerifierErrors {
+    let _tt = timing::verifier();
+    let mut errors = VerifierErrors::default();
+    let verifier = Verifier {
+        keep_going: true,
+        ..Verifier::new(func, fisa.into())
+    };
+    let _ = categorized(VerifierErrorKind::Cfg, &mut errors, |errors| {
+        if cfg.is_valid() {
+            let _ = verifier.cfg_integrity(cfg, errors);
+        }
+        if domtree.is_valid() {
+            verifier.domtree_integrity(domtree, errors)?;
+        }
+        Ok(())
+    });
+    let _ = verifier.run(&mut errors);
+    errors
+}
+
 struct Verifier<'a> {
     func: &'a Function,
     expected_cfg: ControlFlowGraph,
@@ -269,6 +398,8 @@ struct Verifier<'a> {
     isa: Option<&'a dyn TargetIsa>,
     // To be removed when #796 is completed.
     verify_encodable_as_bb: bool,
+    /// Continue with the next instruction after a fatal error.
+    keep_going: bool,
 }
 
 impl<'a> Verifier<'a> {
@@ -281,12 +412,14 @@ impl<'a> Verifier<'a> {
             expected_domtree,
             isa: fisa.isa,
             verify_encodable_as_bb: std::env::var("CRANELIFT_BB").is_ok(),
+            keep_going: false,
         }
     }
 
     // Check for:
     //  - cycles in the global value declarations.
     //  - use of 'vmctx' when no special parameter declares it.
+    //  - use of thread-local global values without a TLS model.
     fn verify_global_values(&self, errors: &mut VerifierErrors) -> VerifierStepResult<()> {
         let mut cycle_seen = false;
         let mut seen = SparseSet::new();
@@ -296,28 +429,34 @@ impl<'a> Verifier<'a> {
             seen.insert(gv);
 
             let mut cur = gv;
-            loop {
-                match self.func.global_values[cur] {
-                    ir::GlobalValueData::Load { base, .. }
-                    | ir::GlobalValueData::IAddImm { base, .. } => {
-                        if seen.insert(base).is_some() {
-                            if !cycle_seen {
-                                report!(
-                                    errors,
-                                    gv,
-                                    "global value cycle: {}",
-                                    DisplayList(seen.as_slice())
-                                );
-                                // ensures we don't report the cycle multiple times
-                                cycle_seen = true;
-                            }
-                            continue 'gvs;
-                        }
-
-                        cur = base;
+            while let Some(base) = self.func.global_values[cur].base() {
+                if seen.insert(base).is_some() {
+                    if !cycle_seen {
+                        report!(
+                            errors,
+                            gv,
+                            "global value cycle: {}",
+                            DisplayList(seen.as_slice())
+                        );
+                        // ensures we don't report the cycle multiple times
+                        cycle_seen = true;
                     }
-                    _ => break,
+                    continue 'gvs;
                 }
+
+                cur = base;
+            }
+
+            // `seen` holds `gv` and every global value it is computed from.
+            if seen.len() > ir::MAX_GLOBAL_VALUE_DEPTH + 1 {
+                report!(
+                    errors,
+                    gv,
+                    "{} is computed from {} global values, the limit is {}",
+                    gv,
+                    seen.len() - 1,
+                    ir::MAX_GLOBAL_VALUE_DEPTH
+                );
             }
 
             match self.func.global_values[gv] {
@@ -369,6 +508,25 @@ impl<'a> Verifier<'a> {
                         }
                     }
                 }
+                ir::GlobalValueData::TlsValue { .. } => {
+                    if let Some(isa) = self.isa {
+                        if isa.flags().tls_model() == TlsModel::None {
+                            report!(
+                                errors,
+                                gv,
+                                "thread-local global value {} requires a tls_model setting",
+                                gv
+                            );
+                        } else if isa.pointer_bits() != 64 {
+                            report!(
+                                errors,
+                                gv,
+                                "thread-local global value {} is only supported on 64-bit targets",
+                                gv
+                            );
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -402,6 +560,19 @@ impl<'a> Verifier<'a> {
                         return nonfatal!(errors, heap, "invalid bound global value {}", bound_gv);
                     }
 
+                    // A dynamic heap moves when it is resized. Loading its base from readonly
+                    // memory would allow the load to be shared across calls that resize it.
+                    if let ir::GlobalValueData::Load { readonly: true, .. } =
+                        self.func.global_values[base]
+                    {
+                        report!(
+                            errors,
+                            heap,
+                            "dynamic heap base {} is loaded from readonly memory",
+                            base
+                        );
+                    }
+
                     let index_type = heap_data.index_type;
                     let bound_type = self.func.global_values[bound_gv].global_type(isa);
                     if index_type != bound_type {
@@ -414,6 +585,25 @@ impl<'a> Verifier<'a> {
                         );
                     }
                 }
+
+                if let ir::HeapViolation::CustomLibcall(callee) = heap_data.violation {
+                    if !self.func.dfg.ext_funcs.is_valid(callee) {
+                        return nonfatal!(errors, heap, "invalid violation libcall {}", callee);
+                    }
+
+                    // The libcall provides the address to access instead of the heap address.
+                    let sig_ref = self.func.dfg.ext_funcs[callee].signature;
+                    let returns = &self.func.dfg.signatures[sig_ref].returns;
+                    if returns.len() != 1 || returns[0].value_type != pointer_type {
+                        report!(
+                            errors,
+                            heap,
+                            "violation libcall {} must return a single {}",
+                            callee,
+                            pointer_type
+                        );
+                    }
+                }
             }
         }
 
@@ -614,7 +804,9 @@ impl<'a> Verifier<'a> {
         use crate::ir::instructions::InstructionData::*;
 
         for &arg in self.func.dfg.inst_args(inst) {
-            self.verify_inst_arg(inst, arg, errors)?;
+            categorized(VerifierErrorKind::Ssa, errors, |errors| {
+                self.verify_inst_arg(inst, arg, errors)
+            })?;
 
             // All used values must be attached to something.
             let original = self.func.dfg.resolve_aliases(arg);
@@ -700,7 +892,7 @@ impl<'a> Verifier<'a> {
             HeapAddr { heap, .. } => {
                 self.verify_heap(inst, heap, errors)?;
             }
-            TableAddr { table, .. } => {
+            TableAddr { table, .. } | TableGrow { table, .. } | TableFill { table, .. } => {
                 self.verify_table(inst, table, errors)?;
             }
             RegSpill { dst, .. } => {
@@ -1092,6 +1284,19 @@ impl<'a> Verifier<'a> {
                         arg_type
                     );
                 }
+                match expected_types[i].purpose {
+                    ir::ArgumentPurpose::VectorRegCount | ir::ArgumentPurpose::FloatArgCopy => {
+                        report!(
+                            errors,
+                            ebb,
+                            "entry block parameter {} is a {} parameter, which is only passed \
+                             to variadic calls",
+                            i,
+                            expected_types[i].purpose
+                        );
+                    }
+                    _ => {}
+                }
             }
         }
 
@@ -1502,6 +1707,63 @@ impl<'a> Verifier<'a> {
                     );
                 }
             }
+            ir::InstructionData::TableGrow { opcode, table, .. }
+            | ir::InstructionData::TableFill { opcode, table, .. } => {
+                let table_data = &self.func.tables[table];
+                if ctrl_type != table_data.index_type {
+                    return nonfatal!(
+                        errors,
+                        inst,
+                        "index type {} differs from table index type {}",
+                        ctrl_type,
+                        table_data.index_type
+                    );
+                }
+
+                // The element value and count of `table_fill` are in its untyped value list.
+                if opcode == Opcode::TableFill {
+                    let args = self.func.dfg.inst_args(inst);
+                    if args.len() != 3 {
+                        return nonfatal!(errors, inst, "expected 3 arguments, got {}", args.len());
+                    }
+                    let elem_type = self.func.dfg.value_type(args[1]);
+                    if elem_type != types::I32 && elem_type != types::I64 {
+                        return nonfatal!(
+                            errors,
+                            inst,
+                            "table element type {} is not i32 or i64",
+                            elem_type
+                        );
+                    }
+                    let count_type = self.func.dfg.value_type(args[2]);
+                    if count_type != table_data.index_type {
+                        return nonfatal!(
+                            errors,
+                            inst,
+                            "element count type {} differs from table index type {}",
+                            count_type,
+                            table_data.index_type
+                        );
+                    }
+                }
+
+                // Growing the table changes its bound and can move it.
+                if opcode == Opcode::TableGrow {
+                    for &gv in &[table_data.base_gv, table_data.bound_gv] {
+                        if let ir::GlobalValueData::Load { readonly: true, .. } =
+                            self.func.global_values[gv]
+                        {
+                            return nonfatal!(
+                                errors,
+                                inst,
+                                "grows {}, whose {} is loaded from readonly memory",
+                                table,
+                                gv
+                            );
+                        }
+                    }
+                }
+            }
             ir::InstructionData::UnaryGlobalValue { global_value, .. } => {
                 if let Some(isa) = self.isa {
                     let inst_type = self.func.dfg.value_type(self.func.dfg.first_result(inst));
@@ -1763,8 +2025,12 @@ impl<'a> Verifier<'a> {
 
         // If this is some sort of a store instruction, get the memflags, else, just return.
         let memflags = match *inst_data {
-            ir::InstructionData::Store { flags, .. }
-            | ir::InstructionData::StoreComplex { flags, .. } => flags,
+            ir::InstructionData::Store { opcode, flags, .. }
+            | ir::InstructionData::StoreComplex { opcode, flags, .. }
+                if opcode.can_store() =>
+            {
+                flags
+            }
             _ => return Ok(()),
         };
 
@@ -1784,32 +2050,74 @@ impl<'a> Verifier<'a> {
         self.verify_heaps(errors)?;
         self.verify_tables(errors)?;
         self.verify_jump_tables(errors)?;
-        self.typecheck_entry_block_params(errors)?;
+        categorized(VerifierErrorKind::Typing, errors, |errors| {
+            self.typecheck_entry_block_params(errors)
+        })?;
 
+        let mut result = Ok(());
         for ebb in self.func.layout.ebbs() {
             for inst in self.func.layout.ebb_insts(ebb) {
-                self.ebb_integrity(ebb, inst, errors)?;
-                self.instruction_integrity(inst, errors)?;
-                self.typecheck(inst, errors)?;
-                self.verify_encoding(inst, errors)?;
-                self.immediate_constraints(inst, errors)?;
+                self.check_inst(ebb, inst, errors)
+                    .or_else(|()| self.fatal_error(&mut result))?;
             }
-            self.encodable_as_bb(ebb, errors)?;
+            self.encodable_as_bb(ebb, errors)
+                .or_else(|()| self.fatal_error(&mut result))?;
         }
+        result?;
 
-        verify_flags(self.func, &self.expected_cfg, self.isa, errors)?;
+        categorized(VerifierErrorKind::Ssa, errors, |errors| {
+            verify_flags(self.func, &self.expected_cfg, self.isa, errors)
+        })
+    }
 
-        Ok(())
+    /// Run all the checks of a single instruction, stopping at the first fatal error.
+    fn check_inst(
+        &self,
+        ebb: Ebb,
+        inst: Inst,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        self.ebb_integrity(ebb, inst, errors)?;
+        self.instruction_integrity(inst, errors)?;
+        categorized(VerifierErrorKind::Typing, errors, |errors| {
+            self.typecheck(inst, errors)
+        })?;
+        categorized(VerifierErrorKind::Encoding, errors, |errors| {
+            self.verify_encoding(inst, errors)
+        })?;
+        categorized(VerifierErrorKind::Typing, errors, |errors| {
+            self.immediate_constraints(inst, errors)
+        })
+    }
+
+    /// Handle a fatal error in the checks of an EBB or an instruction.
+    ///
+    /// Verification stops immediately unless `keep_going` is set, in which case the failure is
+    /// recorded in `result` and the remaining EBBs and instructions are still checked.
+    fn fatal_error(&self, result: &mut VerifierStepResult<()>) -> VerifierStepResult<()> {
+        *result = Err(());
+        if self.keep_going {
+            Ok(())
+        } else {
+            Err(())
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Verifier, VerifierError, VerifierErrors};
+    use super::{
+        verify_context, verify_context_all, Verifier, VerifierError, VerifierErrorKind,
+        VerifierErrors,
+    };
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::dominator_tree::DominatorTree;
     use crate::entity::EntityList;
+    use crate::flowgraph::ControlFlowGraph;
     use crate::ir::instructions::{InstructionData, Opcode};
-    use crate::ir::Function;
+    use crate::ir::{types, Function, InstBuilder, SourceLoc};
     use crate::settings;
+    use std::vec::Vec;
 
     macro_rules! assert_err_with_msg {
         ($e:expr, $msg:expr) => {
@@ -1867,4 +2175,49 @@ mod tests {
 
         assert_err_with_msg!(errors, "instruction format");
     }
+
+    #[test]
+    fn all_errors() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let (use_inst, fadd_inst) = {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v0 = pos.ins().iconst(types::I32, 1);
+            let v2 = pos.ins().iconst(types::I32, 2);
+            // An ill-typed `fadd`, built directly since the instruction builder may check types.
+            let fadd_inst = pos.func.dfg.make_inst(InstructionData::Binary {
+                opcode: Opcode::Fadd,
+                args: [v0, v0],
+            });
+            pos.func.dfg.make_inst_results(fadd_inst, types::I32);
+            pos.insert_inst(fadd_inst);
+            pos.ins().return_(&[]);
+
+            // Use `v2` before it is defined.
+            let v2_inst = pos.func.dfg.value_def(v2).unwrap_inst();
+            pos.goto_inst(v2_inst);
+            pos.set_srcloc(SourceLoc::new(42));
+            let v1 = pos.ins().iadd(v0, v2);
+            (pos.func.dfg.value_def(v1).unwrap_inst(), fadd_inst)
+        };
+        let flags = &settings::Flags::new(settings::builder());
+        let cfg = ControlFlowGraph::new();
+        let domtree = DominatorTree::new();
+
+        // The regular verifier stops at the first fatal error.
+        let mut errors = VerifierErrors::default();
+        assert!(verify_context(&func, &cfg, &domtree, flags, &mut errors).is_err());
+        assert_eq!(errors.0.len(), 1);
+
+        let errors = verify_context_all(&func, &cfg, &domtree, flags);
+        let ssa: Vec<_> = errors.of_kind(VerifierErrorKind::Ssa).collect();
+        assert_eq!(ssa.len(), 1);
+        assert_eq!(ssa[0].inst(&func), Some(use_inst));
+        assert_eq!(ssa[0].srcloc(&func), SourceLoc::new(42));
+        let typing: Vec<_> = errors.of_kind(VerifierErrorKind::Typing).collect();
+        assert!(!typing.is_empty());
+        assert!(typing.iter().all(|err| err.inst(&func) == Some(fadd_inst)));
+        assert_eq!(typing[0].srcloc(&func), SourceLoc::default());
+    }
 }
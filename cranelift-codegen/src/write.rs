@@ -595,8 +595,20 @@ pub fn write_operands(
             offset,
             ..
         } => write!(w, " {}, {}{}", arg, stack_slot, offset),
-        HeapAddr { heap, arg, imm, .. } => write!(w, " {}, {}, {}", heap, arg, imm),
-        TableAddr { table, arg, .. } => write!(w, " {}, {}", table, arg),
+        HeapAddr {
+            flags,
+            heap,
+            arg,
+            imm,
+            ..
+        } => write!(w, "{} {}, {}, {}", flags, heap, arg, imm),
+        TableAddr {
+            flags, table, arg, ..
+        } => write!(w, "{} {}, {}", flags, table, arg),
+        TableGrow { table, args, .. } => write!(w, " {}, {}, {}", table, args[0], args[1]),
+        TableFill {
+            table, ref args, ..
+        } => write!(w, " {}, {}", table, DisplayValues(args.as_slice(pool))),
         Load {
             flags, arg, offset, ..
         } => write!(w, "{} {}{}", flags, arg, offset),
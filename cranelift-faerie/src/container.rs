@@ -25,10 +25,15 @@ pub fn raw_relocation(reloc: Reloc, triple: &Triple) -> (u32, i64) {
                             Reloc::Abs4 => elf::reloc::R_X86_64_32,
                             Reloc::Abs8 => elf::reloc::R_X86_64_64,
                             Reloc::X86PCRel4 | Reloc::X86CallPCRel4 => elf::reloc::R_X86_64_PC32,
+                            Reloc::X86PCRel8 => elf::reloc::R_X86_64_PC64,
                             // TODO: Get Cranelift to tell us when we can use
                             // R_X86_64_GOTPCRELX/R_X86_64_REX_GOTPCRELX.
                             Reloc::X86CallPLTRel4 => elf::reloc::R_X86_64_PLT32,
                             Reloc::X86GOTPCRel4 => elf::reloc::R_X86_64_GOTPCREL,
+                            Reloc::X86GOTOff8 => elf::reloc::R_X86_64_GOTOFF64,
+                            Reloc::ElfX86_64TlsGd => elf::reloc::R_X86_64_TLSGD,
+                            Reloc::ElfX86_64GotTpOff => elf::reloc::R_X86_64_GOTTPOFF,
+                            Reloc::ElfX86_64TpOff32 => elf::reloc::R_X86_64_TPOFF32,
                             _ => unimplemented!(),
                         }
                     }
@@ -54,6 +59,9 @@ pub fn raw_relocation(reloc: Reloc, triple: &Triple) -> (u32, i64) {
                         Reloc::X86GOTPCRel4 => {
                             (u32::from(mach::relocation::X86_64_RELOC_GOT_LOAD), 4)
                         }
+                        Reloc::MachOX86_64Tlv => {
+                            (u32::from(mach::relocation::X86_64_RELOC_TLV), 4)
+                        }
                         _ => unimplemented!("unsupported mach-o reloc: {}", reloc),
                     }
                 }
@@ -38,17 +38,24 @@ mod subtest;
 mod test_binemit;
 mod test_cat;
 mod test_compile;
+mod test_complex_addresses;
 mod test_dce;
 mod test_domtree;
+mod test_heap_bounds;
 mod test_legalizer;
 mod test_licm;
+mod test_peephole;
 mod test_postopt;
 mod test_preopt;
 mod test_print_cfg;
+mod test_prune_ebb_params;
+mod test_redundant_traps;
 mod test_regalloc;
+mod test_remat_constants;
 mod test_shrink;
 mod test_simple_gvn;
 mod test_simple_preopt;
+mod test_simplify_cfg;
 mod test_verifier;
 
 /// The result of running the test in a file.
@@ -115,16 +122,23 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
         "binemit" => test_binemit::subtest(parsed),
         "cat" => test_cat::subtest(parsed),
         "compile" => test_compile::subtest(parsed),
+        "complex_addresses" => test_complex_addresses::subtest(parsed),
         "dce" => test_dce::subtest(parsed),
         "domtree" => test_domtree::subtest(parsed),
+        "heap-bounds" => test_heap_bounds::subtest(parsed),
         "legalizer" => test_legalizer::subtest(parsed),
         "licm" => test_licm::subtest(parsed),
+        "peephole" => test_peephole::subtest(parsed),
         "postopt" => test_postopt::subtest(parsed),
         "simple_preopt" => test_simple_preopt::subtest(parsed),
         "print-cfg" => test_print_cfg::subtest(parsed),
+        "prune-ebb-params" => test_prune_ebb_params::subtest(parsed),
+        "redundant-traps" => test_redundant_traps::subtest(parsed),
         "regalloc" => test_regalloc::subtest(parsed),
+        "remat-constants" => test_remat_constants::subtest(parsed),
         "shrink" => test_shrink::subtest(parsed),
         "simple-gvn" => test_simple_gvn::subtest(parsed),
+        "simplify-cfg" => test_simplify_cfg::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
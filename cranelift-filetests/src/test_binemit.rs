@@ -201,6 +201,9 @@ impl SubTest for TestBinEmit {
         let mut sink = TextSink::new();
         for ebb in func.layout.ebbs() {
             divert.clear();
+            if sink.offset < func.offsets[ebb] {
+                isa.emit_padding(func.offsets[ebb] - sink.offset, &mut sink);
+            }
             // Correct header offsets should have been computed by `relax_branches()`.
             assert_eq!(
                 sink.offset, func.offsets[ebb],
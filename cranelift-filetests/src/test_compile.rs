@@ -54,6 +54,7 @@ impl SubTest for TestCompile {
         binemit::emit_function(
             &comp_ctx.func,
             |func, inst, div, sink| isa.emit_inst(func, inst, div, sink),
+            |size, sink| isa.emit_padding(size, sink),
             &mut sink,
         );
 
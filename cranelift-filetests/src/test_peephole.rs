@@ -0,0 +1,65 @@
+//! Test command for testing the post-regalloc peephole pass.
+//!
+//! The `peephole` test command runs each function through the peephole pass. The input functions
+//! must have register and stack slot assignments for all values. Instructions without an encoding
+//! are given the first legal encoding before running the pass.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use cranelift_codegen;
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::print_errors::pretty_error;
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+
+struct TestPeephole;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "peephole");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestPeephole))
+    }
+}
+
+impl SubTest for TestPeephole {
+    fn name(&self) -> &'static str {
+        "peephole"
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn needs_isa(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let isa = context.isa.expect("peephole needs an ISA");
+        let mut comp_ctx = cranelift_codegen::Context::for_function(func.into_owned());
+
+        let func = &mut comp_ctx.func;
+        func.encodings.resize(func.dfg.num_insts());
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if !func.encodings[inst].is_legal() {
+                    let ctrl_type = func.dfg.ctrl_typevar(inst);
+                    let enc = isa.legal_encodings(func, &func.dfg[inst], ctrl_type).next();
+                    if let Some(enc) = enc {
+                        func.encodings[inst] = enc;
+                    }
+                }
+            }
+        }
+
+        comp_ctx
+            .peephole(isa)
+            .map_err(|e| pretty_error(&comp_ctx.func, context.isa, Into::into(e)))?;
+
+        let text = comp_ctx.func.display(isa).to_string();
+        run_filecheck(&text, context)
+    }
+}
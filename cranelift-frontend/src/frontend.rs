@@ -8,8 +8,8 @@ use cranelift_codegen::ir::function::DisplayFunction;
 use cranelift_codegen::ir::{
     types, AbiParam, DataFlowGraph, Ebb, ExtFuncData, ExternalName, FuncRef, Function, GlobalValue,
     GlobalValueData, Heap, HeapData, Inst, InstBuilder, InstBuilderBase, InstructionData,
-    JumpTable, JumpTableData, LibCall, MemFlags, SigRef, Signature, StackSlot, StackSlotData, Type,
-    Value, ValueLabel, ValueLabelAssignments, ValueLabelStart,
+    JumpTable, JumpTableData, LibCall, Linkage, MemFlags, SigRef, Signature, StackSlot,
+    StackSlotData, Type, Value, ValueLabel, ValueLabelAssignments, ValueLabelStart, Visibility,
 };
 use cranelift_codegen::isa::{TargetFrontendConfig, TargetIsa};
 use cranelift_codegen::packed_option::PackedOption;
@@ -598,6 +598,8 @@ impl<'a> FunctionBuilder<'a> {
             name: ExternalName::LibCall(LibCall::Memcpy),
             signature,
             colocated: false,
+            linkage: Linkage::Import,
+            visibility: Visibility::Default,
         });
 
         self.ins().call(libc_memcpy, &[dest, src, size]);
@@ -677,6 +679,8 @@ impl<'a> FunctionBuilder<'a> {
             name: ExternalName::LibCall(LibCall::Memset),
             signature,
             colocated: false,
+            linkage: Linkage::Import,
+            visibility: Visibility::Default,
         });
 
         let ch = self.ins().uextend(types::I32, ch);
@@ -771,6 +775,8 @@ impl<'a> FunctionBuilder<'a> {
             name: ExternalName::LibCall(LibCall::Memmove),
             signature,
             colocated: false,
+            linkage: Linkage::Import,
+            visibility: Visibility::Default,
         });
 
         self.ins().call(libc_memmove, &[dest, source, size]);
@@ -7,7 +7,7 @@ use std::boxed::Box;
 use std::vec::Vec;
 
 /// This specifies how data is to be initialized.
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Init {
     /// This indicates that no initialization has been specified yet.
     Uninitialized,
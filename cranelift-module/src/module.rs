@@ -6,7 +6,7 @@
 // shared with `DataContext`?
 
 use super::HashMap;
-use crate::data_context::DataContext;
+use crate::data_context::{DataContext, DataDescription, Init};
 use crate::Backend;
 use cranelift_codegen::binemit::{self, CodeInfo};
 use cranelift_codegen::entity::{entity_impl, PrimaryMap};
@@ -92,6 +92,18 @@ impl Linkage {
             Linkage::Local | Linkage::Export => true,
         }
     }
+
+    /// Get the corresponding symbol linkage of the Cranelift IR.
+    ///
+    /// Preemptible definitions are weak symbols.
+    pub fn to_ir(self) -> ir::Linkage {
+        match self {
+            Linkage::Import => ir::Linkage::Import,
+            Linkage::Local => ir::Linkage::Local,
+            Linkage::Preemptible => ir::Linkage::Weak,
+            Linkage::Export => ir::Linkage::Export,
+        }
+    }
 }
 
 /// A declared name may refer to either a function or data declaration
@@ -160,6 +172,8 @@ where
 {
     /// The function declaration.
     pub decl: FunctionDeclaration,
+    /// The declared signature, interned in the module's `SignatureInterner`.
+    pub sig_id: ir::SigId,
     /// The compiled artifact, once it's available.
     pub compiled: Option<B::CompiledFunction>,
 }
@@ -211,6 +225,41 @@ where
     }
 }
 
+/// The contents and relocations of a constant data object, used to find identical constants.
+///
+/// Relocations refer to functions and data objects by their external names, since the
+/// `ir::FuncRef` and `ir::GlobalValue` references are local to each `DataContext`.
+#[derive(PartialEq, Eq, Hash)]
+struct ConstantKey {
+    init: Init,
+    align: Option<u8>,
+    function_relocs: Vec<(binemit::CodeOffset, ir::ExternalName)>,
+    data_relocs: Vec<(binemit::CodeOffset, ir::ExternalName, binemit::Addend)>,
+}
+
+impl ConstantKey {
+    fn new(description: &DataDescription, align: Option<u8>) -> Self {
+        let mut function_relocs: Vec<_> = description
+            .function_relocs
+            .iter()
+            .map(|&(offset, func)| (offset, description.function_decls[func].clone()))
+            .collect();
+        let mut data_relocs: Vec<_> = description
+            .data_relocs
+            .iter()
+            .map(|&(offset, data, addend)| (offset, description.data_decls[data].clone(), addend))
+            .collect();
+        function_relocs.sort_by_key(|&(offset, _)| offset);
+        data_relocs.sort_by_key(|&(offset, _, _)| offset);
+        Self {
+            init: description.init.clone(),
+            align,
+            function_relocs,
+            data_relocs,
+        }
+    }
+}
+
 /// The functions and data objects belonging to a module.
 struct ModuleContents<B>
 where
@@ -324,6 +373,8 @@ where
 {
     names: HashMap<String, FuncOrDataId>,
     contents: ModuleContents<B>,
+    signatures: ir::SignatureInterner,
+    constants: HashMap<ConstantKey, DataId>,
     functions_to_finalize: Vec<FuncId>,
     data_objects_to_finalize: Vec<DataId>,
     backend: B,
@@ -341,6 +392,8 @@ where
                 functions: PrimaryMap::new(),
                 data_objects: PrimaryMap::new(),
             },
+            signatures: ir::SignatureInterner::new(),
+            constants: HashMap::new(),
             functions_to_finalize: Vec::new(),
             data_objects_to_finalize: Vec::new(),
             backend: B::new(backend_builder),
@@ -415,12 +468,14 @@ where
                 }
             },
             Vacant(entry) => {
+                let sig_id = self.signatures.intern(signature);
                 let id = self.contents.functions.push(ModuleFunction {
                     decl: FunctionDeclaration {
                         name: name.to_owned(),
                         linkage,
                         signature: signature.clone(),
                     },
+                    sig_id,
                     compiled: None,
                 });
                 entry.insert(FuncOrDataId::Func(id));
@@ -482,19 +537,42 @@ where
 
     /// Use this when you're building the IR of a function to reference a function.
     ///
-    /// TODO: Coalesce redundant decls and signatures.
+    /// Functions with identical signatures share a single `SigRef` in `in_func`.
+    ///
+    /// TODO: Coalesce redundant decls.
     /// TODO: Look into ways to reduce the risk of using a FuncRef in the wrong function.
     pub fn declare_func_in_func(&self, func: FuncId, in_func: &mut ir::Function) -> ir::FuncRef {
-        let decl = &self.contents.functions[func].decl;
-        let signature = in_func.import_signature(decl.signature.clone());
-        let colocated = decl.linkage.is_final();
+        let info = &self.contents.functions[func];
+        let signature = self.signatures.import(info.sig_id, in_func);
+        let colocated = info.decl.linkage.is_final();
         in_func.import_function(ir::ExtFuncData {
             name: ir::ExternalName::user(0, func.as_u32()),
             signature,
             colocated,
+            linkage: info.decl.linkage.to_ir(),
+            visibility: ir::Visibility::Default,
         })
     }
 
+    /// Use this when you're building the IR of a function to reference a signature, for example
+    /// for an indirect call.
+    ///
+    /// The signature is interned in the module, and shares a single `SigRef` in `func` with the
+    /// identical signatures declared before.
+    pub fn declare_signature_in_func(
+        &mut self,
+        signature: &ir::Signature,
+        func: &mut ir::Function,
+    ) -> ir::SigRef {
+        let sig_id = self.signatures.intern(signature);
+        self.signatures.import(sig_id, func)
+    }
+
+    /// Get the distinct signatures of the functions declared in this module.
+    pub fn signatures(&self) -> &ir::SignatureInterner {
+        &self.signatures
+    }
+
     /// Use this when you're building the IR of a function to reference a data object.
     ///
     /// TODO: Same as above.
@@ -544,6 +622,7 @@ where
         if !info.decl.linkage.is_definable() {
             return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
         }
+        ctx.func.linkage = info.decl.linkage.to_ir();
 
         let compiled = Some(self.backend.define_function(
             &info.decl.name,
@@ -584,6 +663,40 @@ where
         Ok(())
     }
 
+    /// Define a read-only data object with the contents of `data_ctx`, or reuse an identical one.
+    ///
+    /// This is meant for constants that several functions may need, such as string literals and
+    /// lookup tables. A constant with the same contents, alignment and relocations as one defined
+    /// earlier by this method is only emitted once, and its `DataId` is returned again. New
+    /// constants are declared as local, non-writable data objects, which backends place in a
+    /// read-only section.
+    pub fn define_constant(
+        &mut self,
+        data_ctx: &DataContext,
+        align: Option<u8>,
+    ) -> ModuleResult<DataId> {
+        let key = ConstantKey::new(data_ctx.description(), align);
+        if let Some(&id) = self.constants.get(&key) {
+            return Ok(id);
+        }
+
+        // Constants get generated names that don't clash with the declared ones.
+        let mut name;
+        let mut index = self.constants.len();
+        loop {
+            name = format!("__cranelift_constant{}", index);
+            if !self.names.contains_key(&name) {
+                break;
+            }
+            index += 1;
+        }
+
+        let id = self.declare_data(&name, Linkage::Local, false, align)?;
+        self.define_data(id, data_ctx)?;
+        self.constants.insert(key, id);
+        Ok(id)
+    }
+
     /// Write the address of `what` into the data for `data` at `offset`. `data` must refer to a
     /// defined data object.
     pub fn write_data_funcaddr(&mut self, data: DataId, offset: usize, what: ir::FuncRef) {
@@ -65,6 +65,9 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
         if info.has_sse42() {
             isa_builder.enable("has_sse42").unwrap();
         }
+        if info.has_fma() {
+            isa_builder.enable("has_fma").unwrap();
+        }
         if info.has_popcnt() {
             isa_builder.enable("has_popcnt").unwrap();
         }
@@ -2,7 +2,7 @@
 
 use cranelift_codegen::{
     cursor::{Cursor, FuncCursor},
-    ir::{self, dfg::ValueDef, InstBuilder},
+    ir::{self, condcodes::IntCC, dfg::ValueDef, InstBuilder},
 };
 // use rustc_apfloat::{
 //     ieee::{Double, Single},
@@ -17,14 +17,6 @@ enum ConstImm {
 }
 
 impl ConstImm {
-    fn unwrap_i64(self) -> i64 {
-        if let ConstImm::I64(imm) = self {
-            imm
-        } else {
-            panic!("self did not contain an `i64`.")
-        }
-    }
-
     fn evaluate_truthiness(self) -> bool {
         match self {
             ConstImm::Bool(b) => b,
@@ -38,6 +30,8 @@ impl ConstImm {
 
 /// Fold operations on constants.
 ///
+/// This also applies simple algebraic identities like `x + 0 = x` and `x ^ x = 0`.
+///
 /// It's important to note that this will not remove unused constants. It's
 /// assumed that the DCE pass will take care of them.
 pub fn fold_constants(func: &mut ir::Function) {
@@ -48,11 +42,23 @@ pub fn fold_constants(func: &mut ir::Function) {
             use self::ir::InstructionData::*;
             match pos.func.dfg[inst] {
                 Binary { opcode, args } => {
-                    fold_binary(&mut pos.func.dfg, inst, opcode, args);
+                    fold_binary(&mut pos, inst, opcode, args);
+                }
+                BinaryImm { opcode, arg, imm } => {
+                    fold_binary_imm(&mut pos, inst, opcode, arg, imm.into());
                 }
                 Unary { opcode, arg } => {
                     fold_unary(&mut pos.func.dfg, inst, opcode, arg);
                 }
+                IntCompare { cond, args, .. } => {
+                    fold_icmp(&mut pos.func.dfg, inst, cond, args[0], args[1]);
+                }
+                IntCompareImm { cond, arg, imm, .. } => {
+                    if let Some(imm0) = resolve_value_to_imm(&pos.func.dfg, arg) {
+                        let imm1 = ConstImm::I64(imm.into());
+                        fold_icmp_imms(&mut pos.func.dfg, inst, cond, imm0, imm1);
+                    }
+                }
                 Branch {
                     opcode,
                     args: _,
@@ -66,6 +72,31 @@ pub fn fold_constants(func: &mut ir::Function) {
     }
 }
 
+/// Is `ty` a scalar integer type that fits in an `i64`?
+fn is_foldable_int(ty: ir::Type) -> bool {
+    ty.is_int() && !ty.is_vector() && ty.bits() <= 64
+}
+
+/// Sign-extend the low bits of `x` that make up a value of type `ty`.
+fn sext(ty: ir::Type, x: i64) -> i64 {
+    let shift = 64 - u32::from(ty.bits());
+    if shift == 0 {
+        x
+    } else {
+        (x << shift) >> shift
+    }
+}
+
+/// Zero-extend the low bits of `x` that make up a value of type `ty`.
+fn zext(ty: ir::Type, x: i64) -> u64 {
+    let shift = 64 - u32::from(ty.bits());
+    if shift == 0 {
+        x as u64
+    } else {
+        ((x as u64) << shift) >> shift
+    }
+}
+
 fn resolve_value_to_imm(dfg: &ir::DataFlowGraph, value: ir::Value) -> Option<ConstImm> {
     let original = dfg.resolve_aliases(value);
 
@@ -104,70 +135,155 @@ fn resolve_value_to_imm(dfg: &ir::DataFlowGraph, value: ir::Value) -> Option<Con
     }
 }
 
-fn evaluate_binary(opcode: ir::Opcode, imm0: ConstImm, imm1: ConstImm) -> Option<ConstImm> {
-    use core::num::Wrapping;
+fn evaluate_binary(
+    opcode: ir::Opcode,
+    ty: ir::Type,
+    imm0: ConstImm,
+    imm1: ConstImm,
+) -> Option<ConstImm> {
+    use self::ir::Opcode::*;
 
-    match opcode {
-        ir::Opcode::Iadd => {
-            let imm0 = Wrapping(imm0.unwrap_i64());
-            let imm1 = Wrapping(imm1.unwrap_i64());
-            Some(ConstImm::I64((imm0 + imm1).0))
+    match (imm0, imm1) {
+        (ConstImm::I64(imm0), ConstImm::I64(imm1)) => {
+            evaluate_int_binary(opcode, ty, imm0, imm1).map(ConstImm::I64)
         }
-        ir::Opcode::Isub => {
-            let imm0 = Wrapping(imm0.unwrap_i64());
-            let imm1 = Wrapping(imm1.unwrap_i64());
-            Some(ConstImm::I64((imm0 - imm1).0))
+        (ConstImm::Ieee32(imm0), ConstImm::Ieee32(imm1)) => match opcode {
+            Fadd => Some(ConstImm::Ieee32(imm0 + imm1)),
+            Fsub => Some(ConstImm::Ieee32(imm0 - imm1)),
+            Fmul => Some(ConstImm::Ieee32(imm0 * imm1)),
+            Fdiv => Some(ConstImm::Ieee32(imm0 / imm1)),
+            _ => None,
+        },
+        (ConstImm::Ieee64(imm0), ConstImm::Ieee64(imm1)) => match opcode {
+            Fadd => Some(ConstImm::Ieee64(imm0 + imm1)),
+            Fsub => Some(ConstImm::Ieee64(imm0 - imm1)),
+            Fmul => Some(ConstImm::Ieee64(imm0 * imm1)),
+            Fdiv => Some(ConstImm::Ieee64(imm0 / imm1)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Evaluate an integer operation of type `ty`, including the `_imm` forms.
+///
+/// Returns `None` for operations that would trap, so the trap is preserved.
+fn evaluate_int_binary(opcode: ir::Opcode, ty: ir::Type, x: i64, y: i64) -> Option<i64> {
+    use self::ir::Opcode::*;
+
+    if !is_foldable_int(ty) {
+        return None;
+    }
+
+    let bits = u32::from(ty.bits());
+    let (sx, sy) = (sext(ty, x), sext(ty, y));
+    let (ux, uy) = (zext(ty, x), zext(ty, y));
+    // Shift amounts are taken modulo the number of bits in the type.
+    let amt = (y as u32) & (bits - 1);
+
+    let result = match opcode {
+        Iadd | IaddImm => x.wrapping_add(y),
+        Isub => x.wrapping_sub(y),
+        IrsubImm => y.wrapping_sub(x),
+        Imul | ImulImm => x.wrapping_mul(y),
+        Udiv | UdivImm => {
+            if uy == 0 {
+                return None;
+            }
+            (ux / uy) as i64
         }
-        ir::Opcode::Imul => {
-            let imm0 = Wrapping(imm0.unwrap_i64());
-            let imm1 = Wrapping(imm1.unwrap_i64());
-            Some(ConstImm::I64((imm0 * imm1).0))
+        Urem | UremImm => {
+            if uy == 0 {
+                return None;
+            }
+            (ux % uy) as i64
         }
-        ir::Opcode::Udiv => {
-            let imm0 = Wrapping(imm0.unwrap_i64());
-            let imm1 = Wrapping(imm1.unwrap_i64());
-            if imm1.0 == 0 {
-                panic!("Cannot divide by a zero.")
+        Sdiv | SdivImm => {
+            if sy == 0 || (sy == -1 && sx == sext(ty, 1 << (bits - 1))) {
+                return None;
             }
-            Some(ConstImm::I64((imm0 / imm1).0))
+            sx / sy
         }
-        ir::Opcode::Fadd => match (imm0, imm1) {
-            (ConstImm::Ieee32(imm0), ConstImm::Ieee32(imm1)) => Some(ConstImm::Ieee32(imm0 + imm1)),
-            (ConstImm::Ieee64(imm0), ConstImm::Ieee64(imm1)) => Some(ConstImm::Ieee64(imm0 + imm1)),
-            _ => unreachable!(),
-        },
-        ir::Opcode::Fsub => match (imm0, imm1) {
-            (ConstImm::Ieee32(imm0), ConstImm::Ieee32(imm1)) => Some(ConstImm::Ieee32(imm0 - imm1)),
-            (ConstImm::Ieee64(imm0), ConstImm::Ieee64(imm1)) => Some(ConstImm::Ieee64(imm0 - imm1)),
-            _ => unreachable!(),
-        },
-        ir::Opcode::Fmul => match (imm0, imm1) {
-            (ConstImm::Ieee32(imm0), ConstImm::Ieee32(imm1)) => Some(ConstImm::Ieee32(imm0 * imm1)),
-            (ConstImm::Ieee64(imm0), ConstImm::Ieee64(imm1)) => Some(ConstImm::Ieee64(imm0 * imm1)),
-            _ => unreachable!(),
-        },
-        ir::Opcode::Fdiv => match (imm0, imm1) {
-            (ConstImm::Ieee32(imm0), ConstImm::Ieee32(imm1)) => Some(ConstImm::Ieee32(imm0 / imm1)),
-            (ConstImm::Ieee64(imm0), ConstImm::Ieee64(imm1)) => Some(ConstImm::Ieee64(imm0 / imm1)),
-            _ => unreachable!(),
-        },
-        _ => None,
+        Srem | SremImm => {
+            if sy == 0 {
+                return None;
+            }
+            sx.wrapping_rem(sy)
+        }
+        Band | BandImm => x & y,
+        Bor | BorImm => x | y,
+        Bxor | BxorImm => x ^ y,
+        Ishl | IshlImm => x << amt,
+        Ushr | UshrImm => (ux >> amt) as i64,
+        Sshr | SshrImm => sx >> amt,
+        Rotl | RotlImm if amt != 0 => ((ux << amt) | (ux >> (bits - amt))) as i64,
+        Rotr | RotrImm if amt != 0 => ((ux >> amt) | (ux << (bits - amt))) as i64,
+        Rotl | RotlImm | Rotr | RotrImm => x,
+        _ => return None,
+    };
+
+    Some(sext(ty, result))
+}
+
+/// Evaluate an integer comparison of type `ty`.
+fn evaluate_icmp(cond: IntCC, ty: ir::Type, x: i64, y: i64) -> Option<bool> {
+    if !is_foldable_int(ty) {
+        return None;
     }
+
+    let (sx, sy) = (sext(ty, x), sext(ty, y));
+    let (ux, uy) = (zext(ty, x), zext(ty, y));
+
+    Some(match cond {
+        IntCC::Equal => ux == uy,
+        IntCC::NotEqual => ux != uy,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => ux < uy,
+        IntCC::UnsignedGreaterThanOrEqual => ux >= uy,
+        IntCC::UnsignedGreaterThan => ux > uy,
+        IntCC::UnsignedLessThanOrEqual => ux <= uy,
+    })
 }
 
-fn evaluate_unary(opcode: ir::Opcode, imm: ConstImm) -> Option<ConstImm> {
-    match opcode {
-        ir::Opcode::Fneg => match imm {
-            ConstImm::Ieee32(imm) => Some(ConstImm::Ieee32(-imm)),
-            ConstImm::Ieee64(imm) => Some(ConstImm::Ieee64(-imm)),
-            _ => unreachable!(),
+fn evaluate_unary(
+    opcode: ir::Opcode,
+    arg_ty: ir::Type,
+    ty: ir::Type,
+    imm: ConstImm,
+) -> Option<ConstImm> {
+    use self::ir::Opcode::*;
+
+    match imm {
+        ConstImm::I64(imm) => {
+            if !is_foldable_int(arg_ty) || !is_foldable_int(ty) {
+                return None;
+            }
+            match opcode {
+                Uextend => Some(ConstImm::I64(sext(ty, zext(arg_ty, imm) as i64))),
+                Sextend => Some(ConstImm::I64(sext(arg_ty, imm))),
+                Ireduce => Some(ConstImm::I64(sext(ty, imm))),
+                Bnot => Some(ConstImm::I64(sext(ty, !imm))),
+                _ => None,
+            }
+        }
+        ConstImm::Bool(imm) => match opcode {
+            Bnot if !ty.is_vector() => Some(ConstImm::Bool(!imm)),
+            Bint if is_foldable_int(ty) => Some(ConstImm::I64(imm as i64)),
+            _ => None,
         },
-        ir::Opcode::Fabs => match imm {
-            ConstImm::Ieee32(imm) => Some(ConstImm::Ieee32(imm.abs())),
-            ConstImm::Ieee64(imm) => Some(ConstImm::Ieee64(imm.abs())),
-            _ => unreachable!(),
+        ConstImm::Ieee32(imm) => match opcode {
+            Fneg => Some(ConstImm::Ieee32(-imm)),
+            Fabs => Some(ConstImm::Ieee32(imm.abs())),
+            _ => None,
+        },
+        ConstImm::Ieee64(imm) => match opcode {
+            Fneg => Some(ConstImm::Ieee64(-imm)),
+            Fabs => Some(ConstImm::Ieee64(imm.abs())),
+            _ => None,
         },
-        _ => None,
     }
 }
 
@@ -175,8 +291,8 @@ fn replace_inst(dfg: &mut ir::DataFlowGraph, inst: ir::Inst, const_imm: ConstImm
     use self::ConstImm::*;
     match const_imm {
         I64(imm) => {
-            let typevar = dfg.ctrl_typevar(inst);
-            dfg.replace(inst).iconst(typevar, imm);
+            let ty = dfg.value_type(dfg.first_result(inst));
+            dfg.replace(inst).iconst(ty, imm);
         }
         Ieee32(imm) => {
             dfg.replace(inst)
@@ -187,30 +303,94 @@ fn replace_inst(dfg: &mut ir::DataFlowGraph, inst: ir::Inst, const_imm: ConstImm
                 .f64const(ir::immediates::Ieee64::with_bits(imm.to_bits()));
         }
         Bool(imm) => {
-            let typevar = dfg.ctrl_typevar(inst);
-            dfg.replace(inst).bconst(typevar, imm);
+            let ty = dfg.value_type(dfg.first_result(inst));
+            dfg.replace(inst).bconst(ty, imm);
         }
     }
 }
 
+/// Replace the result of `inst` with `value` and remove `inst`.
+fn replace_with_alias(pos: &mut FuncCursor, inst: ir::Inst, value: ir::Value) {
+    let result = pos.func.dfg.first_result(inst);
+    pos.func.dfg.clear_results(inst);
+    pos.func.dfg.change_to_alias(result, value);
+    pos.remove_inst_and_step_back();
+}
+
+/// Get the integer constant defining `value`, if any.
+fn resolve_value_to_int(dfg: &ir::DataFlowGraph, value: ir::Value) -> Option<i64> {
+    match resolve_value_to_imm(dfg, value) {
+        Some(ConstImm::I64(imm)) => Some(imm),
+        _ => None,
+    }
+}
+
 /// Fold a binary instruction.
-fn fold_binary(
-    dfg: &mut ir::DataFlowGraph,
+fn fold_binary(pos: &mut FuncCursor, inst: ir::Inst, opcode: ir::Opcode, args: [ir::Value; 2]) {
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+
+    if let (Some(imm0), Some(imm1)) = (
+        resolve_value_to_imm(&pos.func.dfg, args[0]),
+        resolve_value_to_imm(&pos.func.dfg, args[1]),
+    ) {
+        if let Some(const_imm) = evaluate_binary(opcode, ty, imm0, imm1) {
+            replace_inst(&mut pos.func.dfg, inst, const_imm);
+        }
+        return;
+    }
+
+    if !is_foldable_int(ty) {
+        return;
+    }
+
+    // Apply algebraic identities when only some of the operands are known.
+    use self::ir::Opcode::*;
+    let x = pos.func.dfg.resolve_aliases(args[0]);
+    let y = pos.func.dfg.resolve_aliases(args[1]);
+    let imm0 = resolve_value_to_int(&pos.func.dfg, x).map(|imm| sext(ty, imm));
+    let imm1 = resolve_value_to_int(&pos.func.dfg, y).map(|imm| sext(ty, imm));
+
+    match (opcode, imm0, imm1) {
+        (Iadd, _, Some(0)) | (Isub, _, Some(0)) | (Imul, _, Some(1)) => {
+            replace_with_alias(pos, inst, x)
+        }
+        (Iadd, Some(0), _) | (Imul, Some(1), _) => replace_with_alias(pos, inst, y),
+        (Band, _, _) | (Bor, _, _) if x == y => replace_with_alias(pos, inst, x),
+        (Bxor, _, _) | (Isub, _, _) if x == y => {
+            replace_inst(&mut pos.func.dfg, inst, ConstImm::I64(0))
+        }
+        _ => {}
+    }
+}
+
+/// Fold a binary instruction with an immediate operand.
+fn fold_binary_imm(
+    pos: &mut FuncCursor,
     inst: ir::Inst,
     opcode: ir::Opcode,
-    args: [ir::Value; 2],
+    arg: ir::Value,
+    imm: i64,
 ) {
-    let (imm0, imm1) = if let (Some(imm0), Some(imm1)) = (
-        resolve_value_to_imm(dfg, args[0]),
-        resolve_value_to_imm(dfg, args[1]),
-    ) {
-        (imm0, imm1)
-    } else {
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+
+    if let Some(arg_imm) = resolve_value_to_int(&pos.func.dfg, arg) {
+        if let Some(result) = evaluate_int_binary(opcode, ty, arg_imm, imm) {
+            replace_inst(&mut pos.func.dfg, inst, ConstImm::I64(result));
+        }
         return;
-    };
+    }
 
-    if let Some(const_imm) = evaluate_binary(opcode, imm0, imm1) {
-        replace_inst(dfg, inst, const_imm);
+    if !is_foldable_int(ty) {
+        return;
+    }
+
+    use self::ir::Opcode::*;
+    match (opcode, sext(ty, imm)) {
+        (IaddImm, 0) | (ImulImm, 1) | (BorImm, 0) | (BxorImm, 0) | (BandImm, -1) => {
+            let arg = pos.func.dfg.resolve_aliases(arg);
+            replace_with_alias(pos, inst, arg)
+        }
+        _ => {}
     }
 }
 
@@ -222,11 +402,45 @@ fn fold_unary(dfg: &mut ir::DataFlowGraph, inst: ir::Inst, opcode: ir::Opcode, a
         return;
     };
 
-    if let Some(const_imm) = evaluate_unary(opcode, imm) {
+    let arg_ty = dfg.value_type(arg);
+    let ty = dfg.value_type(dfg.first_result(inst));
+    if let Some(const_imm) = evaluate_unary(opcode, arg_ty, ty, imm) {
         replace_inst(dfg, inst, const_imm);
     }
 }
 
+/// Fold an `icmp` instruction.
+fn fold_icmp(
+    dfg: &mut ir::DataFlowGraph,
+    inst: ir::Inst,
+    cond: IntCC,
+    arg0: ir::Value,
+    arg1: ir::Value,
+) {
+    if let (Some(imm0), Some(imm1)) = (
+        resolve_value_to_imm(dfg, arg0),
+        resolve_value_to_imm(dfg, arg1),
+    ) {
+        fold_icmp_imms(dfg, inst, cond, imm0, imm1);
+    }
+}
+
+/// Fold an `icmp` or `icmp_imm` instruction whose operands are known.
+fn fold_icmp_imms(
+    dfg: &mut ir::DataFlowGraph,
+    inst: ir::Inst,
+    cond: IntCC,
+    imm0: ConstImm,
+    imm1: ConstImm,
+) {
+    let ty = dfg.ctrl_typevar(inst);
+    if let (ConstImm::I64(x), ConstImm::I64(y)) = (imm0, imm1) {
+        if let Some(result) = evaluate_icmp(cond, ty, x, y) {
+            replace_inst(dfg, inst, ConstImm::Bool(result));
+        }
+    }
+}
+
 fn fold_branch(pos: &mut FuncCursor, inst: ir::Inst, opcode: ir::Opcode) {
     let (cond, ebb, args) = {
         let values = pos.func.dfg.inst_args(inst);
@@ -2,6 +2,9 @@
 //!
 //! The `cranelift_reader` library supports reading .clif files. This functionality is needed for
 //! testing Cranelift, but is not essential for a JIT compiler.
+//!
+//! The parser itself lives in the `parse` module of `cranelift_codegen`, which is enabled by its
+//! `parse` feature. This crate re-exports it for compatibility.
 
 #![deny(
     missing_docs,
@@ -26,17 +29,7 @@
     )
 )]
 
-pub use crate::error::{Location, ParseError, ParseResult};
-pub use crate::isaspec::{parse_options, IsaSpec};
-pub use crate::parser::{parse_functions, parse_test};
-pub use crate::sourcemap::SourceMap;
-pub use crate::testcommand::{TestCommand, TestOption};
-pub use crate::testfile::{Comment, Details, TestFile};
-
-mod error;
-mod isaspec;
-mod lexer;
-mod parser;
-mod sourcemap;
-mod testcommand;
-mod testfile;
+pub use cranelift_codegen::parse::{
+    parse_functions, parse_options, parse_test, Comment, Details, IsaSpec, Location, ParseError,
+    ParseResult, SourceMap, TestCommand, TestFile, TestOption,
+};
@@ -190,15 +190,27 @@ pub enum SerInstData {
     HeapAddr {
         opcode: String,
         arg: String,
+        flags: String,
         heap: String,
         imm: String,
     },
     TableAddr {
         opcode: String,
         arg: String,
+        flags: String,
         table: String,
         offset: String,
     },
+    TableGrow {
+        opcode: String,
+        args: [String; 2],
+        table: String,
+    },
+    TableFill {
+        opcode: String,
+        args: Vec<String>,
+        table: String,
+    },
     RegMove {
         opcode: String,
         arg: String,
@@ -616,25 +628,54 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
         InstructionData::HeapAddr {
             opcode,
             arg,
+            flags,
             heap,
             imm,
         } => SerInstData::HeapAddr {
             opcode: opcode.to_string(),
             arg: arg.to_string(),
+            flags: flags.to_string(),
             heap: heap.to_string(),
             imm: imm.to_string(),
         },
         InstructionData::TableAddr {
             opcode,
             arg,
+            flags,
             table,
             offset,
         } => SerInstData::TableAddr {
             opcode: opcode.to_string(),
             arg: arg.to_string(),
+            flags: flags.to_string(),
             table: table.to_string(),
             offset: offset.to_string(),
         },
+        InstructionData::TableGrow {
+            opcode,
+            args,
+            table,
+        } => SerInstData::TableGrow {
+            opcode: opcode.to_string(),
+            args: [args[0].to_string(), args[1].to_string()],
+            table: table.to_string(),
+        },
+        InstructionData::TableFill {
+            opcode,
+            ref args,
+            table,
+        } => {
+            let mut hold_args = Vec::new();
+            let args_iter = args.as_slice(&func.dfg.value_lists);
+            for arg in args_iter {
+                hold_args.push(arg.to_string());
+            }
+            SerInstData::TableFill {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                table: table.to_string(),
+            }
+        }
         InstructionData::RegMove {
             opcode,
             arg,
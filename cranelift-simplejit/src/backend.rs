@@ -235,9 +235,12 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction> {
         let size = code_size as usize;
+        let isa = &*self.isa;
         let ptr = self
             .code_memory
-            .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
+            .allocate_padded(size, EXECUTABLE_DATA_ALIGNMENT, |gap| {
+                isa.fill_function_padding(gap)
+            })
             .expect("TODO: handle OOM etc.");
 
         if cfg!(target_os = "linux") && ::std::env::var_os("PERF_BUILDID_DIR").is_some() {
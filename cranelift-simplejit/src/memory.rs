@@ -10,6 +10,7 @@ use memmap::MmapMut;
 use region;
 use std::mem;
 use std::ptr;
+use std::slice;
 
 /// Round `size` up to the nearest multiple of `page_size`.
 fn round_up_to_page_size(size: usize, page_size: usize) -> usize {
@@ -132,8 +133,21 @@ impl Memory {
 
     /// TODO: Use a proper error type.
     pub fn allocate(&mut self, size: usize, align: u8) -> Result<*mut u8, String> {
+        self.allocate_padded(size, align, |_| {})
+    }
+
+    /// Like `allocate`, but call `pad` to fill the alignment gap between the previous allocation
+    /// and the new one.
+    pub fn allocate_padded<F>(&mut self, size: usize, align: u8, pad: F) -> Result<*mut u8, String>
+    where
+        F: FnOnce(&mut [u8]),
+    {
         if self.position % align as usize != 0 {
-            self.position += align as usize - self.position % align as usize;
+            let gap = align as usize - self.position % align as usize;
+            if self.position + gap <= self.current.len {
+                pad(unsafe { slice::from_raw_parts_mut(self.current.ptr.add(self.position), gap) });
+            }
+            self.position += gap;
             debug_assert!(self.position % align as usize == 0);
         }
 
@@ -224,4 +238,20 @@ mod tests {
         assert_eq!(round_up_to_page_size(4096, 4096), 4096);
         assert_eq!(round_up_to_page_size(4097, 4096), 8192);
     }
+
+    #[test]
+    fn test_allocate_padded() {
+        let mut memory = Memory::new();
+        let first = memory.allocate(3, 1).unwrap();
+        let second = memory
+            .allocate_padded(4, 16, |gap| {
+                for byte in gap {
+                    *byte = 0xcc;
+                }
+            })
+            .unwrap();
+        assert_eq!(second as usize - first as usize, 16);
+        let gap = unsafe { slice::from_raw_parts(first.add(3), 13) };
+        assert!(gap.iter().all(|&byte| byte == 0xcc));
+    }
 }
@@ -14,6 +14,7 @@ fn error_on_incompatible_sig_in_declare_function() {
         params: vec![AbiParam::new(types::I64)],
         returns: vec![],
         call_conv: CallConv::SystemV,
+        variadic: false,
     };
     module
         .declare_function("abc", Linkage::Local, &sig)
@@ -30,6 +31,7 @@ fn define_simple_function(module: &mut Module<SimpleJITBackend>) -> FuncId {
         params: vec![],
         returns: vec![],
         call_conv: CallConv::SystemV,
+        variadic: false,
     };
 
     let func_id = module
@@ -83,6 +85,7 @@ fn switch_error() {
         params: vec![AbiParam::new(types::I32)],
         returns: vec![AbiParam::new(types::I32)],
         call_conv: CallConv::SystemV,
+        variadic: false,
     };
 
     let mut func = Function::with_name_signature(ExternalName::user(0, 0), sig);
@@ -157,6 +160,7 @@ fn libcall_function() {
         params: vec![],
         returns: vec![],
         call_conv: CallConv::SystemV,
+        variadic: false,
     };
 
     let func_id = module
@@ -195,3 +199,26 @@ fn libcall_function() {
 
     module.finalize_definitions();
 }
+
+#[test]
+fn deduplicate_constants() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let mut data_ctx = DataContext::new();
+    data_ctx.define(b"hello\0".to_vec().into_boxed_slice());
+    let hello = module.define_constant(&data_ctx, None).unwrap();
+    assert_eq!(module.define_constant(&data_ctx, None).unwrap(), hello);
+
+    // A different alignment or contents make a different constant.
+    assert_ne!(module.define_constant(&data_ctx, Some(8)).unwrap(), hello);
+    data_ctx.clear();
+    data_ctx.define(b"world\0".to_vec().into_boxed_slice());
+    let world = module.define_constant(&data_ctx, None).unwrap();
+    assert_ne!(world, hello);
+
+    module.finalize_definitions();
+    let (ptr, size) = module.get_finalized_data(world);
+    assert_eq!(size, 6);
+    assert_eq!(unsafe { *ptr }, b'w');
+}
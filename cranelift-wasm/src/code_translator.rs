@@ -1141,16 +1141,49 @@ fn translate_unreachable_operator(
     }
 }
 
-/// Get the address+offset to use for a heap access.
+/// Get the size in bytes of the memory accessed by a load or store instruction.
+fn access_size(opcode: ir::Opcode, ty: Type) -> u32 {
+    match opcode {
+        ir::Opcode::Uload8 | ir::Opcode::Sload8 | ir::Opcode::Istore8 => 1,
+        ir::Opcode::Uload16 | ir::Opcode::Sload16 | ir::Opcode::Istore16 => 2,
+        ir::Opcode::Uload32 | ir::Opcode::Sload32 | ir::Opcode::Istore32 => 4,
+        _ => ty.bytes(),
+    }
+}
+
+/// Get the address+offset and the memory flags to use for a heap access of `size` bytes.
 fn get_heap_addr(
     heap: ir::Heap,
     addr32: ir::Value,
     offset: u32,
+    size: u32,
     addr_ty: Type,
     builder: &mut FunctionBuilder,
-) -> (ir::Value, i32) {
+) -> (ir::Value, i32, MemFlags) {
     use core::cmp::min;
 
+    // Note that we don't set `is_aligned` here, even if the load instruction's
+    // alignment immediate says it's aligned, because WebAssembly's immediate
+    // field is just a hint, while Cranelift's aligned flag needs a guarantee.
+    let mut flags = MemFlags::new();
+
+    // A constant address whose whole access fits in the minimum heap size can never be out of
+    // bounds, so the access doesn't need a bounds check and can't trap.
+    if let ir::ValueDef::Result(def, _) = builder.func.dfg.value_def(addr32) {
+        if let ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } = builder.func.dfg[def]
+        {
+            let imm: i64 = imm.into();
+            let addr = u64::from(imm as u32);
+            let min_size: u64 = builder.func.heaps[heap].min_size.into();
+            if addr + u64::from(offset) + u64::from(size) <= min_size {
+                flags.set_notrap();
+            }
+        }
+    }
+
     let mut adjusted_offset = u64::from(offset);
     let offset_guard_size: u64 = builder.func.heaps[heap].offset_guard_size.into();
 
@@ -1165,16 +1198,18 @@ fn get_heap_addr(
     // even if the access goes beyond the offset-guard pages. This is because the first byte
     // pointed to is inside the offset-guard pages.
     let check_size = min(u64::from(u32::MAX), 1 + adjusted_offset) as u32;
-    let base = builder.ins().heap_addr(addr_ty, heap, addr32, check_size);
+    let base = builder
+        .ins()
+        .heap_addr(addr_ty, flags, heap, addr32, check_size);
 
     // Native load/store instructions take a signed `Offset32` immediate, so adjust the base
     // pointer if necessary.
     if offset > i32::MAX as u32 {
         // Offset doesn't fit in the load/store instruction.
         let adj = builder.ins().iadd_imm(base, i64::from(i32::MAX) + 1);
-        (adj, (offset - (i32::MAX as u32 + 1)) as i32)
+        (adj, (offset - (i32::MAX as u32 + 1)) as i32, flags)
     } else {
-        (base, offset as i32)
+        (base, offset as i32, flags)
     }
 }
 
@@ -1190,11 +1225,14 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     let addr32 = state.pop1();
     // We don't yet support multiple linear memories.
     let heap = state.get_heap(builder.func, 0, environ)?;
-    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
-    // Note that we don't set `is_aligned` here, even if the load instruction's
-    // alignment immediate says it's aligned, because WebAssembly's immediate
-    // field is just a hint, while Cranelift's aligned flag needs a guarantee.
-    let flags = MemFlags::new();
+    let (base, offset, flags) = get_heap_addr(
+        heap,
+        addr32,
+        offset,
+        access_size(opcode, result_ty),
+        environ.pointer_type(),
+        builder,
+    );
     let (load, dfg) = builder
         .ins()
         .Load(opcode, result_ty, flags, offset.into(), base);
@@ -1215,9 +1253,14 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
 
     // We don't yet support multiple linear memories.
     let heap = state.get_heap(builder.func, 0, environ)?;
-    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
-    // See the comments in `translate_load` about the flags.
-    let flags = MemFlags::new();
+    let (base, offset, flags) = get_heap_addr(
+        heap,
+        addr32,
+        offset,
+        access_size(opcode, val_ty),
+        environ.pointer_type(),
+        builder,
+    );
     builder
         .ins()
         .Store(opcode, val_ty, flags, offset.into(), val, base);
@@ -223,10 +223,12 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
                 bound: 0x1_0000_0000.into(),
             },
             index_type: I32,
+            trap_code: ir::TrapCode::HeapOutOfBounds,
+            violation: ir::HeapViolation::Trap,
         }))
     }
 
-    fn make_table(&mut self, func: &mut ir::Function, _index: TableIndex) -> WasmResult<ir::Table> {
+    fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> WasmResult<ir::Table> {
         // Create a table whose base address is stored at `vmctx+0`.
         let vmctx = func.create_global_value(ir::GlobalValueData::VMContext);
         let base_gv = func.create_global_value(ir::GlobalValueData::Load {
@@ -248,6 +250,8 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
             bound_gv,
             element_size: Uimm64::from(u64::from(self.pointer_bytes()) * 2),
             index_type: I32,
+            trap_code: ir::TrapCode::TableOutOfBounds,
+            id: index.as_u32().into(),
         }))
     }
 
@@ -275,6 +279,8 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
             name,
             signature,
             colocated: false,
+            linkage: ir::Linkage::Import,
+            visibility: ir::Visibility::Default,
         }))
     }
 